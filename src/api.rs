@@ -0,0 +1,197 @@
+use crate::entry::Entry;
+use crate::error::GitError;
+use crate::http::{Request, Response};
+use crate::repo::{Commit, GitRepository};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+struct BranchView {
+    name: String,
+    commit: String,
+    current: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CommitView<'a> {
+    id: &'a str,
+    message: &'a str,
+    date_time: i64,
+    date: String,
+    parent: &'a str,
+    blobs: &'a BTreeMap<String, Entry>,
+}
+
+impl<'a> CommitView<'a> {
+    fn new(id: &'a str, commit: &'a Commit) -> Self {
+        Self {
+            id,
+            message: commit.message(),
+            date_time: commit.date_time(),
+            date: commit.date_string(),
+            parent: commit.parent(),
+            blobs: commit.blobs(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BlobView<'a> {
+    id: &'a str,
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorView {
+    error: String,
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Response, GitError> {
+    let body =
+        serde_json::to_string(value).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+    Ok(Response::json(200, body))
+}
+
+fn error(status: u16, msg: String) -> Response {
+    let body = serde_json::to_string(&ErrorView { error: msg }).unwrap_or_default();
+    Response::json(status, body)
+}
+
+fn branches(repo: &mut GitRepository) -> Result<Response, GitError> {
+    let branches = repo.branches()?;
+    let current = repo.current_branch();
+    let views: Vec<BranchView> = branches
+        .into_iter()
+        .map(|(name, commit)| BranchView {
+            current: name == current,
+            name,
+            commit,
+        })
+        .collect();
+    to_json(&views)
+}
+
+fn commits(repo: &GitRepository, rev: &str) -> Result<Response, GitError> {
+    let sha1 = repo.resolve_commit(rev)?;
+    let history = repo.history(&sha1)?;
+    let views: Vec<CommitView> = history
+        .iter()
+        .map(|(id, commit)| CommitView::new(id, commit))
+        .collect();
+    to_json(&views)
+}
+
+fn commit(repo: &GitRepository, rev: &str) -> Result<Response, GitError> {
+    let sha1 = repo.resolve_commit(rev)?;
+    let commit = repo.read_commit(&sha1)?;
+    to_json(&CommitView::new(&sha1, &commit))
+}
+
+fn blob(repo: &GitRepository, id: &str) -> Result<Response, GitError> {
+    if id.contains('/') || id.contains("..") {
+        return Err(GitError::FileNotExistError(id.to_string()));
+    }
+    to_json(&BlobView {
+        id,
+        content: repo.read_blob_text(id)?,
+    })
+}
+
+/// Route a request of the read-only JSON api
+/// /branches              all branches with their commit
+/// /commits               history of HEAD
+/// /commits/<rev>         a single commit
+/// /status                same sections as `status`
+/// /blob/<id>             blob content
+pub fn handle(repo: &mut GitRepository, request: &Request) -> Response {
+    if request.method != "GET" {
+        return error(405, "method not allowed".to_string());
+    }
+    let segments = request.segments();
+    let res = match segments.as_slice() {
+        ["branches"] => branches(repo),
+        ["commits"] => commits(repo, "HEAD"),
+        ["commits", rev] => commit(repo, rev),
+        ["status"] => repo.status_report().and_then(|report| to_json(&report)),
+        ["blob", id] => blob(repo, id),
+        _ => return error(404, format!("no such endpoint {}", request.path)),
+    };
+    match res {
+        Ok(response) => response,
+        Err(GitError::RevisionError(msg)) => error(404, msg),
+        Err(GitError::FileNotExistError(msg)) => error(404, format!("{} does not exist", msg)),
+        Err(e) => error(500, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::{env, fs};
+
+    fn get(repo: &mut GitRepository, path: &str) -> (u16, Value) {
+        let raw = format!("GET {} HTTP/1.1\r\n\r\n", path);
+        let res = handle(repo, &Request::parse(&mut raw.as_bytes()).unwrap());
+        (res.status, serde_json::from_slice(&res.body).unwrap())
+    }
+
+    #[test]
+    fn handle_ut() {
+        let repo_dir = ".api_handle_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("api_handle_ut");
+        for path in [work_dir, &env::current_dir().unwrap().join(repo_dir)] {
+            if path.exists() {
+                assert!(fs::remove_dir_all(path).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "api handle ut").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "api handle ut f2").is_ok());
+
+        let mut repo = GitRepository::new(repo_dir);
+        assert!(repo.init().is_ok());
+        assert!(repo.add(&["api_handle_ut/f1".to_string()]).is_ok());
+        assert!(repo.commit("add f1").is_ok());
+        assert!(repo.add(&["api_handle_ut/f2".to_string()]).is_ok());
+        let head = repo.resolve_commit("main").unwrap();
+
+        let (status, branches) = get(&mut repo, "/branches");
+        assert_eq!(200, status);
+        assert_eq!(
+            serde_json::json!([{"name": "main", "commit": head, "current": true}]),
+            branches
+        );
+
+        let (status, commit) = get(&mut repo, &format!("/commits/{}", head));
+        assert_eq!(200, status);
+        assert_eq!("add f1", commit["message"]);
+        let blob_id = commit["blobs"]["api_handle_ut/f1"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let (status, commits) = get(&mut repo, "/commits");
+        assert_eq!(200, status);
+        assert_eq!(2, commits.as_array().unwrap().len());
+        assert_eq!("initial commit", commits[1]["message"]);
+
+        let (status, blob) = get(&mut repo, &format!("/blob/{}", blob_id));
+        assert_eq!(200, status);
+        assert_eq!("api handle ut", blob["content"]);
+
+        let (status, report) = get(&mut repo, "/status");
+        assert_eq!(200, status);
+        assert_eq!("main", report["branch"]);
+        assert_eq!(serde_json::json!(["api_handle_ut/f2"]), report["staged"]);
+
+        let (status, err) = get(&mut repo, "/commits/unknown");
+        assert_eq!(404, status);
+        assert_eq!("unknown revision unknown", err["error"]);
+        assert_eq!(404, get(&mut repo, "/blob/missing").0);
+        assert_eq!(404, get(&mut repo, "/nothing").0);
+
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(repo.repo_path).is_ok());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::entry::{Entry, FileMode};
+use crate::error::GitError;
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::BTreeMap;
+use std::io::{Seek, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Format of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ArchiveFormat {
+    Tar,
+    /// gzip compressed tar
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// format implied by the extension of an output file name
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Write the files of a tree into an archive, one blob in memory at a time.
+/// blobs: file path --> entry, read_blob: content of a blob, symbolic links are stored as links,
+/// date_time: modification time of every entry, seconds since the epoch
+pub fn write<W, F>(
+    out: W,
+    format: ArchiveFormat,
+    blobs: &BTreeMap<String, Entry>,
+    date_time: i64,
+    read_blob: F,
+) -> Result<W, GitError>
+where
+    W: Write + Seek,
+    F: Fn(&str) -> Result<Vec<u8>, GitError>,
+{
+    match format {
+        ArchiveFormat::Tar => write_tar(out, blobs, date_time, read_blob),
+        ArchiveFormat::TarGz => {
+            let gz = GzEncoder::new(out, Compression::default());
+            write_tar(gz, blobs, date_time, read_blob)?
+                .finish()
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+        }
+        ArchiveFormat::Zip => write_zip(out, blobs, date_time, read_blob),
+    }
+}
+
+/// unix permissions of an archived file
+fn permissions(mode: FileMode) -> u32 {
+    match mode {
+        FileMode::Executable => 0o755,
+        _ => 0o644,
+    }
+}
+
+fn write_tar<W, F>(
+    out: W,
+    blobs: &BTreeMap<String, Entry>,
+    date_time: i64,
+    read_blob: F,
+) -> Result<W, GitError>
+where
+    W: Write,
+    F: Fn(&str) -> Result<Vec<u8>, GitError>,
+{
+    let mut builder = tar::Builder::new(out);
+    for (path, entry) in blobs.iter() {
+        let content = read_blob(&entry.sha1)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(date_time.max(0) as u64);
+        let res = if entry.is_symlink() {
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            header.set_mode(0o777);
+            builder.append_link(&mut header, path, &*String::from_utf8_lossy(&content))
+        } else {
+            header.set_size(content.len() as u64);
+            header.set_mode(permissions(entry.mode));
+            builder.append_data(&mut header, path, content.as_slice())
+        };
+        res.map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    }
+    builder
+        .into_inner()
+        .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+}
+
+fn write_zip<W, F>(
+    out: W,
+    blobs: &BTreeMap<String, Entry>,
+    date_time: i64,
+    read_blob: F,
+) -> Result<W, GitError>
+where
+    W: Write + Seek,
+    F: Fn(&str) -> Result<Vec<u8>, GitError>,
+{
+    let mut options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(0o644);
+    // zip dates start in 1980, older commits keep the default date
+    if let Some(date) = Utc.timestamp_opt(date_time, 0).single() {
+        if let Ok(date) = zip::DateTime::from_date_and_time(
+            date.year() as u16,
+            date.month() as u8,
+            date.day() as u8,
+            date.hour() as u8,
+            date.minute() as u8,
+            date.second() as u8,
+        ) {
+            options = options.last_modified_time(date);
+        }
+    }
+    let mut writer = ZipWriter::new(out);
+    for (path, entry) in blobs.iter() {
+        let content = read_blob(&entry.sha1)?;
+        if entry.is_symlink() {
+            writer
+                .add_symlink(path, String::from_utf8_lossy(&content), options)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            continue;
+        }
+        writer
+            .start_file(path, options.unix_permissions(permissions(entry.mode)))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        writer
+            .write_all(&content)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    }
+    writer
+        .finish()
+        .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::{Cursor, Read};
+
+    fn tree() -> BTreeMap<String, Entry> {
+        BTreeMap::from([
+            ("a.txt".to_string(), Entry::new("blob a")),
+            (
+                "dir/b.txt".to_string(),
+                Entry::with_mode("blob b", FileMode::Executable),
+            ),
+        ])
+    }
+
+    fn read_blob(sha1: &str) -> Result<Vec<u8>, GitError> {
+        Ok(format!("content of {}\n", sha1).into_bytes())
+    }
+
+    fn tar_entries<R: Read>(input: R) -> Vec<(String, String)> {
+        let mut archive = tar::Archive::new(input);
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                assert_eq!(1700000000, entry.header().mtime().unwrap());
+                let path = entry.path().unwrap().display().to_string();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (path, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn write_ut() {
+        let expected = vec![
+            ("a.txt".to_string(), "content of blob a\n".to_string()),
+            ("dir/b.txt".to_string(), "content of blob b\n".to_string()),
+        ];
+        let date_time = 1700000000;
+
+        let out = write(
+            Cursor::new(vec![]),
+            ArchiveFormat::Tar,
+            &tree(),
+            date_time,
+            read_blob,
+        )
+        .unwrap();
+        assert_eq!(expected, tar_entries(Cursor::new(out.into_inner())));
+
+        let out = write(
+            Cursor::new(vec![]),
+            ArchiveFormat::TarGz,
+            &tree(),
+            date_time,
+            read_blob,
+        )
+        .unwrap();
+        let gz = GzDecoder::new(Cursor::new(out.into_inner()));
+        assert_eq!(expected, tar_entries(gz));
+
+        let out = write(
+            Cursor::new(vec![]),
+            ArchiveFormat::Zip,
+            &tree(),
+            date_time,
+            read_blob,
+        )
+        .unwrap();
+        let mut zip = zip::ZipArchive::new(out).unwrap();
+        assert_eq!(2, zip.len());
+        let mut content = String::new();
+        let mut file = zip.by_name("dir/b.txt").unwrap();
+        assert_eq!(Some(0o755), file.unix_mode().map(|mode| mode & 0o777));
+        file.read_to_string(&mut content).unwrap();
+        assert_eq!("content of blob b\n", content);
+
+        let failing = |_: &str| Err(GitError::FileNotExistError("blob".to_string()));
+        assert!(write(Cursor::new(vec![]), ArchiveFormat::Tar, &tree(), 0, failing).is_err());
+    }
+
+    #[test]
+    fn from_file_name_ut() {
+        assert_eq!(
+            Some(ArchiveFormat::TarGz),
+            ArchiveFormat::from_file_name("out.tar.gz")
+        );
+        assert_eq!(
+            Some(ArchiveFormat::TarGz),
+            ArchiveFormat::from_file_name("out.tgz")
+        );
+        assert_eq!(
+            Some(ArchiveFormat::Tar),
+            ArchiveFormat::from_file_name("out.tar")
+        );
+        assert_eq!(
+            Some(ArchiveFormat::Zip),
+            ArchiveFormat::from_file_name("x/out.zip")
+        );
+        assert_eq!(None, ArchiveFormat::from_file_name("out.gz"));
+    }
+}
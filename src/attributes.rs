@@ -0,0 +1,251 @@
+use crate::error::GitError;
+use crate::ignore::path_regex;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// name of the per-directory attributes files
+pub const ATTRIBUTES_FILE: &str = ".gitattributes";
+
+/// State of an attribute for a path, an attribute no line mentions is unspecified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    regex: Regex,
+    /// directory of the attributes file relative to the root, empty for the root
+    base: String,
+    /// None for `!attr`, which makes the attribute unspecified again
+    attrs: Vec<(String, Option<AttrValue>)>,
+}
+
+/// Attributes of paths from `.gitattributes` files, paths are relative to the working
+/// directory root and use `/` separators.
+/// ```text
+/// *.sh      text eol=lf
+/// *.png     binary
+/// docs/*.md diff=markdown -text
+/// [attr]generated -diff linguist-generated
+/// ```
+/// Each line gives a pattern, matched like an ignore pattern, and attributes set for the paths
+/// it matches. For every attribute the last matching line wins and files in deeper directories
+/// come after the root. `binary` is a macro for `-diff -merge -text`, `[attr]` lines define
+/// more macros. Attributes files are read on demand, the first time a path under their
+/// directory is looked up.
+#[derive(Debug, Clone)]
+pub struct Attributes {
+    root: PathBuf,
+    loaded: HashSet<String>,
+    lines: Vec<Line>,
+    macros: HashMap<String, Vec<(String, Option<AttrValue>)>>,
+}
+
+/// parse `attr`, `-attr`, `!attr` and `attr=value`
+fn parse_attr(token: &str) -> (String, Option<AttrValue>) {
+    if let Some(name) = token.strip_prefix('-') {
+        (name.to_string(), Some(AttrValue::Unset))
+    } else if let Some(name) = token.strip_prefix('!') {
+        (name.to_string(), None)
+    } else if let Some((name, value)) = token.split_once('=') {
+        (name.to_string(), Some(AttrValue::Value(value.to_string())))
+    } else {
+        (token.to_string(), Some(AttrValue::Set))
+    }
+}
+
+impl Attributes {
+    /// attributes of the working directory at root, no file is read yet
+    pub fn new(root: &Path) -> Self {
+        let binary = ["-diff", "-merge", "-text"].map(parse_attr).to_vec();
+        Self {
+            root: root.to_path_buf(),
+            loaded: HashSet::new(),
+            lines: vec![],
+            macros: HashMap::from([("binary".to_string(), binary)]),
+        }
+    }
+
+    /// add the lines of an attributes file living in the `base` directory, e.g. `src` for
+    /// `src/.gitattributes` and an empty string for the root
+    pub fn add_patterns(&mut self, content: &str, base: &str) -> Result<(), GitError> {
+        for (no, line) in content.lines().enumerate() {
+            let mut tokens = line.split_whitespace();
+            let pattern = match tokens.next() {
+                Some(pattern) if !pattern.starts_with('#') => pattern,
+                _ => continue,
+            };
+            let attrs: Vec<_> = tokens.map(parse_attr).collect();
+            if let Some(name) = pattern.strip_prefix("[attr]") {
+                self.macros.insert(name.to_string(), attrs);
+                continue;
+            }
+            // negative patterns are not allowed in attributes files
+            if pattern.starts_with('!') {
+                continue;
+            }
+            let regex = path_regex(pattern.trim_end_matches('/'))
+                .map_err(|e| GitError::AttributeError(format!("{}:{}: {:?}", base, no + 1, e)))?;
+            self.lines.push(Line {
+                regex,
+                base: base.trim_matches('/').to_string(),
+                attrs,
+            });
+        }
+        Ok(())
+    }
+
+    /// read the attributes files of the root and of every directory above the path
+    fn load_dirs(&mut self, path: &str) -> Result<(), GitError> {
+        let mut dirs = vec![String::new()];
+        let mut end = 0;
+        while let Some(pos) = path[end..].find('/') {
+            end += pos;
+            dirs.push(path[..end].to_string());
+            end += 1;
+        }
+        for dir in dirs {
+            if !self.loaded.insert(dir.clone()) {
+                continue;
+            }
+            let file = self.root.join(&dir).join(ATTRIBUTES_FILE);
+            if file.is_file() {
+                let content = fs::read_to_string(&file)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                self.add_patterns(&content, &dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// every specified attribute of the path, macros expanded
+    pub fn lookup(&mut self, path: &str) -> Result<BTreeMap<String, AttrValue>, GitError> {
+        let path = path.trim_matches('/');
+        self.load_dirs(path)?;
+        let mut found = BTreeMap::new();
+        for line in self.lines.iter() {
+            let relative = if line.base.is_empty() {
+                Some(path)
+            } else {
+                path.strip_prefix(&line.base)
+                    .and_then(|rest| rest.strip_prefix('/'))
+            };
+            if !relative.is_some_and(|relative| line.regex.is_match(relative)) {
+                continue;
+            }
+            for (name, value) in line.attrs.iter() {
+                if value == &Some(AttrValue::Set) {
+                    for (name, value) in self.macros.get(name).into_iter().flatten() {
+                        Self::apply(&mut found, name, value);
+                    }
+                }
+                Self::apply(&mut found, name, value);
+            }
+        }
+        Ok(found)
+    }
+
+    fn apply(found: &mut BTreeMap<String, AttrValue>, name: &str, value: &Option<AttrValue>) {
+        match value {
+            Some(value) => found.insert(name.to_string(), value.clone()),
+            None => found.remove(name),
+        };
+    }
+
+    /// whether the path is binary: `binary`, `-text` or `-diff`
+    pub fn is_binary(&mut self, path: &str) -> Result<bool, GitError> {
+        let attrs = self.lookup(path)?;
+        Ok(attrs.get("binary") == Some(&AttrValue::Set)
+            || attrs.get("text") == Some(&AttrValue::Unset)
+            || attrs.get("diff") == Some(&AttrValue::Unset))
+    }
+
+    /// line ending of the working directory file, `eol=lf` or `eol=crlf`
+    pub fn eol(&mut self, path: &str) -> Result<Option<String>, GitError> {
+        Ok(match self.lookup(path)?.remove("eol") {
+            Some(AttrValue::Value(eol)) => Some(eol),
+            _ => None,
+        })
+    }
+
+    /// diff driver of `diff=<driver>`
+    pub fn diff_driver(&mut self, path: &str) -> Result<Option<String>, GitError> {
+        Ok(match self.lookup(path)?.remove("diff") {
+            Some(AttrValue::Value(driver)) => Some(driver),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn lookup_ut() {
+        let mut attributes = Attributes::new(Path::new("/nonexistent"));
+        let root = "# comment\n*.sh text eol=lf\n*.png binary\n[attr]generated -diff gen\n\
+                    *.lock generated\ndocs/*.md diff=markdown\n!*.x text\n";
+        assert!(attributes.add_patterns(root, "").is_ok());
+        assert!(attributes
+            .add_patterns("*.sh eol=crlf !text\n", "win")
+            .is_ok());
+
+        assert_eq!(
+            BTreeMap::from([
+                ("eol".to_string(), AttrValue::Value("lf".to_string())),
+                ("text".to_string(), AttrValue::Set)
+            ]),
+            attributes.lookup("bin/run.sh").unwrap()
+        );
+        assert_eq!(
+            BTreeMap::from([("eol".to_string(), AttrValue::Value("crlf".to_string()))]),
+            attributes.lookup("win/run.sh").unwrap()
+        );
+        assert_eq!(
+            Some("crlf".to_string()),
+            attributes.eol("win/run.sh").unwrap()
+        );
+        assert!(attributes.is_binary("img/a.png").unwrap());
+        assert_eq!(
+            Some(&AttrValue::Unset),
+            attributes.lookup("a.png").unwrap().get("merge")
+        );
+        assert!(attributes.is_binary("Cargo.lock").unwrap());
+        assert!(attributes.lookup("Cargo.lock").unwrap().contains_key("gen"));
+        assert!(!attributes.is_binary("run.sh").unwrap());
+        assert_eq!(
+            Some("markdown".to_string()),
+            attributes.diff_driver("docs/a.md").unwrap()
+        );
+        assert_eq!(None, attributes.diff_driver("a.md").unwrap());
+        assert!(attributes.lookup("a.x").unwrap().is_empty());
+    }
+
+    #[test]
+    fn load_dirs_ut() {
+        let root = &env::current_dir().unwrap().join("attributes_load_dirs_ut");
+        if root.exists() {
+            assert!(fs::remove_dir_all(root).is_ok());
+        }
+        assert!(fs::create_dir_all(root.join("sub")).is_ok());
+        assert!(fs::write(root.join(ATTRIBUTES_FILE), "*.txt text\n*.bin binary\n").is_ok());
+        assert!(fs::write(root.join("sub").join(ATTRIBUTES_FILE), "*.txt -text\n").is_ok());
+
+        let mut attributes = Attributes::new(root);
+        assert!(!attributes.is_binary("a.txt").unwrap());
+        assert!(attributes.is_binary("a.bin").unwrap());
+        assert!(attributes.is_binary("sub/a.txt").unwrap());
+        assert!(!attributes.is_binary("other/a.txt").unwrap());
+        assert!(fs::remove_dir_all(root).is_ok());
+    }
+}
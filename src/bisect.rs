@@ -0,0 +1,108 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Operation of the bisect command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BisectAction {
+    /// start bisecting, optionally with a bad commit followed by good commits
+    Start,
+    /// mark a commit, the checked out one by default, as containing the bug
+    Bad,
+    /// mark a commit, the checked out one by default, as free of the bug
+    Good,
+    /// stop bisecting and go back to the branch head
+    Reset,
+}
+
+/// Progress of a bisection
+/// branch: the branch bisect started on, its head is restored by reset
+/// current: the commit whose tree is in the working directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BisectState {
+    pub branch: String,
+    pub current: String,
+    pub bad: Option<String>,
+    pub good: Vec<String>,
+}
+
+/// Number of candidates reachable from each candidate, itself included.
+/// parents: candidate commit sha1 --> parent sha1s, parents outside the candidates are ignored
+fn reach_counts(parents: &BTreeMap<String, Vec<String>>) -> BTreeMap<&str, usize> {
+    parents
+        .keys()
+        .map(|start| {
+            let mut seen = HashSet::new();
+            let mut pending = vec![start.as_str()];
+            while let Some(sha1) = pending.pop() {
+                if let Some(ps) = parents.get(sha1) {
+                    if seen.insert(sha1) {
+                        pending.extend(ps.iter().map(|p| p.as_str()));
+                    }
+                }
+            }
+            (start.as_str(), seen.len())
+        })
+        .collect()
+}
+
+/// The candidate that splits the remaining candidates most evenly: whether it turns out good
+/// or bad, as few candidates as possible are left. None when only one candidate is left.
+pub fn midpoint(parents: &BTreeMap<String, Vec<String>>) -> Option<String> {
+    let total = parents.len();
+    reach_counts(parents)
+        .into_iter()
+        .map(|(sha1, reach)| (reach.min(total - reach), sha1))
+        .filter(|(score, _)| *score > 0)
+        .max_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(a.1)))
+        .map(|(_, sha1)| sha1.to_string())
+}
+
+/// rough number of steps left to bisect `n` candidates
+pub fn steps_left(n: usize) -> u32 {
+    usize::BITS - n.max(1).leading_zeros() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(n: usize) -> BTreeMap<String, Vec<String>> {
+        (0..n)
+            .map(|i| {
+                let parents = if i == 0 {
+                    vec!["good".to_string()]
+                } else {
+                    vec![format!("c{}", i - 1)]
+                };
+                (format!("c{}", i), parents)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn midpoint_ut() {
+        // c0 <- c1 <- ... <- c6, c6 is bad
+        assert_eq!(Some("c2".to_string()), midpoint(&linear(7)));
+        assert_eq!(Some("c0".to_string()), midpoint(&linear(2)));
+        assert_eq!(None, midpoint(&linear(1)));
+        assert_eq!(None, midpoint(&BTreeMap::new()));
+
+        // a merge: m has parents a and b, both on top of the good commit
+        let parents = BTreeMap::from([
+            ("a".to_string(), vec!["good".to_string()]),
+            ("b".to_string(), vec!["good".to_string()]),
+            ("m".to_string(), vec!["a".to_string(), "b".to_string()]),
+        ]);
+        assert_eq!(Some("a".to_string()), midpoint(&parents));
+    }
+
+    #[test]
+    fn steps_left_ut() {
+        assert_eq!(0, steps_left(0));
+        assert_eq!(0, steps_left(1));
+        assert_eq!(1, steps_left(2));
+        assert_eq!(2, steps_left(7));
+        assert_eq!(3, steps_left(8));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::diff::{self, Edit};
+use crate::repo::Commit;
+use chrono::{TimeZone, Utc};
+
+/// longest commit subject shown in front of a line
+const SUBJECT_WIDTH: usize = 20;
+
+/// Attribute every line of the newest version of a file to the version that introduced it.
+/// versions: file contents newest first, one for each change, the oldest version is compared
+/// with an empty file. Returns the index of the introducing version for each line of versions[0].
+pub fn attribute(versions: &[&str]) -> Vec<usize> {
+    let Some(newest) = versions.first() else {
+        return vec![];
+    };
+    let mut owners = vec![versions.len().saturating_sub(1); newest.lines().count()];
+    // (line of the newest version, its position in the version being looked at)
+    let mut pending: Vec<(usize, usize)> = (0..owners.len()).map(|i| (i, i)).collect();
+    for (i, content) in versions.iter().enumerate() {
+        if pending.is_empty() {
+            break;
+        }
+        let new_lines: Vec<&str> = content.lines().collect();
+        let old_lines: Vec<&str> = versions.get(i + 1).map_or(vec![], |c| c.lines().collect());
+        // position in the older version of every line of this one, None when it was added
+        let mut old_positions = vec![];
+        let mut old_no = 0;
+        for edit in diff::edits(&old_lines, &new_lines) {
+            match edit {
+                Edit::Same(_) => {
+                    old_positions.push(Some(old_no));
+                    old_no += 1;
+                }
+                Edit::Removed(_) => old_no += 1,
+                Edit::Added(_) => old_positions.push(None),
+            }
+        }
+        pending.retain_mut(|(line, pos)| match old_positions[*pos] {
+            Some(old_pos) => {
+                *pos = old_pos;
+                true
+            }
+            None => {
+                owners[*line] = i;
+                false
+            }
+        });
+    }
+    owners
+}
+
+/// Render blamed lines as `<short sha1> <date> <subject> <line no>) <line>`
+pub fn render(lines: &[(&str, &Commit, &str)]) -> String {
+    let no_width = lines.len().to_string().len();
+    let subject = |commit: &Commit| -> String {
+        commit
+            .message()
+            .lines()
+            .next()
+            .unwrap_or("")
+            .chars()
+            .take(SUBJECT_WIDTH)
+            .collect()
+    };
+    let subject_width = lines
+        .iter()
+        .map(|(_, commit, _)| subject(commit).chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (no, (sha1, commit, line)) in lines.iter().enumerate() {
+        let date = Utc
+            .timestamp_opt(commit.date_time(), 0)
+            .single()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "{} {} {:<subject_width$} {:>no_width$}) {}\n",
+            &sha1[..sha1.len().min(8)],
+            date,
+            subject(commit),
+            no + 1,
+            line
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_ut() {
+        // newest first: b changed a line and appended one, a added the first three
+        let versions = ["one\nTWO\nthree\nfour\n", "one\ntwo\nthree\n"];
+        assert_eq!(vec![1, 0, 1, 0], attribute(&versions));
+
+        let versions = ["x\na\n", "a\n", "a\nb\n"];
+        assert_eq!(vec![0, 2], attribute(&versions));
+        assert_eq!(vec![0, 0], attribute(&["a\nb\n"]));
+        assert!(attribute(&[]).is_empty());
+    }
+
+    #[test]
+    fn render_ut() {
+        let mut commit = Commit::new();
+        commit.set_message("a rather long commit subject\n\nbody");
+        let sha1 = "a".repeat(40);
+        let lines: Vec<(&str, &Commit, &str)> =
+            (0..10).map(|_| (sha1.as_str(), &commit, "line")).collect();
+        let out = render(&lines);
+        assert!(out.starts_with("aaaaaaaa 1970-01-01 a rather long commit  1) line\n"));
+        assert!(out.ends_with("aaaaaaaa 1970-01-01 a rather long commit 10) line\n"));
+    }
+}
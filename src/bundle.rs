@@ -0,0 +1,134 @@
+use crate::error::GitError;
+use crate::repo::{Commit, Tag};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// first line of every bundle file
+const BUNDLE_HEADER: &str = "# git-rs bundle v1";
+
+/// Operation of the bundle command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BundleAction {
+    /// pack refs and everything reachable from them into a file
+    Create,
+    /// import the objects and refs of a bundle file
+    Unbundle,
+}
+
+/// Refs and every object reachable from them, packed in a single file.
+/// refs: ref name, e.g. `refs/heads/main` --> commit sha1, or tag object sha1 for annotated tags
+/// commits, tags: sha1 --> object, blobs: sha1 --> content of text blobs,
+/// binary_blobs: sha1 --> bytes of the blobs that are not UTF-8
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub refs: BTreeMap<String, String>,
+    pub commits: BTreeMap<String, Commit>,
+    pub tags: BTreeMap<String, Tag>,
+    pub blobs: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub binary_blobs: BTreeMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    /// add a blob, as text when it is UTF-8
+    pub fn add_blob(&mut self, sha1: &str, content: Vec<u8>) {
+        match String::from_utf8(content) {
+            Ok(text) => {
+                self.blobs.insert(sha1.to_string(), text);
+            }
+            Err(e) => {
+                self.binary_blobs.insert(sha1.to_string(), e.into_bytes());
+            }
+        }
+    }
+
+    pub fn has_blob(&self, sha1: &str) -> bool {
+        self.blobs.contains_key(sha1) || self.binary_blobs.contains_key(sha1)
+    }
+
+    /// sha1 --> content of every blob
+    pub fn blob_contents(&self) -> impl Iterator<Item = (&String, &[u8])> {
+        self.blobs
+            .iter()
+            .map(|(sha1, content)| (sha1, content.as_bytes()))
+            .chain(
+                self.binary_blobs
+                    .iter()
+                    .map(|(sha1, content)| (sha1, content.as_slice())),
+            )
+    }
+
+    /// every object id the bundle names: its objects, the parents, blobs and tagged commits
+    /// they refer to and the ref targets
+    pub fn object_ids(&self) -> impl Iterator<Item = &str> {
+        let commits = self.commits.iter().flat_map(|(sha1, commit)| {
+            let blobs = commit.blobs().values().map(|entry| entry.sha1.as_str());
+            [sha1.as_str()]
+                .into_iter()
+                .chain(commit.parents())
+                .chain(blobs)
+        });
+        let tags = self
+            .tags
+            .iter()
+            .flat_map(|(sha1, tag)| [sha1.as_str(), tag.object()]);
+        self.blob_contents()
+            .map(|(sha1, _)| sha1.as_str())
+            .chain(commits)
+            .chain(tags)
+            .chain(self.refs.values().map(|target| target.as_str()))
+    }
+
+    /// the bundle file content: the header line followed by the bundle as JSON
+    pub fn to_file_content(&self) -> Result<String, GitError> {
+        let json =
+            serde_json::to_string(self).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+        Ok(format!("{}\n{}\n", BUNDLE_HEADER, json))
+    }
+
+    /// parse the content of a bundle file
+    pub fn from_file_content(content: &str) -> Result<Self, GitError> {
+        let json = content
+            .strip_prefix(BUNDLE_HEADER)
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .ok_or_else(|| GitError::BundleError("not a git-rs bundle".to_string()))?;
+        serde_json::from_str(json).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_content_ut() {
+        let commit = Commit::init_commit();
+        let sha1 = "a".repeat(40);
+        let bundle = Bundle {
+            refs: BTreeMap::from([("refs/heads/main".to_string(), sha1.clone())]),
+            commits: BTreeMap::from([(sha1, commit)]),
+            tags: BTreeMap::new(),
+            blobs: BTreeMap::from([("b".repeat(40), "content\n".to_string())]),
+            ..Default::default()
+        };
+        let content = bundle.to_file_content().unwrap();
+        assert!(content.starts_with("# git-rs bundle v1\n{\"refs\":"));
+        assert!(!content.contains("binary_blobs"));
+        assert_eq!(bundle, Bundle::from_file_content(&content).unwrap());
+
+        let mut bundle = bundle;
+        bundle.add_blob(&"c".repeat(40), vec![0xff, 0]);
+        assert!(bundle.has_blob(&"c".repeat(40)));
+        assert_eq!(2, bundle.blob_contents().count());
+        let mut ids: Vec<&str> = bundle.object_ids().collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(vec!["a".repeat(40), "b".repeat(40), "c".repeat(40)], ids);
+        let content = bundle.to_file_content().unwrap();
+        assert_eq!(bundle, Bundle::from_file_content(&content).unwrap());
+
+        assert!(Bundle::from_file_content("{}").is_err());
+        assert!(Bundle::from_file_content("# git-rs bundle v1\n{").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::conventional::ConventionalMessage;
+use crate::error::GitError;
+use crate::repo::Commit;
+use std::fs;
+use std::path::Path;
+
+/// changelog file header
+const HEADER: &str = "# Changelog";
+
+/// conventional commit type --> changelog section title, in rendering order
+const SECTIONS: [(&str, &str); 11] = [
+    ("feat", "Features"),
+    ("fix", "Bug Fixes"),
+    ("perf", "Performance Improvements"),
+    ("revert", "Reverts"),
+    ("refactor", "Code Refactoring"),
+    ("docs", "Documentation"),
+    ("style", "Styles"),
+    ("test", "Tests"),
+    ("build", "Build System"),
+    ("ci", "Continuous Integration"),
+    ("chore", "Chores"),
+];
+/// section for commits that are not conventional or have an unknown type
+const OTHER_SECTION: &str = "Other Changes";
+
+/// one changelog line, e.g. `- **repo:** add log (1fb6db29)`
+fn entry(sha1: &str, scope: &Option<String>, description: &str) -> String {
+    match scope {
+        Some(scope) => format!(
+            "- **{}:** {} ({})",
+            scope,
+            description,
+            &sha1[..sha1.len().min(8)]
+        ),
+        None => format!("- {} ({})", description, &sha1[..sha1.len().min(8)]),
+    }
+}
+
+/// Render a markdown changelog section for the given commits (newest first)
+/// grouped by conventional commit type, breaking changes are listed first
+pub fn render(title: &str, commits: &[(String, Commit)]) -> String {
+    let mut breaking = vec![];
+    let mut groups: Vec<Vec<String>> = vec![vec![]; SECTIONS.len() + 1];
+    for (sha1, commit) in commits.iter() {
+        match ConventionalMessage::parse(commit.message()) {
+            Some(msg) => {
+                let line = entry(sha1, &msg.scope, &msg.description);
+                if msg.breaking {
+                    breaking.push(line.clone());
+                }
+                let idx = SECTIONS
+                    .iter()
+                    .position(|(kind, _)| *kind == msg.kind)
+                    .unwrap_or(SECTIONS.len());
+                groups[idx].push(line);
+            }
+            None => {
+                let subject = commit.message().lines().next().unwrap_or("");
+                groups[SECTIONS.len()].push(entry(sha1, &None, subject));
+            }
+        }
+    }
+
+    let mut out = format!("## {}\n", title);
+    if !breaking.is_empty() {
+        out.push_str(&format!(
+            "\n### BREAKING CHANGES\n\n{}\n",
+            breaking.join("\n")
+        ));
+    }
+    let titles = SECTIONS
+        .iter()
+        .map(|(_, title)| *title)
+        .chain([OTHER_SECTION]);
+    for (title, lines) in titles.zip(groups.iter()) {
+        if !lines.is_empty() {
+            out.push_str(&format!("\n### {}\n\n{}\n", title, lines.join("\n")));
+        }
+    }
+    out
+}
+
+/// Insert a rendered section at the top of a changelog file, below its header.
+/// The file is created when it does not exist.
+pub fn prepend(path: &Path, section: &str) -> Result<(), GitError> {
+    let existing = if path.exists() {
+        fs::read_to_string(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+    } else {
+        String::new()
+    };
+    let rest = existing
+        .strip_prefix(HEADER)
+        .unwrap_or(&existing)
+        .trim_start_matches('\n');
+    let mut content = format!("{}\n\n{}", HEADER, section);
+    if !rest.is_empty() {
+        content.push('\n');
+        content.push_str(rest);
+    }
+    fs::write(path, content).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn commit(msg: &str) -> Commit {
+        let mut commit = Commit::new();
+        commit.set_message(msg);
+        commit
+    }
+
+    #[test]
+    fn render_ut() {
+        let commits = vec![
+            ("a".repeat(40), commit("feat(repo): add changelog")),
+            ("b".repeat(40), commit("fix: typo in status")),
+            ("c".repeat(40), commit("feat!: drop old index format")),
+            ("d".repeat(40), commit("initial commit")),
+        ];
+        assert_eq!(
+            r#"## v1.0.0
+
+### BREAKING CHANGES
+
+- drop old index format (cccccccc)
+
+### Features
+
+- **repo:** add changelog (aaaaaaaa)
+- drop old index format (cccccccc)
+
+### Bug Fixes
+
+- typo in status (bbbbbbbb)
+
+### Other Changes
+
+- initial commit (dddddddd)
+"#,
+            render("v1.0.0", &commits)
+        );
+    }
+
+    #[test]
+    fn prepend_ut() {
+        let tmp_dir = &env::current_dir().unwrap().join("changelog_prepend_ut");
+        if tmp_dir.exists() {
+            assert!(fs::remove_dir_all(tmp_dir).is_ok());
+        }
+        assert!(fs::create_dir(tmp_dir).is_ok());
+        let path = tmp_dir.join("CHANGELOG.md");
+        assert!(prepend(&path, "## v1\n\n- a\n").is_ok());
+        assert!(prepend(&path, "## v2\n\n- b\n").is_ok());
+        assert_eq!(
+            "# Changelog\n\n## v2\n\n- b\n\n## v1\n\n- a\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        assert!(fs::remove_dir_all(tmp_dir).is_ok());
+    }
+}
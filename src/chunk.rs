@@ -0,0 +1,216 @@
+use crate::error::GitError;
+use std::io::{ErrorKind, Read};
+
+/// splitmix64 step, used to build a deterministic gear table
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        let (next, value) = splitmix64(state);
+        state = next;
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+/// random value per byte value for the gear hash
+const GEAR: [u64; 256] = gear_table();
+
+/// mask with the given number of high bits set
+const fn high_bits(bits: u32) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        u64::MAX << (64 - bits)
+    }
+}
+
+/// Chunk size bounds in bytes, avg_size should be a power of two
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkParams {
+    pub min_size: usize,
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for ChunkParams {
+    fn default() -> Self {
+        Self {
+            min_size: 16 * 1024,
+            avg_size: 64 * 1024,
+            max_size: 256 * 1024,
+        }
+    }
+}
+
+impl ChunkParams {
+    /// normalized chunking: a harder mask before the average size and an easier one after
+    fn masks(&self) -> (u64, u64) {
+        let bits = self.avg_size.max(2).ilog2();
+        (high_bits(bits + 1), high_bits(bits - 1))
+    }
+}
+
+/// length of the first chunk of data
+fn next_cut(data: &[u8], params: &ChunkParams, masks: (u64, u64)) -> usize {
+    let len = data.len();
+    if len <= params.min_size {
+        return len;
+    }
+    let len = len.min(params.max_size);
+    let normal = params.avg_size.min(len);
+    let mut hash = 0u64;
+    let mut i = params.min_size;
+    while i < normal {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & masks.0 == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+    while i < len {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & masks.1 == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+    len
+}
+
+/// split everything the reader yields into content-defined chunks (FastCDC) passed to `f`
+/// in order, holding about `max_size` bytes in memory at a time.
+/// A rolling gear hash is computed over the content and a chunk ends where the hash
+/// matches a mask. Cut points depend only on nearby content, so an edit in a large
+/// file only changes the chunks around it and every other chunk is deduplicated.
+pub fn for_each_chunk<R, F>(mut reader: R, params: &ChunkParams, mut f: F) -> Result<(), GitError>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> Result<(), GitError>,
+{
+    let masks = params.masks();
+    let mut buf = Vec::with_capacity(2 * params.max_size);
+    let mut read_buf = vec![0; params.max_size];
+    let mut eof = false;
+    loop {
+        // a cut point only depends on the first max_size bytes
+        while !eof && buf.len() < params.max_size {
+            match reader.read(&mut read_buf) {
+                Ok(0) => eof = true,
+                Ok(n) => buf.extend_from_slice(&read_buf[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(GitError::FileOpError(format!("{:?}", e))),
+            }
+        }
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let cut = next_cut(&buf, params, masks);
+        f(&buf[..cut])?;
+        buf.drain(..cut);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn chunks(data: &[u8], params: &ChunkParams) -> Vec<Vec<u8>> {
+        let mut chunks = vec![];
+        let res = for_each_chunk(data, params, |chunk| {
+            chunks.push(chunk.to_vec());
+            Ok(())
+        });
+        assert!(res.is_ok());
+        chunks
+    }
+
+    /// reader yielding at most 1000 bytes per read
+    struct SmallReads<'a>(&'a [u8]);
+
+    impl Read for SmallReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1000);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                let (next, value) = splitmix64(state);
+                state = next;
+                value as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_bounds_ut() {
+        let params = ChunkParams::default();
+        let data = random_bytes(2 * 1024 * 1024, 1);
+        let cut = chunks(&data, &params);
+        assert!(cut.len() > 8, "{}", cut.len());
+        assert_eq!(data, cut.concat());
+        for chunk in cut[..cut.len() - 1].iter() {
+            assert!(chunk.len() >= params.min_size);
+            assert!(chunk.len() <= params.max_size);
+        }
+        assert!(chunks(&[], &params).is_empty());
+        assert_eq!(1, chunks(&data[..100], &params).len());
+    }
+
+    #[test]
+    fn for_each_chunk_ut() {
+        let params = ChunkParams::default();
+        let data = random_bytes(2 * 1024 * 1024 + 123, 3);
+        // cut points do not depend on how the content is read
+        let mut streamed = vec![];
+        let res = for_each_chunk(SmallReads(&data), &params, |chunk| {
+            streamed.push(chunk.to_vec());
+            Ok(())
+        });
+        assert!(res.is_ok());
+        assert_eq!(chunks(&data, &params), streamed);
+
+        let failing = for_each_chunk(data.as_slice(), &params, |_| {
+            Err(GitError::FileOpError("full".to_string()))
+        });
+        assert!(failing.is_err());
+    }
+
+    #[test]
+    fn chunks_dedup_after_edit_ut() {
+        let params = ChunkParams::default();
+        let data = random_bytes(2 * 1024 * 1024, 2);
+        let mut edited = data.clone();
+        edited.splice(
+            1024 * 1024..1024 * 1024,
+            b"inserted in the middle".iter().cloned(),
+        );
+
+        let before: HashSet<Vec<u8>> = chunks(&data, &params).into_iter().collect();
+        let after = chunks(&edited, &params);
+        let new_chunks = after.iter().filter(|c| !before.contains(*c)).count();
+        assert!(
+            new_chunks <= 2,
+            "{} of {} chunks changed",
+            new_chunks,
+            after.len()
+        );
+    }
+}
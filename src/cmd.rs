@@ -1,5 +1,21 @@
-use crate::repo::{GitRepository, GIT_DIR};
-use clap::Parser;
+use crate::archive::ArchiveFormat;
+use crate::bisect::BisectAction;
+use crate::bundle::BundleAction;
+use crate::daemon::{self, Daemon};
+use crate::error::GitError;
+use crate::merge::{MergeOptions, Strategy, StrategyOption};
+use crate::release::Bump;
+use crate::remote::RemoteAction;
+use crate::repo::{
+    CommitOptions, DiffOptions, GitRepository, LogOptions, LsFilesOptions, PushOptions,
+    StatusOptions, GIT_DIR,
+};
+use crate::stash::{self, StashAction};
+use crate::worktree::WorktreeAction;
+use crate::{api, changelog, http, interactive, revision, transport, web};
+use clap::{Parser, ValueEnum};
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
 #[derive(Debug, Parser)]
 #[clap(name = "git-rs")]
 pub enum GitCommand {
@@ -9,11 +25,25 @@ pub enum GitCommand {
     Init {},
 
     /// add [file name]
+    /// add -A [path]...
+    /// add -u [path]...
     /// Description: Stage the file for addition to the next commit.
+    /// With -A, stage every new, modified and deleted file under the paths or the whole working directory.
+    /// With -u, stage only the modified and deleted tracked files, untracked files are left alone.
+    /// With -i, list the changed files in the terminal to stage, unstage or diff them one by one.
     #[command(arg_required_else_help = true)]
     Add {
+        /// Stage all changes
+        #[arg(short = 'A', long = "all")]
+        all: bool,
+        /// Stage changes to tracked files only
+        #[arg(short = 'u', long = "update", conflicts_with = "all")]
+        update: bool,
+        /// Pick the files to stage interactively
+        #[arg(short = 'i', long, conflicts_with_all = ["all", "update", "paths"])]
+        interactive: bool,
         /// Stuff to add
-        #[arg(required = true)]
+        #[arg(required_unless_present_any = ["all", "update", "interactive"])]
         paths: Vec<String>,
     },
     /// rm [file name]
@@ -32,7 +62,7 @@ pub enum GitCommand {
         paths: Vec<String>,
     },
     ///
-    /// commit [message]
+    /// commit [message] [-- <path>...]
     /// Description: Saves a snapshot of tracked files in the current commit and staging area
     /// so they can be restored at a later time, creating a new commit.
     /// The commit is said to be tracking the saved files.
@@ -52,10 +82,33 @@ pub enum GitCommand {
     /// If it doesn’t, print the error message Please enter a commit message.
     /// It is not a failure for tracked files to be missing from the working directory or
     /// changed in the working directory.
+    /// With `-- <path>...`, only the staged changes under the paths are committed and
+    /// the other staged changes stay in the index.
+    /// With --amend, the head commit is replaced by one with the same parents, its files plus
+    /// every staged change, and the given message or, without one, the old message.
+    /// The author and committer are `user.name <user.email>` from the config, --author records
+    /// someone else as author. An amended commit keeps its author.
+    /// With -S, or when commit.gpgSign is set, the commit is signed with user.signingKey by gpg
+    /// or, when gpg.format is ssh, by ssh-keygen.
+    /// The pre-commit, commit-msg and post-commit scripts of .git-rs/hooks, or of core.hooksPath,
+    /// run around the commit: a failing pre-commit or commit-msg aborts it, commit-msg may
+    /// rewrite the message file it is given. -n skips both.
     #[command(arg_required_else_help = true)]
     Commit {
-        #[arg(required = true)]
-        message: String,
+        #[arg(required_unless_present = "amend")]
+        message: Option<String>,
+        #[arg(long, conflicts_with = "paths")]
+        amend: bool,
+        /// `Name <email>` of the author
+        #[arg(long, conflicts_with = "amend")]
+        author: Option<String>,
+        #[arg(short = 'S', long = "gpg-sign", conflicts_with = "amend")]
+        sign: bool,
+        /// skip the pre-commit and commit-msg hooks
+        #[arg(short = 'n', long, conflicts_with = "amend")]
+        no_verify: bool,
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
 
     /// Usage: java gitlet.Main status
@@ -81,10 +134,51 @@ pub enum GitCommand {
     /// === Untracked Files ===
     /// random.stuff
     ///
+    /// When the current branch has an upstream, see branch -u, a first paragraph tells how many
+    /// commits each side has that the other lacks, e.g.
+    /// Your branch is ahead of 'origin/main' by 2 commits.
+    ///
+    /// With --porcelain, prints one `XY path` line per changed file instead, X the staged
+    /// change and Y the change not staged: `A ` added, `M ` modified and `D ` removed in the
+    /// index, ` M` modified and ` D` deleted in the working directory, and `??` untracked.
+    /// With -s, prints the same lines as --porcelain, colored like the long format.
+    /// With --json, prints an object with the current branch and the lists of other branches,
+    /// staged, removed, modified and untracked files.
+    /// On a terminal, staged files are green, modified and deleted ones red and untracked ones
+    /// cyan; the color.ui setting, auto, always or never, changes when colors are used.
     #[clap(name = "status")]
-    Status {},
+    Status {
+        #[arg(long)]
+        porcelain: bool,
+        #[arg(long, conflicts_with = "porcelain")]
+        json: bool,
+        #[arg(short = 's', long, conflicts_with_all = ["porcelain", "json"])]
+        short: bool,
+    },
 
-    /// Usage: git log
+    /// Usage: git ls-files [--staged] [--deleted] [--others] [--modified] [<path>...]
+    /// Description: Prints the selected files one path per line, every file in the index when
+    /// no flag is given. Flags can be combined, paths limit the output to the given files and
+    /// directories.
+    #[clap(name = "ls-files")]
+    LsFiles {
+        /// files staged for addition
+        #[arg(long)]
+        staged: bool,
+        /// files in the index deleted from the working directory
+        #[arg(long)]
+        deleted: bool,
+        /// untracked files
+        #[arg(long)]
+        others: bool,
+        /// files in the index changed in the working directory
+        #[arg(long)]
+        modified: bool,
+        paths: Vec<String>,
+    },
+
+    /// Usage: git log [--stat] [-n <count>] [--since <date>] [--until <date>]
+    /// [--author <text>] [--grep <regex>] [<range> | --follow <file>]
     /// Description: Displays information about each commit backwards along the commit tree
     /// starting at the current head commit, until the initial commit. For every commit, it
     /// should display the commit id, the time the commit was made, the commit message,
@@ -105,10 +199,239 @@ pub enum GitCommand {
     /// commit e881c9575d180a215d1a636545b8fd9abfb1d2bb
     /// Date: Wed Dec 31 16:00:00 1969 -0800
     /// initial commit
+    ///
+    /// With --format=dot, the commit graph of every branch and tag is printed in Graphviz DOT
+    /// format instead, e.g. `git-rs log --format=dot | dot -Tsvg > history.svg`.
+    /// With a range, only its commits are shown, newest first: `A..B` the commits reachable
+    /// from B but not from A, `A...B` those reachable from either but not from both.
+    /// The filters keep the commits made in a date range, by a matching author or with a
+    /// matching message, and -n keeps the first commits passing them. Dates are like
+    /// `2023-11-14`, `2023-11-14 13:00`, `@<seconds>` or `2 weeks ago`.
+    /// With --follow, only the commits that changed the file are shown, also under the names
+    /// it had before being renamed. With --stat, each commit other than a merge is followed
+    /// by the lines it added and removed in every file it changed and their totals.
     #[clap(name = "log")]
-    Log {},
+    Log {
+        #[arg(long)]
+        format: Option<LogFormat>,
+        #[arg(conflicts_with = "format")]
+        range: Option<String>,
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["format", "range"])]
+        follow: Option<String>,
+        /// tell after each commit id whether it is unsigned or its signature is good or bad
+        #[arg(long, conflicts_with = "format")]
+        show_signature: bool,
+        /// list the branches, remote-tracking branches and tags pointing at each commit
+        #[arg(long, conflicts_with = "format")]
+        decorate: bool,
+        #[arg(long, conflicts_with = "format")]
+        stat: bool,
+        #[arg(short = 'n', long, conflicts_with = "format")]
+        max_count: Option<usize>,
+        #[arg(long, conflicts_with = "format")]
+        since: Option<String>,
+        #[arg(long, conflicts_with = "format")]
+        until: Option<String>,
+        #[arg(long, conflicts_with = "format")]
+        author: Option<String>,
+        #[arg(long, conflicts_with = "format")]
+        grep: Option<String>,
+    },
+
+    /// Usage: git diff [--staged] [-C] [<commit1> <commit2> | <commit1>..<commit2> |
+    /// <commit1>...<commit2>]
+    /// Description: Shows the changes of tracked files in the working directory against
+    /// the head commit as unified diffs. With --staged, shows the staged changes the next
+    /// commit will contain instead. With two commits, or `A..B`, shows the files added,
+    /// deleted and modified from the first commit to the second. `A...B` shows the changes
+    /// of B since its common ancestor with A. With -C, added files mostly copied from an
+    /// existing file are shown as copies diffed against it. Removed lines are red and added
+    /// lines green on a terminal, or as color.ui says.
+    #[clap(name = "diff")]
+    Diff {
+        #[arg(long, conflicts_with = "commits")]
+        staged: bool,
+        #[arg(short = 'C', long)]
+        find_copies: bool,
+        #[arg(num_args = 1..=2)]
+        commits: Vec<String>,
+    },
+
+    /// Usage: git show <commit>
+    /// Description: Shows the id, parents, date and message of the commit followed by
+    /// the diff against its first parent.
+    #[command(arg_required_else_help = true)]
+    Show { commit: String },
+
+    /// Usage: git global-log
+    /// Description: Like log, except displays information about all commits ever made.
+    /// The order of the commits does not matter.
+    #[clap(name = "global-log")]
+    GlobalLog {},
+
+    /// Usage: git find [commit message]
+    /// Description: Prints out the ids of all commits that have the given commit message,
+    /// one per line. If there are multiple such commits, it prints the ids out on separate lines.
+    #[command(arg_required_else_help = true)]
+    Find { message: String },
+
+    /// Usage: git cat-file [-t] <object>
+    /// Description: Prints the raw contents of a blob, or the pretty-printed structure of a
+    /// commit or tag object. Any revision naming a commit is accepted. With -t, only the type
+    /// of the object is printed.
+    #[clap(name = "cat-file")]
+    #[command(arg_required_else_help = true)]
+    CatFile {
+        /// print the object type instead of its contents
+        #[arg(short = 't')]
+        type_only: bool,
+        object: String,
+    },
+
+    /// Usage: git hash-object [-w] <file>
+    /// Description: Prints the sha1 of the file. With -w, the file is also written into the
+    /// object store as a blob, without being staged.
+    #[clap(name = "hash-object")]
+    #[command(arg_required_else_help = true)]
+    HashObject {
+        /// write the blob into the object store
+        #[arg(short = 'w')]
+        write: bool,
+        file: String,
+    },
+
+    /// Usage: git rev-parse [--short] <rev>...
+    /// Description: Prints the commit id every revision resolves to, one per line. A revision
+    /// is HEAD, a branch, remote-tracking branch or tag name, a commit id or a unique prefix
+    /// of at least 4 characters of one, followed by any number of ~<n>, the n-th ancestor
+    /// following first parents, and ^<n>, the n-th parent, where <n> defaults to 1: HEAD~2 and
+    /// HEAD^^ are the grandparent of HEAD, HEAD^2 is the second parent of a merge. Every
+    /// command taking a commit accepts the same forms. With --short, prints
+    /// the shortest unique abbreviation of at least 7 characters instead.
+    #[clap(name = "rev-parse")]
+    #[command(arg_required_else_help = true)]
+    RevParse {
+        #[arg(long)]
+        short: bool,
+        #[arg(required = true)]
+        revs: Vec<String>,
+    },
+
+    /// Usage: git describe [<commit>]
+    /// Description: Names the commit, HEAD by default, after the nearest tag reachable from it.
+    /// Prints the tag alone when it points at the commit, otherwise `<tag>-<N>-g<short id>`
+    /// where N is the number of commits since the tag.
+    Describe { commit: Option<String> },
+
+    /// Usage: git archive [--format <format>] -o <file> <commit>
+    /// Description: Writes the files of the commit into a tar, gzip compressed tar or zip
+    /// archive without touching the working directory. The format is guessed from the file
+    /// name (.tar, .tar.gz or .tgz, .zip) unless given.
+    #[command(arg_required_else_help = true)]
+    Archive {
+        #[arg(long, value_enum)]
+        format: Option<ArchiveFormat>,
+        #[arg(short = 'o', long = "output")]
+        output: String,
+        commit: String,
+    },
+
+    /// Usage: git bundle create <file> [<ref>...]
+    ///        git bundle unbundle <file>
+    /// Description: create packs the given branches and tags, every branch and tag by default,
+    /// and every commit, tag object and blob reachable from them into a single file.
+    /// unbundle imports the objects of such a file into this repository, creates its branches
+    /// and tags, and moves existing branches forward, except the checked out one.
+    #[command(arg_required_else_help = true)]
+    Bundle {
+        #[arg(value_enum)]
+        action: BundleAction,
+        file: String,
+        refs: Vec<String>,
+    },
+
+    /// Usage: git format-patch [-o <dir>] <range>
+    /// Description: Writes every non-merge commit of the range, oldest first, as an mbox style
+    /// patch file named after its number and subject, e.g. 0001-fix-the-parser.patch, with the
+    /// commit metadata and its unified diff. Ranges are written as for changelog.
+    #[clap(name = "format-patch")]
+    #[command(arg_required_else_help = true)]
+    FormatPatch {
+        #[arg(short = 'o', long = "output-directory")]
+        output_directory: Option<String>,
+        range: String,
+    },
+
+    /// Usage: git apply [--cached] <patch>
+    /// Description: Applies a unified diff, or the diff of a patch file written by
+    /// format-patch, to the working directory, or only to the index with --cached.
+    /// Nothing is changed unless every file of the patch applies.
+    #[command(arg_required_else_help = true)]
+    Apply {
+        #[arg(long)]
+        cached: bool,
+        patch: String,
+    },
+
+    /// Usage: git am <patch>...
+    /// Description: Applies patch files written by format-patch in order and commits each
+    /// of them with its original message and date. Stops at the first patch that does not
+    /// apply, keeping the commits made so far.
+    #[command(arg_required_else_help = true)]
+    Am {
+        #[arg(required = true)]
+        patches: Vec<String>,
+    },
+
+    /// Usage: git notes add [-f] -m <message> [<commit>] | git notes show [<commit>]
+    /// Description: Attaches a free-form note to a commit, HEAD by default, without rewriting
+    /// it, or prints the note of a commit. log and show display notes after the commit message.
+    /// An existing note is only replaced with -f.
+    #[command(arg_required_else_help = true)]
+    Notes {
+        action: NotesAction,
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+        #[arg(short = 'f', long)]
+        force: bool,
+        commit: Option<String>,
+    },
+
+    /// Usage: git grep [--commit <commit>] <pattern>
+    /// Description: Prints `path:line_no:line` for every line matching the regular expression
+    /// in the files tracked by the head commit, or by the given commit.
+    #[command(arg_required_else_help = true)]
+    Grep {
+        pattern: String,
+        #[arg(long)]
+        commit: Option<String>,
+    },
+
+    /// Usage: git blame <file>
+    /// Description: Shows every line of the file in the head commit with the id, date and
+    /// subject of the commit that introduced it.
+    #[command(arg_required_else_help = true)]
+    Blame { file: String },
+
+    /// Usage: git file-log [file name]
+    /// Description: Displays the commits of the current branch that changed the given file,
+    /// newest first, with the blob id of the file after each commit. Only the blob hash of
+    /// the file is compared between commits, so it stays fast on long histories.
+    #[clap(name = "file-log")]
+    FileLog {
+        #[arg(required = true)]
+        path: String,
+    },
 
-    /// Usage: git branch [branch name]
+    /// Usage: git branch [-a] | git branch -u <upstream> [branch name] | git branch [branch name]
+    /// Without a name, lists the branches, the current one marked with *, and with -a the
+    /// remote-tracking branches too, as remotes/<remote>/<branch>.
+    /// -u makes the remote-tracking branch <upstream>, e.g. origin/main, the upstream of the
+    /// branch, the current one by default, which status compares the branch with.
+    /// -m <old> <new> renames a branch, the current one when only the new name is given, its
+    /// upstream setting moves with it.
+    /// -d <name> deletes a branch the current branch has merged, -D even when it has not.
+    /// The branch checked out in a worktree is never deleted.
     /// Creates a new branch with the given name, and points it at the current head commit.
     /// A branch is nothing more than a name for a reference (a SHA-1 identifier) to a commit node.
     /// This command does NOT immediately switch to the newly created branch (just as in real Git).
@@ -116,11 +439,459 @@ pub enum GitCommand {
     /// Failure cases: If a branch with the given name already exists, print the error message A branch with that name already exists.
     #[clap(name = "branch")]
     Branch {
+        name: Option<String>,
+        /// the new name with -m
+        #[arg(requires = "rename")]
+        new_name: Option<String>,
+        #[arg(
+            short = 'm',
+            long = "move",
+            requires = "name",
+            conflicts_with = "upstream"
+        )]
+        rename: bool,
+        #[arg(short = 'd', long, requires = "name", conflicts_with_all = ["rename", "upstream", "new_name"])]
+        delete: bool,
+        /// delete even when not merged
+        #[arg(short = 'D', requires = "name", conflicts_with_all = ["rename", "upstream", "new_name"])]
+        force_delete: bool,
+        #[arg(short, long, conflicts_with = "name")]
+        all: bool,
+        #[arg(short = 'u', long = "set-upstream-to", conflicts_with = "all")]
+        upstream: Option<String>,
+    },
+
+    /// Usage: git tag [-a -m <message>] [<name> [<commit>]] | git tag -v <name>
+    /// Description: Creates a lightweight tag pointing at the commit, HEAD by default.
+    /// With -a (or -m) an annotated tag object storing the message, tagger and date is created.
+    /// -v shows the annotation of an annotated tag.
+    /// Without a name, lists all tags. Tag names can be used wherever a commit id is accepted.
+    #[clap(name = "tag")]
+    Tag {
+        #[arg(short = 'a', long = "annotate", requires = "message")]
+        annotate: bool,
+        #[arg(short, long)]
+        message: Option<String>,
+        #[arg(short = 'v', long = "verify", requires = "name", conflicts_with_all = ["annotate", "message", "commit"])]
+        verify: bool,
+        name: Option<String>,
+        commit: Option<String>,
+    },
+
+    /// Usage: git changelog <from>..<to> [--title <title>] [--output <file>]
+    /// Description: Renders the commits reachable from <to> but not from <from> as a markdown
+    /// changelog grouped by conventional commit type (feat, fix, ...). Either side of the range
+    /// may be empty, e.g. `v1..` lists everything since v1 up to HEAD.
+    /// With --output the section is inserted at the top of the given changelog file.
+    #[clap(name = "changelog")]
+    Changelog {
+        #[arg(required = true)]
+        range: String,
+        /// section title, defaults to the range
+        #[arg(long)]
+        title: Option<String>,
+        /// changelog file to create or update
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Usage: git release [major|minor|patch]
+    /// Description: Finds the latest semantic version tag, computes the next version and
+    /// creates an annotated tag for it at HEAD, then prints the release notes.
+    /// Without a bump level it is inferred from the conventional commits since the last
+    /// release: breaking changes bump major, features bump minor, anything else bumps patch.
+    #[clap(name = "release")]
+    Release { bump: Option<Bump> },
+
+    /// Usage: git config [--list] [--unset] [key] [value]
+    /// Description: Get or set repository options stored in .git-rs/config,
+    /// e.g. `git-rs config commit.lint true`.
+    #[clap(name = "config")]
+    Config {
+        key: Option<String>,
+        value: Option<String>,
+        /// remove the entry
+        #[arg(long)]
+        unset: bool,
+        /// print all entries
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// Usage: git checkout -- <file>...
+    /// Description: Takes the version of the file as it exists in the head commit and puts it
+    /// in the working directory, overwriting the version of the file that's already there if
+    /// there is one. The new version of the file is not staged.
+    ///
+    /// Usage: git checkout <commit> -- <file>...
+    /// Description: Same, with the version of the file in the given commit, e.g. main~3.
+    ///
+    /// Usage: git checkout <branch>
+    /// Description: Takes all files in the commit at the head of the given branch, and puts them
+    /// in the working directory, overwriting the versions of the files that are already there.
+    /// The given branch will now be considered the current branch (HEAD). Any files that are
+    /// tracked in the current branch but are not present in the checked-out branch are deleted.
+    /// The staging area is cleared, unless the checked-out branch is the current branch.
+    /// A remote-tracking branch, <remote>/<branch> or a <branch> only one remote has, is
+    /// checked out into a new branch of the same name.
+    ///
+    /// Usage: git checkout -b <new-branch> [<start-point>]
+    /// Description: Creates a new branch at the start point, HEAD by default, and switches to
+    /// it. Started at HEAD, the working directory and the staging area are kept.
+    #[command(arg_required_else_help = true)]
+    Checkout {
+        #[arg(short = 'b', value_name = "NEW_BRANCH", conflicts_with = "files")]
+        new_branch: Option<String>,
+        branch: Option<String>,
+        #[arg(last = true)]
+        files: Vec<String>,
+    },
+
+    /// Usage: git switch <branch> | git switch -c <new-branch> [<start-point>]
+    /// Description: Switches to the branch like checkout <branch>, files are never restored.
+    /// -c creates the branch at the start point, HEAD by default, like checkout -b.
+    #[command(arg_required_else_help = true)]
+    Switch {
+        #[arg(short = 'c', long = "create", value_name = "NEW_BRANCH")]
+        create: Option<String>,
+        /// the branch, or the start point with -c
+        branch: Option<String>,
+    },
+
+    /// Usage: git restore [--staged] [--worktree] <path>...
+    /// Description: Restores files without switching branches. By default, the working
+    /// directory files come back to their staged version, or their version in HEAD.
+    /// --staged drops their staged changes instead, with --worktree too it does both.
+    #[command(arg_required_else_help = true)]
+    Restore {
+        #[arg(short = 'S', long)]
+        staged: bool,
+        #[arg(short = 'W', long)]
+        worktree: bool,
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+
+    /// Usage: git merge [-s <strategy>] [-X <option>]... <branch>... | git merge --abort
+    /// Description: Merges files from the given branch, or a remote-tracking branch such as
+    /// origin/main, into the current branch.
+    /// Several branches merging without conflicts are merged by one commit with all of them
+    /// as parents, otherwise they are merged one after the other until a conflict.
+    /// Files modified since the split point in only one of the branches take that branch's
+    /// version, when the current branch is the split point it is fast-forwarded.
+    /// -s ours|theirs takes the whole tree of one side,
+    /// -X ours|theirs|ignore-space-change resolves files changed in both branches.
+    /// --abort undoes a merge that stopped on conflicts, the working directory and the index
+    /// are restored as they were before it.
+    #[command(arg_required_else_help = true)]
+    Merge {
+        #[arg(required_unless_present = "abort")]
+        branches: Vec<String>,
+        #[arg(short = 's', long)]
+        strategy: Option<Strategy>,
+        #[arg(short = 'X', long = "strategy-option")]
+        options: Vec<StrategyOption>,
+        #[arg(long, conflicts_with_all = ["branches", "strategy", "options"])]
+        abort: bool,
+    },
+
+    /// Usage: git cherry-pick <commit>... | git cherry-pick --continue | git cherry-pick --abort
+    /// Description: Applies the change introduced by each commit on top of the current branch
+    /// as a new commit with the same message, in order. <commit> may be a range A..B, whose
+    /// commits are applied oldest first.
+    /// A commit that conflicts stops the cherry-pick: fix the files, add them and --continue,
+    /// or --abort to put the branch back as it was.
+    #[clap(name = "cherry-pick", arg_required_else_help = true)]
+    CherryPick {
+        #[arg(required_unless_present_any = ["resume", "abort"])]
+        commits: Vec<String>,
+        #[arg(long = "continue", conflicts_with_all = ["abort", "commits"])]
+        resume: bool,
+        #[arg(long, conflicts_with = "commits")]
+        abort: bool,
+    },
+
+    /// Usage: git rebase <branch> | git rebase --onto <newbase> <upstream> | git rebase -i <base>
+    /// | git rebase --continue | git rebase --abort
+    /// Description: Replays the commits of the current branch since the split point on top of
+    /// the given branch and moves the current branch to the last replayed commit.
+    /// With --onto the commits of <upstream>..HEAD are replayed on top of <newbase> instead.
+    /// With -i the commits after <base> are listed in .git-rs/rebase-todo and opened in the
+    /// editor to pick, reword, squash or drop them, the plan then runs from top to bottom.
+    /// A commit that conflicts stops the rebase: fix the files, add them and --continue.
+    /// Other steps that stop are fixed in the todo list, --abort puts the branch back as it was.
+    #[command(arg_required_else_help = true)]
+    Rebase {
+        #[arg(short, long)]
+        interactive: bool,
+        #[arg(long = "continue", conflicts_with_all = ["interactive", "abort", "branch", "onto"])]
+        resume: bool,
+        #[arg(long, conflicts_with_all = ["interactive", "branch", "onto"])]
+        abort: bool,
+        #[arg(long, value_name = "NEWBASE", conflicts_with = "interactive")]
+        onto: Option<String>,
+        #[arg(required_unless_present_any = ["resume", "abort"])]
+        branch: Option<String>,
+    },
+
+    /// Usage: git stash [push [-u] [-m <message>] | pop [<stash>] | apply [<stash>] | list
+    /// | drop [<stash>] | branch <name> [<stash>]]
+    /// Description: Saves the staged changes and the changes of tracked files in the working
+    /// directory as a new stash entry and resets both to HEAD (push, the default), with -u
+    /// the untracked files are saved and deleted too.
+    /// pop reapplies an entry on top of the current commit and drops it, apply keeps it,
+    /// list shows the entries with the newest as stash@{0}, drop discards an entry.
+    /// branch creates a branch at the commit the entry was taken on and pops it there.
+    /// <stash> defaults to stash@{0}.
+    #[clap(name = "stash")]
+    Stash {
+        action: Option<StashAction>,
+        entry: Option<String>,
+        #[arg(requires = "entry")]
+        stash: Option<String>,
+        #[arg(short, long)]
+        message: Option<String>,
+        #[arg(short = 'u', long)]
+        include_untracked: bool,
+    },
+
+    /// Usage: git clean [-n] [-d] [<path>...]
+    /// Description: Deletes the untracked files under the paths, or in the whole working
+    /// directory without paths. Tracked and staged files are never touched.
+    /// -n only lists what would be deleted, -d also deletes untracked directories.
+    #[clap(name = "clean")]
+    Clean {
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        #[arg(short = 'd')]
+        dirs: bool,
+        paths: Vec<String>,
+    },
+
+    /// Usage: git bisect start [<bad> [<good>...]] | git bisect bad [<commit>]
+    ///        git bisect good [<commit>...] | git bisect reset
+    /// Description: Finds the commit that introduced a bug by binary search. Once a bad and a
+    /// good commit are marked, the commit halfway between them is checked out into the working
+    /// directory to be tested and marked in turn, until the first bad commit is found.
+    /// bad and good mark the checked out commit by default. reset goes back to the branch head.
+    #[command(arg_required_else_help = true)]
+    Bisect {
+        action: BisectAction,
+        commits: Vec<String>,
+    },
+
+    /// Usage: git count-objects
+    /// Description: Reports the number of commits, tags, blobs and chunks, their size on disk
+    /// and how many of them are unreachable, i.e. what gc would delete.
+    #[clap(name = "count-objects")]
+    CountObjects {},
+
+    /// Usage: git verify-commit <commit>
+    /// Description: Checks the signature of a commit with gpg or, when gpg.format is ssh, with
+    /// ssh-keygen and the principals of gpg.ssh.allowedSignersFile.
+    #[clap(name = "verify-commit")]
+    VerifyCommit { commit: String },
+
+    /// Usage: git check-ignore [-v] <path>...
+    /// Description: Prints the paths that are ignored by .gitignore files or core.excludesFile.
+    /// With -v, prints the matching pattern of every path that has one as
+    /// `<file>:<line>:<pattern>\t<path>`, including `!` patterns that re-include a path.
+    #[clap(name = "check-ignore")]
+    CheckIgnore {
+        #[arg(short = 'v', long)]
+        verbose: bool,
         #[arg(required = true)]
-        name: String,
+        paths: Vec<String>,
+    },
+
+    /// Usage: git remote [-v] | git remote add <name> <url> | git remote remove <name>
+    /// Description: Lists the remotes, with -v their urls too, adds a remote, the path of
+    /// another repository, a http:// url or a git-rs:// url, or removes a remote with its
+    /// settings. Remotes are stored in the config as remote.<name>.url. https:// urls need
+    /// git-rs built with the tls feature.
+    #[clap(name = "remote")]
+    Remote {
+        action: Option<RemoteAction>,
+        name: Option<String>,
+        url: Option<String>,
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Usage: git worktree [list] | git worktree add <path> <branch> | git worktree remove [-f] <path>
+    /// Description: Lists the working trees of the repository, checks a branch out in a new
+    /// working tree at <path> sharing the repository, or deletes one. A branch can only be
+    /// checked out in one working tree, and one with local changes or untracked files is only
+    /// deleted with -f.
+    #[clap(name = "worktree")]
+    Worktree {
+        action: Option<WorktreeAction>,
+        path: Option<String>,
+        branch: Option<String>,
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Usage: git fetch [--depth <n>] <remote>
+    /// Description: Copies the commits and blobs of the remote's branches this repository
+    /// lacks and points the remote-tracking branches .git-rs/refs/remotes/<remote>/<branch>
+    /// at them. With --depth only the latest <n> commits of each branch are fetched and the
+    /// commits where history is cut are listed in .git-rs/shallow; fetching without it later
+    /// fetches the rest. Local branches, the index and the working directory are left alone.
+    /// Over http the commits to send are negotiated with the server and sent at once.
+    #[command(arg_required_else_help = true)]
+    Fetch {
+        remote: String,
+        #[arg(long)]
+        depth: Option<usize>,
+    },
+
+    /// Usage: git push [--force | --force-with-lease] <remote> [<branch>]
+    /// Description: Sends the commits of <branch>, the current branch by default, the remote
+    /// lacks and moves the remote's branch of the same name to it. The remote only moves its
+    /// branches forward and never the one it has checked out. The remote-tracking branch
+    /// <remote>/<branch> follows an accepted push.
+    /// --force overwrites the remote's branch even when the push is not a fast-forward.
+    /// --force-with-lease only does when the remote's branch is still where <remote>/<branch>
+    /// says, so that commits pushed by others since the last fetch are not lost.
+    #[command(arg_required_else_help = true)]
+    Push {
+        remote: String,
+        branch: Option<String>,
+        #[arg(short, long)]
+        force: bool,
+        #[arg(long, conflicts_with = "force")]
+        force_with_lease: bool,
+    },
+
+    /// Usage: git clone [--depth <n>] <src> [<dst>]
+    /// Description: Creates <dst>, by default named after <src>, with a copy of the git-rs
+    /// repository at <src>, a path, a http:// or a git-rs:// url: its objects, its branches as
+    /// origin/<branch> remote-tracking branches and its tags. <src> becomes the origin remote
+    /// and its current branch is checked out. With --depth only the latest <n> commits are
+    /// copied, see fetch. https:// urls need git-rs built with the tls feature.
+    #[command(arg_required_else_help = true)]
+    Clone {
+        src: String,
+        dst: Option<String>,
+        #[arg(long)]
+        depth: Option<usize>,
+    },
+
+    /// Usage: git fast-import < <stream>
+    /// Description: Reads a fast-import stream on stdin, e.g. from `git fast-export --all`,
+    /// and stores its blobs, commits and tags and points its branches and tags at them. The
+    /// working directory and the index are left alone.
+    #[clap(name = "fast-import")]
+    FastImport {},
+
+    /// Usage: git import-git <path>
+    /// Description: Imports the history of a Git repository, its work tree or its .git
+    /// directory: every branch and tag with the commits, files and annotated tags they reach,
+    /// read from loose objects and packs. The working directory and the index are left alone.
+    #[clap(name = "import-git")]
+    ImportGit { path: String },
+
+    /// Usage: git export-git
+    /// Description: Writes every branch and tag, with the commits, trees and blobs they reach,
+    /// in Git's object format into .git-rs/git, a bare repository stock git can read, e.g.
+    /// `git --git-dir=.git-rs/git log`. With core.gitObjects set, every commit is exported
+    /// as it is made.
+    #[clap(name = "export-git")]
+    ExportGit {},
+
+    /// Usage: git repack
+    /// Description: Rolls the loose blobs and the existing packs into one pack file, storing
+    /// each version of a file as a delta against its previous version when that is smaller.
+    Repack {},
+
+    /// Usage: git gc
+    /// Description: Deletes every object nothing refers to: commits not reachable from a
+    /// branch, a tag, a remote-tracking branch, the index, a stash entry or a rebase or bisect
+    /// in progress, and the blobs and chunks only such commits use, e.g. file versions added
+    /// but never committed. Moves blobs and commits of older repositories into the fan-out
+    /// layout, `blobs/ab/cdef...`.
+    Gc {},
+
+    /// Usage: git prune [-n]
+    /// Description: Deletes the unreachable objects gc deletes and prints them. With -n, only
+    /// prints them.
+    Prune {
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Usage: git fsck
+    /// Description: Verifies that every object still hashes to its name, that the parents and
+    /// files of every commit exist and that every ref points at a commit. Reports corrupt,
+    /// missing and dangling objects.
+    Fsck {},
+
+    /// Usage: git repair
+    /// Description: Detects and fixes broken repository state: stale lock files, a HEAD pointing
+    /// at a missing branch and an unreadable index. Refs pointing at missing commits are rewound
+    /// to the newest commit of their reflog that is still readable, or reported.
+    #[clap(name = "repair")]
+    Repair {},
+
+    /// Usage: git instaweb [--port <port>]
+    /// Description: Serves a read-only web interface on localhost for browsing branches,
+    /// the commit log, the files changed by each commit with their diffs and the files of
+    /// any commit.
+    #[clap(name = "instaweb")]
+    Instaweb {
+        #[arg(long, default_value_t = 1234)]
+        port: u16,
+    },
+
+    /// Usage: git serve [--api] [--host <host>] [--port <port>]
+    /// Description: Serves the repository over http, on localhost unless --host says
+    /// otherwise, e.g. 0.0.0.0 to collaborate with other machines. Other repositories fetch,
+    /// clone and push with http://<host>:<port> as the url. Pushes of any size are taken
+    /// unless receive.maxInputSize sets a limit in bytes, other requests are limited to 16 MiB.
+    /// With --api, read-only JSON endpoints are exposed for dashboards and bots instead:
+    /// /branches, /commits, /commits/<id>, /status and /blob/<id>.
+    #[clap(name = "serve")]
+    Serve {
+        #[arg(long)]
+        api: bool,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+
+    /// Usage: git daemon <dir> --export <repo>... [--host <host>] [--port <port>]
+    /// Description: Serves the repositories in <dir> named with --export, and no other, for
+    /// anonymous fetch and clone at git-rs://<host>:<port>/<repo> over a plain TCP protocol.
+    /// Nothing can be pushed. Listens on localhost unless --host says otherwise.
+    #[command(arg_required_else_help = true)]
+    Daemon {
+        dir: String,
+        #[arg(long = "export", required = true)]
+        exports: Vec<String>,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = daemon::DEFAULT_PORT)]
+        port: u16,
     },
 }
 
+/// Operation of the notes command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum NotesAction {
+    /// attach a note to a commit
+    Add,
+    /// print the note of a commit
+    Show,
+}
+
+/// Output format of `log`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Dot,
+}
+
 impl GitCommand {
     pub fn execute(self) {
         let mut repo = GitRepository::new(GIT_DIR);
@@ -136,25 +907,832 @@ impl GitCommand {
                     println!("{:?}", err);
                 }
             },
-            GitCommand::Add { paths } => match repo.add(&paths) {
+            GitCommand::Add {
+                all,
+                update,
+                interactive,
+                paths,
+            } => {
+                let res = if interactive {
+                    interactive::run(&mut repo)
+                } else if all {
+                    repo.add_all(&paths)
+                } else if update {
+                    repo.add_update(&paths)
+                } else {
+                    repo.add(&paths)
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Rm { paths } => match repo.remove(&paths) {
                 Ok(_) => {}
                 Err(err) => {
                     println!("{:?}", err);
                 }
             },
-            GitCommand::Rm { paths } => match repo.remove(&paths) {
-                Ok(_) => {}
+            GitCommand::Commit {
+                message,
+                amend,
+                author,
+                sign,
+                no_verify,
+                paths,
+            } => {
+                let options = CommitOptions {
+                    author,
+                    sign,
+                    no_verify,
+                };
+                let res = match message {
+                    _ if amend => repo.amend(message.as_deref()),
+                    Some(message) => repo.commit_with(&message, &paths, &options),
+                    None => Ok(()),
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Status {
+                porcelain,
+                json,
+                short,
+            } => {
+                let res = match (porcelain, json) {
+                    (true, _) => repo.status_porcelain().map(|lines| print!("{}", lines)),
+                    (_, true) => repo.status_json().map(|json| println!("{}", json)),
+                    _ => repo
+                        .color_enabled(io::stdout().is_terminal())
+                        .and_then(|color| match short {
+                            true => repo.status_short(&StatusOptions { color }),
+                            false => repo
+                                .status_with(&StatusOptions { color })
+                                .map(|msg| msg + "\n"),
+                        })
+                        .map(|msg| print!("{}", msg)),
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::LsFiles {
+                staged,
+                deleted,
+                others,
+                modified,
+                paths,
+            } => {
+                let options = LsFilesOptions {
+                    staged,
+                    deleted,
+                    others,
+                    modified,
+                };
+                match repo.ls_files(&options, &paths) {
+                    Ok(files) => {
+                        for file in files {
+                            println!("{}", file);
+                        }
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Log {
+                format,
+                range,
+                follow,
+                show_signature,
+                decorate,
+                stat,
+                max_count,
+                since,
+                until,
+                author,
+                grep,
+            } => {
+                let res = match format {
+                    Some(LogFormat::Dot) => repo.graph_dot(),
+                    None => repo.log_with(&LogOptions {
+                        show_signature,
+                        decorate,
+                        stat,
+                        range,
+                        follow,
+                        max_count,
+                        since,
+                        until,
+                        author,
+                        grep,
+                    }),
+                };
+                match res {
+                    Ok(msg) => {
+                        println!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Tag {
+                annotate: _,
+                message,
+                verify,
+                name,
+                commit,
+            } => {
+                let res = match name {
+                    Some(name) if verify => repo.read_tag(&name).map(|tag| match tag {
+                        Some(tag) => print!("{}", tag),
+                        None => println!("tag {} is not annotated", name),
+                    }),
+                    Some(name) => repo
+                        .create_tag(
+                            &name,
+                            commit.as_deref().unwrap_or("HEAD"),
+                            message.as_deref(),
+                        )
+                        .map(|_| ()),
+                    None => repo.tags().map(|tags| {
+                        for name in tags.keys() {
+                            println!("{}", name);
+                        }
+                    }),
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Diff {
+                staged,
+                find_copies,
+                commits,
+            } => {
+                let res = repo
+                    .color_enabled(io::stdout().is_terminal())
+                    .and_then(|color| {
+                        let options = DiffOptions {
+                            find_copies,
+                            color,
+                            ..Default::default()
+                        };
+                        match commits.as_slice() {
+                            [old, new] => repo.diff_commits_with(old, new, &options),
+                            [range] => repo.diff_range_with(range, &options),
+                            _ if staged => repo.diff_staged_with(&options),
+                            _ => repo.diff_with(&options),
+                        }
+                    });
+                match res {
+                    Ok(msg) => {
+                        print!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Show { commit } => match repo.show(&commit) {
+                Ok(msg) => {
+                    print!("{}", msg);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::GlobalLog {} => match repo.global_log() {
+                Ok(msg) => {
+                    println!("{}", msg);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Find { message } => match repo.find(&message) {
+                Ok(ids) => {
+                    println!("{}", ids.join("\n"));
+                }
+                Err(GitError::CommitError(msg)) => {
+                    println!("{}", msg);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::CatFile { type_only, object } => {
+                let res = if type_only {
+                    repo.object_type(&object).map(|t| format!("{}\n", t))
+                } else {
+                    repo.cat_file(&object)
+                };
+                match res {
+                    Ok(msg) => {
+                        print!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::HashObject { write, file } => match repo.hash_object(&file, write) {
+                Ok(hash) => {
+                    println!("{}", hash);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::RevParse { short, revs } => {
+                for rev in revs {
+                    let res = repo.resolve_commit(&rev).and_then(|sha1| {
+                        if short {
+                            repo.abbreviate(&sha1, revision::DEFAULT_ABBREV)
+                        } else {
+                            Ok(sha1)
+                        }
+                    });
+                    match res {
+                        Ok(sha1) => {
+                            println!("{}", sha1);
+                        }
+                        Err(err) => {
+                            println!("{:?}", err);
+                        }
+                    }
+                }
+            }
+            GitCommand::Describe { commit } => match repo.describe(commit.as_deref()) {
+                Ok(name) => {
+                    println!("{}", name);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Archive {
+                format,
+                output,
+                commit,
+            } => {
+                if let Err(err) = repo.archive(&commit, &output, format) {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Bundle { action, file, refs } => {
+                let res = match action {
+                    BundleAction::Create => repo.bundle_create(&file, &refs),
+                    BundleAction::Unbundle if refs.is_empty() => {
+                        repo.unbundle(&file).map(|lines| lines.join("\n"))
+                    }
+                    BundleAction::Unbundle => {
+                        Ok("usage: git-rs bundle unbundle <file>".to_string())
+                    }
+                };
+                match res {
+                    Ok(msg) if msg.is_empty() => {}
+                    Ok(msg) => {
+                        println!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::FormatPatch {
+                output_directory,
+                range,
+            } => match repo.format_patch(&range, output_directory.as_deref()) {
+                Ok(paths) => {
+                    for path in paths {
+                        println!("{}", path);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Apply { cached, patch } => {
+                if let Err(err) = repo.apply(&patch, cached) {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Am { patches } => match repo.am(&patches) {
+                Ok(subjects) => {
+                    for subject in subjects {
+                        println!("Applying: {}", subject);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Notes {
+                action,
+                message,
+                force,
+                commit,
+            } => {
+                let commit = commit.as_deref().unwrap_or("HEAD");
+                let res = match (action, message) {
+                    (NotesAction::Add, Some(message)) => repo
+                        .notes_add(commit, &message, force)
+                        .map(|_| String::new()),
+                    (NotesAction::Add, None) => {
+                        Ok("usage: git-rs notes add [-f] -m <message> [<commit>]".to_string())
+                    }
+                    (NotesAction::Show, _) => repo.notes_show(commit),
+                };
+                match res {
+                    Ok(msg) if msg.is_empty() => {}
+                    Ok(msg) => {
+                        println!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Grep { pattern, commit } => match repo.grep(&pattern, commit.as_deref()) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Blame { file } => match repo.blame(&file) {
+                Ok(msg) => {
+                    print!("{}", msg);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::FileLog { path } => match repo.file_log(&path) {
+                Ok(msg) => {
+                    println!("{}", msg);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Branch {
+                name: Some(name),
+                delete,
+                force_delete,
+                ..
+            } if delete || force_delete => match repo.branch_delete(&name, force_delete) {
+                Ok(msg) => println!("{}", msg),
                 Err(err) => {
                     println!("{:?}", err);
                 }
             },
-            GitCommand::Commit { message } => match repo.commit(message.as_str()) {
+            GitCommand::Branch {
+                name: Some(name),
+                new_name,
+                rename: true,
+                ..
+            } => {
+                let res = match new_name {
+                    Some(new_name) => repo.branch_rename(Some(&name), &new_name),
+                    None => repo.branch_rename(None, &name),
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Branch {
+                name,
+                all: _,
+                upstream: Some(upstream),
+                ..
+            } => match repo.set_upstream(&upstream, name.as_deref()) {
+                Ok(msg) => println!("{}", msg),
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Branch {
+                name: None,
+                all,
+                upstream: None,
+                ..
+            } => match repo.branch_list(all) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Branch {
+                name: Some(name),
+                all: _,
+                upstream: None,
+                ..
+            } => match repo.branch(name.as_str()) {
                 Ok(_) => {}
                 Err(err) => {
                     println!("{:?}", err);
                 }
             },
-            GitCommand::Status {} => match repo.status() {
+            GitCommand::Changelog {
+                range,
+                title,
+                output,
+            } => match repo.commit_range(&range) {
+                Ok(commits) => {
+                    let section = changelog::render(&title.unwrap_or(range), &commits);
+                    match output {
+                        Some(output) => {
+                            if let Err(err) = changelog::prepend(Path::new(&output), &section) {
+                                println!("{:?}", err);
+                            }
+                        }
+                        None => print!("{}", section),
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Release { bump } => match repo.release(bump) {
+                Ok(notes) => {
+                    print!("{}", notes);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Config {
+                key,
+                value,
+                unset,
+                list,
+            } => {
+                let res = match (key, value) {
+                    _ if list => repo.config().map(|config| {
+                        for (k, v) in config.entries() {
+                            println!("{}={}", k, v);
+                        }
+                    }),
+                    (Some(key), None) if unset => repo.unset_config(&key),
+                    (Some(key), Some(value)) => repo.set_config(&key, &value),
+                    (Some(key), None) => repo.config().map(|config| {
+                        if let Some(value) = config.get(&key) {
+                            println!("{}", value);
+                        }
+                    }),
+                    (None, _) => {
+                        println!("usage: git-rs config [--list] [--unset] <key> [value]");
+                        Ok(())
+                    }
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Checkout {
+                new_branch: Some(name),
+                branch,
+                ..
+            } => {
+                if let Err(err) = repo.checkout_create(&name, branch.as_deref()) {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Checkout {
+                new_branch: None,
+                branch,
+                files,
+            } => {
+                let res = match (branch, files.is_empty()) {
+                    (None, false) => files.iter().try_for_each(|file| repo.checkout_file(file)),
+                    (Some(branch), true) => repo.checkout_branch(&branch),
+                    (Some(rev), false) => files
+                        .iter()
+                        .try_for_each(|file| repo.checkout_file_from(&rev, file)),
+                    _ => {
+                        println!(
+                            "usage: git-rs checkout <branch> | git-rs checkout [<commit>] -- <file>..."
+                        );
+                        Ok(())
+                    }
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Switch { create, branch } => {
+                let res = match (create, branch) {
+                    (Some(name), start) => repo.checkout_create(&name, start.as_deref()),
+                    (None, Some(branch)) => repo.checkout_branch(&branch),
+                    (None, None) => {
+                        println!("usage: git-rs switch <branch> | git-rs switch -c <new-branch>");
+                        Ok(())
+                    }
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Restore {
+                staged,
+                worktree,
+                paths,
+            } => {
+                if let Err(err) = repo.restore(&paths, staged, worktree || !staged) {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Merge { abort: true, .. } => {
+                if let Err(err) = repo.merge_abort() {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Merge {
+                branches,
+                strategy,
+                options,
+                ..
+            } => {
+                let options = MergeOptions {
+                    strategy: strategy.unwrap_or_default(),
+                    options,
+                };
+                match repo.merge_branches(&branches, &options) {
+                    Ok(msg) if msg.is_empty() => {}
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::CherryPick {
+                commits,
+                resume,
+                abort,
+            } => {
+                let res = if resume {
+                    repo.cherry_pick_continue()
+                } else if abort {
+                    repo.cherry_pick_abort()
+                } else {
+                    repo.cherry_pick_revs(&commits)
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Rebase {
+                interactive,
+                resume,
+                abort,
+                onto,
+                branch,
+            } => {
+                let res = match (branch, onto) {
+                    _ if resume => repo.rebase_continue(),
+                    _ if abort => repo.rebase_abort().map(|_| String::new()),
+                    (Some(upstream), Some(newbase)) => repo.rebase_onto(&newbase, &upstream),
+                    (Some(base), None) if interactive => repo.rebase_interactive(&base),
+                    (Some(branch), None) => repo.rebase(&branch),
+                    (None, _) => Ok(String::new()),
+                };
+                match res {
+                    Ok(msg) if msg.is_empty() => {}
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Stash {
+                action,
+                entry,
+                stash,
+                message,
+                include_untracked,
+            } => {
+                let label = |entry: Option<String>| {
+                    entry
+                        .as_deref()
+                        .map(stash::parse_label)
+                        .transpose()
+                        .map(|n| n.unwrap_or(0))
+                };
+                let res = match action.unwrap_or_default() {
+                    StashAction::Push => repo.stash_push(message.as_deref(), include_untracked),
+                    StashAction::Pop => label(entry).and_then(|n| repo.stash_pop(n)),
+                    StashAction::Apply => label(entry).and_then(|n| repo.stash_apply(n)),
+                    StashAction::List => repo.stash_list().map(|list| list.join("\n")),
+                    StashAction::Drop => label(entry).and_then(|n| repo.stash_drop(n)),
+                    StashAction::Branch => match entry {
+                        Some(name) => label(stash).and_then(|n| repo.stash_branch(&name, n)),
+                        None => Err(GitError::StashError(
+                            "usage: git-rs stash branch <name> [<stash>]".to_string(),
+                        )),
+                    },
+                };
+                match res {
+                    Ok(msg) if msg.is_empty() => {}
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Clean {
+                dry_run,
+                dirs,
+                paths,
+            } => match repo.clean(&paths, dry_run, dirs) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Bisect { action, commits } => {
+                let res = match action {
+                    BisectAction::Start => repo.bisect_start(
+                        commits.first().map(|c| c.as_str()),
+                        commits.get(1..).unwrap_or_default(),
+                    ),
+                    BisectAction::Bad => repo.bisect_mark(&commits, true),
+                    BisectAction::Good => repo.bisect_mark(&commits, false),
+                    BisectAction::Reset => repo.bisect_reset(),
+                };
+                match res {
+                    Ok(msg) => println!("{}", msg),
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::VerifyCommit { commit } => match repo.verify_commit(&commit) {
+                Ok(good) => {
+                    println!("{}", good);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::CheckIgnore { verbose, paths } => match repo.check_ignore(&paths) {
+                Ok(matched) => {
+                    for (path, rule) in matched {
+                        if verbose {
+                            println!("{}:{}:{}\t{}", rule.source, rule.line, rule.pattern, path);
+                        } else if !rule.negated {
+                            println!("{}", path);
+                        }
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::CountObjects {} => match repo.count_objects() {
+                Ok(counts) => {
+                    println!("commits: {}", counts.commits);
+                    println!("tags: {}", counts.tags);
+                    println!("blobs: {}", counts.blobs);
+                    println!("chunks: {}", counts.chunks);
+                    println!("in-pack: {}", counts.in_pack);
+                    println!("packs: {}", counts.packs);
+                    println!("size: {} bytes", counts.size);
+                    println!("unreachable: {}", counts.unreachable);
+                    println!("size-unreachable: {} bytes", counts.unreachable_size);
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Remote {
+                action,
+                name,
+                url,
+                verbose,
+            } => {
+                let res = match (action.unwrap_or_default(), name, url) {
+                    (RemoteAction::List, None, None) => repo.remotes().map(|remotes| {
+                        for (name, url) in remotes {
+                            if verbose {
+                                println!("{}\t{} (fetch)", name, url);
+                                println!("{}\t{} (push)", name, url);
+                            } else {
+                                println!("{}", name);
+                            }
+                        }
+                    }),
+                    (RemoteAction::Add, Some(name), Some(url)) => repo.remote_add(&name, &url),
+                    (RemoteAction::Remove, Some(name), None) => repo.remote_remove(&name),
+                    _ => {
+                        println!("usage: git-rs remote [-v] | add <name> <url> | remove <name>");
+                        Ok(())
+                    }
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Worktree {
+                action,
+                path,
+                branch,
+                force,
+            } => {
+                let res = match (action.unwrap_or_default(), path, branch) {
+                    (WorktreeAction::List, None, None) => repo.worktree_list(),
+                    (WorktreeAction::Add, Some(path), Some(branch)) => {
+                        repo.worktree_add(&path, &branch).map(|msg| vec![msg])
+                    }
+                    (WorktreeAction::Remove, Some(path), None) => {
+                        repo.worktree_remove(&path, force).map(|_| vec![])
+                    }
+                    _ => Ok(vec![
+                        "usage: git-rs worktree [list] | add <path> <branch> | remove [-f] <path>"
+                            .to_string(),
+                    ]),
+                };
+                match res {
+                    Ok(lines) => {
+                        for line in lines {
+                            println!("{}", line);
+                        }
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Fetch { remote, depth } => match repo.fetch(&remote, depth) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Push {
+                remote,
+                branch,
+                force,
+                force_with_lease,
+            } => {
+                let options = PushOptions {
+                    force,
+                    force_with_lease,
+                };
+                match repo.push_with(&remote, branch.as_deref(), &options) {
+                    Ok(lines) => {
+                        for line in lines {
+                            println!("{}", line);
+                        }
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::Clone { src, dst, depth } => {
+                match GitRepository::clone_repo(&src, dst.as_deref(), depth) {
+                    Ok(msg) => {
+                        println!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::FastImport {} => {
+                let mut input = vec![];
+                let res = io::stdin()
+                    .read_to_end(&mut input)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+                    .and_then(|_| repo.fast_import(&input));
+                match res {
+                    Ok(msg) => {
+                        println!("{}", msg);
+                    }
+                    Err(err) => {
+                        println!("{:?}", err);
+                    }
+                }
+            }
+            GitCommand::ImportGit { path } => match repo.import_git(&path) {
                 Ok(msg) => {
                     println!("{}", msg);
                 }
@@ -162,7 +1740,17 @@ impl GitCommand {
                     println!("{:?}", err);
                 }
             },
-            GitCommand::Log {} => match repo.log() {
+            GitCommand::ExportGit {} => match repo.export_git() {
+                Ok(refs) => {
+                    for (name, id) in refs {
+                        println!("{} {}", id, name);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Repack {} => match repo.repack() {
                 Ok(msg) => {
                     println!("{}", msg);
                 }
@@ -170,12 +1758,91 @@ impl GitCommand {
                     println!("{:?}", err);
                 }
             },
-            GitCommand::Branch { name } => match repo.branch(name.as_str()) {
-                Ok(_) => {}
+            GitCommand::Gc {} => match repo.gc() {
+                Ok(msg) => {
+                    println!("{}", msg);
+                }
                 Err(err) => {
                     println!("{:?}", err);
                 }
             },
+            GitCommand::Prune { dry_run } => match repo.prune(dry_run) {
+                Ok(lines) => {
+                    for line in lines {
+                        println!("{}", line);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Fsck {} => match repo.fsck() {
+                Ok(problems) => {
+                    for problem in problems {
+                        println!("{}", problem);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Repair {} => match repo.repair() {
+                Ok(actions) if actions.is_empty() => println!("Nothing to repair."),
+                Ok(actions) => {
+                    for action in actions {
+                        println!("{}", action);
+                    }
+                }
+                Err(err) => {
+                    println!("{:?}", err);
+                }
+            },
+            GitCommand::Instaweb { port } => {
+                let addr = format!("127.0.0.1:{}", port);
+                println!("Serving repository on http://{}", addr);
+                if let Err(err) = http::serve(&addr, |request| web::handle(&mut repo, request)) {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Serve { api, host, port } => {
+                let addr = format!("{}:{}", host, port);
+                let res = if api {
+                    println!("Serving JSON api on http://{}", addr);
+                    http::serve(&addr, |request| api::handle(&mut repo, request))
+                } else {
+                    println!("Serving repository on http://{}", addr);
+                    repo.max_input_size().and_then(|max_input| {
+                        http::serve_with(
+                            &addr,
+                            |request| transport::body_limit(request, max_input),
+                            |request| transport::handle(&mut repo, request),
+                        )
+                    })
+                };
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+            GitCommand::Daemon {
+                dir,
+                exports,
+                host,
+                port,
+            } => {
+                let addr = format!("{}:{}", host, port);
+                let res = Daemon::new(Path::new(&dir), &exports).and_then(|daemon| {
+                    for name in exports.iter() {
+                        println!("Serving {} at {}{}/{}", name, daemon::SCHEME, addr, name);
+                    }
+                    daemon.serve(&addr)
+                });
+                if let Err(err) = res {
+                    println!("{:?}", err);
+                }
+            }
+        }
+        for warning in repo.take_warnings() {
+            eprintln!("warning: {}", warning);
         }
     }
 }
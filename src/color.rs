@@ -0,0 +1,100 @@
+use crate::error::GitError;
+
+/// When output is colored, from the `color.ui` setting
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// only when writing to a terminal, `auto` or `true`, the default
+    #[default]
+    Auto,
+    /// `always`
+    Always,
+    /// `never` or `false`
+    Never,
+}
+
+impl ColorMode {
+    pub fn parse(value: &str) -> Result<Self, GitError> {
+        match value.to_lowercase().as_str() {
+            "auto" | "true" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" | "false" => Ok(Self::Never),
+            _ => Err(GitError::ConfigError(format!(
+                "invalid color.ui value {}",
+                value
+            ))),
+        }
+    }
+
+    /// whether to color output written to a terminal or not
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        match self {
+            Self::Auto => is_terminal,
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/// ANSI colors of the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Red,
+    Green,
+    Cyan,
+    Bold,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Self::Red => "\x1b[31m",
+            Self::Green => "\x1b[32m",
+            Self::Cyan => "\x1b[36m",
+            Self::Bold => "\x1b[1m",
+        }
+    }
+}
+
+const RESET: &str = "\x1b[m";
+
+/// Colors pieces of output, or leaves them as they are when color is off, the default
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    enabled: bool,
+}
+
+impl Palette {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// text wrapped in the escape codes of the color, empty text stays empty
+    pub fn paint(&self, color: Color, text: &str) -> String {
+        match self.enabled && !text.is_empty() {
+            true => format!("{}{}{}", color.code(), text, RESET),
+            false => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_ut() {
+        assert_eq!(ColorMode::Auto, ColorMode::parse("true").unwrap());
+        assert_eq!(ColorMode::Always, ColorMode::parse("Always").unwrap());
+        assert_eq!(ColorMode::Never, ColorMode::parse("false").unwrap());
+        assert!(ColorMode::parse("sometimes").is_err());
+        assert!(ColorMode::Auto.enabled(true) && !ColorMode::Auto.enabled(false));
+        assert!(ColorMode::Always.enabled(false) && !ColorMode::Never.enabled(true));
+
+        assert_eq!(
+            "\x1b[32mf1\x1b[m",
+            Palette::new(true).paint(Color::Green, "f1")
+        );
+        assert_eq!("", Palette::new(true).paint(Color::Red, ""));
+        assert_eq!("f1", Palette::default().paint(Color::Green, "f1"));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::Config;
+use crate::error::GitError;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::io::{self, Cursor, ErrorKind, Read, Write};
+
+/// leading bytes of a zlib compressed object file, the zlib stream follows
+const MAGIC: &[u8] = b"\0zlib\n";
+/// leading bytes of a zstd compressed object file, as long as `MAGIC`
+const ZSTD_MAGIC: &[u8] = b"\0zstd\n";
+
+/// How object files are written, from `core.compression` and `core.compressionAlgorithm`,
+/// zlib or zstd, zlib by default:
+/// ```text
+/// unset or 0   stored as they are
+/// 1 to 9       zlib, 1 is the fastest and 9 the smallest
+/// 1 to 22      zstd, 1 is the fastest and 22 the smallest
+/// -1           the default level of the algorithm
+/// ```
+/// Reading does not depend on the config, compressed files start with a magic header naming
+/// the algorithm and anything else is read as it is, so repositories can change the settings
+/// at any time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Zlib(u32),
+    Zstd(i32),
+}
+
+impl Compression {
+    pub fn from_config(config: &Config) -> Result<Self, GitError> {
+        let zstd = match config.get("core.compressionAlgorithm").map(str::trim) {
+            None | Some("zlib") => false,
+            Some("zstd") => true,
+            Some(value) => {
+                return Err(GitError::ConfigError(format!(
+                    "bad core.compressionAlgorithm value {}",
+                    value
+                )))
+            }
+        };
+        match (
+            zstd,
+            config
+                .get("core.compression")
+                .map(|v| v.trim().parse::<i32>()),
+        ) {
+            (_, None) | (_, Some(Ok(0))) => Ok(Self::None),
+            (false, Some(Ok(-1))) => Ok(Self::Zlib(flate2::Compression::default().level())),
+            (false, Some(Ok(level @ 1..=9))) => Ok(Self::Zlib(level as u32)),
+            (true, Some(Ok(-1))) => Ok(Self::Zstd(zstd::DEFAULT_COMPRESSION_LEVEL)),
+            (true, Some(Ok(level @ 1..=22))) => Ok(Self::Zstd(level)),
+            _ => Err(GitError::ConfigError(format!(
+                "bad core.compression value {}",
+                config.get("core.compression").unwrap_or_default()
+            ))),
+        }
+    }
+
+    /// Copy everything the reader yields into `out` as an object file. Content that happens
+    /// to start with the magic header is compressed even when compression is off, so reading
+    /// it back is never ambiguous.
+    pub fn copy<R: Read, W: Write>(&self, mut reader: R, mut out: W) -> io::Result<()> {
+        let head = read_head(&mut reader)?;
+        let level = match self {
+            Self::None if head != MAGIC && head != ZSTD_MAGIC => {
+                out.write_all(&head)?;
+                io::copy(&mut reader, &mut out)?;
+                return out.flush();
+            }
+            Self::None => flate2::Compression::default().level(),
+            Self::Zlib(level) => *level,
+            Self::Zstd(level) => {
+                out.write_all(ZSTD_MAGIC)?;
+                let mut encoder = zstd::Encoder::new(out, *level)?;
+                encoder.write_all(&head)?;
+                io::copy(&mut reader, &mut encoder)?;
+                return encoder.finish()?.flush();
+            }
+        };
+        out.write_all(MAGIC)?;
+        let mut encoder = ZlibEncoder::new(out, flate2::Compression::new(level));
+        encoder.write_all(&head)?;
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?.flush()
+    }
+
+    /// content as an object file holds it
+    pub fn encode(&self, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        // writing into memory does not fail
+        let _ = self.copy(content, &mut out);
+        out
+    }
+}
+
+/// the first bytes of the reader, as many as the magic header unless it ends before
+fn read_head<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut head = vec![0; MAGIC.len()];
+    let mut len = 0;
+    while len < head.len() {
+        match reader.read(&mut head[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    head.truncate(len);
+    Ok(head)
+}
+
+/// reader of the content an object file holds, decompressed when it was compressed
+pub fn reader<'a, R: Read + 'a>(mut input: R) -> io::Result<Box<dyn Read + 'a>> {
+    let head = read_head(&mut input)?;
+    if head == MAGIC {
+        Ok(Box::new(ZlibDecoder::new(input)))
+    } else if head == ZSTD_MAGIC {
+        Ok(Box::new(zstd::Decoder::new(input)?))
+    } else {
+        Ok(Box::new(Cursor::new(head).chain(input)))
+    }
+}
+
+/// content an object file holds
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, GitError> {
+    let mut content = vec![];
+    reader(data)
+        .and_then(|mut reader| reader.read_to_end(&mut content))
+        .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_ut() {
+        let text = "line of text\n".repeat(1000).into_bytes();
+        let zlib = Compression::Zlib(6);
+        let compressed = zlib.encode(&text);
+        assert!(compressed.starts_with(MAGIC));
+        assert!(compressed.len() < text.len() / 10);
+        assert_eq!(text, decode(&compressed).unwrap());
+
+        assert_eq!(text, Compression::None.encode(&text));
+        assert_eq!(text, decode(&text).unwrap());
+        assert_eq!(b"\0z".to_vec(), decode(b"\0z").unwrap());
+        assert!(decode(b"").unwrap().is_empty());
+        assert!(decode(&zlib.encode(b"")).unwrap().is_empty());
+
+        // content looking like a compressed file is compressed anyway
+        let tricky = [MAGIC, b"raw"].concat();
+        assert_ne!(tricky, Compression::None.encode(&tricky));
+        assert_eq!(tricky, decode(&Compression::None.encode(&tricky)).unwrap());
+
+        let mut corrupt = compressed.clone();
+        corrupt.truncate(compressed.len() / 2);
+        assert!(decode(&corrupt).is_err());
+
+        let zstd = Compression::Zstd(3);
+        let compressed = zstd.encode(&text);
+        assert!(compressed.starts_with(ZSTD_MAGIC));
+        assert!(compressed.len() < text.len() / 10);
+        assert_eq!(text, decode(&compressed).unwrap());
+        assert!(decode(&zstd.encode(b"")).unwrap().is_empty());
+        let tricky = [ZSTD_MAGIC, b"raw"].concat();
+        assert_eq!(tricky, decode(&Compression::None.encode(&tricky)).unwrap());
+        assert!(decode(&compressed[..compressed.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn from_config_ut() {
+        let mut config = Config::new();
+        assert_eq!(
+            Compression::None,
+            Compression::from_config(&config).unwrap()
+        );
+        for (value, expected) in [
+            ("0", Compression::None),
+            ("1", Compression::Zlib(1)),
+            ("9", Compression::Zlib(9)),
+            ("-1", Compression::Zlib(6)),
+        ] {
+            assert!(config.set("core.compression", value).is_ok());
+            assert_eq!(expected, Compression::from_config(&config).unwrap());
+        }
+        for value in ["10", "-2", "zstd"] {
+            assert!(config.set("core.compression", value).is_ok());
+            assert!(Compression::from_config(&config).is_err());
+        }
+
+        assert!(config.set("core.compressionAlgorithm", "zstd").is_ok());
+        for (value, expected) in [
+            ("0", Compression::None),
+            ("1", Compression::Zstd(1)),
+            ("22", Compression::Zstd(22)),
+            ("-1", Compression::Zstd(3)),
+        ] {
+            assert!(config.set("core.compression", value).is_ok());
+            assert_eq!(expected, Compression::from_config(&config).unwrap());
+        }
+        assert!(config.set("core.compression", "23").is_ok());
+        assert!(Compression::from_config(&config).is_err());
+        assert!(config.set("core.compressionAlgorithm", "lz4").is_ok());
+        assert!(Compression::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::error::GitError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// git style configuration file
+/// ```text
+/// [commit]
+///     lint = true
+/// [remote "origin"]
+///     url = ../other
+/// ```
+/// entries are addressed by dotted keys like `commit.lint` or `remote.origin.url`.
+/// section and variable names are case-insensitive, subsection names are not.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Config {
+    entries: BTreeMap<String, String>,
+}
+
+/// normalize a dotted key: lowercase section and variable name, keep subsection
+fn normalize_key(key: &str) -> Result<String, GitError> {
+    let (section, rest) = key
+        .split_once('.')
+        .ok_or_else(|| GitError::ConfigError(format!("key does not contain a section: {}", key)))?;
+    let (subsection, name) = match rest.rsplit_once('.') {
+        Some((subsection, name)) => (Some(subsection), name),
+        None => (None, rest),
+    };
+    if section.is_empty() || name.is_empty() {
+        return Err(GitError::ConfigError(format!("invalid key: {}", key)));
+    }
+    Ok(match subsection {
+        Some(subsection) => format!(
+            "{}.{}.{}",
+            section.to_lowercase(),
+            subsection,
+            name.to_lowercase()
+        ),
+        None => format!("{}.{}", section.to_lowercase(), name.to_lowercase()),
+    })
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// parse config file content
+    pub fn parse(content: &str) -> Result<Self, GitError> {
+        let mut config = Self::new();
+        let mut section: Option<String> = None;
+        for (no, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header.strip_suffix(']').ok_or_else(|| {
+                    GitError::ConfigError(format!("bad section header at line {}", no + 1))
+                })?;
+                section = Some(match header.split_once(' ') {
+                    Some((name, sub)) => format!(
+                        "{}.{}",
+                        name.trim().to_lowercase(),
+                        sub.trim().trim_matches('"')
+                    ),
+                    None => header.trim().to_lowercase(),
+                });
+                continue;
+            }
+            let section = section.as_ref().ok_or_else(|| {
+                GitError::ConfigError(format!("entry outside of a section at line {}", no + 1))
+            })?;
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim().trim_matches('"')),
+                // a bare variable is a boolean true
+                None => (line, "true"),
+            };
+            config.entries.insert(
+                format!("{}.{}", section, name.to_lowercase()),
+                value.to_string(),
+            );
+        }
+        Ok(config)
+    }
+
+    /// load config from file, a missing file is an empty config
+    pub fn load(path: &Path) -> Result<Self, GitError> {
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        let content =
+            fs::read_to_string(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Self::parse(&content)
+    }
+
+    /// write config to file
+    pub fn save(&self, path: &Path) -> Result<(), GitError> {
+        fs::write(path, self.to_string()).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        normalize_key(key)
+            .ok()
+            .and_then(|key| self.entries.get(&key))
+            .map(|v| v.as_str())
+    }
+
+    /// boolean value, accepts true/false, yes/no, on/off and 1/0
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, GitError> {
+        match self.get(key).map(|v| v.to_lowercase()) {
+            None => Ok(None),
+            Some(v) => match v.as_str() {
+                "true" | "yes" | "on" | "1" => Ok(Some(true)),
+                "false" | "no" | "off" | "0" => Ok(Some(false)),
+                _ => Err(GitError::ConfigError(format!(
+                    "bad boolean value {} for {}",
+                    v, key
+                ))),
+            },
+        }
+    }
+
+    /// unsigned integer value
+    pub fn get_usize(&self, key: &str) -> Result<Option<usize>, GitError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(v) => v
+                .parse::<usize>()
+                .map(Some)
+                .map_err(|_| GitError::ConfigError(format!("bad numeric value {} for {}", v, key))),
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), GitError> {
+        self.entries.insert(normalize_key(key)?, value.to_string());
+        Ok(())
+    }
+
+    /// remove an entry, returns the removed value
+    pub fn unset(&mut self, key: &str) -> Result<Option<String>, GitError> {
+        Ok(self.entries.remove(&normalize_key(key)?))
+    }
+
+    /// all entries as dotted key --> value pairs
+    pub fn entries(&self) -> &BTreeMap<String, String> {
+        &self.entries
+    }
+}
+
+/// serialize grouped by section, e.g.
+/// [commit]
+///     lint = true
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut current: Option<&str> = None;
+        for (key, value) in self.entries.iter() {
+            let (section, name) = key.rsplit_once('.').unwrap();
+            if current != Some(section) {
+                match section.split_once('.') {
+                    Some((name, sub)) => writeln!(f, "[{} \"{}\"]", name, sub)?,
+                    None => writeln!(f, "[{}]", section)?,
+                }
+                current = Some(section);
+            }
+            writeln!(f, "\t{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_ut() {
+        let config = Config::parse(
+            r#"
+# comment
+[commit]
+    lint = true
+[Lint]
+    subjectMaxLength = 50
+    types = feat, fix
+[remote "Origin"]
+    url = "../other repo"
+[core]
+    bare
+"#,
+        )
+        .unwrap();
+        assert_eq!(Some(true), config.get_bool("commit.lint").unwrap());
+        assert_eq!(Some(50), config.get_usize("lint.subjectmaxlength").unwrap());
+        assert_eq!(Some(50), config.get_usize("LINT.subjectMaxLength").unwrap());
+        assert_eq!(Some("feat, fix"), config.get("lint.types"));
+        assert_eq!(Some("../other repo"), config.get("remote.Origin.url"));
+        assert_eq!(None, config.get("remote.origin.url"));
+        assert_eq!(Some(true), config.get_bool("core.bare").unwrap());
+        assert_eq!(None, config.get_bool("core.missing").unwrap());
+        assert!(config.get_usize("lint.types").is_err());
+    }
+
+    #[test]
+    fn parse_config_error_ut() {
+        assert!(Config::parse("lint = true").is_err());
+        assert!(Config::parse("[commit\nlint = true").is_err());
+    }
+
+    #[test]
+    fn set_unset_display_ut() {
+        let mut config = Config::new();
+        assert!(config.set("commit.lint", "true").is_ok());
+        assert!(config.set("remote.origin.url", "../other").is_ok());
+        assert!(config.set("Core.ExcludesFile", "~/.ignore").is_ok());
+        assert!(config.set("lint", "true").is_err());
+        assert_eq!(
+            "[commit]\n\tlint = true\n[core]\n\texcludesfile = ~/.ignore\n[remote \"origin\"]\n\turl = ../other\n",
+            config.to_string()
+        );
+        assert_eq!(config, Config::parse(&config.to_string()).unwrap());
+        assert_eq!(
+            Some("true".to_string()),
+            config.unset("commit.lint").unwrap()
+        );
+        assert_eq!(None, config.get("commit.lint"));
+    }
+}
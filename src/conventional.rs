@@ -0,0 +1,220 @@
+use crate::config::Config;
+use crate::error::GitError;
+
+/// default conventional commit types
+const DEFAULT_TYPES: [&str; 11] = [
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+/// default maximum length of the subject line
+const DEFAULT_SUBJECT_MAX_LENGTH: usize = 72;
+/// default maximum length of a body line
+const DEFAULT_BODY_MAX_LINE_LENGTH: usize = 100;
+
+/// A commit message following the conventional commits format
+/// ```text
+/// type(scope)!: description
+///
+/// body
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConventionalMessage {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub description: String,
+    pub body: String,
+}
+
+impl ConventionalMessage {
+    /// parse a commit message, None if the subject line is not conventional
+    pub fn parse(msg: &str) -> Option<Self> {
+        let (subject, body) = match msg.split_once('\n') {
+            Some((subject, body)) => (subject, body.trim()),
+            None => (msg, ""),
+        };
+        let (header, description) = subject.split_once(':')?;
+        let (header, breaking) = match header.strip_suffix('!') {
+            Some(header) => (header, true),
+            None => (header, false),
+        };
+        let (kind, scope) = match header.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?.to_string())),
+            None => (header, None),
+        };
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return None;
+        }
+        if scope.as_ref().is_some_and(|s| s.is_empty()) {
+            return None;
+        }
+        Some(Self {
+            kind: kind.to_lowercase(),
+            scope,
+            breaking: breaking || body.contains("BREAKING CHANGE:"),
+            description: description.trim().to_string(),
+            body: body.to_string(),
+        })
+    }
+}
+
+/// Rules checked by `commit.lint`, configured by
+/// lint.types               comma separated allowed types
+/// lint.subjectMaxLength    maximum subject line length
+/// lint.bodyMaxLineLength   maximum body line length
+#[derive(Debug, PartialEq, Clone)]
+pub struct LintRules {
+    pub types: Vec<String>,
+    pub subject_max_length: usize,
+    pub body_max_line_length: usize,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        Self {
+            types: DEFAULT_TYPES.iter().map(|t| t.to_string()).collect(),
+            subject_max_length: DEFAULT_SUBJECT_MAX_LENGTH,
+            body_max_line_length: DEFAULT_BODY_MAX_LINE_LENGTH,
+        }
+    }
+}
+
+impl LintRules {
+    pub fn from_config(config: &Config) -> Result<Self, GitError> {
+        let mut rules = Self::default();
+        if let Some(types) = config.get("lint.types") {
+            rules.types = types
+                .split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect();
+        }
+        if let Some(len) = config.get_usize("lint.subjectMaxLength")? {
+            rules.subject_max_length = len;
+        }
+        if let Some(len) = config.get_usize("lint.bodyMaxLineLength")? {
+            rules.body_max_line_length = len;
+        }
+        Ok(rules)
+    }
+
+    /// check a commit message, returns every violated rule
+    pub fn check(&self, msg: &str) -> Vec<String> {
+        let mut violations = vec![];
+        let lines: Vec<&str> = msg.lines().collect();
+        let subject = lines.first().copied().unwrap_or("");
+        if subject.trim().is_empty() {
+            return vec!["subject line is empty".to_string()];
+        }
+        match ConventionalMessage::parse(msg) {
+            None => violations.push(format!(
+                "subject \"{}\" does not match \"type(scope): description\"",
+                subject
+            )),
+            Some(parsed) => {
+                if !self.types.contains(&parsed.kind) {
+                    violations.push(format!(
+                        "type \"{}\" is not one of {}",
+                        parsed.kind,
+                        self.types.join(", ")
+                    ));
+                }
+                if parsed.description.is_empty() {
+                    violations.push("description after \":\" is empty".to_string());
+                }
+            }
+        }
+        let len = subject.chars().count();
+        if len > self.subject_max_length {
+            violations.push(format!(
+                "subject is {} characters long, the limit is {}",
+                len, self.subject_max_length
+            ));
+        }
+        if lines.len() > 1 && !lines[1].trim().is_empty() {
+            violations.push("line 2 must be blank to separate subject and body".to_string());
+        }
+        for (no, line) in lines.iter().enumerate().skip(1) {
+            let len = line.chars().count();
+            if len > self.body_max_line_length {
+                violations.push(format!(
+                    "line {} is {} characters long, the limit is {}",
+                    no + 1,
+                    len,
+                    self.body_max_line_length
+                ));
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_conventional_message_ut() {
+        assert_eq!(
+            Some(ConventionalMessage {
+                kind: "feat".to_string(),
+                scope: Some("repo".to_string()),
+                breaking: true,
+                description: "add log".to_string(),
+                body: "details".to_string(),
+            }),
+            ConventionalMessage::parse("feat(repo)!: add log\n\ndetails")
+        );
+        let msg = ConventionalMessage::parse("fix: typo\n\nBREAKING CHANGE: x").unwrap();
+        assert_eq!("fix", msg.kind);
+        assert_eq!(None, msg.scope);
+        assert!(msg.breaking);
+        assert_eq!(None, ConventionalMessage::parse("initial commit"));
+        assert_eq!(None, ConventionalMessage::parse("fix(): empty scope"));
+        assert_eq!(None, ConventionalMessage::parse("a b: not a type"));
+    }
+
+    #[test]
+    fn lint_rules_ut() {
+        let rules = LintRules::default();
+        assert!(rules.check("feat: add lint").is_empty());
+        assert!(rules.check("fix(cmd): x\n\nbody line").is_empty());
+        assert_eq!(vec!["subject line is empty"], rules.check(""));
+        assert_eq!(
+            vec!["subject \"add lint\" does not match \"type(scope): description\""],
+            rules.check("add lint")
+        );
+        assert_eq!(
+            vec![
+                "type \"wip\" is not one of feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert"
+            ],
+            rules.check("wip: add lint")
+        );
+        assert_eq!(
+            vec!["line 2 must be blank to separate subject and body"],
+            rules.check("feat: x\nbody")
+        );
+        let long = "x".repeat(101);
+        assert_eq!(
+            vec!["line 3 is 101 characters long, the limit is 100"],
+            rules.check(&format!("feat: x\n\n{}", long))
+        );
+    }
+
+    #[test]
+    fn lint_rules_from_config_ut() {
+        let config = Config::parse(
+            "[lint]\ntypes = feat, Fix\nsubjectMaxLength = 10\nbodyMaxLineLength = 5\n",
+        )
+        .unwrap();
+        let rules = LintRules::from_config(&config).unwrap();
+        assert_eq!(vec!["feat", "fix"], rules.types);
+        assert_eq!(
+            vec![
+                "type \"docs\" is not one of feat, fix",
+                "subject is 15 characters long, the limit is 10",
+                "line 3 is 6 characters long, the limit is 5"
+            ],
+            rules.check("docs: too long.\n\nbody..")
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use crate::error::GitError;
+use crate::http::{Request, Response};
+use crate::repo::GitRepository;
+use crate::transport;
+use log::info;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// scheme of the urls of repositories a daemon serves, `git-rs://host[:port]/<repo>`
+pub const SCHEME: &str = "git-rs://";
+pub const DEFAULT_PORT: u16 = 9418;
+/// largest request body a daemon reads, fetch requests are small, answers are not limited
+const MAX_REQUEST_LEN: usize = 1 << 24;
+/// how long a connection may stall a read or a write before it is dropped, connections are
+/// served one after the other and a silent client would block all the others
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// whether the url points at a repository served by a daemon
+pub fn is_daemon_url(url: &str) -> bool {
+    url.starts_with(SCHEME)
+}
+
+/// Read-only server of some of the repositories under a directory: the exported ones, by
+/// the name of their directory. Each connection carries one request of the fetch protocol,
+/// see `transport::handle`, framed as `<method> /<repo>/<path> <body length>\n<body>` and
+/// answered with `<status> <body length>\n<body>`.
+#[derive(Debug)]
+pub struct Daemon {
+    base: PathBuf,
+    exports: BTreeSet<String>,
+}
+
+impl Daemon {
+    /// export the repositories in `base` named in `exports`, each must exist
+    pub fn new(base: &Path, exports: &[String]) -> Result<Self, GitError> {
+        for name in exports {
+            let valid = !name.is_empty() && !name.contains('/') && !name.starts_with('.');
+            if !valid || GitRepository::open_dir(&base.join(name)).is_err() {
+                return Err(GitError::RemoteError(format!(
+                    "{} is not a git-rs repository in {}",
+                    name,
+                    base.display()
+                )));
+            }
+        }
+        Ok(Self {
+            base: base.to_path_buf(),
+            exports: exports.iter().cloned().collect(),
+        })
+    }
+
+    /// Answer a request for an exported repository. Nothing can be pushed and repositories
+    /// that are not exported do not exist.
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments = request.segments();
+        let Some((name, rest)) = segments.split_first() else {
+            return Response::not_found();
+        };
+        if !self.exports.contains(*name) {
+            return Response::not_found();
+        }
+        if request.method != "GET" && rest != ["upload"] {
+            return Response::text(405, "read-only");
+        }
+        let mut repo = match GitRepository::open_dir(&self.base.join(name)) {
+            Ok(repo) => repo,
+            Err(e) => return Response::text(500, &e.to_string()),
+        };
+        let request = Request {
+            method: request.method.clone(),
+            path: format!("/{}", rest.join("/")),
+            query: BTreeMap::new(),
+            headers: BTreeMap::new(),
+            body: request.body.clone(),
+        };
+        transport::handle(&mut repo, &request)
+    }
+
+    /// serve requests one at a time on the given address until the process exits
+    pub fn serve(&self, addr: &str) -> Result<(), GitError> {
+        let listener =
+            TcpListener::bind(addr).map_err(|e| GitError::RemoteError(format!("{:?}", e)))?;
+        self.serve_on(listener)
+    }
+
+    /// serve requests one at a time on a bound listener until the process exits
+    pub fn serve_on(&self, listener: TcpListener) -> Result<(), GitError> {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    info!("accept failed: {:?}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = stream
+                .set_read_timeout(Some(TIMEOUT))
+                .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+            {
+                info!("setting timeouts failed: {:?}", e);
+                continue;
+            }
+            let response = match read_request(&mut BufReader::new(&stream)) {
+                Ok(request) => self.handle(&request),
+                Err(response) => response,
+            };
+            if let Err(e) = write_message(&mut stream, &response.status.to_string(), &response.body)
+            {
+                info!("write failed: {:?}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// first line of a message, a word and the length of the body that follows
+fn read_head<R: BufRead>(reader: &mut R) -> Result<(String, usize), GitError> {
+    let mut line = String::new();
+    reader
+        .by_ref()
+        .take(1024)
+        .read_line(&mut line)
+        .map_err(|e| GitError::RemoteError(format!("{:?}", e)))?;
+    let bad = || GitError::RemoteError(format!("bad message {:?}", line));
+    let (head, len) = line.trim_end().rsplit_once(' ').ok_or_else(bad)?;
+    let len = len.parse::<usize>().map_err(|_| bad())?;
+    Ok((head.to_string(), len))
+}
+
+/// the body of `len` bytes following the head of a message
+fn read_body<R: Read>(reader: &mut R, len: usize) -> Result<Vec<u8>, GitError> {
+    let mut body = vec![0; len];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| GitError::RemoteError(format!("{:?}", e)))?;
+    Ok(body)
+}
+
+fn write_message<W: Write>(writer: &mut W, head: &str, body: &[u8]) -> Result<(), GitError> {
+    writeln!(writer, "{} {}", head, body.len())
+        .and_then(|_| writer.write_all(body))
+        .and_then(|_| writer.flush())
+        .map_err(|e| GitError::RemoteError(format!("{:?}", e)))
+}
+
+/// A request, or the answer to a message that is not one: 400 when it is malformed, 413 when
+/// its body is longer than `MAX_REQUEST_LEN`, the body is then read and discarded so the
+/// client reads the answer
+fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, Response> {
+    let bad = |e: GitError| Response::text(400, &e.to_string());
+    let (head, len) = read_head(reader).map_err(bad)?;
+    if len > MAX_REQUEST_LEN {
+        let _ = io::copy(&mut reader.take(len as u64), &mut io::sink());
+        return Err(Response::text(
+            413,
+            &format!(
+                "request body of {} bytes is larger than the limit of {} bytes",
+                len, MAX_REQUEST_LEN
+            ),
+        ));
+    }
+    let body = read_body(reader, len).map_err(bad)?;
+    let (method, path) = head
+        .split_once(' ')
+        .ok_or_else(|| bad(GitError::RemoteError(format!("bad request {:?}", head))))?;
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: BTreeMap::new(),
+        headers: BTreeMap::new(),
+        body,
+    })
+}
+
+/// Send a request to a `git-rs://host[:port]/<repo>/<path>` url and read the response
+pub fn send(method: &str, url: &str, body: &[u8]) -> Result<Response, GitError> {
+    let rest = url
+        .strip_prefix(SCHEME)
+        .ok_or_else(|| GitError::RemoteError(format!("not a {} url: {}", SCHEME, url)))?;
+    let (host, path) = match rest.find('/') {
+        Some(at) => (&rest[..at], &rest[at..]),
+        None => (rest, "/"),
+    };
+    let addr = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{}:{}", host, DEFAULT_PORT),
+    };
+    let mut stream = TcpStream::connect(&addr)
+        .map_err(|e| GitError::RemoteError(format!("{}: {:?}", url, e)))?;
+    write_message(&mut stream, &format!("{} {}", method, path), body)?;
+    let reader = &mut BufReader::new(stream);
+    let (status, len) = read_head(reader)?;
+    let body = read_body(reader, len)?;
+    let status = status
+        .parse::<u16>()
+        .map_err(|_| GitError::RemoteError(format!("bad status {:?}", status)))?;
+    Ok(Response::new(status, "", body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_ut() {
+        let mut out = vec![];
+        assert!(write_message(&mut out, "GET /repo/info/refs", b"").is_ok());
+        assert!(write_message(&mut out, "POST /repo/upload", b"{}\n").is_ok());
+        assert_eq!(
+            b"GET /repo/info/refs 0\nPOST /repo/upload 3\n{}\n".to_vec(),
+            out
+        );
+        let mut reader = out.as_slice();
+        let request = read_request(&mut reader).unwrap();
+        assert_eq!(
+            ("GET", vec!["repo", "info", "refs"]),
+            (request.method.as_str(), request.segments())
+        );
+        let request = read_request(&mut reader).unwrap();
+        assert_eq!(
+            ("POST", b"{}\n".to_vec()),
+            (request.method.as_str(), request.body)
+        );
+        assert!(read_request(&mut "GET /repo/upload 5\n{}".as_bytes()).is_err());
+        assert!(read_request(&mut "GET /repo/upload\n".as_bytes()).is_err());
+
+        // a request over the limit is skipped and refused, answers are not limited
+        let len = MAX_REQUEST_LEN + 1;
+        let mut out = vec![];
+        assert!(write_message(&mut out, "POST /repo/upload", &vec![b'x'; len]).is_ok());
+        assert!(write_message(&mut out, "GET /repo/info/refs", b"").is_ok());
+        let mut reader = out.as_slice();
+        assert_eq!(413, read_request(&mut reader).unwrap_err().status);
+        assert_eq!("/repo/info/refs", read_request(&mut reader).unwrap().path);
+        let mut reader = out.as_slice();
+        let (_, len) = read_head(&mut reader).unwrap();
+        assert_eq!(
+            MAX_REQUEST_LEN + 1,
+            read_body(&mut reader, len).unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,567 @@
+use crate::color::{Color, Palette};
+
+/// number of unchanged lines shown around each change
+const CONTEXT: usize = 3;
+/// longest `+`/`-` bar of a diffstat, longer changes are scaled down
+const STAT_WIDTH: usize = 40;
+
+/// One line of an edit script turning the old text into the new one
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// One line of a hunk
+#[derive(Debug, PartialEq, Clone)]
+pub enum Line {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A group of changes with surrounding context lines
+/// starts are 1-based line numbers, or the line before the hunk when its length is 0
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<Line>,
+}
+
+/// Shortest line edit script with Myers' O(ND) algorithm, in its linear space variant: the
+/// middle snake of the shortest path splits the texts and both halves are diffed on their own.
+/// Removals come before additions when a line is replaced.
+pub fn edits<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Edit<'a>> {
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    diff_into(a, b, &mut edits);
+    // removals first in every run of changes
+    let mut start = 0;
+    while start < edits.len() {
+        let end = edits[start..]
+            .iter()
+            .position(|e| matches!(e, Edit::Same(_)))
+            .map_or(edits.len(), |n| start + n);
+        edits[start..end].sort_by_key(|e| matches!(e, Edit::Added(_)));
+        start = end + 1;
+    }
+    edits
+}
+
+fn diff_into<'a>(a: &[&'a str], b: &[&'a str], edits: &mut Vec<Edit<'a>>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a_rest, b_rest) = (&a[prefix..], &b[prefix..]);
+    let suffix = a_rest
+        .iter()
+        .rev()
+        .zip(b_rest.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (
+        &a_rest[..a_rest.len() - suffix],
+        &b_rest[..b_rest.len() - suffix],
+    );
+    edits.extend(a[..prefix].iter().map(|line| Edit::Same(line)));
+    if a_mid.is_empty() {
+        edits.extend(b_mid.iter().map(|line| Edit::Added(line)));
+    } else if b_mid.is_empty() {
+        edits.extend(a_mid.iter().map(|line| Edit::Removed(line)));
+    } else {
+        let (x, y, u, v) = middle_snake(a_mid, b_mid);
+        diff_into(&a_mid[..x], &b_mid[..y], edits);
+        edits.extend(a_mid[x..u].iter().map(|line| Edit::Same(line)));
+        diff_into(&a_mid[u..], &b_mid[v..], edits);
+    }
+    edits.extend(
+        a_rest[a_rest.len() - suffix..]
+            .iter()
+            .map(|line| Edit::Same(line)),
+    );
+}
+
+/// the snake (x, y) to (u, v) in the middle of a shortest edit path, found by searching
+/// forward from the start and backward from the end until the paths overlap
+fn middle_snake(a: &[&str], b: &[&str]) -> (usize, usize, usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    // forward[k + offset]: furthest x reached on diagonal k = x - y from (0, 0),
+    // backward[k + offset]: furthest distance from (n, m) on diagonal k of the reversed texts
+    let offset = max + 1;
+    let idx = |k: isize| (k + offset) as usize;
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[idx(k - 1)] < forward[idx(k + 1)]) {
+                forward[idx(k + 1)]
+            } else {
+                forward[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[idx(k)] = x;
+            let rk = delta - k;
+            if odd && (-(d - 1)..=d - 1).contains(&rk) && x + backward[idx(rk)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[idx(k - 1)] < backward[idx(k + 1)]) {
+                backward[idx(k + 1)]
+            } else {
+                backward[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[idx(k)] = x;
+            let fk = delta - k;
+            if !odd && (-d..=d).contains(&fk) && x + forward[idx(fk)] >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - x0) as usize,
+                    (m - y0) as usize,
+                );
+            }
+        }
+    }
+    unreachable!("the forward and backward paths always meet")
+}
+
+/// hunk start line, 1-based, or the line before an empty range
+fn start(line_no: usize, len: usize) -> usize {
+    if len == 0 {
+        line_no
+    } else {
+        line_no + 1
+    }
+}
+
+/// group an edit script into hunks with `context` unchanged lines around changes
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    // ranges of edit indexes, changes whose context overlaps share a hunk
+    let mut groups: Vec<(usize, usize)> = vec![];
+    for (i, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Same(_)) {
+            continue;
+        }
+        let begin = i.saturating_sub(context);
+        let end = (i + context + 1).min(edits.len());
+        match groups.last_mut() {
+            Some(last) if begin <= last.1 => last.1 = end,
+            _ => groups.push((begin, end)),
+        }
+    }
+
+    let mut hunks = vec![];
+    // line numbers in the old and new text before the next edit
+    let (mut old_no, mut new_no, mut pos) = (0, 0, 0);
+    for (begin, end) in groups {
+        for edit in edits[pos..begin].iter() {
+            match edit {
+                Edit::Same(_) => {
+                    old_no += 1;
+                    new_no += 1;
+                }
+                Edit::Removed(_) => old_no += 1,
+                Edit::Added(_) => new_no += 1,
+            }
+        }
+        let lines: Vec<Line> = edits[begin..end]
+            .iter()
+            .map(|edit| match edit {
+                Edit::Same(line) => Line::Context(line.to_string()),
+                Edit::Removed(line) => Line::Removed(line.to_string()),
+                Edit::Added(line) => Line::Added(line.to_string()),
+            })
+            .collect();
+        let old_len = lines
+            .iter()
+            .filter(|l| !matches!(l, Line::Added(_)))
+            .count();
+        let new_len = lines
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+        hunks.push(Hunk {
+            old_start: start(old_no, old_len),
+            old_len,
+            new_start: start(new_no, new_len),
+            new_len,
+            lines,
+        });
+        old_no += old_len;
+        new_no += new_len;
+        pos = end;
+    }
+    hunks
+}
+
+/// line diff of two texts as hunks with three lines of context, empty when they are equal
+pub fn diff_lines(a: &str, b: &str) -> Vec<Hunk> {
+    let a_lines: Vec<&str> = a.lines().collect();
+    let b_lines: Vec<&str> = b.lines().collect();
+    hunks(&edits(&a_lines, &b_lines), CONTEXT)
+}
+
+/// Whether content is not text and is not diffed line by line: it has a NUL byte or is not
+/// valid UTF-8
+pub fn is_binary(content: &[u8]) -> bool {
+    content.contains(&0) || std::str::from_utf8(content).is_err()
+}
+
+/// How much of two texts is the same, in percent of their lines: 100 for equal texts and
+/// 0 when no line is kept
+pub fn similarity(a: &str, b: &str) -> usize {
+    let a_lines: Vec<&str> = a.lines().collect();
+    let b_lines: Vec<&str> = b.lines().collect();
+    let total = a_lines.len() + b_lines.len();
+    if total == 0 {
+        return 100;
+    }
+    let same = edits(&a_lines, &b_lines)
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Same(_)))
+        .count();
+    same * 200 / total
+}
+
+impl Hunk {
+    /// the hunk in unified diff format, the header cyan, removed lines red and added lines
+    /// green
+    pub fn render(&self, palette: &Palette) -> String {
+        let header = format!(
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_len, self.new_start, self.new_len
+        );
+        let mut out = format!("{}\n", palette.paint(Color::Cyan, &header));
+        for line in self.lines.iter() {
+            let line = match line {
+                Line::Context(line) => format!(" {}", line),
+                Line::Removed(line) => palette.paint(Color::Red, &format!("-{}", line)),
+                Line::Added(line) => palette.paint(Color::Green, &format!("+{}", line)),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// render a hunk in unified diff format
+impl std::fmt::Display for Hunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(&Palette::default()))
+    }
+}
+
+/// Unified diff of two texts, empty when they are equal
+/// ```text
+/// --- a/f1
+/// +++ b/f1
+/// @@ -1,2 +1,2 @@
+///  same
+/// -old
+/// +new
+/// ```
+pub fn unified(old_name: &str, new_name: &str, a: &str, b: &str) -> String {
+    unified_with(old_name, new_name, a, b, &Palette::default())
+}
+
+/// `unified` with the file names bold and the hunks colored, see `Hunk::render`
+pub fn unified_with(old_name: &str, new_name: &str, a: &str, b: &str, palette: &Palette) -> String {
+    let hunks = diff_lines(a, b);
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let mut out = format!(
+        "{}\n{}\n",
+        palette.paint(Color::Bold, &format!("--- {}", old_name)),
+        palette.paint(Color::Bold, &format!("+++ {}", new_name))
+    );
+    for hunk in hunks.iter() {
+        out.push_str(&hunk.render(palette));
+    }
+    out
+}
+
+/// lines added and lines removed by the hunks
+pub fn line_counts(hunks: &[Hunk]) -> (usize, usize) {
+    let lines = || hunks.iter().flat_map(|hunk| hunk.lines.iter());
+    let added = lines().filter(|l| matches!(l, Line::Added(_))).count();
+    let removed = lines().filter(|l| matches!(l, Line::Removed(_))).count();
+    (added, removed)
+}
+
+/// Diffstat of changed files given with the lines added and removed, None for binary files,
+/// empty without files
+/// ```text
+///  src/a.rs | 5 ++---
+///  logo.png | Bin
+///  2 files changed, 2 insertions(+), 3 deletions(-)
+/// ```
+pub fn stat(files: &[(String, Option<(usize, usize)>)]) -> String {
+    if files.is_empty() {
+        return String::new();
+    }
+    let counts: Vec<String> = files
+        .iter()
+        .map(|(_, lines)| match lines {
+            Some((added, removed)) => (added + removed).to_string(),
+            None => "Bin".to_string(),
+        })
+        .collect();
+    let name_width = files.iter().map(|(path, _)| path.chars().count()).max();
+    let count_width = counts.iter().map(|count| count.len()).max();
+    let (name_width, count_width) = (name_width.unwrap_or(0), count_width.unwrap_or(0));
+    let most = files
+        .iter()
+        .filter_map(|(_, lines)| lines.map(|(added, removed)| added + removed))
+        .max()
+        .unwrap_or(0);
+    let scale = |n: usize| match n {
+        0 => 0,
+        _ if most <= STAT_WIDTH => n,
+        _ => 1 + n * (STAT_WIDTH - 1) / most,
+    };
+    let mut out = String::new();
+    let (mut insertions, mut deletions) = (0, 0);
+    for ((path, lines), count) in files.iter().zip(counts.iter()) {
+        let bar = match lines {
+            Some((added, removed)) => {
+                insertions += added;
+                deletions += removed;
+                let total = scale(added + removed);
+                let plus = scale(*added).min(total);
+                format!("{}{}", "+".repeat(plus), "-".repeat(total - plus))
+            }
+            None => String::new(),
+        };
+        let line = format!(" {:<name_width$} | {:>count_width$} {}", path, count, bar);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    let plural = |n: usize, one: &str, many: &str| match n {
+        1 => format!("{} {}", n, one),
+        _ => format!("{} {}", n, many),
+    };
+    out.push_str(&format!(
+        " {}",
+        plural(files.len(), "file changed", "files changed")
+    ));
+    if insertions > 0 || deletions == 0 {
+        out.push_str(&format!(
+            ", {}",
+            plural(insertions, "insertion(+)", "insertions(+)")
+        ));
+    }
+    if deletions > 0 || insertions == 0 {
+        out.push_str(&format!(
+            ", {}",
+            plural(deletions, "deletion(-)", "deletions(-)")
+        ));
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// apply an edit script to get both texts back
+    fn sides<'a>(edits: &[Edit<'a>]) -> (Vec<&'a str>, Vec<&'a str>) {
+        let mut a = vec![];
+        let mut b = vec![];
+        for edit in edits {
+            match edit {
+                Edit::Same(line) => {
+                    a.push(*line);
+                    b.push(*line);
+                }
+                Edit::Removed(line) => a.push(*line),
+                Edit::Added(line) => b.push(*line),
+            }
+        }
+        (a, b)
+    }
+
+    #[test]
+    fn edits_ut() {
+        assert_eq!(
+            vec![
+                Edit::Same("a"),
+                Edit::Removed("b"),
+                Edit::Added("x"),
+                Edit::Same("c"),
+                Edit::Added("d"),
+            ],
+            edits(&["a", "b", "c"], &["a", "x", "c", "d"])
+        );
+        assert!(edits(&[], &[]).is_empty());
+        assert_eq!(vec![Edit::Added("a")], edits(&[], &["a"]));
+        assert_eq!(vec![Edit::Removed("a")], edits(&["a"], &[]));
+
+        // the example from the paper, the shortest edit script has 5 changes
+        let a = ["A", "B", "C", "A", "B", "B", "A"];
+        let b = ["C", "B", "A", "B", "A", "C"];
+        let script = edits(&a, &b);
+        assert_eq!((a.to_vec(), b.to_vec()), sides(&script));
+        let changes = script
+            .iter()
+            .filter(|e| !matches!(e, Edit::Same(_)))
+            .count();
+        assert_eq!(5, changes);
+
+        // shortest against the longest common subsequence of small texts
+        let mut seed = 7u32;
+        let mut text = |len: usize| -> Vec<&str> {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    ["a", "b", "c"][(seed >> 16) as usize % 3]
+                })
+                .collect()
+        };
+        for len in 0..40 {
+            let (a, b) = (text(len), text(len / 2 + 3));
+            let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in 0..a.len() {
+                for j in 0..b.len() {
+                    lcs[i + 1][j + 1] = match a[i] == b[j] {
+                        true => lcs[i][j] + 1,
+                        false => lcs[i][j + 1].max(lcs[i + 1][j]),
+                    };
+                }
+            }
+            let script = edits(&a, &b);
+            assert_eq!((a.clone(), b.clone()), sides(&script));
+            let same = script.iter().filter(|e| matches!(e, Edit::Same(_))).count();
+            assert_eq!(lcs[a.len()][b.len()], same);
+        }
+
+        // a full rewrite of a long file
+        let old: Vec<String> = (0..4000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..4000).map(|i| format!("new {}", i)).collect();
+        let old: Vec<&str> = old.iter().map(|s| s.as_str()).collect();
+        let new: Vec<&str> = new.iter().map(|s| s.as_str()).collect();
+        let script = edits(&old, &new);
+        assert_eq!(Edit::Removed("old 0"), script[0]);
+        assert_eq!(Edit::Added("new 0"), script[4000]);
+    }
+
+    #[test]
+    fn diff_lines_ut() {
+        let hunks = diff_lines("a\nb\nc\n", "a\nB\nc\n");
+        assert_eq!(
+            vec![Hunk {
+                old_start: 1,
+                old_len: 3,
+                new_start: 1,
+                new_len: 3,
+                lines: vec![
+                    Line::Context("a".to_string()),
+                    Line::Removed("b".to_string()),
+                    Line::Added("B".to_string()),
+                    Line::Context("c".to_string()),
+                ],
+            }],
+            hunks
+        );
+        assert!(diff_lines("same\n", "same\n").is_empty());
+    }
+
+    #[test]
+    fn is_binary_ut() {
+        assert!(!is_binary(b"text\r\n"));
+        assert!(!is_binary("utf-8 \u{e9}".as_bytes()));
+        assert!(is_binary(b"nul\0"));
+        assert!(is_binary(&[0x89, b'P', b'N', b'G']));
+    }
+
+    #[test]
+    fn similarity_ut() {
+        assert_eq!(100, similarity("", ""));
+        assert_eq!(100, similarity("a\nb\n", "a\nb\n"));
+        assert_eq!(0, similarity("a\n", "b\n"));
+        assert_eq!(0, similarity("", "b\n"));
+        assert_eq!(75, similarity("a\nb\nc\n", "a\nb\nc\nd\ne\n"));
+    }
+
+    #[test]
+    fn unified_ut() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let b = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        assert_eq!(
+            r#"--- a/f
++++ b/f
+@@ -1,6 +1,6 @@
+ 1
+ 2
+-3
++three
+ 4
+ 5
+ 6
+@@ -10,3 +10,4 @@
+ 10
+ 11
+ 12
++13
+"#,
+            unified("a/f", "b/f", a, b)
+        );
+        assert_eq!("", unified("a/f", "b/f", a, a));
+        assert_eq!(
+            "\x1b[1m--- a/f\x1b[m\n\x1b[1m+++ b/f\x1b[m\n\x1b[36m@@ -1,2 +1,2 @@\x1b[m\n \
+             1\n\x1b[31m-2\x1b[m\n\x1b[32m+two\x1b[m\n",
+            unified_with("a/f", "b/f", "1\n2\n", "1\ntwo\n", &Palette::new(true))
+        );
+        assert_eq!(
+            "--- a/f\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-1\n-2\n",
+            unified("a/f", "/dev/null", "1\n2\n", "")
+        );
+    }
+
+    #[test]
+    fn stat_ut() {
+        let hunks = diff_lines("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!((2, 1), line_counts(&hunks));
+        assert_eq!(
+            " src/a.rs |   3 ++-\n logo     | Bin\n mode     |   0\n \
+             3 files changed, 2 insertions(+), 1 deletion(-)\n",
+            stat(&[
+                ("src/a.rs".to_string(), Some((2, 1))),
+                ("logo".to_string(), None),
+                ("mode".to_string(), Some((0, 0))),
+            ])
+        );
+        assert_eq!(
+            " a | 100 ++++++++++++++++++++++++++++++\
+             ----------\n b |   2 +\n \
+             2 files changed, 76 insertions(+), 26 deletions(-)\n",
+            stat(&[
+                ("a".to_string(), Some((75, 25))),
+                ("b".to_string(), Some((1, 1))),
+            ])
+        );
+        assert_eq!(
+            " a | 0\n 1 file changed, 0 insertions(+), 0 deletions(-)\n",
+            stat(&[("a".to_string(), Some((0, 0)))])
+        );
+        assert_eq!("", stat(&[]));
+    }
+}
@@ -0,0 +1,242 @@
+use crate::error::GitError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Kind of a tracked path, named after the git tree entry modes
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub enum FileMode {
+    #[default]
+    #[serde(rename = "100644")]
+    Regular,
+    #[serde(rename = "100755")]
+    Executable,
+    /// the blob holds the link target
+    #[serde(rename = "120000")]
+    Symlink,
+}
+
+impl FileMode {
+    /// mode of the file at path, symbolic links are not followed
+    pub fn of(path: &Path) -> Result<Self, GitError> {
+        let meta =
+            fs::symlink_metadata(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if meta.file_type().is_symlink() {
+            return Ok(Self::Symlink);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if meta.permissions().mode() & 0o111 != 0 {
+                return Ok(Self::Executable);
+            }
+        }
+        Ok(Self::Regular)
+    }
+}
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Regular => write!(f, "100644"),
+            Self::Executable => write!(f, "100755"),
+            Self::Symlink => write!(f, "120000"),
+        }
+    }
+}
+
+/// Tracked path of a tree or the staging area: its blob and its mode.
+/// Regular files serialize as the bare blob sha1, so trees written before modes were
+/// recorded keep their sha1.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "Repr", into = "Repr")]
+pub struct Entry {
+    pub sha1: String,
+    pub mode: FileMode,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Sha1(String),
+    Entry { sha1: String, mode: FileMode },
+}
+
+impl From<Repr> for Entry {
+    fn from(repr: Repr) -> Self {
+        match repr {
+            Repr::Sha1(sha1) => Self::new(sha1),
+            Repr::Entry { sha1, mode } => Self { sha1, mode },
+        }
+    }
+}
+
+impl From<Entry> for Repr {
+    fn from(entry: Entry) -> Self {
+        match entry.mode {
+            FileMode::Regular => Self::Sha1(entry.sha1),
+            mode => Self::Entry {
+                sha1: entry.sha1,
+                mode,
+            },
+        }
+    }
+}
+
+impl Entry {
+    /// regular file with the blob
+    pub fn new(sha1: impl Into<String>) -> Self {
+        Self {
+            sha1: sha1.into(),
+            mode: FileMode::Regular,
+        }
+    }
+
+    pub fn with_mode(sha1: impl Into<String>, mode: FileMode) -> Self {
+        Self {
+            sha1: sha1.into(),
+            mode,
+        }
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode == FileMode::Symlink
+    }
+}
+
+/// whether anything is at path, a dangling symbolic link included
+pub fn exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Content of the file at path as a blob holds it and its mode: the target of a symbolic
+/// link, which is never followed, or the file bytes
+pub fn read_content(path: &Path) -> Result<(Vec<u8>, FileMode), GitError> {
+    let mode = FileMode::of(path)?;
+    let content = match mode {
+        FileMode::Symlink => {
+            link_bytes(fs::read_link(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?)
+        }
+        _ => fs::read(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?,
+    };
+    Ok((content, mode))
+}
+
+#[cfg(unix)]
+fn link_bytes(target: std::path::PathBuf) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+    target.into_os_string().into_vec()
+}
+
+#[cfg(not(unix))]
+fn link_bytes(target: std::path::PathBuf) -> Vec<u8> {
+    target.display().to_string().into_bytes()
+}
+
+/// Write a blob's content at path with the mode, replacing whatever is there: a symbolic
+/// link to the content, or a file holding it where links are not supported. Executable
+/// files get the execute permission wherever they are readable, other files lose it.
+pub fn write_content(path: &Path, content: &[u8], mode: FileMode) -> Result<(), GitError> {
+    // writing through an existing link would change its target instead
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+        fs::remove_file(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    }
+    match mode {
+        #[cfg(unix)]
+        FileMode::Symlink => {
+            if path.exists() {
+                fs::remove_file(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+            use std::os::unix::ffi::OsStrExt;
+            std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(content), path)
+                .map_err(|e| GitError::FileOpError(format!("{}: {:?}", path.display(), e)))
+        }
+        _ => {
+            fs::write(path, content)
+                .map_err(|e| GitError::FileOpError(format!("{}: {:?}", path.display(), e)))?;
+            set_executable(path, mode == FileMode::Executable)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path, executable: bool) -> Result<(), GitError> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)
+        .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+        .permissions();
+    let old = permissions.mode();
+    let new = if executable {
+        old | (old & 0o444) >> 2
+    } else {
+        old & !0o111
+    };
+    if new == old {
+        return Ok(());
+    }
+    permissions.set_mode(new);
+    fs::set_permissions(path, permissions)
+        .map_err(|e| GitError::FileOpError(format!("{}: {:?}", path.display(), e)))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _executable: bool) -> Result<(), GitError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::env;
+
+    #[test]
+    fn serde_ut() {
+        let tree = BTreeMap::from([
+            ("f".to_string(), Entry::new("a")),
+            ("l".to_string(), Entry::with_mode("b", FileMode::Symlink)),
+        ]);
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(r#"{"f":"a","l":{"sha1":"b","mode":"120000"}}"#, json);
+        assert_eq!(
+            tree,
+            serde_json::from_str::<BTreeMap<String, Entry>>(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn content_ut() {
+        let dir = &env::current_dir().unwrap().join("entry_content_ut");
+        if dir.exists() {
+            assert!(fs::remove_dir_all(dir).is_ok());
+        }
+        assert!(fs::create_dir(dir).is_ok());
+        let (file, link) = (dir.join("file"), dir.join("link"));
+        assert!(write_content(&file, b"text", FileMode::Regular).is_ok());
+        assert!(write_content(&link, b"file", FileMode::Symlink).is_ok());
+        assert_eq!(
+            (b"file".to_vec(), FileMode::Symlink),
+            read_content(&link).unwrap()
+        );
+        assert_eq!("text", fs::read_to_string(&link).unwrap());
+
+        // a file replacing the link leaves the link target alone
+        assert!(write_content(&link, b"new\0", FileMode::Regular).is_ok());
+        assert_eq!(
+            (b"new\0".to_vec(), FileMode::Regular),
+            read_content(&link).unwrap()
+        );
+        assert_eq!("text", fs::read_to_string(&file).unwrap());
+
+        // the execute permission follows the mode
+        let script = dir.join("script");
+        assert!(write_content(&script, b"run", FileMode::Executable).is_ok());
+        assert_eq!(FileMode::Executable, FileMode::of(&script).unwrap());
+        assert!(write_content(&script, b"run", FileMode::Regular).is_ok());
+        assert_eq!(FileMode::Regular, FileMode::of(&script).unwrap());
+        assert!(fs::remove_dir_all(dir).is_ok());
+    }
+}
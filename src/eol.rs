@@ -0,0 +1,218 @@
+use crate::attributes::{AttrValue, Attributes};
+use crate::config::Config;
+use crate::error::GitError;
+use std::borrow::Cow;
+
+/// Line ending of text files in the working directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eol {
+    Lf,
+    Crlf,
+}
+
+/// The `core.autocrlf` config, for files without `text` or `eol` attributes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoCrlf {
+    /// no conversion
+    False,
+    /// CRLF becomes LF when adding, LF becomes CRLF on checkout
+    True,
+    /// CRLF becomes LF when adding, files are checked out as stored
+    Input,
+}
+
+impl AutoCrlf {
+    pub fn from_config(config: &Config) -> Result<Self, GitError> {
+        match config.get("core.autocrlf") {
+            Some(value) if value.eq_ignore_ascii_case("input") => Ok(Self::Input),
+            _ => match config.get_bool("core.autocrlf")? {
+                Some(true) => Ok(Self::True),
+                _ => Ok(Self::False),
+            },
+        }
+    }
+}
+
+/// How the line endings of one path are converted between the working directory and the
+/// repository, which always stores LF
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conversion {
+    /// line ending written into the working directory, None for no conversion at all
+    pub eol: Option<Eol>,
+    /// only convert content that looks like text, i.e. has no NUL character
+    pub auto: bool,
+}
+
+impl Conversion {
+    pub const NONE: Self = Self {
+        eol: None,
+        auto: false,
+    };
+
+    fn applies(&self, content: &[u8]) -> bool {
+        self.eol.is_some() && !(self.auto && content.contains(&0))
+    }
+
+    /// content of a working directory file as the repository stores it
+    pub fn to_repo<'a>(&self, content: &'a [u8]) -> Cow<'a, [u8]> {
+        if !self.applies(content) || !content.windows(2).any(|pair| pair == b"\r\n") {
+            return Cow::Borrowed(content);
+        }
+        let mut out = Vec::with_capacity(content.len());
+        for (i, byte) in content.iter().enumerate() {
+            if *byte != b'\r' || content.get(i + 1) != Some(&b'\n') {
+                out.push(*byte);
+            }
+        }
+        Cow::Owned(out)
+    }
+
+    /// content of a blob as the working directory file holds it
+    pub fn to_worktree<'a>(&self, content: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.eol != Some(Eol::Crlf) || !self.applies(content) {
+            return Cow::Borrowed(content);
+        }
+        let mut out = Vec::with_capacity(content.len() + content.len() / 32);
+        let mut prev = 0;
+        for byte in content.iter() {
+            if *byte == b'\n' && prev != b'\r' {
+                out.push(b'\r');
+            }
+            out.push(*byte);
+            prev = *byte;
+        }
+        Cow::Owned(out)
+    }
+}
+
+/// Picks the conversion of each path from its attributes and `core.autocrlf`:
+/// ```text
+/// *.sh  eol=lf      text, checked out with LF
+/// *.bat eol=crlf    text, checked out with CRLF
+/// *.txt text        text, checked out with the autocrlf line ending
+/// *     text=auto   converted when the content looks like text
+/// *.png -text       never converted, as are binary files
+/// ```
+/// paths without attributes are converted like `text=auto` unless autocrlf is false.
+#[derive(Debug, Clone)]
+pub struct EolFilter {
+    attributes: Attributes,
+    autocrlf: AutoCrlf,
+}
+
+impl EolFilter {
+    pub fn new(attributes: Attributes, autocrlf: AutoCrlf) -> Self {
+        Self {
+            attributes,
+            autocrlf,
+        }
+    }
+
+    /// conversion of the path
+    pub fn conversion(&mut self, path: &str) -> Result<Conversion, GitError> {
+        let attrs = self.attributes.lookup(path)?;
+        let eol = match attrs.get("eol") {
+            Some(AttrValue::Value(eol)) if eol == "crlf" => Some(Eol::Crlf),
+            Some(AttrValue::Value(eol)) if eol == "lf" => Some(Eol::Lf),
+            _ => None,
+        };
+        let auto = match attrs.get("text") {
+            Some(AttrValue::Unset) => return Ok(Conversion::NONE),
+            Some(AttrValue::Set) => false,
+            Some(AttrValue::Value(value)) if value == "auto" => true,
+            // an eol attribute makes the path text
+            _ if eol.is_some() => false,
+            _ if self.autocrlf == AutoCrlf::False => return Ok(Conversion::NONE),
+            _ => true,
+        };
+        let eol = eol.unwrap_or(match self.autocrlf {
+            AutoCrlf::True => Eol::Crlf,
+            _ => Eol::Lf,
+        });
+        Ok(Conversion {
+            eol: Some(eol),
+            auto,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn conversion_ut() {
+        let crlf = Conversion {
+            eol: Some(Eol::Crlf),
+            auto: true,
+        };
+        assert_eq!(b"a\nb\n", crlf.to_repo(b"a\r\nb\r\n").as_ref());
+        assert_eq!(b"a\r\nb\r\nc", crlf.to_worktree(b"a\nb\r\nc").as_ref());
+        assert_eq!(b"a\0\r\n", crlf.to_repo(b"a\0\r\n").as_ref());
+        assert_eq!(b"a\0\n", crlf.to_worktree(b"a\0\n").as_ref());
+        let lf = Conversion {
+            eol: Some(Eol::Lf),
+            auto: false,
+        };
+        assert_eq!(b"a\0\n", lf.to_repo(b"a\0\r\n").as_ref());
+        assert_eq!(b"a\n", lf.to_worktree(b"a\n").as_ref());
+        assert_eq!(b"a\r\r\n", lf.to_repo(b"a\r\r\r\n").as_ref());
+        assert_eq!(b"a\r\n", Conversion::NONE.to_repo(b"a\r\n").as_ref());
+    }
+
+    #[test]
+    fn filter_ut() {
+        let attributes = || {
+            let mut attributes = Attributes::new(Path::new("/nonexistent"));
+            let content = "*.sh eol=lf\n*.bat eol=crlf\n*.txt text\n*.md text=auto\n*.png binary\n";
+            assert!(attributes.add_patterns(content, "").is_ok());
+            attributes
+        };
+        let conversion = |eol, auto| Conversion {
+            eol: Some(eol),
+            auto,
+        };
+
+        let mut filter = EolFilter::new(attributes(), AutoCrlf::False);
+        assert_eq!(
+            conversion(Eol::Lf, false),
+            filter.conversion("a.sh").unwrap()
+        );
+        assert_eq!(
+            conversion(Eol::Crlf, false),
+            filter.conversion("a.bat").unwrap()
+        );
+        assert_eq!(
+            conversion(Eol::Lf, false),
+            filter.conversion("a.txt").unwrap()
+        );
+        assert_eq!(
+            conversion(Eol::Lf, true),
+            filter.conversion("a.md").unwrap()
+        );
+        assert_eq!(Conversion::NONE, filter.conversion("a.png").unwrap());
+        assert_eq!(Conversion::NONE, filter.conversion("a.rs").unwrap());
+
+        let mut filter = EolFilter::new(attributes(), AutoCrlf::True);
+        assert_eq!(
+            conversion(Eol::Lf, false),
+            filter.conversion("a.sh").unwrap()
+        );
+        assert_eq!(
+            conversion(Eol::Crlf, false),
+            filter.conversion("a.txt").unwrap()
+        );
+        assert_eq!(
+            conversion(Eol::Crlf, true),
+            filter.conversion("a.rs").unwrap()
+        );
+        assert_eq!(Conversion::NONE, filter.conversion("a.png").unwrap());
+
+        let mut filter = EolFilter::new(attributes(), AutoCrlf::Input);
+        assert_eq!(
+            conversion(Eol::Lf, true),
+            filter.conversion("a.rs").unwrap()
+        );
+    }
+}
@@ -15,6 +15,48 @@ pub enum GitError {
     CommitError(String),
     #[error("branch: {0}")]
     BranchError(String),
+    #[error("checkout: {0}")]
+    CheckoutError(String),
+    #[error("grep: {0}")]
+    GrepError(String),
+    #[error("merge: {0}")]
+    MergeError(String),
+    #[error("archive: {0}")]
+    ArchiveError(String),
+    #[error("bisect: {0}")]
+    BisectError(String),
+    #[error("bundle: {0}")]
+    BundleError(String),
+    #[error("cherry-pick: {0}")]
+    CherryPickError(String),
+    #[error("patch: {0}")]
+    PatchError(String),
+    #[error("rebase: {0}")]
+    RebaseError(String),
+    #[error("stash: {0}")]
+    StashError(String),
+    #[error("tag: {0}")]
+    TagError(String),
+    #[error("notes: {0}")]
+    NotesError(String),
+    #[error("signature: {0}")]
+    SignatureError(String),
+    #[error("hook: {0}")]
+    HookError(String),
+    #[error("ignore: {0}")]
+    IgnoreError(String),
+    #[error("attributes: {0}")]
+    AttributeError(String),
+    #[error("object: {0}")]
+    ObjectError(String),
+    #[error("pack: {0}")]
+    PackError(String),
+    #[error("fast-import: {0}")]
+    ImportError(String),
+    #[error("remote: {0}")]
+    RemoteError(String),
+    #[error("worktree: {0}")]
+    WorktreeError(String),
     #[error("File {0} does not exist.")]
     FileNotExistError(String),
     #[error("file op fatal: {0}")]
@@ -23,4 +65,10 @@ pub enum GitError {
     SerdeOpError(String),
     #[error("crypto error: {0}")]
     CryptoError(String),
+    #[error("revision: {0}")]
+    RevisionError(String),
+    #[error("http: {0}")]
+    HttpError(String),
+    #[error("config: {0}")]
+    ConfigError(String),
 }
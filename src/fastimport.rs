@@ -0,0 +1,419 @@
+use crate::entry::FileMode;
+use crate::error::GitError;
+
+/// `Name <email>` and the time of an author, committer or tagger line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub date_time: i64,
+}
+
+/// Content of a file modification, a mark or object reference, or inline data
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataRef {
+    Ref(String),
+    Inline(Vec<u8>),
+}
+
+/// Change a commit makes to the files of its first parent
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileOp {
+    /// `M <mode> <dataref> <path>`
+    Modify {
+        mode: FileMode,
+        data: DataRef,
+        path: String,
+    },
+    /// `D <path>`
+    Delete(String),
+    /// `R <from> <to>`
+    Rename(String, String),
+    /// `C <from> <to>`
+    Copy(String, String),
+    /// `deleteall`
+    DeleteAll,
+}
+
+/// One command of a fast-import stream
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Blob {
+        mark: Option<String>,
+        data: Vec<u8>,
+    },
+    Commit {
+        /// ref the commit goes on, e.g. `refs/heads/main`
+        reference: String,
+        mark: Option<String>,
+        author: Option<Ident>,
+        committer: Ident,
+        message: Vec<u8>,
+        from: Option<String>,
+        merges: Vec<String>,
+        ops: Vec<FileOp>,
+    },
+    Reset {
+        reference: String,
+        from: Option<String>,
+    },
+    Tag {
+        name: String,
+        from: String,
+        tagger: Option<Ident>,
+        message: Vec<u8>,
+    },
+}
+
+/// Reads the commands of a stream line by line, `data` blocks byte exact
+struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    line_no: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, msg: &str) -> GitError {
+        GitError::ImportError(format!("line {}: {}", self.line_no, msg))
+    }
+
+    /// next line without its newline, None at the end of the stream
+    fn peek_line(&self) -> Option<&'a str> {
+        let rest = self.input.get(self.pos..).filter(|rest| !rest.is_empty())?;
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        std::str::from_utf8(&rest[..end]).ok()
+    }
+
+    fn next_line(&mut self) -> Result<Option<&'a str>, GitError> {
+        if self.pos >= self.input.len() {
+            return Ok(None);
+        }
+        let line = self
+            .peek_line()
+            .ok_or_else(|| self.error("line is not UTF-8"))?;
+        self.pos = (self.pos + line.len() + 1).min(self.input.len());
+        self.line_no += 1;
+        Ok(Some(line))
+    }
+
+    /// consume the next line when it starts with the prefix, returns the rest of it
+    fn optional(&mut self, prefix: &str) -> Result<Option<&'a str>, GitError> {
+        match self.peek_line().and_then(|line| line.strip_prefix(prefix)) {
+            Some(_) => Ok(self.next_line()?.and_then(|line| line.strip_prefix(prefix))),
+            None => Ok(None),
+        }
+    }
+
+    /// `data <count>` followed by that many bytes, or `data <<<delim>` followed by lines
+    /// up to the delimiter line
+    fn data(&mut self) -> Result<Vec<u8>, GitError> {
+        let header = self
+            .next_line()?
+            .and_then(|line| line.strip_prefix("data "))
+            .ok_or_else(|| self.error("expected data"))?;
+        if let Some(delim) = header.strip_prefix("<<") {
+            let mut data = vec![];
+            loop {
+                match self.next_line()? {
+                    Some(line) if line == delim => break,
+                    Some(line) => {
+                        data.extend_from_slice(line.as_bytes());
+                        data.push(b'\n');
+                    }
+                    None => return Err(self.error("unterminated data")),
+                }
+            }
+            return Ok(data);
+        }
+        let len: usize = header
+            .parse()
+            .map_err(|_| self.error(&format!("bad data length {}", header)))?;
+        let data = self
+            .input
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| self.error("data runs past the end of the stream"))?
+            .to_vec();
+        self.line_no += data.iter().filter(|b| **b == b'\n').count();
+        self.pos += len;
+        // an optional newline ends the data
+        if self.input.get(self.pos) == Some(&b'\n') {
+            self.pos += 1;
+            self.line_no += 1;
+        }
+        Ok(data)
+    }
+
+    fn ident(&self, value: &str) -> Result<Ident, GitError> {
+        // `Name <email> <time> <tz>`
+        let bad = || self.error(&format!("bad identity {}", value));
+        let end = value.rfind('>').ok_or_else(bad)?;
+        let date_time = value[end + 1..]
+            .split_whitespace()
+            .next()
+            .and_then(|time| time.parse().ok())
+            .ok_or_else(bad)?;
+        Ok(Ident {
+            name: value[..end + 1].to_string(),
+            date_time,
+        })
+    }
+}
+
+/// a path, C-style quoted or up to the end of the value, and the rest after it
+fn split_path(value: &str) -> Option<(String, &str)> {
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Some(match value.split_once(' ') {
+            Some((path, rest)) => (path.to_string(), rest),
+            None => (value.to_string(), ""),
+        });
+    };
+    let mut path = vec![];
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let path = String::from_utf8(path).ok()?;
+                return Some((path, quoted[i + 1..].trim_start()));
+            }
+            '\\' => match chars.next()?.1 {
+                'n' => path.push(b'\n'),
+                't' => path.push(b'\t'),
+                '"' => path.push(b'"'),
+                '\\' => path.push(b'\\'),
+                d @ '0'..='7' => {
+                    let mut value = d.to_digit(8)?;
+                    for _ in 0..2 {
+                        value = value * 8 + chars.next()?.1.to_digit(8)?;
+                    }
+                    path.push(value as u8);
+                }
+                _ => return None,
+            },
+            c => path.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    None
+}
+
+fn file_mode(mode: &str) -> Option<FileMode> {
+    match mode {
+        "100644" | "644" => Some(FileMode::Regular),
+        "100755" | "755" => Some(FileMode::Executable),
+        "120000" => Some(FileMode::Symlink),
+        _ => None,
+    }
+}
+
+/// Parse a fast-import stream as `git fast-export` writes it: blob, commit, reset and tag
+/// commands with marks. `feature`, `option`, `progress`, `checkpoint` and `done` are accepted
+/// and ignored; submodules and notes are not supported.
+pub fn parse(input: &[u8]) -> Result<Vec<Command>, GitError> {
+    let mut reader = Reader {
+        input,
+        pos: 0,
+        line_no: 0,
+    };
+    let mut commands = vec![];
+    while let Some(line) = reader.next_line()? {
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "" => continue,
+            _ if line.starts_with('#') => continue,
+            "blob" => {
+                let mark = reader.optional("mark ")?.map(|m| m.to_string());
+                reader.optional("original-oid ")?;
+                commands.push(Command::Blob {
+                    mark,
+                    data: reader.data()?,
+                });
+            }
+            "commit" => {
+                let mark = reader.optional("mark ")?.map(|m| m.to_string());
+                reader.optional("original-oid ")?;
+                let author = match reader.optional("author ")? {
+                    Some(author) => Some(reader.ident(author)?),
+                    None => None,
+                };
+                let committer = reader
+                    .optional("committer ")?
+                    .ok_or_else(|| reader.error("expected committer"))?;
+                let committer = reader.ident(committer)?;
+                reader.optional("encoding ")?;
+                let message = reader.data()?;
+                let from = reader.optional("from ")?.map(|f| f.to_string());
+                let mut merges = vec![];
+                while let Some(merge) = reader.optional("merge ")? {
+                    merges.push(merge.to_string());
+                }
+                let mut ops = vec![];
+                while let Some(line) = reader.peek_line().filter(|l| !l.is_empty()) {
+                    let bad = || reader.error(&format!("bad file command {}", line));
+                    let (op, value) = line.split_once(' ').unwrap_or((line, ""));
+                    let op = match op {
+                        "M" => {
+                            let (mode, rest) = value.split_once(' ').ok_or_else(bad)?;
+                            let (data, path) = rest.split_once(' ').ok_or_else(bad)?;
+                            let mode = file_mode(mode).ok_or_else(|| {
+                                reader.error(&format!("unsupported file mode {}", mode))
+                            })?;
+                            let (path, _) = split_path(path).ok_or_else(bad)?;
+                            reader.next_line()?;
+                            let data = match data {
+                                "inline" => DataRef::Inline(reader.data()?),
+                                data => DataRef::Ref(data.to_string()),
+                            };
+                            FileOp::Modify { mode, data, path }
+                        }
+                        "D" => FileOp::Delete(split_path(value).ok_or_else(bad)?.0),
+                        "R" | "C" => {
+                            let (from, rest) = split_path(value).ok_or_else(bad)?;
+                            let (to, _) = split_path(rest).ok_or_else(bad)?;
+                            match op {
+                                "R" => FileOp::Rename(from, to),
+                                _ => FileOp::Copy(from, to),
+                            }
+                        }
+                        "deleteall" => FileOp::DeleteAll,
+                        _ => break,
+                    };
+                    if !matches!(op, FileOp::Modify { .. }) {
+                        reader.next_line()?;
+                    }
+                    ops.push(op);
+                }
+                commands.push(Command::Commit {
+                    reference: arg.to_string(),
+                    mark,
+                    author,
+                    committer,
+                    message,
+                    from,
+                    merges,
+                    ops,
+                });
+            }
+            "reset" => {
+                let from = reader.optional("from ")?.map(|f| f.to_string());
+                commands.push(Command::Reset {
+                    reference: arg.to_string(),
+                    from,
+                });
+            }
+            "tag" => {
+                reader.optional("mark ")?;
+                let from = reader
+                    .optional("from ")?
+                    .ok_or_else(|| reader.error("expected from"))?
+                    .to_string();
+                reader.optional("original-oid ")?;
+                let tagger = match reader.optional("tagger ")? {
+                    Some(tagger) => Some(reader.ident(tagger)?),
+                    None => None,
+                };
+                commands.push(Command::Tag {
+                    name: arg.to_string(),
+                    from,
+                    tagger,
+                    message: reader.data()?,
+                });
+            }
+            "feature" | "option" | "progress" | "checkpoint" => continue,
+            "done" => break,
+            _ => return Err(reader.error(&format!("unsupported command {}", line))),
+        }
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ut() {
+        let stream = b"feature done\n\
+            blob\nmark :1\ndata 6\nhello\n\n\
+            reset refs/heads/main\n\
+            commit refs/heads/main\nmark :2\n\
+            author A U Thor <a@example.com> 1700000000 +0100\n\
+            committer C O Mitter <c@example.com> 1700000100 +0000\n\
+            data <<EOF\nfirst\nEOF\n\
+            M 100644 :1 a.txt\nM 100755 inline \"dir/with space\\\\q\\303\\251\"\ndata 3\nrun\n\
+            D old\n\n\
+            commit refs/heads/main\n\
+            committer C <c@example.com> 1700000200 +0000\ndata 7\nsecond\n\
+            from :2\nmerge :3\nR a.txt b.txt\ndeleteall\n\n\
+            tag v1\nfrom :2\ntagger T <t@example.com> 1700000300 +0000\ndata 4\nrel\n\n\
+            done\nignored after done\n";
+        let commands = parse(stream).unwrap();
+        assert_eq!(5, commands.len());
+        assert_eq!(
+            Command::Blob {
+                mark: Some(":1".to_string()),
+                data: b"hello\n".to_vec()
+            },
+            commands[0]
+        );
+        match &commands[2] {
+            Command::Commit {
+                reference,
+                author,
+                committer,
+                message,
+                ops,
+                ..
+            } => {
+                assert_eq!("refs/heads/main", reference);
+                assert_eq!(
+                    Some(Ident {
+                        name: "A U Thor <a@example.com>".to_string(),
+                        date_time: 1700000000
+                    }),
+                    *author
+                );
+                assert_eq!(1700000100, committer.date_time);
+                assert_eq!(b"first\n".to_vec(), *message);
+                assert_eq!(
+                    vec![
+                        FileOp::Modify {
+                            mode: FileMode::Regular,
+                            data: DataRef::Ref(":1".to_string()),
+                            path: "a.txt".to_string()
+                        },
+                        FileOp::Modify {
+                            mode: FileMode::Executable,
+                            data: DataRef::Inline(b"run".to_vec()),
+                            path: "dir/with space\\q\u{e9}".to_string()
+                        },
+                        FileOp::Delete("old".to_string())
+                    ],
+                    *ops
+                );
+            }
+            command => panic!("{:?}", command),
+        }
+        match &commands[3] {
+            Command::Commit {
+                from, merges, ops, ..
+            } => {
+                assert_eq!(Some(":2".to_string()), *from);
+                assert_eq!(vec![":3".to_string()], *merges);
+                assert_eq!(
+                    vec![
+                        FileOp::Rename("a.txt".to_string(), "b.txt".to_string()),
+                        FileOp::DeleteAll
+                    ],
+                    *ops
+                );
+            }
+            command => panic!("{:?}", command),
+        }
+        assert!(matches!(&commands[4], Command::Tag { name, .. } if name == "v1"));
+
+        assert!(parse(b"blob\ndata 10\nshort").is_err());
+        assert!(parse(b"commit refs/heads/x\ndata 0\n").is_err());
+        assert!(parse(b"unknown\n").is_err());
+        let err = parse(b"commit refs/heads/x\ncommitter C <c> 1 +0000\ndata 0\nM 160000 :1 sub\n")
+            .unwrap_err();
+        assert!(format!("{:?}", err).contains("unsupported file mode"));
+    }
+}
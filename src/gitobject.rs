@@ -0,0 +1,779 @@
+use crate::entry::FileMode;
+use crate::error::GitError;
+use crate::utils;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Type of a Git object, named as in the object header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Blob => "blob",
+            Self::Tree => "tree",
+            Self::Commit => "commit",
+            Self::Tag => "tag",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "blob" => Some(Self::Blob),
+            "tree" => Some(Self::Tree),
+            "commit" => Some(Self::Commit),
+            "tag" => Some(Self::Tag),
+            _ => None,
+        }
+    }
+}
+
+/// canonical encoding of an object, `<kind> <size>\0` followed by the content
+pub fn encode(kind: ObjectKind, content: &[u8]) -> Vec<u8> {
+    let mut data = format!("{} {}\0", kind.name(), content.len()).into_bytes();
+    data.extend_from_slice(content);
+    data
+}
+
+/// id Git gives the object, the sha1 of its canonical encoding
+pub fn object_id(kind: ObjectKind, content: &[u8]) -> String {
+    utils::crypto_bytes(&encode(kind, content))
+}
+
+/// One entry of a tree object, a file or a subtree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub name: String,
+    /// None for a subtree
+    pub mode: Option<FileMode>,
+    pub id: String,
+}
+
+/// Content of a tree object: `<mode> <name>\0<20 byte id>` per entry, in Git's order where
+/// a subtree sorts as if its name ended with `/`
+pub fn tree_content(entries: &[TreeEntry]) -> Result<Vec<u8>, GitError> {
+    let sort_key = |entry: &TreeEntry| match entry.mode {
+        None => format!("{}/", entry.name),
+        Some(_) => entry.name.clone(),
+    };
+    let mut entries: Vec<&TreeEntry> = entries.iter().collect();
+    entries.sort_by_key(|entry| sort_key(entry));
+    let mut content = vec![];
+    for entry in entries {
+        let mode = match entry.mode {
+            None => "40000".to_string(),
+            Some(mode) => mode.to_string(),
+        };
+        content.extend(format!("{} {}\0", mode, entry.name).into_bytes());
+        content.extend(hex_bytes(&entry.id)?);
+    }
+    Ok(content)
+}
+
+fn hex_bytes(id: &str) -> Result<Vec<u8>, GitError> {
+    if id.len() != 40 {
+        return Err(GitError::ObjectError(format!("bad object id {}", id)));
+    }
+    (0..id.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&id[i..i + 2], 16)
+                .map_err(|_| GitError::ObjectError(format!("bad object id {}", id)))
+        })
+        .collect()
+}
+
+/// Loose objects of a Git `objects` directory: zlib compressed canonical encodings stored
+/// as `ab/cdef...` after their id
+#[derive(Debug, Clone)]
+pub struct ObjectStore {
+    dir: PathBuf,
+}
+
+impl ObjectStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(&id[..2]).join(&id[2..])
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        id.len() == 40 && self.path(id).is_file()
+    }
+
+    /// write an object unless it is there already, returns its id
+    pub fn write(&self, kind: ObjectKind, content: &[u8]) -> Result<String, GitError> {
+        let data = encode(kind, content);
+        let id = utils::crypto_bytes(&data);
+        let path = self.path(&id);
+        if path.is_file() {
+            return Ok(id);
+        }
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        let compressed = encoder
+            .write_all(&data)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| GitError::ObjectError(format!("{:?}", e)))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        fs::write(path, compressed).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Ok(id)
+    }
+
+    /// kind and content of an object
+    pub fn read(&self, id: &str) -> Result<(ObjectKind, Vec<u8>), GitError> {
+        if !self.contains(id) {
+            return Err(GitError::ObjectError(format!("no object {}", id)));
+        }
+        let compressed =
+            fs::read(self.path(id)).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let mut data = vec![];
+        ZlibDecoder::new(compressed.as_slice())
+            .read_to_end(&mut data)
+            .map_err(|e| GitError::ObjectError(format!("{}: {:?}", id, e)))?;
+        let bad = || GitError::ObjectError(format!("bad object {}", id));
+        let nul = data.iter().position(|b| *b == 0).ok_or_else(bad)?;
+        let header = String::from_utf8_lossy(&data[..nul]).to_string();
+        let (kind, size) = header.split_once(' ').ok_or_else(bad)?;
+        let kind = ObjectKind::from_name(kind).ok_or_else(bad)?;
+        let content = data.split_off(nul + 1);
+        if size.parse::<usize>().ok() != Some(content.len()) {
+            return Err(bad());
+        }
+        Ok((kind, content))
+    }
+}
+
+/// Loose and packed objects of a Git `objects` directory
+#[derive(Debug)]
+pub struct ObjectDatabase {
+    loose: ObjectStore,
+    packs: Vec<PackFile>,
+}
+
+impl ObjectDatabase {
+    pub fn open(dir: &Path) -> Result<Self, GitError> {
+        let mut packs = vec![];
+        if let Ok(files) = fs::read_dir(dir.join("pack")) {
+            let mut paths: Vec<PathBuf> = files
+                .filter_map(|file| file.ok().map(|file| file.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+                .collect();
+            paths.sort();
+            for path in paths {
+                packs.push(PackFile::open(&path)?);
+            }
+        }
+        Ok(Self {
+            loose: ObjectStore::new(dir),
+            packs,
+        })
+    }
+
+    /// kind and content of an object, loose or packed
+    pub fn read(&self, id: &str) -> Result<(ObjectKind, Vec<u8>), GitError> {
+        if self.loose.contains(id) {
+            return self.loose.read(id);
+        }
+        for pack in self.packs.iter() {
+            if let Some(offset) = pack.offsets.get(id) {
+                return pack.read_at(*offset, self);
+            }
+        }
+        Err(GitError::ObjectError(format!("no object {}", id)))
+    }
+}
+
+/// resolved delta bases a pack keeps for the deltas that follow, dropped all at once when full
+const BASE_CACHE_SIZE: usize = 64;
+/// most bytes reserved up front for an object, larger ones grow as they inflate
+const MAX_RESERVE: usize = 1 << 20;
+
+/// A version 2 pack of a Git repository, `pack-*.pack` read where an object is and the
+/// offsets of its objects from `pack-*.idx`
+#[derive(Debug)]
+struct PackFile {
+    file: fs::File,
+    offsets: HashMap<String, usize>,
+    bases: RefCell<HashMap<usize, (ObjectKind, Vec<u8>)>>,
+}
+
+/// Header of a pack entry: type, inflated size, what a delta applies to and where the
+/// deflated data starts
+struct PackEntry {
+    kind: u8,
+    size: usize,
+    base: Option<EntryBase>,
+    data: usize,
+}
+
+enum EntryBase {
+    Offset(usize),
+    Id(String),
+}
+
+impl PackFile {
+    fn open(idx_path: &Path) -> Result<Self, GitError> {
+        let pack_path = idx_path.with_extension("pack");
+        let io = |e: std::io::Error| GitError::FileOpError(format!("{:?}", e));
+        let idx = fs::read(idx_path).map_err(io)?;
+        let bad = || GitError::ObjectError(format!("bad pack index {}", idx_path.display()));
+        let u32_at = |at: usize| {
+            idx.get(at..at + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        if !idx.starts_with(b"\xfftOc") || u32_at(4) != Some(2) {
+            return Err(bad());
+        }
+        // the last fan-out entry counts every object
+        let fan_out = 8;
+        let count = u32_at(fan_out + 255 * 4).ok_or_else(bad)? as usize;
+        let ids = fan_out + 256 * 4;
+        let offsets_at = ids + count * (20 + 4);
+        let large_offsets = offsets_at + count * 4;
+        let mut offsets = HashMap::with_capacity(count.min(idx.len() / 28));
+        for i in 0..count {
+            let id = idx.get(ids + i * 20..ids + (i + 1) * 20).ok_or_else(bad)?;
+            let offset = u32_at(offsets_at + i * 4).ok_or_else(bad)?;
+            let offset = match offset & 0x8000_0000 {
+                0 => offset as u64,
+                _ => {
+                    let at = large_offsets + (offset & 0x7fff_ffff) as usize * 8;
+                    let b = idx.get(at..at + 8).ok_or_else(bad)?;
+                    u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+                }
+            };
+            offsets.insert(hex_string(id), offset as usize);
+        }
+        let mut file = fs::File::open(&pack_path).map_err(io)?;
+        let mut signature = [0u8; 4];
+        if file.read_exact(&mut signature).is_err() || &signature != b"PACK" {
+            return Err(GitError::ObjectError(format!(
+                "bad pack {}",
+                pack_path.display()
+            )));
+        }
+        Ok(Self {
+            file,
+            offsets,
+            bases: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// object at the offset, deltas against objects elsewhere in the database resolved
+    /// 1. walk back along the delta bases to a cached or whole object
+    /// 2. apply the deltas on the way forward, keeping each base for later reads
+    fn read_at(
+        &self,
+        offset: usize,
+        db: &ObjectDatabase,
+    ) -> Result<(ObjectKind, Vec<u8>), GitError> {
+        let bad = || GitError::ObjectError(format!("bad pack entry at {}", offset));
+        // deltas on the way back, with the offset of their base when it is in this pack
+        let mut deltas: Vec<(usize, PackEntry, Option<usize>)> = vec![];
+        let mut at = offset;
+        let (kind, mut content) = loop {
+            if let Some(object) = self.bases.borrow().get(&at) {
+                break object.clone();
+            }
+            let entry = self.entry_at(at)?;
+            let base = match &entry.base {
+                None => {
+                    let content = self.inflate(at, &entry)?;
+                    break (object_kind(entry.kind).ok_or_else(bad)?, content);
+                }
+                Some(EntryBase::Offset(base)) => *base,
+                Some(EntryBase::Id(id)) => match self.offsets.get(id) {
+                    Some(base) => *base,
+                    None => {
+                        let base = db.read(id)?;
+                        deltas.push((at, entry, None));
+                        break base;
+                    }
+                },
+            };
+            // a base met twice never resolves
+            if base == offset || deltas.iter().any(|(delta, _, _)| *delta == base) {
+                return Err(bad());
+            }
+            deltas.push((at, entry, Some(base)));
+            at = base;
+        };
+        while let Some((at, entry, base)) = deltas.pop() {
+            if let Some(base) = base {
+                self.keep_base(base, kind, &content);
+            }
+            content = apply_delta(&content, &self.inflate(at, &entry)?)?;
+        }
+        Ok((kind, content))
+    }
+
+    /// header of the entry at the offset
+    fn entry_at(&self, offset: usize) -> Result<PackEntry, GitError> {
+        let bad = || GitError::ObjectError(format!("bad pack entry at {}", offset));
+        // type and size, then a delta base: at most 10 + 10 or 10 + 20 bytes
+        let mut header = Vec::with_capacity(32);
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| file.take(32).read_to_end(&mut header))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let byte_at = |at: usize| header.get(at).copied().ok_or_else(bad);
+        // type and size: 3 type bits and 4 size bits, then 7 size bits a byte
+        let mut pos = 0;
+        let mut byte = byte_at(pos)?;
+        let kind = (byte >> 4) & 7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            pos += 1;
+            byte = byte_at(pos)?;
+            size |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or_else(bad)?;
+            shift += 7;
+        }
+        pos += 1;
+        let base = match kind {
+            // OFS_DELTA: distance back to the base, before this entry and after the pack header
+            6 => {
+                byte = byte_at(pos)?;
+                let mut distance = (byte & 0x7f) as usize;
+                while byte & 0x80 != 0 {
+                    pos += 1;
+                    byte = byte_at(pos)?;
+                    distance = distance
+                        .checked_add(1)
+                        .and_then(|d| d.checked_mul(1 << 7))
+                        .ok_or_else(bad)?
+                        | (byte & 0x7f) as usize;
+                }
+                pos += 1;
+                if distance == 0 || distance >= offset {
+                    return Err(bad());
+                }
+                Some(EntryBase::Offset(offset - distance))
+            }
+            // REF_DELTA: id of the base
+            7 => {
+                let id = header.get(pos..pos + 20).ok_or_else(bad)?;
+                pos += 20;
+                Some(EntryBase::Id(hex_string(id)))
+            }
+            _ => None,
+        };
+        Ok(PackEntry {
+            kind,
+            size,
+            base,
+            data: offset + pos,
+        })
+    }
+
+    /// inflated data of the entry at the offset, exactly as long as its header says
+    fn inflate(&self, offset: usize, entry: &PackEntry) -> Result<Vec<u8>, GitError> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(entry.data as u64))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let mut content = Vec::with_capacity(entry.size.min(MAX_RESERVE));
+        ZlibDecoder::new(file)
+            .take(entry.size as u64)
+            .read_to_end(&mut content)
+            .map_err(|e| GitError::ObjectError(format!("pack entry at {}: {:?}", offset, e)))?;
+        if content.len() != entry.size {
+            return Err(GitError::ObjectError(format!(
+                "bad pack entry at {}",
+                offset
+            )));
+        }
+        Ok(content)
+    }
+
+    fn keep_base(&self, offset: usize, kind: ObjectKind, content: &[u8]) {
+        let mut bases = self.bases.borrow_mut();
+        if bases.len() >= BASE_CACHE_SIZE {
+            bases.clear();
+        }
+        bases.insert(offset, (kind, content.to_vec()));
+    }
+}
+
+/// kind of a whole object from its pack entry type
+fn object_kind(kind: u8) -> Option<ObjectKind> {
+    match kind {
+        1 => Some(ObjectKind::Commit),
+        2 => Some(ObjectKind::Tree),
+        3 => Some(ObjectKind::Blob),
+        4 => Some(ObjectKind::Tag),
+        _ => None,
+    }
+}
+
+/// Rebuild an object from its base and a Git delta: the two sizes, then instructions that
+/// copy a range of the base or insert the bytes that follow them
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, GitError> {
+    let bad = || GitError::ObjectError("bad delta".to_string());
+    let mut pos = 0;
+    let mut size = || {
+        let mut size = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(pos).ok_or_else(bad)?;
+            pos += 1;
+            size |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .ok_or_else(bad)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok::<usize, GitError>(size);
+            }
+        }
+    };
+    let (base_size, size) = (size()?, size()?);
+    if base_size != base.len() {
+        return Err(bad());
+    }
+    let mut out = Vec::with_capacity(size.min(MAX_RESERVE));
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 == 0 {
+            // op 0 is reserved
+            let data = delta.get(pos..pos + op as usize).filter(|_| op != 0);
+            out.extend_from_slice(data.ok_or_else(bad)?);
+            pos += op as usize;
+            continue;
+        }
+        // bits 0-3 flag the offset bytes present, bits 4-6 the size bytes
+        let mut fields = [0usize; 2];
+        for (bit, field) in (0..7).map(|bit| (bit, if bit < 4 { 0 } else { 1 })) {
+            if op & (1 << bit) != 0 {
+                let byte = *delta.get(pos).ok_or_else(bad)? as usize;
+                pos += 1;
+                fields[field] |= byte << (8 * (bit % 4));
+            }
+        }
+        let [start, len] = fields;
+        let len = if len == 0 { 0x10000 } else { len };
+        out.extend_from_slice(base.get(start..start + len).ok_or_else(bad)?);
+    }
+    if out.len() != size {
+        return Err(bad());
+    }
+    Ok(out)
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Fields of a commit object; other headers, e.g. `gpgsig`, are left out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitObject {
+    pub tree: String,
+    pub parents: Vec<String>,
+    /// `Name <email>`
+    pub author: String,
+    pub committer: String,
+    /// seconds since the epoch the commit was made at
+    pub date_time: i64,
+    pub message: String,
+}
+
+/// Fields of an annotated tag object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagObject {
+    pub object: String,
+    pub kind: String,
+    pub name: String,
+    pub tagger: String,
+    pub date_time: i64,
+    pub message: String,
+}
+
+/// headers of a commit or tag object and its message, continuation lines dropped
+fn parse_headers(content: &[u8]) -> (Vec<(String, String)>, String) {
+    let text = String::from_utf8_lossy(content);
+    let (head, message) = text.split_once("\n\n").unwrap_or((&text, ""));
+    let headers = head
+        .lines()
+        .filter(|line| !line.starts_with(' '))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    (headers, message.to_string())
+}
+
+/// `Name <email>` and the time of a `Name <email> <seconds> <zone>` line
+fn parse_person(value: &str) -> (String, i64) {
+    let mut parts = value.rsplitn(3, ' ');
+    let (_zone, time, person) = (parts.next(), parts.next(), parts.next());
+    match (person, time.and_then(|time| time.parse().ok())) {
+        (Some(person), Some(time)) => (person.to_string(), time),
+        _ => (value.to_string(), 0),
+    }
+}
+
+pub fn parse_commit(id: &str, content: &[u8]) -> Result<CommitObject, GitError> {
+    let (headers, message) = parse_headers(content);
+    let mut commit = CommitObject {
+        tree: String::new(),
+        parents: vec![],
+        author: String::new(),
+        committer: String::new(),
+        date_time: 0,
+        message,
+    };
+    for (name, value) in headers.iter() {
+        match name.as_str() {
+            "tree" => commit.tree = value.to_string(),
+            "parent" => commit.parents.push(value.to_string()),
+            "author" => commit.author = parse_person(value).0,
+            "committer" => (commit.committer, commit.date_time) = parse_person(value),
+            _ => {}
+        }
+    }
+    if commit.tree.is_empty() {
+        return Err(GitError::ObjectError(format!("bad commit {}", id)));
+    }
+    Ok(commit)
+}
+
+pub fn parse_tag(id: &str, content: &[u8]) -> Result<TagObject, GitError> {
+    let (headers, message) = parse_headers(content);
+    let mut tag = TagObject {
+        object: String::new(),
+        kind: String::new(),
+        name: String::new(),
+        tagger: String::new(),
+        date_time: 0,
+        message,
+    };
+    for (name, value) in headers.iter() {
+        match name.as_str() {
+            "object" => tag.object = value.to_string(),
+            "type" => tag.kind = value.to_string(),
+            "tag" => tag.name = value.to_string(),
+            "tagger" => (tag.tagger, tag.date_time) = parse_person(value),
+            _ => {}
+        }
+    }
+    if tag.object.is_empty() {
+        return Err(GitError::ObjectError(format!("bad tag {}", id)));
+    }
+    Ok(tag)
+}
+
+/// Entries of a tree object. Submodules, which have no counterpart here, are left out.
+pub fn parse_tree(id: &str, content: &[u8]) -> Result<Vec<TreeEntry>, GitError> {
+    let bad = || GitError::ObjectError(format!("bad tree {}", id));
+    let mut entries = vec![];
+    let mut rest = content;
+    while !rest.is_empty() {
+        let nul = rest.iter().position(|b| *b == 0).ok_or_else(bad)?;
+        let header = std::str::from_utf8(&rest[..nul]).map_err(|_| bad())?;
+        let (mode, name) = header.split_once(' ').ok_or_else(bad)?;
+        let object = rest.get(nul + 1..nul + 21).ok_or_else(bad)?;
+        rest = &rest[nul + 21..];
+        let mode = match mode {
+            "40000" | "040000" => None,
+            "100755" => Some(FileMode::Executable),
+            "120000" => Some(FileMode::Symlink),
+            "160000" => continue,
+            mode if mode.starts_with("100") => Some(FileMode::Regular),
+            _ => return Err(bad()),
+        };
+        entries.push(TreeEntry {
+            name: name.to_string(),
+            mode,
+            id: hex_string(object),
+        });
+    }
+    Ok(entries)
+}
+
+/// Refs of a Git directory, name --> object id: `packed-refs` and the files under `refs`,
+/// which win. Symbolic refs are left out.
+pub fn read_refs(git_dir: &Path) -> Result<BTreeMap<String, String>, GitError> {
+    let mut refs = BTreeMap::new();
+    if let Ok(packed) = fs::read_to_string(git_dir.join("packed-refs")) {
+        for line in packed.lines() {
+            if line.starts_with('#') || line.starts_with('^') {
+                continue;
+            }
+            if let Some((id, name)) = line.split_once(' ') {
+                refs.insert(name.to_string(), id.to_string());
+            }
+        }
+    }
+    let mut dirs = vec![git_dir.join("refs")];
+    while let Some(dir) = dirs.pop() {
+        let Ok(files) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for file in files.filter_map(|file| file.ok()) {
+            let path = file.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let id =
+                fs::read_to_string(&path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let id = id.trim();
+            let name = path.strip_prefix(git_dir).unwrap_or(&path);
+            if id.len() == 40 && !id.starts_with("ref:") {
+                let name = name.to_string_lossy().replace('\\', "/");
+                refs.insert(name, id.to_string());
+            }
+        }
+    }
+    Ok(refs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn object_id_ut() {
+        // ids stock git gives these objects
+        assert_eq!(
+            "ce013625030ba8dba906f756967f9e9ca394464a",
+            object_id(ObjectKind::Blob, b"hello\n")
+        );
+        assert_eq!(
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+            object_id(ObjectKind::Tree, &tree_content(&[]).unwrap())
+        );
+        let blob = "ce013625030ba8dba906f756967f9e9ca394464a".to_string();
+        let entries = [
+            TreeEntry {
+                name: "a.txt".to_string(),
+                mode: Some(FileMode::Regular),
+                id: blob.clone(),
+            },
+            TreeEntry {
+                name: "a".to_string(),
+                mode: None,
+                id: object_id(ObjectKind::Tree, &tree_content(&[]).unwrap()),
+            },
+        ];
+        let content = tree_content(&entries).unwrap();
+        // "a.txt" sorts before "a/"
+        assert!(content.starts_with(b"100644 a.txt\0"));
+        assert!(tree_content(&[TreeEntry {
+            name: "x".to_string(),
+            mode: None,
+            id: "bad".to_string(),
+        }])
+        .is_err());
+    }
+
+    #[test]
+    fn store_ut() {
+        let dir = &env::current_dir().unwrap().join("gitobject_store_ut");
+        if dir.exists() {
+            assert!(fs::remove_dir_all(dir).is_ok());
+        }
+        let store = ObjectStore::new(dir);
+        let id = store.write(ObjectKind::Blob, b"hello\n").unwrap();
+        assert_eq!("ce013625030ba8dba906f756967f9e9ca394464a", id);
+        assert!(dir.join("ce").join(&id[2..]).is_file());
+        assert_eq!(
+            (ObjectKind::Blob, b"hello\n".to_vec()),
+            store.read(&id).unwrap()
+        );
+        assert_eq!(id, store.write(ObjectKind::Blob, b"hello\n").unwrap());
+        assert!(store.read(&"0".repeat(40)).is_err());
+        assert!(fs::remove_dir_all(dir).is_ok());
+    }
+
+    #[test]
+    fn pack_file_ut() {
+        let dir = &env::current_dir().unwrap().join("gitobject_pack_file_ut");
+        if dir.exists() {
+            assert!(fs::remove_dir_all(dir).is_ok());
+        }
+        assert!(fs::create_dir_all(dir.join("pack")).is_ok());
+        let deflate = |data: &[u8]| {
+            let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+            assert!(encoder.write_all(data).is_ok());
+            encoder.finish().unwrap()
+        };
+        // a pack of the entries and an index giving each its id, ids in order
+        let write_pack = |entries: &[(&str, Vec<u8>)]| {
+            let mut pack = b"PACK".to_vec();
+            pack.extend(2u32.to_be_bytes());
+            pack.extend((entries.len() as u32).to_be_bytes());
+            let mut offsets = vec![];
+            for (_, entry) in entries {
+                offsets.push(pack.len() as u32);
+                pack.extend(entry);
+            }
+            let mut idx = b"\xfftOc".to_vec();
+            idx.extend(2u32.to_be_bytes());
+            for byte in 0..256 {
+                let count = entries
+                    .iter()
+                    .filter(|(id, _)| hex_bytes(id).unwrap()[0] <= byte as u8);
+                idx.extend((count.count() as u32).to_be_bytes());
+            }
+            for (id, _) in entries {
+                idx.extend(hex_bytes(id).unwrap());
+            }
+            idx.extend(vec![0u8; entries.len() * 4]);
+            for offset in offsets {
+                idx.extend(offset.to_be_bytes());
+            }
+            assert!(fs::write(dir.join("pack/pack-x.pack"), pack).is_ok());
+            assert!(fs::write(dir.join("pack/pack-x.idx"), idx).is_ok());
+        };
+        // "hello\n" as a blob, then deltas each copying their base whole
+        let blob = [vec![0x36], deflate(b"hello\n")].concat();
+        let delta = |distance: u8| [vec![0x64, distance], deflate(&[6, 6, 0x90, 6])].concat();
+        let (base, first, second) = ("1".repeat(40), "2".repeat(40), "3".repeat(40));
+        // the second delta builds on the first
+        let first_delta = delta(blob.len() as u8);
+        let second_delta = delta(first_delta.len() as u8);
+        write_pack(&[
+            (&base, blob.clone()),
+            (&first, first_delta),
+            (&second, second_delta),
+        ]);
+        let db = ObjectDatabase::open(dir).unwrap();
+        for id in [&base, &first, &second] {
+            assert_eq!(
+                (ObjectKind::Blob, b"hello\n".to_vec()),
+                db.read(id).unwrap()
+            );
+        }
+
+        // a delta on itself or on something before the pack starts never resolves
+        write_pack(&[
+            (&base, blob.clone()),
+            (&first, delta(0)),
+            (&second, delta(100)),
+        ]);
+        let db = ObjectDatabase::open(dir).unwrap();
+        assert!(db.read(&base).is_ok());
+        assert!(db.read(&first).is_err());
+        assert!(db.read(&second).is_err());
+        assert!(fs::remove_dir_all(dir).is_ok());
+    }
+}
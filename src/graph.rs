@@ -0,0 +1,83 @@
+use crate::repo::Commit;
+use std::collections::BTreeMap;
+
+/// escape a string for a double quoted DOT id
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Render the commit graph in Graphviz DOT format, e.g. `git-rs log --format=dot | dot -Tsvg`
+/// commits: commit sha1 --> commit, nodes are labeled with the abbreviated sha1 and subject
+/// refs: ref label --> commit sha1, e.g. `main` or `tag: v1.0.0`, drawn as boxes
+pub fn render_dot(commits: &BTreeMap<String, Commit>, refs: &BTreeMap<String, String>) -> String {
+    let mut out = vec![
+        "digraph commits {".to_string(),
+        "    rankdir=\"RL\";".to_string(),
+        "    node [shape=ellipse, fontname=\"monospace\"];".to_string(),
+    ];
+    for (sha1, commit) in commits.iter() {
+        let subject = commit.message().lines().next().unwrap_or("");
+        out.push(format!(
+            "    \"{}\" [label=\"{} {}\"];",
+            sha1,
+            &sha1[..sha1.len().min(8)],
+            escape(subject)
+        ));
+    }
+    for (sha1, commit) in commits.iter() {
+        for parent in commit.parents() {
+            out.push(format!("    \"{}\" -> \"{}\";", sha1, parent));
+        }
+    }
+    for (label, sha1) in refs.iter() {
+        if commits.contains_key(sha1) {
+            out.push(format!(
+                "    \"ref:{}\" [label=\"{}\", shape=box, style=filled, fillcolor=\"lightgrey\"];",
+                escape(label),
+                escape(label)
+            ));
+            out.push(format!("    \"ref:{}\" -> \"{}\";", escape(label), sha1));
+        }
+    }
+    out.push("}".to_string());
+    out.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_dot_ut() {
+        let mut first = Commit::new();
+        first.set_message("initial commit");
+        let mut second = Commit::new();
+        second.set_message("fix \"quoted\" bug\n\nbody");
+        second.set_parent("a".repeat(40).as_str());
+        let commits = BTreeMap::from([("a".repeat(40), first), ("b".repeat(40), second)]);
+        let refs = BTreeMap::from([
+            ("main".to_string(), "b".repeat(40)),
+            ("tag: v1".to_string(), "a".repeat(40)),
+            ("gone".to_string(), "c".repeat(40)),
+        ]);
+        let a = "a".repeat(40);
+        let b = "b".repeat(40);
+        assert_eq!(
+            format!(
+                r#"digraph commits {{
+    rankdir="RL";
+    node [shape=ellipse, fontname="monospace"];
+    "{a}" [label="aaaaaaaa initial commit"];
+    "{b}" [label="bbbbbbbb fix \"quoted\" bug"];
+    "{b}" -> "{a}";
+    "ref:main" [label="main", shape=box, style=filled, fillcolor="lightgrey"];
+    "ref:main" -> "{b}";
+    "ref:tag: v1" [label="tag: v1", shape=box, style=filled, fillcolor="lightgrey"];
+    "ref:tag: v1" -> "{a}";
+}}
+"#
+            ),
+            render_dot(&commits, &refs)
+        );
+    }
+}
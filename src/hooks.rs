@@ -0,0 +1,114 @@
+use crate::error::GitError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// runs before the commit is made, a failure aborts the commit
+pub const PRE_COMMIT: &str = "pre-commit";
+/// runs with the path of the file holding the commit message, it may rewrite the message
+/// and a failure aborts the commit
+pub const COMMIT_MSG: &str = "commit-msg";
+/// runs after the commit is made, its exit status is ignored
+pub const POST_COMMIT: &str = "post-commit";
+
+/// Runs the executable scripts of a hooks directory, one file named after each hook.
+/// Hooks run in the working directory with `GIT_DIR`, `GIT_INDEX_FILE` and `GIT_WORK_TREE`
+/// set, their output goes to the terminal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookRunner {
+    dir: PathBuf,
+    work_dir: PathBuf,
+    env: Vec<(String, String)>,
+}
+
+impl HookRunner {
+    pub fn new(dir: &Path, work_dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            work_dir: work_dir.to_path_buf(),
+            env: vec![],
+        }
+    }
+
+    /// set an environment variable for every hook
+    pub fn env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// path of the hook when it is installed, i.e. an executable file
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        let path = self.dir.join(name);
+        let meta = fs::metadata(&path).ok()?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if meta.permissions().mode() & 0o111 == 0 {
+                return None;
+            }
+        }
+        meta.is_file().then_some(path)
+    }
+
+    /// Run a hook with the arguments, returns whether it was installed. A hook exiting
+    /// with a non-zero status is a `HookError`.
+    pub fn run(&self, name: &str, args: &[&str]) -> Result<bool, GitError> {
+        let path = match self.find(name) {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+        let status = Command::new(&path)
+            .args(args)
+            .current_dir(&self.work_dir)
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .status()
+            .map_err(|e| GitError::HookError(format!("cannot run {}: {:?}", name, e)))?;
+        if !status.success() {
+            return Err(GitError::HookError(format!(
+                "{} hook failed: {}",
+                name, status
+            )));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn install(dir: &Path, name: &str, script: &str) {
+        let path = dir.join(name);
+        assert!(fs::write(&path, format!("#!/bin/sh\n{}", script)).is_ok());
+        assert!(fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).is_ok());
+    }
+
+    #[test]
+    fn run_ut() {
+        let dir = &env::current_dir().unwrap().join("hook_run_ut");
+        if dir.exists() {
+            assert!(fs::remove_dir_all(dir).is_ok());
+        }
+        assert!(fs::create_dir(dir).is_ok());
+        let runner = HookRunner::new(dir, dir).env("HOOK_UT", "set");
+
+        assert!(!runner.run(PRE_COMMIT, &[]).unwrap());
+        install(dir, PRE_COMMIT, "echo \"$HOOK_UT $1\" > out\n");
+        assert!(runner.run(PRE_COMMIT, &["arg"]).unwrap());
+        assert_eq!("set arg\n", fs::read_to_string(dir.join("out")).unwrap());
+
+        install(dir, COMMIT_MSG, "exit 3\n");
+        let err = runner.run(COMMIT_MSG, &[]).unwrap_err();
+        assert!(format!("{:?}", err).contains("commit-msg hook failed"));
+
+        // scripts without the executable bit are not hooks
+        assert!(
+            fs::set_permissions(dir.join(COMMIT_MSG), fs::Permissions::from_mode(0o644)).is_ok()
+        );
+        assert!(runner.find(COMMIT_MSG).is_none());
+        assert!(!runner.run(COMMIT_MSG, &[]).unwrap());
+        assert!(fs::remove_dir_all(dir).is_ok());
+    }
+}
@@ -0,0 +1,501 @@
+use crate::error::GitError;
+use log::info;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// largest request body a server reads by default, requests of the web interface and of
+/// fetches are small
+pub const MAX_REQUEST_LEN: usize = 1 << 24;
+
+/// A parsed HTTP/1.1 request
+/// path is percent-decoded and does not contain the query string
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// parse request line, headers and a Content-Length delimited body of at most
+    /// `MAX_REQUEST_LEN` bytes
+    pub fn parse<R: BufRead>(reader: &mut R) -> Result<Self, GitError> {
+        let mut request = Self::parse_head(reader)?;
+        if !request.read_body(reader, Some(MAX_REQUEST_LEN))? {
+            return Err(GitError::HttpError(format!(
+                "request body of {} bytes is too large",
+                request.content_length()?
+            )));
+        }
+        Ok(request)
+    }
+
+    /// parse request line and headers, the body is left in the reader, see `read_body`
+    pub fn parse_head<R: BufRead>(reader: &mut R) -> Result<Self, GitError> {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+            _ => return Err(GitError::HttpError(format!("bad request line {:?}", line))),
+        };
+
+        let mut headers = BTreeMap::new();
+        loop {
+            let mut line = String::new();
+            let n = reader
+                .read_line(&mut line)
+                .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+            let line = line.trim_end();
+            if n == 0 || line.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                headers.insert(k.trim().to_lowercase(), v.trim().to_string());
+            }
+        }
+
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, query),
+            None => (target.as_str(), ""),
+        };
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.split_once('=') {
+                Some((k, v)) => (percent_decode(k), percent_decode(v)),
+                None => (percent_decode(pair), String::new()),
+            })
+            .collect();
+        Ok(Self {
+            method,
+            path: percent_decode(path),
+            query,
+            headers,
+            body: vec![],
+        })
+    }
+
+    /// length of the body from the Content-Length header, 0 without one
+    pub fn content_length(&self) -> Result<usize, GitError> {
+        self.headers.get("content-length").map_or(Ok(0), |len| {
+            len.parse::<usize>()
+                .map_err(|e| GitError::HttpError(format!("{:?}", e)))
+        })
+    }
+
+    /// Read the body following the head, unless it is longer than `limit` bytes, None for no
+    /// limit: it is then read and discarded so the client can read the answer, and false is
+    /// returned.
+    pub fn read_body<R: BufRead>(
+        &mut self,
+        reader: &mut R,
+        limit: Option<usize>,
+    ) -> Result<bool, GitError> {
+        let len = self.content_length()?;
+        if limit.is_some_and(|limit| len > limit) {
+            io::copy(&mut reader.take(len as u64), &mut io::sink())
+                .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+            return Ok(false);
+        }
+        self.body.resize(len, 0);
+        reader
+            .read_exact(&mut self.body)
+            .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+        Ok(true)
+    }
+
+    /// path split into non-empty segments
+    pub fn segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|s| !s.is_empty()).collect()
+    }
+}
+
+/// A HTTP response with a full in-memory body
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type: content_type.to_string(),
+            body,
+        }
+    }
+
+    pub fn html(body: String) -> Self {
+        Self::new(200, "text/html; charset=utf-8", body.into_bytes())
+    }
+
+    pub fn text(status: u16, body: &str) -> Self {
+        Self::new(
+            status,
+            "text/plain; charset=utf-8",
+            body.as_bytes().to_vec(),
+        )
+    }
+
+    pub fn json(status: u16, body: String) -> Self {
+        Self::new(status, "application/json", body.into_bytes())
+    }
+
+    pub fn not_found() -> Self {
+        Self::text(404, "Not Found")
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+
+    /// parse status line, headers and a body delimited by Content-Length or the end of the
+    /// stream
+    pub fn parse<R: BufRead>(reader: &mut R) -> Result<Self, GitError> {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| GitError::HttpError(format!("bad status line {:?}", line)))?;
+        let mut headers = BTreeMap::new();
+        loop {
+            let mut line = String::new();
+            let n = reader
+                .read_line(&mut line)
+                .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+            let line = line.trim_end();
+            if n == 0 || line.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                headers.insert(k.trim().to_lowercase(), v.trim().to_string());
+            }
+        }
+        let mut body = vec![];
+        match headers.get("content-length") {
+            Some(len) => {
+                let len = len
+                    .parse::<usize>()
+                    .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+                body.resize(len, 0);
+                reader.read_exact(&mut body)
+            }
+            None => reader.read_to_end(&mut body).map(|_| ()),
+        }
+        .map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+        Ok(Self {
+            status,
+            content_type: headers.remove("content-type").unwrap_or_default(),
+            body,
+        })
+    }
+
+    /// write status line, headers and body
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), GitError> {
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )
+        .and_then(|_| writer.write_all(&self.body))
+        .and_then(|_| writer.flush())
+        .map_err(|e| GitError::HttpError(format!("{:?}", e)))
+    }
+}
+
+/// decode %XX escapes and '+' in a url component
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b'+' => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Send a request to a `http://host[:port]/path` url and read the whole response.
+/// `https://` urls are sent over TLS when git-rs is built with the `tls` feature, the server
+/// certificate is checked against the Mozilla root certificates.
+pub fn send(method: &str, url: &str, body: &[u8]) -> Result<Response, GitError> {
+    let (tls, rest) = match (url.strip_prefix("http://"), url.strip_prefix("https://")) {
+        (Some(rest), _) => (false, rest),
+        (_, Some(rest)) => (true, rest),
+        _ => return Err(GitError::HttpError(format!("not a http url: {}", url))),
+    };
+    let (host, path) = match rest.find('/') {
+        Some(at) => (&rest[..at], &rest[at..]),
+        None => (rest, "/"),
+    };
+    let addr = match (host.contains(':'), tls) {
+        (true, _) => host.to_string(),
+        (false, false) => format!("{}:80", host),
+        (false, true) => format!("{}:443", host),
+    };
+    let stream =
+        TcpStream::connect(&addr).map_err(|e| GitError::HttpError(format!("{}: {:?}", url, e)))?;
+    let head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method,
+        path,
+        host,
+        body.len()
+    );
+    let exchange = |mut stream: Box<dyn ReadWrite>| {
+        stream
+            .write_all(head.as_bytes())
+            .and_then(|_| stream.write_all(body))
+            .and_then(|_| stream.flush())
+            .map_err(|e| GitError::HttpError(format!("{}: {:?}", url, e)))?;
+        Response::parse(&mut BufReader::new(stream))
+    };
+    match tls {
+        false => exchange(Box::new(stream)),
+        true => {
+            let name = host.rsplit_once(':').map_or(host, |(name, _)| name);
+            exchange(tls_stream(name, stream)?)
+        }
+    }
+}
+
+/// a stream a request is written to and its response read from, plain or TLS
+trait ReadWrite: Read + Write {}
+
+impl<T: Read + Write> ReadWrite for T {}
+
+/// client side of a TLS connection to the server `name` over the connected stream
+#[cfg(feature = "tls")]
+fn tls_stream(name: &str, stream: TcpStream) -> Result<Box<dyn ReadWrite>, GitError> {
+    use rustls::pki_types::ServerName;
+    use std::sync::Arc;
+    let tls_error = |e: &dyn std::fmt::Debug| GitError::HttpError(format!("{}: {:?}", name, e));
+    let roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .map_err(|e| tls_error(&e))?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let server = ServerName::try_from(name.to_string()).map_err(|e| tls_error(&e))?;
+    let connection =
+        rustls::ClientConnection::new(Arc::new(config), server).map_err(|e| tls_error(&e))?;
+    Ok(Box::new(rustls::StreamOwned::new(connection, stream)))
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_stream(name: &str, _stream: TcpStream) -> Result<Box<dyn ReadWrite>, GitError> {
+    Err(GitError::HttpError(format!(
+        "https needs git-rs built with the tls feature: {}",
+        name
+    )))
+}
+
+/// serve requests one at a time on the given address until the process exits, request
+/// bodies are at most `MAX_REQUEST_LEN` bytes
+pub fn serve<F: FnMut(&Request) -> Response>(addr: &str, handler: F) -> Result<(), GitError> {
+    serve_with(addr, |_| Some(MAX_REQUEST_LEN), handler)
+}
+
+/// `serve` with the largest body `limit` allows for each request, None for no limit
+pub fn serve_with<L, F>(addr: &str, limit: L, handler: F) -> Result<(), GitError>
+where
+    L: Fn(&Request) -> Option<usize>,
+    F: FnMut(&Request) -> Response,
+{
+    let listener = TcpListener::bind(addr).map_err(|e| GitError::HttpError(format!("{:?}", e)))?;
+    serve_on(listener, limit, handler)
+}
+
+/// Serve requests one at a time on a bound listener until the process exits. `limit` gets
+/// the request without its body, a longer body is answered with 413.
+pub fn serve_on<L, F>(listener: TcpListener, limit: L, mut handler: F) -> Result<(), GitError>
+where
+    L: Fn(&Request) -> Option<usize>,
+    F: FnMut(&Request) -> Response,
+{
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                info!("accept failed: {:?}", e);
+                continue;
+            }
+        };
+        let response = respond(&mut BufReader::new(&mut stream), &limit, &mut handler);
+        if let Err(e) = response.write_to(&mut stream) {
+            info!("write response failed: {:?}", e);
+        }
+    }
+    Ok(())
+}
+
+/// read a request and answer it: 400 when it can not be parsed, 413 when its body is longer
+/// than `limit` allows
+fn respond<R, L, F>(reader: &mut R, limit: &L, handler: &mut F) -> Response
+where
+    R: BufRead,
+    L: Fn(&Request) -> Option<usize>,
+    F: FnMut(&Request) -> Response,
+{
+    let mut request = match Request::parse_head(reader) {
+        Ok(request) => request,
+        Err(e) => return Response::text(400, &e.to_string()),
+    };
+    let limit = limit(&request);
+    match request.read_body(reader, limit) {
+        Ok(true) => {
+            info!("{} {}", request.method, request.path);
+            handler(&request)
+        }
+        Ok(false) => Response::text(
+            413,
+            &format!(
+                "request body of {} bytes is larger than the limit of {} bytes",
+                request.content_length().unwrap_or_default(),
+                limit.unwrap_or_default()
+            ),
+        ),
+        Err(e) => Response::text(400, &e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request_ut() {
+        let raw = "GET /file/main/src%20dir/a.rs?x=1&y=a+b HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::parse(&mut raw.as_bytes()).unwrap();
+        assert_eq!("GET", request.method);
+        assert_eq!("/file/main/src dir/a.rs", request.path);
+        assert_eq!(vec!["file", "main", "src dir", "a.rs"], request.segments());
+        assert_eq!("1", request.query.get("x").unwrap());
+        assert_eq!("a b", request.query.get("y").unwrap());
+        assert_eq!("localhost", request.headers.get("host").unwrap());
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn parse_request_body_ut() {
+        let raw = "POST /x HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+        let request = Request::parse(&mut raw.as_bytes()).unwrap();
+        assert_eq!(b"hello".to_vec(), request.body);
+        assert!(Request::parse(&mut "".as_bytes()).is_err());
+        let raw = format!(
+            "POST /x HTTP/1.1\r\nContent-Length: {}\r\n\r\nhello",
+            usize::MAX
+        );
+        assert!(Request::parse(&mut raw.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn send_ut() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/echo?x=1", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            serve_on(
+                listener,
+                |request| match request.path.as_str() {
+                    "/large" => None,
+                    _ => Some(MAX_REQUEST_LEN),
+                },
+                |request| {
+                    let body =
+                        format!("{} {} {}", request.method, request.path, request.body.len());
+                    Response::text(200, &body)
+                },
+            )
+        });
+        let response = send("POST", &url, b"hello").unwrap();
+        assert_eq!(200, response.status);
+        assert_eq!("text/plain; charset=utf-8", response.content_type);
+        assert_eq!(b"POST /echo 5".to_vec(), response.body);
+
+        // a body over the limit is read and refused, routes without a limit take it
+        let large = vec![b'x'; MAX_REQUEST_LEN + 1];
+        let response = send("POST", &url, &large).unwrap();
+        assert_eq!(413, response.status);
+        assert_eq!(
+            format!(
+                "request body of {} bytes is larger than the limit of {} bytes",
+                MAX_REQUEST_LEN + 1,
+                MAX_REQUEST_LEN
+            )
+            .into_bytes(),
+            response.body
+        );
+        let url = url.replace("/echo?x=1", "/large");
+        let response = send("POST", &url, &large).unwrap();
+        assert_eq!(
+            format!("POST /large {}", MAX_REQUEST_LEN + 1).into_bytes(),
+            response.body
+        );
+        // a plain http server does not speak TLS
+        let err = send("GET", &url.replace("http://", "https://"), b"")
+            .unwrap_err()
+            .to_string();
+        match cfg!(feature = "tls") {
+            true => assert!(!err.contains("tls feature"), "{}", err),
+            false => assert!(err.contains("https needs git-rs built with the tls feature")),
+        }
+        assert!(send("GET", "ftp://localhost/", b"").is_err());
+
+        let raw = "HTTP/1.1 404 Not Found\r\n\r\nno length";
+        let response = Response::parse(&mut raw.as_bytes()).unwrap();
+        assert_eq!(
+            (404, b"no length".to_vec()),
+            (response.status, response.body)
+        );
+    }
+
+    #[test]
+    fn write_response_ut() {
+        let mut out = vec![];
+        assert!(Response::text(404, "nope").write_to(&mut out).is_ok());
+        assert_eq!(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 4\r\nConnection: close\r\n\r\nnope",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
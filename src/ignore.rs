@@ -0,0 +1,282 @@
+use crate::error::GitError;
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+/// name of the per-directory ignore files
+pub const IGNORE_FILE: &str = ".gitignore";
+
+/// Where an ignore pattern comes from, for reporting which rule matched a path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// ignore file the pattern was read from
+    pub source: String,
+    /// line number of the pattern in its file, from 1
+    pub line: usize,
+    /// the pattern as written
+    pub pattern: String,
+    /// `!pattern`, re-includes what an earlier pattern excluded
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    rule: Rule,
+    regex: Regex,
+    /// `pattern/` only matches directories
+    dir_only: bool,
+    /// directory of the ignore file relative to the root, empty for the root
+    base: String,
+}
+
+impl Pattern {
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative = if self.base.is_empty() {
+            Some(path)
+        } else {
+            path.strip_prefix(&self.base)
+                .and_then(|rest| rest.strip_prefix('/'))
+        };
+        relative.is_some_and(|relative| self.regex.is_match(relative))
+    }
+}
+
+/// Ignore rules of `.gitignore` style files, paths are relative to the working directory root
+/// and use `/` separators.
+/// ```text
+/// # comment
+/// *.log        any file or directory named like this, at any depth
+/// /todo.txt    a leading or inner slash anchors the pattern to the file's directory
+/// build/       a trailing slash only matches directories
+/// docs/**/*.md `**` matches any number of directories
+/// !keep.log    re-include a path an earlier pattern excluded
+/// ```
+/// the last matching pattern wins, and nothing inside an excluded directory is re-included.
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    patterns: Vec<Pattern>,
+}
+
+impl Ignore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add the patterns of an ignore file living in the `base` directory, e.g. `src` for
+    /// `src/.gitignore` and an empty string for the root
+    pub fn add_patterns(
+        &mut self,
+        content: &str,
+        base: &str,
+        source: &str,
+    ) -> Result<(), GitError> {
+        for (no, line) in content.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, glob) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, glob) = match glob.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, glob),
+            };
+            if glob.is_empty() {
+                continue;
+            }
+            let regex = path_regex(glob)
+                .map_err(|e| GitError::IgnoreError(format!("{}:{}: {:?}", source, no + 1, e)))?;
+            self.patterns.push(Pattern {
+                rule: Rule {
+                    source: source.to_string(),
+                    line: no + 1,
+                    pattern: line.to_string(),
+                    negated,
+                },
+                regex,
+                dir_only,
+                base: base.trim_matches('/').to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// add the patterns of an ignore file, a missing file adds nothing
+    pub fn add_file(&mut self, path: &Path, base: &str) -> Result<(), GitError> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        let content =
+            fs::read_to_string(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        self.add_patterns(&content, base, &path.display().to_string())
+    }
+
+    /// The rule deciding whether the path is ignored: the rule excluding one of its parent
+    /// directories, or else the last pattern matching the path, None when nothing matches
+    pub fn matching(&self, path: &str, is_dir: bool) -> Option<&Rule> {
+        let path = path.trim_matches('/');
+        let mut end = 0;
+        while let Some(pos) = path[end..].find('/') {
+            end += pos;
+            if let Some(rule) = self.last_match(&path[..end], true) {
+                if !rule.negated {
+                    return Some(rule);
+                }
+            }
+            end += 1;
+        }
+        self.last_match(path, is_dir)
+    }
+
+    /// whether the path is ignored
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.matching(path, is_dir)
+            .is_some_and(|rule| !rule.negated)
+    }
+
+    fn last_match(&self, path: &str, is_dir: bool) -> Option<&Rule> {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(path, is_dir))
+            .map(|pattern| &pattern.rule)
+    }
+}
+
+/// Regex matching the paths a pattern selects relative to the directory of its file: a
+/// pattern with a leading or inner slash is anchored there, any other pattern matches a name
+/// at any depth
+pub(crate) fn path_regex(glob: &str) -> Result<Regex, regex::Error> {
+    match glob.strip_prefix('/') {
+        Some(glob) => Regex::new(&format!("^{}$", glob_regex(glob))),
+        None if glob.contains('/') => Regex::new(&format!("^{}$", glob_regex(glob))),
+        None => Regex::new(&format!("^(?:.*/)?{}$", glob_regex(glob))),
+    }
+}
+
+/// regex matching the same paths as a glob: `*` and `?` do not match `/`, `**` matches any
+/// number of directories, `[...]` is a character class and `\` escapes the next character
+fn glob_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let after_slash = i == 0 || chars[i - 1] == '/';
+                match chars.get(i + 2) {
+                    None if after_slash => {
+                        regex.push_str(".*");
+                        i += 2;
+                    }
+                    Some('/') if after_slash => {
+                        regex.push_str("(?:.*/)?");
+                        i += 3;
+                    }
+                    _ => {
+                        regex.push_str("[^/]*");
+                        i += 2;
+                    }
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().position(|c| *c == ']') {
+                Some(len) if len > 0 => {
+                    let class: String = chars[i + 1..i + 1 + len].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(rest) => format!("^{}", rest),
+                        None => class,
+                    };
+                    regex.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+                    i += len + 2;
+                    continue;
+                }
+                _ => regex.push_str("\\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_regex_ut() {
+        assert_eq!("[^/]*\\.log", glob_regex("*.log"));
+        assert_eq!("a/(?:.*/)?b", glob_regex("a/**/b"));
+        assert_eq!("(?:.*/)?b", glob_regex("**/b"));
+        assert_eq!("a/.*", glob_regex("a/**"));
+        assert_eq!("a[^/]*b", glob_regex("a**b"));
+        assert_eq!("f[^/][^0-9]\\[", glob_regex("f?[!0-9]["));
+        assert_eq!("\\*x", glob_regex("\\*x"));
+    }
+
+    #[test]
+    fn matching_ut() {
+        let mut ignore = Ignore::new();
+        let root = "# build output\n*.log\n!keep.log\nbuild/\n/todo.txt\ndocs/**/*.md\n\\!bang\n";
+        assert!(ignore.add_patterns(root, "", ".gitignore").is_ok());
+        assert!(ignore
+            .add_patterns("*.tmp\n!important.log\n/local\n", "sub", "sub/.gitignore")
+            .is_ok());
+
+        for (path, is_dir) in [
+            ("a.log", false),
+            ("x/y/a.log", false),
+            ("build", true),
+            ("x/build", true),
+            ("build/out.o", false),
+            ("todo.txt", false),
+            ("docs/a.md", false),
+            ("docs/x/y/a.md", false),
+            ("!bang", false),
+            ("sub/a.tmp", false),
+            ("sub/local", false),
+            ("sub/local/f", false),
+        ] {
+            assert!(ignore.is_ignored(path, is_dir), "{}", path);
+        }
+        for (path, is_dir) in [
+            ("keep.log", false),
+            ("x/keep.log", false),
+            ("build", false),
+            ("x/todo.txt", false),
+            ("docs.md", false),
+            ("a.tmp", false),
+            ("sub/important.log", false),
+            ("local", false),
+            ("sub/x/local", false),
+            ("src/main.rs", false),
+        ] {
+            assert!(!ignore.is_ignored(path, is_dir), "{}", path);
+        }
+
+        // nothing inside an excluded directory comes back
+        assert!(ignore.is_ignored("build/keep.log", false));
+        let rule = ignore.matching("build/keep.log", false).unwrap();
+        assert_eq!(("build/", 4), (rule.pattern.as_str(), rule.line));
+        let rule = ignore.matching("sub/important.log", false).unwrap();
+        assert_eq!(
+            ("sub/.gitignore", true),
+            (rule.source.as_str(), rule.negated)
+        );
+        assert!(ignore.matching("src/main.rs", false).is_none());
+
+        assert!(ignore.add_patterns("[z-a]\n", "", "bad").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::error::GitError;
+use crate::repo::{DiffOptions, GitRepository};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+const HELP: &str = "j/k move  s stage  u unstage  d diff  q quit";
+const DIFF_HELP: &str = "j/k scroll  d/q back";
+
+/// What a key pressed in `add -i` asks of the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// stage the changes of the file, its deletion included
+    Stage(String),
+    /// drop the staged changes of the file
+    Unstage(String),
+    /// show the diff of the file against HEAD
+    Diff(String),
+    Quit,
+}
+
+/// State of the `add -i` screen: the changed files as `status_codes` lists them, the
+/// selected one and the diff on show, if any
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AddScreen {
+    files: Vec<(char, char, String)>,
+    selected: usize,
+    diff: Option<String>,
+    scroll: u16,
+}
+
+impl AddScreen {
+    pub fn new(files: Vec<(char, char, String)>) -> Self {
+        Self {
+            files,
+            ..Default::default()
+        }
+    }
+
+    /// replace the files after the repository changed, the selection stays in the list
+    pub fn refresh(&mut self, files: Vec<(char, char, String)>) {
+        self.files = files;
+        self.selected = self.selected.min(self.files.len().saturating_sub(1));
+    }
+
+    /// show a diff, replaced by the file list on the next `d`, `q` or escape
+    pub fn show_diff(&mut self, diff: String) {
+        self.diff = Some(diff);
+        self.scroll = 0;
+    }
+
+    /// move or scroll for navigation keys, the action asked for by the others
+    pub fn key(&mut self, code: KeyCode) -> Option<Action> {
+        if self.diff.is_some() {
+            match code {
+                KeyCode::Char('j') | KeyCode::Down => self.scroll = self.scroll.saturating_add(1),
+                KeyCode::Char('k') | KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+                KeyCode::Char('d') | KeyCode::Char('q') | KeyCode::Esc => self.diff = None,
+                _ => {}
+            }
+            return None;
+        }
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.files.len().saturating_sub(1))
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            _ => {}
+        }
+        let path = self.files.get(self.selected)?.2.clone();
+        match code {
+            KeyCode::Char('s') | KeyCode::Char(' ') => Some(Action::Stage(path)),
+            KeyCode::Char('u') => Some(Action::Unstage(path)),
+            KeyCode::Char('d') | KeyCode::Enter => Some(Action::Diff(path)),
+            _ => None,
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        match &self.diff {
+            Some(diff) => {
+                let text = match diff.is_empty() {
+                    true => "no diff, untracked files are not compared",
+                    false => diff.as_str(),
+                };
+                frame.render_widget(
+                    Paragraph::new(text)
+                        .block(Block::default().borders(Borders::ALL).title("diff"))
+                        .scroll((self.scroll, 0)),
+                    main,
+                );
+                frame.render_widget(Line::from(DIFF_HELP), help);
+            }
+            None => {
+                let items: Vec<ListItem> = self
+                    .files
+                    .iter()
+                    .map(|(x, y, path)| ListItem::new(format!("{}{} {}", x, y, path)))
+                    .collect();
+                let list = List::new(items)
+                    .block(Block::default().borders(Borders::ALL).title("add -i"))
+                    .highlight_style(
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::REVERSED),
+                    );
+                let mut state = ListState::default().with_selected(Some(self.selected));
+                frame.render_stateful_widget(list, main, &mut state);
+                frame.render_widget(Line::from(HELP), help);
+            }
+        }
+    }
+}
+
+/// Interactive staging in the terminal: lists the modified, deleted and untracked files
+/// and stages, unstages or shows the diff of the selected one until `q`
+pub fn run(repo: &mut GitRepository) -> Result<(), GitError> {
+    let mut terminal =
+        ratatui::try_init().map_err(|e| GitError::StagedAddError(format!("terminal: {:?}", e)))?;
+    let result = event_loop(&mut terminal, repo);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, repo: &mut GitRepository) -> Result<(), GitError> {
+    let mut screen = AddScreen::new(repo.status_codes()?);
+    loop {
+        terminal
+            .draw(|frame| screen.draw(frame))
+            .map_err(|e| GitError::StagedAddError(format!("terminal: {:?}", e)))?;
+        let Event::Key(key) =
+            event::read().map_err(|e| GitError::StagedAddError(format!("terminal: {:?}", e)))?
+        else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match screen.key(key.code) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Stage(path)) => repo.add_all(&[path])?,
+            Some(Action::Unstage(path)) => repo.unstage(&[path])?,
+            Some(Action::Diff(path)) => {
+                let options = DiffOptions {
+                    paths: vec![path],
+                    ..Default::default()
+                };
+                // files added since HEAD only have a staged diff
+                let mut diff = repo.diff_with(&options)?;
+                if diff.is_empty() {
+                    diff = repo.diff_staged_with(&options)?;
+                }
+                screen.show_diff(diff);
+            }
+            None => {}
+        }
+        screen.refresh(repo.status_codes()?);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_screen_ut() {
+        let mut screen = AddScreen::new(vec![
+            ('M', ' ', "f1".to_string()),
+            (' ', 'D', "f2".to_string()),
+            ('?', '?', "f3".to_string()),
+        ]);
+        assert_eq!(
+            Some(Action::Stage("f1".to_string())),
+            screen.key(KeyCode::Char('s'))
+        );
+        assert_eq!(None, screen.key(KeyCode::Down));
+        assert_eq!(
+            Some(Action::Unstage("f2".to_string())),
+            screen.key(KeyCode::Char('u'))
+        );
+        assert_eq!(None, screen.key(KeyCode::Char('j')));
+        assert_eq!(None, screen.key(KeyCode::Char('j')));
+        assert_eq!(
+            Some(Action::Diff("f3".to_string())),
+            screen.key(KeyCode::Enter)
+        );
+
+        // keys scroll the diff until it is closed
+        screen.show_diff("diff".to_string());
+        assert_eq!(None, screen.key(KeyCode::Char('q')));
+        assert_eq!(Some(Action::Quit), screen.key(KeyCode::Char('q')));
+
+        screen.refresh(vec![('A', ' ', "f1".to_string())]);
+        assert_eq!(
+            Some(Action::Stage("f1".to_string())),
+            screen.key(KeyCode::Char(' '))
+        );
+        screen.refresh(vec![]);
+        assert_eq!(None, screen.key(KeyCode::Char('s')));
+    }
+}
@@ -1,4 +1,40 @@
+pub mod api;
+pub mod archive;
+pub mod attributes;
+pub mod bisect;
+pub mod blame;
+pub mod bundle;
+pub mod changelog;
+mod chunk;
 pub mod cmd;
+pub mod color;
+mod compress;
+pub mod config;
+pub mod conventional;
+pub mod daemon;
+pub mod diff;
+pub mod entry;
+pub mod eol;
 pub mod error;
+pub mod fastimport;
+pub mod gitobject;
+pub mod graph;
+pub mod hooks;
+pub mod http;
+pub mod ignore;
+pub mod interactive;
+mod materialize;
+pub mod merge;
+pub mod pack;
+pub mod patch;
+pub mod rebase;
+pub mod release;
+pub mod remote;
 pub mod repo;
+pub mod revision;
+pub mod signing;
+pub mod stash;
+pub mod transport;
 mod utils;
+pub mod web;
+pub mod worktree;
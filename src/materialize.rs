@@ -0,0 +1,106 @@
+use crate::entry::{self, Entry};
+use crate::error::GitError;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// default number of workers, bounded so big snapshots do not exhaust file handles
+pub fn default_workers() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(8)
+}
+
+/// Write the files of a snapshot under root with a bounded pool of workers.
+/// files: relative file path --> entry pairs, read_blob returns the content of the file
+/// at a path from its blob. Symbolic links are created as links.
+/// Parent directories are created up front in one pass so workers only write files.
+/// The first error stops the remaining work and is returned.
+pub fn write_files<F>(
+    root: &Path,
+    files: &[(String, Entry)],
+    workers: usize,
+    read_blob: F,
+) -> Result<(), GitError>
+where
+    F: Fn(&str, &str) -> Result<Vec<u8>, GitError> + Sync,
+{
+    let dirs: BTreeSet<&Path> = files
+        .iter()
+        .filter_map(|(path, _)| Path::new(path).parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect();
+    for dir in dirs {
+        fs::create_dir_all(root.join(dir))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    }
+
+    let next = AtomicUsize::new(0);
+    let error: Mutex<Option<GitError>> = Mutex::new(None);
+    let write = || loop {
+        let i = next.fetch_add(1, Ordering::Relaxed);
+        if i >= files.len() || error.lock().unwrap().is_some() {
+            return;
+        }
+        let (path, entry) = &files[i];
+        let res = read_blob(path, &entry.sha1)
+            .and_then(|content| entry::write_content(&root.join(path), &content, entry.mode));
+        if let Err(e) = res {
+            error.lock().unwrap().get_or_insert(e);
+        }
+    };
+    let workers = workers.clamp(1, files.len().max(1));
+    thread::scope(|s| {
+        for _ in 1..workers {
+            s.spawn(write);
+        }
+        write();
+    });
+    match error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn write_files_ut() {
+        let root = &env::current_dir().unwrap().join("write_files_ut");
+        if root.exists() {
+            assert!(fs::remove_dir_all(root).is_ok());
+        }
+        let files: Vec<(String, Entry)> = (0..100)
+            .map(|i| {
+                (
+                    format!("d{}/s{}/f{}", i % 3, i % 7, i),
+                    Entry::new(format!("content {}", i)),
+                )
+            })
+            .chain([("top".to_string(), Entry::new("top"))])
+            .collect();
+        // blob sha1 is its content in this test
+        assert!(write_files(root, &files, 4, |_, sha1| Ok(sha1.as_bytes().to_vec())).is_ok());
+        for (path, content) in files.iter() {
+            assert_eq!(content.sha1, fs::read_to_string(root.join(path)).unwrap());
+        }
+
+        let res = write_files(root, &files, 4, |_, sha1| {
+            if sha1 == "content 42" {
+                Err(GitError::FileNotExistError(sha1.to_string()))
+            } else {
+                Ok(sha1.as_bytes().to_vec())
+            }
+        });
+        assert!(matches!(res, Err(GitError::FileNotExistError(_))));
+        assert!(write_files(root, &[], 4, |_, sha1| Ok(sha1.as_bytes().to_vec())).is_ok());
+        assert!(fs::remove_dir_all(root).is_ok());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::entry::Entry;
+use crate::error::GitError;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Whole-tree merge strategy, `-s <strategy>`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// three-way merge of every file against the split point
+    #[default]
+    Resolve,
+    /// keep the current tree, the other branch is only recorded as parent
+    Ours,
+    /// take the tree of the other branch
+    Theirs,
+}
+
+/// Per-conflict tweak of the resolve strategy, `-X <option>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StrategyOption {
+    /// conflicting files take the current branch's version
+    Ours,
+    /// conflicting files take the other branch's version
+    Theirs,
+    /// changes in the amount of whitespace are not changes
+    IgnoreSpaceChange,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MergeOptions {
+    pub strategy: Strategy,
+    pub options: Vec<StrategyOption>,
+}
+
+/// Result of merging file path --> entry trees
+/// conflicts: file path --> (our entry, their entry), None when the side deleted the file
+#[derive(Debug, Default, PartialEq)]
+pub struct TreeMerge {
+    pub blobs: BTreeMap<String, Entry>,
+    pub conflicts: BTreeMap<String, (Option<Entry>, Option<Entry>)>,
+}
+
+/// What a merge changed, persisted so `merge --abort` can undo it while it is the last commit
+/// branch: the branch merged into, orig_head: its head before the merge,
+/// merge_head: the merge commit, empty until it is made,
+/// backups: tracked files that differed from orig_head in the working directory, path -->
+/// their content stored as a blob with their mode, None for files that were missing.
+/// The index was empty, merges refuse to start with staged changes.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeState {
+    pub branch: String,
+    pub orig_head: String,
+    pub merge_head: String,
+    pub backups: BTreeMap<String, Option<Entry>>,
+}
+
+/// collapse every run of whitespace into a single space and trim line ends, content that
+/// is not text is kept as is
+fn normalize_space(content: Vec<u8>) -> Vec<u8> {
+    match String::from_utf8(content) {
+        Ok(text) => text
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_bytes(),
+        Err(e) => e.into_bytes(),
+    }
+}
+
+impl MergeOptions {
+    fn has(&self, option: StrategyOption) -> bool {
+        self.options.contains(&option)
+    }
+
+    /// resolve a file changed differently on both sides, None if it stays conflicted
+    fn resolve_conflict<F>(
+        &self,
+        base: Option<&Entry>,
+        ours: Option<&Entry>,
+        theirs: Option<&Entry>,
+        read_blob: &F,
+    ) -> Result<Option<Option<Entry>>, GitError>
+    where
+        F: Fn(&str) -> Result<Vec<u8>, GitError>,
+    {
+        // a mode change is a change, whatever the content
+        let mode = |entry: Option<&Entry>| entry.map(|entry| entry.mode);
+        let same_modes = mode(ours) == mode(theirs) && mode(base) == mode(ours);
+        if self.has(StrategyOption::IgnoreSpaceChange) && same_modes {
+            let read = |entry: Option<&Entry>| -> Result<Option<Vec<u8>>, GitError> {
+                entry
+                    .map(|entry| read_blob(&entry.sha1).map(normalize_space))
+                    .transpose()
+            };
+            let (base_content, our_content, their_content) =
+                (read(base)?, read(ours)?, read(theirs)?);
+            if our_content == their_content || their_content == base_content {
+                return Ok(Some(ours.cloned()));
+            }
+            if our_content == base_content {
+                return Ok(Some(theirs.cloned()));
+            }
+        }
+        if self.has(StrategyOption::Ours) {
+            Ok(Some(ours.cloned()))
+        } else if self.has(StrategyOption::Theirs) {
+            Ok(Some(theirs.cloned()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Three-way merge of file trees against their split point.
+/// A file changed on one side only takes that side's version (including deletion),
+/// a file changed differently on both sides is resolved by the strategy options or
+/// reported as a conflict.
+pub fn merge_trees<F>(
+    base: &BTreeMap<String, Entry>,
+    ours: &BTreeMap<String, Entry>,
+    theirs: &BTreeMap<String, Entry>,
+    options: &MergeOptions,
+    read_blob: F,
+) -> Result<TreeMerge, GitError>
+where
+    F: Fn(&str) -> Result<Vec<u8>, GitError>,
+{
+    match options.strategy {
+        Strategy::Ours => {
+            return Ok(TreeMerge {
+                blobs: ours.clone(),
+                ..Default::default()
+            })
+        }
+        Strategy::Theirs => {
+            return Ok(TreeMerge {
+                blobs: theirs.clone(),
+                ..Default::default()
+            })
+        }
+        Strategy::Resolve => {}
+    }
+    let mut merged = TreeMerge::default();
+    let paths: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let resolved = if o == t || t == b {
+            Some(o.cloned())
+        } else if o == b {
+            Some(t.cloned())
+        } else {
+            options.resolve_conflict(b, o, t, &read_blob)?
+        };
+        match resolved {
+            Some(Some(entry)) => {
+                merged.blobs.insert(path.clone(), entry);
+            }
+            Some(None) => {}
+            None => {
+                merged
+                    .conflicts
+                    .insert(path.clone(), (o.cloned(), t.cloned()));
+            }
+        }
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(entries: &[(&str, &str)]) -> BTreeMap<String, Entry> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), Entry::new(*v)))
+            .collect()
+    }
+
+    /// blob sha1 is its content in these tests
+    fn read_blob(sha1: &str) -> Result<Vec<u8>, GitError> {
+        Ok(sha1.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn merge_trees_ut() {
+        let base = tree(&[("same", "a"), ("ours", "a"), ("theirs", "a"), ("both", "a")]);
+        let ours = tree(&[("same", "a"), ("ours", "b"), ("theirs", "a"), ("both", "b")]);
+        let theirs = tree(&[("same", "a"), ("ours", "a"), ("both", "c"), ("new", "n")]);
+        let merged =
+            merge_trees(&base, &ours, &theirs, &MergeOptions::default(), read_blob).unwrap();
+        assert_eq!(
+            tree(&[("same", "a"), ("ours", "b"), ("new", "n")]),
+            merged.blobs
+        );
+        assert_eq!(
+            BTreeMap::from([(
+                "both".to_string(),
+                (Some(Entry::new("b")), Some(Entry::new("c")))
+            )]),
+            merged.conflicts
+        );
+
+        let options = MergeOptions {
+            options: vec![StrategyOption::Theirs],
+            ..Default::default()
+        };
+        let merged = merge_trees(&base, &ours, &theirs, &options, read_blob).unwrap();
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(Some(&Entry::new("c")), merged.blobs.get("both"));
+
+        let options = MergeOptions {
+            options: vec![StrategyOption::Ours],
+            ..Default::default()
+        };
+        let merged = merge_trees(&base, &ours, &theirs, &options, read_blob).unwrap();
+        assert_eq!(Some(&Entry::new("b")), merged.blobs.get("both"));
+    }
+
+    #[test]
+    fn merge_strategy_ut() {
+        let base = tree(&[("f", "a")]);
+        let ours = tree(&[("f", "b")]);
+        let theirs = tree(&[("f", "c"), ("g", "g")]);
+        for (strategy, expected) in [(Strategy::Ours, &ours), (Strategy::Theirs, &theirs)] {
+            let options = MergeOptions {
+                strategy,
+                options: vec![],
+            };
+            let merged = merge_trees(&base, &ours, &theirs, &options, read_blob).unwrap();
+            assert_eq!(expected, &merged.blobs);
+            assert!(merged.conflicts.is_empty());
+        }
+    }
+
+    #[test]
+    fn ignore_space_change_ut() {
+        let options = MergeOptions {
+            options: vec![StrategyOption::IgnoreSpaceChange],
+            ..Default::default()
+        };
+        let base = tree(&[("f", "a b\nc"), ("g", "x")]);
+        let ours = tree(&[("f", "a  b \nc"), ("g", "x y")]);
+        let theirs = tree(&[("f", "a b\nd"), ("g", "x z")]);
+        let merged = merge_trees(&base, &ours, &theirs, &options, read_blob).unwrap();
+        assert_eq!(Some(&Entry::new("a b\nd")), merged.blobs.get("f"));
+        assert!(merged.conflicts.contains_key("g"));
+    }
+}
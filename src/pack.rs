@@ -0,0 +1,405 @@
+use crate::error::GitError;
+use crate::utils;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// leading bytes of every pack file
+const PACK_MAGIC: &[u8] = b"GITRSPACK1\n";
+/// delta chains longer than this are cut by storing the blob whole
+pub const MAX_DEPTH: usize = 10;
+/// size of the base blocks a delta looks up copies by
+const BLOCK: usize = 16;
+
+/// where a blob is in the pack file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PackEntry {
+    offset: u64,
+    /// bytes of the compressed data
+    size: u64,
+    /// blob the data is a delta against, None when it holds the whole content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base: Option<String>,
+}
+
+/// A pack holding many blobs in one file: `pack-<sha1>.pack` with the zlib compressed
+/// content of each blob, or a delta against another blob of the pack, and `pack-<sha1>.idx`
+/// mapping every blob sha1 to its place in the pack file
+#[derive(Debug, Clone)]
+pub struct Pack {
+    path: PathBuf,
+    index: BTreeMap<String, PackEntry>,
+}
+
+impl Pack {
+    /// open the pack of an index file
+    pub fn open(idx_path: &Path) -> Result<Self, GitError> {
+        let content =
+            fs::read_to_string(idx_path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let index = serde_json::from_str(&content)
+            .map_err(|e| GitError::SerdeOpError(format!("{}: {:?}", idx_path.display(), e)))?;
+        Ok(Self {
+            path: idx_path.with_extension("pack"),
+            index,
+        })
+    }
+
+    /// every pack of the directory, sorted by name
+    pub fn open_dir(dir: &Path) -> Result<Vec<Self>, GitError> {
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut idx_paths = vec![];
+        for entry in fs::read_dir(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))? {
+            let path = entry
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                .path();
+            if path.extension().is_some_and(|ext| ext == "idx") {
+                idx_paths.push(path);
+            }
+        }
+        idx_paths.sort();
+        idx_paths.iter().map(|path| Self::open(path)).collect()
+    }
+
+    /// the pack file, its index file has the same name with the `idx` extension
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contains(&self, sha1: &str) -> bool {
+        self.index.contains_key(sha1)
+    }
+
+    /// sha1 of every blob in the pack
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.index.keys()
+    }
+
+    /// number of blobs stored as deltas
+    pub fn deltas(&self) -> usize {
+        self.index.values().filter(|e| e.base.is_some()).count()
+    }
+
+    /// content of a blob of the pack, its delta chain resolved
+    pub fn read(&self, sha1: &str) -> Result<Vec<u8>, GitError> {
+        let mut file =
+            fs::File::open(&self.path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let mut chain = vec![];
+        let mut next = Some(sha1.to_string());
+        while let Some(sha1) = next {
+            let entry = self.index.get(&sha1).ok_or_else(|| {
+                GitError::PackError(format!("{} is not in {}", sha1, self.path.display()))
+            })?;
+            if chain.len() > MAX_DEPTH {
+                return Err(GitError::PackError(format!(
+                    "delta chain of {} too long",
+                    sha1
+                )));
+            }
+            chain.push(Self::read_entry(&mut file, entry)?);
+            next = entry.base.clone();
+        }
+        let mut content = chain.pop().unwrap_or_default();
+        while let Some(delta) = chain.pop() {
+            content = apply_delta(&content, &delta)?;
+        }
+        Ok(content)
+    }
+
+    fn read_entry(file: &mut fs::File, entry: &PackEntry) -> Result<Vec<u8>, GitError> {
+        let mut data = vec![0; entry.size as usize];
+        file.seek(SeekFrom::Start(entry.offset))
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|e| GitError::PackError(format!("{:?}", e)))?;
+        let mut content = vec![];
+        ZlibDecoder::new(data.as_slice())
+            .read_to_end(&mut content)
+            .map_err(|e| GitError::PackError(format!("{:?}", e)))?;
+        Ok(content)
+    }
+}
+
+/// Builds a pack in memory, blobs are added whole or as a delta against a blob added before
+#[derive(Debug)]
+pub struct PackBuilder {
+    data: Vec<u8>,
+    index: BTreeMap<String, PackEntry>,
+    depths: HashMap<String, usize>,
+}
+
+impl Default for PackBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Self {
+            data: PACK_MAGIC.to_vec(),
+            index: BTreeMap::new(),
+            depths: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, sha1: &str) -> bool {
+        self.index.contains_key(sha1)
+    }
+
+    /// Add a blob, as a delta against `base` when the base was added before, its chain is
+    /// not too long and the delta is less than half the size of the content. Returns
+    /// whether the blob was stored as a delta.
+    pub fn add(
+        &mut self,
+        sha1: &str,
+        content: &[u8],
+        base: Option<(&str, &[u8])>,
+    ) -> Result<bool, GitError> {
+        if self.contains(sha1) {
+            return Ok(false);
+        }
+        let base = base
+            .filter(|(base, _)| *base != sha1)
+            .and_then(|(base, base_content)| {
+                let depth = *self.depths.get(base)?;
+                (depth < MAX_DEPTH).then_some((base, base_content, depth))
+            })
+            .map(|(base, base_content, depth)| (base, delta(base_content, content), depth))
+            .filter(|(_, delta, _)| delta.len() < content.len() / 2);
+        let (data, base, depth) = match base {
+            Some((base, delta, depth)) => (delta, Some(base.to_string()), depth + 1),
+            None => (content.to_vec(), None, 0),
+        };
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        let compressed = encoder
+            .write_all(&data)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| GitError::PackError(format!("{:?}", e)))?;
+        let is_delta = base.is_some();
+        self.index.insert(
+            sha1.to_string(),
+            PackEntry {
+                offset: self.data.len() as u64,
+                size: compressed.len() as u64,
+                base,
+            },
+        );
+        self.depths.insert(sha1.to_string(), depth);
+        self.data.extend(compressed);
+        Ok(is_delta)
+    }
+
+    /// Write the pack and its index into the directory, returns the pack file path. The
+    /// files are named after the sha1 of the pack content.
+    pub fn write(self, dir: &Path) -> Result<PathBuf, GitError> {
+        fs::create_dir_all(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let name = format!("pack-{}", utils::crypto_bytes(&self.data));
+        let path = dir.join(format!("{}.pack", name));
+        let index = serde_json::to_string(&self.index)
+            .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+        fs::write(&path, &self.data).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        // the index goes last, a pack without one is never read
+        fs::write(dir.join(format!("{}.idx", name)), index)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Ok(path)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize, GitError> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data
+            .get(*pos)
+            .ok_or_else(|| GitError::PackError("truncated delta".to_string()))?;
+        *pos += 1;
+        if shift >= usize::BITS {
+            return Err(GitError::PackError("bad delta varint".to_string()));
+        }
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// Delta turning `base` into `target`: the sizes of both, then copies of base ranges
+/// (`1 offset len`) and inserts of new bytes (`0 len bytes`), numbers as varints.
+/// Copies are found by looking up every block of the target among the blocks of the base.
+pub fn delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    write_varint(&mut out, base.len());
+    write_varint(&mut out, target.len());
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for offset in (0..base.len().saturating_sub(BLOCK - 1)).step_by(BLOCK) {
+        blocks
+            .entry(&base[offset..offset + BLOCK])
+            .or_insert(offset);
+    }
+    let insert = |out: &mut Vec<u8>, bytes: &[u8]| {
+        if !bytes.is_empty() {
+            out.push(0);
+            write_varint(out, bytes.len());
+            out.extend_from_slice(bytes);
+        }
+    };
+    let (mut pending, mut i) = (0, 0);
+    while i + BLOCK <= target.len() {
+        let start = match blocks.get(&target[i..i + BLOCK]) {
+            Some(start) => *start,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        let mut len = BLOCK;
+        while start + len < base.len()
+            && i + len < target.len()
+            && base[start + len] == target[i + len]
+        {
+            len += 1;
+        }
+        // grow the copy back over bytes that would otherwise be inserted
+        let mut back = 0;
+        while back < i - pending && back < start && base[start - back - 1] == target[i - back - 1] {
+            back += 1;
+        }
+        insert(&mut out, &target[pending..i - back]);
+        out.push(1);
+        write_varint(&mut out, start - back);
+        write_varint(&mut out, len + back);
+        i += len;
+        pending = i;
+    }
+    insert(&mut out, &target[pending..]);
+    out
+}
+
+/// the target of a delta made by `delta` against the base
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, GitError> {
+    let bad = |msg: &str| GitError::PackError(format!("bad delta: {}", msg));
+    let mut pos = 0;
+    if read_varint(delta, &mut pos)? != base.len() {
+        return Err(bad("base size differs"));
+    }
+    let size = read_varint(delta, &mut pos)?;
+    let mut out = Vec::with_capacity(size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        match op {
+            0 => {
+                let len = read_varint(delta, &mut pos)?;
+                let bytes = delta
+                    .get(pos..pos + len)
+                    .ok_or_else(|| bad("insert out of range"))?;
+                out.extend_from_slice(bytes);
+                pos += len;
+            }
+            1 => {
+                let offset = read_varint(delta, &mut pos)?;
+                let len = read_varint(delta, &mut pos)?;
+                let bytes = base
+                    .get(offset..offset + len)
+                    .ok_or_else(|| bad("copy out of range"))?;
+                out.extend_from_slice(bytes);
+            }
+            _ => return Err(bad("unknown op")),
+        }
+    }
+    if out.len() != size {
+        return Err(bad("target size differs"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn delta_ut() {
+        let base: String = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let target = base.replace("line 1000\n", "changed\n") + "appended\n";
+        let d = delta(base.as_bytes(), target.as_bytes());
+        assert!(d.len() < 100, "{}", d.len());
+        assert_eq!(target.as_bytes(), apply_delta(base.as_bytes(), &d).unwrap());
+
+        for (base, target) in [
+            (&b""[..], &b"new"[..]),
+            (b"old", b""),
+            (b"short", b"short"),
+            (
+                b"abcdefghijklmnopqrstuvwxyz",
+                b"0abcdefghijklmnopqrstuvwxyz1",
+            ),
+        ] {
+            assert_eq!(target, apply_delta(base, &delta(base, target)).unwrap());
+        }
+        assert!(apply_delta(b"other base", &d).is_err());
+        assert!(apply_delta(base.as_bytes(), &d[..d.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn pack_ut() {
+        let dir = &env::current_dir().unwrap().join("pack_ut");
+        if dir.exists() {
+            assert!(fs::remove_dir_all(dir).is_ok());
+        }
+        let versions: Vec<Vec<u8>> = (0..15)
+            .map(|v| {
+                (0..500)
+                    .map(|i| format!("line {} of version {}\n", i, if i == v { v } else { 0 }))
+                    .collect::<String>()
+                    .into_bytes()
+            })
+            .collect();
+        let mut builder = PackBuilder::new();
+        let mut deltas = 0;
+        for (i, version) in versions.iter().enumerate() {
+            let base = i
+                .checked_sub(1)
+                .map(|prev| (utils::crypto_bytes(&versions[prev]), &versions[prev]));
+            let base = base.as_ref().map(|(sha1, c)| (sha1.as_str(), c.as_slice()));
+            if builder
+                .add(&utils::crypto_bytes(version), version, base)
+                .unwrap()
+            {
+                deltas += 1;
+            }
+        }
+        // chains are cut at the max depth
+        assert_eq!(versions.len() - 2, deltas);
+        assert!(builder.add("unrelated", b"x", None).is_ok());
+        let path = builder.write(dir).unwrap();
+
+        let packs = Pack::open_dir(dir).unwrap();
+        assert_eq!(1, packs.len());
+        assert_eq!(path, packs[0].path());
+        assert_eq!(deltas, packs[0].deltas());
+        for version in versions.iter() {
+            assert_eq!(
+                version,
+                &packs[0].read(&utils::crypto_bytes(version)).unwrap()
+            );
+        }
+        assert_eq!(b"x".to_vec(), packs[0].read("unrelated").unwrap());
+        assert!(packs[0].read("missing").is_err());
+        assert!(fs::remove_dir_all(dir).is_ok());
+    }
+}
@@ -0,0 +1,420 @@
+use crate::diff::{Hunk, Line};
+use crate::error::GitError;
+use chrono::{DateTime, TimeZone, Utc};
+
+/// longest subject part of a patch file name
+const FILE_NAME_SUBJECT_LEN: usize = 52;
+
+/// One commit as an mbox style patch e-mail, the format `format-patch` writes.
+/// commit: sha1 of the original commit, from: who sends the patch,
+/// diff: unified diff of the commit against its first parent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub commit: String,
+    pub from: String,
+    pub date_time: i64,
+    pub message: String,
+    pub diff: String,
+}
+
+impl Mail {
+    /// the e-mail of patch `n` out of `total`, numbered from 1
+    pub fn render(&self, n: usize, total: usize) -> String {
+        let date = Utc
+            .timestamp_opt(self.date_time, 0)
+            .single()
+            .map(|date| date.to_rfc2822())
+            .unwrap_or_default();
+        let (subject, body) = self.message.split_once('\n').unwrap_or((&self.message, ""));
+        let prefix = if total > 1 {
+            format!("[PATCH {}/{}]", n, total)
+        } else {
+            "[PATCH]".to_string()
+        };
+        let mut out = format!(
+            "From {} Mon Sep 17 00:00:00 2001\nFrom: {}\nDate: {}\nSubject: {} {}\n\n",
+            self.commit, self.from, date, prefix, subject
+        );
+        let body = body.trim();
+        if !body.is_empty() {
+            out.push_str(&format!("{}\n", body));
+        }
+        out.push_str(&format!("---\n{}-- \ngit-rs\n", self.diff));
+        out
+    }
+}
+
+/// Changes of one file in a unified diff, a None path is /dev/null
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    /// path of the patched file, the old one for a deletion
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// path of a `---` or `+++` line without its a/ or b/ prefix, None for /dev/null
+fn diff_path(name: &str, prefix: &str) -> Option<String> {
+    let name = name.split('\t').next().unwrap_or(name).trim_end();
+    if name == "/dev/null" {
+        return None;
+    }
+    Some(name.strip_prefix(prefix).unwrap_or(name).to_string())
+}
+
+/// start and length of one side of a hunk header, e.g. `-1,3` or `+4`
+fn hunk_range(range: &str, sign: char) -> Option<(usize, usize)> {
+    let range = range.strip_prefix(sign)?;
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Parse the file patches of a unified diff, lines before the first `---` line are ignored
+pub fn parse_diff(text: &str) -> Result<Vec<FilePatch>, GitError> {
+    let bad = |no: usize, msg: &str| {
+        Err(GitError::PatchError(format!(
+            "corrupt patch at line {}: {}",
+            no + 1,
+            msg
+        )))
+    };
+    let mut patches: Vec<FilePatch> = vec![];
+    let mut lines = text.lines().enumerate().peekable();
+    while let Some((no, line)) = lines.next() {
+        if let Some(old) = line.strip_prefix("--- ") {
+            let new = match lines.next() {
+                Some((_, next)) if next.starts_with("+++ ") => &next[4..],
+                _ => return bad(no, "--- without +++"),
+            };
+            patches.push(FilePatch {
+                old_path: diff_path(old, "a/"),
+                new_path: diff_path(new, "b/"),
+                hunks: vec![],
+            });
+        } else if line.starts_with("@@ ") {
+            let Some(patch) = patches.last_mut() else {
+                return bad(no, "hunk without file header");
+            };
+            let mut ranges = line.split(' ').skip(1);
+            let old = ranges.next().and_then(|r| hunk_range(r, '-'));
+            let new = ranges.next().and_then(|r| hunk_range(r, '+'));
+            let (Some((old_start, old_len)), Some((new_start, new_len))) = (old, new) else {
+                return bad(no, line);
+            };
+            let mut hunk = Hunk {
+                old_start,
+                old_len,
+                new_start,
+                new_len,
+                lines: vec![],
+            };
+            let (mut old_left, mut new_left) = (old_len, new_len);
+            while old_left > 0 || new_left > 0 {
+                let Some((no, line)) = lines.next() else {
+                    return bad(no, "truncated hunk");
+                };
+                let (kind, content) = line.split_at(line.len().min(1));
+                match kind {
+                    // some editors strip the space of empty context lines
+                    " " | "" if old_left > 0 && new_left > 0 => {
+                        hunk.lines.push(Line::Context(content.to_string()));
+                        old_left -= 1;
+                        new_left -= 1;
+                    }
+                    "-" if old_left > 0 => {
+                        hunk.lines.push(Line::Removed(content.to_string()));
+                        old_left -= 1;
+                    }
+                    "+" if new_left > 0 => {
+                        hunk.lines.push(Line::Added(content.to_string()));
+                        new_left -= 1;
+                    }
+                    "\\" => {}
+                    _ => return bad(no, line),
+                }
+            }
+            patch.hunks.push(hunk);
+        }
+    }
+    Ok(patches)
+}
+
+/// Apply hunks to the old content of a file, None when the file does not exist.
+/// A hunk is applied where its old lines are, closest to its recorded position.
+/// Returns the new content, None when the file is deleted.
+pub fn apply_hunks(content: Option<&str>, patch: &FilePatch) -> Result<Option<String>, GitError> {
+    let old = content.unwrap_or_default();
+    let mut lines: Vec<String> = old.lines().map(|l| l.to_string()).collect();
+    // shift of the hunk positions caused by the hunks applied so far
+    let mut shift: isize = 0;
+    for hunk in patch.hunks.iter() {
+        let before: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Context(l) | Line::Removed(l) => Some(l.as_str()),
+                Line::Added(_) => None,
+            })
+            .collect();
+        let after: Vec<String> = hunk
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Context(l) | Line::Added(l) => Some(l.clone()),
+                Line::Removed(_) => None,
+            })
+            .collect();
+        let start = if hunk.old_len == 0 {
+            hunk.old_start
+        } else {
+            hunk.old_start.saturating_sub(1)
+        };
+        let expected = (start as isize + shift).max(0) as usize;
+        let matches_at = |at: usize| {
+            at + before.len() <= lines.len()
+                && lines[at..at + before.len()]
+                    .iter()
+                    .zip(before.iter())
+                    .all(|(a, b)| a == b)
+        };
+        let Some(at) = (0..=lines.len())
+            .filter(|at| matches_at(*at))
+            .min_by_key(|at| at.abs_diff(expected))
+        else {
+            return Err(GitError::PatchError(format!(
+                "patch does not apply to {}",
+                patch.path()
+            )));
+        };
+        lines.splice(at..at + before.len(), after.iter().cloned());
+        shift += at as isize - start as isize + after.len() as isize - before.len() as isize;
+    }
+    if patch.new_path.is_none() {
+        return if lines.is_empty() {
+            Ok(None)
+        } else {
+            Err(GitError::PatchError(format!(
+                "{} is not empty after its deletion",
+                patch.path()
+            )))
+        };
+    }
+    if lines.is_empty() {
+        return Ok(Some(String::new()));
+    }
+    // diffs do not record a missing newline at the end, keep the one of the old content
+    let newline = if old.is_empty() || old.ends_with('\n') {
+        "\n"
+    } else {
+        ""
+    };
+    Ok(Some(lines.join("\n") + newline))
+}
+
+/// Parse a patch e-mail written by `format-patch`
+pub fn parse_mail(content: &str) -> Result<Mail, GitError> {
+    let bad = |msg: &str| Err(GitError::PatchError(format!("not a patch e-mail: {}", msg)));
+    let (headers, rest) = match content.split_once("\n\n") {
+        Some(parts) => parts,
+        None => return bad("no headers"),
+    };
+    let mut mail = Mail {
+        commit: String::new(),
+        from: String::new(),
+        date_time: 0,
+        message: String::new(),
+        diff: String::new(),
+    };
+    for header in headers.lines() {
+        if let Some(from) = header.strip_prefix("From: ") {
+            mail.from = from.to_string();
+        } else if let Some(date) = header.strip_prefix("Date: ") {
+            mail.date_time = DateTime::parse_from_rfc2822(date)
+                .map_err(|e| GitError::PatchError(format!("bad date {}: {}", date, e)))?
+                .timestamp();
+        } else if let Some(subject) = header.strip_prefix("Subject: ") {
+            mail.message = match subject.strip_prefix("[PATCH") {
+                Some(rest) => rest.split_once("] ").map_or("", |(_, s)| s).to_string(),
+                None => subject.to_string(),
+            };
+        } else if let Some(from) = header.strip_prefix("From ") {
+            mail.commit = from.split(' ').next().unwrap_or_default().to_string();
+        }
+    }
+    if mail.message.is_empty() {
+        return bad("no subject");
+    }
+    let (body, diff) = if let Some(diff) = rest.strip_prefix("---\n") {
+        ("", diff)
+    } else {
+        match rest.split_once("\n---\n") {
+            Some(parts) => parts,
+            None => return bad("no --- line before the diff"),
+        }
+    };
+    let body = body.trim();
+    if !body.is_empty() {
+        mail.message = format!("{}\n\n{}", mail.message, body);
+    }
+    mail.diff = diff
+        .rsplit_once("-- \n")
+        .map_or(diff, |(diff, _)| diff)
+        .to_string();
+    Ok(mail)
+}
+
+/// file name of patch `n`: `0001-` followed by the subject with runs of other characters than
+/// letters and digits replaced by a dash, e.g. `0001-fix-the-parser.patch`
+pub fn file_name(n: usize, subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.chars().take(FILE_NAME_SUBJECT_LEN).collect();
+    format!("{:04}-{}.patch", n, slug.trim_end_matches('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_ut() {
+        let mut mail = Mail {
+            commit: "a".repeat(40),
+            from: "Jane <jane@example.com>".to_string(),
+            date_time: 1700000000,
+            message: "fix: the parser\n\nit no longer panics".to_string(),
+            diff: "--- a/f1\n+++ b/f1\n@@ -1,1 +1,1 @@\n-a\n+b\n".to_string(),
+        };
+        assert_eq!(
+            format!(
+                "From {} Mon Sep 17 00:00:00 2001
+From: Jane <jane@example.com>
+Date: Tue, 14 Nov 2023 22:13:20 +0000
+Subject: [PATCH 2/3] fix: the parser
+
+it no longer panics
+---
+--- a/f1
++++ b/f1
+@@ -1,1 +1,1 @@
+-a
++b
+--\x20
+git-rs
+",
+                "a".repeat(40)
+            ),
+            mail.render(2, 3)
+        );
+        mail.message = "one line".to_string();
+        assert!(mail
+            .render(1, 1)
+            .contains("\nSubject: [PATCH] one line\n\n---\n--- a/f1\n"));
+    }
+
+    #[test]
+    fn parse_mail_ut() {
+        let mail = Mail {
+            commit: "a".repeat(40),
+            from: "Jane <jane@example.com>".to_string(),
+            date_time: 1700000000,
+            message: "fix: the parser\n\nit no longer panics\nat all".to_string(),
+            diff: "--- a/f1\n+++ b/f1\n@@ -1,1 +1,1 @@\n-a\n+b\n".to_string(),
+        };
+        assert_eq!(mail, parse_mail(&mail.render(2, 3)).unwrap());
+        let one_line = Mail {
+            message: "one line".to_string(),
+            ..mail.clone()
+        };
+        assert_eq!(one_line, parse_mail(&one_line.render(1, 1)).unwrap());
+        assert!(parse_mail("no headers").is_err());
+        assert!(parse_mail("From: x\n\nbody").is_err());
+    }
+
+    #[test]
+    fn parse_diff_ut() {
+        let text = "From x\n\n---\n--- a/f1\n+++ b/f1\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n\n\
+                    --- /dev/null\n+++ b/f2\n@@ -0,0 +1 @@\n+new\n\
+                    --- a/f3\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-gone\n-- \ngit-rs\n";
+        let patches = parse_diff(text).unwrap();
+        assert_eq!(3, patches.len());
+        assert_eq!("f1", patches[0].path());
+        assert_eq!(
+            vec![
+                Line::Context("a".to_string()),
+                Line::Removed("b".to_string()),
+                Line::Added("B".to_string()),
+                Line::Context("".to_string()),
+            ],
+            patches[0].hunks[0].lines
+        );
+        assert_eq!(
+            (None, "f2"),
+            (patches[1].old_path.clone(), patches[1].path())
+        );
+        assert_eq!(1, patches[1].hunks[0].new_len);
+        assert_eq!(
+            (None, "f3"),
+            (patches[2].new_path.clone(), patches[2].path())
+        );
+
+        assert!(parse_diff("--- a/f1\nno plus line\n").is_err());
+        assert!(parse_diff("@@ -1 +1 @@\n").is_err());
+        assert!(parse_diff("--- a/f1\n+++ b/f1\n@@ -1,2 +1,2 @@\n a\n").is_err());
+        assert!(parse_diff("--- a/f1\n+++ b/f1\n@@ -1 +1 @@\n?a\n+b\n").is_err());
+    }
+
+    #[test]
+    fn apply_hunks_ut() {
+        let patch = |text: &str| parse_diff(text).unwrap().remove(0);
+        let change = patch("--- a/f\n+++ b/f\n@@ -2,2 +2,2 @@\n b\n-c\n+C\n");
+        assert_eq!(
+            Some("a\nb\nC\n".to_string()),
+            apply_hunks(Some("a\nb\nc\n"), &change).unwrap()
+        );
+        // applied where the lines moved to
+        assert_eq!(
+            Some("x\ny\na\nb\nC".to_string()),
+            apply_hunks(Some("x\ny\na\nb\nc"), &change).unwrap()
+        );
+        assert!(apply_hunks(Some("a\nb\nd\n"), &change).is_err());
+        assert!(apply_hunks(None, &change).is_err());
+
+        let create = patch("--- /dev/null\n+++ b/f\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+        assert_eq!(
+            Some("a\nb\n".to_string()),
+            apply_hunks(None, &create).unwrap()
+        );
+        let delete = patch("--- a/f\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-a\n-b\n");
+        assert_eq!(None, apply_hunks(Some("a\nb\n"), &delete).unwrap());
+        assert!(apply_hunks(Some("a\nb\nc\n"), &delete).is_err());
+    }
+
+    #[test]
+    fn file_name_ut() {
+        assert_eq!("0001-fix-the-parser.patch", file_name(1, "fix: the parser"));
+        assert_eq!("0012-a-b.patch", file_name(12, "  a -- b!!"));
+        assert_eq!("0003-.patch", file_name(3, "???"));
+        assert_eq!(
+            format!("0001-{}.patch", "x".repeat(52)),
+            file_name(1, &"x".repeat(80))
+        );
+    }
+}
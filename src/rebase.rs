@@ -0,0 +1,163 @@
+use crate::error::GitError;
+use serde::{Deserialize, Serialize};
+
+/// help appended to a fresh todo list, comment lines are ignored when it is read back
+const TODO_HELP: &str = "
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# s, squash <commit> = use commit, but meld into previous commit
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+# If you remove everything, the rebase will be aborted.
+";
+
+/// What an interactive rebase does with a commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Pick,
+    Reword,
+    Squash,
+    Drop,
+}
+
+impl Action {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "p" | "pick" => Some(Self::Pick),
+            "r" | "reword" => Some(Self::Reword),
+            "s" | "squash" => Some(Self::Squash),
+            "d" | "drop" => Some(Self::Drop),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Pick => "pick",
+            Self::Reword => "reword",
+            Self::Squash => "squash",
+            Self::Drop => "drop",
+        }
+    }
+}
+
+/// One line of the todo list: `<action> <commit sha1> <subject>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub action: Action,
+    pub commit: String,
+    pub subject: String,
+}
+
+/// Progress of a rebase, persisted so it can be resumed with `--continue`
+/// branch: the branch being rebased, orig_head: its head before the rebase,
+/// onto: the last commit made so far, squashable: whether that commit was made by this rebase,
+/// conflicts: the conflicted files of the first todo step, while the rebase is stopped on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RebaseState {
+    pub branch: String,
+    pub orig_head: String,
+    pub onto: String,
+    pub squashable: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+/// Progress of a cherry-pick of several commits, persisted so it can be resumed with
+/// `--continue`. branch: the branch picked onto, orig_head: its head before the cherry-pick,
+/// todo: the sha1 of the commits left to pick, oldest first,
+/// conflicts: the conflicted files of the first of them, while the cherry-pick is stopped on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CherryPickState {
+    pub branch: String,
+    pub orig_head: String,
+    pub todo: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+/// parse a todo list, blank and `#` comment lines are skipped
+pub fn parse_todo(content: &str) -> Result<Vec<Step>, GitError> {
+    let mut steps = vec![];
+    for (no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.splitn(3, ' ');
+        let action = parts.next().and_then(Action::parse);
+        let commit = parts.next().filter(|c| !c.is_empty());
+        match (action, commit) {
+            (Some(action), Some(commit)) => steps.push(Step {
+                action,
+                commit: commit.to_string(),
+                subject: parts.next().unwrap_or("").trim().to_string(),
+            }),
+            _ => {
+                return Err(GitError::RebaseError(format!(
+                    "bad todo line {}: {}",
+                    no + 1,
+                    line
+                )))
+            }
+        }
+    }
+    Ok(steps)
+}
+
+/// render steps one per line, with the command help when `help` is set
+pub fn render_todo(steps: &[Step], help: bool) -> String {
+    let mut out: String = steps
+        .iter()
+        .map(|step| format!("{} {} {}\n", step.action.name(), step.commit, step.subject))
+        .collect();
+    if help {
+        out.push_str(TODO_HELP);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn todo_ut() {
+        let steps = vec![
+            Step {
+                action: Action::Pick,
+                commit: "a".repeat(40),
+                subject: "first".to_string(),
+            },
+            Step {
+                action: Action::Squash,
+                commit: "b".repeat(40),
+                subject: "second one".to_string(),
+            },
+        ];
+        let todo = render_todo(&steps, true);
+        assert!(todo.starts_with(&format!(
+            "pick {} first\nsquash {} second one\n\n# Commands:",
+            "a".repeat(40),
+            "b".repeat(40)
+        )));
+        assert_eq!(steps, parse_todo(&todo).unwrap());
+        assert_eq!(
+            steps,
+            parse_todo(&render_todo(&steps, false).replace("pick", "p")).unwrap()
+        );
+
+        let edited = parse_todo("  d abc  \nr def reworded subject\n").unwrap();
+        assert_eq!(Action::Drop, edited[0].action);
+        assert_eq!("abc", edited[0].commit);
+        assert_eq!("", edited[0].subject);
+        assert_eq!(Action::Reword, edited[1].action);
+        assert_eq!("reworded subject", edited[1].subject);
+
+        assert!(parse_todo("edit abc subject").is_err());
+        assert!(parse_todo("pick").is_err());
+        assert!(parse_todo("# only comments\n\n").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::conventional::ConventionalMessage;
+use crate::repo::Commit;
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+/// Semantic version `major.minor.patch`, tags may carry a leading `v`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+/// Which part of the version a release increments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl Version {
+    /// parse `1.2.3` or `v1.2.3`, pre-release and build suffixes are not versions
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+        let mut parts = s.split('.').map(|p| {
+            if !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()) {
+                p.parse::<u64>().ok()
+            } else {
+                None
+            }
+        });
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => Some(Self {
+                major,
+                minor,
+                patch,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn bump(self, bump: Bump) -> Self {
+        match bump {
+            Bump::Major => Self {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Bump::Minor => Self {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            Bump::Patch => Self {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl Bump {
+    /// breaking changes bump major, features bump minor, anything else bumps patch
+    pub fn infer(commits: &[(String, Commit)]) -> Self {
+        let messages: Vec<ConventionalMessage> = commits
+            .iter()
+            .filter_map(|(_, commit)| ConventionalMessage::parse(commit.message()))
+            .collect();
+        if messages.iter().any(|m| m.breaking) {
+            Bump::Major
+        } else if messages.iter().any(|m| m.kind == "feat") {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+}
+
+/// the tag with the highest semantic version among tag name --> commit pairs
+pub fn latest_version(tags: &BTreeMap<String, String>) -> Option<(String, Version)> {
+    tags.keys()
+        .filter_map(|name| Version::parse(name).map(|v| (name.clone(), v)))
+        .max_by_key(|(_, v)| *v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_ut() {
+        assert_eq!(
+            Some(Version {
+                major: 1,
+                minor: 2,
+                patch: 3
+            }),
+            Version::parse("v1.2.3")
+        );
+        assert_eq!(Some(Version::default()), Version::parse("0.0.0"));
+        assert_eq!(None, Version::parse("v1.2"));
+        assert_eq!(None, Version::parse("v1.2.3.4"));
+        assert_eq!(None, Version::parse("v1.2.3-rc1"));
+        assert_eq!(None, Version::parse("release"));
+    }
+
+    #[test]
+    fn bump_version_ut() {
+        let v = Version::parse("1.2.3").unwrap();
+        assert_eq!("2.0.0", v.bump(Bump::Major).to_string());
+        assert_eq!("1.3.0", v.bump(Bump::Minor).to_string());
+        assert_eq!("1.2.4", v.bump(Bump::Patch).to_string());
+    }
+
+    #[test]
+    fn infer_bump_ut() {
+        let commit = |msg: &str| {
+            let mut commit = Commit::new();
+            commit.set_message(msg);
+            ("sha1".to_string(), commit)
+        };
+        assert_eq!(Bump::Patch, Bump::infer(&[commit("fix: a"), commit("wip")]));
+        assert_eq!(
+            Bump::Minor,
+            Bump::infer(&[commit("fix: a"), commit("feat: b")])
+        );
+        assert_eq!(
+            Bump::Major,
+            Bump::infer(&[commit("feat!: b"), commit("fix: a")])
+        );
+        assert_eq!(Bump::Patch, Bump::infer(&[]));
+    }
+
+    #[test]
+    fn latest_version_ut() {
+        let tags = BTreeMap::from([
+            ("v0.9.0".to_string(), "a".to_string()),
+            ("v0.10.0".to_string(), "b".to_string()),
+            ("nightly".to_string(), "c".to_string()),
+        ]);
+        assert_eq!(
+            Some(("v0.10.0".to_string(), Version::parse("0.10.0").unwrap())),
+            latest_version(&tags)
+        );
+        assert_eq!(None, latest_version(&BTreeMap::new()));
+    }
+}
@@ -0,0 +1,229 @@
+use crate::config::Config;
+use crate::error::GitError;
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+/// Operation of the remote command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RemoteAction {
+    /// list the remotes
+    #[default]
+    List,
+    /// add a remote
+    Add,
+    /// remove a remote and its settings
+    Remove,
+}
+
+/// check that a remote url can be reached: https urls need the `tls` feature
+pub fn check_url(url: &str) -> Result<(), GitError> {
+    if url.is_empty() {
+        return Err(GitError::RemoteError("empty remote url".to_string()));
+    }
+    if url.starts_with("https://") && !cfg!(feature = "tls") {
+        return Err(GitError::RemoteError(format!(
+            "https needs git-rs built with the tls feature, use a http:// or git-rs:// url: {}",
+            url
+        )));
+    }
+    Ok(())
+}
+
+/// check that a remote name can be a config subsection and a ref directory
+pub fn check_name(name: &str) -> Result<(), GitError> {
+    let invalid = name.is_empty()
+        || name.starts_with('-')
+        || name.contains("..")
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "/\\\"[]:~^?*".contains(c));
+    if invalid {
+        return Err(GitError::RemoteError(format!(
+            "invalid remote name {}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+fn url_key(name: &str) -> String {
+    format!("remote.{}.url", name)
+}
+
+/// Remotes of the config, name --> url, from `[remote "<name>"]` sections
+pub fn remotes(config: &Config) -> BTreeMap<String, String> {
+    config
+        .entries()
+        .iter()
+        .filter_map(|(key, url)| {
+            let name = key.strip_prefix("remote.")?.strip_suffix(".url")?;
+            Some((name.to_string(), url.clone()))
+        })
+        .collect()
+}
+
+/// url of the remote
+pub fn url<'a>(config: &'a Config, name: &str) -> Result<&'a str, GitError> {
+    check_name(name)?;
+    config
+        .get(&url_key(name))
+        .ok_or_else(|| GitError::RemoteError(format!("no such remote {}", name)))
+}
+
+pub fn add(config: &mut Config, name: &str, url: &str) -> Result<(), GitError> {
+    check_name(name)?;
+    if config.get(&url_key(name)).is_some() {
+        return Err(GitError::RemoteError(format!(
+            "remote {} already exists",
+            name
+        )));
+    }
+    check_url(url)?;
+    config.set(&url_key(name), url)
+}
+
+/// remove the remote with every setting of its section
+pub fn remove(config: &mut Config, name: &str) -> Result<(), GitError> {
+    url(config, name)?;
+    let prefix = format!("remote.{}.", name);
+    let keys: Vec<String> = config
+        .entries()
+        .keys()
+        .filter(|key| {
+            key.strip_prefix(&prefix)
+                .is_some_and(|rest| !rest.contains('.'))
+        })
+        .cloned()
+        .collect();
+    for key in keys {
+        config.unset(&key)?;
+    }
+    // branches tracking the remote do not any more
+    let tracking: Vec<String> = config
+        .entries()
+        .iter()
+        .filter_map(|(key, value)| {
+            let branch = key.strip_prefix("branch.")?.strip_suffix(".remote")?;
+            (value == name).then(|| branch.to_string())
+        })
+        .collect();
+    for branch in tracking {
+        unset_upstream(config, &branch)?;
+    }
+    Ok(())
+}
+
+/// upstream of a branch as (remote, branch of the remote), from `branch.<name>.remote` and
+/// `branch.<name>.merge`
+pub fn upstream(config: &Config, branch: &str) -> Option<(String, String)> {
+    let remote = config.get(&format!("branch.{}.remote", branch))?;
+    let merge = config.get(&format!("branch.{}.merge", branch))?;
+    let merge = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+    Some((remote.to_string(), merge.to_string()))
+}
+
+pub fn set_upstream(
+    config: &mut Config,
+    branch: &str,
+    remote: &str,
+    merge: &str,
+) -> Result<(), GitError> {
+    url(config, remote)?;
+    config.set(&format!("branch.{}.remote", branch), remote)?;
+    config.set(
+        &format!("branch.{}.merge", branch),
+        &format!("refs/heads/{}", merge),
+    )
+}
+
+pub fn unset_upstream(config: &mut Config, branch: &str) -> Result<(), GitError> {
+    config.unset(&format!("branch.{}.remote", branch))?;
+    config.unset(&format!("branch.{}.merge", branch))?;
+    Ok(())
+}
+
+/// move the settings of a branch, its upstream included, to its new name
+pub fn rename_branch(config: &mut Config, old: &str, new: &str) -> Result<(), GitError> {
+    let prefix = format!("branch.{}.", old);
+    let settings: Vec<(String, String)> = config
+        .entries()
+        .iter()
+        .filter_map(|(key, value)| {
+            let setting = key.strip_prefix(&prefix)?;
+            (!setting.contains('.')).then(|| (setting.to_string(), value.clone()))
+        })
+        .collect();
+    for (setting, value) in settings {
+        config.unset(&format!("{}{}", prefix, setting))?;
+        config.set(&format!("branch.{}.{}", new, setting), &value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remotes_ut() {
+        let mut config = Config::new();
+        assert!(add(&mut config, "origin", "../other").is_ok());
+        assert!(add(&mut config, "backup", "/srv/repo").is_ok());
+        assert!(config.set("remote.origin.pushurl", "../push").is_ok());
+        assert!(config.set("remote.origin.x.url", "nested").is_ok());
+        assert!(add(&mut config, "origin", "../again").is_err());
+        for name in ["", "a/b", "a b", "-x", "a..b"] {
+            assert!(add(&mut config, name, "../other").is_err());
+        }
+        assert!(add(&mut config, "empty", "").is_err());
+        assert_eq!(
+            cfg!(feature = "tls"),
+            add(&mut Config::new(), "secure", "https://example.com/repo").is_ok()
+        );
+        assert_eq!("../other", url(&config, "origin").unwrap());
+
+        assert!(remove(&mut config, "origin").is_ok());
+        assert!(url(&config, "origin").is_err());
+        assert_eq!(None, config.get("remote.origin.pushurl"));
+        assert_eq!(
+            vec![
+                ("backup".to_string(), "/srv/repo".to_string()),
+                ("origin.x".to_string(), "nested".to_string())
+            ],
+            remotes(&config).into_iter().collect::<Vec<_>>()
+        );
+        assert!(remove(&mut config, "origin").is_err());
+    }
+
+    #[test]
+    fn upstream_ut() {
+        let mut config = Config::new();
+        assert!(add(&mut config, "origin", "../other").is_ok());
+        assert!(set_upstream(&mut config, "topic", "missing", "main").is_err());
+        assert!(set_upstream(&mut config, "topic", "origin", "main").is_ok());
+        assert!(set_upstream(&mut config, "v1.x", "origin", "v1.x").is_ok());
+        assert_eq!(Some("refs/heads/main"), config.get("branch.topic.merge"));
+        assert_eq!(
+            Some(("origin".to_string(), "main".to_string())),
+            upstream(&config, "topic")
+        );
+        assert_eq!(
+            Some(("origin".to_string(), "v1.x".to_string())),
+            upstream(&config, "v1.x")
+        );
+        assert_eq!(None, upstream(&config, "main"));
+
+        assert!(rename_branch(&mut config, "v1", "v2").is_ok());
+        assert!(rename_branch(&mut config, "topic", "feature").is_ok());
+        assert_eq!(None, upstream(&config, "topic"));
+        assert_eq!(
+            Some(("origin".to_string(), "main".to_string())),
+            upstream(&config, "feature")
+        );
+        assert!(upstream(&config, "v1.x").is_some());
+
+        assert!(remove(&mut config, "origin").is_ok());
+        assert_eq!(None, upstream(&config, "feature"));
+        assert!(config.entries().is_empty());
+    }
+}
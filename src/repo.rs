@@ -1,35 +1,121 @@
+use crate::archive::{self, ArchiveFormat};
+use crate::attributes::Attributes;
+use crate::bisect::{self, BisectState};
+use crate::blame;
+use crate::bundle::Bundle;
+use crate::changelog;
+use crate::chunk::{self, ChunkParams};
+use crate::color::{Color, ColorMode, Palette};
+use crate::compress::{self, Compression};
+use crate::config::Config;
+use crate::conventional::LintRules;
+use crate::diff;
+use crate::entry::{self, Entry, FileMode};
+use crate::eol::{AutoCrlf, Conversion, EolFilter};
 use crate::error::GitError;
+use crate::fastimport::{self, DataRef, FileOp};
+use crate::gitobject::{self, ObjectDatabase, ObjectKind, ObjectStore, TreeEntry};
+use crate::graph;
+use crate::hooks::{self, HookRunner};
+use crate::ignore::{Ignore, Rule, IGNORE_FILE};
+use crate::materialize;
+use crate::merge::{self, MergeOptions, MergeState, Strategy};
+use crate::pack::{Pack, PackBuilder};
+use crate::patch::{self, FilePatch, Mail};
+use crate::rebase::{self, Action, CherryPickState, RebaseState, Step};
+use crate::release::{self, Bump, Version};
+use crate::remote;
+use crate::revision::{self, Ancestry};
+use crate::signing::{SignatureFormat, Signer};
+use crate::stash::{self, StashEntry};
+use crate::transport::{self, Advertisement, NetworkTransport, Transport, UploadRequest};
 use crate::utils;
+use crate::worktree::{self, GITDIR_FILE, WORKTREES_DIR};
 use chrono::{TimeZone, Utc};
 use log::info;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
-use std::io::{Read, Write};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::ops::Add;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
 use std::{env, fs};
 
 /// git repository directory
 pub const GIT_DIR: &str = ".git-rs";
 /// git blobs directory
 const BLOBS_DIR: &str = "blobs";
+/// git chunks directory for large blobs split by content-defined chunking
+const CHUNKS_DIR: &str = "chunks";
+/// git manifests directory, chunk ids of each chunked blob
+const MANIFESTS_DIR: &str = "manifests";
+/// git packs directory, blobs rolled up by repack
+const PACKS_DIR: &str = "packs";
+/// bare Git repository the objects are exported to in Git's own format
+const GIT_EXPORT_DIR: &str = "git";
+/// exported objects, blob and commit sha1 --> Git object id, in the export directory
+const GIT_EXPORT_MAP_FILE: &str = "rs-map";
+/// default minimal file size in bytes to store a blob as chunks, see `core.chunkThreshold`
+const CHUNK_THRESHOLD: usize = 1024 * 1024;
+/// least similarity in percent for `diff -C` to show an added file as a copy
+const COPY_SIMILARITY: usize = 50;
 /// git commits directory
 const COMMITS_DIR: &str = "commits";
 /// git index file
 const INDEX_FILE: &str = "index";
 /// git HEAD file
 const HEAD_FILE: &str = "HEAD";
+/// git config file
+const CONFIG_FILE: &str = "config";
+/// interactive rebase todo list, the steps still to run
+const REBASE_TODO_FILE: &str = "rebase-todo";
+/// interactive rebase progress, present while a rebase is in progress
+const REBASE_STATE_FILE: &str = "rebase-state";
+/// progress of a cherry-pick of several commits, present while one is stopped
+const CHERRY_PICK_STATE_FILE: &str = "cherry-pick-state";
+/// commit message being edited by a rebase reword
+const REBASE_MSG_FILE: &str = "REBASE_MSG";
+/// bisection progress, present while bisecting
+const BISECT_FILE: &str = "BISECT";
+/// state before the last merge, present after a merge that stopped on conflicts
+const MERGE_STATE_FILE: &str = "MERGE_STATE";
+/// stash entries, newest first
+const STASH_FILE: &str = "stash";
+/// notes directory, one file named after the commit sha1 per note
+const NOTES_DIR: &str = "notes";
+/// hooks directory, one executable file named after each hook, see `core.hooksPath`
+const HOOKS_DIR: &str = "hooks";
+/// commit message handed to the commit-msg hook
+const COMMIT_MSG_FILE: &str = "COMMIT_EDITMSG";
+/// commits of a shallow clone whose parents were not fetched, one sha1 a line
+const SHALLOW_FILE: &str = "shallow";
+/// ref logs directory, `logs/<ref>` lists the commits a ref pointed at, one `<old> <new>`
+/// line per move
+const LOGS_DIR: &str = "logs";
 /// git refs/heads directory
 const HEADS_DIR: &str = "refs/heads";
+/// git refs/tags directory
+const TAG_REFS_DIR: &str = "refs/tags";
+/// remote-tracking branches, `refs/remotes/<remote>/<branch>`
+const REMOTE_REFS_DIR: &str = "refs/remotes";
+/// git annotated tag objects directory
+const TAGS_DIR: &str = "tags";
+/// error message when an operation would overwrite an untracked file
+const UNTRACKED_IN_WAY: &str =
+    "There is an untracked file in the way; delete it, or add and commit it first.";
 /// git main branch name
 const MAIN_BRANCH: &str = "main";
+/// remote a clone is made from
+const ORIGIN: &str = "origin";
 
 /// Staging area for files to be committed
-/// staged: staged file path --> file sha1 pair
+/// staged: staged file path --> entry pair
 /// deleted: deleted file path --> file sha1 pair
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 struct StagingArea {
-    staged: BTreeMap<String, String>,
+    staged: BTreeMap<String, Entry>,
     deleted: BTreeMap<String, String>,
 }
 
@@ -42,9 +128,28 @@ impl StagingArea {
         }
     }
 
-    /// staged file path --> file sha1 pair
-    pub fn add(&mut self, path: String, hash: String) {
-        self.staged.insert(path, hash);
+    /// staged file path --> entry pair
+    pub fn add(&mut self, path: String, entry: Entry) {
+        self.staged.insert(path, entry);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty() && self.deleted.is_empty()
+    }
+
+    /// move the entries under the pathspecs into a new staging area, keep the others
+    pub fn split_off(&mut self, pathspecs: &[String]) -> Self {
+        fn take<V>(from: &mut BTreeMap<String, V>, pathspecs: &[String]) -> BTreeMap<String, V> {
+            let (matched, rest) = std::mem::take(from)
+                .into_iter()
+                .partition(|(path, _)| utils::pathspec_matches(pathspecs, path));
+            *from = rest;
+            matched
+        }
+        Self {
+            staged: take(&mut self.staged, pathspecs),
+            deleted: take(&mut self.deleted, pathspecs),
+        }
     }
 }
 
@@ -52,13 +157,31 @@ impl StagingArea {
 struct CommitMeta {
     message: String,
     date_time: i64,
+    /// `Name <email>` of who wrote the change and of who made the commit,
+    /// empty in commits made before they were recorded
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    author: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    committer: String,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Commit {
     meta: CommitMeta,
-    blobs: BTreeMap<String, String>,
+    blobs: BTreeMap<String, Entry>,
     parent: String,
+    /// parents other than the first one, only merge commits have them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merge_parents: Vec<String>,
+    /// signature of the commit without it, see `signed_payload`, empty when unsigned
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    signature: String,
+}
+
+impl Default for Commit {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Commit {
@@ -66,10 +189,14 @@ impl Commit {
         Self {
             meta: CommitMeta {
                 message: "".to_string(),
-                date_time: 0 as i64,
+                date_time: 0,
+                author: String::new(),
+                committer: String::new(),
             },
             blobs: BTreeMap::new(),
             parent: String::new(),
+            merge_parents: vec![],
+            signature: String::new(),
         }
     }
 
@@ -79,10 +206,99 @@ impl Commit {
             meta: CommitMeta {
                 message: "initial commit".to_string(),
                 date_time: Utc::now().timestamp(),
+                author: String::new(),
+                committer: String::new(),
             },
             blobs: BTreeMap::new(),
             parent: String::new(),
+            merge_parents: vec![],
+            signature: String::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_message(&mut self, message: &str) {
+        self.meta.message = message.to_string();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_parent(&mut self, parent: &str) {
+        self.parent = parent.to_string();
+    }
+
+    /// commit message
+    pub fn message(&self) -> &str {
+        &self.meta.message
+    }
+
+    /// commit timestamp in seconds
+    pub fn date_time(&self) -> i64 {
+        self.meta.date_time
+    }
+
+    /// `Name <email>` of the author, empty for commits made before authors were recorded
+    pub fn author(&self) -> &str {
+        &self.meta.author
+    }
+
+    /// `Name <email>` of the committer, empty for commits made before committers were recorded
+    pub fn committer(&self) -> &str {
+        &self.meta.committer
+    }
+
+    /// commit date formatted like `Thu Nov 9 20:00:05 2017 -0800`
+    pub fn date_string(&self) -> String {
+        #![allow(deprecated)]
+        Utc.timestamp(self.meta.date_time, 0)
+            .format("%a %b %e %T %Y %z")
+            .to_string()
+    }
+
+    /// tracked file path --> blob sha1 pairs
+    pub fn blobs(&self) -> &BTreeMap<String, Entry> {
+        &self.blobs
+    }
+
+    /// parent commit sha1, empty for the initial commit
+    pub fn parent(&self) -> &str {
+        &self.parent
+    }
+
+    /// signature made when committing, empty for unsigned commits
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// what the signature signs: the commit as JSON without its signature
+    pub fn signed_payload(&self) -> Result<String, GitError> {
+        let unsigned = Self {
+            signature: String::new(),
+            ..self.clone()
+        };
+        serde_json::to_string(&unsigned).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// `Author:` line and `Committer:` line when the committer is someone else, each one only
+    /// when recorded
+    pub fn identity_lines(&self) -> String {
+        let mut lines = String::new();
+        if !self.meta.author.is_empty() {
+            lines.push_str(&format!("Author: {}\n", self.meta.author));
+        }
+        if !self.meta.committer.is_empty() && self.meta.committer != self.meta.author {
+            lines.push_str(&format!("Committer: {}\n", self.meta.committer));
+        }
+        lines
+    }
+
+    /// all parent commit sha1s, the first parent first
+    pub fn parents(&self) -> Vec<&str> {
+        let mut parents = vec![];
+        if !self.parent.is_empty() {
+            parents.push(self.parent.as_str());
         }
+        parents.extend(self.merge_parents.iter().map(|p| p.as_str()));
+        parents
     }
 }
 
@@ -93,53 +309,336 @@ impl Commit {
 /// Date: Thu Nov 9 20:00:05 2017 -0800
 /// A commit message.
 ///
+/// merge commits also show the first seven digits of their parents, the committer is only
+/// shown when it is not the author
+/// ===
+/// commit 3e8bf1d794ca2e9ef8a4007275acf3751c7170ff
+/// Merge: 4975af1 2c1ead1
+/// Author: Jane <jane@example.com>
+/// Committer: Joe <joe@example.com>
+/// Date: Sat Nov 11 12:30:00 2017 -0800
+/// Merged development into master.
+///
 impl std::fmt::Display for Commit {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "===\ncommit {}\n", utils::sha1(&self).unwrap())?;
+        if !self.merge_parents.is_empty() {
+            let parents: Vec<&str> = self
+                .parents()
+                .into_iter()
+                .map(|p| &p[..p.len().min(7)])
+                .collect();
+            writeln!(f, "Merge: {}", parents.join(" "))?;
+        }
+        write!(f, "{}", self.identity_lines())?;
+        write!(f, "Date: {}\n{}\n", self.date_string(), self.meta.message)
+    }
+}
+/// Annotated tag object, persisted like a commit and referenced from refs/tags
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    object: String,
+    name: String,
+    message: String,
+    date_time: i64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    tagger: String,
+}
+
+impl Tag {
+    /// tagged commit sha1
+    pub fn object(&self) -> &str {
+        &self.object
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn date_time(&self) -> i64 {
+        self.date_time
+    }
+
+    /// `Name <email>` of whoever created the tag, empty for tags made before it was recorded
+    pub fn tagger(&self) -> &str {
+        &self.tagger
+    }
+
+    /// tag date formatted like `Thu Nov 9 20:00:05 2017 -0800`
+    pub fn date_string(&self) -> String {
         #![allow(deprecated)]
-        let date_time = Utc.timestamp(self.meta.date_time, 0);
-        let date_time_str = date_time.format("%a %b %e %T %Y %z").to_string();
-        write!(
-            f,
-            "===\ncommit {}\nDate: {}\n{}\n",
-            utils::sha1(&self).unwrap(),
-            date_time_str,
-            self.meta.message
-        )
+        Utc.timestamp(self.date_time, 0)
+            .format("%a %b %e %T %Y %z")
+            .to_string()
+    }
+}
+
+/// implement Display trait for Tag
+/// For example
+/// ===
+/// tag v1.0.0
+/// object a0da1ea5a15ab613bf9961fd86f010cf74c7ee48
+/// Tagger: Jane <jane@example.com>
+/// Date: Thu Nov 9 20:00:05 2017 -0800
+///
+/// First release.
+/// ===
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "tag {}\nobject {}", self.name, self.object)?;
+        if !self.tagger.is_empty() {
+            writeln!(f, "Tagger: {}", self.tagger)?;
+        }
+        writeln!(f, "Date: {}\n\n{}", self.date_string(), self.message)
+    }
+}
+
+/// Structured form of `status`
+/// branch: current branch, branches: other branches
+/// modified: modifications not staged for commit, e.g. `f1 (modified)`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub branch: String,
+    pub branches: Vec<String>,
+    pub staged: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    pub untracked: Vec<String>,
+}
+
+/// Which files `ls_files` lists, every file in the index when none is set
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LsFilesOptions {
+    /// files staged for addition
+    pub staged: bool,
+    /// files in the index deleted from the working directory
+    pub deleted: bool,
+    /// files neither tracked nor staged
+    pub others: bool,
+    /// files in the index changed in the working directory, deletions included
+    pub modified: bool,
+}
+
+/// Size of the object store reported by `count_objects`, sizes are bytes on disk
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ObjectCounts {
+    pub commits: usize,
+    /// annotated tag objects
+    pub tags: usize,
+    /// whole, chunked and packed blobs
+    pub blobs: usize,
+    pub chunks: usize,
+    /// blobs stored in packs
+    pub in_pack: usize,
+    pub packs: usize,
+    pub size: u64,
+    /// objects gc would delete
+    pub unreachable: usize,
+    pub unreachable_size: u64,
+}
+
+/// How `diff_staged_with` and `diff_commits_with` compare trees
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DiffOptions {
+    /// show added files copied from a file of the old tree as copies, `-C`
+    pub find_copies: bool,
+    /// color file names, hunk headers and changed lines, see `diff::unified_with`
+    pub color: bool,
+    /// only the files under these pathspecs, all when empty; working directory and staged
+    /// diffs only
+    pub paths: Vec<String>,
+}
+
+/// How `status_with` shows the files
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StatusOptions {
+    /// staged files green, modified and deleted ones red and untracked ones cyan
+    pub color: bool,
+}
+
+/// Which commits `log_with` shows and what it shows besides them
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LogOptions {
+    /// tell after each commit id whether it is unsigned or its signature is good or bad
+    pub show_signature: bool,
+    /// list the branches, remote-tracking branches and tags pointing at each commit
+    pub decorate: bool,
+    /// follow the message of each commit other than a merge with the diffstat of its
+    /// changes, see `diff::stat`
+    pub stat: bool,
+    /// show the commits of a revision range, see `commit_range`, instead of the history of HEAD
+    pub range: Option<String>,
+    /// show the commits of the history of HEAD that changed this file, following renames,
+    /// see `follow_history`. Takes precedence over `range`.
+    pub follow: Option<String>,
+    /// show at most this many of the commits passing the filters below
+    pub max_count: Option<usize>,
+    /// only commits made at or after this date, see `revision::parse_date`
+    pub since: Option<String>,
+    /// only commits made at or before this date
+    pub until: Option<String>,
+    /// only commits whose author contains this text
+    pub author: Option<String>,
+    /// only commits whose message matches this regex
+    pub grep: Option<String>,
+}
+
+/// test a commit must pass to be shown by `log_with`
+type CommitFilter = Box<dyn Fn(&Commit) -> bool>;
+
+impl LogOptions {
+    /// one filter per option given, a commit is shown when it passes all of them
+    fn filters(&self) -> Result<Vec<CommitFilter>, GitError> {
+        let now = Utc::now().timestamp();
+        let mut filters: Vec<CommitFilter> = vec![];
+        if let Some(since) = &self.since {
+            let since = revision::parse_date(since, now)?;
+            filters.push(Box::new(move |commit| commit.date_time() >= since));
+        }
+        if let Some(until) = &self.until {
+            let until = revision::parse_date(until, now)?;
+            filters.push(Box::new(move |commit| commit.date_time() <= until));
+        }
+        if let Some(author) = self.author.clone() {
+            filters.push(Box::new(move |commit| commit.author().contains(&author)));
+        }
+        if let Some(pattern) = &self.grep {
+            let regex = Regex::new(pattern).map_err(|e| GitError::GrepError(format!("{}", e)))?;
+            filters.push(Box::new(move |commit| regex.is_match(commit.message())));
+        }
+        Ok(filters)
     }
 }
+
+/// Which branches `receive_with` may move backwards or sideways, tags are never replaced
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ReceiveOptions {
+    /// any branch
+    pub force: bool,
+    /// only branches still at this commit, empty for branches that must not exist yet
+    pub lease: Option<String>,
+}
+
+/// How `push_with` overwrites the branch of the remote
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PushOptions {
+    /// even when the push is not a fast-forward, `--force`
+    pub force: bool,
+    /// only when the branch of the remote is still where its remote-tracking branch last saw
+    /// it, `--force-with-lease`
+    pub force_with_lease: bool,
+}
+
+/// How `commit_with` makes the commit
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CommitOptions {
+    /// `Name <email>` of the author, the committer when None
+    pub author: Option<String>,
+    /// sign the commit even when commit.gpgSign is not set
+    pub sign: bool,
+    /// skip the pre-commit and commit-msg hooks
+    pub no_verify: bool,
+}
+
 pub struct GitRepository {
     pub repo_path: PathBuf,
     cwd: PathBuf,
     blobs_path: PathBuf,
+    chunks_path: PathBuf,
+    manifests_path: PathBuf,
+    packs_path: PathBuf,
     commits_path: PathBuf,
     head_file: PathBuf,
     index_file: PathBuf,
     heads_path: PathBuf,
+    tag_refs_path: PathBuf,
+    tags_path: PathBuf,
+    config_file: PathBuf,
+    rebase_todo_file: PathBuf,
+    rebase_state_file: PathBuf,
+    cherry_pick_state_file: PathBuf,
+    rebase_msg_file: PathBuf,
+    stash_file: PathBuf,
+    bisect_file: PathBuf,
+    merge_state_file: PathBuf,
+    notes_path: PathBuf,
+    commit_msg_file: PathBuf,
+    shallow_file: PathBuf,
     staging_area: StagingArea,
     commit: Commit,
     commit_sha1: String,
     branch: String,
+    warnings: Vec<String>,
 }
 
 impl GitRepository {
     pub fn new(git_dir: &str) -> Self {
         let cwd = &env::current_dir().unwrap();
-        let repo_path = &cwd.join(git_dir);
+        Self::at(cwd, &cwd.join(git_dir))
+    }
+
+    /// Repository with the working directory `cwd` and the git directory `git_dir`. The git
+    /// directory of a linked worktree is a file pointing at its administrative directory, see
+    /// `worktree_add`: HEAD, the index and the state of the operations in progress are kept
+    /// there, everything else is shared with the repository.
+    fn at(cwd: &Path, git_dir: &Path) -> Self {
+        let (repo_path, worktree_path) = match worktree::admin_dir(git_dir) {
+            Some(admin) => (
+                admin
+                    .parent()
+                    .and_then(Path::parent)
+                    .map_or_else(|| admin.clone(), Path::to_path_buf),
+                admin,
+            ),
+            None => (git_dir.to_path_buf(), git_dir.to_path_buf()),
+        };
+        Self::with_dirs(cwd, &repo_path, &worktree_path)
+    }
+
+    /// Repository of the worktree at `cwd` whose own files are kept in `worktree_path`, the
+    /// administrative directory of a linked worktree or the repository for the main one
+    fn with_dirs(cwd: &Path, repo_path: &Path, worktree_path: &Path) -> Self {
         Self {
             cwd: cwd.to_owned(),
             repo_path: repo_path.to_owned(),
             blobs_path: repo_path.join(BLOBS_DIR),
+            chunks_path: repo_path.join(CHUNKS_DIR),
+            manifests_path: repo_path.join(MANIFESTS_DIR),
+            packs_path: repo_path.join(PACKS_DIR),
             commits_path: repo_path.join(COMMITS_DIR),
-            head_file: repo_path.join(HEAD_FILE),
-            index_file: repo_path.join(INDEX_FILE),
+            head_file: worktree_path.join(HEAD_FILE),
+            index_file: worktree_path.join(INDEX_FILE),
             heads_path: repo_path.join(HEADS_DIR),
+            tag_refs_path: repo_path.join(TAG_REFS_DIR),
+            tags_path: repo_path.join(TAGS_DIR),
+            config_file: repo_path.join(CONFIG_FILE),
+            rebase_todo_file: worktree_path.join(REBASE_TODO_FILE),
+            rebase_state_file: worktree_path.join(REBASE_STATE_FILE),
+            cherry_pick_state_file: worktree_path.join(CHERRY_PICK_STATE_FILE),
+            rebase_msg_file: worktree_path.join(REBASE_MSG_FILE),
+            stash_file: repo_path.join(STASH_FILE),
+            bisect_file: worktree_path.join(BISECT_FILE),
+            merge_state_file: worktree_path.join(MERGE_STATE_FILE),
+            notes_path: repo_path.join(NOTES_DIR),
+            commit_msg_file: worktree_path.join(COMMIT_MSG_FILE),
+            shallow_file: repo_path.join(SHALLOW_FILE),
             staging_area: StagingArea::new(),
             commit: Commit::new(),
             commit_sha1: String::new(),
             branch: MAIN_BRANCH.to_string(),
+            warnings: vec![],
         }
     }
 
+    /// non-fatal problems collected since the last call, e.g. files skipped by a worktree scan
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
     /// init repository directory including .git, commits, blobs, etc
     fn init_repo_dir(path: &PathBuf) -> Result<(), GitError> {
         if !path.exists() {
@@ -160,8 +659,12 @@ impl GitRepository {
     pub fn init(&self) -> Result<(), GitError> {
         Self::init_repo_dir(&self.repo_path)?;
         Self::init_repo_dir(&self.blobs_path)?;
+        Self::init_repo_dir(&self.chunks_path)?;
+        Self::init_repo_dir(&self.manifests_path)?;
         Self::init_repo_dir(&self.commits_path)?;
         Self::init_repo_dir(&self.heads_path)?;
+        Self::init_repo_dir(&self.tag_refs_path)?;
+        Self::init_repo_dir(&self.tags_path)?;
         Self::init_repo_file(&self.index_file, "")?;
         self.init_commit()?;
         Ok(())
@@ -172,7 +675,7 @@ impl GitRepository {
     fn init_commit(&self) -> Result<(), GitError> {
         let commit = Commit::init_commit();
         let sha1 = utils::sha1(&commit)?;
-        Self::persist(&commit, &self.commits_path.join(&sha1))?;
+        self.persist_object(&commit, &self.commit_file(&sha1)?)?;
         Self::init_repo_file(&self.heads_path.join(&self.branch), sha1.as_str())?;
         Self::init_repo_file(&self.heads_path.join(MAIN_BRANCH), "")?;
         Self::init_repo_file(
@@ -201,13 +704,13 @@ impl GitRepository {
 
     /// load current commit
     fn load_current_commit(&mut self) -> Result<(), GitError> {
-        self.commit_sha1 = fs::read_to_string(&self.repo_path.join(&self.branch))
+        self.commit_sha1 = fs::read_to_string(self.repo_path.join(&self.branch))
             .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
         info!("current commit: {}", &self.commit_sha1);
         if self.commit_sha1.is_empty() {
             self.commit = Commit::new();
         } else {
-            self.commit = Self::unpersist_commit(&self.commits_path.join(&self.commit_sha1))?;
+            self.commit = self.read_commit(&self.commit_sha1)?;
             info!("{:?}", self.commit);
         }
         Ok(())
@@ -236,26 +739,142 @@ impl GitRepository {
         info!("persist_basic_info");
         Self::persist(&self.staging_area, &self.index_file)?;
         if !&self.commit_sha1.is_empty() {
-            Self::persist(&self.commit, &self.commits_path.join(&self.commit_sha1))?;
-            fs::write(&self.repo_path.join(&self.branch), &self.commit_sha1)
-                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            self.persist_object(&self.commit, &self.commit_file(&self.commit_sha1)?)?;
+            self.update_ref(&self.branch, &self.commit_sha1)?;
         }
         info!("persist_basic_info done!");
         Ok(())
     }
-    pub fn add(&mut self, paths: &Vec<String>) -> Result<(), GitError> {
+
+    /// point a ref, e.g. `refs/heads/main`, at a commit and log the move in `logs/<ref>`
+    fn update_ref(&self, name: &str, sha1: &str) -> Result<(), GitError> {
+        let file = self.repo_path.join(name);
+        let old = fs::read_to_string(&file).unwrap_or_default();
+        fs::write(&file, sha1).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if old == sha1 {
+            return Ok(());
+        }
+        let log_file = self.repo_path.join(LOGS_DIR).join(name);
+        if let Some(dir) = log_file.parent() {
+            fs::create_dir_all(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        writeln!(log, "{} {}", old, sha1).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// commits a ref pointed at, newest first, as logged by `update_ref`
+    fn reflog(&self, name: &str) -> Vec<String> {
+        let log = fs::read_to_string(self.repo_path.join(LOGS_DIR).join(name)).unwrap_or_default();
+        log.lines()
+            .rev()
+            .filter_map(|line| line.split_once(' ').map(|(_, new)| new.to_string()))
+            .collect()
+    }
+
+    /// every commit any ref pointed at, old and new sides of all the logs under `logs/`
+    fn reflog_commits(&self) -> Result<HashSet<String>, GitError> {
+        let mut commits = HashSet::new();
+        let mut dirs = vec![self.repo_path.join(LOGS_DIR)];
+        while let Some(dir) = dirs.pop() {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in
+                fs::read_dir(&dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+            {
+                let path = entry
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                    .path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let log = fs::read_to_string(&path)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                commits.extend(
+                    log.split_whitespace()
+                        .filter(|sha1| utils::is_sha1(sha1))
+                        .map(|sha1| sha1.to_string()),
+                );
+            }
+        }
+        Ok(commits)
+    }
+    pub fn add(&mut self, paths: &[String]) -> Result<(), GitError> {
         self.load_basic_info()?;
         for path in paths.iter() {
-            self.add_file(&self.cwd.join(&path))?
+            self.add_file(&self.cwd.join(path))?
+        }
+        self.persist_basic_info()?;
+        Ok(())
+    }
+
+    /// stage every change in the working directory under the given paths (all when empty):
+    /// new and modified files are staged for addition, deleted tracked files for removal
+    pub fn add_all(&mut self, paths: &[String]) -> Result<(), GitError> {
+        self.stage_changes(paths, true)
+    }
+
+    /// stage every tracked file modified or deleted in the working directory under the given
+    /// paths (all when empty), untracked files are left alone
+    pub fn add_update(&mut self, paths: &[String]) -> Result<(), GitError> {
+        self.stage_changes(paths, false)
+    }
+
+    fn stage_changes(&mut self, paths: &[String], untracked: bool) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        let matched = |path: &str| utils::pathspec_matches(paths, path);
+        let mut file_sha1_map = self.working_file_sha1_map()?;
+        file_sha1_map.retain(|path, _| {
+            matched(path)
+                && (untracked
+                    || self.commit.blobs.contains_key(path)
+                    || self.staging_area.staged.contains_key(path))
+        });
+        for (path, sha1) in file_sha1_map.iter() {
+            let unchanged = match self.staging_area.staged.get(path) {
+                Some(staged) => staged == sha1,
+                None => self.commit.blobs.get(path) == Some(sha1),
+            };
+            if !unchanged {
+                self.add_file(&self.cwd.join(path))?;
+            }
+            self.staging_area.deleted.remove(path);
         }
+        self.staging_area
+            .staged
+            .retain(|path, _| !matched(path) || file_sha1_map.contains_key(path));
+        for path in self.commit.blobs.keys() {
+            // unreadable files still exist and are not deleted
+            if matched(path) && !entry::exists(&self.cwd.join(path)) {
+                self.staging_area
+                    .deleted
+                    .insert(path.clone(), "".to_string());
+            }
+        }
+        self.persist_basic_info()?;
+        Ok(())
+    }
+
+    /// drop the staged additions and removals under the given paths, the working directory
+    /// is left as it is
+    pub fn unstage(&mut self, paths: &[String]) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        let matched = |path: &String| utils::pathspec_matches(paths, path);
+        self.staging_area.staged.retain(|path, _| !matched(path));
+        self.staging_area.deleted.retain(|path, _| !matched(path));
         self.persist_basic_info()?;
         Ok(())
     }
 
-    pub fn remove(&mut self, paths: &Vec<String>) -> Result<(), GitError> {
+    pub fn remove(&mut self, paths: &[String]) -> Result<(), GitError> {
         self.load_basic_info()?;
         for path in paths.iter() {
-            self.remove_file(&self.cwd.join(&path))?
+            self.remove_file(&self.cwd.join(path))?
         }
         self.persist_basic_info()?;
         Ok(())
@@ -263,9 +882,9 @@ impl GitRepository {
 
     /// create new commit blobs with parent commit's blobs and staging area info
     fn generate_commit_blobs(
-        old_blobs: &BTreeMap<String, String>,
+        old_blobs: &BTreeMap<String, Entry>,
         adding_staged: &StagingArea,
-    ) -> Result<BTreeMap<String, String>, GitError> {
+    ) -> Result<BTreeMap<String, Entry>, GitError> {
         let mut new_blobs = old_blobs.clone();
         for (k, v) in adding_staged.staged.iter() {
             new_blobs.insert(k.to_owned(), v.to_owned());
@@ -278,36 +897,377 @@ impl GitRepository {
         Ok(new_blobs)
     }
 
+    /// repository configuration from the config file
+    pub fn config(&self) -> Result<Config, GitError> {
+        Config::load(&self.config_file)
+    }
+
+    /// set a configuration entry
+    pub fn set_config(&self, key: &str, value: &str) -> Result<(), GitError> {
+        let mut config = self.config()?;
+        config.set(key, value)?;
+        config.save(&self.config_file)
+    }
+
+    /// remove a configuration entry
+    pub fn unset_config(&self, key: &str) -> Result<(), GitError> {
+        let mut config = self.config()?;
+        match config.unset(key)? {
+            Some(_) => config.save(&self.config_file),
+            None => Err(GitError::ConfigError(format!("no such key {}", key))),
+        }
+    }
+
+    /// remotes, name --> url
+    pub fn remotes(&self) -> Result<BTreeMap<String, String>, GitError> {
+        Ok(remote::remotes(&self.config()?))
+    }
+
+    /// largest bundle a push can send to this repository over the network, from
+    /// `receive.maxInputSize`, unset or 0 for no limit
+    pub fn max_input_size(&self) -> Result<Option<usize>, GitError> {
+        Ok(self
+            .config()?
+            .get_usize("receive.maxInputSize")?
+            .filter(|size| *size > 0))
+    }
+
+    /// add a remote, the path of another repository, a http:// or a git-rs:// url
+    pub fn remote_add(&self, name: &str, url: &str) -> Result<(), GitError> {
+        let mut config = self.config()?;
+        remote::add(&mut config, name, url)?;
+        config.save(&self.config_file)
+    }
+
+    /// remove a remote and its settings
+    pub fn remote_remove(&self, name: &str) -> Result<(), GitError> {
+        let mut config = self.config()?;
+        remote::remove(&mut config, name)?;
+        config.save(&self.config_file)
+    }
+
+    /// Make `upstream`, a remote-tracking branch `<remote>/<branch>`, the upstream of a
+    /// branch, the current one by default
+    pub fn set_upstream(
+        &mut self,
+        upstream: &str,
+        branch: Option<&str>,
+    ) -> Result<String, GitError> {
+        self.load_branch()?;
+        let branch = branch.map_or_else(|| self.current_branch(), |b| b.to_string());
+        if !self.heads_path.join(&branch).is_file() {
+            return Err(GitError::BranchError(format!(
+                "branch {} does not exist",
+                branch
+            )));
+        }
+        if self.remote_branch_file(upstream).is_none() {
+            return Err(GitError::BranchError(format!(
+                "{} is not a remote-tracking branch",
+                upstream
+            )));
+        }
+        let (remote, merge) = upstream.split_once('/').unwrap();
+        let mut config = self.config()?;
+        remote::set_upstream(&mut config, &branch, remote, merge)?;
+        config.save(&self.config_file)?;
+        Ok(format!(
+            "branch '{}' set up to track '{}'.",
+            branch, upstream
+        ))
+    }
+
+    /// How the current branch compares with its upstream, None without an upstream, e.g.
+    /// `Your branch is ahead of 'origin/main' by 2 commits.`
+    fn tracking_status(&self) -> Result<Option<String>, GitError> {
+        let Some((remote, merge)) = remote::upstream(&self.config()?, &self.current_branch())
+        else {
+            return Ok(None);
+        };
+        let upstream = format!("{}/{}", remote, merge);
+        let Some(file) = self.remote_branch_file(&upstream) else {
+            return Ok(Some(format!(
+                "Your branch is based on '{}', but the upstream is gone.",
+                upstream
+            )));
+        };
+        let theirs =
+            fs::read_to_string(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let ours = self.ancestors(&self.commit_sha1)?;
+        let theirs = self.ancestors(&theirs)?;
+        let ahead = ours.difference(&theirs).count();
+        let behind = theirs.difference(&ours).count();
+        let commits = |n: usize| match n {
+            1 => "1 commit".to_string(),
+            n => format!("{} commits", n),
+        };
+        Ok(Some(match (ahead, behind) {
+            (0, 0) => format!("Your branch is up to date with '{}'.", upstream),
+            (ahead, 0) => format!(
+                "Your branch is ahead of '{}' by {}.",
+                upstream,
+                commits(ahead)
+            ),
+            (0, behind) => format!(
+                "Your branch is behind '{}' by {}, and can be fast-forwarded.",
+                upstream,
+                commits(behind)
+            ),
+            (ahead, behind) => format!(
+                "Your branch and '{}' have diverged,\nand have {} and {} different commits each, respectively.",
+                upstream, ahead, behind
+            ),
+        }))
+    }
+
+    /// reject the commit message if `commit.lint` is enabled and the message breaks a lint rule
+    fn lint_commit_message(&self, msg: &str) -> Result<(), GitError> {
+        let config = self.config()?;
+        if !config.get_bool("commit.lint")?.unwrap_or(false) {
+            return Ok(());
+        }
+        let violations = LintRules::from_config(&config)?.check(msg);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(GitError::CommitError(format!(
+                "commit message rejected by commit.lint:\n  {}",
+                violations.join("\n  ")
+            )))
+        }
+    }
+
     /// commit
     pub fn commit(&mut self, msg: &str) -> Result<(), GitError> {
+        self.commit_paths(msg, &[])
+    }
+
+    /// commit only the staged changes under the pathspecs, every staged change when empty.
+    /// Staged changes outside the pathspecs stay in the index for a later commit.
+    pub fn commit_paths(&mut self, msg: &str, paths: &[String]) -> Result<(), GitError> {
+        self.commit_with(msg, paths, &CommitOptions::default())
+    }
+
+    /// `commit_paths` recording someone else than the committer configured by user.name and
+    /// user.email as author, or signing the commit
+    pub fn commit_with(
+        &mut self,
+        msg: &str,
+        paths: &[String],
+        options: &CommitOptions,
+    ) -> Result<(), GitError> {
+        self.commit_at(msg, paths, Utc::now().timestamp(), options)
+    }
+
+    /// `commit_with` with the given commit date in seconds
+    fn commit_at(
+        &mut self,
+        msg: &str,
+        paths: &[String],
+        date_time: i64,
+        options: &CommitOptions,
+    ) -> Result<(), GitError> {
+        if !options.no_verify {
+            self.hooks()?.run(hooks::PRE_COMMIT, &[])?;
+        }
         self.load_basic_info()?;
+        let msg = &match options.no_verify {
+            true => msg.to_string(),
+            false => self.commit_msg_hook(msg)?,
+        };
+        self.lint_commit_message(msg)?;
+        let committer = self.identity()?;
+        let author = match options.author.as_deref().map(str::trim) {
+            Some("") => return Err(GitError::CommitError("empty author".to_string())),
+            Some(author) => author.to_string(),
+            None => committer.clone(),
+        };
         info!("commit start...");
-        for (removed_path, _) in self.staging_area.deleted.iter() {
-            if self.cwd.join(removed_path).exists() {
-                fs::remove_file(&self.cwd.join(removed_path)).map_err(|_| {
+        let staging_area = self.staging_area.split_off(paths);
+        if !paths.is_empty() && staging_area.is_empty() {
+            return Err(GitError::CommitError(format!(
+                "no staged changes match {}",
+                paths.join(" ")
+            )));
+        }
+        self.remove_staged_deletions(&staging_area)?;
+        let blobs = Self::generate_commit_blobs(&self.commit.blobs, &staging_area)
+            .map_err(|e| GitError::CommitError(format!("{:?}", e)))?;
+        self.commit = Commit {
+            meta: CommitMeta {
+                message: msg.to_string(),
+                date_time,
+                author,
+                committer,
+            },
+            blobs,
+            parent: self.commit_sha1.clone(),
+            merge_parents: vec![],
+            signature: String::new(),
+        };
+        self.sign_commit(options.sign)?;
+        self.commit_sha1 = utils::sha1(&self.commit)?;
+        self.persist_basic_info()?;
+        if self.config()?.get_bool("core.gitObjects")?.unwrap_or(false) {
+            self.export_git()?;
+        }
+        // the commit is made whatever post-commit does
+        let _ = self.hooks()?.run(hooks::POST_COMMIT, &[]);
+        Ok(())
+    }
+
+    /// hooks of `core.hooksPath`, relative to the working directory, or of the hooks directory
+    fn hooks(&self) -> Result<HookRunner, GitError> {
+        let dir = match self.config()?.get("core.hooksPath") {
+            Some(dir) => self.cwd.join(dir),
+            None => self.repo_path.join(HOOKS_DIR),
+        };
+        Ok(HookRunner::new(&dir, &self.cwd)
+            .env("GIT_DIR", &self.repo_path.display().to_string())
+            .env("GIT_INDEX_FILE", &self.index_file.display().to_string())
+            .env("GIT_WORK_TREE", &self.cwd.display().to_string()))
+    }
+
+    /// commit message after the commit-msg hook, which gets the message file and may rewrite it
+    fn commit_msg_hook(&self, msg: &str) -> Result<String, GitError> {
+        let hooks = self.hooks()?;
+        if hooks.find(hooks::COMMIT_MSG).is_none() {
+            return Ok(msg.to_string());
+        }
+        fs::write(&self.commit_msg_file, msg)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        hooks.run(
+            hooks::COMMIT_MSG,
+            &[&self.commit_msg_file.display().to_string()],
+        )?;
+        let msg = fs::read_to_string(&self.commit_msg_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let msg = msg.trim_end();
+        if msg.is_empty() {
+            return Err(GitError::HookError(
+                "commit-msg hook left an empty message".to_string(),
+            ));
+        }
+        Ok(msg.to_string())
+    }
+
+    /// Sign the head commit when `force` or commit.gpgSign is set, with the key of
+    /// user.signingKey and the program of gpg.format
+    fn sign_commit(&mut self, force: bool) -> Result<(), GitError> {
+        if force || self.config()?.get_bool("commit.gpgSign")?.unwrap_or(false) {
+            self.commit.signature = self.signer()?.sign(&self.commit.signed_payload()?)?;
+        }
+        Ok(())
+    }
+
+    /// signer configured by gpg.format, gpg.program or gpg.ssh.program, user.signingKey
+    /// and gpg.ssh.allowedSignersFile
+    fn signer(&self) -> Result<Signer, GitError> {
+        let config = self.config()?;
+        let format = SignatureFormat::parse(config.get("gpg.format").unwrap_or("openpgp"))?;
+        let program = match format {
+            SignatureFormat::OpenPgp => config.get("gpg.program"),
+            SignatureFormat::Ssh => config.get("gpg.ssh.program"),
+        };
+        Ok(Signer {
+            format,
+            program: program.unwrap_or(format.default_program()).to_string(),
+            key: config.get("user.signingKey").map(str::to_string),
+            allowed_signers: config.get("gpg.ssh.allowedSignersFile").map(PathBuf::from),
+        })
+    }
+
+    /// Verify the signature of a commit, returns the verifier's description of a good signature
+    pub fn verify_commit(&self, rev: &str) -> Result<String, GitError> {
+        let sha1 = self.resolve_commit(rev)?;
+        let commit = self.read_commit(&sha1)?;
+        if commit.signature.is_empty() {
+            return Err(GitError::SignatureError(format!(
+                "commit {} has no signature",
+                sha1
+            )));
+        }
+        self.signer()?
+            .verify(&commit.signed_payload()?, &commit.signature)
+    }
+
+    /// `Signature:` line of log --show-signature
+    fn signature_line(&self, commit: &Commit) -> Result<String, GitError> {
+        if commit.signature.is_empty() {
+            return Ok("Signature: none\n".to_string());
+        }
+        let signer = self.signer()?;
+        Ok(
+            match signer.verify(&commit.signed_payload()?, &commit.signature) {
+                Ok(good) => format!("Signature: {}\n", good.lines().next().unwrap_or("good")),
+                Err(err) => format!("Signature: BAD {:?}\n", err),
+            },
+        )
+    }
+
+    /// delete the files staged for removal from the working directory
+    fn remove_staged_deletions(&self, staging_area: &StagingArea) -> Result<(), GitError> {
+        for (removed_path, _) in staging_area.deleted.iter() {
+            if entry::exists(&self.cwd.join(removed_path)) {
+                fs::remove_file(self.cwd.join(removed_path)).map_err(|_| {
                     GitError::CommitError("fail to remove file from current workspace".to_string())
                 })?;
             }
         }
-        let blobs = Self::generate_commit_blobs(&self.commit.blobs, &self.staging_area)
+        Ok(())
+    }
+
+    /// Replace the head commit with a new one that has the same parents, its files plus every
+    /// staged change, and the given message or the old one, then move the branch to it
+    pub fn amend(&mut self, msg: Option<&str>) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        if self.commit.parent.is_empty() {
+            return Err(GitError::CommitError(
+                "Cannot amend the initial commit.".to_string(),
+            ));
+        }
+        self.hooks()?.run(hooks::PRE_COMMIT, &[])?;
+        self.load_basic_info()?;
+        let message = match msg {
+            Some(msg) => {
+                let msg = self.commit_msg_hook(msg)?;
+                self.lint_commit_message(&msg)?;
+                msg
+            }
+            None => self.commit_msg_hook(&self.commit.meta.message)?,
+        };
+        let committer = self.identity()?;
+        let author = match self.commit.meta.author.as_str() {
+            "" => committer.clone(),
+            author => author.to_string(),
+        };
+        let staging_area = self.staging_area.split_off(&[]);
+        self.remove_staged_deletions(&staging_area)?;
+        let blobs = Self::generate_commit_blobs(&self.commit.blobs, &staging_area)
             .map_err(|e| GitError::CommitError(format!("{:?}", e)))?;
-        self.staging_area = StagingArea::new();
         self.commit = Commit {
             meta: CommitMeta {
-                message: msg.to_string(),
+                message,
                 date_time: Utc::now().timestamp(),
+                author,
+                committer,
             },
             blobs,
-            parent: self.commit_sha1.clone(),
+            parent: self.commit.parent.clone(),
+            merge_parents: self.commit.merge_parents.clone(),
+            signature: String::new(),
         };
+        self.sign_commit(false)?;
         self.commit_sha1 = utils::sha1(&self.commit)?;
         self.persist_basic_info()?;
+        let _ = self.hooks()?.run(hooks::POST_COMMIT, &[]);
         Ok(())
     }
 
     /// Branch
     pub fn branch(&mut self, name: &str) -> Result<(), GitError> {
         self.load_basic_info()?;
+        revision::check_name(name)?;
         let branch_file = self.heads_path.join(name);
         if branch_file.exists() {
             Err(GitError::BranchError(format!(
@@ -321,567 +1281,11030 @@ impl GitRepository {
                 .to_str()
                 .unwrap()
                 .to_string();
-            fs::write(&branch_file, &self.commit_sha1)
-                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            self.update_ref(&self.branch, &self.commit_sha1)?;
             fs::write(&self.head_file, self.branch.as_bytes())
                 .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
             Ok(())
         }
     }
-    /// Displays Untracked Files
-    /// The final category (“Untracked Files”) is for files present in the working directory
-    /// but neither staged for addition nor tracked.
-    /// This includes files that have been staged for removal,
-    /// but then re-created without Gitlet’s knowledge.
-    fn untrack_status(&self) -> Result<String, GitError> {
-        let ignore_set = HashSet::from([
-            self.repo_path.clone(),
-            self.cwd.join("target"),
-            self.cwd.join(".git"),
-            self.cwd.join(".idea"),
-            self.cwd.join(".DS_Store"),
-            self.cwd.join("doc/.DS_Store"),
-        ]); // Initialize an empty HashSet
-        let file_sha1_map: BTreeMap<String, String> =
-            utils::generate_file_sha1_map(&self.cwd, &ignore_set)?;
-        let mut msg: Vec<String> = vec![];
-        msg.push("=== Untracked Files ===".to_string());
-        msg.extend(Self::untracked_file(
-            &file_sha1_map,
-            &self.commit.blobs,
-            &self.staging_area.staged,
-        ));
-        Ok(msg.join("\n"))
+    /// Delete a branch, e.g. `Deleted branch topic (was 1a2b3c4).`. A branch checked out in
+    /// a worktree is never deleted, one whose tip the current branch can not reach, its
+    /// unmerged commits would be lost, only with `force`.
+    pub fn branch_delete(&mut self, name: &str, force: bool) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let branch_file = self.heads_path.join(name);
+        if !branch_file.is_file() {
+            return Err(GitError::BranchError(format!(
+                "branch {} does not exist",
+                name
+            )));
+        }
+        let head = format!("{}/{}", HEADS_DIR, name);
+        if let Some((path, _, _)) = self.worktrees()?.into_iter().find(|(_, h, _)| *h == head) {
+            return Err(GitError::BranchError(format!(
+                "cannot delete branch '{}' checked out at '{}'",
+                name,
+                path.display()
+            )));
+        }
+        let sha1 = fs::read_to_string(&branch_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if !force && !self.is_ancestor(&sha1, &self.commit_sha1)? {
+            return Err(GitError::BranchError(format!(
+                "branch '{}' is not fully merged, use -D to delete it anyway",
+                name
+            )));
+        }
+        fs::remove_file(&branch_file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let _ = fs::remove_file(self.repo_path.join(LOGS_DIR).join(&head));
+        let mut config = self.config()?;
+        remote::unset_upstream(&mut config, name)?;
+        config.save(&self.config_file)?;
+        Ok(format!(
+            "Deleted branch {} (was {}).",
+            name,
+            &sha1[..sha1.len().min(revision::DEFAULT_ABBREV)]
+        ))
     }
 
-    /// Untracked file
-    fn untracked_file(
-        file_sha1_map: &BTreeMap<String, String>,
-        commit: &BTreeMap<String, String>,
-        staged: &BTreeMap<String, String>,
-    ) -> Vec<String> {
-        file_sha1_map
-            .iter()
-            .filter(|(k, _)| {
-                !commit.contains_key(k.to_owned()) && !staged.contains_key(k.to_owned())
-            })
-            .map(|(k, _)| k.clone())
-            .collect()
+    /// Rename a branch, the current one by default: its ref moves under the new name, the
+    /// worktrees on it follow it and its settings, the upstream included, move with it
+    pub fn branch_rename(&mut self, old: Option<&str>, new: &str) -> Result<(), GitError> {
+        self.load_branch()?;
+        let old = &old.map_or_else(|| self.current_branch(), |old| old.to_string());
+        revision::check_name(new)?;
+        let (old_file, new_file) = (self.heads_path.join(old), self.heads_path.join(new));
+        if !old_file.is_file() {
+            return Err(GitError::BranchError(format!(
+                "branch {} does not exist",
+                old
+            )));
+        }
+        if new == HEAD_FILE || new_file.exists() {
+            return Err(GitError::BranchError(format!(
+                "branch {} already exists",
+                new
+            )));
+        }
+        fs::rename(&old_file, &new_file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let (old_head, new_head) = (
+            format!("{}/{}", HEADS_DIR, old),
+            format!("{}/{}", HEADS_DIR, new),
+        );
+        let old_log = self.repo_path.join(LOGS_DIR).join(&old_head);
+        if old_log.is_file() {
+            fs::rename(&old_log, self.repo_path.join(LOGS_DIR).join(&new_head))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        for (_, head, admin) in self.worktrees()? {
+            if head == old_head {
+                let head_file = admin.unwrap_or_else(|| self.repo_path.clone());
+                fs::write(head_file.join(HEAD_FILE), &new_head)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+        }
+        if self.branch == old_head {
+            self.branch = new_head;
+        }
+        let mut config = self.config()?;
+        remote::rename_branch(&mut config, old, new)?;
+        config.save(&self.config_file)
     }
 
-    /// Collection files tracked in the current commit which have been modified but not Staged For Commit
-    fn committed_file_modified_not_stage(
-        file_sha1_map: &BTreeMap<String, String>,
-        commit: &BTreeMap<String, String>,
-        staged: &BTreeMap<String, String>,
-    ) -> Vec<String> {
-        file_sha1_map
-            .iter()
-            .filter(|(k, v)| {
-                commit.contains_key(k.to_owned())
-                    && commit.get(k.to_owned()) != Some(v.to_owned())
-                    && !staged.contains_key(k.to_owned())
-            })
-            .map(|(k, _)| k.clone().add(" (modified)"))
-            .collect::<Vec<String>>()
+    /// whether writing the given tree would overwrite a file that is not tracked in the current commit
+    fn untracked_file_in_way(&self, blobs: &BTreeMap<String, Entry>) -> Result<bool, GitError> {
+        let filter = &mut self.eol_filter()?;
+        Ok(blobs.iter().any(|(path, blob)| {
+            !self.commit.blobs.contains_key(path)
+                && entry::exists(&self.cwd.join(path))
+                && self.hash_file(filter, path).ok().as_ref() != Some(blob)
+        }))
     }
-    /// Staged for addition, but with different contents than in the working directory
-    fn staged_for_addition_but_with_different_contents(
-        file_sha1_map: &BTreeMap<String, String>,
-        staged: &BTreeMap<String, String>,
-    ) -> Vec<String> {
-        file_sha1_map
+
+    /// make the working directory match the given tree: files that differ from the current
+    /// commit are written by `checkout.workers` workers, files only tracked in the current
+    /// commit are deleted
+    fn materialize_tree(&self, blobs: &BTreeMap<String, Entry>) -> Result<(), GitError> {
+        let workers = self
+            .config()?
+            .get_usize("checkout.workers")?
+            .unwrap_or_else(materialize::default_workers);
+        let files: Vec<(String, Entry)> = blobs
             .iter()
-            .filter(|(k, v)| {
-                staged.contains_key(k.to_owned()) && staged.get(k.to_owned()) != Some(v)
-            })
-            .map(|(k, _)| k.clone().add(" (modified)"))
-            .collect::<Vec<String>>()
+            .filter(|(path, blob)| self.commit.blobs.get(*path) != Some(blob))
+            .map(|(path, blob)| (path.clone(), blob.clone()))
+            .collect();
+        self.write_files(&files, workers)?;
+        for path in self.commit.blobs.keys() {
+            let file = self.cwd.join(path);
+            if !blobs.contains_key(path) && entry::exists(&file) {
+                fs::remove_file(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+        }
+        Ok(())
     }
 
-    /// Staged for addition, but deleted in the working directory.
-    fn staged_for_addition_but_deleted(
-        file_sha1_map: &BTreeMap<String, String>,
-        staged: &BTreeMap<String, String>,
-    ) -> Vec<String> {
-        staged
-            .iter()
-            .filter(|(k, _)| !file_sha1_map.contains_key(k.to_owned()))
-            .map(|(k, _)| k.clone().add(" (deleted)"))
-            .collect::<Vec<String>>()
+    /// write tree files, file path --> blob sha1 pairs, with `workers` workers and the line
+    /// endings the working directory uses
+    fn write_files(&self, files: &[(String, Entry)], workers: usize) -> Result<(), GitError> {
+        let filter = &mut self.eol_filter()?;
+        let mut conversions = HashMap::new();
+        for (path, entry) in files.iter() {
+            let conversion = match entry.mode {
+                FileMode::Symlink => Conversion::NONE,
+                _ => filter.conversion(path)?,
+            };
+            conversions.insert(path.as_str(), conversion);
+        }
+        materialize::write_files(&self.cwd, files, workers, |path, blob| {
+            let content = self.read_blob(blob)?;
+            Ok(conversions[path].to_worktree(&content).into_owned())
+        })
     }
 
-    /// Not staged for removal, but tracked in the current commit and deleted from the working directory.
-    fn not_staged_for_removal_but_deleted(
-        file_sha1_map: &BTreeMap<String, String>,
-        commit: &BTreeMap<String, String>,
-        deleted: &BTreeMap<String, String>,
-    ) -> Vec<String> {
-        commit
-            .iter()
-            .filter(|(k, _)| {
-                !file_sha1_map.contains_key(k.to_owned()) && !deleted.contains_key(k.to_owned())
-            })
-            .map(|(k, _)| k.clone().add(" (deleted)"))
-            .collect::<Vec<String>>()
+    /// sha1 of the commit itself and every commit reachable through any parent
+    pub fn ancestors(&self, sha1: &str) -> Result<HashSet<String>, GitError> {
+        let mut ancestors = HashSet::new();
+        let mut pending = vec![sha1.to_string()];
+        while let Some(sha1) = pending.pop() {
+            if sha1.is_empty() || ancestors.contains(&sha1) {
+                continue;
+            }
+            let commit = self.read_commit(&sha1)?;
+            pending.extend(commit.parents().into_iter().map(|p| p.to_string()));
+            ancestors.insert(sha1);
+        }
+        Ok(ancestors)
     }
 
-    /// Displays what files have been modified by not Staged For Commit
-    ///  A file in the working directory is “modified but not staged” if it is
-    /// Tracked in the current commit, changed in the working directory, but not staged; or
-    /// Staged for addition, but with different contents than in the working directory; or
-    /// Staged for addition, but deleted in the working directory; or
-    /// Not staged for removal, but tracked in the current commit and deleted from the working directory.
-    fn modified_not_staged(&self) -> Result<String, GitError> {
-        let ignore_set = HashSet::from([
-            self.repo_path.clone(),
-            self.cwd.join("target"),
-            self.cwd.join(".git"),
-            self.cwd.join(".idea"),
-            self.cwd.join(".DS_Store"),
-            self.cwd.join("doc/.DS_Store"),
-        ]); // Initialize an empty HashSet
-        let file_sha1_map: BTreeMap<String, String> =
-            utils::generate_file_sha1_map(&self.cwd, &ignore_set)?;
-
-        let tracked_file = Self::committed_file_modified_not_stage(
-            &file_sha1_map,
-            &self.commit.blobs,
-            &self.staging_area.staged,
-        );
+    /// whether `ancestor` can be reached from `descendant` following parents, every commit
+    /// is its own ancestor; the walk stops as soon as it is found
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool, GitError> {
+        let mut seen = HashSet::new();
+        let mut pending = vec![descendant.to_string()];
+        while let Some(sha1) = pending.pop() {
+            if sha1 == ancestor {
+                return Ok(true);
+            }
+            if sha1.is_empty() || !seen.insert(sha1.clone()) {
+                continue;
+            }
+            let commit = self.read_commit(&sha1)?;
+            pending.extend(commit.parents().into_iter().map(|p| p.to_string()));
+        }
+        Ok(false)
+    }
 
-        let staged_file = Self::staged_for_addition_but_with_different_contents(
-            &file_sha1_map,
-            &self.staging_area.staged,
-        );
+    /// split point of two commits: the common ancestor closest to `ours`
+    pub fn merge_base(&self, ours: &str, theirs: &str) -> Result<Option<String>, GitError> {
+        let their_ancestors = self.ancestors(theirs)?;
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::from([ours.to_string()]);
+        while let Some(sha1) = queue.pop_front() {
+            if sha1.is_empty() || !visited.insert(sha1.clone()) {
+                continue;
+            }
+            if their_ancestors.contains(&sha1) {
+                return Ok(Some(sha1));
+            }
+            let commit = self.read_commit(&sha1)?;
+            queue.extend(commit.parents().into_iter().map(|p| p.to_string()));
+        }
+        Ok(None)
+    }
 
-        let staged_deleted_file =
-            Self::staged_for_addition_but_deleted(&file_sha1_map, &self.staging_area.staged);
+    /// Merge the given branch into the current branch.
+    /// The merge is refused when a file it writes is untracked in the working directory.
+    /// Files changed since the split point on one branch only take that branch's version,
+    /// files changed on both are resolved by the strategy options, otherwise conflict markers
+    /// with both versions are written and committed.
+    /// Returns a message for the user, empty after a merge commit was made.
+    pub fn merge(&mut self, name: &str, options: &MergeOptions) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        if !self.staging_area.is_empty() {
+            return Err(GitError::MergeError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let branch_file = self
+            .remote_branch_file(name)
+            .unwrap_or_else(|| self.heads_path.join(name));
+        if !branch_file.is_file() {
+            return Err(GitError::MergeError(
+                "A branch with that name does not exist.".to_string(),
+            ));
+        }
+        if self.current_branch() == name {
+            return Err(GitError::MergeError(
+                "Cannot merge a branch with itself.".to_string(),
+            ));
+        }
+        let their_sha1 = fs::read_to_string(&branch_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let split = self
+            .merge_base(&self.commit_sha1, &their_sha1)?
+            .unwrap_or_default();
+        if split == their_sha1 {
+            return Ok("Given branch is an ancestor of the current branch.".to_string());
+        }
+        let theirs = self.read_commit(&their_sha1)?;
+        if split == self.commit_sha1 && options.strategy == Strategy::Resolve {
+            if self.untracked_file_in_way(&theirs.blobs)? {
+                return Err(GitError::MergeError(UNTRACKED_IN_WAY.to_string()));
+            }
+            self.materialize_tree(&theirs.blobs)?;
+            self.commit = theirs;
+            self.commit_sha1 = their_sha1;
+            self.persist_basic_info()?;
+            return Ok("Current branch fast-forwarded.".to_string());
+        }
 
-        let not_staged_deleted_file = Self::not_staged_for_removal_but_deleted(
-            &file_sha1_map,
+        let base = if split.is_empty() {
+            Commit::new()
+        } else {
+            self.read_commit(&split)?
+        };
+        let merged = merge::merge_trees(
+            &base.blobs,
             &self.commit.blobs,
-            &self.staging_area.deleted,
-        );
+            &theirs.blobs,
+            options,
+            |blob| self.read_blob(blob),
+        )?;
+        if self.merge_touches_untracked(&merged)? {
+            return Err(GitError::MergeError(UNTRACKED_IN_WAY.to_string()));
+        }
+        let mut state = self.start_merge()?;
 
-        let mut msg: Vec<String> = vec![];
-        msg.push("=== Modifications Not Staged For Commit ===".to_string());
-        msg.extend(tracked_file);
-        msg.extend(staged_file);
-        msg.extend(staged_deleted_file);
-        msg.extend(not_staged_deleted_file);
-        Ok(msg.join("\n"))
+        let mut blobs = merged.blobs;
+        self.materialize_tree(&blobs)?;
+        blobs.extend(self.write_conflicts(&merged.conflicts)?);
+        self.commit = Commit {
+            meta: CommitMeta {
+                message: format!("Merged {} into {}.", name, self.current_branch()),
+                date_time: Utc::now().timestamp(),
+                author: self.identity()?,
+                committer: self.identity()?,
+            },
+            blobs,
+            parent: self.commit_sha1.clone(),
+            merge_parents: vec![their_sha1],
+            signature: String::new(),
+        };
+        self.sign_commit(false)?;
+        self.commit_sha1 = utils::sha1(&self.commit)?;
+        self.persist_basic_info()?;
+        if merged.conflicts.is_empty() {
+            fs::remove_file(&self.merge_state_file)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            Ok(String::new())
+        } else {
+            state.merge_head = self.commit_sha1.clone();
+            Self::persist(&state, &self.merge_state_file)?;
+            Ok("Encountered a merge conflict.".to_string())
+        }
     }
 
-    /// Displays what files have been staged for addition
-    fn staged_status(&self) -> Result<String, GitError> {
-        let mut msg: Vec<String> = vec![];
-        msg.push("=== Staged Files ===".to_string());
-        for (k, _) in self.staging_area.staged.iter() {
-            msg.push(k.clone());
+    /// Write the conflicted files of a merge with both versions between conflict markers, the
+    /// contents written are stored as blobs. Returns path --> blob of each file.
+    fn write_conflicts(
+        &self,
+        conflicts: &BTreeMap<String, (Option<Entry>, Option<Entry>)>,
+    ) -> Result<BTreeMap<String, Entry>, GitError> {
+        let filter = &mut self.eol_filter()?;
+        let mut blobs = BTreeMap::new();
+        for (path, (ours, theirs)) in conflicts.iter() {
+            let read = |entry: &Option<Entry>| match entry {
+                Some(entry) => self.read_blob(&entry.sha1),
+                None => Ok(vec![]),
+            };
+            let (our_content, their_content) = (read(ours)?, read(theirs)?);
+            let content = if diff::is_binary(&our_content) || diff::is_binary(&their_content) {
+                // binary files cannot hold conflict markers, ours is kept
+                our_content
+            } else {
+                utils::conflict_content(
+                    &String::from_utf8_lossy(&our_content),
+                    &String::from_utf8_lossy(&their_content),
+                )
+                .into_bytes()
+            };
+            let worktree = filter.conversion(path)?.to_worktree(&content).into_owned();
+            entry::write_content(&self.cwd.join(path), &worktree, FileMode::Regular)?;
+            let hash = utils::crypto_bytes(&content);
+            self.store_blob(&content, &hash)?;
+            blobs.insert(path.clone(), Entry::new(hash));
         }
-        Ok(msg.join("\n"))
+        Ok(blobs)
     }
-    /// Displays what files have been staged for removal.
-    fn removal_status(&self) -> Result<String, GitError> {
-        let mut msg: Vec<String> = vec![];
-        msg.push("=== Removed Files ===".to_string());
-        for (k, _) in self.staging_area.deleted.iter() {
-            msg.push(k.clone());
+
+    /// Persist the state before a merge, see `MergeState`, the files of the working directory
+    /// that differ from the current commit are backed up as blobs
+    fn start_merge(&mut self) -> Result<MergeState, GitError> {
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let mut backups = BTreeMap::new();
+        for (path, entry) in self.commit.blobs.iter() {
+            if file_sha1_map.get(path) == Some(entry) {
+                continue;
+            }
+            let file = self.cwd.join(path);
+            let backup = match entry::exists(&file) {
+                true => {
+                    let (content, mode) = entry::read_content(&file)?;
+                    Some(Entry::with_mode(self.store_blob_content(&content)?, mode))
+                }
+                false => None,
+            };
+            backups.insert(path.clone(), backup);
         }
-        Ok(msg.join("\n"))
+        let state = MergeState {
+            branch: self.current_branch(),
+            orig_head: self.commit_sha1.clone(),
+            merge_head: String::new(),
+            backups,
+        };
+        Self::persist(&state, &self.merge_state_file)?;
+        Ok(state)
     }
 
-    /// Displays what branches currently exist, and marks the current branch with a *.
-    fn branch_status(&self) -> Result<String, GitError> {
-        let mut msg: Vec<String> = vec![];
-
-        msg.push("=== Branches ===".to_string());
-
-        let current_branch_path = self.repo_path.join(&self.branch);
-        let current_branch_name = current_branch_path
-            .strip_prefix(&self.heads_path)
-            .map_err(|_| GitError::BranchError("invalid branch name".to_string()))?;
-        msg.push(format!("*{}", current_branch_name.display()));
-        for entry in
-            fs::read_dir(&self.heads_path).map_err(|e| GitError::BranchError(format!("{:?}", e)))?
-        {
-            let path = entry
-                .map_err(|_| GitError::BranchError("invalid branch name".to_lowercase()))?
-                .path();
-            let branch_name = path
-                .strip_prefix(&self.heads_path)
-                .map_err(|_| GitError::BranchError("invalid branch name".to_string()))?;
-
-            info!("{:?}", branch_name.display());
-            if current_branch_name != branch_name {
-                msg.push(branch_name.display().to_string());
+    /// Undo the last merge when it stopped on conflicts and its commit is still the head of
+    /// the branch: the branch, the index and the working directory are restored as they were
+    /// before the merge, changes made since to files the merge did not touch are kept
+    pub fn merge_abort(&mut self) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        if !self.merge_state_file.is_file() {
+            return Err(GitError::MergeError(
+                "There is no merge to abort.".to_string(),
+            ));
+        }
+        let state = self.read_merge_state()?;
+        if state.branch != self.current_branch() || state.merge_head != self.commit_sha1 {
+            return Err(GitError::MergeError(format!(
+                "{} has moved since the merge, there is no merge to abort.",
+                state.branch
+            )));
+        }
+        let orig = self.read_commit(&state.orig_head)?;
+        self.materialize_tree(&orig.blobs)?;
+        for (path, backup) in state.backups.iter() {
+            let file = self.cwd.join(path);
+            match backup {
+                Some(backup) => {
+                    entry::write_content(&file, &self.read_blob(&backup.sha1)?, backup.mode)?
+                }
+                None if entry::exists(&file) => {
+                    fs::remove_file(&file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                }
+                None => {}
             }
         }
-        Ok(msg.join("\n"))
+        self.commit = orig;
+        self.commit_sha1 = state.orig_head;
+        self.staging_area = StagingArea::new();
+        self.persist_basic_info()?;
+        fs::remove_file(&self.merge_state_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
     }
 
-    /// Displays what branches currently exist, and marks the current branch with a *.
-    /// Also displays what files have been staged for addition or removal. An example of the exact
-    /// format it should follow is as follows.
-    pub fn status(&mut self) -> Result<String, GitError> {
-        info!("status >> ");
-        assert!(self.load_basic_info().is_ok());
-        let mut msg: Vec<String> = vec![];
-        msg.push(self.branch_status()?);
-        msg.push(self.staged_status()?);
-        msg.push(self.removal_status()?);
-        msg.push(self.modified_not_staged()?);
-        msg.push(self.untrack_status()?);
-        info!("status << ");
-        Ok(msg.join("\n\n"))
+    fn read_merge_state(&self) -> Result<MergeState, GitError> {
+        let content = fs::read_to_string(&self.merge_state_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        serde_json::from_str(&content).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
     }
 
-    pub fn log(&mut self) -> Result<String, GitError> {
-        info!("log >> ");
+    /// whether a merge into the current branch writes or removes an untracked file
+    fn merge_touches_untracked(&mut self, merged: &merge::TreeMerge) -> Result<bool, GitError> {
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let untracked = Self::untracked_file(
+            &file_sha1_map,
+            &self.commit.blobs,
+            &self.staging_area.staged,
+        );
+        let mut touched = merged
+            .blobs
+            .iter()
+            .filter(|(path, blob)| self.commit.blobs.get(*path) != Some(blob))
+            .map(|(path, _)| path)
+            .chain(merged.conflicts.keys())
+            .chain(
+                self.commit
+                    .blobs
+                    .keys()
+                    .filter(|path| !merged.blobs.contains_key(*path)),
+            );
+        Ok(touched.any(|path| untracked.contains(path)))
+    }
+
+    /// Merge several branches into the current one. When they all merge without conflicts,
+    /// one merge commit with every branch not merged yet as a parent is made, an octopus
+    /// merge. Otherwise, or with a single branch left to merge, each is merged by `merge` in
+    /// turn, stopping at the first conflict. Returns the messages for the user.
+    pub fn merge_branches(
+        &mut self,
+        names: &[String],
+        options: &MergeOptions,
+    ) -> Result<String, GitError> {
+        if let [name] = names {
+            return self.merge(name, options);
+        }
         self.load_basic_info()?;
-        let mut msg: Vec<String> = vec![];
-        let mut commit = self.commit.clone();
-        while commit.parent != "" {
-            msg.push(format!("{}\n\n", commit));
-            commit = Self::unpersist_commit(&self.commits_path.join(&commit.parent))?;
+        if !self.staging_area.is_empty() {
+            return Err(GitError::MergeError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let merged = self.ancestors(&self.commit_sha1)?;
+        let mut heads: Vec<(&str, String)> = vec![];
+        for name in names {
+            let branch_file = self
+                .remote_branch_file(name)
+                .unwrap_or_else(|| self.heads_path.join(name));
+            if !branch_file.is_file() {
+                return Err(GitError::MergeError(format!(
+                    "A branch named {} does not exist.",
+                    name
+                )));
+            }
+            if self.current_branch() == *name {
+                return Err(GitError::MergeError(
+                    "Cannot merge a branch with itself.".to_string(),
+                ));
+            }
+            let sha1 = fs::read_to_string(&branch_file)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            if !merged.contains(&sha1) && heads.iter().all(|(_, head)| *head != sha1) {
+                heads.push((name, sha1));
+            }
+        }
+        if heads.len() < 2 {
+            return match heads.first() {
+                Some((name, _)) => self.merge(name, options),
+                None => Ok("Given branches are ancestors of the current branch.".to_string()),
+            };
         }
-        msg.push(format!("{}\n\n", commit));
-        info!("log << ");
-        Ok(msg.join("\n"))
-    }
-    /// add file under path into staging area
-    /// 1. check if added file has been modified
-    fn add_file(&mut self, path: &PathBuf) -> Result<(), GitError> {
-        if path.exists() {
-            let hash = utils::crypto_file(path)?;
-            let relative_path = path.strip_prefix(&self.cwd).map_err(|_| {
-                GitError::StagedAddError(format!("file {} is outside repository", path.display()))
-            })?;
-            // TODO: replace only when file is modified
-            // move file to staging area
-            utils::copy_to(&path, &self.blobs_path.join(&hash))?;
-            self.staging_area
-                .add(relative_path.display().to_string(), hash);
 
-            Ok(())
-        } else {
-            Err(GitError::FileNotExistError(path.display().to_string()))
+        let mut octopus = merge::TreeMerge {
+            blobs: self.commit.blobs.clone(),
+            ..Default::default()
+        };
+        for (_, sha1) in heads.iter() {
+            let base = match self.merge_base(&self.commit_sha1, sha1)? {
+                Some(split) => self.read_commit(&split)?,
+                None => Commit::new(),
+            };
+            let theirs = self.read_commit(sha1)?;
+            octopus = merge::merge_trees(
+                &base.blobs,
+                &octopus.blobs,
+                &theirs.blobs,
+                options,
+                |blob| self.read_blob(blob),
+            )?;
+            if !octopus.conflicts.is_empty() {
+                break;
+            }
+        }
+        if !octopus.conflicts.is_empty() {
+            let mut messages = vec![];
+            for (name, _) in heads {
+                let message = self.merge(name, options)?;
+                let conflict = message == "Encountered a merge conflict.";
+                messages.push(message);
+                if conflict {
+                    break;
+                }
+            }
+            messages.retain(|message| !message.is_empty());
+            return Ok(messages.join("\n"));
         }
+        if self.merge_touches_untracked(&octopus)? {
+            return Err(GitError::MergeError(UNTRACKED_IN_WAY.to_string()));
+        }
+        self.materialize_tree(&octopus.blobs)?;
+        let names: Vec<&str> = heads.iter().map(|(name, _)| *name).collect();
+        let (last, others) = names.split_last().unwrap_or((&"", &[]));
+        self.commit = Commit {
+            meta: CommitMeta {
+                message: format!(
+                    "Merged {} and {} into {}.",
+                    others.join(", "),
+                    last,
+                    self.current_branch()
+                ),
+                date_time: Utc::now().timestamp(),
+                author: self.identity()?,
+                committer: self.identity()?,
+            },
+            blobs: octopus.blobs,
+            parent: self.commit_sha1.clone(),
+            merge_parents: heads.into_iter().map(|(_, sha1)| sha1).collect(),
+            signature: String::new(),
+        };
+        self.sign_commit(false)?;
+        self.commit_sha1 = utils::sha1(&self.commit)?;
+        self.persist_basic_info()?;
+        Ok(String::new())
     }
 
-    /// remove file
-    /// 1. Unstage the file if it is currently staged for addition.
-    /// 2. If the file is tracked in the current commit, stage it for removal and remove the file from the working directory if the user has not already done so (do not remove it unless it is tracked in the current commit).
-    fn remove_file(&mut self, path: &PathBuf) -> Result<(), GitError> {
-        let relative_path = path.strip_prefix(&self.cwd).map_err(|_| {
-            GitError::StagedRemoveError(format!("file {} is outside repository", path.display()))
-        })?;
-        let path_name = relative_path.display().to_string();
-        if self.staging_area.staged.contains_key(&path_name) {
-            self.staging_area.staged.remove(&path_name);
-            Ok(())
-        } else if self.commit.blobs.contains_key(&path_name) {
-            self.staging_area.deleted.insert(path_name, "".to_string());
-            Ok(())
+    /// Replay the change a commit made against its first parent on top of the `onto` tree.
+    /// Files changed differently by the commit and in `onto` are reported as conflicts.
+    fn pick(
+        &self,
+        onto: &BTreeMap<String, Entry>,
+        commit: &Commit,
+    ) -> Result<merge::TreeMerge, GitError> {
+        let base = if commit.parent.is_empty() {
+            Commit::new()
         } else {
-            Err(GitError::StagedRemoveNoReasonError)
-        }
+            self.read_commit(&commit.parent)?
+        };
+        merge::merge_trees(
+            &base.blobs,
+            onto,
+            &commit.blobs,
+            &MergeOptions::default(),
+            |blob| self.read_blob(blob),
+        )
     }
-    /// persistence staged area
-    /// 1. serialize StageArea into json string
-    /// 2. write/update serialized string into staging area file
-    fn persist<T: Serialize>(value: &T, path: &PathBuf) -> Result<(), GitError> {
-        let mut file =
-            fs::File::create(&path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-        let content =
-            serde_json::to_string(value).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
-        file.write_all(content.as_bytes())
-            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-        Ok(())
+
+    /// error message for a commit that could not be replayed
+    fn pick_conflict(sha1: &str, commit: &Commit, picked: &merge::TreeMerge) -> String {
+        format!(
+            "could not apply {} {}: conflict in {}",
+            &sha1[..sha1.len().min(7)],
+            commit.message().lines().next().unwrap_or(""),
+            picked
+                .conflicts
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
     }
 
-    fn unpersist_commit(path: &PathBuf) -> Result<Commit, GitError> {
-        info!("unpersist_commit {}", path.display());
-        if !path.exists() || !path.is_file() {
-            info!("{}", path.display());
-            Err(GitError::FileNotExistError(path.display().to_string()))
-        } else {
-            let mut file =
-                fs::File::open(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    /// Apply the change introduced by the given commit on top of the current branch as a new
+    /// commit with the same message and date, see `cherry_pick_revs`
+    pub fn cherry_pick(&mut self, rev: &str) -> Result<(), GitError> {
+        self.cherry_pick_revs(&[rev.to_string()])
+    }
 
-            let mut content = String::new();
-            file.read_to_string(&mut content)
-                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-            info!("content {}", content);
-            let commit =
-                serde_json::from_str(content.as_str()).expect("JSON was not well-formatted");
-            Ok(commit)
+    /// Cherry-pick commits one after the other, given as commits or `A..B` ranges whose
+    /// commits are picked oldest first. A commit whose change conflicts stops the
+    /// cherry-pick: the conflicted files hold conflict markers and the rest of the change is
+    /// staged, to be resolved and added for `cherry_pick_continue`, or undone by
+    /// `cherry_pick_abort`. A commit whose change is already in the branch stops it too.
+    pub fn cherry_pick_revs(&mut self, revs: &[String]) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        if self.cherry_pick_state_file.exists() {
+            return Err(GitError::CherryPickError(
+                "A cherry-pick is in progress; run cherry-pick --continue or cherry-pick --abort."
+                    .to_string(),
+            ));
         }
-    }
-    /// unpersistence staged area
-    fn unpersist_staging_area(path: &PathBuf) -> Result<StagingArea, GitError> {
-        if !path.exists() || !path.is_file() {
-            Err(GitError::FileNotExistError(path.display().to_string()))
-        } else {
-            let mut file =
-                fs::File::open(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if !self.staging_area.is_empty() {
+            return Err(GitError::CherryPickError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let mut todo = vec![];
+        for rev in revs {
+            if rev.contains("..") {
+                let mut range = self.commit_range(rev)?;
+                range.reverse();
+                todo.extend(range);
+            } else {
+                let sha1 = self.resolve_commit(rev)?;
+                let commit = self.read_commit(&sha1)?;
+                todo.push((sha1, commit));
+            }
+        }
+        if todo.is_empty() {
+            return Err(GitError::CherryPickError(
+                "empty commit set passed".to_string(),
+            ));
+        }
+        if let Some((sha1, _)) = todo.iter().find(|(_, c)| c.parents().len() > 1) {
+            return Err(GitError::CherryPickError(format!(
+                "commit {} is a merge",
+                sha1
+            )));
+        }
+        let state = CherryPickState {
+            branch: self.current_branch(),
+            orig_head: self.commit_sha1.clone(),
+            todo: todo.into_iter().map(|(sha1, _)| sha1).collect(),
+            conflicts: vec![],
+        };
+        self.run_cherry_picks(state)
+    }
 
-            let mut content = String::new();
-            file.read_to_string(&mut content)
+    /// Pick the commits of the todo list in order, see `cherry_pick_revs`. The state is
+    /// persisted when a commit stops the cherry-pick with commits left to pick.
+    fn run_cherry_picks(&mut self, mut state: CherryPickState) -> Result<(), GitError> {
+        let hint = "run cherry-pick --continue, or run cherry-pick --abort";
+        while let Some(sha1) = state.todo.first().cloned() {
+            let commit = self.read_commit(&sha1)?;
+            let picked = self.pick(&self.commit.blobs, &commit)?;
+            if self.merge_touches_untracked(&picked)? {
+                self.save_cherry_pick(&state)?;
+                return Err(GitError::CherryPickError(UNTRACKED_IN_WAY.to_string()));
+            }
+            if !picked.conflicts.is_empty() {
+                state.conflicts = picked.conflicts.keys().cloned().collect();
+                self.save_cherry_pick(&state)?;
+                self.materialize_tree(&picked.blobs)?;
+                self.stage_conflicted_pick(&picked)?;
+                return Err(GitError::CherryPickError(format!(
+                    "{}\nfix the conflicts, add the files and {}",
+                    Self::pick_conflict(&sha1, &commit, &picked),
+                    hint
+                )));
+            }
+            state.todo.remove(0);
+            if picked.blobs == self.commit.blobs {
+                self.save_cherry_pick(&state)?;
+                let mut msg = format!("the change of {} is already in the current branch", sha1);
+                if !state.todo.is_empty() {
+                    msg = format!("{}\nto pick the commits left {}", msg, hint);
+                }
+                return Err(GitError::CherryPickError(msg));
+            }
+            self.materialize_tree(&picked.blobs)?;
+            self.commit_pick(commit, picked.blobs)?;
+            self.save_cherry_pick(&state)?;
+        }
+        Ok(())
+    }
+
+    /// commit a picked change on top of the current commit with the message and the author
+    /// of the commit it comes from
+    fn commit_pick(
+        &mut self,
+        commit: Commit,
+        blobs: BTreeMap<String, Entry>,
+    ) -> Result<(), GitError> {
+        self.commit = Commit {
+            meta: CommitMeta {
+                committer: self.identity()?,
+                ..commit.meta
+            },
+            blobs,
+            parent: self.commit_sha1.clone(),
+            merge_parents: vec![],
+            signature: String::new(),
+        };
+        self.sign_commit(false)?;
+        self.commit_sha1 = utils::sha1(&self.commit)?;
+        self.persist_basic_info()
+    }
+
+    /// persist the cherry-pick state while commits are left to pick, remove it otherwise
+    fn save_cherry_pick(&self, state: &CherryPickState) -> Result<(), GitError> {
+        if !state.todo.is_empty() {
+            return Self::persist(state, &self.cherry_pick_state_file);
+        }
+        if self.cherry_pick_state_file.exists() {
+            fs::remove_file(&self.cherry_pick_state_file)
                 .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-            if content.is_empty() {
-                Ok(StagingArea::new())
+        }
+        Ok(())
+    }
+
+    /// the cherry-pick in progress
+    fn read_cherry_pick_state(&self) -> Result<CherryPickState, GitError> {
+        if !self.cherry_pick_state_file.is_file() {
+            return Err(GitError::CherryPickError(
+                "No cherry-pick in progress.".to_string(),
+            ));
+        }
+        let content = fs::read_to_string(&self.cherry_pick_state_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        serde_json::from_str(&content).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// Resume a stopped cherry-pick: the commit it stopped on is made from the current commit
+    /// and the staged changes, then the commits left are picked
+    pub fn cherry_pick_continue(&mut self) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        let mut state = self.read_cherry_pick_state()?;
+        if self.current_branch() != state.branch {
+            return Err(GitError::CherryPickError(format!(
+                "cherry-picking onto {}, checkout it first",
+                state.branch
+            )));
+        }
+        if !state.conflicts.is_empty() {
+            let resolved = self
+                .resolved_tree(&state.conflicts)
+                .map_err(GitError::CherryPickError)?;
+            let commit = self.read_commit(&state.todo.remove(0))?;
+            state.conflicts.clear();
+            self.staging_area = StagingArea::new();
+            if resolved == self.commit.blobs {
+                self.persist_basic_info()?;
             } else {
-                let staging_area =
-                    serde_json::from_str(content.as_str()).expect("JSON was not well-formatted");
-                Ok(staging_area)
+                self.commit_pick(commit, resolved)?;
             }
+            self.save_cherry_pick(&state)?;
         }
+        if !self.staging_area.is_empty() {
+            return Err(GitError::CherryPickError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        self.run_cherry_picks(state)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Read;
+    /// Stop the cherry-pick in progress and put the branch, the index and the working
+    /// directory back as they were before it
+    pub fn cherry_pick_abort(&mut self) -> Result<(), GitError> {
+        let state = self.read_cherry_pick_state()?;
+        self.load_basic_info()?;
+        self.restore_head(&state.branch, &state.orig_head, &state.conflicts)?;
+        fs::remove_file(&self.cherry_pick_state_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
 
-    fn clean_repo(repo_dir: &str) {
-        let path = &env::current_dir().unwrap().join(repo_dir);
-        if path.exists() {
-            assert!(fs::remove_dir_all(path).is_ok());
+    /// Rebase the current branch onto the given branch: the commits since the split point are
+    /// cherry-picked one by one on top of the branch head, keeping their messages and dates,
+    /// and the current branch is moved to the last new commit. Merge commits and commits
+    /// whose change is already upstream are dropped. A commit that conflicts stops the
+    /// rebase, see `stop_rebase`.
+    pub fn rebase(&mut self, name: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        self.check_no_rebase()?;
+        if !self.staging_area.is_empty() {
+            return Err(GitError::RebaseError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let branch_file = self.heads_path.join(name);
+        if !branch_file.is_file() {
+            return Err(GitError::RebaseError(
+                "A branch with that name does not exist.".to_string(),
+            ));
+        }
+        if self.current_branch() == name {
+            return Err(GitError::RebaseError(
+                "Cannot rebase a branch onto itself.".to_string(),
+            ));
+        }
+        let upstream = fs::read_to_string(&branch_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if self.ancestors(&self.commit_sha1)?.contains(&upstream) {
+            return Ok("Current branch is up to date.".to_string());
         }
+
+        // commits of the current branch since the split point, oldest first
+        let upstream_ancestors = self.ancestors(&upstream)?;
+        let mut todo = vec![];
+        let mut sha1 = self.commit_sha1.clone();
+        while !sha1.is_empty() && !upstream_ancestors.contains(&sha1) {
+            let commit = self.read_commit(&sha1)?;
+            let parent = commit.parent.clone();
+            todo.push((sha1, commit));
+            sha1 = parent;
+        }
+        todo.reverse();
+        self.replay(todo, upstream, name)
     }
-    fn init() {
-        let _ = env_logger::builder().is_test(true).try_init();
+
+    /// Rebase the commits of `upstream..HEAD` onto `newbase` like `rebase`, whatever
+    /// `newbase` is, and move the current branch to the last new commit. The range selects
+    /// the commits to transplant, merge commits are dropped and the others are replayed
+    /// oldest first. A commit that conflicts stops the rebase, see `stop_rebase`.
+    pub fn rebase_onto(&mut self, newbase: &str, upstream: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        self.check_no_rebase()?;
+        if !self.staging_area.is_empty() {
+            return Err(GitError::RebaseError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let onto_sha1 = self.resolve_commit(newbase)?;
+        let upstream = self.resolve_commit(upstream)?;
+        let mut todo = self.commit_range(&format!("{}..{}", upstream, self.commit_sha1))?;
+        todo.reverse();
+        self.replay(todo, onto_sha1, newbase)
+    }
+
+    /// Cherry-pick the commits, oldest first, on top of `onto_sha1` and move the current
+    /// branch to the last new commit, see `rebase`. `name` is how `onto_sha1` was given.
+    /// Until a commit conflicts nothing is persisted, then the commits left become the todo
+    /// list of a stopped rebase.
+    fn replay(
+        &mut self,
+        todo: Vec<(String, Commit)>,
+        onto_sha1: String,
+        name: &str,
+    ) -> Result<String, GitError> {
+        let mut onto_sha1 = onto_sha1;
+        let mut onto = self.read_commit(&onto_sha1)?;
+        let mut replayed: Vec<(String, Commit)> = vec![];
+        let committer = self.identity()?;
+        let todo: Vec<(String, Commit)> = todo
+            .into_iter()
+            .filter(|(_, commit)| commit.parents().len() == 1)
+            .collect();
+        for (i, (_, commit)) in todo.iter().enumerate() {
+            let picked = self.pick(&onto.blobs, commit)?;
+            if !picked.conflicts.is_empty() {
+                for (sha1, commit) in replayed.iter() {
+                    self.persist_object(commit, &self.commit_file(sha1)?)?;
+                }
+                let steps: Vec<Step> = todo[i..]
+                    .iter()
+                    .map(|(sha1, commit)| Step {
+                        action: Action::Pick,
+                        commit: sha1.clone(),
+                        subject: commit.message().lines().next().unwrap_or("").to_string(),
+                    })
+                    .collect();
+                let mut state = RebaseState {
+                    branch: self.current_branch(),
+                    orig_head: self.commit_sha1.clone(),
+                    onto: onto_sha1,
+                    squashable: !replayed.is_empty(),
+                    conflicts: vec![],
+                };
+                let message = self.stop_rebase(&mut state, &steps, &picked)?;
+                return Err(GitError::RebaseError(message));
+            }
+            if picked.blobs == onto.blobs {
+                continue;
+            }
+            onto = Commit {
+                meta: CommitMeta {
+                    committer: committer.clone(),
+                    ..commit.meta.clone()
+                },
+                blobs: picked.blobs,
+                parent: onto_sha1,
+                merge_parents: vec![],
+                signature: String::new(),
+            };
+            onto_sha1 = utils::sha1(&onto)?;
+            replayed.push((onto_sha1.clone(), onto.clone()));
+        }
+        if self.untracked_file_in_way(&onto.blobs)? {
+            return Err(GitError::RebaseError(UNTRACKED_IN_WAY.to_string()));
+        }
+        for (sha1, commit) in replayed.iter() {
+            self.persist_object(commit, &self.commit_file(sha1)?)?;
+        }
+        self.materialize_tree(&onto.blobs)?;
+        self.commit = onto;
+        self.commit_sha1 = onto_sha1;
+        self.persist_basic_info()?;
+        Ok(format!(
+            "Successfully rebased {} onto {}.",
+            self.current_branch(),
+            name
+        ))
+    }
+
+    /// refuse to start a rebase while another one is in progress
+    fn check_no_rebase(&self) -> Result<(), GitError> {
+        if self.rebase_state_file.exists() {
+            return Err(GitError::RebaseError(
+                "A rebase is in progress; run rebase --continue or rebase --abort.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// open the file in the editor: `core.editor`, $VISUAL, $EDITOR or vi, in that order
+    fn edit_file(&self, path: &Path) -> Result<(), GitError> {
+        let editor = match self.config()?.get("core.editor") {
+            Some(editor) => editor.to_string(),
+            None => env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| "vi".to_string()),
+        };
+        // run through the shell so the editor may carry its own arguments
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(&editor)
+            .arg(path)
+            .status()
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if !status.success() {
+            return Err(GitError::FileOpError(format!(
+                "editor {} failed: {}",
+                editor, status
+            )));
+        }
+        Ok(())
+    }
+
+    /// let the user edit a commit message, `#` lines are dropped and an empty message is refused
+    fn edit_message(&self, message: &str) -> Result<String, GitError> {
+        fs::write(
+            &self.rebase_msg_file,
+            format!(
+                "{}\n\n# Please enter the commit message. Lines starting with '#' are ignored,\n\
+                 # and an empty message aborts the rebase step.\n",
+                message
+            ),
+        )
+        .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        self.edit_file(&self.rebase_msg_file)?;
+        let content = fs::read_to_string(&self.rebase_msg_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        fs::remove_file(&self.rebase_msg_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let message = content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string();
+        if message.is_empty() {
+            return Err(GitError::RebaseError(
+                "Aborting commit due to empty commit message.".to_string(),
+            ));
+        }
+        Ok(message)
+    }
+
+    /// Interactive rebase of the commits after `base`: they are listed oldest first in the
+    /// todo list, which is opened in the editor. The edited plan is then executed on top of
+    /// `base` by `rebase_continue`, where each line picks, rewords, squashes or drops a commit.
+    pub fn rebase_interactive(&mut self, base: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        self.check_no_rebase()?;
+        if !self.staging_area.is_empty() {
+            return Err(GitError::RebaseError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let base_sha1 = self.resolve_commit(base)?;
+        let base_ancestors = self.ancestors(&base_sha1)?;
+        let mut steps = vec![];
+        let mut sha1 = self.commit_sha1.clone();
+        while !sha1.is_empty() && !base_ancestors.contains(&sha1) {
+            let commit = self.read_commit(&sha1)?;
+            let parent = commit.parent.clone();
+            if commit.parents().len() == 1 {
+                steps.push(Step {
+                    action: Action::Pick,
+                    commit: sha1,
+                    subject: commit.message().lines().next().unwrap_or("").to_string(),
+                });
+            }
+            sha1 = parent;
+        }
+        steps.reverse();
+        if steps.is_empty() {
+            return Ok("Nothing to do.".to_string());
+        }
+
+        fs::write(&self.rebase_todo_file, rebase::render_todo(&steps, true))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let edited = self.edit_file(&self.rebase_todo_file).and_then(|_| {
+            let content = fs::read_to_string(&self.rebase_todo_file)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            rebase::parse_todo(&content)
+        });
+        let steps = match edited {
+            Ok(steps) if !steps.is_empty() => steps,
+            res => {
+                fs::remove_file(&self.rebase_todo_file)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                return res.map(|_| "Nothing to do.".to_string());
+            }
+        };
+        fs::write(&self.rebase_todo_file, rebase::render_todo(&steps, false))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let state = RebaseState {
+            branch: self.current_branch(),
+            orig_head: self.commit_sha1.clone(),
+            onto: base_sha1,
+            squashable: false,
+            conflicts: vec![],
+        };
+        Self::persist(&state, &self.rebase_state_file)?;
+        self.rebase_continue()
+    }
+
+    /// progress of the interactive rebase in progress
+    fn read_rebase_state(&self) -> Result<RebaseState, GitError> {
+        if !self.rebase_state_file.is_file() {
+            return Err(GitError::RebaseError("No rebase in progress.".to_string()));
+        }
+        let content = fs::read_to_string(&self.rebase_state_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        serde_json::from_str(&content).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// Run the remaining steps of the todo list. The state and the todo list are persisted
+    /// after every step, so a step that stops (a squash without a previous commit, an empty
+    /// message) can be fixed in the todo list and resumed. A step that conflicts stops the
+    /// rebase, see `stop_rebase`, and the files added since make its commit when resumed.
+    /// Otherwise the working directory and the branch are only updated once every step has run.
+    pub fn rebase_continue(&mut self) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let mut state = self.read_rebase_state()?;
+        if self.current_branch() != state.branch {
+            return Err(GitError::RebaseError(format!(
+                "rebasing {}, checkout it first",
+                state.branch
+            )));
+        }
+        let content = fs::read_to_string(&self.rebase_todo_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let mut steps = rebase::parse_todo(&content)?;
+        if !state.conflicts.is_empty() && !steps.is_empty() {
+            if self.commit_sha1 != state.onto {
+                return Err(GitError::RebaseError(format!(
+                    "{} has moved since the rebase stopped, run rebase --abort",
+                    state.branch
+                )));
+            }
+            let resolved = self
+                .resolved_tree(&state.conflicts)
+                .map_err(GitError::RebaseError)?;
+            if let Some(picked) = self.rebase_step(&mut state, &steps[0], Some(resolved))? {
+                return Err(GitError::RebaseError(
+                    self.stop_rebase(&mut state, &steps, &picked)?,
+                ));
+            }
+            steps.remove(0);
+            state.conflicts.clear();
+            // the branch follows the commits made while stopped
+            self.commit = self.read_commit(&state.onto)?;
+            self.commit_sha1 = state.onto.clone();
+            self.staging_area = StagingArea::new();
+            self.persist_basic_info()?;
+            Self::persist(&state, &self.rebase_state_file)?;
+            fs::write(&self.rebase_todo_file, rebase::render_todo(&steps, false))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        if !self.staging_area.is_empty() {
+            return Err(GitError::RebaseError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        while !steps.is_empty() {
+            if let Some(picked) = self.rebase_step(&mut state, &steps[0], None)? {
+                return Err(GitError::RebaseError(
+                    self.stop_rebase(&mut state, &steps, &picked)?,
+                ));
+            }
+            steps.remove(0);
+            Self::persist(&state, &self.rebase_state_file)?;
+            fs::write(&self.rebase_todo_file, rebase::render_todo(&steps, false))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+
+        let onto = self.read_commit(&state.onto)?;
+        if self.untracked_file_in_way(&onto.blobs)? {
+            return Err(GitError::RebaseError(UNTRACKED_IN_WAY.to_string()));
+        }
+        self.materialize_tree(&onto.blobs)?;
+        self.commit = onto;
+        self.commit_sha1 = state.onto;
+        self.persist_basic_info()?;
+        for file in [&self.rebase_todo_file, &self.rebase_state_file] {
+            fs::remove_file(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        Ok(format!(
+            "Successfully rebased and updated {}.",
+            state.branch
+        ))
+    }
+
+    /// Stop the rebase on its first todo step, whose pick conflicts: the branch moves to the
+    /// last commit made so far, the change of the step is staged but for the conflicted
+    /// files, which are written with conflict markers. The state and the todo list are
+    /// persisted for `rebase_continue` and `rebase_abort`. Returns the message for the user.
+    fn stop_rebase(
+        &mut self,
+        state: &mut RebaseState,
+        steps: &[Step],
+        picked: &merge::TreeMerge,
+    ) -> Result<String, GitError> {
+        let step = &steps[0];
+        let commit = self.read_commit(&self.resolve_commit(&step.commit)?)?;
+        if self.merge_touches_untracked(picked)? {
+            return Err(GitError::RebaseError(UNTRACKED_IN_WAY.to_string()));
+        }
+        state.conflicts = picked.conflicts.keys().cloned().collect();
+        Self::persist(&*state, &self.rebase_state_file)?;
+        fs::write(&self.rebase_todo_file, rebase::render_todo(steps, false))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+
+        self.materialize_tree(&picked.blobs)?;
+        self.commit = self.read_commit(&state.onto)?;
+        self.commit_sha1 = state.onto.clone();
+        self.stage_conflicted_pick(picked)?;
+        Ok(format!(
+            "{}\nfix the conflicts, add the files and run rebase --continue, or run rebase --abort",
+            Self::pick_conflict(&step.commit, &commit, picked)
+        ))
+    }
+
+    /// Leave a conflicted pick for the user to resolve on top of the current commit, the
+    /// working directory already holding its clean files: the conflicted files are written
+    /// with conflict markers and everything else the pick changes is staged
+    fn stage_conflicted_pick(&mut self, picked: &merge::TreeMerge) -> Result<(), GitError> {
+        self.write_conflicts(&picked.conflicts)?;
+        let mut staging_area = StagingArea::new();
+        for (path, entry) in picked.blobs.iter() {
+            if self.commit.blobs.get(path) != Some(entry) {
+                staging_area.add(path.clone(), entry.clone());
+            }
+        }
+        for path in self.commit.blobs.keys() {
+            if !picked.blobs.contains_key(path) && !picked.conflicts.contains_key(path) {
+                staging_area.deleted.insert(path.clone(), "".to_string());
+            }
+        }
+        self.staging_area = staging_area;
+        self.persist_basic_info()
+    }
+
+    /// Tree of a stopped pick once resolved: the current commit with the staged changes.
+    /// Returns the conflicted files that were neither added nor removed when there are some.
+    fn resolved_tree(&self, conflicts: &[String]) -> Result<BTreeMap<String, Entry>, String> {
+        let unresolved: Vec<&str> = conflicts
+            .iter()
+            .filter(|path| {
+                !self.staging_area.staged.contains_key(*path)
+                    && !self.staging_area.deleted.contains_key(*path)
+            })
+            .map(|path| path.as_str())
+            .collect();
+        if !unresolved.is_empty() {
+            return Err(format!(
+                "fix the conflicts and add the files first: {}",
+                unresolved.join(", ")
+            ));
+        }
+        Self::generate_commit_blobs(&self.commit.blobs, &self.staging_area)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Put the branch back at `orig_head` after a rebase or a cherry-pick. When it is the
+    /// current branch, the index and the tracked files, and the conflicted files of a
+    /// stopped pick, are reset to that commit too.
+    fn restore_head(
+        &mut self,
+        branch: &str,
+        orig_head: &str,
+        conflicts: &[String],
+    ) -> Result<(), GitError> {
+        if self.current_branch() != branch {
+            return self.update_ref(&format!("{}/{}", HEADS_DIR, branch), orig_head);
+        }
+        if self.commit_sha1 == orig_head && self.staging_area.is_empty() && conflicts.is_empty() {
+            return Ok(());
+        }
+        let orig = self.read_commit(orig_head)?;
+        let paths: BTreeSet<String> = self
+            .commit
+            .blobs
+            .keys()
+            .chain(orig.blobs.keys())
+            .chain(conflicts.iter())
+            .chain(self.staging_area.staged.keys())
+            .cloned()
+            .collect();
+        self.reset_paths(&paths, &orig.blobs)?;
+        self.commit = orig;
+        self.commit_sha1 = orig_head.to_string();
+        self.staging_area = StagingArea::new();
+        self.persist_basic_info()
+    }
+
+    /// Run one todo step on top of `state.onto`, a pick whose change is already there is
+    /// dropped. `resolved` is the tree of the step when its conflicts were fixed by hand.
+    /// Returns the pick when it conflicts, nothing is made then.
+    fn rebase_step(
+        &self,
+        state: &mut RebaseState,
+        step: &Step,
+        resolved: Option<BTreeMap<String, Entry>>,
+    ) -> Result<Option<merge::TreeMerge>, GitError> {
+        if step.action == Action::Drop {
+            return Ok(None);
+        }
+        let commit = self.read_commit(&self.resolve_commit(&step.commit)?)?;
+        let onto = self.read_commit(&state.onto)?;
+        let picked = match resolved {
+            Some(blobs) => merge::TreeMerge {
+                blobs,
+                ..Default::default()
+            },
+            None => self.pick(&onto.blobs, &commit)?,
+        };
+        if !picked.conflicts.is_empty() {
+            return Ok(Some(picked));
+        }
+        let (meta, parent) = match step.action {
+            Action::Squash if !state.squashable => {
+                return Err(GitError::RebaseError(format!(
+                    "cannot squash {} without a previous commit",
+                    step.commit
+                )))
+            }
+            Action::Squash => (
+                CommitMeta {
+                    message: format!("{}\n\n{}", onto.meta.message, commit.meta.message),
+                    committer: self.identity()?,
+                    ..onto.meta
+                },
+                onto.parent,
+            ),
+            _ if picked.blobs == onto.blobs => return Ok(None),
+            Action::Reword => (
+                CommitMeta {
+                    message: self.edit_message(&commit.meta.message)?,
+                    committer: self.identity()?,
+                    ..commit.meta
+                },
+                state.onto.clone(),
+            ),
+            _ => (
+                CommitMeta {
+                    committer: self.identity()?,
+                    ..commit.meta
+                },
+                state.onto.clone(),
+            ),
+        };
+        let commit = Commit {
+            meta,
+            blobs: picked.blobs,
+            parent,
+            merge_parents: vec![],
+            signature: String::new(),
+        };
+        state.onto = utils::sha1(&commit)?;
+        state.squashable = true;
+        self.persist_object(&commit, &self.commit_file(&state.onto)?)?;
+        Ok(None)
+    }
+
+    /// Stop the rebase in progress and put the branch back where it was before the rebase.
+    /// When the rebase stopped on a conflict, the index and the working directory are reset
+    /// to that commit too, otherwise they were not touched yet.
+    pub fn rebase_abort(&mut self) -> Result<(), GitError> {
+        let state = self.read_rebase_state()?;
+        self.load_basic_info()?;
+        self.restore_head(&state.branch, &state.orig_head, &state.conflicts)?;
+        for file in [&self.rebase_todo_file, &self.rebase_state_file] {
+            if file.exists() {
+                fs::remove_file(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// make the given working files match the tree: write the files it has, delete the others
+    fn reset_paths<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a String>,
+        tree: &BTreeMap<String, Entry>,
+    ) -> Result<(), GitError> {
+        let filter = &mut self.eol_filter()?;
+        let mut files = vec![];
+        for path in paths {
+            let file = self.cwd.join(path);
+            match tree.get(path) {
+                Some(blob) => {
+                    if self.hash_file(filter, path).ok().as_ref() != Some(blob) {
+                        files.push((path.clone(), blob.clone()));
+                    }
+                }
+                None => {
+                    if entry::exists(&file) {
+                        fs::remove_file(file)
+                            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                    }
+                }
+            }
+        }
+        self.write_files(&files, materialize::default_workers())
+    }
+
+    /// the stash entries, newest first
+    fn read_stash(&self) -> Result<Vec<StashEntry>, GitError> {
+        if !self.stash_file.is_file() {
+            return Ok(vec![]);
+        }
+        let content = fs::read_to_string(&self.stash_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        serde_json::from_str(&content).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// Save the staged changes and the changes of tracked files in the working directory as
+    /// a new stash entry, then reset both the working directory and the staging area to HEAD.
+    /// With `include_untracked` the untracked files that are not ignored are saved and
+    /// deleted too.
+    pub fn stash_push(
+        &mut self,
+        message: Option<&str>,
+        include_untracked: bool,
+    ) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let index = Self::generate_commit_blobs(&self.commit.blobs, &self.staging_area)?;
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let tracked: BTreeSet<String> = index
+            .keys()
+            .chain(self.commit.blobs.keys())
+            .cloned()
+            .collect();
+        let worktree: BTreeMap<String, Entry> = tracked
+            .iter()
+            .filter_map(|path| {
+                file_sha1_map
+                    .get(path)
+                    .map(|sha1| (path.clone(), sha1.clone()))
+            })
+            .collect();
+        let untracked: BTreeMap<String, Entry> = file_sha1_map
+            .into_iter()
+            .filter(|(path, _)| include_untracked && !tracked.contains(path))
+            .collect();
+        if index == self.commit.blobs && worktree == index && untracked.is_empty() {
+            return Err(GitError::StashError("No local changes to save".to_string()));
+        }
+        let filter = &mut self.eol_filter()?;
+        for (path, entry) in worktree.iter() {
+            if index.get(path) != Some(entry) && self.commit.blobs.get(path) != Some(entry) {
+                self.store_file(filter, path)?;
+            }
+        }
+        for path in untracked.keys() {
+            self.store_file(filter, path)?;
+        }
+
+        let branch = self.current_branch();
+        let message = match message {
+            Some(message) => format!("On {}: {}", branch, message),
+            None => format!(
+                "WIP on {}: {} {}",
+                branch,
+                &self.commit_sha1[..self.commit_sha1.len().min(7)],
+                self.commit.message().lines().next().unwrap_or("")
+            ),
+        };
+        let mut entries = self.read_stash()?;
+        entries.insert(
+            0,
+            StashEntry {
+                message: message.clone(),
+                base: self.commit_sha1.clone(),
+                index,
+                worktree,
+                untracked: untracked.clone(),
+                date_time: Utc::now().timestamp(),
+            },
+        );
+        self.reset_paths(tracked.iter().chain(untracked.keys()), &self.commit.blobs)?;
+        Self::persist(&entries, &self.stash_file)?;
+        self.staging_area = StagingArea::new();
+        self.persist_basic_info()?;
+        Ok(format!(
+            "Saved working directory and index state {}",
+            message
+        ))
+    }
+
+    /// list the stash entries as `stash@{n}: message`, newest first
+    pub fn stash_list(&self) -> Result<Vec<String>, GitError> {
+        Ok(self
+            .read_stash()?
+            .iter()
+            .enumerate()
+            .map(|(n, entry)| format!("{}: {}", stash::label(n), entry.message))
+            .collect())
+    }
+
+    /// the n-th stash entry
+    fn stash_entry(entries: &[StashEntry], n: usize) -> Result<&StashEntry, GitError> {
+        if entries.is_empty() {
+            return Err(GitError::StashError("No stash entries found.".to_string()));
+        }
+        entries.get(n).ok_or_else(|| {
+            GitError::StashError(format!("{} is not a valid reference", stash::label(n)))
+        })
+    }
+
+    /// Reapply a stash entry on top of the current commit. Changes made since the entry's
+    /// base commit are merged with it, nothing is changed when they conflict, when a file
+    /// the entry writes has local changes or when one of its untracked files exists.
+    fn apply_stash_entry(&mut self, entry: &StashEntry) -> Result<(), GitError> {
+        if !self.staging_area.is_empty() {
+            return Err(GitError::StashError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let base = self.read_commit(&entry.base)?;
+        let merge = |tree: &BTreeMap<String, Entry>| {
+            merge::merge_trees(
+                &base.blobs,
+                &self.commit.blobs,
+                tree,
+                &MergeOptions::default(),
+                |blob| self.read_blob(blob),
+            )
+        };
+        let (index, worktree) = (merge(&entry.index)?, merge(&entry.worktree)?);
+        let conflicts: BTreeSet<&String> = index
+            .conflicts
+            .keys()
+            .chain(worktree.conflicts.keys())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(GitError::StashError(format!(
+                "conflict in {}",
+                conflicts
+                    .into_iter()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let paths: BTreeSet<&String> = worktree
+            .blobs
+            .keys()
+            .chain(self.commit.blobs.keys())
+            .filter(|path| worktree.blobs.get(*path) != self.commit.blobs.get(*path))
+            .collect();
+        if let Some(path) = paths
+            .iter()
+            .find(|path| file_sha1_map.get(**path) != self.commit.blobs.get(**path))
+        {
+            return Err(GitError::StashError(format!(
+                "Your local changes to {} would be overwritten.",
+                path
+            )));
+        }
+        if let Some(path) = entry
+            .untracked
+            .keys()
+            .find(|path| entry::exists(&self.cwd.join(path)))
+        {
+            return Err(GitError::StashError(format!(
+                "{} already exists, no checkout",
+                path
+            )));
+        }
+        self.reset_paths(paths, &worktree.blobs)?;
+        let untracked: Vec<(String, Entry)> = entry.untracked.clone().into_iter().collect();
+        self.write_files(&untracked, materialize::default_workers())?;
+        for (path, blob) in index.blobs.iter() {
+            if self.commit.blobs.get(path) != Some(blob) {
+                self.staging_area.add(path.clone(), blob.clone());
+            }
+        }
+        for path in self.commit.blobs.keys() {
+            if !index.blobs.contains_key(path) {
+                self.staging_area
+                    .deleted
+                    .insert(path.clone(), "".to_string());
+            }
+        }
+        self.persist_basic_info()
+    }
+
+    /// reapply the n-th stash entry on top of the current commit and drop it
+    pub fn stash_pop(&mut self, n: usize) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let mut entries = self.read_stash()?;
+        let entry = Self::stash_entry(&entries, n)?.clone();
+        self.apply_stash_entry(&entry)?;
+        entries.remove(n);
+        Self::persist(&entries, &self.stash_file)?;
+        Ok(format!("Dropped {} ({})", stash::label(n), entry.message))
+    }
+
+    /// reapply the n-th stash entry on top of the current commit and keep it
+    pub fn stash_apply(&mut self, n: usize) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let entries = self.read_stash()?;
+        let entry = Self::stash_entry(&entries, n)?;
+        self.apply_stash_entry(entry)?;
+        Ok(format!("Applied {} ({})", stash::label(n), entry.message))
+    }
+
+    /// Create a branch at the commit the n-th stash entry was taken on, switch to it and
+    /// reapply the entry there, where it cannot conflict, then drop it. When the entry does
+    /// not apply, e.g. an untracked file of it is in the way, HEAD goes back to the branch it
+    /// was on and the new branch is deleted again
+    pub fn stash_branch(&mut self, name: &str, n: usize) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let mut entries = self.read_stash()?;
+        let entry = Self::stash_entry(&entries, n)?.clone();
+        if self.heads_path.join(name).exists() {
+            return Err(GitError::BranchError(format!(
+                "branch {} already exists",
+                name
+            )));
+        }
+        let original = self.current_branch();
+        self.checkout_new_branch(name, &entry.base)?;
+        if let Err(e) = self.apply_stash_entry(&entry) {
+            return match self
+                .checkout_branch(&original)
+                .and_then(|_| self.branch_delete(name, true))
+            {
+                Ok(_) => Err(e),
+                Err(undo) => Err(GitError::StashError(format!(
+                    "{}, and switching back to {} failed: {}",
+                    e, original, undo
+                ))),
+            };
+        }
+        entries.remove(n);
+        Self::persist(&entries, &self.stash_file)?;
+        Ok(format!(
+            "Switched to a new branch '{}'\nDropped {} ({})",
+            name,
+            stash::label(n),
+            entry.message
+        ))
+    }
+
+    /// discard the n-th stash entry
+    pub fn stash_drop(&self, n: usize) -> Result<String, GitError> {
+        let mut entries = self.read_stash()?;
+        Self::stash_entry(&entries, n)?;
+        let entry = entries.remove(n);
+        Self::persist(&entries, &self.stash_file)?;
+        Ok(format!("Dropped {} ({})", stash::label(n), entry.message))
+    }
+
+    /// the bisection in progress
+    fn read_bisect_state(&self) -> Result<BisectState, GitError> {
+        if !self.bisect_file.is_file() {
+            return Err(GitError::BisectError(
+                "You need to start by \"bisect start\".".to_string(),
+            ));
+        }
+        let content = fs::read_to_string(&self.bisect_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        serde_json::from_str(&content).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// Start a bisection of the current branch, optionally marking a bad commit and good commits.
+    /// The branch stays where it is, bisect only changes the working directory.
+    pub fn bisect_start(&mut self, bad: Option<&str>, good: &[String]) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        if self.bisect_file.exists() {
+            return Err(GitError::BisectError(
+                "Already bisecting; run bisect reset first.".to_string(),
+            ));
+        }
+        if !self.staging_area.is_empty() {
+            return Err(GitError::BisectError(
+                "You have uncommitted changes.".to_string(),
+            ));
+        }
+        let state = BisectState {
+            branch: self.current_branch(),
+            current: self.commit_sha1.clone(),
+            bad: bad.map(|rev| self.resolve_commit(rev)).transpose()?,
+            good: good
+                .iter()
+                .map(|rev| self.resolve_commit(rev))
+                .collect::<Result<_, _>>()?,
+        };
+        self.bisect_next(state)
+    }
+
+    /// Mark commits as bad or good, the checked out commit when none is given,
+    /// then check out the next commit to test
+    pub fn bisect_mark(&mut self, revs: &[String], bad: bool) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let mut state = self.read_bisect_state()?;
+        let mut sha1s: Vec<String> = revs
+            .iter()
+            .map(|rev| self.resolve_commit(rev))
+            .collect::<Result<_, _>>()?;
+        if sha1s.is_empty() {
+            sha1s.push(state.current.clone());
+        }
+        if bad {
+            if sha1s.len() > 1 {
+                return Err(GitError::BisectError(
+                    "bisect bad takes a single commit".to_string(),
+                ));
+            }
+            state.bad = sha1s.pop();
+        } else {
+            state.good.extend(sha1s);
+        }
+        self.bisect_next(state)
+    }
+
+    /// Narrow the candidates down to the commits reachable from the bad commit but not from
+    /// any good one, then check out the one splitting them most evenly, or report the first
+    /// bad commit when it is the only candidate left. The state is persisted either way.
+    fn bisect_next(&mut self, mut state: BisectState) -> Result<String, GitError> {
+        let bad = match &state.bad {
+            Some(bad) if !state.good.is_empty() => bad.clone(),
+            _ => {
+                Self::persist(&state, &self.bisect_file)?;
+                return Ok("status: waiting for both good and bad commits".to_string());
+            }
+        };
+        let mut good_ancestors = HashSet::new();
+        for good in state.good.iter() {
+            good_ancestors.extend(self.ancestors(good)?);
+        }
+        let mut parents = BTreeMap::new();
+        for sha1 in self.ancestors(&bad)? {
+            if !good_ancestors.contains(&sha1) {
+                let commit = self.read_commit(&sha1)?;
+                let commit_parents = commit.parents().iter().map(|p| p.to_string()).collect();
+                parents.insert(sha1, commit_parents);
+            }
+        }
+        if parents.is_empty() {
+            return Err(GitError::BisectError(format!(
+                "the bad commit {} is an ancestor of a good commit",
+                bad
+            )));
+        }
+        let next = match bisect::midpoint(&parents) {
+            Some(next) => next,
+            None => {
+                Self::persist(&state, &self.bisect_file)?;
+                return Ok(format!(
+                    "{} is the first bad commit\n{}",
+                    bad,
+                    self.read_commit(&bad)?
+                ));
+            }
+        };
+
+        // the working directory holds the tree of the current commit, not of the branch head
+        self.commit = self.read_commit(&state.current)?;
+        let target = self.read_commit(&next)?;
+        if self.untracked_file_in_way(&target.blobs)? {
+            return Err(GitError::BisectError(UNTRACKED_IN_WAY.to_string()));
+        }
+        self.materialize_tree(&target.blobs)?;
+        state.current = next.clone();
+        Self::persist(&state, &self.bisect_file)?;
+        let left = parents.len() / 2;
+        Ok(format!(
+            "Bisecting: {} revisions left to test after this (roughly {} steps)\n[{}] {}",
+            left,
+            bisect::steps_left(left),
+            next,
+            target.message().lines().next().unwrap_or("")
+        ))
+    }
+
+    /// Stop bisecting and restore the working directory to the head of the bisected branch
+    pub fn bisect_reset(&mut self) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let state = self.read_bisect_state()?;
+        let head = fs::read_to_string(self.heads_path.join(&state.branch))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let target = self.read_commit(&head)?;
+        self.commit = self.read_commit(&state.current)?;
+        if self.untracked_file_in_way(&target.blobs)? {
+            return Err(GitError::BisectError(UNTRACKED_IN_WAY.to_string()));
+        }
+        self.materialize_tree(&target.blobs)?;
+        fs::remove_file(&self.bisect_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Ok(format!("Back on {}.", state.branch))
+    }
+
+    /// Delete untracked files under the pathspecs, every untracked file when empty.
+    /// Untracked directories, which hold no tracked or staged file, are skipped unless `dirs`
+    /// is set, then they are deleted as a whole. With `dry_run` nothing is deleted.
+    /// Returns a line for every file or directory (with a trailing `/`) deleted.
+    pub fn clean(
+        &mut self,
+        paths: &[String],
+        dry_run: bool,
+        dirs: bool,
+    ) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let untracked = Self::untracked_file(
+            &file_sha1_map,
+            &self.commit.blobs,
+            &self.staging_area.staged,
+        );
+        let tracked_dirs: HashSet<&Path> = self
+            .commit
+            .blobs
+            .keys()
+            .chain(self.staging_area.staged.keys())
+            .flat_map(|path| Path::new(path).ancestors().skip(1))
+            .collect();
+        let mut targets = BTreeSet::new();
+        for path in untracked
+            .iter()
+            .filter(|path| utils::pathspec_matches(paths, path))
+        {
+            // directories above the file that hold nothing tracked, innermost first
+            let untracked_dirs: Vec<&Path> = Path::new(path)
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| !dir.as_os_str().is_empty() && !tracked_dirs.contains(dir))
+                .collect();
+            let dir = untracked_dirs
+                .iter()
+                .rev()
+                .find(|dir| utils::pathspec_matches(paths, &dir.display().to_string()));
+            match dir {
+                _ if untracked_dirs.is_empty() => targets.insert(path.clone()),
+                Some(dir) if dirs => targets.insert(format!("{}/", dir.display())),
+                None if dirs => targets.insert(path.clone()),
+                _ => false,
+            };
+        }
+
+        let mut lines = vec![];
+        for target in targets {
+            if dry_run {
+                lines.push(format!("Would remove {}", target));
+                continue;
+            }
+            let file = self.cwd.join(&target);
+            if target.ends_with('/') {
+                fs::remove_dir_all(file)
+            } else {
+                fs::remove_file(file)
+            }
+            .map_err(|e| GitError::FileOpError(format!("{}: {:?}", target, e)))?;
+            lines.push(format!("Removing {}", target));
+        }
+        Ok(lines)
+    }
+
+    /// Restore a file to its version in the head commit, the restored file is not staged
+    pub fn checkout_file(&mut self, path: &str) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        let entry = self.commit.blobs.get(path).ok_or_else(|| {
+            GitError::CheckoutError("File does not exist in that commit.".to_string())
+        })?;
+        self.write_files(&[(path.to_string(), entry.clone())], 1)
+    }
+
+    /// `restore`: with `staged`, drop the staged changes of the files under the paths, then
+    /// with `worktree`, write them back as the staging area has them, their HEAD version
+    /// when they have no staged change
+    pub fn restore(
+        &mut self,
+        paths: &[String],
+        staged: bool,
+        worktree: bool,
+    ) -> Result<(), GitError> {
+        if staged {
+            self.unstage(paths)?;
+        }
+        if !worktree {
+            return Ok(());
+        }
+        self.load_basic_info()?;
+        let mut index = self.commit.blobs.clone();
+        index.extend(self.staging_area.staged.clone());
+        let mut files = vec![];
+        for path in paths {
+            let matched: Vec<(String, Entry)> = index
+                .iter()
+                .filter(|(file, _)| {
+                    utils::pathspec_matches(std::slice::from_ref(path), file)
+                        && !self.staging_area.deleted.contains_key(*file)
+                })
+                .map(|(file, entry)| (file.clone(), entry.clone()))
+                .collect();
+            if matched.is_empty() {
+                return Err(GitError::CheckoutError(format!(
+                    "pathspec '{}' did not match any file known to git-rs",
+                    path
+                )));
+            }
+            files.extend(matched);
+        }
+        self.write_files(&files, 1)
+    }
+
+    /// Restore a file to its version in a commit, the restored file is not staged
+    pub fn checkout_file_from(&mut self, rev: &str, path: &str) -> Result<(), GitError> {
+        let commit = self.read_commit(&self.resolve_commit(rev)?)?;
+        let entry = commit.blobs.get(path).ok_or_else(|| {
+            GitError::CheckoutError("File does not exist in that commit.".to_string())
+        })?;
+        self.write_files(&[(path.to_string(), entry.clone())], 1)
+    }
+
+    /// Switch to the given branch: files tracked in its head commit are written into the
+    /// working directory, files tracked only in the current commit are deleted and the
+    /// staging area is cleared. Files are written concurrently by `checkout.workers` workers.
+    pub fn checkout_branch(&mut self, name: &str) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        let branch_file = self.heads_path.join(name);
+        if !branch_file.is_file() {
+            let Some((branch, tracking)) = self.branch_to_track(name)? else {
+                return Err(GitError::CheckoutError(
+                    "No such branch exists.".to_string(),
+                ));
+            };
+            let sha1 = self.resolve_commit(&tracking)?;
+            self.checkout_new_branch(&branch, &sha1)?;
+            self.set_upstream(&tracking, Some(&branch))?;
+            return Ok(());
+        }
+        if self.current_branch() == name {
+            return Err(GitError::CheckoutError(
+                "No need to checkout the current branch.".to_string(),
+            ));
+        }
+        let sha1 = fs::read_to_string(&branch_file)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let target = if sha1.is_empty() {
+            Commit::new()
+        } else {
+            self.read_commit(&sha1)?
+        };
+        if self.untracked_file_in_way(&target.blobs)? {
+            return Err(GitError::CheckoutError(UNTRACKED_IN_WAY.to_string()));
+        }
+        self.materialize_tree(&target.blobs)?;
+
+        self.branch = format!("{}/{}", HEADS_DIR, name);
+        fs::write(&self.head_file, self.branch.as_bytes())
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        self.commit = target;
+        self.commit_sha1 = sha1;
+        self.staging_area = StagingArea::new();
+        self.persist_basic_info()
+    }
+
+    /// The new local branch a remote-tracking branch is checked out into, the branch of the
+    /// same name, and the remote-tracking branch: for `<remote>/<branch>` or a `<branch>`
+    /// only one remote has. None when no remote-tracking branch matches.
+    fn branch_to_track(&self, name: &str) -> Result<Option<(String, String)>, GitError> {
+        let tracking = match self.remote_branch_file(name) {
+            Some(_) => name.to_string(),
+            None if revision::check_name(name).is_ok() => {
+                let mut matches = self
+                    .remote_branches()?
+                    .into_keys()
+                    .filter(|tracking| tracking.split_once('/').unwrap().1 == name);
+                match (matches.next(), matches.next()) {
+                    (Some(tracking), None) => tracking,
+                    (Some(_), Some(_)) => {
+                        return Err(GitError::CheckoutError(format!(
+                            "{} matches several remote-tracking branches",
+                            name
+                        )))
+                    }
+                    _ => return Ok(None),
+                }
+            }
+            None => return Ok(None),
+        };
+        let branch = tracking.split_once('/').unwrap().1.to_string();
+        if self.heads_path.join(&branch).exists() {
+            return Err(GitError::CheckoutError(format!(
+                "a branch named {} already exists",
+                branch
+            )));
+        }
+        Ok(Some((branch, tracking)))
+    }
+
+    /// `checkout -b`: create a branch at the start point, HEAD by default, and switch to it.
+    /// Started at HEAD, the working directory and the staging area are kept as they are.
+    pub fn checkout_create(&mut self, name: &str, start: Option<&str>) -> Result<(), GitError> {
+        self.load_basic_info()?;
+        revision::check_name(name)?;
+        if name == HEAD_FILE || self.heads_path.join(name).exists() {
+            return Err(GitError::CheckoutError(format!(
+                "a branch named '{}' already exists",
+                name
+            )));
+        }
+        let sha1 = match start {
+            Some(rev) => self.resolve_commit(rev)?,
+            None => self.commit_sha1.clone(),
+        };
+        if sha1 != self.commit_sha1 {
+            return self.checkout_new_branch(name, &sha1);
+        }
+        self.update_ref(&format!("{}/{}", HEADS_DIR, name), &sha1)?;
+        self.branch = format!("{}/{}", HEADS_DIR, name);
+        fs::write(&self.head_file, self.branch.as_bytes())
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// create a branch at a commit and switch to it, the branch is not kept when the switch
+    /// fails
+    fn checkout_new_branch(&mut self, name: &str, sha1: &str) -> Result<(), GitError> {
+        let branch_file = self.heads_path.join(name);
+        self.update_ref(&format!("{}/{}", HEADS_DIR, name), sha1)?;
+        let res = self.checkout_branch(name);
+        if res.is_err() {
+            let _ = fs::remove_file(branch_file);
+        }
+        res
+    }
+
+    /// Every worktree of the repository as (path, HEAD, administrative directory), the main
+    /// one first without an administrative directory, then the linked ones by name
+    fn worktrees(&self) -> Result<Vec<(PathBuf, String, Option<PathBuf>)>, GitError> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+        };
+        let main = self.repo_path.parent().unwrap_or(&self.repo_path);
+        let mut worktrees = vec![(
+            main.canonicalize().unwrap_or_else(|_| main.to_path_buf()),
+            read(&self.repo_path.join(HEAD_FILE))?,
+            None,
+        )];
+        let worktrees_path = self.repo_path.join(WORKTREES_DIR);
+        if !worktrees_path.is_dir() {
+            return Ok(worktrees);
+        }
+        let mut admins = vec![];
+        for entry in
+            fs::read_dir(&worktrees_path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+        {
+            let entry = entry.map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            admins.push(entry.path());
+        }
+        admins.sort();
+        for admin in admins {
+            let path = PathBuf::from(read(&admin.join(GITDIR_FILE))?.trim_end());
+            worktrees.push((path, read(&admin.join(HEAD_FILE))?, Some(admin)));
+        }
+        Ok(worktrees)
+    }
+
+    /// Check a branch out in a new working directory at `path`, a linked worktree sharing
+    /// the objects, the refs and the config of the repository. Its HEAD and its index are
+    /// kept in `worktrees/<name>` of the repository, which its `.git-rs` file points at.
+    /// A branch can only be checked out in one worktree.
+    pub fn worktree_add(&mut self, path: &str, branch: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let dir = self.cwd.join(path);
+        let used = dir.is_file()
+            || (dir.is_dir()
+                && fs::read_dir(&dir)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                    .next()
+                    .is_some());
+        if used {
+            return Err(GitError::WorktreeError(format!(
+                "'{}' already exists",
+                path
+            )));
+        }
+        let head = format!("{}/{}", HEADS_DIR, branch);
+        if revision::check_name(branch).is_err() || !self.repo_path.join(&head).is_file() {
+            return Err(GitError::WorktreeError(format!(
+                "invalid reference: {}",
+                branch
+            )));
+        }
+        if let Some((at, _, _)) = self.worktrees()?.iter().find(|(_, h, _)| *h == head) {
+            return Err(GitError::WorktreeError(format!(
+                "'{}' is already checked out at '{}'",
+                branch,
+                at.display()
+            )));
+        }
+
+        let worktrees_path = self.repo_path.join(WORKTREES_DIR);
+        let name = worktree::admin_name(&dir, |name| worktrees_path.join(name).exists());
+        let admin = worktrees_path.join(name);
+        for dir in [&admin, &dir] {
+            fs::create_dir_all(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        for (file, content) in [
+            (admin.join(HEAD_FILE), head),
+            (admin.join(INDEX_FILE), String::new()),
+            (admin.join(GITDIR_FILE), dir.display().to_string()),
+            (dir.join(GIT_DIR), worktree::link(&admin)),
+        ] {
+            fs::write(file, content).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        let mut linked = Self::at(&dir, &dir.join(GIT_DIR));
+        linked.load_basic_info()?;
+        let files: Vec<(String, Entry)> = linked.commit.blobs.clone().into_iter().collect();
+        linked.write_files(&files, materialize::default_workers())?;
+        Ok(format!("Preparing worktree (checking out '{}')", branch))
+    }
+
+    /// one line per worktree, `<path> <commit> [<branch>]`, the main one first
+    pub fn worktree_list(&self) -> Result<Vec<String>, GitError> {
+        let worktrees = self.worktrees()?;
+        let width = worktrees
+            .iter()
+            .map(|(path, _, _)| path.display().to_string().len())
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec![];
+        for (path, head, _) in worktrees {
+            let sha1 = fs::read_to_string(self.repo_path.join(&head)).unwrap_or_default();
+            lines.push(format!(
+                "{:<width$}  {} [{}]",
+                path.display().to_string(),
+                &sha1[..sha1.len().min(7)],
+                head.strip_prefix(&format!("{}/", HEADS_DIR))
+                    .unwrap_or(&head),
+                width = width
+            ));
+        }
+        Ok(lines)
+    }
+
+    /// Delete a linked worktree, its working directory and its administrative directory.
+    /// One with local changes or untracked files is only deleted with `force`.
+    pub fn worktree_remove(&mut self, path: &str, force: bool) -> Result<(), GitError> {
+        let dir = self.cwd.join(path);
+        let dir = dir.canonicalize().unwrap_or(dir);
+        let found = self.worktrees()?.into_iter().find(|(at, _, _)| *at == dir);
+        let Some((dir, _, Some(admin))) = found else {
+            let reason = match found {
+                Some(_) => "is a main working tree",
+                None => "is not a working tree",
+            };
+            return Err(GitError::WorktreeError(format!("'{}' {}", path, reason)));
+        };
+        if !force && dir.exists() {
+            let mut linked = Self::at(&dir, &dir.join(GIT_DIR));
+            if !linked.status_codes()?.is_empty() {
+                return Err(GitError::WorktreeError(format!(
+                    "'{}' contains modified or untracked files, use --force to delete it",
+                    path
+                )));
+            }
+        }
+        for dir in [&dir, &admin] {
+            if dir.exists() {
+                fs::remove_dir_all(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// file path --> sha1 pairs of every readable file in the working directory,
+    /// unreadable files are skipped and reported through `take_warnings`.
+    /// Untracked files excluded by `.gitignore` files are left out, tracked files never are.
+    fn working_file_sha1_map(&mut self) -> Result<BTreeMap<String, Entry>, GitError> {
+        let mut ignore = self.base_ignore()?;
+        let mut file_sha1_map =
+            utils::generate_file_sha1_map(&self.cwd, &mut ignore, &mut self.warnings)?;
+        let filter = &mut self.eol_filter()?;
+        // files whose line endings are converted hash as the repository stores them
+        let mut rehash = vec![];
+        for path in file_sha1_map.keys() {
+            if filter.conversion(path)?.eol.is_some() {
+                rehash.push(path.clone());
+            }
+        }
+        for path in self
+            .commit
+            .blobs
+            .keys()
+            .chain(self.staging_area.staged.keys())
+        {
+            if !file_sha1_map.contains_key(path) && self.cwd.join(path).is_file() {
+                rehash.push(path.clone());
+            }
+        }
+        for path in rehash {
+            match self.hash_file(filter, &path) {
+                Ok(sha1) => {
+                    file_sha1_map.insert(path, sha1);
+                }
+                Err(e) => {
+                    file_sha1_map.remove(&path);
+                    self.warnings.push(format!("skip file {}: {}", path, e));
+                }
+            }
+        }
+        Ok(file_sha1_map)
+    }
+
+    /// line ending conversions of working directory files, from `core.autocrlf` and the
+    /// `text` and `eol` attributes
+    fn eol_filter(&self) -> Result<EolFilter, GitError> {
+        let autocrlf = AutoCrlf::from_config(&self.config()?)?;
+        Ok(EolFilter::new(self.attributes(), autocrlf))
+    }
+
+    /// compression of newly written blobs, chunks and commits, from `core.compression` and
+    /// `core.compressionAlgorithm`, see `Compression::from_config`
+    fn compression(&self) -> Result<Compression, GitError> {
+        Compression::from_config(&self.config()?)
+    }
+
+    /// mode of a working directory file
+    fn file_mode(&self, path: &str) -> Result<FileMode, GitError> {
+        let file = self.cwd.join(path);
+        if !entry::exists(&file) {
+            return Err(GitError::FileNotExistError(file.display().to_string()));
+        }
+        FileMode::of(&file)
+    }
+
+    /// whether a working directory file is stored byte for byte: a file without line
+    /// ending conversion, which can be hashed and copied without holding it in memory
+    fn stored_as_is(
+        &self,
+        filter: &mut EolFilter,
+        path: &str,
+        mode: FileMode,
+    ) -> Result<bool, GitError> {
+        Ok(mode != FileMode::Symlink && filter.conversion(path)? == Conversion::NONE)
+    }
+
+    /// content of a working directory file as the repository stores it, and its mode
+    fn clean_file(
+        &self,
+        filter: &mut EolFilter,
+        path: &str,
+    ) -> Result<(Vec<u8>, FileMode), GitError> {
+        self.file_mode(path)?;
+        let (content, mode) = entry::read_content(&self.cwd.join(path))?;
+        if mode == FileMode::Symlink {
+            return Ok((content, mode));
+        }
+        let content = filter.conversion(path)?.to_repo(&content).into_owned();
+        Ok((content, mode))
+    }
+
+    /// entry of a working directory file as the repository stores it
+    fn hash_file(&self, filter: &mut EolFilter, path: &str) -> Result<Entry, GitError> {
+        let mode = self.file_mode(path)?;
+        if self.stored_as_is(filter, path, mode)? {
+            return Ok(Entry::with_mode(
+                utils::crypto_file(&self.cwd.join(path))?,
+                mode,
+            ));
+        }
+        let (content, mode) = self.clean_file(filter, path)?;
+        Ok(Entry::with_mode(utils::crypto_bytes(&content), mode))
+    }
+
+    /// The rule deciding whether each path is ignored, for the paths that have one: the
+    /// pattern, its file relative to the working directory and its line. Negated rules mean the
+    /// path is not ignored. Tracked and staged files are never ignored.
+    pub fn check_ignore(&mut self, paths: &[String]) -> Result<Vec<(String, Rule)>, GitError> {
+        self.load_basic_info()?;
+        let mut matched = vec![];
+        for path in paths.iter() {
+            let name = path.trim_end_matches('/');
+            if self.commit.blobs.contains_key(name) || self.staging_area.staged.contains_key(name) {
+                continue;
+            }
+            // the ignore files of the root and of every directory above the path
+            let mut ignore = self.base_ignore()?;
+            let mut dir = PathBuf::new();
+            ignore.add_file(&self.cwd.join(IGNORE_FILE), "")?;
+            for component in Path::new(name)
+                .parent()
+                .into_iter()
+                .flat_map(Path::components)
+            {
+                dir.push(component);
+                let base = dir.display().to_string();
+                ignore.add_file(&self.cwd.join(&dir).join(IGNORE_FILE), &base)?;
+            }
+            let is_dir = path.ends_with('/') || self.cwd.join(name).is_dir();
+            if let Some(rule) = ignore.matching(name, is_dir) {
+                let mut rule = rule.clone();
+                if let Ok(source) = Path::new(&rule.source).strip_prefix(&self.cwd) {
+                    rule.source = source.display().to_string();
+                }
+                matched.push((path.clone(), rule));
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Rules every working directory scan starts with, before any `.gitignore` file: the
+    /// repository directories are never part of the working tree, then the user-level ignore
+    /// file of `core.excludesFile`, e.g. for editor temp files and OS junk, where a leading
+    /// `~/` is the home directory and relative paths start at the working directory
+    fn base_ignore(&self) -> Result<Ignore, GitError> {
+        let repo_dir = self
+            .repo_path
+            .strip_prefix(&self.cwd)
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_else(|_| GIT_DIR.to_string());
+        let mut ignore = Ignore::new();
+        // the git directory of a linked worktree is a file
+        ignore.add_patterns(
+            &format!("/{}/\n/{}\n/.git/\n", repo_dir, GIT_DIR),
+            "",
+            "<built-in>",
+        )?;
+        if let Some(file) = self.config()?.get("core.excludesFile") {
+            let path = match (file.strip_prefix("~/"), env::var("HOME")) {
+                (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+                _ => self.cwd.join(file),
+            };
+            ignore.add_file(&path, "")?;
+        }
+        Ok(ignore)
+    }
+
+    /// Displays Untracked Files
+    /// The final category (“Untracked Files”) is for files present in the working directory
+    /// but neither staged for addition nor tracked.
+    /// This includes files that have been staged for removal,
+    /// but then re-created without Gitlet’s knowledge.
+    fn untrack_status(&mut self, palette: &Palette) -> Result<String, GitError> {
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let mut msg: Vec<String> = vec![];
+        msg.push("=== Untracked Files ===".to_string());
+        let untracked = Self::untracked_file(
+            &file_sha1_map,
+            &self.commit.blobs,
+            &self.staging_area.staged,
+        );
+        msg.extend(untracked.iter().map(|f| palette.paint(Color::Cyan, f)));
+        Ok(msg.join("\n"))
+    }
+
+    /// Untracked file
+    fn untracked_file(
+        file_sha1_map: &BTreeMap<String, Entry>,
+        commit: &BTreeMap<String, Entry>,
+        staged: &BTreeMap<String, Entry>,
+    ) -> Vec<String> {
+        file_sha1_map
+            .iter()
+            .filter(|(k, _)| {
+                !commit.contains_key(k.to_owned()) && !staged.contains_key(k.to_owned())
+            })
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+
+    /// Collection files tracked in the current commit which have been modified but not Staged For Commit
+    fn committed_file_modified_not_stage(
+        file_sha1_map: &BTreeMap<String, Entry>,
+        commit: &BTreeMap<String, Entry>,
+        staged: &BTreeMap<String, Entry>,
+    ) -> Vec<String> {
+        file_sha1_map
+            .iter()
+            .filter(|(k, v)| {
+                commit.contains_key(k.to_owned())
+                    && commit.get(k.to_owned()) != Some(v.to_owned())
+                    && !staged.contains_key(k.to_owned())
+            })
+            .map(|(k, _)| k.clone().add(" (modified)"))
+            .collect::<Vec<String>>()
+    }
+    /// Staged for addition, but with different contents than in the working directory
+    fn staged_for_addition_but_with_different_contents(
+        file_sha1_map: &BTreeMap<String, Entry>,
+        staged: &BTreeMap<String, Entry>,
+    ) -> Vec<String> {
+        file_sha1_map
+            .iter()
+            .filter(|(k, v)| {
+                staged.contains_key(k.to_owned()) && staged.get(k.to_owned()) != Some(v)
+            })
+            .map(|(k, _)| k.clone().add(" (modified)"))
+            .collect::<Vec<String>>()
+    }
+
+    /// Staged for addition, but deleted in the working directory.
+    fn staged_for_addition_but_deleted(
+        file_sha1_map: &BTreeMap<String, Entry>,
+        staged: &BTreeMap<String, Entry>,
+    ) -> Vec<String> {
+        staged
+            .iter()
+            .filter(|(k, _)| !file_sha1_map.contains_key(k.to_owned()))
+            .map(|(k, _)| k.clone().add(" (deleted)"))
+            .collect::<Vec<String>>()
+    }
+
+    /// Not staged for removal, but tracked in the current commit and deleted from the working directory.
+    fn not_staged_for_removal_but_deleted(
+        file_sha1_map: &BTreeMap<String, Entry>,
+        commit: &BTreeMap<String, Entry>,
+        deleted: &BTreeMap<String, String>,
+    ) -> Vec<String> {
+        commit
+            .iter()
+            .filter(|(k, _)| {
+                !file_sha1_map.contains_key(k.to_owned()) && !deleted.contains_key(k.to_owned())
+            })
+            .map(|(k, _)| k.clone().add(" (deleted)"))
+            .collect::<Vec<String>>()
+    }
+
+    /// Displays what files have been modified by not Staged For Commit
+    ///  A file in the working directory is “modified but not staged” if it is
+    /// Tracked in the current commit, changed in the working directory, but not staged; or
+    /// Staged for addition, but with different contents than in the working directory; or
+    /// Staged for addition, but deleted in the working directory; or
+    /// Not staged for removal, but tracked in the current commit and deleted from the working directory.
+    fn modified_not_staged(&mut self, palette: &Palette) -> Result<String, GitError> {
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let mut msg: Vec<String> = vec![];
+        msg.push("=== Modifications Not Staged For Commit ===".to_string());
+        let modified = self.modified_files(&file_sha1_map);
+        msg.extend(modified.iter().map(|f| palette.paint(Color::Red, f)));
+        Ok(msg.join("\n"))
+    }
+
+    /// All four kinds of modifications not staged for commit
+    fn modified_files(&self, file_sha1_map: &BTreeMap<String, Entry>) -> Vec<String> {
+        let mut files = Self::committed_file_modified_not_stage(
+            file_sha1_map,
+            &self.commit.blobs,
+            &self.staging_area.staged,
+        );
+        files.extend(Self::staged_for_addition_but_with_different_contents(
+            file_sha1_map,
+            &self.staging_area.staged,
+        ));
+        files.extend(Self::staged_for_addition_but_deleted(
+            file_sha1_map,
+            &self.staging_area.staged,
+        ));
+        files.extend(Self::not_staged_for_removal_but_deleted(
+            file_sha1_map,
+            &self.commit.blobs,
+            &self.staging_area.deleted,
+        ));
+        files
+    }
+
+    /// Displays what files have been staged for addition
+    fn staged_status(&self, palette: &Palette) -> Result<String, GitError> {
+        let mut msg: Vec<String> = vec![];
+        msg.push("=== Staged Files ===".to_string());
+        for (k, _) in self.staging_area.staged.iter() {
+            msg.push(palette.paint(Color::Green, k));
+        }
+        Ok(msg.join("\n"))
+    }
+    /// Displays what files have been staged for removal.
+    fn removal_status(&self, palette: &Palette) -> Result<String, GitError> {
+        let mut msg: Vec<String> = vec![];
+        msg.push("=== Removed Files ===".to_string());
+        for (k, _) in self.staging_area.deleted.iter() {
+            msg.push(palette.paint(Color::Green, k));
+        }
+        Ok(msg.join("\n"))
+    }
+
+    /// Displays what branches currently exist, and marks the current branch with a *.
+    fn branch_status(&self) -> Result<String, GitError> {
+        let mut msg: Vec<String> = vec![];
+
+        msg.push("=== Branches ===".to_string());
+
+        let current_branch_path = self.repo_path.join(&self.branch);
+        let current_branch_name = current_branch_path
+            .strip_prefix(&self.heads_path)
+            .map_err(|_| GitError::BranchError("invalid branch name".to_string()))?;
+        msg.push(format!("*{}", current_branch_name.display()));
+        for entry in
+            fs::read_dir(&self.heads_path).map_err(|e| GitError::BranchError(format!("{:?}", e)))?
+        {
+            let path = entry
+                .map_err(|_| GitError::BranchError("invalid branch name".to_lowercase()))?
+                .path();
+            let branch_name = path
+                .strip_prefix(&self.heads_path)
+                .map_err(|_| GitError::BranchError("invalid branch name".to_string()))?;
+
+            info!("{:?}", branch_name.display());
+            if current_branch_name != branch_name {
+                msg.push(branch_name.display().to_string());
+            }
+        }
+        Ok(msg.join("\n"))
+    }
+
+    /// Displays what branches currently exist, and marks the current branch with a *.
+    /// Also displays what files have been staged for addition or removal. An example of the exact
+    /// format it should follow is as follows.
+    pub fn status(&mut self) -> Result<String, GitError> {
+        self.status_with(&StatusOptions::default())
+    }
+
+    /// `status` with options
+    pub fn status_with(&mut self, options: &StatusOptions) -> Result<String, GitError> {
+        info!("status >> ");
+        self.load_basic_info()?;
+        let palette = &Palette::new(options.color);
+        let mut msg: Vec<String> = vec![
+            self.branch_status()?,
+            self.staged_status(palette)?,
+            self.removal_status(palette)?,
+            self.modified_not_staged(palette)?,
+            self.untrack_status(palette)?,
+        ];
+        if let Some(tracking) = self.tracking_status()? {
+            msg.insert(0, tracking);
+        }
+        info!("status << ");
+        Ok(msg.join("\n\n"))
+    }
+
+    /// Same information as `status` as a structured report
+    pub fn status_report(&mut self) -> Result<StatusReport, GitError> {
+        self.load_basic_info()?;
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let mut branches: Vec<String> = self.branches()?.into_keys().collect();
+        branches.retain(|b| *b != self.current_branch());
+        Ok(StatusReport {
+            branch: self.current_branch(),
+            branches,
+            staged: self.staging_area.staged.keys().cloned().collect(),
+            removed: self.staging_area.deleted.keys().cloned().collect(),
+            modified: self.modified_files(&file_sha1_map),
+            untracked: Self::untracked_file(
+                &file_sha1_map,
+                &self.commit.blobs,
+                &self.staging_area.staged,
+            ),
+        })
+    }
+
+    /// Whether to color output written to a terminal or not, see `color.ui`
+    pub fn color_enabled(&self, is_terminal: bool) -> Result<bool, GitError> {
+        let mode = match self.config()?.get("color.ui") {
+            Some(value) => ColorMode::parse(value)?,
+            None => ColorMode::default(),
+        };
+        Ok(mode.enabled(is_terminal))
+    }
+
+    /// `status_report` as pretty-printed JSON, an object with the fields of `StatusReport`
+    pub fn status_json(&mut self) -> Result<String, GitError> {
+        serde_json::to_string_pretty(&self.status_report()?)
+            .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// Two-letter code and path of every changed file, tracked changes sorted by path then
+    /// untracked files. The first letter is the staged change, `A`dded, `M`odified or
+    /// `D`eleted, the second the change in the working directory not staged, `M` or `D`,
+    /// and untracked files are `??`.
+    pub fn status_codes(&mut self) -> Result<Vec<(char, char, String)>, GitError> {
+        let report = self.status_report()?;
+        let mut codes: BTreeMap<&str, (char, char)> = BTreeMap::new();
+        for path in report.staged.iter() {
+            let staged = match self.commit.blobs.contains_key(path) {
+                true => 'M',
+                false => 'A',
+            };
+            codes.entry(path).or_insert((' ', ' ')).0 = staged;
+        }
+        for path in report.removed.iter() {
+            codes.entry(path).or_insert((' ', ' ')).0 = 'D';
+        }
+        for change in report.modified.iter() {
+            let (path, code) = match change.strip_suffix(" (deleted)") {
+                Some(path) => (path, 'D'),
+                None => (change.strip_suffix(" (modified)").unwrap_or(change), 'M'),
+            };
+            codes.entry(path).or_insert((' ', ' ')).1 = code;
+        }
+        Ok(codes
+            .into_iter()
+            .map(|(path, (x, y))| (x, y, path.to_string()))
+            .chain(report.untracked.iter().map(|path| ('?', '?', path.clone())))
+            .collect())
+    }
+
+    /// Same information as `status` as stable `XY path` lines for scripts, see
+    /// `status_codes`
+    pub fn status_porcelain(&mut self) -> Result<String, GitError> {
+        Ok(self
+            .status_codes()?
+            .into_iter()
+            .map(|(x, y, path)| format!("{}{} {}\n", x, y, path))
+            .collect())
+    }
+
+    /// Compact `status`, one `XY path` line per changed file like `status_porcelain`, the
+    /// staged letter green, the other red and untracked files cyan when colored
+    pub fn status_short(&mut self, options: &StatusOptions) -> Result<String, GitError> {
+        let palette = &Palette::new(options.color);
+        let paint = |color: Color, code: char| match code {
+            ' ' => " ".to_string(),
+            _ => palette.paint(color, &code.to_string()),
+        };
+        Ok(self
+            .status_codes()?
+            .into_iter()
+            .map(|(x, y, path)| match (x, y) {
+                ('?', '?') => format!("{}\n", palette.paint(Color::Cyan, &format!("?? {}", path))),
+                _ => format!(
+                    "{}{} {}\n",
+                    paint(Color::Green, x),
+                    paint(Color::Red, y),
+                    path
+                ),
+            })
+            .collect())
+    }
+
+    /// Paths selected by the options under the pathspecs, sorted, one entry per file
+    pub fn ls_files(
+        &mut self,
+        options: &LsFilesOptions,
+        paths: &[String],
+    ) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let changed = self.modified_files(&file_sha1_map);
+        let deleted = changed.iter().filter_map(|f| f.strip_suffix(" (deleted)"));
+        let mut files: BTreeSet<String> = BTreeSet::new();
+        if !(options.staged || options.deleted || options.others || options.modified) {
+            files.extend(
+                self.commit
+                    .blobs
+                    .keys()
+                    .filter(|path| !self.staging_area.deleted.contains_key(*path))
+                    .cloned(),
+            );
+            files.extend(self.staging_area.staged.keys().cloned());
+        }
+        if options.staged {
+            files.extend(self.staging_area.staged.keys().cloned());
+        }
+        if options.deleted {
+            files.extend(deleted.clone().map(String::from));
+        }
+        if options.others {
+            files.extend(Self::untracked_file(
+                &file_sha1_map,
+                &self.commit.blobs,
+                &self.staging_area.staged,
+            ));
+        }
+        if options.modified {
+            files.extend(
+                changed
+                    .iter()
+                    .filter_map(|f| f.strip_suffix(" (modified)"))
+                    .chain(deleted)
+                    .map(String::from),
+            );
+        }
+        Ok(files
+            .into_iter()
+            .filter(|path| utils::pathspec_matches(paths, path))
+            .collect())
+    }
+
+    pub fn log(&mut self) -> Result<String, GitError> {
+        self.log_with(&LogOptions::default())
+    }
+
+    /// `log` with a `Signature:` line after each commit id when `show_signature` is set,
+    /// telling whether the commit is unsigned or its signature is good or bad, and the refs
+    /// pointing at each commit after its id when `decorate` is set
+    pub fn log_with(&mut self, options: &LogOptions) -> Result<String, GitError> {
+        info!("log >> ");
+        self.load_basic_info()?;
+        let decorations = match options.decorate {
+            true => self.decorations()?,
+            false => HashMap::new(),
+        };
+        let commits = match (&options.follow, &options.range) {
+            (Some(path), _) => self
+                .follow_history(&self.commit_sha1, path)?
+                .into_iter()
+                .map(|(sha1, commit, _)| (sha1, commit))
+                .collect(),
+            (None, Some(range)) => self.commit_range(range)?,
+            (None, None) => self.history(&self.commit_sha1)?,
+        };
+        let filters = options.filters()?;
+        let shown = commits
+            .into_iter()
+            .filter(|(_, commit)| filters.iter().all(|filter| filter(commit)))
+            .take(options.max_count.unwrap_or(usize::MAX));
+        let mut msg: Vec<String> = vec![];
+        for (sha1, commit) in shown {
+            let id_line = format!("commit {}\n", sha1);
+            let decoration = decorations.get(&sha1).map_or("", |d| d.as_str());
+            let mut header = format!("commit {}{}\n", sha1, decoration);
+            if options.show_signature {
+                header.push_str(&self.signature_line(&commit)?);
+            }
+            let entry = commit.to_string().replacen(&id_line, &header, 1);
+            let mut entry = format!("{}{}", entry, self.notes_section(&sha1)?);
+            if options.stat && commit.merge_parents.is_empty() {
+                let parent = match commit.parent.is_empty() {
+                    true => BTreeMap::new(),
+                    false => self.read_commit(&commit.parent)?.blobs,
+                };
+                let stat = self.diff_stat(&parent, &commit.blobs)?;
+                if !stat.is_empty() {
+                    entry.push_str(&format!("\n{}", stat));
+                }
+            }
+            msg.push(format!("{}\n\n", entry));
+        }
+        info!("log << ");
+        Ok(msg.join("\n"))
+    }
+    /// note attached to a commit, None when it has none
+    pub fn read_note(&self, sha1: &str) -> Result<Option<String>, GitError> {
+        let path = self.notes_path.join(sha1);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        fs::read_to_string(path)
+            .map(Some)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// `Notes:` section shown after the commit message by log and show, empty without a note
+    fn notes_section(&self, sha1: &str) -> Result<String, GitError> {
+        Ok(match self.read_note(sha1)? {
+            Some(note) => {
+                let lines: String = note.lines().map(|l| format!("    {}\n", l)).collect();
+                format!("\nNotes:\n{}", lines)
+            }
+            None => String::new(),
+        })
+    }
+
+    /// Attach a note to a commit without rewriting it. An existing note is only replaced
+    /// when `force` is set.
+    pub fn notes_add(&self, rev: &str, message: &str, force: bool) -> Result<(), GitError> {
+        let sha1 = self.resolve_commit(rev)?;
+        let path = self.notes_path.join(&sha1);
+        if path.exists() && !force {
+            return Err(GitError::NotesError(format!(
+                "Cannot add notes. Found existing notes for object {}. \
+                 Use '-f' to overwrite existing notes",
+                sha1
+            )));
+        }
+        Self::init_repo_dir(&self.notes_path)?;
+        fs::write(path, message).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// the note attached to a commit
+    pub fn notes_show(&self, rev: &str) -> Result<String, GitError> {
+        let sha1 = self.resolve_commit(rev)?;
+        self.read_note(&sha1)?
+            .ok_or_else(|| GitError::NotesError(format!("no note found for object {}", sha1)))
+    }
+
+    /// every commit ever made as sha1 --> commit pairs, reachable from a branch or not
+    pub fn all_commits(&self) -> Result<BTreeMap<String, Commit>, GitError> {
+        let mut commits = BTreeMap::new();
+        for sha1 in self.commit_ids()? {
+            let commit = Self::unpersist_commit(&self.commit_file(&sha1)?)?;
+            commits.insert(sha1, commit);
+        }
+        Ok(commits)
+    }
+
+    /// Like log, except displays information about all commits ever made. The order of
+    /// the commits does not matter.
+    pub fn global_log(&mut self) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let mut msg: Vec<String> = vec![];
+        for (sha1, commit) in self.all_commits()? {
+            msg.push(format!("{}{}\n\n", commit, self.notes_section(&sha1)?));
+        }
+        Ok(msg.join("\n"))
+    }
+
+    /// ids of all commits that have exactly the given commit message
+    pub fn find(&self, msg: &str) -> Result<Vec<String>, GitError> {
+        let ids: Vec<String> = self
+            .all_commits()?
+            .into_iter()
+            .filter(|(_, commit)| commit.message() == msg)
+            .map(|(sha1, _)| sha1)
+            .collect();
+        if ids.is_empty() {
+            Err(GitError::CommitError(
+                "Found no commit with that message.".to_string(),
+            ))
+        } else {
+            Ok(ids)
+        }
+    }
+
+    /// attributes of the working directory paths from their `.gitattributes` files
+    fn attributes(&self) -> Attributes {
+        Attributes::new(&self.cwd)
+    }
+
+    /// `mode change 100644 => 100755 path` line when a file kept on both sides changed mode
+    fn mode_change(path: &str, old: Option<&Entry>, new: Option<&Entry>) -> String {
+        match (old, new) {
+            (Some(old), Some(new)) if old.mode != new.mode => {
+                format!("mode change {} => {} {}\n", old.mode, new.mode, path)
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// unified diff of one file, None content means the file does not exist on that side.
+    /// Binary files, see `Attributes::is_binary` and `diff::is_binary`, only tell that they
+    /// differ.
+    fn file_diff(
+        attributes: &mut Attributes,
+        palette: &Palette,
+        (old_path, new_path): (&str, &str),
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+    ) -> Result<String, GitError> {
+        let name = |prefix: &str, path: &str, content: Option<&[u8]>| match content {
+            Some(_) => format!("{}/{}", prefix, path),
+            None => "/dev/null".to_string(),
+        };
+        let (old_name, new_name) = (name("a", old_path, old), name("b", new_path, new));
+        let binary = attributes.is_binary(new_path)?
+            || old.is_some_and(diff::is_binary)
+            || new.is_some_and(diff::is_binary);
+        if binary {
+            if old == new {
+                return Ok(String::new());
+            }
+            return Ok(format!(
+                "Binary files {} and {} differ\n",
+                old_name, new_name
+            ));
+        }
+        // neither side is binary, so both are UTF-8
+        let old_text = std::str::from_utf8(old.unwrap_or_default()).unwrap_or_default();
+        let new_text = std::str::from_utf8(new.unwrap_or_default()).unwrap_or_default();
+        Ok(diff::unified_with(
+            &old_name, &new_name, old_text, new_text, palette,
+        ))
+    }
+
+    /// unified diff of every tracked file whose working directory content differs from HEAD
+    pub fn diff(&mut self) -> Result<String, GitError> {
+        self.diff_with(&DiffOptions::default())
+    }
+
+    /// `diff` with options, copies are not looked for
+    pub fn diff_with(&mut self, options: &DiffOptions) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let palette = &Palette::new(options.color);
+        let file_sha1_map = self.working_file_sha1_map()?;
+        let attributes = &mut self.attributes();
+        let filter = &mut self.eol_filter()?;
+        let mut out = String::new();
+        for (path, entry) in self.commit.blobs.iter() {
+            if file_sha1_map.get(path) == Some(entry)
+                || !utils::pathspec_matches(&options.paths, path)
+            {
+                continue;
+            }
+            let old = self.read_blob(&entry.sha1)?;
+            let new = match fs::symlink_metadata(self.cwd.join(path)) {
+                Ok(_) => Some(self.clean_file(filter, path)?.0),
+                Err(_) => None,
+            };
+            out.push_str(&Self::mode_change(
+                path,
+                Some(entry),
+                file_sha1_map.get(path),
+            ));
+            out.push_str(&Self::file_diff(
+                attributes,
+                palette,
+                (path, path),
+                Some(&old),
+                new.as_deref(),
+            )?);
+        }
+        Ok(out)
+    }
+
+    /// unified diff of the staging area against HEAD, i.e. what the next commit will contain
+    pub fn diff_staged(&mut self) -> Result<String, GitError> {
+        self.diff_staged_with(&DiffOptions::default())
+    }
+
+    /// `diff_staged` with options
+    pub fn diff_staged_with(&mut self, options: &DiffOptions) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        // files outside the pathspecs keep their HEAD version and do not show up
+        let matched = |path: &&String| utils::pathspec_matches(&options.paths, path);
+        let mut staged = self.commit.blobs.clone();
+        for (path, entry) in self.staging_area.staged.iter() {
+            if matched(&path) {
+                staged.insert(path.clone(), entry.clone());
+            }
+        }
+        for path in self.staging_area.deleted.keys().filter(matched) {
+            staged.remove(path);
+        }
+        self.diff_trees(&self.commit.blobs, &staged, options)
+    }
+
+    /// unified diff of every file added, deleted or modified between two commits
+    pub fn diff_commits(&self, old_rev: &str, new_rev: &str) -> Result<String, GitError> {
+        self.diff_commits_with(old_rev, new_rev, &DiffOptions::default())
+    }
+
+    /// `diff_commits` with options
+    pub fn diff_commits_with(
+        &self,
+        old_rev: &str,
+        new_rev: &str,
+        options: &DiffOptions,
+    ) -> Result<String, GitError> {
+        let old = self.read_commit(&self.resolve_commit(old_rev)?)?;
+        let new = self.read_commit(&self.resolve_commit(new_rev)?)?;
+        self.diff_trees(&old.blobs, &new.blobs, options)
+    }
+
+    /// `diff_commits_with` between the ends of a `from..to` or `a...b` range
+    pub fn diff_range_with(&self, range: &str, options: &DiffOptions) -> Result<String, GitError> {
+        let (old, new) = self.range_ends(range)?;
+        self.diff_commits_with(&old, &new, options)
+    }
+
+    /// Show a commit: its id, parents, author, date and message followed by the diff against
+    /// its first parent. An annotated tag shows its annotation first.
+    pub fn show(&self, rev: &str) -> Result<String, GitError> {
+        let sha1 = self.resolve_commit(rev)?;
+        let commit = self.read_commit(&sha1)?;
+        let mut out = String::new();
+        if !self.heads_path.join(rev).is_file() && self.tag_refs_path.join(rev).is_file() {
+            if let Some(tag) = self.read_tag(rev)? {
+                out.push_str(&format!("{}\n", tag));
+            }
+        }
+        out.push_str(&format!("commit {}\n", sha1));
+        let parents = commit.parents();
+        if !parents.is_empty() {
+            out.push_str(&format!("Parents: {}\n", parents.join(" ")));
+        }
+        out.push_str(&commit.identity_lines());
+        out.push_str(&format!(
+            "Date: {}\n\n{}\n{}\n",
+            commit.date_string(),
+            commit.message(),
+            self.notes_section(&sha1)?
+        ));
+        let parent = match parents.first() {
+            Some(parent) => self.read_commit(parent)?,
+            None => Commit::new(),
+        };
+        out.push_str(&self.diff_trees(&parent.blobs, &commit.blobs, &DiffOptions::default())?);
+        Ok(out)
+    }
+
+    /// Unified diff of every file added, deleted or modified between two trees. With
+    /// `find_copies` an added file whose content is at least `COPY_SIMILARITY` percent the
+    /// same as a file of the old tree is shown as a copy of it, diffed against it:
+    /// ```text
+    /// similarity index 80%
+    /// copy from src/a.rs
+    /// copy to src/b.rs
+    /// --- a/src/a.rs
+    /// +++ b/src/b.rs
+    /// ```
+    fn diff_trees(
+        &self,
+        old: &BTreeMap<String, Entry>,
+        new: &BTreeMap<String, Entry>,
+        options: &DiffOptions,
+    ) -> Result<String, GitError> {
+        let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        let attributes = &mut self.attributes();
+        let palette = &Palette::new(options.color);
+        let mut out = String::new();
+        for path in paths {
+            let (old_blob, new_blob) = (old.get(path), new.get(path));
+            if old_blob == new_blob {
+                continue;
+            }
+            let new_content = new_blob.map(|b| self.read_blob(&b.sha1)).transpose()?;
+            if let (None, Some(content)) = (old_blob, &new_content) {
+                if options.find_copies {
+                    if let Some((source, similarity)) =
+                        self.copy_source(attributes, old, path, content)?
+                    {
+                        out.push_str(&format!(
+                            "similarity index {}%\ncopy from {}\ncopy to {}\n",
+                            similarity, source, path
+                        ));
+                        out.push_str(&Self::file_diff(
+                            attributes,
+                            palette,
+                            (source, path),
+                            Some(&self.read_blob(&old[source].sha1)?),
+                            Some(content),
+                        )?);
+                        continue;
+                    }
+                }
+            }
+            let old_content = old_blob.map(|b| self.read_blob(&b.sha1)).transpose()?;
+            out.push_str(&Self::mode_change(path, old_blob, new_blob));
+            out.push_str(&Self::file_diff(
+                attributes,
+                palette,
+                (path, path),
+                old_content.as_deref(),
+                new_content.as_deref(),
+            )?);
+        }
+        Ok(out)
+    }
+
+    /// diffstat of the files added, deleted or modified between two trees, see `diff::stat`
+    fn diff_stat(
+        &self,
+        old: &BTreeMap<String, Entry>,
+        new: &BTreeMap<String, Entry>,
+    ) -> Result<String, GitError> {
+        let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        let attributes = &mut self.attributes();
+        let mut files = vec![];
+        for path in paths {
+            let (old_blob, new_blob) = (old.get(path), new.get(path));
+            if old_blob == new_blob {
+                continue;
+            }
+            let content = |blob: Option<&Entry>| -> Result<Vec<u8>, GitError> {
+                blob.map_or(Ok(vec![]), |blob| self.read_blob(&blob.sha1))
+            };
+            let (old_content, new_content) = (content(old_blob)?, content(new_blob)?);
+            let binary = attributes.is_binary(path)?
+                || diff::is_binary(&old_content)
+                || diff::is_binary(&new_content);
+            let lines = match binary {
+                true => None,
+                // neither side is binary, so both are UTF-8
+                false => Some(diff::line_counts(&diff::diff_lines(
+                    std::str::from_utf8(&old_content).unwrap_or_default(),
+                    std::str::from_utf8(&new_content).unwrap_or_default(),
+                ))),
+            };
+            files.push((path.clone(), lines));
+        }
+        Ok(diff::stat(&files))
+    }
+
+    /// The file of the tree an added file was most likely copied from and their similarity:
+    /// a file with the same blob, or else the most similar text file when it reaches
+    /// `COPY_SIMILARITY`
+    fn copy_source<'a>(
+        &self,
+        attributes: &mut Attributes,
+        tree: &'a BTreeMap<String, Entry>,
+        path: &str,
+        content: &[u8],
+    ) -> Result<Option<(&'a str, usize)>, GitError> {
+        let sha1 = utils::crypto_bytes(content);
+        if let Some((source, _)) = tree.iter().find(|(_, entry)| entry.sha1 == sha1) {
+            return Ok(Some((source, 100)));
+        }
+        let text = match std::str::from_utf8(content) {
+            Ok(text) if !attributes.is_binary(path)? && !diff::is_binary(content) => text,
+            _ => return Ok(None),
+        };
+        let mut best = None;
+        for (source, entry) in tree.iter() {
+            if entry.is_symlink() || attributes.is_binary(source)? {
+                continue;
+            }
+            let source_content = self.read_blob(&entry.sha1)?;
+            let source_text = match std::str::from_utf8(&source_content) {
+                Ok(source_text) if !diff::is_binary(&source_content) => source_text,
+                _ => continue,
+            };
+            let similarity = diff::similarity(source_text, text);
+            if similarity >= COPY_SIMILARITY && best.is_none_or(|(_, best)| similarity > best) {
+                best = Some((source.as_str(), similarity));
+            }
+        }
+        Ok(best)
+    }
+
+    /// Write every non-merge commit of a revision range, oldest first, as a patch e-mail
+    /// into `out_dir`, the working directory by default. Returns the paths written.
+    pub fn format_patch(
+        &self,
+        range: &str,
+        out_dir: Option<&str>,
+    ) -> Result<Vec<String>, GitError> {
+        let commits: Vec<(String, Commit)> = self
+            .commit_range(range)?
+            .into_iter()
+            .rev()
+            .filter(|(_, commit)| commit.parents().len() == 1)
+            .collect();
+        let out_dir = out_dir.map(Path::new).unwrap_or(Path::new(""));
+        if !commits.is_empty() {
+            fs::create_dir_all(self.cwd.join(out_dir))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        let identity = self.identity()?;
+        let mut written = vec![];
+        for (n, (sha1, commit)) in commits.iter().enumerate() {
+            let parent = self.read_commit(&commit.parent)?;
+            let from = match commit.author() {
+                "" => identity.clone(),
+                author => author.to_string(),
+            };
+            let mail = Mail {
+                commit: sha1.clone(),
+                from,
+                date_time: commit.date_time(),
+                message: commit.message().to_string(),
+                diff: self.diff_trees(&parent.blobs, &commit.blobs, &DiffOptions::default())?,
+            };
+            let subject = commit.message().lines().next().unwrap_or("");
+            let path = out_dir.join(patch::file_name(n + 1, subject));
+            fs::write(self.cwd.join(&path), mail.render(n + 1, commits.len()))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            written.push(path.display().to_string());
+        }
+        Ok(written)
+    }
+
+    /// entry of a path in the index: staged for addition, or tracked in the head commit
+    /// and not staged for removal
+    fn index_blob(&self, path: &str) -> Option<&Entry> {
+        if self.staging_area.deleted.contains_key(path) {
+            return None;
+        }
+        self.staging_area
+            .staged
+            .get(path)
+            .or_else(|| self.commit.blobs.get(path))
+    }
+
+    /// New content of every patched path, None for a deleted file, patching the index
+    /// version of the files when `cached` is set and the working directory version otherwise.
+    /// Fails without side effects when any file patch does not apply.
+    fn patched_files(
+        &self,
+        patches: &[FilePatch],
+        cached: bool,
+    ) -> Result<Vec<(String, Option<String>)>, GitError> {
+        let filter = &mut self.eol_filter()?;
+        let mut files = vec![];
+        for file_patch in patches {
+            let path = file_patch.path();
+            if !Path::new(path)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)))
+            {
+                return Err(GitError::PatchError(format!("invalid path {}", path)));
+            }
+            let old = if cached {
+                self.index_blob(path).map(|b| self.read_blob(&b.sha1))
+            } else {
+                self.clean_file(filter, path)
+                    .ok()
+                    .map(|(content, _)| Ok(content))
+            };
+            let old = old
+                .transpose()?
+                .map(String::from_utf8)
+                .transpose()
+                .map_err(|_| GitError::PatchError(format!("{} is a binary file", path)))?;
+            if old.is_some() && file_patch.old_path.is_none() {
+                return Err(GitError::PatchError(format!("{} already exists", path)));
+            }
+            files.push((
+                path.to_string(),
+                patch::apply_hunks(old.as_deref(), file_patch)?,
+            ));
+        }
+        Ok(files)
+    }
+
+    /// write patched files into the working directory, deleting the ones set to None
+    fn write_patched_files(&self, files: &[(String, Option<String>)]) -> Result<(), GitError> {
+        let filter = &mut self.eol_filter()?;
+        for (path, content) in files {
+            let file = self.cwd.join(path);
+            match content {
+                Some(content) => {
+                    if let Some(dir) = file.parent() {
+                        fs::create_dir_all(dir)
+                            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                    }
+                    fs::write(
+                        file,
+                        filter
+                            .conversion(path)?
+                            .to_worktree(content.as_bytes())
+                            .as_ref(),
+                    )
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                }
+                None if entry::exists(&file) => {
+                    fs::remove_file(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// stage patched files, the ones set to None for removal
+    fn stage_patched_files(&mut self, files: &[(String, Option<String>)]) -> Result<(), GitError> {
+        for (path, content) in files {
+            match content {
+                Some(content) => {
+                    let entry = Entry::new(self.store_blob_content(content.as_bytes())?);
+                    self.staging_area.deleted.remove(path);
+                    if self.commit.blobs.get(path) == Some(&entry) {
+                        self.staging_area.staged.remove(path);
+                    } else {
+                        self.staging_area.add(path.clone(), entry);
+                    }
+                }
+                None => {
+                    self.staging_area.staged.remove(path);
+                    if self.commit.blobs.contains_key(path) {
+                        self.staging_area
+                            .deleted
+                            .insert(path.clone(), "".to_string());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply a unified diff, or the diff of a patch e-mail, to the working directory, or only
+    /// to the index when `cached` is set. Nothing is changed unless every file patch applies.
+    /// Returns the patched paths.
+    pub fn apply(&mut self, patch_path: &str, cached: bool) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        let text = fs::read_to_string(self.cwd.join(patch_path))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let patches = patch::parse_diff(&text)?;
+        if patches.is_empty() {
+            return Err(GitError::PatchError(format!(
+                "no patch found in {}",
+                patch_path
+            )));
+        }
+        let files = self.patched_files(&patches, cached)?;
+        if cached {
+            self.stage_patched_files(&files)?;
+            self.persist_basic_info()?;
+        } else {
+            self.write_patched_files(&files)?;
+        }
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Apply patch e-mails written by `format-patch` in order, each to the index and the
+    /// working directory, and commit it with the message and date of the e-mail.
+    /// Stops at the first patch that does not apply, the commits made so far are kept.
+    /// Returns the subject of every applied patch.
+    pub fn am(&mut self, mail_paths: &[String]) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        let mut applied = vec![];
+        for mail_path in mail_paths {
+            if !self.staging_area.is_empty() {
+                return Err(GitError::PatchError(
+                    "You have uncommitted changes.".to_string(),
+                ));
+            }
+            let content = fs::read_to_string(self.cwd.join(mail_path))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let mail = patch::parse_mail(&content)?;
+            let subject = mail.message.lines().next().unwrap_or("").to_string();
+            let failed =
+                |e: GitError| GitError::PatchError(format!("patch {} failed: {}", mail_path, e));
+            let files = self
+                .patched_files(&patch::parse_diff(&mail.diff)?, true)
+                .map_err(failed)?;
+            let filter = &mut self.eol_filter()?;
+            for (path, _) in files.iter() {
+                let local = entry::exists(&self.cwd.join(path))
+                    .then(|| self.hash_file(filter, path))
+                    .transpose()?;
+                if local.as_ref() != self.commit.blobs.get(path) {
+                    return Err(failed(GitError::PatchError(format!(
+                        "Your local changes to {} would be overwritten.",
+                        path
+                    ))));
+                }
+            }
+            self.stage_patched_files(&files)?;
+            self.persist_basic_info()?;
+            self.write_patched_files(&files)?;
+            let options = CommitOptions {
+                author: Some(mail.from.clone()),
+                sign: false,
+                no_verify: true,
+            };
+            self.commit_at(&mail.message, &[], mail.date_time, &options)?;
+            applied.push(subject);
+        }
+        Ok(applied)
+    }
+
+    /// current branch name, e.g. main
+    pub fn current_branch(&self) -> String {
+        self.branch
+            .strip_prefix(&format!("{}/", HEADS_DIR))
+            .unwrap_or(&self.branch)
+            .to_string()
+    }
+
+    /// One line per branch, `* ` before the current one, and with `all` one per
+    /// remote-tracking branch after them, as `remotes/<remote>/<branch>`.
+    pub fn branch_list(&mut self, all: bool) -> Result<Vec<String>, GitError> {
+        self.load_branch()?;
+        let current = self.current_branch();
+        let mut lines: Vec<String> = self
+            .branches()?
+            .into_keys()
+            .map(|name| match name == current {
+                true => format!("* {}", name),
+                false => format!("  {}", name),
+            })
+            .collect();
+        if all {
+            lines.extend(
+                self.remote_branches()?
+                    .into_keys()
+                    .map(|name| format!("  remotes/{}", name)),
+            );
+        }
+        Ok(lines)
+    }
+
+    /// list all branches as branch name --> commit sha1 pairs
+    pub fn branches(&mut self) -> Result<BTreeMap<String, String>, GitError> {
+        self.load_branch()?;
+        let mut branches = BTreeMap::new();
+        for entry in
+            fs::read_dir(&self.heads_path).map_err(|e| GitError::BranchError(format!("{:?}", e)))?
+        {
+            let entry = entry.map_err(|e| GitError::BranchError(format!("{:?}", e)))?;
+            let sha1 = fs::read_to_string(entry.path())
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            branches.insert(entry.file_name().to_string_lossy().to_string(), sha1);
+        }
+        Ok(branches)
+    }
+
+    /// names of the objects in an object store directory, those in fan-out directories
+    /// included, empty when it does not exist
+    fn object_ids(dir: &Path) -> Result<Vec<String>, GitError> {
+        let mut ids = vec![];
+        if !dir.is_dir() {
+            return Ok(ids);
+        }
+        for entry in fs::read_dir(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))? {
+            let entry = entry.map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            if path.is_file() {
+                ids.push(name);
+            } else if Self::is_fan_out_dir(&name) {
+                for entry in
+                    fs::read_dir(&path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                {
+                    let entry = entry.map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                    if entry.path().is_file() {
+                        ids.push(format!("{}{}", name, entry.file_name().to_string_lossy()));
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn is_fan_out_dir(name: &str) -> bool {
+        name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit())
+    }
+
+    /// Path of a loose object in an object store directory: `ab/cdef...` in the fan-out
+    /// directory named after the first two characters of its sha1, or `abcdef...` for an
+    /// object written before the fan-out layout and not migrated yet, see `gc`. An id that
+    /// is not a sha1, e.g. one read from a bundle, is an error and never joined to the path
+    fn object_path(dir: &Path, sha1: &str) -> Result<PathBuf, GitError> {
+        if !utils::is_sha1(sha1) {
+            return Err(GitError::FileNotExistError(sha1.to_string()));
+        }
+        let flat = dir.join(sha1);
+        match flat.is_file() {
+            true => Ok(flat),
+            false => Ok(dir.join(&sha1[..2]).join(&sha1[2..])),
+        }
+    }
+
+    /// create the fan-out directory of an object file about to be written
+    fn create_object_dir(path: &Path) -> Result<(), GitError> {
+        match path.parent() {
+            Some(dir) => {
+                fs::create_dir_all(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn blob_file(&self, sha1: &str) -> Result<PathBuf, GitError> {
+        Self::object_path(&self.blobs_path, sha1)
+    }
+
+    fn commit_file(&self, sha1: &str) -> Result<PathBuf, GitError> {
+        Self::object_path(&self.commits_path, sha1)
+    }
+
+    /// Move the blobs and commits of the flat layout into fan-out directories, returns how
+    /// many were moved
+    fn migrate_object_layout(&self) -> Result<usize, GitError> {
+        let mut moved = 0;
+        for dir in [&self.blobs_path, &self.commits_path] {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))? {
+                let path = entry
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                    .path();
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if !path.is_file() || name.len() <= 2 || !name.is_char_boundary(2) {
+                    continue;
+                }
+                let target = dir.join(&name[..2]).join(&name[2..]);
+                Self::create_object_dir(&target)?;
+                fs::rename(&path, &target)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                moved += 1;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// sha1 of every commit in the object store
+    fn commit_ids(&self) -> Result<Vec<String>, GitError> {
+        Self::object_ids(&self.commits_path)
+    }
+
+    /// resolve HEAD, a branch or tag name, a commit sha1 or a unique prefix of one, followed
+    /// by any number of `~n` and `^n` ancestry steps, into a commit sha1
+    pub fn resolve_commit(&self, rev: &str) -> Result<String, GitError> {
+        let (base, steps) = revision::split_ancestry(rev)?;
+        if !steps.is_empty() {
+            let parent = |sha1: &str, n: usize| -> Result<String, GitError> {
+                let commit = self.read_commit(sha1)?;
+                let parent = commit.parents().get(n - 1).map(|p| p.to_string());
+                parent.ok_or_else(|| GitError::RevisionError(format!("{}: no such ancestor", rev)))
+            };
+            let mut sha1 = self.resolve_commit(base)?;
+            for step in steps {
+                match step {
+                    Ancestry::Parent(0) => {}
+                    Ancestry::Parent(n) => sha1 = parent(&sha1, n)?,
+                    Ancestry::Ancestor(n) => {
+                        for _ in 0..n {
+                            sha1 = parent(&sha1, 1)?;
+                        }
+                    }
+                }
+            }
+            return Ok(sha1);
+        }
+        if let Some(file) = self.remote_branch_file(rev) {
+            return fs::read_to_string(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)));
+        }
+        revision::check_name(rev)?;
+        if rev == HEAD_FILE {
+            let branch = fs::read_to_string(&self.head_file)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            return fs::read_to_string(self.repo_path.join(branch))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)));
+        }
+        let branch_file = self.heads_path.join(rev);
+        let tag_file = self.tag_refs_path.join(rev);
+        if branch_file.is_file() {
+            fs::read_to_string(branch_file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+        } else if tag_file.is_file() {
+            self.peel_tag(rev)
+        } else if self.commit_file(rev).is_ok_and(|file| file.is_file()) {
+            Ok(rev.to_string())
+        } else {
+            let ids = self.commit_ids()?;
+            revision::expand_prefix(rev, ids.iter().map(|id| id.as_str()))?
+                .ok_or_else(|| GitError::RevisionError(format!("unknown revision {}", rev)))
+        }
+    }
+
+    /// shortest prefix of a commit sha1, at least `min_len` long, naming no other commit
+    pub fn abbreviate(&self, sha1: &str, min_len: usize) -> Result<String, GitError> {
+        let ids = self.commit_ids()?;
+        Ok(revision::abbreviate(
+            sha1,
+            ids.iter().map(|id| id.as_str()),
+            min_len,
+        ))
+    }
+
+    /// annotated tag object of the tag ref `name`, None for a lightweight tag
+    pub fn read_tag(&self, name: &str) -> Result<Option<Tag>, GitError> {
+        let tag_file = self.tag_refs_path.join(name);
+        if !tag_file.is_file() {
+            return Err(GitError::TagError(format!("tag {} does not exist", name)));
+        }
+        let sha1 =
+            fs::read_to_string(tag_file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if !self.tags_path.join(&sha1).is_file() {
+            return Ok(None);
+        }
+        self.read_tag_object(&sha1).map(Some)
+    }
+
+    /// annotated tag object with the given sha1
+    fn read_tag_object(&self, sha1: &str) -> Result<Tag, GitError> {
+        let content = fs::read_to_string(self.tags_path.join(sha1))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        serde_json::from_str(&content).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// commit sha1 a tag ref points at, following annotated tag objects
+    fn peel_tag(&self, name: &str) -> Result<String, GitError> {
+        match self.read_tag(name)? {
+            Some(tag) => Ok(tag.object),
+            None => fs::read_to_string(self.tag_refs_path.join(name))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e))),
+        }
+    }
+
+    /// `Name <email>` from the user.name and user.email config, the user name defaults to $USER
+    fn identity(&self) -> Result<String, GitError> {
+        let config = self.config()?;
+        let name = match config.get("user.name") {
+            Some(name) => name.to_string(),
+            None => env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+        };
+        Ok(match config.get("user.email") {
+            Some(email) => format!("{} <{}>", name, email),
+            None => name,
+        })
+    }
+
+    /// list all tags as tag name --> commit sha1 pairs
+    pub fn tags(&self) -> Result<BTreeMap<String, String>, GitError> {
+        let mut tags = BTreeMap::new();
+        if !self.tag_refs_path.exists() {
+            return Ok(tags);
+        }
+        for entry in
+            fs::read_dir(&self.tag_refs_path).map_err(|e| GitError::TagError(format!("{:?}", e)))?
+        {
+            let entry = entry.map_err(|e| GitError::TagError(format!("{:?}", e)))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let sha1 = self.peel_tag(&name)?;
+            tags.insert(name, sha1);
+        }
+        Ok(tags)
+    }
+
+    /// create tag `name` pointing at the commit `rev` resolves to.
+    /// with a message an annotated tag object is stored under tags/,
+    /// otherwise the ref points at the commit directly
+    pub fn create_tag(
+        &self,
+        name: &str,
+        rev: &str,
+        message: Option<&str>,
+    ) -> Result<String, GitError> {
+        if revision::check_name(name).is_err() || name == HEAD_FILE {
+            return Err(GitError::TagError(format!("invalid tag name {}", name)));
+        }
+        let tag_file = self.tag_refs_path.join(name);
+        if tag_file.exists() {
+            return Err(GitError::TagError(format!("tag {} already exists", name)));
+        }
+        let commit_sha1 = self.resolve_commit(rev)?;
+        Self::init_repo_dir(&self.tag_refs_path)?;
+        let target = match message {
+            Some(message) => {
+                let tag = Tag {
+                    object: commit_sha1.clone(),
+                    name: name.to_string(),
+                    message: message.to_string(),
+                    date_time: Utc::now().timestamp(),
+                    tagger: self.identity()?,
+                };
+                let sha1 = utils::sha1(&tag)?;
+                Self::init_repo_dir(&self.tags_path)?;
+                Self::persist(&tag, &self.tags_path.join(&sha1))?;
+                sha1
+            }
+            None => commit_sha1.clone(),
+        };
+        fs::write(tag_file, target).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Ok(commit_sha1)
+    }
+
+    /// Name a commit, HEAD by default, after the nearest tag reachable from it: the tag itself
+    /// when it points at the commit, `<tag>-<N>-g<short sha1>` otherwise, where N is the
+    /// number of commits reachable from the commit but not from the tag
+    pub fn describe(&self, rev: Option<&str>) -> Result<String, GitError> {
+        let sha1 = self.resolve_commit(rev.unwrap_or(HEAD_FILE))?;
+        let ancestors = self.ancestors(&sha1)?;
+        let mut nearest: Option<(usize, String)> = None;
+        for (name, target) in self.tags()? {
+            if !ancestors.contains(&target) {
+                continue;
+            }
+            // every ancestor of the tag is an ancestor of the commit too
+            let since = ancestors.len() - self.ancestors(&target)?.len();
+            if nearest.as_ref().is_none_or(|(n, _)| since < *n) {
+                nearest = Some((since, name));
+            }
+        }
+        match nearest {
+            None => Err(GitError::RevisionError(
+                "No names found, cannot describe anything.".to_string(),
+            )),
+            Some((0, name)) => Ok(name),
+            Some((since, name)) => Ok(format!(
+                "{}-{}-g{}",
+                name,
+                since,
+                self.abbreviate(&sha1, revision::DEFAULT_ABBREV)?
+            )),
+        }
+    }
+
+    /// Tag HEAD with the next semantic version after the latest version tag.
+    /// Without an explicit bump level it is inferred from the conventional commit
+    /// types since that tag. Returns the release notes stored in the annotated tag.
+    pub fn release(&mut self, bump: Option<Bump>) -> Result<String, GitError> {
+        let (range, version) = match release::latest_version(&self.tags()?) {
+            Some((name, version)) => (format!("{}..{}", name, HEAD_FILE), version),
+            None => (HEAD_FILE.to_string(), Version::default()),
+        };
+        let commits = self.commit_range(&range)?;
+        if commits.is_empty() {
+            return Err(GitError::TagError(format!(
+                "nothing to release since v{}",
+                version
+            )));
+        }
+        let bump = bump.unwrap_or_else(|| Bump::infer(&commits));
+        let name = format!("v{}", version.bump(bump));
+        let notes = changelog::render(&name, &commits);
+        self.create_tag(&name, HEAD_FILE, Some(&notes))?;
+        Ok(notes)
+    }
+
+    /// Detect and fix broken repository state, returns every action taken
+    /// 1. remove stale lock files: of HEAD, the index and the config of every worktree, and
+    ///    of refs, no ref name ends in `.lock`
+    /// 2. point HEAD at an existing branch
+    /// 3. rebuild an unreadable index from HEAD's snapshot, i.e. nothing staged
+    /// 4. rewind branches pointing at missing or unreadable commits to the newest readable
+    ///    commit of their reflog, report the refs that can not be rewound
+    pub fn repair(&mut self) -> Result<Vec<String>, GitError> {
+        let mut actions = vec![];
+        let mut locks = vec![];
+        for (_, _, admin) in self.worktrees().unwrap_or_default() {
+            let admin = admin.unwrap_or_else(|| self.repo_path.clone());
+            for file in [HEAD_FILE, INDEX_FILE, CONFIG_FILE] {
+                locks.push(admin.join(format!("{}.lock", file)));
+            }
+        }
+        let mut dirs = vec![self.repo_path.join("refs")];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).into_iter().flatten() {
+                let path = entry
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                    .path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "lock") {
+                    locks.push(path);
+                }
+            }
+        }
+        for lock in locks.into_iter().filter(|lock| lock.is_file()) {
+            fs::remove_file(&lock).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            actions.push(format!("removed stale lock file {}", lock.display()));
+        }
+
+        let head = fs::read_to_string(&self.head_file).unwrap_or_default();
+        let head = head.trim();
+        if !head.starts_with(HEADS_DIR) || !self.repo_path.join(head).is_file() {
+            let mut branches: Vec<String> =
+                self.branches().unwrap_or_default().into_keys().collect();
+            let branch = if branches.is_empty() || branches.iter().any(|b| b == MAIN_BRANCH) {
+                MAIN_BRANCH.to_string()
+            } else {
+                branches.remove(0)
+            };
+            self.branch = format!("{}/{}", HEADS_DIR, branch);
+            let branch_file = self.repo_path.join(&self.branch);
+            if !branch_file.exists() {
+                fs::write(&branch_file, "")
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+            fs::write(&self.head_file, self.branch.as_bytes())
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            actions.push(format!(
+                "HEAD pointed at missing ref \"{}\", reset to {}",
+                head, self.branch
+            ));
+        }
+
+        if Self::unpersist_staging_area(&self.index_file).is_err() {
+            Self::persist(&StagingArea::new(), &self.index_file)?;
+            actions.push("index was unreadable, rebuilt it from HEAD".to_string());
+        }
+
+        for (refs, refs_path) in [
+            (HEADS_DIR, &self.heads_path),
+            (TAG_REFS_DIR, &self.tag_refs_path),
+        ] {
+            for entry in
+                fs::read_dir(refs_path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+            {
+                let path = entry
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                    .path();
+                let sha1 = fs::read_to_string(&path)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                if sha1.is_empty() || self.read_commit(&sha1).is_ok() {
+                    continue;
+                }
+                // annotated tags point at tag objects
+                if refs == TAG_REFS_DIR && self.tags_path.join(&sha1).is_file() {
+                    continue;
+                }
+                let name = format!(
+                    "{}/{}",
+                    refs,
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                );
+                let rewind = self
+                    .reflog(&name)
+                    .into_iter()
+                    .find(|old| !old.is_empty() && self.read_commit(old).is_ok());
+                match rewind {
+                    Some(old) => {
+                        self.update_ref(&name, &old)?;
+                        actions.push(format!(
+                            "{} pointed at missing commit {}, rewound it to {}",
+                            name, sha1, old
+                        ));
+                    }
+                    None => actions.push(format!(
+                        "{} points at missing commit {}, no reflog entry to rewind it to",
+                        name, sha1
+                    )),
+                }
+            }
+        }
+        Ok(actions)
+    }
+
+    /// Objects of the store nothing refers to: not reachable from a branch, a tag, a stash
+    /// entry, a reflog entry under `logs/` or what a worktree keeps, see `worktree_roots`, so
+    /// commits a branch was moved away from stay recoverable. Returns (kind, id, path)
+    /// triples, kind is commit, tag, blob or chunk.
+    fn unreachable_objects(&self) -> Result<Vec<(&'static str, String, PathBuf)>, GitError> {
+        let mut tips = vec![self.commit_sha1.clone()];
+        let mut tags = HashSet::new();
+        let mut refs = self.ref_files(&self.heads_path)?;
+        refs.extend(self.ref_files(&self.tag_refs_path)?);
+        refs.extend(self.remote_ref_files()?);
+        for target in refs.into_values() {
+            if self.tags_path.join(&target).is_file() {
+                tips.push(self.read_tag_object(&target)?.object);
+                tags.insert(target);
+            } else {
+                tips.push(target);
+            }
+        }
+        tips.extend(self.reflog_commits()?);
+        let sha1s = |tree: BTreeMap<String, Entry>| tree.into_values().map(|entry| entry.sha1);
+        let mut blobs: HashSet<String> = sha1s(self.staging_area.staged.clone()).collect();
+        for entry in self.read_stash()? {
+            tips.push(entry.base);
+            blobs.extend(
+                sha1s(entry.index)
+                    .chain(sha1s(entry.worktree))
+                    .chain(sha1s(entry.untracked)),
+            );
+        }
+        for (path, _, admin) in self.worktrees()? {
+            let worktree = Self::with_dirs(
+                &path,
+                &self.repo_path,
+                admin.as_deref().unwrap_or(&self.repo_path),
+            );
+            worktree.worktree_roots(&mut tips, &mut blobs)?;
+        }
+        let mut commits = HashSet::new();
+        for tip in tips.iter().filter(|tip| !tip.is_empty()) {
+            if !commits.contains(tip) {
+                commits.extend(self.ancestors(tip)?);
+            }
+        }
+        for sha1 in commits.iter() {
+            blobs.extend(sha1s(self.read_commit(sha1)?.blobs));
+        }
+        let mut chunks = HashSet::new();
+        for blob in blobs.iter() {
+            let manifest = self.manifests_path.join(blob);
+            if manifest.is_file() {
+                let content = fs::read_to_string(manifest)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+                let chunk_ids: Vec<String> = serde_json::from_str(&content)
+                    .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+                chunks.extend(chunk_ids);
+            }
+        }
+
+        let mut unreachable = vec![];
+        for (kind, dir, reachable) in [
+            ("commit", &self.commits_path, &commits),
+            ("tag", &self.tags_path, &tags),
+            ("blob", &self.blobs_path, &blobs),
+            ("blob", &self.manifests_path, &blobs),
+            ("chunk", &self.chunks_path, &chunks),
+        ] {
+            for id in Self::object_ids(dir)? {
+                if !reachable.contains(&id) {
+                    let path = Self::object_path(dir, &id)?;
+                    unreachable.push((kind, id, path));
+                }
+            }
+        }
+        Ok(unreachable)
+    }
+
+    /// Commits and blobs the worktree keeps alive besides the refs: its HEAD, its index and
+    /// the state of the rebase, cherry-pick, bisect or merge in progress
+    fn worktree_roots(
+        &self,
+        tips: &mut Vec<String>,
+        blobs: &mut HashSet<String>,
+    ) -> Result<(), GitError> {
+        let head = fs::read_to_string(&self.head_file).unwrap_or_default();
+        tips.extend(fs::read_to_string(self.repo_path.join(&head)).ok());
+        if self.index_file.is_file() {
+            let index = Self::unpersist_staging_area(&self.index_file)?;
+            blobs.extend(index.staged.into_values().map(|entry| entry.sha1));
+        }
+        if self.rebase_state_file.is_file() {
+            let state = self.read_rebase_state()?;
+            tips.extend([state.orig_head, state.onto]);
+            let todo = fs::read_to_string(&self.rebase_todo_file).unwrap_or_default();
+            for step in rebase::parse_todo(&todo).unwrap_or_default() {
+                tips.extend(self.resolve_commit(&step.commit).ok());
+            }
+        }
+        if self.cherry_pick_state_file.is_file() {
+            let state = self.read_cherry_pick_state()?;
+            tips.push(state.orig_head);
+            for rev in state.todo.iter() {
+                tips.extend(self.resolve_commit(rev).ok());
+            }
+        }
+        if self.bisect_file.is_file() {
+            let state = self.read_bisect_state()?;
+            tips.push(state.current);
+            tips.extend(state.bad.into_iter().chain(state.good));
+        }
+        // `merge --abort` restores the dirty files from their backups
+        if self.merge_state_file.is_file() {
+            let state = self.read_merge_state()?;
+            tips.extend([state.orig_head, state.merge_head]);
+            blobs.extend(
+                state
+                    .backups
+                    .into_values()
+                    .flatten()
+                    .map(|entry| entry.sha1),
+            );
+        }
+        Ok(())
+    }
+
+    /// Count the objects of the store and their size on disk, the unreachable ones included
+    pub fn count_objects(&mut self) -> Result<ObjectCounts, GitError> {
+        self.load_basic_info()?;
+        let file_size = |path: &Path| {
+            fs::metadata(path)
+                .map(|meta| meta.len())
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+        };
+        let mut counts = ObjectCounts::default();
+        for dir in [
+            &self.commits_path,
+            &self.tags_path,
+            &self.blobs_path,
+            &self.manifests_path,
+            &self.chunks_path,
+        ] {
+            let ids = Self::object_ids(dir)?;
+            for id in ids.iter() {
+                counts.size += file_size(&Self::object_path(dir, id)?)?;
+            }
+            if dir == &self.commits_path {
+                counts.commits = ids.len();
+            } else if dir == &self.tags_path {
+                counts.tags = ids.len();
+            } else if dir == &self.chunks_path {
+                counts.chunks = ids.len();
+            } else {
+                counts.blobs += ids.len();
+            }
+        }
+        for pack in self.packs()? {
+            counts.packs += 1;
+            counts.in_pack += pack.ids().count();
+            counts.size += file_size(pack.path())? + file_size(&pack.path().with_extension("idx"))?;
+        }
+        counts.blobs += counts.in_pack;
+        for (_, _, path) in self.unreachable_objects()? {
+            counts.unreachable += 1;
+            counts.unreachable_size += file_size(&path)?;
+        }
+        Ok(counts)
+    }
+
+    /// Verify the object store, returns every problem found, nothing when the repository is sound
+    /// 1. every blob, chunk, commit and tag object still hashes to its file name
+    /// 2. the parents and blobs of every commit and the commit of every tag object exist
+    /// 3. every branch points at a commit, every tag at a commit or a tag object
+    /// 4. dangling objects, which gc would delete
+    pub fn fsck(&mut self) -> Result<Vec<String>, GitError> {
+        // a corrupt HEAD commit is reported below
+        let loaded = self.load_basic_info().is_ok();
+        let mut problems = vec![];
+        for sha1 in Self::object_ids(&self.blobs_path)? {
+            if !utils::verify_file(&self.blob_file(&sha1)?, &sha1)? {
+                problems.push(format!("corrupt blob {}", sha1));
+            }
+        }
+        for sha1 in Self::object_ids(&self.manifests_path)? {
+            let sound = self
+                .read_blob(&sha1)
+                .is_ok_and(|content| utils::verify_bytes(&content, &sha1));
+            if !sound {
+                problems.push(format!("corrupt blob {}", sha1));
+            }
+        }
+        for sha1 in Self::object_ids(&self.chunks_path)? {
+            if !utils::verify_file(&self.chunks_path.join(&sha1), &sha1)? {
+                problems.push(format!("corrupt chunk {}", sha1));
+            }
+        }
+        for pack in self.packs()? {
+            for sha1 in pack.ids() {
+                let sound = pack
+                    .read(sha1)
+                    .is_ok_and(|content| utils::verify_bytes(&content, sha1));
+                if !sound {
+                    problems.push(format!("corrupt blob {}", sha1));
+                }
+            }
+        }
+        let has_commit = |sha1: &str| self.commit_file(sha1).is_ok_and(|file| file.is_file());
+        let shallow = self.shallow_commits()?;
+        for sha1 in self.commit_ids()? {
+            let commit = match Self::unpersist_commit(&self.commit_file(&sha1)?) {
+                Ok(commit) if utils::verify_sha1(&commit, &sha1)? => commit,
+                _ => {
+                    problems.push(format!("corrupt commit {}", sha1));
+                    continue;
+                }
+            };
+            // the parents of shallow commits were never fetched
+            let parents = match shallow.contains(&sha1) {
+                true => vec![],
+                false => commit.parents(),
+            };
+            for parent in parents.into_iter().filter(|p| !has_commit(p)) {
+                problems.push(format!(
+                    "broken link from commit {} to commit {}",
+                    sha1, parent
+                ));
+            }
+            for blob in commit
+                .blobs
+                .values()
+                .map(|entry| &entry.sha1)
+                .filter(|b| !self.has_blob(b))
+            {
+                problems.push(format!("broken link from commit {} to blob {}", sha1, blob));
+            }
+        }
+        for sha1 in Self::object_ids(&self.tags_path)? {
+            match self.read_tag_object(&sha1) {
+                Ok(tag) if utils::verify_sha1(&tag, &sha1)? => {
+                    if !has_commit(&tag.object) {
+                        problems.push(format!(
+                            "broken link from tag {} to commit {}",
+                            sha1, tag.object
+                        ));
+                    }
+                }
+                _ => problems.push(format!("corrupt tag {}", sha1)),
+            }
+        }
+        let mut refs = self.ref_files(&self.heads_path)?;
+        refs.extend(self.ref_files(&self.tag_refs_path)?);
+        refs.extend(self.remote_ref_files()?);
+        for (name, target) in refs {
+            let is_tag = name.starts_with(TAG_REFS_DIR) && self.tags_path.join(&target).is_file();
+            // a branch without commits is empty
+            if !target.is_empty() && !has_commit(&target) && !is_tag {
+                problems.push(format!("bad ref {}: {} is not a commit", name, target));
+            }
+        }
+        // broken history leaves reachability unknown
+        if let Some(Ok(unreachable)) = loaded.then(|| self.unreachable_objects()) {
+            for (kind, sha1, _) in unreachable {
+                problems.push(format!("dangling {} {}", kind, sha1));
+            }
+        }
+        Ok(problems)
+    }
+
+    /// Delete every unreachable object, or only list them when `dry_run` is set.
+    /// Returns `<kind> <sha1>` for each of them.
+    pub fn prune(&mut self, dry_run: bool) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        let mut pruned = vec![];
+        for (kind, id, path) in self.unreachable_objects()? {
+            if !dry_run {
+                fs::remove_file(&path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+            pruned.push(format!("{} {}", kind, id));
+        }
+        Ok(pruned)
+    }
+
+    /// Delete every unreachable object, e.g. the blobs of file versions that were added but
+    /// never committed and commits replaced by amend or rebase. Blobs and commits left in the
+    /// flat layout of older repositories are moved into fan-out directories first.
+    pub fn gc(&mut self) -> Result<String, GitError> {
+        self.migrate_object_layout()?;
+        let pruned = self.prune(false)?;
+        let count = |kind: &str| {
+            pruned
+                .iter()
+                .filter(|line| line.split(' ').next() == Some(kind))
+                .count()
+        };
+        Ok(format!(
+            "Removed {} unreachable objects: {} commits, {} tags, {} blobs, {} chunks.",
+            pruned.len(),
+            count("commit"),
+            count("tag"),
+            count("blob"),
+            count("chunk")
+        ))
+    }
+
+    /// Write every branch and tag, with the commits, trees and blobs they reach, in Git's
+    /// object format into `.git-rs/git`, a bare repository stock git can read, e.g.
+    /// `git --git-dir=.git-rs/git cat-file -p HEAD`. Objects exported before are skipped.
+    /// Returns (ref, Git object id) pairs.
+    pub fn export_git(&mut self) -> Result<Vec<(String, String)>, GitError> {
+        let git_dir = self.repo_path.join(GIT_EXPORT_DIR);
+        for dir in ["objects", HEADS_DIR, TAG_REFS_DIR] {
+            Self::init_repo_dir(&git_dir.join(dir))?;
+        }
+        let store = ObjectStore::new(&git_dir.join("objects"));
+        let map_file = git_dir.join(GIT_EXPORT_MAP_FILE);
+        let mut map: BTreeMap<String, String> = match fs::read_to_string(&map_file) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?,
+            Err(_) => BTreeMap::new(),
+        };
+
+        let mut refs = vec![];
+        for (name, sha1) in self.branches()? {
+            refs.push((format!("{}/{}", HEADS_DIR, name), sha1));
+        }
+        for (name, sha1) in self.tags()? {
+            refs.push((format!("{}/{}", TAG_REFS_DIR, name), sha1));
+        }
+        let mut exported = vec![];
+        for (name, sha1) in refs {
+            // a branch without commits has nothing to export
+            if sha1.is_empty() {
+                continue;
+            }
+            let id = self.export_git_commit(&store, &mut map, &sha1)?;
+            fs::write(git_dir.join(&name), format!("{}\n", id))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            exported.push((name, id));
+        }
+        fs::write(git_dir.join(HEAD_FILE), format!("ref: {}\n", self.branch))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Self::persist(&map, &map_file)?;
+        Ok(exported)
+    }
+
+    /// export a commit and every ancestor not exported yet, parents first
+    fn export_git_commit(
+        &self,
+        store: &ObjectStore,
+        map: &mut BTreeMap<String, String>,
+        sha1: &str,
+    ) -> Result<String, GitError> {
+        let mut stack = vec![sha1.to_string()];
+        while let Some(sha1) = stack.last().cloned() {
+            if map.get(&sha1).is_some_and(|id| store.contains(id)) {
+                stack.pop();
+                continue;
+            }
+            let commit = self.read_commit(&sha1)?;
+            let parents = commit.parents();
+            let missing: Vec<String> = parents
+                .iter()
+                .filter(|parent| !map.get(**parent).is_some_and(|id| store.contains(id)))
+                .map(|parent| parent.to_string())
+                .collect();
+            if !missing.is_empty() {
+                stack.extend(missing);
+                continue;
+            }
+            stack.pop();
+
+            let files: Vec<(&str, &Entry)> = commit
+                .blobs
+                .iter()
+                .map(|(path, entry)| (path.as_str(), entry))
+                .collect();
+            let mut content = format!("tree {}\n", self.export_git_tree(store, map, &files)?);
+            for parent in parents {
+                content.push_str(&format!("parent {}\n", map[parent]));
+            }
+            // Git needs an email, even an empty one
+            let person = |name: &str| match name {
+                "" => "unknown <unknown>".to_string(),
+                name if !name.contains('<') => format!("{} <>", name),
+                name => name.to_string(),
+            };
+            let committer = person(commit.committer());
+            let author = match commit.author() {
+                "" => committer.clone(),
+                author => person(author),
+            };
+            let date = format!("{} +0000", commit.date_time());
+            content.push_str(&format!("author {} {}\n", author, date));
+            content.push_str(&format!("committer {} {}\n\n", committer, date));
+            content.push_str(commit.message());
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+            let id = store.write(ObjectKind::Commit, content.as_bytes())?;
+            map.insert(sha1, id);
+        }
+        Ok(map[sha1].clone())
+    }
+
+    /// export the tree of the files, paths relative to it, and the blobs it holds
+    fn export_git_tree(
+        &self,
+        store: &ObjectStore,
+        map: &mut BTreeMap<String, String>,
+        files: &[(&str, &Entry)],
+    ) -> Result<String, GitError> {
+        let mut entries = vec![];
+        let mut dirs: BTreeMap<&str, Vec<(&str, &Entry)>> = BTreeMap::new();
+        for (path, entry) in files.iter() {
+            if let Some((dir, rest)) = path.split_once('/') {
+                dirs.entry(dir).or_default().push((rest, entry));
+                continue;
+            }
+            let id = match map.get(&entry.sha1) {
+                Some(id) if store.contains(id) => id.clone(),
+                _ => {
+                    let id = store.write(ObjectKind::Blob, &self.read_blob(&entry.sha1)?)?;
+                    map.insert(entry.sha1.clone(), id.clone());
+                    id
+                }
+            };
+            entries.push(TreeEntry {
+                name: path.to_string(),
+                mode: Some(entry.mode),
+                id,
+            });
+        }
+        for (dir, files) in dirs {
+            entries.push(TreeEntry {
+                name: dir.to_string(),
+                mode: None,
+                id: self.export_git_tree(store, map, &files)?,
+            });
+        }
+        store.write(ObjectKind::Tree, &gitobject::tree_content(&entries)?)
+    }
+
+    /// Import a fast-import stream, e.g. from `git fast-export --all`: store its blobs,
+    /// commits and annotated tags and point its branches and tags at them. Commits on a
+    /// branch without `from` continue the branch. The working directory and the index are
+    /// left alone. Returns a summary of what was imported.
+    pub fn fast_import(&mut self, input: &[u8]) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let commands = fastimport::parse(input)?;
+        // mark --> blob or commit sha1, ref --> content of its file
+        let mut marks: HashMap<String, String> = HashMap::new();
+        let mut refs: BTreeMap<String, String> = BTreeMap::new();
+        let (mut blobs, mut commits, mut tags) = (0, 0, 0);
+        for command in commands {
+            match command {
+                fastimport::Command::Blob { mark, data } => {
+                    let sha1 = self.store_blob_content(&data)?;
+                    if let Some(mark) = mark {
+                        marks.insert(mark, sha1);
+                    }
+                    blobs += 1;
+                }
+                fastimport::Command::Reset { reference, from } => {
+                    let target = match from {
+                        Some(from) => self.import_commit(&from, &marks, &refs)?,
+                        None => String::new(),
+                    };
+                    refs.insert(reference, target);
+                }
+                fastimport::Command::Commit {
+                    reference,
+                    mark,
+                    author,
+                    committer,
+                    message,
+                    from,
+                    merges,
+                    ops,
+                } => {
+                    let parent = match from {
+                        Some(from) => self.import_commit(&from, &marks, &refs)?,
+                        None => match refs.get(&reference) {
+                            Some(target) => target.clone(),
+                            None => self
+                                .import_commit(&reference, &marks, &refs)
+                                .unwrap_or_default(),
+                        },
+                    };
+                    let mut files = match parent.as_str() {
+                        "" => BTreeMap::new(),
+                        parent => self.read_commit(parent)?.blobs,
+                    };
+                    for op in ops {
+                        self.import_file_op(&mut files, op, &marks)?;
+                    }
+                    let mut merge_parents = vec![];
+                    for merge in merges {
+                        merge_parents.push(self.import_commit(&merge, &marks, &refs)?);
+                    }
+                    let commit = Commit {
+                        meta: CommitMeta {
+                            message: String::from_utf8_lossy(&message)
+                                .trim_end_matches('\n')
+                                .to_string(),
+                            date_time: committer.date_time,
+                            author: author.map(|author| author.name).unwrap_or_default(),
+                            committer: committer.name,
+                        },
+                        blobs: files,
+                        parent,
+                        merge_parents,
+                        signature: String::new(),
+                    };
+                    let sha1 = utils::sha1(&commit)?;
+                    self.persist_object(&commit, &self.commit_file(&sha1)?)?;
+                    if let Some(mark) = mark {
+                        marks.insert(mark, sha1.clone());
+                    }
+                    refs.insert(reference, sha1);
+                    commits += 1;
+                }
+                fastimport::Command::Tag {
+                    name,
+                    from,
+                    tagger,
+                    message,
+                } => {
+                    let tagger = tagger.unwrap_or(fastimport::Ident {
+                        name: String::new(),
+                        date_time: 0,
+                    });
+                    let tag = Tag {
+                        object: self.import_commit(&from, &marks, &refs)?,
+                        name: name.clone(),
+                        message: String::from_utf8_lossy(&message)
+                            .trim_end_matches('\n')
+                            .to_string(),
+                        date_time: tagger.date_time,
+                        tagger: tagger.name,
+                    };
+                    let sha1 = utils::sha1(&tag)?;
+                    Self::init_repo_dir(&self.tags_path)?;
+                    Self::persist(&tag, &self.tags_path.join(&sha1))?;
+                    refs.insert(format!("{}/{}", TAG_REFS_DIR, name), sha1);
+                    tags += 1;
+                }
+            }
+        }
+
+        // refs are only written once every object is there
+        self.write_refs(&refs)?;
+        Ok(format!(
+            "Imported {} blobs, {} commits and {} tags, updated {} refs.",
+            blobs,
+            commits,
+            tags,
+            refs.len()
+        ))
+    }
+
+    /// Point branches and tags, `refs/heads/<name>` and `refs/tags/<name>`, at the commits
+    /// or tags; nothing is written when one of the refs is not supported
+    fn write_refs(&self, refs: &BTreeMap<String, String>) -> Result<(), GitError> {
+        let mut files = vec![];
+        for (reference, target) in refs.iter() {
+            let file = self
+                .ref_file_name(reference)
+                .map(|(dir, name)| dir.join(name))
+                .ok_or_else(|| GitError::ImportError(format!("unsupported ref {}", reference)))?;
+            files.push((file, target));
+        }
+        Self::init_repo_dir(&self.tag_refs_path)?;
+        for (file, target) in files {
+            fs::write(file, target).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// directory and file name of a branch or tag ref, None for other refs and for names
+    /// with a '/'
+    fn ref_file_name<'a>(&self, reference: &'a str) -> Option<(&PathBuf, &'a str)> {
+        [
+            (HEADS_DIR, &self.heads_path),
+            (TAG_REFS_DIR, &self.tag_refs_path),
+        ]
+        .into_iter()
+        .find_map(|(dir, path)| {
+            let name = reference.strip_prefix(dir)?.strip_prefix('/')?;
+            let valid = !name.is_empty() && !name.contains('/') && name != HEAD_FILE;
+            valid.then_some((path, name))
+        })
+    }
+
+    /// Import the history of a Git repository, its work tree or its Git directory: every
+    /// branch and tag with the commits, files and annotated tags they reach, from loose
+    /// objects and packs. Branches and tags with a '/' in their name are skipped with a
+    /// warning. The working directory and the index are left alone. Returns a summary of
+    /// what was imported.
+    pub fn import_git(&mut self, path: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let path = Path::new(path);
+        let git_dir = match path.join(".git") {
+            dir if dir.is_dir() => dir,
+            _ => path.to_path_buf(),
+        };
+        if !git_dir.join("objects").is_dir() {
+            return Err(GitError::ImportError(format!(
+                "{} is not a git repository",
+                path.display()
+            )));
+        }
+        let db = ObjectDatabase::open(&git_dir.join("objects"))?;
+        // Git object id --> blob or commit sha1
+        let mut map: HashMap<String, String> = HashMap::new();
+        let mut refs = BTreeMap::new();
+        let (mut blobs, mut tags) = (0, 0);
+        for (reference, id) in gitobject::read_refs(&git_dir)? {
+            if ![HEADS_DIR, TAG_REFS_DIR]
+                .iter()
+                .any(|dir| reference.starts_with(&format!("{}/", dir)))
+            {
+                continue;
+            }
+            if self.ref_file_name(&reference).is_none() {
+                self.warnings
+                    .push(format!("skip {}: unsupported ref name", reference));
+                continue;
+            }
+            let (kind, content) = db.read(&id)?;
+            let target = match kind {
+                ObjectKind::Commit => self.import_git_commit(&db, &mut map, &id, &mut blobs)?,
+                ObjectKind::Tag => {
+                    let object = gitobject::parse_tag(&id, &content)?;
+                    if object.kind != ObjectKind::Commit.name() {
+                        self.warnings
+                            .push(format!("skip {}: tag of a {}", reference, object.kind));
+                        continue;
+                    }
+                    let tag = Tag {
+                        object: self.import_git_commit(
+                            &db,
+                            &mut map,
+                            &object.object,
+                            &mut blobs,
+                        )?,
+                        name: object.name,
+                        message: object.message.trim_end_matches('\n').to_string(),
+                        date_time: object.date_time,
+                        tagger: object.tagger,
+                    };
+                    let sha1 = utils::sha1(&tag)?;
+                    Self::init_repo_dir(&self.tags_path)?;
+                    Self::persist(&tag, &self.tags_path.join(&sha1))?;
+                    tags += 1;
+                    sha1
+                }
+                kind => {
+                    self.warnings
+                        .push(format!("skip {}: ref to a {}", reference, kind.name()));
+                    continue;
+                }
+            };
+            refs.insert(reference, target);
+        }
+        self.write_refs(&refs)?;
+        let commits = map.len() - blobs;
+        Ok(format!(
+            "Imported {} blobs, {} commits and {} tags, updated {} refs.",
+            blobs,
+            commits,
+            tags,
+            refs.len()
+        ))
+    }
+
+    /// import a Git commit and every ancestor not imported yet, parents first, returns its
+    /// sha1; `blobs` counts the blobs imported
+    fn import_git_commit(
+        &self,
+        db: &ObjectDatabase,
+        map: &mut HashMap<String, String>,
+        id: &str,
+        blobs: &mut usize,
+    ) -> Result<String, GitError> {
+        let mut stack = vec![id.to_string()];
+        while let Some(id) = stack.last().cloned() {
+            if map.contains_key(&id) {
+                stack.pop();
+                continue;
+            }
+            let (kind, content) = db.read(&id)?;
+            if kind != ObjectKind::Commit {
+                return Err(GitError::ImportError(format!("{} is not a commit", id)));
+            }
+            let object = gitobject::parse_commit(&id, &content)?;
+            let missing: Vec<String> = object
+                .parents
+                .iter()
+                .filter(|parent| !map.contains_key(*parent))
+                .cloned()
+                .collect();
+            if !missing.is_empty() {
+                stack.extend(missing);
+                continue;
+            }
+            stack.pop();
+
+            let mut files = BTreeMap::new();
+            self.import_git_tree(db, map, &object.tree, "", &mut files, blobs)?;
+            let mut parents = object.parents.iter().map(|parent| map[parent].clone());
+            let commit = Commit {
+                meta: CommitMeta {
+                    message: object.message.trim_end_matches('\n').to_string(),
+                    date_time: object.date_time,
+                    author: object.author,
+                    committer: object.committer,
+                },
+                blobs: files,
+                parent: parents.next().unwrap_or_default(),
+                merge_parents: parents.collect(),
+                signature: String::new(),
+            };
+            let sha1 = utils::sha1(&commit)?;
+            let file = self.commit_file(&sha1)?;
+            if !file.exists() {
+                self.persist_object(&commit, &file)?;
+            }
+            map.insert(id, sha1);
+        }
+        Ok(map[id].clone())
+    }
+
+    /// add the files of a Git tree to `files`, their paths under the prefix
+    fn import_git_tree(
+        &self,
+        db: &ObjectDatabase,
+        map: &mut HashMap<String, String>,
+        id: &str,
+        prefix: &str,
+        files: &mut BTreeMap<String, Entry>,
+        blobs: &mut usize,
+    ) -> Result<(), GitError> {
+        let (_, content) = db.read(id)?;
+        for entry in gitobject::parse_tree(id, &content)? {
+            let path = format!("{}{}", prefix, entry.name);
+            let Some(mode) = entry.mode else {
+                self.import_git_tree(db, map, &entry.id, &format!("{}/", path), files, blobs)?;
+                continue;
+            };
+            let sha1 = match map.get(&entry.id) {
+                Some(sha1) => sha1.clone(),
+                None => {
+                    let (_, content) = db.read(&entry.id)?;
+                    let sha1 = self.store_blob_content(&content)?;
+                    map.insert(entry.id, sha1.clone());
+                    *blobs += 1;
+                    sha1
+                }
+            };
+            files.insert(path, Entry::with_mode(sha1, mode));
+        }
+        Ok(())
+    }
+
+    /// commit sha1 of a fast-import commit reference: a mark, a ref of the stream or a
+    /// revision of the repository
+    fn import_commit(
+        &self,
+        reference: &str,
+        marks: &HashMap<String, String>,
+        refs: &BTreeMap<String, String>,
+    ) -> Result<String, GitError> {
+        if let Some(sha1) = marks.get(reference).or_else(|| refs.get(reference)) {
+            return Ok(sha1.clone());
+        }
+        let name = [HEADS_DIR, TAG_REFS_DIR]
+            .iter()
+            .find_map(|dir| reference.strip_prefix(dir)?.strip_prefix('/'))
+            .unwrap_or(reference);
+        self.resolve_commit(name)
+            .map_err(|_| GitError::ImportError(format!("unknown commit {}", reference)))
+    }
+
+    /// apply a file change of a fast-import commit to its files, renames, copies and deletes
+    /// of a directory apply to every file under it
+    fn import_file_op(
+        &self,
+        files: &mut BTreeMap<String, Entry>,
+        op: FileOp,
+        marks: &HashMap<String, String>,
+    ) -> Result<(), GitError> {
+        let under = |path: &str, dir: &str| {
+            path == dir
+                || path
+                    .strip_prefix(dir)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+        match op {
+            FileOp::Modify { mode, data, path } => {
+                let sha1 = match data {
+                    DataRef::Inline(data) => self.store_blob_content(&data)?,
+                    DataRef::Ref(reference) => match marks.get(&reference) {
+                        Some(sha1) => sha1.clone(),
+                        None if self.has_blob(&reference) => reference,
+                        None => {
+                            return Err(GitError::ImportError(format!(
+                                "unknown blob {}",
+                                reference
+                            )))
+                        }
+                    },
+                };
+                files.insert(path, Entry::with_mode(sha1, mode));
+            }
+            FileOp::Delete(path) => files.retain(|file, _| !under(file, &path)),
+            FileOp::Rename(ref from, ref to) | FileOp::Copy(ref from, ref to) => {
+                let moved: Vec<(String, Entry)> = files
+                    .iter()
+                    .filter(|(file, _)| under(file, from))
+                    .map(|(file, entry)| (format!("{}{}", to, &file[from.len()..]), entry.clone()))
+                    .collect();
+                if moved.is_empty() {
+                    return Err(GitError::ImportError(format!("no file {}", from)));
+                }
+                if matches!(op, FileOp::Rename(..)) {
+                    files.retain(|file, _| !under(file, from));
+                }
+                files.extend(moved);
+            }
+            FileOp::DeleteAll => files.clear(),
+        }
+        Ok(())
+    }
+
+    /// Roll every loose blob and the blobs of the existing packs into one new pack that
+    /// replaces them. Each version of a file is stored as a delta against the version
+    /// committed before it when that saves space, see `PackBuilder::add`; chunked blobs
+    /// keep their chunks.
+    pub fn repack(&mut self) -> Result<String, GitError> {
+        let old_packs = self.packs()?;
+        let loose = Self::object_ids(&self.blobs_path)?;
+        let mut ids: BTreeSet<String> = loose.iter().cloned().collect();
+        for pack in old_packs.iter() {
+            ids.extend(pack.ids().cloned());
+        }
+        if ids.is_empty() {
+            return Ok("Nothing to pack.".to_string());
+        }
+
+        // blobs in the order their paths got them, each after the version it replaced
+        let mut commits = vec![];
+        for sha1 in self.commit_ids()? {
+            commits.push(self.read_commit(&sha1)?);
+        }
+        commits.sort_by_key(|commit| commit.meta.date_time);
+        let mut order = vec![];
+        let mut bases = HashMap::new();
+        let mut latest: HashMap<&String, &String> = HashMap::new();
+        for commit in commits.iter() {
+            for (path, entry) in commit.blobs.iter() {
+                if !ids.contains(&entry.sha1) {
+                    continue;
+                }
+                if !bases.contains_key(&entry.sha1) {
+                    order.push(entry.sha1.clone());
+                    bases.insert(entry.sha1.clone(), latest.get(path).map(|s| s.to_string()));
+                }
+                latest.insert(path, &entry.sha1);
+            }
+        }
+        order.extend(ids.iter().filter(|id| !bases.contains_key(*id)).cloned());
+
+        let mut builder = PackBuilder::new();
+        let mut deltas = 0;
+        for sha1 in order.iter() {
+            let content = self.read_blob(sha1)?;
+            let base = match bases.get(sha1) {
+                Some(Some(base)) if builder.contains(base) => Some((base, self.read_blob(base)?)),
+                _ => None,
+            };
+            let base = base
+                .as_ref()
+                .map(|(sha1, content)| (sha1.as_str(), content.as_slice()));
+            if builder.add(sha1, &content, base)? {
+                deltas += 1;
+            }
+        }
+        let path = builder.write(&self.packs_path)?;
+        for sha1 in loose.iter() {
+            fs::remove_file(self.blob_file(sha1)?)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        for pack in old_packs.iter().filter(|pack| pack.path() != path) {
+            for file in [pack.path().with_extension("idx"), pack.path().to_path_buf()] {
+                fs::remove_file(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+        }
+        Ok(format!(
+            "Packed {} blobs into {}, {} as deltas.",
+            order.len(),
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            deltas
+        ))
+    }
+
+    /// commit graph reachable from every branch and tag in Graphviz DOT format
+    pub fn graph_dot(&mut self) -> Result<String, GitError> {
+        let refs = self.ref_labels()?;
+        let mut commits = BTreeMap::new();
+        for sha1 in refs.values() {
+            for sha1 in self.ancestors(sha1)? {
+                let commit = self.read_commit(&sha1)?;
+                commits.insert(sha1, commit);
+            }
+        }
+        Ok(graph::render_dot(&commits, &refs))
+    }
+
+    /// every branch, remote-tracking branch and tag as the label log shows for it, e.g.
+    /// `main`, `origin/main` or `tag: v1`, --> commit sha1 pairs
+    fn ref_labels(&mut self) -> Result<BTreeMap<String, String>, GitError> {
+        let mut refs = self.branches()?;
+        refs.extend(self.remote_branches()?);
+        for (name, sha1) in self.tags()? {
+            refs.insert(format!("tag: {}", name), sha1);
+        }
+        Ok(refs)
+    }
+
+    /// commit sha1 --> ` (HEAD -> main, origin/main, tag: v1)` style list of the refs
+    /// pointing at it
+    fn decorations(&mut self) -> Result<HashMap<String, String>, GitError> {
+        let current = self.current_branch();
+        let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+        for (label, sha1) in self.ref_labels()? {
+            let entry = labels.entry(sha1).or_default();
+            // HEAD comes first
+            match label == current {
+                true => entry.insert(0, format!("{} -> {}", HEAD_FILE, label)),
+                false => entry.push(label),
+            }
+        }
+        Ok(labels
+            .into_iter()
+            .map(|(sha1, labels)| (sha1, format!(" ({})", labels.join(", "))))
+            .collect())
+    }
+
+    /// load the commit object with the given sha1
+    /// The commit, without parents when it is a shallow commit: history ends there as
+    /// only the commit is stored.
+    pub fn read_commit(&self, sha1: &str) -> Result<Commit, GitError> {
+        let mut commit = Self::unpersist_commit(&self.commit_file(sha1)?)?;
+        if self.shallow_file.is_file() && self.shallow_commits()?.contains(sha1) {
+            commit.parent.clear();
+            commit.merge_parents.clear();
+        }
+        Ok(commit)
+    }
+
+    /// commits whose parents a shallow clone or fetch left out
+    fn shallow_commits(&self) -> Result<BTreeSet<String>, GitError> {
+        match fs::read_to_string(&self.shallow_file) {
+            Ok(content) => Ok(content.lines().map(|line| line.to_string()).collect()),
+            Err(_) => Ok(BTreeSet::new()),
+        }
+    }
+
+    fn write_shallow_commits(&self, shallow: &BTreeSet<String>) -> Result<(), GitError> {
+        if shallow.is_empty() {
+            if self.shallow_file.is_file() {
+                fs::remove_file(&self.shallow_file)
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+            return Ok(());
+        }
+        let content: String = shallow.iter().map(|sha1| format!("{}\n", sha1)).collect();
+        fs::write(&self.shallow_file, content)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// commits reachable from the given commit following parents, newest first
+    pub fn history(&self, sha1: &str) -> Result<Vec<(String, Commit)>, GitError> {
+        let mut commits = vec![];
+        let mut sha1 = sha1.to_string();
+        while !sha1.is_empty() {
+            let commit = self.read_commit(&sha1)?;
+            let parent = commit.parent.clone();
+            commits.push((sha1, commit));
+            sha1 = parent;
+        }
+        Ok(commits)
+    }
+
+    /// Commits of a revision range, newest first:
+    /// - `from..to`: reachable from `to` but not from `from`, an empty `from` means the whole
+    ///   history
+    /// - `a...b`: reachable from either `a` or `b` but not from both
+    ///
+    /// An empty `to`, `a` or `b` means HEAD. A single revision without dots is the history of
+    /// that revision following first parents.
+    pub fn commit_range(&self, range: &str) -> Result<Vec<(String, Commit)>, GitError> {
+        let rev = |rev: &str| self.resolve_commit(if rev.is_empty() { HEAD_FILE } else { rev });
+        if let Some((a, b)) = range.split_once("...") {
+            let (a, b) = (rev(a)?, rev(b)?);
+            let theirs = self.ancestors(&b)?;
+            let common = self
+                .ancestors(&a)?
+                .into_iter()
+                .filter(|sha1| theirs.contains(sha1))
+                .collect();
+            return self.reachable(&[a, b], &common);
+        }
+        match range.split_once("..") {
+            Some((from, to)) => {
+                let excluded = match from.is_empty() {
+                    true => HashSet::new(),
+                    false => self.ancestors(&rev(from)?)?,
+                };
+                self.reachable(&[rev(to)?], &excluded)
+            }
+            None => self.history(&rev(range)?),
+        }
+    }
+
+    /// Commits reachable from the tips without going through an excluded commit. Newest
+    /// first, but never before one of its children.
+    fn reachable(
+        &self,
+        tips: &[String],
+        excluded: &HashSet<String>,
+    ) -> Result<Vec<(String, Commit)>, GitError> {
+        // the order commits are found in breaks ties between equal dates
+        let mut found: HashMap<String, (usize, Commit)> = HashMap::new();
+        let mut queue: VecDeque<String> = tips.iter().cloned().collect();
+        while let Some(sha1) = queue.pop_front() {
+            if sha1.is_empty() || excluded.contains(&sha1) || found.contains_key(&sha1) {
+                continue;
+            }
+            let commit = self.read_commit(&sha1)?;
+            queue.extend(commit.parents().into_iter().map(|p| p.to_string()));
+            found.insert(sha1, (found.len(), commit));
+        }
+        let mut children: HashMap<&str, usize> = HashMap::new();
+        for (_, commit) in found.values() {
+            for parent in commit.parents() {
+                if found.contains_key(parent) {
+                    *children.entry(parent).or_default() += 1;
+                }
+            }
+        }
+        let mut ready: BinaryHeap<(i64, Reverse<usize>, &str)> = found
+            .iter()
+            .filter(|(sha1, _)| !children.contains_key(sha1.as_str()))
+            .map(|(sha1, (seq, commit))| (commit.date_time(), Reverse(*seq), sha1.as_str()))
+            .collect();
+        let mut order = vec![];
+        while let Some((_, _, sha1)) = ready.pop() {
+            order.push(sha1.to_string());
+            for parent in found[sha1].1.parents() {
+                let Some(count) = children.get_mut(parent) else {
+                    continue;
+                };
+                *count -= 1;
+                if *count == 0 {
+                    let (seq, commit) = &found[parent];
+                    ready.push((commit.date_time(), Reverse(*seq), parent));
+                }
+            }
+        }
+        Ok(order
+            .into_iter()
+            .filter_map(|sha1| found.remove(&sha1).map(|(_, commit)| (sha1, commit)))
+            .collect())
+    }
+
+    /// Ends of a range to diff: `from` and `to` of `from..to`, or the merge base of `a` and
+    /// `b` and `b` of `a...b`. An empty end means HEAD.
+    pub fn range_ends(&self, range: &str) -> Result<(String, String), GitError> {
+        let rev = |rev: &str| self.resolve_commit(if rev.is_empty() { HEAD_FILE } else { rev });
+        if let Some((a, b)) = range.split_once("...") {
+            let (a, b) = (rev(a)?, rev(b)?);
+            let base = self.merge_base(&a, &b)?.ok_or_else(|| {
+                GitError::CommitError(format!("{} and {} have no common ancestor", a, b))
+            })?;
+            return Ok((base, b));
+        }
+        match range.split_once("..") {
+            Some((from, to)) => Ok((rev(from)?, rev(to)?)),
+            None => Err(GitError::CommitError(format!("{} is not a range", range))),
+        }
+    }
+
+    /// commits in the history of `sha1` that changed `path`, newest first, together with
+    /// the blob sha1 of the path in that commit (None when the commit deleted it).
+    /// only the blob hash of the path is compared between a commit and its parent
+    pub fn file_history(
+        &self,
+        sha1: &str,
+        path: &str,
+    ) -> Result<Vec<(String, Commit, Option<String>)>, GitError> {
+        let history = self.history(sha1)?;
+        let mut changes = vec![];
+        for (i, (sha1, commit)) in history.iter().enumerate() {
+            let blob = commit.blobs.get(path);
+            let parent_blob = history.get(i + 1).and_then(|(_, p)| p.blobs.get(path));
+            if blob != parent_blob {
+                let blob = blob.map(|entry| entry.sha1.clone());
+                changes.push((sha1.clone(), commit.clone(), blob));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Commits in the history of `sha1` that changed the file at `path`, newest first, with
+    /// the path of the file in each. A commit adding the file and removing a file of its
+    /// parent with the same content, or `COPY_SIMILARITY` percent the same, renamed it: the
+    /// older commits are searched for changes of the removed path.
+    pub fn follow_history(
+        &self,
+        sha1: &str,
+        path: &str,
+    ) -> Result<Vec<(String, Commit, String)>, GitError> {
+        let history = self.history(sha1)?;
+        let attributes = &mut self.attributes();
+        let mut path = path.to_string();
+        let mut changes = vec![];
+        for (i, (sha1, commit)) in history.iter().enumerate() {
+            let parent = history.get(i + 1).map(|(_, p)| &p.blobs);
+            let blob = commit.blobs.get(&path);
+            let parent_blob = parent.and_then(|blobs| blobs.get(&path));
+            if blob == parent_blob {
+                continue;
+            }
+            changes.push((sha1.clone(), commit.clone(), path.clone()));
+            let (Some(entry), None, Some(parent)) = (blob, parent_blob, parent) else {
+                continue;
+            };
+            let removed: BTreeMap<String, Entry> = parent
+                .iter()
+                .filter(|(path, _)| !commit.blobs.contains_key(*path))
+                .map(|(path, entry)| (path.clone(), entry.clone()))
+                .collect();
+            let content = self.read_blob(&entry.sha1)?;
+            if let Some((source, _)) = self.copy_source(attributes, &removed, &path, &content)? {
+                path = source.to_string();
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Search the files of the head commit, or of the given commit, for lines matching the
+    /// regex. Returns `path:line_no:line` for every matching line, ordered by path and line.
+    pub fn grep(&self, pattern: &str, rev: Option<&str>) -> Result<Vec<String>, GitError> {
+        let regex = Regex::new(pattern).map_err(|e| GitError::GrepError(format!("{}", e)))?;
+        let commit = self.read_commit(&self.resolve_commit(rev.unwrap_or(HEAD_FILE))?)?;
+        let mut matches = vec![];
+        for (path, entry) in commit.blobs.iter() {
+            let content = self.read_blob(&entry.sha1)?;
+            // binary files have no lines to match
+            let content = match std::str::from_utf8(&content) {
+                Ok(content) if !diff::is_binary(content.as_bytes()) => content,
+                _ => continue,
+            };
+            for (no, line) in content.lines().enumerate() {
+                if regex.is_match(line) {
+                    matches.push(format!("{}:{}:{}", path, no + 1, line));
+                }
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Annotate every line of the file in the head commit with the id, date and subject
+    /// of the commit that introduced it
+    pub fn blame(&mut self, path: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let changes = self.file_history(&self.commit_sha1, path)?;
+        // versions back to the last time the file was created
+        let mut contents = vec![];
+        for (_, _, blob) in changes.iter() {
+            match blob {
+                Some(blob) => contents.push(self.read_blob_text(blob)?),
+                None => break,
+            }
+        }
+        if contents.is_empty() {
+            return Err(GitError::FileNotExistError(format!(
+                "{} is not tracked in the head commit",
+                path
+            )));
+        }
+        let versions: Vec<&str> = contents.iter().map(|c| c.as_str()).collect();
+        let lines: Vec<(&str, &Commit, &str)> = versions[0]
+            .lines()
+            .zip(blame::attribute(&versions))
+            .map(|(line, i)| (changes[i].0.as_str(), &changes[i].1, line))
+            .collect();
+        Ok(blame::render(&lines))
+    }
+
+    /// Displays the commits of the current branch that changed the given file
+    /// and the blob id of the file after each of them
+    pub fn file_log(&mut self, path: &str) -> Result<String, GitError> {
+        self.load_basic_info()?;
+        let changes = self.file_history(&self.commit_sha1, path)?;
+        if changes.is_empty() {
+            return Err(GitError::FileNotExistError(format!(
+                "{} has no history",
+                path
+            )));
+        }
+        let msg: Vec<String> = changes
+            .iter()
+            .map(|(_, commit, blob)| {
+                format!(
+                    "{}blob {}\n",
+                    commit,
+                    blob.as_deref().unwrap_or("(deleted)")
+                )
+            })
+            .collect();
+        Ok(msg.join("\n"))
+    }
+
+    /// Type of an object: `blob`, `commit` or `tag` for a sha1 in the object store,
+    /// `commit` for any other revision naming a commit
+    pub fn object_type(&self, object: &str) -> Result<&'static str, GitError> {
+        if !object.is_empty() && object.chars().all(|c| c.is_ascii_hexdigit()) {
+            if self.has_blob(object) {
+                return Ok("blob");
+            }
+            if self.tags_path.join(object).is_file() {
+                return Ok("tag");
+            }
+        }
+        self.resolve_commit(object)
+            .map(|_| "commit")
+            .map_err(|_| GitError::ObjectError(format!("Not a valid object name {}", object)))
+    }
+
+    /// Raw contents of a blob, or the pretty-printed structure of a commit or tag object
+    /// followed by a newline
+    pub fn cat_file(&self, object: &str) -> Result<String, GitError> {
+        let content = match self.object_type(object)? {
+            "blob" => return Ok(String::from_utf8_lossy(&self.read_blob(object)?).into_owned()),
+            "tag" => serde_json::to_string_pretty(&self.read_tag_object(object)?),
+            _ => serde_json::to_string_pretty(&self.read_commit(&self.resolve_commit(object)?)?),
+        };
+        content
+            .map(|content| content + "\n")
+            .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+
+    /// ref name, e.g. `refs/heads/main` --> content pairs of the ref files in a refs directory
+    /// remote-tracking branches, `refs/remotes/<remote>/<branch>` --> commit
+    /// remote-tracking branches as `<remote>/<branch>` --> commit sha1 pairs
+    pub fn remote_branches(&self) -> Result<BTreeMap<String, String>, GitError> {
+        let prefix = format!("{}/", REMOTE_REFS_DIR);
+        Ok(self
+            .remote_ref_files()?
+            .into_iter()
+            .filter_map(|(name, sha1)| Some((name.strip_prefix(&prefix)?.to_string(), sha1)))
+            .collect())
+    }
+
+    /// ref file of the remote-tracking branch `<remote>/<branch>`, None when there is none
+    fn remote_branch_file(&self, name: &str) -> Option<PathBuf> {
+        let (remote, branch) = name.split_once('/')?;
+        revision::check_name(remote).ok()?;
+        revision::check_name(branch).ok()?;
+        let file = self
+            .repo_path
+            .join(REMOTE_REFS_DIR)
+            .join(remote)
+            .join(branch);
+        file.is_file().then_some(file)
+    }
+
+    fn remote_ref_files(&self) -> Result<BTreeMap<String, String>, GitError> {
+        let mut refs = BTreeMap::new();
+        if let Ok(remotes) = fs::read_dir(self.repo_path.join(REMOTE_REFS_DIR)) {
+            for remote in remotes.filter_map(|remote| remote.ok()) {
+                refs.extend(self.ref_files(&remote.path())?);
+            }
+        }
+        Ok(refs)
+    }
+
+    fn ref_files(&self, dir: &Path) -> Result<BTreeMap<String, String>, GitError> {
+        let mut refs = BTreeMap::new();
+        if !dir.is_dir() {
+            return Ok(refs);
+        }
+        for entry in fs::read_dir(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))? {
+            let path = entry
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                .path();
+            let name = path
+                .strip_prefix(&self.repo_path)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                .display()
+                .to_string();
+            let target =
+                fs::read_to_string(&path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            refs.insert(name, target);
+        }
+        Ok(refs)
+    }
+
+    /// whether the blob is in the object store, whole, chunked or packed
+    fn has_blob(&self, sha1: &str) -> bool {
+        self.blob_file(sha1).is_ok_and(|file| file.is_file())
+            || self.manifests_path.join(sha1).is_file()
+            || self
+                .packs()
+                .is_ok_and(|packs| packs.iter().any(|pack| pack.contains(sha1)))
+    }
+
+    /// every pack of the object store
+    fn packs(&self) -> Result<Vec<Pack>, GitError> {
+        Pack::open_dir(&self.packs_path)
+    }
+
+    /// store a blob with the given content unless it is already stored, returns its sha1
+    fn store_blob_content(&self, content: &[u8]) -> Result<String, GitError> {
+        let hash = utils::crypto_bytes(content);
+        if !self.has_blob(&hash) {
+            let path = self.blob_file(&hash)?;
+            Self::create_object_dir(&path)?;
+            fs::write(path, self.compression()?.encode(content))
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        Ok(hash)
+    }
+
+    /// Pack the given branches and tags, every branch and tag by default, and every commit,
+    /// tag object and blob reachable from them into a bundle file
+    pub fn bundle_create(&self, path: &str, names: &[String]) -> Result<String, GitError> {
+        let mut all = self.ref_files(&self.heads_path)?;
+        all.extend(self.ref_files(&self.tag_refs_path)?);
+        let refs = if names.is_empty() {
+            all
+        } else {
+            names
+                .iter()
+                .map(|name| {
+                    [HEADS_DIR, TAG_REFS_DIR]
+                        .iter()
+                        .map(|dir| format!("{}/{}", dir, name))
+                        .find_map(|r| all.get(&r).map(|target| (r, target.clone())))
+                        .ok_or_else(|| GitError::BundleError(format!("unknown ref {}", name)))
+                })
+                .collect::<Result<_, _>>()?
+        };
+        let mut bundle = Bundle {
+            refs,
+            ..Default::default()
+        };
+        let mut tips = vec![];
+        for target in bundle.refs.values() {
+            if self.tags_path.join(target).is_file() {
+                let tag = self.read_tag_object(target)?;
+                tips.push(tag.object.clone());
+                bundle.tags.insert(target.clone(), tag);
+            } else {
+                tips.push(target.clone());
+            }
+        }
+        for tip in tips {
+            for sha1 in self.ancestors(&tip)? {
+                if bundle.commits.contains_key(&sha1) {
+                    continue;
+                }
+                let commit = self.read_commit(&sha1)?;
+                for blob in commit.blobs.values().map(|entry| &entry.sha1) {
+                    if !bundle.has_blob(blob) {
+                        bundle.add_blob(blob, self.read_blob(blob)?);
+                    }
+                }
+                bundle.commits.insert(sha1, commit);
+            }
+        }
+        fs::write(self.cwd.join(path), bundle.to_file_content()?)
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Ok(format!(
+            "Bundled {} refs, {} commits and {} blobs.",
+            bundle.refs.len(),
+            bundle.commits.len(),
+            bundle.blob_contents().count()
+        ))
+    }
+
+    /// check that every object of a bundle matches its sha1 and that every object it refers
+    /// to is in the bundle or already in the repository; ids that are not sha1s are refused
+    /// before any of them is looked up in the object store
+    fn verify_bundle(&self, bundle: &Bundle) -> Result<(), GitError> {
+        if let Some(id) = bundle.object_ids().find(|id| !utils::is_sha1(id)) {
+            return Err(GitError::BundleError(format!("invalid object id {:?}", id)));
+        }
+        let has_commit = |sha1: &str| {
+            bundle.commits.contains_key(sha1)
+                || self.commit_file(sha1).is_ok_and(|file| file.is_file())
+        };
+        let corrupt = |kind: &str, sha1: &str| {
+            Err(GitError::BundleError(format!(
+                "{} {} is corrupt",
+                kind, sha1
+            )))
+        };
+        let missing = |kind: &str, sha1: &str| {
+            Err(GitError::BundleError(format!("missing {} {}", kind, sha1)))
+        };
+        for (sha1, content) in bundle.blob_contents() {
+            if utils::crypto_bytes(content) != *sha1 {
+                return corrupt("blob", sha1);
+            }
+        }
+        for (sha1, commit) in bundle.commits.iter() {
+            if utils::sha1(commit)? != *sha1 {
+                return corrupt("commit", sha1);
+            }
+            if let Some(parent) = commit.parents().into_iter().find(|p| !has_commit(p)) {
+                return missing("commit", parent);
+            }
+            if let Some(blob) = commit
+                .blobs
+                .values()
+                .map(|entry| &entry.sha1)
+                .find(|b| !bundle.has_blob(b) && !self.has_blob(b))
+            {
+                return missing("blob", blob);
+            }
+        }
+        for (sha1, tag) in bundle.tags.iter() {
+            if utils::sha1(tag)? != *sha1 {
+                return corrupt("tag", sha1);
+            }
+            if !has_commit(&tag.object) {
+                return missing("commit", &tag.object);
+            }
+        }
+        match bundle
+            .refs
+            .values()
+            .find(|target| !has_commit(target) && !bundle.tags.contains_key(*target))
+        {
+            Some(target) => missing("commit", target),
+            None => Ok(()),
+        }
+    }
+
+    /// Import a bundle file, see `receive`.
+    pub fn unbundle(&mut self, path: &str) -> Result<Vec<String>, GitError> {
+        let content = fs::read_to_string(self.cwd.join(path))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        self.receive(&Bundle::from_file_content(&content)?)
+    }
+
+    /// Store the objects of a bundle, then create its branches and tags, see `receive_with`.
+    pub fn receive(&mut self, bundle: &Bundle) -> Result<Vec<String>, GitError> {
+        self.receive_with(bundle, &ReceiveOptions::default())
+    }
+
+    /// Store the objects of a bundle, then create its branches and tags.
+    /// Existing branches are only moved forward unless forced by the options, never the
+    /// ones checked out in a worktree, and existing tags are kept. Returns one line per ref
+    /// of the bundle.
+    pub fn receive_with(
+        &mut self,
+        bundle: &Bundle,
+        options: &ReceiveOptions,
+    ) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        self.verify_bundle(bundle)?;
+        for (_, content) in bundle.blob_contents() {
+            self.store_blob_content(content)?;
+        }
+        for (sha1, commit) in bundle.commits.iter() {
+            if !self.commit_file(sha1)?.is_file() {
+                self.persist_object(commit, &self.commit_file(sha1)?)?;
+            }
+        }
+        if !bundle.tags.is_empty() {
+            Self::init_repo_dir(&self.tags_path)?;
+        }
+        for (sha1, tag) in bundle.tags.iter() {
+            Self::persist(tag, &self.tags_path.join(sha1))?;
+        }
+
+        // branches checked out in any worktree are never moved under it
+        let checked_out: HashSet<String> = self
+            .worktrees()?
+            .into_iter()
+            .map(|(_, head, _)| head)
+            .chain([self.branch.clone()])
+            .collect();
+        let mut report = vec![];
+        for (name, target) in bundle.refs.iter() {
+            let short = |dir: &str| name.strip_prefix(dir)?.strip_prefix('/');
+            let (kind, short_name) = match (short(HEADS_DIR), short(TAG_REFS_DIR)) {
+                (Some(branch), _) => ("branch", branch),
+                (_, Some(tag)) => ("tag", tag),
+                _ => ("", name.as_str()),
+            };
+            if kind.is_empty() || revision::check_name(short_name).is_err() {
+                report.push(format!("! [rejected] {} (invalid ref)", name));
+                continue;
+            }
+            let ref_file = self.repo_path.join(name);
+            let old = fs::read_to_string(&ref_file).ok();
+            if let Some(lease) = &options.lease {
+                if old.as_deref().unwrap_or_default() != lease {
+                    report.push(format!("! [rejected] {} (stale info)", short_name));
+                    continue;
+                }
+            }
+            let old = match old {
+                Some(old) => old,
+                None => {
+                    Self::init_repo_dir(&self.tag_refs_path)?;
+                    self.update_ref(name, target)?;
+                    report.push(format!("* [new {}] {}", kind, short_name));
+                    continue;
+                }
+            };
+            let line = if old == *target {
+                format!("= [up to date] {}", short_name)
+            } else if kind == "tag" {
+                format!("! [rejected] {} (already exists)", short_name)
+            } else if checked_out.contains(name) {
+                format!("! [rejected] {} (checked out)", short_name)
+            } else if self.ancestors(target)?.contains(&old) {
+                self.update_ref(name, target)?;
+                format!("  {}..{} {}", &old[..7], &target[..7], short_name)
+            } else if options.force || options.lease.is_some() {
+                self.update_ref(name, target)?;
+                format!(
+                    "+ {}...{} {} (forced update)",
+                    &old[..7.min(old.len())],
+                    &target[..7],
+                    short_name
+                )
+            } else {
+                format!("! [rejected] {} (non-fast-forward)", short_name)
+            };
+            report.push(line);
+        }
+        Ok(report)
+    }
+
+    /// repository at a remote url, the path of its work tree or its .git-rs directory
+    fn open_remote(&self, url: &str) -> Result<GitRepository, GitError> {
+        Self::open_dir(&self.cwd.join(url))
+            .map_err(|_| GitError::RemoteError(format!("{} is not a git-rs repository", url)))
+    }
+
+    /// repository at the path of its work tree or its .git-rs directory
+    pub fn open_dir(path: &Path) -> Result<GitRepository, GitError> {
+        let git_dir = match path.join(GIT_DIR) {
+            dir if dir.exists() => dir,
+            _ => path.to_path_buf(),
+        };
+        let repo = Self::at(path, &git_dir);
+        if !repo.head_file.is_file() || !repo.heads_path.is_dir() {
+            return Err(GitError::RemoteError(format!(
+                "{} is not a git-rs repository",
+                path.display()
+            )));
+        }
+        Ok(repo)
+    }
+
+    /// transport to the repository at a remote url, a network url or a path, see `open_remote`
+    fn open_transport(&self, url: &str) -> Result<Box<dyn Transport>, GitError> {
+        match transport::is_network(url) {
+            true => Ok(Box::new(NetworkTransport::new(url))),
+            false => Ok(Box::new(self.open_remote(url)?)),
+        }
+    }
+
+    /// commits this repository has with their whole history, none in a shallow repository
+    fn complete_commits(&self) -> Result<Vec<String>, GitError> {
+        match self.shallow_file.is_file() {
+            true => Ok(vec![]),
+            false => self.commit_ids(),
+        }
+    }
+
+    /// Copy the commits reachable from `tip`, the `depth` latest generations of them when
+    /// given, and the blobs they use from another repository, stopping at commits this one
+    /// has with their whole history. `from_shallow` are the shallow commits of the other
+    /// repository. Copied commits whose parents are left behind are recorded as shallow,
+    /// shallow commits whose parents get copied are not any more. Returns the number of
+    /// commits copied.
+    fn copy_history(
+        &self,
+        from: &mut dyn Transport,
+        tip: &str,
+        depth: Option<usize>,
+        from_shallow: &BTreeSet<String>,
+    ) -> Result<usize, GitError> {
+        let mut shallow = self.shallow_commits()?;
+        let mut missing = vec![];
+        let mut seen = HashSet::new();
+        // breadth first, so every commit is reached at its lowest generation
+        let mut pending = VecDeque::from([(tip.to_string(), 1)]);
+        while let Some((sha1, generation)) = pending.pop_front() {
+            if sha1.is_empty() || !seen.insert(sha1.clone()) {
+                continue;
+            }
+            let have = self.commit_file(&sha1)?.is_file();
+            // without shallow commits, history below a commit this repository has is complete
+            if have && shallow.is_empty() {
+                continue;
+            }
+            let commit = match have {
+                true => Self::unpersist_commit(&self.commit_file(&sha1)?)?,
+                false => from.commit_object(&sha1)?,
+            };
+            if utils::sha1(&commit)? != sha1 {
+                return Err(GitError::RemoteError(format!("commit {} is corrupt", sha1)));
+            }
+            let parents = commit.parents();
+            if have && !shallow.contains(&sha1) {
+                pending.extend(parents.into_iter().map(|p| (p.to_string(), generation + 1)));
+                continue;
+            }
+            if depth.is_some_and(|depth| generation >= depth) || from_shallow.contains(&sha1) {
+                if !parents.is_empty() {
+                    shallow.insert(sha1.clone());
+                }
+            } else {
+                shallow.remove(&sha1);
+                pending.extend(parents.into_iter().map(|p| (p.to_string(), generation + 1)));
+            }
+            if !have {
+                missing.push((sha1, commit));
+            }
+        }
+        for (_, commit) in missing.iter() {
+            for blob in commit.blobs.values().map(|entry| &entry.sha1) {
+                if !self.has_blob(blob)
+                    && self.store_blob_content(&from.blob_content(blob)?)? != *blob
+                {
+                    return Err(GitError::RemoteError(format!("blob {} is corrupt", blob)));
+                }
+            }
+        }
+        // parents are found after their children, written before them
+        for (sha1, commit) in missing.iter().rev() {
+            self.persist_object(commit, &self.commit_file(sha1)?)?;
+        }
+        self.write_shallow_commits(&shallow)?;
+        Ok(missing.len())
+    }
+
+    /// The commits, tags and blobs a fetch asks for, see `UploadRequest`: what `copy_history`
+    /// on the other side reads. Blobs of the commits it has are left out.
+    pub fn upload(&self, request: &UploadRequest) -> Result<Bundle, GitError> {
+        let shallow = self.shallow_commits()?;
+        let haves: HashSet<&String> = request
+            .haves
+            .iter()
+            .filter(|sha1| self.commit_file(sha1).is_ok_and(|file| file.is_file()))
+            .collect();
+        let mut bundle = Bundle::default();
+        let mut boundary = HashSet::new();
+        let mut pending = VecDeque::new();
+        for want in request.wants.iter() {
+            if self.tags_path.join(want).is_file() {
+                let tag = self.read_tag_object(want)?;
+                pending.push_back((tag.object.clone(), 1));
+                bundle.tags.insert(want.clone(), tag);
+            } else {
+                pending.push_back((want.clone(), 1));
+            }
+        }
+        while let Some((sha1, generation)) = pending.pop_front() {
+            if haves.contains(&sha1) {
+                boundary.insert(sha1);
+                continue;
+            }
+            if sha1.is_empty() || bundle.commits.contains_key(&sha1) {
+                continue;
+            }
+            let commit = Self::unpersist_commit(&self.commit_file(&sha1)?)?;
+            let deeper = request.depth.is_none_or(|depth| generation < depth);
+            if deeper && !shallow.contains(&sha1) {
+                pending.extend(
+                    commit
+                        .parents()
+                        .into_iter()
+                        .map(|p| (p.to_string(), generation + 1)),
+                );
+            }
+            bundle.commits.insert(sha1, commit);
+        }
+        let mut known = HashSet::new();
+        for sha1 in boundary {
+            known.extend(
+                Self::unpersist_commit(&self.commit_file(&sha1)?)?
+                    .blobs
+                    .into_values(),
+            );
+        }
+        let blobs: BTreeSet<String> = bundle
+            .commits
+            .values()
+            .flat_map(|commit| commit.blobs.values())
+            .filter(|entry| !known.contains(*entry))
+            .map(|entry| entry.sha1.clone())
+            .collect();
+        for sha1 in blobs {
+            if !bundle.has_blob(&sha1) {
+                bundle.add_blob(&sha1, self.read_blob(&sha1)?);
+            }
+        }
+        Ok(bundle)
+    }
+
+    /// Push a branch, the current one by default, to the same branch of a remote: send the
+    /// commits and blobs the remote lacks and let it move its branch forward, see `receive`.
+    /// The remote-tracking branch follows when the push is accepted. Returns the lines the
+    /// remote reports, after a "To <url>" line.
+    pub fn push(&mut self, name: &str, branch: Option<&str>) -> Result<Vec<String>, GitError> {
+        self.push_with(name, branch, &PushOptions::default())
+    }
+
+    /// `push`, overwriting the branch of the remote as the options allow
+    pub fn push_with(
+        &mut self,
+        name: &str,
+        branch: Option<&str>,
+        options: &PushOptions,
+    ) -> Result<Vec<String>, GitError> {
+        self.load_basic_info()?;
+        let url = remote::url(&self.config()?, name)?.to_string();
+        let branch = branch.map_or_else(|| self.current_branch(), |b| b.to_string());
+        revision::check_name(&branch)?;
+        let target = fs::read_to_string(self.heads_path.join(&branch))
+            .ok()
+            .filter(|target| !target.is_empty())
+            .ok_or_else(|| GitError::RemoteError(format!("no commits on branch {}", branch)))?;
+        let mut to = self.open_transport(&url)?;
+        let advertisement = to.advertise()?;
+        // whatever the remote already has is not sent again
+        let mut bundle = self.upload(&UploadRequest {
+            wants: vec![target.clone()],
+            haves: advertisement.refs.values().cloned().collect(),
+            depth: None,
+        })?;
+        let reference = format!("{}/{}", HEADS_DIR, branch);
+        bundle.refs.insert(reference, target.clone());
+        let tracking_dir = self.repo_path.join(REMOTE_REFS_DIR).join(name);
+        let receive = ReceiveOptions {
+            force: options.force,
+            // where the branch was when last fetched or pushed
+            lease: options
+                .force_with_lease
+                .then(|| fs::read_to_string(tracking_dir.join(&branch)).unwrap_or_default()),
+        };
+        let lines = to.push_bundle(&bundle, &receive)?;
+        if !lines.iter().any(|line| line.starts_with('!')) {
+            Self::init_repo_dir(&tracking_dir)?;
+            fs::write(tracking_dir.join(&branch), &target)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        let mut report = vec![format!("To {}", url)];
+        report.extend(lines);
+        Ok(report)
+    }
+
+    /// Fetch the branches of a remote: copy the commits and blobs this repository lacks and
+    /// point the remote-tracking branches, `refs/remotes/<remote>/<branch>`, at the remote's
+    /// branches. With a depth only that many commits of each branch are fetched, see
+    /// `copy_history`. Branches, the index and the working directory are left alone. Returns
+    /// one line per branch that changed.
+    pub fn fetch(&mut self, name: &str, depth: Option<usize>) -> Result<Vec<String>, GitError> {
+        if depth == Some(0) {
+            return Err(GitError::RemoteError("depth must be positive".to_string()));
+        }
+        let url = remote::url(&self.config()?, name)?.to_string();
+        let mut from = self.open_transport(&url)?;
+        let advertisement = from.advertise()?;
+        let branches = advertisement.branches();
+        from.negotiate(&UploadRequest {
+            wants: branches.values().map(|target| target.to_string()).collect(),
+            haves: self.complete_commits()?,
+            depth,
+        })?;
+        let tracking_dir = self.repo_path.join(REMOTE_REFS_DIR).join(name);
+        Self::init_repo_dir(&tracking_dir)?;
+        let mut report = vec![format!("From {}", url)];
+        for (branch, target) in branches {
+            if revision::check_name(branch).is_err() || branch == HEAD_FILE {
+                self.warnings
+                    .push(format!("skip branch {}: invalid name", branch));
+                continue;
+            }
+            self.copy_history(from.as_mut(), target, depth, &advertisement.shallow)?;
+            let tracking = format!("{}/{}", name, branch);
+            let ref_file = tracking_dir.join(branch);
+            let line = match fs::read_to_string(&ref_file) {
+                Ok(old) if old == target => continue,
+                Err(_) => format!(" * [new branch] {} -> {}", branch, tracking),
+                Ok(old) if self.ancestors(target)?.contains(&old) => {
+                    format!(
+                        "   {}..{} {} -> {}",
+                        &old[..7],
+                        &target[..7],
+                        branch,
+                        tracking
+                    )
+                }
+                Ok(old) => format!(
+                    " + {}...{} {} -> {} (forced update)",
+                    &old[..7.min(old.len())],
+                    &target[..7],
+                    branch,
+                    tracking
+                ),
+            };
+            fs::write(&ref_file, target).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            report.push(line);
+        }
+        Ok(report)
+    }
+
+    /// Clone the repository at `src`, a path or a network url, into `dst`, a new or empty
+    /// directory, by default named after the source: copy its objects, branches as
+    /// `origin/<branch>` remote-tracking branches and tags, add it as the `origin` remote and
+    /// check out its current branch. With a depth only the latest commits are copied, see
+    /// `fetch`. Returns a summary of the clone.
+    pub fn clone_repo(
+        src: &str,
+        dst: Option<&str>,
+        depth: Option<usize>,
+    ) -> Result<String, GitError> {
+        let cwd = env::current_dir().map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        remote::check_url(src)?;
+        let url = match transport::is_network(src) {
+            true => src.to_string(),
+            false => fs::canonicalize(cwd.join(src))
+                .map_err(|_| GitError::RemoteError(format!("{} is not a git-rs repository", src)))?
+                .display()
+                .to_string(),
+        };
+        let name = match dst {
+            Some(dst) => dst.to_string(),
+            // `repo` for both `repo` and `repo/.git-rs`
+            None => Path::new(&url)
+                .components()
+                .rev()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .find(|c| c != GIT_DIR && c != "." && !c.is_empty())
+                .ok_or_else(|| GitError::RemoteError(format!("cannot name a clone of {}", src)))?,
+        };
+        let work_dir = cwd.join(&name);
+        if fs::read_dir(&work_dir).is_ok_and(|mut files| files.next().is_some())
+            || work_dir.is_file()
+        {
+            return Err(GitError::RemoteError(format!(
+                "destination path {} already exists and is not an empty directory",
+                name
+            )));
+        }
+        let mut repo = GitRepository::new(&work_dir.join(GIT_DIR).display().to_string());
+        repo.cwd = work_dir;
+        let mut from = repo.open_transport(&url)?;
+        let advertisement = from.advertise()?;
+
+        let existed = repo.cwd.exists();
+        let res = repo.clone_from(from.as_mut(), &advertisement, &url, depth);
+        let (branch, tags) = match res {
+            Ok(cloned) => cloned,
+            Err(err) => {
+                // leave nothing half made behind
+                let _ = fs::remove_dir_all(if existed { &repo.repo_path } else { &repo.cwd });
+                return Err(err);
+            }
+        };
+        Ok(format!(
+            "Cloned {} into {}: {} branches, {} tags, checked out {}.",
+            src,
+            name,
+            advertisement.branches().len(),
+            tags,
+            branch
+        ))
+    }
+
+    /// fill a new repository with a clone of `from`, returns the branch checked out and the
+    /// number of tags
+    fn clone_from(
+        &mut self,
+        from: &mut dyn Transport,
+        advertisement: &Advertisement,
+        url: &str,
+        depth: Option<usize>,
+    ) -> Result<(String, usize), GitError> {
+        self.init()?;
+        // the history comes from the source, not from a fresh initial commit
+        let init_commit = fs::read_to_string(self.heads_path.join(MAIN_BRANCH))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        for file in [
+            self.commit_file(&init_commit)?,
+            self.heads_path.join(MAIN_BRANCH),
+        ] {
+            fs::remove_file(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        self.remote_add(ORIGIN, url)?;
+        self.fetch(ORIGIN, depth)?;
+
+        let tags: Vec<(&String, &String)> = advertisement
+            .refs
+            .iter()
+            .filter(|(reference, _)| reference.starts_with(&format!("{}/", TAG_REFS_DIR)))
+            .collect();
+        from.negotiate(&UploadRequest {
+            wants: tags.iter().map(|(_, target)| target.to_string()).collect(),
+            haves: self.complete_commits()?,
+            depth,
+        })?;
+        let mut count = 0;
+        for (reference, target) in tags {
+            let Some((_, name)) = self.ref_file_name(reference) else {
+                self.warnings
+                    .push(format!("skip {}: unsupported ref name", reference));
+                continue;
+            };
+            match from.tag_object(target)? {
+                Some(tag) if utils::sha1(&tag)? == *target => {
+                    self.copy_history(from, &tag.object, depth, &advertisement.shallow)?;
+                    Self::persist(&tag, &self.tags_path.join(target))?;
+                }
+                Some(_) => return Err(GitError::RemoteError(format!("tag {} is corrupt", target))),
+                None => {
+                    self.copy_history(from, target, depth, &advertisement.shallow)?;
+                }
+            }
+            fs::write(self.tag_refs_path.join(name), target)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            count += 1;
+        }
+
+        let branch = advertisement.head.clone();
+        if revision::check_name(&branch).is_err() || branch == HEAD_FILE {
+            return Err(GitError::RemoteError(format!("invalid branch {}", branch)));
+        }
+        let target = advertisement
+            .branches()
+            .get(branch.as_str())
+            .map(|target| target.to_string())
+            .unwrap_or_default();
+        self.update_ref(&format!("{}/{}", HEADS_DIR, branch), &target)?;
+        fs::write(&self.head_file, format!("{}/{}", HEADS_DIR, branch))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        if !target.is_empty() {
+            self.materialize_tree(&self.read_commit(&target)?.blobs)?;
+            self.set_upstream(&format!("{}/{}", ORIGIN, branch), Some(&branch))?;
+        }
+        Ok((branch, count))
+    }
+
+    /// Write the files of a commit into an archive file, without touching the working
+    /// directory. The format is guessed from the output file name unless given.
+    pub fn archive(
+        &self,
+        rev: &str,
+        output: &str,
+        format: Option<ArchiveFormat>,
+    ) -> Result<(), GitError> {
+        let format = format
+            .or_else(|| ArchiveFormat::from_file_name(output))
+            .ok_or_else(|| {
+                GitError::ArchiveError(format!("unknown archive format for {}", output))
+            })?;
+        let commit = self.read_commit(&self.resolve_commit(rev)?)?;
+        let file = fs::File::create(self.cwd.join(output))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        archive::write(
+            BufWriter::new(file),
+            format,
+            &commit.blobs,
+            commit.date_time(),
+            |blob| self.read_blob(blob),
+        )?
+        .flush()
+        .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// sha1 of a file, written into the object store as a blob when `write` is set;
+    /// the staging area is left untouched
+    pub fn hash_object(&self, path: &str, write: bool) -> Result<String, GitError> {
+        let filter = &mut self.eol_filter()?;
+        let entry = if write {
+            self.store_file(filter, path)?
+        } else {
+            self.hash_file(filter, path)?
+        };
+        Ok(entry.sha1)
+    }
+
+    /// read the content of the blob with the given sha1
+    pub fn read_blob(&self, sha1: &str) -> Result<Vec<u8>, GitError> {
+        let path = self.blob_file(sha1)?;
+        let manifest = self.manifests_path.join(sha1);
+        if path.is_file() {
+            Self::read_object_file(&path)
+        } else if manifest.is_file() {
+            let content = fs::read_to_string(manifest)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let chunk_ids: Vec<String> = serde_json::from_str(&content)
+                .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+            let mut data = vec![];
+            for chunk_id in chunk_ids.iter() {
+                data.extend(Self::read_object_file(&self.chunks_path.join(chunk_id))?);
+            }
+            Ok(data)
+        } else if let Some(pack) = self.packs()?.iter().find(|pack| pack.contains(sha1)) {
+            pack.read(sha1)
+        } else {
+            Err(GitError::FileNotExistError(path.display().to_string()))
+        }
+    }
+
+    /// read the content of a text blob, binary blobs are an error
+    pub fn read_blob_text(&self, sha1: &str) -> Result<String, GitError> {
+        String::from_utf8(self.read_blob(sha1)?)
+            .map_err(|_| GitError::FileOpError(format!("blob {} is not text", sha1)))
+    }
+
+    /// store the content as blob `hash`, see `store_blob_from`
+    fn store_blob(&self, content: &[u8], hash: &str) -> Result<(), GitError> {
+        self.store_blob_from(content, content.len() as u64, hash)
+    }
+
+    /// Store the `size` bytes the reader yields as blob `hash`, streamed through buffered
+    /// IO and compressed per `core.compression`. Contents of at least `core.chunkThreshold`
+    /// bytes are split into content-defined chunks stored under chunks/, so a small edit
+    /// only adds a few new chunks.
+    fn store_blob_from<R: Read>(&self, reader: R, size: u64, hash: &str) -> Result<(), GitError> {
+        let threshold = self
+            .config()?
+            .get_usize("core.chunkThreshold")?
+            .unwrap_or(CHUNK_THRESHOLD);
+        let compression = self.compression()?;
+        if size < threshold as u64 {
+            let path = self.blob_file(hash)?;
+            Self::create_object_dir(&path)?;
+            let file =
+                fs::File::create(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let out = BufWriter::with_capacity(utils::STREAM_BUFFER_SIZE, file);
+            return compression
+                .copy(reader, out)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)));
+        }
+        Self::init_repo_dir(&self.chunks_path)?;
+        Self::init_repo_dir(&self.manifests_path)?;
+        let mut chunk_ids = vec![];
+        chunk::for_each_chunk(reader, &ChunkParams::default(), |chunk| {
+            let chunk_id = utils::crypto_bytes(chunk);
+            let chunk_path = self.chunks_path.join(&chunk_id);
+            if !chunk_path.exists() {
+                fs::write(chunk_path, compression.encode(chunk))
+                    .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            }
+            chunk_ids.push(chunk_id);
+            Ok(())
+        })?;
+        info!("store {} as {} chunks", hash, chunk_ids.len());
+        Self::persist(&chunk_ids, &self.manifests_path.join(hash))
+    }
+
+    /// Store a working directory file as a blob, returns its entry. Files without line
+    /// ending conversion are hashed and copied while they are read, never whole in memory.
+    fn store_file(&self, filter: &mut EolFilter, path: &str) -> Result<Entry, GitError> {
+        let file = self.cwd.join(path);
+        let mode = self.file_mode(path)?;
+        if !self.stored_as_is(filter, path, mode)? {
+            let (content, mode) = self.clean_file(filter, path)?;
+            let hash = utils::crypto_bytes(&content);
+            self.store_blob(&content, &hash)?;
+            return Ok(Entry::with_mode(hash, mode));
+        }
+        let hash = utils::crypto_file(&file)?;
+        if !self.has_blob(&hash) {
+            let input =
+                fs::File::open(&file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let size = input
+                .metadata()
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?
+                .len();
+            let reader = BufReader::with_capacity(utils::STREAM_BUFFER_SIZE, input);
+            self.store_blob_from(reader, size, &hash)?;
+        }
+        Ok(Entry::with_mode(hash, mode))
+    }
+
+    /// add file under path into staging area, a directory adds every file under it the ignore
+    /// rules do not exclude
+    /// 1. check if added file has been modified
+    fn add_file(&mut self, path: &Path) -> Result<(), GitError> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            let relative_path = path.strip_prefix(&self.cwd).map_err(|_| {
+                GitError::StagedAddError(format!("file {} is outside repository", path.display()))
+            })?;
+            let relative_path = relative_path.display().to_string();
+            if metadata.is_dir() {
+                let mut ignore = self.base_ignore()?;
+                let mut files: BTreeSet<String> =
+                    utils::list_files(&self.cwd, path, &mut ignore, &mut self.warnings)?
+                        .into_iter()
+                        .collect();
+                // tracked files are added again even when the ignore rules exclude them
+                let pathspecs: Vec<String> = match relative_path.is_empty() {
+                    true => vec![],
+                    false => vec![relative_path],
+                };
+                for file in self
+                    .commit
+                    .blobs
+                    .keys()
+                    .chain(self.staging_area.staged.keys())
+                {
+                    if utils::pathspec_matches(&pathspecs, file) && self.cwd.join(file).is_file() {
+                        files.insert(file.clone());
+                    }
+                }
+                let filter = &mut self.eol_filter()?;
+                for file in files {
+                    match self.store_file(filter, &file) {
+                        Ok(entry) => self.staging_area.add(file, entry),
+                        Err(e) => self.warnings.push(format!("skip file {}: {}", file, e)),
+                    }
+                }
+                return Ok(());
+            }
+            // TODO: replace only when file is modified
+            // move file to staging area
+            let entry = self.store_file(&mut self.eol_filter()?, &relative_path)?;
+            self.staging_area.add(relative_path, entry);
+
+            Ok(())
+        } else {
+            Err(GitError::FileNotExistError(path.display().to_string()))
+        }
+    }
+
+    /// remove file
+    /// 1. Unstage the file if it is currently staged for addition.
+    /// 2. If the file is tracked in the current commit, stage it for removal and remove the file from the working directory if the user has not already done so (do not remove it unless it is tracked in the current commit).
+    fn remove_file(&mut self, path: &Path) -> Result<(), GitError> {
+        let relative_path = path.strip_prefix(&self.cwd).map_err(|_| {
+            GitError::StagedRemoveError(format!("file {} is outside repository", path.display()))
+        })?;
+        let path_name = relative_path.display().to_string();
+        if self.staging_area.staged.contains_key(&path_name) {
+            self.staging_area.staged.remove(&path_name);
+            Ok(())
+        } else if self.commit.blobs.contains_key(&path_name) {
+            self.staging_area.deleted.insert(path_name, "".to_string());
+            Ok(())
+        } else {
+            Err(GitError::StagedRemoveNoReasonError)
+        }
+    }
+    /// persistence staged area
+    /// 1. serialize StageArea into json string
+    /// 2. write/update serialized string into staging area file
+    fn persist<T: Serialize>(value: &T, path: &PathBuf) -> Result<(), GitError> {
+        let mut file =
+            fs::File::create(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let content =
+            serde_json::to_string(value).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+        file.write_all(content.as_bytes())
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        Ok(())
+    }
+
+    /// persist an object, e.g. a commit, compressed per `core.compression`
+    fn persist_object<T: Serialize>(&self, value: &T, path: &PathBuf) -> Result<(), GitError> {
+        Self::create_object_dir(path)?;
+        let content =
+            serde_json::to_string(value).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+        fs::write(path, self.compression()?.encode(content.as_bytes()))
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+
+    /// content of an object file, decompressed when it was written compressed
+    fn read_object_file(path: &Path) -> Result<Vec<u8>, GitError> {
+        let data = fs::read(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        compress::decode(&data)
+    }
+
+    fn unpersist_commit(path: &Path) -> Result<Commit, GitError> {
+        info!("unpersist_commit {}", path.display());
+        if !path.exists() || !path.is_file() {
+            info!("{}", path.display());
+            Err(GitError::FileNotExistError(path.display().to_string()))
+        } else {
+            let content = Self::read_object_file(path)?;
+            info!("content {}", String::from_utf8_lossy(&content));
+            serde_json::from_slice(&content)
+                .map_err(|e| GitError::SerdeOpError(format!("{}: {:?}", path.display(), e)))
+        }
+    }
+    /// unpersistence staged area
+    fn unpersist_staging_area(path: &PathBuf) -> Result<StagingArea, GitError> {
+        if !path.exists() || !path.is_file() {
+            Err(GitError::FileNotExistError(path.display().to_string()))
+        } else {
+            let mut file =
+                fs::File::open(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+
+            let mut content = String::new();
+            file.read_to_string(&mut content)
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            if content.is_empty() {
+                Ok(StagingArea::new())
+            } else {
+                serde_json::from_str(content.as_str())
+                    .map_err(|e| GitError::SerdeOpError(format!("{}: {:?}", path.display(), e)))
+            }
+        }
+    }
+}
+
+impl Transport for GitRepository {
+    fn advertise(&mut self) -> Result<Advertisement, GitError> {
+        self.load_branch()?;
+        let mut refs = self.ref_files(&self.heads_path)?;
+        refs.extend(self.ref_files(&self.tag_refs_path)?);
+        // a branch without commits has nothing to fetch
+        refs.retain(|_, target| !target.is_empty());
+        Ok(Advertisement {
+            head: self.current_branch(),
+            refs,
+            shallow: self.shallow_commits()?,
+        })
+    }
+
+    fn commit_object(&mut self, sha1: &str) -> Result<Commit, GitError> {
+        let file = self.commit_file(sha1)?;
+        if !file.is_file() {
+            return Err(GitError::FileNotExistError(sha1.to_string()));
+        }
+        Self::unpersist_commit(&file)
+    }
+
+    fn blob_content(&mut self, sha1: &str) -> Result<Vec<u8>, GitError> {
+        if !self.has_blob(sha1) {
+            return Err(GitError::FileNotExistError(sha1.to_string()));
+        }
+        self.read_blob(sha1)
+    }
+
+    fn tag_object(&mut self, sha1: &str) -> Result<Option<Tag>, GitError> {
+        match self.tags_path.join(sha1).is_file() {
+            true => self.read_tag_object(sha1).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn push_bundle(
+        &mut self,
+        bundle: &Bundle,
+        options: &ReceiveOptions,
+    ) -> Result<Vec<String>, GitError> {
+        self.receive_with(bundle, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::Daemon;
+    use std::io::Read;
+
+    fn clean_repo(repo_dir: &str) {
+        let path = &env::current_dir().unwrap().join(repo_dir);
+        if path.exists() {
+            assert!(fs::remove_dir_all(path).is_ok());
+        }
+    }
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    #[test]
+    fn init_repo_dir_ut() {
+        init();
+        let tmp_path = &env::current_dir().unwrap().join("init_repo_dir_ut");
+        assert!(GitRepository::init_repo_dir(tmp_path).is_ok());
+        assert!(tmp_path.exists());
+        assert!(tmp_path.is_dir());
+        assert!(fs::remove_dir(tmp_path).is_ok());
+    }
+
+    #[test]
+    fn smoke_ut() {
+        init();
+        info!("This record will be captured by `cargo test`");
+        let smoke_ut_repo_dir = ".smoke_ut_repo_dir";
+        let smoke_ut_dir = &env::current_dir().unwrap().join("smoke_ut");
+
+        if smoke_ut_dir.exists() {
+            assert!(fs::remove_dir_all(smoke_ut_dir).is_ok());
+        }
+
+        // prepare dir and files
+        assert!(fs::create_dir(smoke_ut_dir).is_ok());
+        assert!(fs::create_dir(smoke_ut_dir.join("d1")).is_ok());
+        for name in ["f1", "f2", "f3", "f4", "f5", "d1/f1", "d1/f2"] {
+            let mut file = fs::File::create(smoke_ut_dir.join(name)).unwrap();
+            assert!(file
+                .write_all(format!("this is a demo content for smoke_ut/{}", name).as_bytes())
+                .is_ok());
+        }
+
+        clean_repo(GIT_DIR);
+        clean_repo(smoke_ut_repo_dir);
+        let git = &mut GitRepository::new(smoke_ut_repo_dir);
+        assert!(!git.repo_path.exists());
+
+        assert!(git.init().is_ok());
+
+        assert!(git.repo_path.exists());
+        assert!(git.repo_path.is_dir());
+        assert!(git.blobs_path.exists());
+        assert!(git.blobs_path.is_dir());
+        assert!(git.commits_path.exists());
+        assert!(git.commits_path.is_dir());
+        assert!(git.heads_path.exists());
+        assert!(git.heads_path.is_dir());
+
+        assert!(git.head_file.exists());
+        assert!(git.head_file.is_file());
+        assert!(git.head_file.is_file());
+        assert!(git.index_file.exists());
+        assert!(git.index_file.is_file());
+
+        assert!(git.heads_path.join(MAIN_BRANCH).exists());
+        assert!(git.heads_path.join(MAIN_BRANCH).is_file());
+        // Act git add f1
+        assert_eq!(git.branch, "main");
+        assert_eq!(git.commit, Commit::new());
+        let res = git.add(&["smoke_ut/f1".to_string()]);
+        assert!(res.is_ok(), "{:?}", res.err().unwrap());
+        // Verify staging add file
+        let mut file = fs::File::open(&git.index_file).unwrap();
+        let mut content = String::new();
+        assert!(file.read_to_string(&mut content).is_ok());
+        assert_eq!(
+            r#"{"staged":{"smoke_ut/f1":"678a1b4e4584e32f8afdd13e90d31d087f88254d"},"deleted":{}}"#,
+            content.as_str()
+        );
+
+        let res = git.add(&["smoke_ut/f2".to_string(), "smoke_ut/f3".to_string()]);
+        // Act git add f2
+        assert!(res.is_ok(), "{:?}", res);
+        // Verify staging add file
+        let mut file = fs::File::open(&git.index_file).unwrap();
+        let mut content = String::new();
+        assert!(file.read_to_string(&mut content).is_ok());
+        assert_eq!(
+            r#"{"staged":{"smoke_ut/f1":"678a1b4e4584e32f8afdd13e90d31d087f88254d","smoke_ut/f2":"acecbabd47fd2c88ed744f9d56637b959dd91679","smoke_ut/f3":"6c75ae454cc6d6eb115c14a5f4966762962fabf3"},"deleted":{}}"#,
+            content.as_str()
+        );
+
+        // Act git rm f2
+        let res = git.remove(&["smoke_ut/f2".to_string()]);
+        assert!(res.is_ok(), "{:?}", res);
+        // Verify staging add file
+        let mut file = fs::File::open(&git.index_file).unwrap();
+        let mut content = String::new();
+        assert!(file.read_to_string(&mut content).is_ok());
+        assert_eq!(
+            r#"{"staged":{"smoke_ut/f1":"678a1b4e4584e32f8afdd13e90d31d087f88254d","smoke_ut/f3":"6c75ae454cc6d6eb115c14a5f4966762962fabf3"},"deleted":{}}"#,
+            content.as_str()
+        );
+        let mut git = GitRepository::new(smoke_ut_repo_dir);
+        assert!(git.load_basic_info().is_ok());
+        let res = git.staged_status(&Palette::default());
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Staged Files ===
+smoke_ut/f1
+smoke_ut/f3"#,
+            res.unwrap()
+        );
+        // Act git commit "commit test"
+        let res = git.commit("commit test");
+        assert!(res.is_ok(), "{:?}", res);
+        // Verify staging add file
+        // let res = git.load_basic_info();
+        // assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            git.commit.blobs,
+            BTreeMap::from([
+                (
+                    "smoke_ut/f1".to_string(),
+                    Entry::new("678a1b4e4584e32f8afdd13e90d31d087f88254d")
+                ),
+                (
+                    "smoke_ut/f3".to_string(),
+                    Entry::new("6c75ae454cc6d6eb115c14a5f4966762962fabf3")
+                ),
+            ])
+        );
+
+        // Act git rm f1
+        let res = git.remove(&["smoke_ut/f1".to_string()]);
+        assert!(res.is_ok(), "{:?}", res);
+        // Verify staging add file
+        let mut file = fs::File::open(&git.index_file).unwrap();
+        let mut content = String::new();
+        assert!(file.read_to_string(&mut content).is_ok());
+        assert_eq!(
+            r#"{"staged":{},"deleted":{"smoke_ut/f1":""}}"#,
+            content.as_str()
+        );
+
+        let mut git = GitRepository::new(smoke_ut_repo_dir);
+        assert!(git.load_basic_info().is_ok());
+        let res = git.removal_status(&Palette::default());
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Removed Files ===
+smoke_ut/f1"#,
+            res.unwrap()
+        );
+
+        // Act git commit "commit test"
+        let prev_commit = git.commit_sha1.clone();
+        let res = git.commit("commit 2nd");
+        assert!(res.is_ok(), "{:?}", res);
+        // Verify staging add file
+        let mut git = GitRepository::new(smoke_ut_repo_dir);
+        let res = git.load_basic_info();
+        assert!(res.is_ok(), "{:?}", res);
+        let commit = &git.commit;
+        assert_eq!(
+            commit.blobs,
+            BTreeMap::from([(
+                "smoke_ut/f3".to_string(),
+                Entry::new("6c75ae454cc6d6eb115c14a5f4966762962fabf3")
+            ),])
+        );
+        assert_eq!(prev_commit, commit.parent);
+
+        let mut git = GitRepository::new(smoke_ut_repo_dir);
+        assert!(git.load_basic_info().is_ok());
+        let res = git.branch_status();
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Branches ===
+*main"#,
+            res.unwrap()
+        );
+
+        let res = git.modified_not_staged(&Palette::default());
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Modifications Not Staged For Commit ==="#,
+            res.unwrap()
+        );
+
+        fs::write(
+            smoke_ut_dir.join("f3"),
+            "this is a modification content for f3",
+        )
+        .unwrap();
+        let res = git.modified_not_staged(&Palette::default());
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Modifications Not Staged For Commit ===
+smoke_ut/f3 (modified)"#,
+            res.unwrap()
+        );
+
+        fs::remove_file(smoke_ut_dir.join("f3")).unwrap();
+        let res = git.modified_not_staged(&Palette::default());
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Modifications Not Staged For Commit ===
+smoke_ut/f3 (deleted)"#,
+            res.unwrap()
+        );
+
+        let mut git = GitRepository::new(smoke_ut_repo_dir);
+        let res = git.branch("new_branch");
+        assert!(res.is_ok(), "{:?}", res);
+        let res = git.branch_status();
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(
+            r#"=== Branches ===
+*new_branch
+main"#,
+            res.unwrap()
+        );
+        clean_repo(smoke_ut_repo_dir);
+        assert!(fs::remove_dir_all(smoke_ut_dir).is_ok());
+    }
+
+    #[test]
+    fn commit_lint_ut() {
+        init();
+        let repo_dir = ".commit_lint_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+
+        // lint is opt-in
+        assert!(git.commit("not conventional").is_ok());
+        assert!(git.set_config("commit.lint", "true").is_ok());
+        assert!(git.set_config("lint.subjectMaxLength", "20").is_ok());
+        let res = git.commit("wip: a rather long subject line");
+        assert_eq!(
+            "commit : commit message rejected by commit.lint:\n  type \"wip\" is not one of feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert\n  subject is 31 characters long, the limit is 20",
+            res.unwrap_err().to_string()
+        );
+        let mut git = GitRepository::new(repo_dir);
+        assert!(git.load_basic_info().is_ok());
+        assert_eq!("not conventional", git.commit.meta.message);
+
+        assert!(git.commit("feat: lint commits").is_ok());
+        assert!(git.unset_config("commit.lint").is_ok());
+        assert!(git.unset_config("commit.lint").is_err());
+        assert!(git.commit("free form again").is_ok());
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn commit_range_ut() {
+        init();
+        let repo_dir = ".commit_range_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let first = git.resolve_commit("main").unwrap();
+        assert!(git.commit("feat: one").is_ok());
+        let second = git.resolve_commit("main").unwrap();
+        assert!(git.commit("fix: two").is_ok());
+        assert!(git.commit("fix: three").is_ok());
+
+        let messages = |range: &str| -> Vec<String> {
+            git.commit_range(range)
+                .unwrap()
+                .iter()
+                .map(|(_, c)| c.message().to_string())
+                .collect()
+        };
+        assert_eq!(
+            vec!["fix: three", "fix: two"],
+            messages(&format!("{}..main", second))
+        );
+        assert_eq!(
+            vec!["fix: three", "fix: two", "feat: one"],
+            messages(&format!("{}..", first))
+        );
+        assert_eq!(4, messages("..HEAD").len());
+        assert_eq!(4, messages("main").len());
+        assert!(messages("main..").is_empty());
+        assert!(git.commit_range("unknown..main").is_err());
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn tag_and_release_ut() {
+        init();
+        let repo_dir = ".tag_and_release_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let first = git.resolve_commit("main").unwrap();
+
+        // lightweight and annotated tags resolve to commits
+        assert_eq!(first, git.create_tag("base", "main", None).unwrap());
+        assert!(git.create_tag("base", "main", None).is_err());
+        assert!(git.create_tag("a/b", "main", None).is_err());
+        assert!(git.create_tag("x", "unknown", None).is_err());
+        assert_eq!(None, git.read_tag("base").unwrap());
+        assert_eq!(first, git.resolve_commit("base").unwrap());
+
+        assert!(git.set_config("user.name", "Jane").is_ok());
+        assert!(git.set_config("user.email", "jane@example.com").is_ok());
+        assert!(git
+            .create_tag("annotated", "main", Some("the base"))
+            .is_ok());
+        let tag = git.read_tag("annotated").unwrap().unwrap();
+        assert_eq!("Jane <jane@example.com>", tag.tagger());
+        assert!(tag.to_string().starts_with(&format!(
+            "tag annotated\nobject {}\nTagger: Jane <jane@example.com>\nDate: ",
+            first
+        )));
+        assert!(tag.to_string().ends_with("\n\nthe base\n"));
+        let show = git.show("annotated").unwrap();
+        assert!(show.starts_with(&tag.to_string()));
+        assert!(show.ends_with(&git.show(&first).unwrap()));
+        assert!(!git.show("base").unwrap().starts_with("tag "));
+
+        assert!(git.commit("feat: one").is_ok());
+        let notes = git.release(None).unwrap();
+        assert!(notes.starts_with("## v0.1.0\n"), "{}", notes);
+        assert!(notes.contains("- one ("));
+        let tag = git.read_tag("v0.1.0").unwrap().unwrap();
+        assert_eq!(git.resolve_commit("main").unwrap(), tag.object());
+        assert_eq!(notes, tag.message());
+        assert_eq!(tag.object(), git.resolve_commit("v0.1.0").unwrap());
+
+        assert!(git.release(None).is_err());
+        assert!(git.commit("fix: two").is_ok());
+        let notes = git.release(None).unwrap();
+        assert!(notes.starts_with("## v0.1.1\n"), "{}", notes);
+        assert!(!notes.contains("- one ("));
+        assert!(git.commit("docs: three").is_ok());
+        assert!(git
+            .release(Some(Bump::Major))
+            .unwrap()
+            .starts_with("## v1.0.0\n"));
+
+        assert_eq!(
+            vec!["annotated", "base", "v0.1.0", "v0.1.1", "v1.0.0"],
+            git.tags().unwrap().into_keys().collect::<Vec<String>>()
+        );
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn describe_ut() {
+        init();
+        let repo_dir = ".describe_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let first = git.resolve_commit("main").unwrap();
+        assert!(git.describe(None).is_err());
+
+        // the nearest tag wins, lightweight or annotated
+        assert!(git.create_tag("base", "main", None).is_ok());
+        assert!(git.commit("one").is_ok());
+        assert!(git.create_tag("v1", "main", Some("first release")).is_ok());
+        assert_eq!("v1", git.describe(None).unwrap());
+        assert_eq!("base", git.describe(Some(&first)).unwrap());
+        assert!(git.commit("two").is_ok());
+        assert!(git.commit("three").is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+        assert_eq!(format!("v1-2-g{}", &head[..7]), git.describe(None).unwrap());
+        assert!(git.describe(Some("unknown")).is_err());
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn file_log_ut() {
+        init();
+        let repo_dir = ".file_log_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("file_log_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let f1 = "file_log_ut/f1";
+        let f2 = "file_log_ut/f2";
+
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(&[f1.to_string()]).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "v1").is_ok());
+        assert!(git.add(&[f2.to_string()]).is_ok());
+        assert!(git.commit("add f2").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(git.add(&[f1.to_string()]).is_ok());
+        assert!(git.commit("modify f1").is_ok());
+        assert!(git.remove(&[f1.to_string()]).is_ok());
+        assert!(git.commit("remove f1").is_ok());
+
+        let head = git.resolve_commit("main").unwrap();
+        let changes = git.file_history(&head, f1).unwrap();
+        let summary: Vec<(&str, Option<String>)> = changes
+            .iter()
+            .map(|(_, c, b)| (c.message(), b.clone()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("remove f1", None),
+                ("modify f1", Some(utils::crypto_string("v2"))),
+                ("add f1", Some(utils::crypto_string("v1"))),
+            ],
+            summary
+        );
+        let res = git.file_log(f2).unwrap();
+        assert!(res.contains("add f2"));
+        assert!(res.ends_with(&format!("blob {}\n", utils::crypto_string("v1"))));
+        assert!(git.file_log("file_log_ut/none").is_err());
+
+        assert!(fs::write(work_dir.join("f2"), "v1\nline 2\n").is_ok());
+        assert!(git.add(&[f2.to_string()]).is_ok());
+        assert!(git.commit("append to f2").is_ok());
+        let blame = git.blame(f2).unwrap();
+        let add_f2 = &git.file_history(&git.commit_sha1.clone(), f2).unwrap()[1].0;
+        let lines: Vec<&str> = blame.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with(&add_f2[..8]), "{}", blame);
+        assert!(lines[0].ends_with("add f2       1) v1"), "{}", blame);
+        assert!(lines[1].starts_with(&git.commit_sha1[..8]), "{}", blame);
+        assert!(lines[1].ends_with("append to f2 2) line 2"), "{}", blame);
+        assert!(git.blame(f1).is_err());
+
+        assert!(git.archive("main", "file_log_ut/out.zip", None).is_ok());
+        let zip = zip::ZipArchive::new(fs::File::open(work_dir.join("out.zip")).unwrap()).unwrap();
+        assert_eq!(vec![f2], zip.file_names().collect::<Vec<&str>>());
+        assert!(git.archive("main", "file_log_ut/out.txt", None).is_err());
+        assert!(!work_dir.join("out.txt").exists());
+        assert!(git
+            .archive("main", "file_log_ut/out", Some(ArchiveFormat::Tar))
+            .is_ok());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn cat_file_ut() {
+        init();
+        let repo_dir = ".cat_file_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("cat_file_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(&["cat_file_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+
+        let v1 = utils::crypto_string("v1");
+        assert_eq!("blob", git.object_type(&v1).unwrap());
+        assert_eq!("v1", git.cat_file(&v1).unwrap());
+        assert_eq!("commit", git.object_type(&head).unwrap());
+        assert_eq!("commit", git.object_type("main").unwrap());
+        let pretty = git.cat_file("main").unwrap();
+        assert!(pretty.starts_with("{\n  \"meta\": {\n"), "{}", pretty);
+        assert_eq!(
+            git.read_commit(&head).unwrap(),
+            serde_json::from_str::<Commit>(&pretty).unwrap()
+        );
+
+        assert!(git.set_config("user.name", "Jane").is_ok());
+        assert!(git.set_config("user.email", "jane@example.com").is_ok());
+        assert!(git.create_tag("annotated", "main", Some("tagged")).is_ok());
+        let tag = git.read_tag("annotated").unwrap().unwrap();
+        let tag_sha1 = fs::read_to_string(git.tag_refs_path.join("annotated")).unwrap();
+        assert_eq!("tag", git.object_type(&tag_sha1).unwrap());
+        let pretty = git.cat_file(&tag_sha1).unwrap();
+        assert_eq!(tag, serde_json::from_str::<Tag>(&pretty).unwrap());
+        assert!(pretty.contains("\n  \"tagger\": \"Jane <jane@example.com>\""));
+
+        assert!(git.object_type("0123abcd").is_err());
+        assert!(git.cat_file("../config").is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn hash_object_ut() {
+        init();
+        let repo_dir = ".hash_object_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("hash_object_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+
+        // -w stores the blob without staging the file
+        assert!(fs::write(work_dir.join("f3"), "v3").is_ok());
+        let v3 = git.hash_object("hash_object_ut/f3", false).unwrap();
+        assert_eq!(utils::crypto_string("v3"), v3);
+        assert!(git.cat_file(&v3).is_err());
+        assert_eq!(v3, git.hash_object("hash_object_ut/f3", true).unwrap());
+        assert_eq!("v3", git.cat_file(&v3).unwrap());
+        assert!(git.staging_area.is_empty());
+        assert!(git.hash_object("hash_object_ut/none", true).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn grep_ut() {
+        init();
+        let repo_dir = ".grep_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("grep_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let f1 = "grep_ut/f1";
+        let f2 = "grep_ut/f2";
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "v1").is_ok());
+        assert!(git.add(&[f1.to_string(), f2.to_string()]).is_ok());
+        assert!(git.commit("add f1 and f2").is_ok());
+        let first = git.resolve_commit("HEAD").unwrap();
+        assert!(fs::write(work_dir.join("f2"), "v1\nline 2\n").is_ok());
+        assert!(git.add(&[f2.to_string()]).is_ok());
+        assert!(git.remove(&[f1.to_string()]).is_ok());
+        assert!(git.commit("append to f2, remove f1").is_ok());
+
+        assert_eq!(
+            vec![
+                "grep_ut/f2:1:v1".to_string(),
+                "grep_ut/f2:2:line 2".to_string()
+            ],
+            git.grep("^(v|line) ?[0-9]$", None).unwrap()
+        );
+        assert_eq!(
+            vec!["grep_ut/f1:1:v1".to_string(), "grep_ut/f2:1:v1".to_string()],
+            git.grep("v1", Some(&first)).unwrap()
+        );
+        assert!(git.grep("v2", None).unwrap().is_empty());
+        assert!(git.grep("(", None).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn bundle_ut() {
+        init();
+        let repo_dir = ".bundle_ut_repo_dir";
+        let other_dir = ".bundle_ut_other_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("bundle_ut");
+        clean_repo(repo_dir);
+        clean_repo(other_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(&["bundle_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        assert!(git.create_tag("v1", "HEAD", Some("first")).is_ok());
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(git.add(&["bundle_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("modify f1").is_ok());
+        let topic = git.resolve_commit("topic").unwrap();
+
+        assert!(git
+            .bundle_create("bundle_ut/unknown.bundle", &["none".to_string()])
+            .is_err());
+        assert_eq!(
+            "Bundled 1 refs, 3 commits and 2 blobs.",
+            git.bundle_create("bundle_ut/topic.bundle", &["topic".to_string()])
+                .unwrap()
+        );
+        assert_eq!(
+            "Bundled 3 refs, 3 commits and 2 blobs.",
+            git.bundle_create("bundle_ut/all.bundle", &[]).unwrap()
+        );
+
+        let other = &mut GitRepository::new(other_dir);
+        assert!(other.init().is_ok());
+        assert_eq!(
+            vec![
+                "! [rejected] main (checked out)",
+                "* [new branch] topic",
+                "* [new tag] v1"
+            ],
+            other.unbundle("bundle_ut/all.bundle").unwrap()
+        );
+        assert_eq!(topic, other.resolve_commit("topic").unwrap());
+        assert_eq!(git.read_tag("v1").unwrap(), other.read_tag("v1").unwrap());
+        assert_eq!(
+            "v2",
+            other
+                .read_blob_text(&other.read_commit(&topic).unwrap().blobs["bundle_ut/f1"].sha1)
+                .unwrap()
+        );
+        assert_eq!(
+            vec!["= [up to date] topic".to_string()],
+            other.unbundle("bundle_ut/topic.bundle").unwrap()
+        );
+
+        let content = fs::read_to_string(work_dir.join("topic.bundle")).unwrap();
+        assert!(fs::write(work_dir.join("bad.bundle"), content.replace("v2", "v3")).is_ok());
+        assert!(other.unbundle("bundle_ut/bad.bundle").is_err());
+        assert!(fs::write(work_dir.join("bad.bundle"), "not a bundle").is_ok());
+        assert!(other.unbundle("bundle_ut/bad.bundle").is_err());
+
+        clean_repo(repo_dir);
+        clean_repo(other_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn receive_invalid_id_ut() {
+        init();
+        let repo_dir = ".receive_invalid_id_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+        let outside = env::current_dir().unwrap().join("receive_invalid_id_ut");
+        assert!(!outside.exists());
+
+        // ids are checked before any of them becomes a path in the object store
+        let refs =
+            |target: &str| BTreeMap::from([("refs/heads/evil".to_string(), target.to_string())]);
+        let traversal = format!("../../../{}", "receive_invalid_id_ut");
+        for bundle in [
+            Bundle {
+                refs: refs(&traversal),
+                ..Default::default()
+            },
+            Bundle {
+                refs: refs(&head.to_uppercase()),
+                ..Default::default()
+            },
+            Bundle {
+                refs: refs(&head),
+                blobs: BTreeMap::from([(traversal.clone(), "content".to_string())]),
+                ..Default::default()
+            },
+        ] {
+            let err = git.receive(&bundle).unwrap_err();
+            assert!(err.to_string().contains("invalid object id"), "{}", err);
+        }
+        assert!(!outside.exists());
+        assert!(!git.heads_path.join("evil").exists());
+        assert!(GitRepository::object_path(&git.commits_path, &traversal).is_err());
+        assert!(git.resolve_commit(&traversal).is_err());
+
+        assert_eq!(
+            vec!["* [new branch] evil"],
+            git.receive(&Bundle {
+                refs: refs(&head),
+                ..Default::default()
+            })
+            .unwrap()
+        );
+
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn apply_ut() {
+        init();
+        let repo_dir = ".apply_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("apply_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let pathspec = &["apply_ut".to_string()];
+        assert!(fs::write(work_dir.join("f1"), "a\nb\nc\n").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "x\n").is_ok());
+        assert!(git.add_all(pathspec).is_ok());
+        assert!(git.commit("add files").is_ok());
+        let base = git.resolve_commit("HEAD").unwrap();
+
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "a\nB\nc\n").is_ok());
+        assert!(fs::remove_file(work_dir.join("f2")).is_ok());
+        assert!(fs::write(work_dir.join("f3"), "new\n").is_ok());
+        assert!(git.add_all(pathspec).is_ok());
+        assert!(git.commit("change files\n\nwith a body").is_ok());
+        let topic = git.resolve_commit("HEAD").unwrap();
+        let patches = git
+            .format_patch(&format!("{}..HEAD", base), Some("apply_ut_patches"))
+            .unwrap();
+        assert_eq!(1, patches.len());
+        assert!(git.checkout_branch("main").is_ok());
+
+        // working directory only
+        let patched = vec!["apply_ut/f1", "apply_ut/f2", "apply_ut/f3"];
+        assert_eq!(patched, git.apply(&patches[0], false).unwrap());
+        assert_eq!(
+            "a\nB\nc\n",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(!work_dir.join("f2").exists());
+        assert_eq!("new\n", fs::read_to_string(work_dir.join("f3")).unwrap());
+        assert!(git.staging_area.is_empty());
+        // applying again fails and changes nothing
+        assert!(git.apply(&patches[0], false).is_err());
+        assert_eq!(
+            "a\nB\nc\n",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(git.checkout_file("apply_ut/f1").is_ok());
+        assert!(git.checkout_file("apply_ut/f2").is_ok());
+        assert!(fs::remove_file(work_dir.join("f3")).is_ok());
+
+        // index only
+        assert_eq!(patched, git.apply(&patches[0], true).unwrap());
+        assert_eq!(
+            vec!["apply_ut/f1", "apply_ut/f3"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["apply_ut/f2"],
+            git.staging_area.deleted.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "a\nb\nc\n",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(git.am(&patches).is_err());
+        git.staging_area = StagingArea::new();
+        assert!(git.persist_basic_info().is_ok());
+
+        // am recreates the very same commit on the same parent
+        assert_eq!(vec!["change files".to_string()], git.am(&patches).unwrap());
+        assert_eq!(topic, git.resolve_commit("main").unwrap());
+        assert_eq!("new\n", fs::read_to_string(work_dir.join("f3")).unwrap());
+        assert!(!work_dir.join("f2").exists());
+        assert!(git.am(&patches).is_err());
+        assert_eq!(topic, git.resolve_commit("main").unwrap());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all("apply_ut_patches").is_ok());
+    }
+
+    #[test]
+    fn gc_ut() {
+        init();
+        let repo_dir = ".gc_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("gc_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let f1 = &["gc_ut/f1".to_string()];
+        assert_eq!(
+            "Removed 0 unreachable objects: 0 commits, 0 tags, 0 blobs, 0 chunks.",
+            git.gc().unwrap()
+        );
+
+        // v1 is added but replaced before the commit, the first commit is amended
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        let amended = git.resolve_commit("HEAD").unwrap();
+        assert!(git.amend(Some("add f1 at v2")).is_ok());
+        assert!(git.create_tag("v2", "HEAD", Some("tagged")).is_ok());
+        // stashed and staged versions stay
+        assert!(fs::write(work_dir.join("f1"), "v3").is_ok());
+        assert!(git.stash_push(None, false).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v4").is_ok());
+        assert!(git.add(f1).is_ok());
+
+        let v1 = utils::crypto_string("v1");
+        assert_eq!(vec![format!("blob {}", v1)], git.prune(true).unwrap());
+        assert!(git.read_blob(&v1).is_ok());
+        assert_eq!(
+            "Removed 1 unreachable objects: 0 commits, 0 tags, 1 blobs, 0 chunks.",
+            git.gc().unwrap()
+        );
+        assert!(git.read_blob(&v1).is_err());
+        // the amended commit is only in the reflog of the branch, which keeps it
+        let head = git.resolve_commit("HEAD").unwrap();
+        assert!(git
+            .history(&head)
+            .unwrap()
+            .iter()
+            .all(|(sha1, _)| *sha1 != amended));
+        assert!(git.read_commit(&amended).is_ok());
+        for content in ["v2", "v3", "v4"] {
+            assert!(git.read_blob(&utils::crypto_string(content)).is_ok());
+        }
+        assert!(git.read_tag("v2").unwrap().is_some());
+        assert_eq!(
+            2,
+            git.history(&git.resolve_commit("HEAD").unwrap())
+                .unwrap()
+                .len()
+        );
+        assert!(git.prune(true).unwrap().is_empty());
+
+        // without the reflog nothing refers to the amended commit any more
+        assert!(fs::remove_dir_all(git.repo_path.join(LOGS_DIR)).is_ok());
+        assert_eq!(
+            vec![format!("commit {}", amended)],
+            git.prune(true).unwrap()
+        );
+        assert_eq!(
+            "Removed 1 unreachable objects: 1 commits, 0 tags, 0 blobs, 0 chunks.",
+            git.gc().unwrap()
+        );
+        assert!(git.read_commit(&amended).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn count_objects_ut() {
+        init();
+        let repo_dir = ".count_objects_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("count_objects_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let counts = git.count_objects().unwrap();
+        assert_eq!(
+            (1, 0, 0, 0),
+            (
+                counts.commits,
+                counts.tags,
+                counts.blobs,
+                counts.unreachable
+            )
+        );
+
+        // v1 is added but replaced before the commit
+        let f1 = &["count_objects_ut/f1".to_string()];
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        assert!(git.create_tag("v2", "HEAD", Some("tagged")).is_ok());
+        let counts = git.count_objects().unwrap();
+        assert_eq!(
+            (2, 1, 2, 1),
+            (
+                counts.commits,
+                counts.tags,
+                counts.blobs,
+                counts.unreachable
+            )
+        );
+        assert_eq!((0, 0, 0), (counts.chunks, counts.in_pack, counts.packs));
+        assert!(counts.size > counts.unreachable_size && counts.unreachable_size > 1);
+
+        assert!(git.gc().is_ok());
+        let after = git.count_objects().unwrap();
+        assert_eq!((2, 1, 0), (after.commits, after.blobs, after.unreachable));
+        assert_eq!(counts.size - counts.unreachable_size, after.size);
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn fsck_ut() {
+        init();
+        let repo_dir = ".fsck_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("fsck_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.fsck().unwrap().is_empty());
+
+        let f1 = &["fsck_ut/f1".to_string()];
+        let f2 = &["fsck_ut/f2".to_string()];
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.add(f2).is_ok());
+        assert!(git.commit("add f1 and f2").is_ok());
+        assert!(git.create_tag("v1", "HEAD", Some("tagged")).is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+        let v1 = utils::crypto_string("v1");
+        assert_eq!(vec![format!("dangling blob {}", v1)], git.fsck().unwrap());
+
+        let v2 = utils::crypto_string("v2");
+        let f2_blob = utils::crypto_string("f2");
+        assert!(fs::write(git.blob_file(&v2).unwrap(), "tampered").is_ok());
+        assert!(fs::remove_file(git.blob_file(&f2_blob).unwrap()).is_ok());
+        assert!(fs::write(git.heads_path.join("lost"), "f".repeat(40)).is_ok());
+        // the bad ref hides dangling objects
+        let mut problems = git.fsck().unwrap();
+        problems.sort();
+        let mut expected = vec![
+            format!("corrupt blob {}", v2),
+            format!("broken link from commit {} to blob {}", head, f2_blob),
+            format!(
+                "bad ref refs/heads/lost: {} is not a commit",
+                "f".repeat(40)
+            ),
+        ];
+        expected.sort();
+        assert_eq!(expected, problems);
+
+        assert!(fs::remove_file(git.heads_path.join("lost")).is_ok());
+        assert!(fs::write(git.commit_file(&head).unwrap(), "{}").is_ok());
+        let problems = git.fsck().unwrap();
+        assert!(problems.contains(&format!("corrupt commit {}", head)));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn chunked_blob_ut() {
+        init();
+        let repo_dir = ".chunked_blob_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("chunked_blob_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.set_config("core.chunkThreshold", "1024").is_ok());
+
+        let content: String = (0..40000)
+            .map(|i| format!("line {}\n", i * 7919 % 10007))
+            .collect();
+        assert!(fs::write(work_dir.join("big"), &content).is_ok());
+        assert!(fs::write(work_dir.join("small"), "small").is_ok());
+        assert!(git
+            .add(&[
+                "chunked_blob_ut/big".to_string(),
+                "chunked_blob_ut/small".to_string()
+            ])
+            .is_ok());
+        let hash = utils::crypto_string(&content);
+        assert!(!git.blob_file(&hash).unwrap().exists());
+        assert!(git.manifests_path.join(&hash).is_file());
+        assert!(git
+            .blob_file(&utils::crypto_string("small"))
+            .unwrap()
+            .is_file());
+        assert_eq!(content, git.read_blob_text(&hash).unwrap());
+        let chunk_count = fs::read_dir(&git.chunks_path).unwrap().count();
+        assert!(chunk_count > 1);
+
+        let edited = content.replacen("line 7919", "line edited", 1);
+        assert!(fs::write(work_dir.join("big"), &edited).is_ok());
+        assert!(git.add(&["chunked_blob_ut/big".to_string()]).is_ok());
+        assert_eq!(
+            edited,
+            git.read_blob_text(&utils::crypto_string(&edited)).unwrap()
+        );
+        let new_chunks = fs::read_dir(&git.chunks_path).unwrap().count() - chunk_count;
+        assert!(new_chunks <= 2, "{} new chunks", new_chunks);
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn repack_ut() {
+        init();
+        let repo_dir = ".repack_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("repack_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert_eq!("Nothing to pack.", git.repack().unwrap());
+        let f1 = &["repack_ut/f1".to_string()];
+
+        let lines: Vec<String> = (0..500).map(|i| format!("line {}\n", i)).collect();
+        let mut versions = vec![];
+        for v in 0..4 {
+            let mut lines = lines.clone();
+            lines[v * 100] = format!("version {}\n", v);
+            let content = lines.concat();
+            assert!(fs::write(work_dir.join("f1"), &content).is_ok());
+            assert!(git.add(f1).is_ok());
+            assert!(git.commit(&format!("v{}", v)).is_ok());
+            versions.push(content);
+        }
+        assert!(fs::write(work_dir.join("f2"), "staged").is_ok());
+        assert!(git.add(&["repack_ut/f2".to_string()]).is_ok());
+
+        let msg = git.repack().unwrap();
+        assert!(msg.starts_with("Packed 5 blobs into pack-"), "{}", msg);
+        assert!(msg.ends_with(", 3 as deltas."), "{}", msg);
+        assert!(GitRepository::object_ids(&git.blobs_path)
+            .unwrap()
+            .is_empty());
+        for content in versions.iter() {
+            assert_eq!(
+                content,
+                &git.read_blob_text(&utils::crypto_string(content)).unwrap()
+            );
+        }
+        assert_eq!(
+            "blob",
+            git.object_type(&utils::crypto_string("staged")).unwrap()
+        );
+        let counts = git.count_objects().unwrap();
+        assert_eq!((5, 5, 1), (counts.blobs, counts.in_pack, counts.packs));
+        assert!(git.fsck().unwrap().is_empty());
+
+        // new loose blobs join the packed ones
+        assert!(fs::write(work_dir.join("f1"), "last").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("last").is_ok());
+        assert!(git.repack().unwrap().starts_with("Packed 6 blobs"));
+        assert_eq!(1, git.packs().unwrap().len());
+        assert!(git.fsck().unwrap().is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn fan_out_ut() {
+        init();
+        let repo_dir = ".fan_out_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("fan_out_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(&["fan_out_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("v1").is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+        let blob = utils::crypto_string("v1");
+        let sharded = git.blobs_path.join(&blob[..2]).join(&blob[2..]);
+        assert!(sharded.is_file());
+        assert!(git.commits_path.join(&head[..2]).join(&head[2..]).is_file());
+
+        // objects of the flat layout are still found until gc moves them
+        assert!(fs::rename(&sharded, git.blobs_path.join(&blob)).is_ok());
+        let flat_commit = git.commits_path.join(&head);
+        assert!(fs::rename(git.commit_file(&head).unwrap(), &flat_commit).is_ok());
+        assert_eq!("v1", git.read_blob_text(&blob).unwrap());
+        assert_eq!(head, git.resolve_commit(&head[..8]).unwrap());
+        assert!(git.fsck().unwrap().is_empty());
+        assert!(git.gc().is_ok());
+        assert!(sharded.is_file());
+        assert!(!flat_commit.exists());
+        assert_eq!("v1", git.read_commit(&head).unwrap().message());
+        assert!(git.fsck().unwrap().is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn export_git_ut() {
+        init();
+        let repo_dir = ".export_git_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("export_git_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("sub")).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "hello\n").is_ok());
+        assert!(fs::write(work_dir.join("sub").join("f2"), "f2\n").is_ok());
+        assert!(git.add_all(&["export_git_ut".to_string()]).is_ok());
+        assert!(git.commit("first").is_ok());
+        assert!(git.set_config("core.gitObjects", "true").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "changed\n").is_ok());
+        assert!(git.add(&["export_git_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("second").is_ok());
+
+        let git_dir = git.repo_path.join(GIT_EXPORT_DIR);
+        let head = fs::read_to_string(git_dir.join(&git.branch)).unwrap();
+        let store = ObjectStore::new(&git_dir.join("objects"));
+        let (kind, content) = store.read(head.trim()).unwrap();
+        assert_eq!(ObjectKind::Commit, kind);
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.starts_with("tree "), "{}", content);
+        assert!(content.contains("\nparent "), "{}", content);
+        assert!(content.ends_with("\n\nsecond\n"), "{}", content);
+        // blobs keep the id stock git gives them
+        assert!(store.contains(&gitobject::object_id(ObjectKind::Blob, b"hello\n")));
+
+        // exporting again only rewrites the refs
+        let exported = git.export_git().unwrap();
+        assert_eq!(
+            vec![(git.branch.clone(), head.trim().to_string())],
+            exported
+        );
+        let stock = Command::new("git")
+            .args(["--git-dir", &git_dir.display().to_string()])
+            .args(["cat-file", "-p", "HEAD:export_git_ut/sub/f2"])
+            .output();
+        if let Ok(output) = stock {
+            assert_eq!("f2\n", String::from_utf8_lossy(&output.stdout));
+        }
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn fast_import_ut() {
+        init();
+        let repo_dir = ".fast_import_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let stream = b"blob\nmark :1\ndata 3\nv1\n\n\
+            commit refs/heads/topic\nmark :2\n\
+            author A <a@example.com> 1700000000 +0000\n\
+            committer C <c@example.com> 1700000100 +0000\n\
+            data 6\nfirst\n\nM 100644 :1 dir/a\nM 100755 inline run\ndata 2\nx\n\n\
+            commit refs/heads/topic\nmark :3\n\
+            committer C <c@example.com> 1700000200 +0000\n\
+            data 6\nmoved\nR dir renamed\nD run\n\n\
+            tag v1\nfrom :2\ntagger T <t@example.com> 1700000300 +0000\ndata 4\nrel\n";
+        assert_eq!(
+            "Imported 1 blobs, 2 commits and 1 tags, updated 2 refs.",
+            git.fast_import(stream).unwrap()
+        );
+
+        let tip = git.resolve_commit("topic").unwrap();
+        let commit = git.read_commit(&tip).unwrap();
+        assert_eq!("moved", commit.message());
+        assert_eq!(1700000200, commit.date_time());
+        assert_eq!(vec!["renamed/a"], commit.blobs().keys().collect::<Vec<_>>());
+        let first = git.read_commit(commit.parent()).unwrap();
+        assert_eq!("A <a@example.com>", first.author());
+        assert_eq!("", first.parent());
+        assert_eq!(FileMode::Executable, first.blobs()["run"].mode);
+        assert_eq!(
+            "x\n",
+            git.read_blob_text(&first.blobs()["run"].sha1).unwrap()
+        );
+        assert_eq!(commit.parent(), git.resolve_commit("v1").unwrap());
+        assert_eq!("rel", git.read_tag("v1").unwrap().unwrap().message());
+
+        // commits without from continue the branch
+        let stream = b"commit refs/heads/topic\n\
+            committer C <c@example.com> 1700000400 +0000\n\
+            data 5\nthird\nM 644 inline b\ndata 1\nb\n";
+        assert!(git.fast_import(stream).is_ok());
+        let third = git
+            .read_commit(&git.resolve_commit("topic").unwrap())
+            .unwrap();
+        assert_eq!(tip, third.parent());
+        assert_eq!(2, third.blobs().len());
+
+        let bad_ref = b"reset refs/remotes/origin/main\nfrom :1\n";
+        assert!(git.fast_import(bad_ref).is_err());
+        let unknown = b"commit refs/heads/x\ncommitter C <c> 1 +0000\ndata 0\nM 644 :9 f\n";
+        assert!(git.fast_import(unknown).is_err());
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn import_git_ut() {
+        init();
+        let (repo_dir, copy_dir) = (".import_git_ut_repo_dir", ".import_git_ut_copy_dir");
+        clean_repo(repo_dir);
+        clean_repo(copy_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let stream = b"commit refs/heads/topic\nmark :1\n\
+            author A <a@example.com> 1700000000 +0000\n\
+            committer C <c@example.com> 1700000100 +0000\n\
+            data 5\nfirst\nM 100644 inline dir/a\ndata 2\na\nM 120000 inline link\ndata 5\ndir/a\n\
+            commit refs/heads/topic\n\
+            author A <a@example.com> 1700000200 +0000\n\
+            committer C <c@example.com> 1700000200 +0000\n\
+            data 6\nsecond\nM 100755 inline dir/run\ndata 2\nx\n\n\
+            reset refs/tags/v1\nfrom :1\n";
+        assert!(git.fast_import(stream).is_ok());
+        assert!(git.export_git().is_ok());
+        let git_dir = git.repo_path.join(GIT_EXPORT_DIR);
+
+        // the round trip through Git's format gives back the same commits
+        let copy = &mut GitRepository::new(copy_dir);
+        assert!(copy.init().is_ok());
+        assert_eq!(
+            "Imported 3 blobs, 3 commits and 0 tags, updated 3 refs.",
+            copy.import_git(&git_dir.display().to_string()).unwrap()
+        );
+        let tip = git.resolve_commit("topic").unwrap();
+        assert_eq!(tip, copy.resolve_commit("topic").unwrap());
+        assert_eq!(
+            git.resolve_commit("v1").ok(),
+            copy.resolve_commit("v1").ok()
+        );
+        let commit = copy.read_commit(&tip).unwrap();
+        assert_eq!(FileMode::Symlink, commit.blobs()["link"].mode);
+        assert_eq!(
+            "x\n",
+            copy.read_blob_text(&commit.blobs()["dir/run"].sha1)
+                .unwrap()
+        );
+        assert!(copy.import_git(".import_git_ut_missing").is_err());
+
+        // packed objects, annotated tags and packed refs, with stock git at hand
+        let stock = |args: &[&str]| {
+            Command::new("git")
+                .args(["--git-dir", &git_dir.display().to_string()])
+                .args(["-c", "user.name=T", "-c", "user.email=t@example.com"])
+                .args(args)
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        if stock(&["tag", "-a", "-m", "release", "v2", "topic~1"])
+            && stock(&["update-ref", "refs/heads/feature/x", "topic"])
+            && stock(&["repack", "-adq"])
+            && stock(&["pack-refs", "--all"])
+        {
+            clean_repo(copy_dir);
+            let copy = &mut GitRepository::new(copy_dir);
+            assert!(copy.init().is_ok());
+            assert_eq!(
+                "Imported 3 blobs, 3 commits and 1 tags, updated 4 refs.",
+                copy.import_git(&git_dir.display().to_string()).unwrap()
+            );
+            assert_eq!(tip, copy.resolve_commit("topic").unwrap());
+            let tag = copy.read_tag("v2").unwrap().unwrap();
+            assert_eq!("release", tag.message());
+            assert_eq!(commit.parent(), tag.object());
+            assert_eq!(
+                vec!["skip refs/heads/feature/x: unsupported ref name".to_string()],
+                copy.take_warnings()
+            );
+        }
+        clean_repo(repo_dir);
+        clean_repo(copy_dir);
+    }
+
+    #[test]
+    fn fetch_ut() {
+        init();
+        let (repo_dir, remote_dir) = (".fetch_ut_repo_dir", ".fetch_ut_remote_dir");
+        let work_dir = &env::current_dir().unwrap().join("fetch_ut");
+        clean_repo(repo_dir);
+        clean_repo(remote_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let upstream = &mut GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        let f1 = &["fetch_ut/f1".to_string()];
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(upstream.add(f1).is_ok());
+        assert!(upstream.commit("first").is_ok());
+
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let main = git.resolve_commit("main").unwrap();
+        assert!(git.fetch("origin", None).is_err());
+        assert!(git.remote_add("origin", remote_dir).is_ok());
+        assert_eq!(
+            vec![
+                format!("From {}", remote_dir),
+                " * [new branch] main -> origin/main".to_string()
+            ],
+            git.fetch("origin", None).unwrap()
+        );
+        let tracking = git
+            .repo_path
+            .join(REMOTE_REFS_DIR)
+            .join("origin")
+            .join("main");
+        let first = upstream.resolve_commit("main").unwrap();
+        assert_eq!(first, fs::read_to_string(&tracking).unwrap());
+        let commit = git.read_commit(&first).unwrap();
+        assert_eq!("first", commit.message());
+        assert_eq!(
+            "v1",
+            git.read_blob_text(&commit.blobs()["fetch_ut/f1"].sha1)
+                .unwrap()
+        );
+        // local branches are left alone
+        assert_eq!(main, git.resolve_commit("main").unwrap());
+        assert_eq!(1, git.fetch("origin", None).unwrap().len());
+
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(upstream.add(f1).is_ok());
+        assert!(upstream.commit("second").is_ok());
+        let second = upstream.resolve_commit("main").unwrap();
+        assert_eq!(
+            format!("   {}..{} main -> origin/main", &first[..7], &second[..7]),
+            git.fetch("origin", None).unwrap()[1]
+        );
+        assert!(upstream.amend(Some("second, amended")).is_ok());
+        let amended = upstream.resolve_commit("main").unwrap();
+        assert_eq!(
+            format!(
+                " + {}...{} main -> origin/main (forced update)",
+                &second[..7],
+                &amended[..7]
+            ),
+            git.fetch("origin", None).unwrap()[1]
+        );
+        assert_eq!(amended, fs::read_to_string(&tracking).unwrap());
+        assert_eq!(
+            "v2",
+            git.read_blob_text(&utils::crypto_string("v2")).unwrap()
+        );
+
+        assert!(git.remote_add("bad", "fetch_ut").is_ok());
+        assert!(git.fetch("bad", None).is_err());
+        clean_repo(repo_dir);
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn clone_ut() {
+        init();
+        let remote_dir = ".clone_ut_remote_dir";
+        let work_dir = &env::current_dir().unwrap().join("clone_ut");
+        let dst = &env::current_dir().unwrap().join("clone_ut_dst");
+        clean_repo(remote_dir);
+        for dir in [work_dir, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let upstream = &mut GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(upstream.add(&["clone_ut/f1".to_string()]).is_ok());
+        assert!(upstream.commit("first").is_ok());
+        assert!(upstream.create_tag("v1", "HEAD", Some("release")).is_ok());
+        assert!(upstream.branch("topic").is_ok());
+        let tip = upstream.resolve_commit("topic").unwrap();
+
+        assert_eq!(
+            "Cloned .clone_ut_remote_dir into clone_ut_dst: 2 branches, 1 tags, checked out topic.",
+            GitRepository::clone_repo(remote_dir, Some("clone_ut_dst"), None).unwrap()
+        );
+        assert_eq!("v1", fs::read_to_string(dst.join("clone_ut/f1")).unwrap());
+        let git = &mut GitRepository::new("clone_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+        assert!(git.load_basic_info().is_ok());
+        assert_eq!("topic", git.current_branch());
+        assert_eq!(tip, git.commit_sha1);
+        assert_eq!(
+            vec!["topic"],
+            git.branches().unwrap().into_keys().collect::<Vec<_>>()
+        );
+        assert_eq!("release", git.read_tag("v1").unwrap().unwrap().message());
+        let remotes = git.remotes().unwrap();
+        assert_eq!(vec!["origin"], remotes.keys().collect::<Vec<_>>());
+        assert!(Path::new(&remotes["origin"]).is_absolute());
+        let tracking = git.repo_path.join(REMOTE_REFS_DIR).join("origin");
+        assert_eq!(tip, fs::read_to_string(tracking.join("main")).unwrap());
+        let status = git.status().unwrap();
+        assert!(status.ends_with("=== Untracked Files ==="), "{}", status);
+        assert!(git.fsck().unwrap().is_empty());
+
+        // the destination must be new or empty, a failed clone leaves nothing behind
+        assert!(GitRepository::clone_repo(remote_dir, Some("clone_ut_dst"), None).is_err());
+        assert!(GitRepository::clone_repo(".clone_ut_missing", None, None).is_err());
+        assert!(!Path::new(".clone_ut_missing").exists());
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn shallow_ut() {
+        init();
+        let remote_dir = ".shallow_ut_remote_dir";
+        let work_dir = &env::current_dir().unwrap().join("shallow_ut");
+        let dst = &env::current_dir().unwrap().join("shallow_ut_dst");
+        clean_repo(remote_dir);
+        for dir in [work_dir, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let upstream = &mut GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        for version in ["v1", "v2", "v3"] {
+            assert!(fs::write(work_dir.join("f1"), version).is_ok());
+            assert!(upstream.add(&["shallow_ut/f1".to_string()]).is_ok());
+            assert!(upstream.commit(version).is_ok());
+        }
+        let tip = upstream.resolve_commit("main").unwrap();
+        let history = upstream.history(&tip).unwrap();
+
+        assert!(GitRepository::clone_repo(remote_dir, Some("shallow_ut_dst"), Some(1)).is_ok());
+        let git = &mut GitRepository::new("shallow_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+        assert_eq!(vec![tip.clone()], git.commit_ids().unwrap());
+        assert_eq!(
+            format!("{}\n", tip),
+            fs::read_to_string(&git.shallow_file).unwrap()
+        );
+        // history stops at the shallow commit
+        let log = git.log().unwrap();
+        assert_eq!(1, log.matches("\ncommit ").count(), "{}", log);
+        assert_eq!(1, git.history(&tip).unwrap().len());
+        assert!(git.fsck().unwrap().is_empty());
+        assert!(git.gc().is_ok());
+        assert!(git.read_commit(&tip).is_ok());
+
+        // deepening moves the cut, fetching without a depth removes it
+        assert!(git.fetch("origin", Some(2)).is_ok());
+        assert_eq!(
+            format!("{}\n", history[1].0),
+            fs::read_to_string(&git.shallow_file).unwrap()
+        );
+        assert_eq!(2, git.history(&tip).unwrap().len());
+        assert!(git.fetch("origin", Some(0)).is_err());
+        assert!(git.fetch("origin", None).is_ok());
+        assert!(!git.shallow_file.exists());
+        assert_eq!(history.len(), git.history(&tip).unwrap().len());
+        assert!(git.fsck().unwrap().is_empty());
+
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn http_fetch_ut() {
+        init();
+        let remote_dir = ".http_fetch_ut_remote_dir";
+        let work_dir = &env::current_dir().unwrap().join("http_fetch_ut");
+        let dst = &env::current_dir().unwrap().join("http_fetch_ut_dst");
+        clean_repo(remote_dir);
+        for dir in [work_dir, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let mut upstream = GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        for (i, content) in ["v1", "v2"].iter().enumerate() {
+            assert!(fs::write(work_dir.join(format!("f{}", i)), content).is_ok());
+            let file = format!("http_fetch_ut/f{}", i);
+            assert!(upstream.add(&[file]).is_ok());
+            assert!(upstream.commit(content).is_ok());
+        }
+        assert!(upstream.create_tag("v1", "HEAD", Some("release")).is_ok());
+        let tip = upstream.resolve_commit("HEAD").unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            crate::http::serve_on(
+                listener,
+                |request| transport::body_limit(request, None),
+                move |request| transport::handle(&mut upstream, request),
+            )
+        });
+
+        assert_eq!(
+            format!(
+                "Cloned {} into http_fetch_ut_dst: 1 branches, 1 tags, checked out main.",
+                url
+            ),
+            GitRepository::clone_repo(&url, Some("http_fetch_ut_dst"), Some(1)).unwrap()
+        );
+        assert_eq!(
+            "v2",
+            fs::read_to_string(dst.join("http_fetch_ut/f1")).unwrap()
+        );
+        let git = &mut GitRepository::new("http_fetch_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+        assert!(git.load_basic_info().is_ok());
+        assert_eq!(tip, git.commit_sha1);
+        assert_eq!(url, git.remotes().unwrap()["origin"]);
+        assert_eq!("release", git.read_tag("v1").unwrap().unwrap().message());
+        assert_eq!(
+            BTreeSet::from([tip.clone()]),
+            git.shallow_commits().unwrap()
+        );
+
+        // fetching the whole history over http unshallows the clone
+        assert!(git.fetch("origin", None).unwrap().len() == 1);
+        assert!(git.shallow_commits().unwrap().is_empty());
+        assert_eq!(3, git.log().unwrap().matches("commit ").count());
+        assert!(git.fsck().unwrap().is_empty());
+
+        let err = GitRepository::clone_repo("https://127.0.0.1:1/", None, None).unwrap_err();
+        assert_eq!(
+            !cfg!(feature = "tls"),
+            err.to_string()
+                .contains("https needs git-rs built with the tls feature")
+        );
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn push_ut() {
+        init();
+        let remote_dir = ".push_ut_remote_dir";
+        let work_dir = &env::current_dir().unwrap().join("push_ut");
+        let dst = &env::current_dir().unwrap().join("push_ut_dst");
+        clean_repo(remote_dir);
+        for dir in [work_dir, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let mut upstream = GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        assert!(upstream.branch("shared").is_ok());
+        assert!(upstream.checkout_branch("main").is_ok());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            crate::http::serve_on(
+                listener,
+                |request| transport::body_limit(request, None),
+                move |request| transport::handle(&mut upstream, request),
+            )
+        });
+        assert!(GitRepository::clone_repo(&url, Some("push_ut_dst"), None).is_ok());
+        let git = &mut GitRepository::new("push_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+        assert!(git.branch("shared").is_ok());
+        assert!(fs::write(dst.join("f1"), "v1").is_ok());
+        assert!(git.add(&["f1".to_string()]).is_ok());
+        assert!(git.commit("first").is_ok());
+        let tip = git.resolve_commit("HEAD").unwrap();
+
+        let lines = git.push("origin", None).unwrap();
+        assert_eq!(format!("To {}", url), lines[0]);
+        let upstream = &mut GitRepository::new(remote_dir);
+        assert_eq!(tip, upstream.resolve_commit("shared").unwrap());
+        assert_eq!(
+            b"v1".to_vec(),
+            upstream
+                .blob_content(&git.read_commit(&tip).unwrap().blobs["f1"].sha1)
+                .unwrap()
+        );
+        let tracking = git.repo_path.join(REMOTE_REFS_DIR).join("origin");
+        assert_eq!(tip, fs::read_to_string(tracking.join("shared")).unwrap());
+
+        // new branches are created, the checked out one and rewritten history are refused
+        assert!(git.branch("topic").is_ok());
+        let lines = git.push("origin", None).unwrap();
+        assert_eq!(" * [new branch] topic", format!(" {}", lines[1]));
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(dst.join("f2"), "v2").is_ok());
+        assert!(git.add(&["f2".to_string()]).is_ok());
+        assert!(git.commit("second").is_ok());
+        let lines = git.push("origin", None).unwrap();
+        assert_eq!("! [rejected] main (checked out)", lines[1]);
+        let main = git.resolve_commit("HEAD").unwrap();
+        assert!(fs::write(git.heads_path.join("shared"), main).is_ok());
+        let lines = git.push("origin", Some("shared")).unwrap();
+        assert_eq!("! [rejected] shared (non-fast-forward)", lines[1]);
+        assert_eq!(tip, fs::read_to_string(tracking.join("shared")).unwrap());
+        assert_eq!(tip, upstream.resolve_commit("shared").unwrap());
+        // objects of rejected pushes are kept until gc, like any unreachable object
+        let problems = upstream.fsck().unwrap();
+        assert!(
+            problems.iter().all(|p| p.starts_with("dangling")),
+            "{:?}",
+            problems
+        );
+
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn large_push_ut() {
+        init();
+        let remote_dir = ".large_push_ut_remote_dir";
+        let dst = &env::current_dir().unwrap().join("large_push_ut_dst");
+        clean_repo(remote_dir);
+        if dst.exists() {
+            assert!(fs::remove_dir_all(dst).is_ok());
+        }
+        let upstream = GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        assert!(upstream.set_config("receive.maxInputSize", "0").is_ok());
+        assert_eq!(None, upstream.max_input_size().unwrap());
+        let serve = |mut repo: GitRepository| {
+            let max_input = repo.max_input_size().unwrap();
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            std::thread::spawn(move || {
+                crate::http::serve_on(
+                    listener,
+                    |request| transport::body_limit(request, max_input),
+                    move |request| transport::handle(&mut repo, request),
+                )
+            });
+            url
+        };
+        let url = serve(upstream);
+        assert!(GitRepository::clone_repo(&url, Some("large_push_ut_dst"), None).is_ok());
+        let git = &mut GitRepository::new("large_push_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+
+        // pushes are not held to the limit of other requests
+        let large = "a line of a large file\n".repeat(crate::http::MAX_REQUEST_LEN / 20);
+        assert!(large.len() > crate::http::MAX_REQUEST_LEN);
+        assert!(git.checkout_create("large", None).is_ok());
+        assert!(fs::write(dst.join("f1"), &large).is_ok());
+        assert!(git.add(&["f1".to_string()]).is_ok());
+        assert!(git.commit("large").is_ok());
+        let lines = git.push("origin", None).unwrap();
+        assert_eq!("* [new branch] large", lines[1]);
+
+        // receive.maxInputSize refuses larger pushes with a reason
+        let limited = GitRepository::new(remote_dir);
+        assert!(limited.set_config("receive.maxInputSize", "1000").is_ok());
+        let url = serve(limited);
+        assert!(git.remote_add("limited", &url).is_ok());
+        assert!(fs::write(dst.join("f2"), "x".repeat(2000)).is_ok());
+        assert!(git.add(&["f2".to_string()]).is_ok());
+        assert!(git.commit("over the limit").is_ok());
+        let err = git.push("limited", None).unwrap_err().to_string();
+        assert!(err.contains("413"), "{}", err);
+        assert!(
+            err.contains("larger than the limit of 1000 bytes"),
+            "{}",
+            err
+        );
+
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn daemon_ut() {
+        init();
+        let base = &env::current_dir().unwrap().join("daemon_ut");
+        let dst = &env::current_dir().unwrap().join("daemon_ut_dst");
+        for dir in [base, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        for name in ["public", "private"] {
+            let git = &mut GitRepository::new(&format!("daemon_ut/{}/.git-rs", name));
+            git.cwd = base.join(name);
+            assert!(git.init().is_ok());
+        }
+        let exports = ["public".to_string()];
+        assert!(Daemon::new(base, &["missing".to_string()]).is_err());
+        assert!(Daemon::new(base, &["../daemon_ut".to_string()]).is_err());
+        let daemon = Daemon::new(base, &exports).unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("git-rs://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || daemon.serve_on(listener));
+
+        let public = format!("{}/public", url);
+        assert_eq!(
+            format!(
+                "Cloned {} into daemon_ut_dst: 1 branches, 0 tags, checked out main.",
+                public
+            ),
+            GitRepository::clone_repo(&public, Some("daemon_ut_dst"), None).unwrap()
+        );
+        let git = &mut GitRepository::new("daemon_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+        assert!(git.load_basic_info().is_ok());
+        let upstream = GitRepository::open_dir(&base.join("public")).unwrap();
+        assert_eq!(upstream.resolve_commit("main").unwrap(), git.commit_sha1);
+
+        // only exported repositories exist and nothing can be pushed
+        let private = format!("{}/private", url);
+        assert!(GitRepository::clone_repo(&private, Some("daemon_ut_private"), None).is_err());
+        assert!(!Path::new("daemon_ut_private").exists());
+        assert!(git.branch("topic").is_ok());
+        let err = git.push("origin", None).unwrap_err();
+        assert!(err.to_string().contains("read-only"), "{}", err);
+
+        assert!(fs::remove_dir_all(base).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn remote_branches_ut() {
+        init();
+        let remote_dir = ".remote_branches_ut_remote_dir";
+        let work_dir = &env::current_dir().unwrap().join("remote_branches_ut");
+        let dst = &env::current_dir().unwrap().join("remote_branches_ut_dst");
+        clean_repo(remote_dir);
+        for dir in [work_dir, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let upstream = &mut GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        assert!(upstream.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(upstream.add(&["remote_branches_ut/f1".to_string()]).is_ok());
+        assert!(upstream.commit("first").is_ok());
+        assert!(upstream.create_tag("v1", "HEAD", None).is_ok());
+        assert!(upstream.checkout_branch("main").is_ok());
+        let main = upstream.resolve_commit("main").unwrap();
+        let topic = upstream.resolve_commit("topic").unwrap();
+        let dst_name = "remote_branches_ut_dst";
+        assert!(GitRepository::clone_repo(remote_dir, Some(dst_name), None).is_ok());
+        let git = &mut GitRepository::new(&format!("{}/.git-rs", dst_name));
+        git.cwd = dst.clone();
+
+        assert_eq!(vec!["* main"], git.branch_list(false).unwrap());
+        assert_eq!(
+            vec!["* main", "  remotes/origin/main", "  remotes/origin/topic"],
+            git.branch_list(true).unwrap()
+        );
+        assert_eq!(topic, git.resolve_commit("origin/topic").unwrap());
+        assert!(git.resolve_commit("origin/missing").is_err());
+
+        // checking out a remote-tracking branch creates the local branch
+        assert!(git.checkout_branch("origin/main").is_err());
+        assert!(git.checkout_branch("topic").is_ok());
+        assert_eq!("topic", git.current_branch());
+        assert_eq!(topic, git.commit_sha1);
+        assert!(dst.join("remote_branches_ut/f1").is_file());
+        assert!(git.checkout_branch("missing").is_err());
+        assert!(!git.heads_path.join("missing").exists());
+
+        let log = git
+            .log_with(&LogOptions {
+                decorate: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let decorated = format!("commit {} (HEAD -> topic, origin/topic, tag: v1)\n", topic);
+        assert!(log.contains(&decorated), "{}", log);
+        let decorated = format!("commit {} (main, origin/main)\n", main);
+        assert!(log.contains(&decorated), "{}", log);
+        assert!(!git.log().unwrap().contains("(HEAD"));
+        assert!(git.graph_dot().unwrap().contains("origin/topic"));
+
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn upstream_ut() {
+        init();
+        let remote_dir = ".upstream_ut_remote_dir";
+        let work_dir = &env::current_dir().unwrap().join("upstream_ut");
+        let dst = &env::current_dir().unwrap().join("upstream_ut_dst");
+        clean_repo(remote_dir);
+        for dir in [work_dir, dst] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let upstream = &mut GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        assert!(GitRepository::clone_repo(remote_dir, Some("upstream_ut_dst"), None).is_ok());
+        let git = &mut GitRepository::new("upstream_ut_dst/.git-rs");
+        git.cwd = dst.clone();
+        let status = git.status().unwrap();
+        assert!(
+            status.starts_with("Your branch is up to date with 'origin/main'.\n\n=== Branches"),
+            "{}",
+            status
+        );
+
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(upstream.add(&["upstream_ut/f1".to_string()]).is_ok());
+        assert!(upstream.commit("upstream").is_ok());
+        assert!(git.fetch("origin", None).is_ok());
+        assert!(git.status().unwrap().starts_with(
+            "Your branch is behind 'origin/main' by 1 commit, and can be fast-forwarded."
+        ));
+        assert!(fs::write(dst.join("f2"), "v2").is_ok());
+        assert!(git.add(&["f2".to_string()]).is_ok());
+        assert!(git.commit("local").is_ok());
+        assert!(git.status().unwrap().starts_with(
+            "Your branch and 'origin/main' have diverged,\n\
+             and have 1 and 1 different commits each, respectively."
+        ));
+        assert!(git.merge("origin/main", &MergeOptions::default()).is_ok());
+        assert!(git
+            .status()
+            .unwrap()
+            .starts_with("Your branch is ahead of 'origin/main' by 2 commits."));
+
+        assert!(git.branch("topic").is_ok());
+        assert!(git.status().unwrap().starts_with("=== Branches"));
+        assert!(git.set_upstream("origin/missing", None).is_err());
+        assert!(git.set_upstream("origin/main", Some("missing")).is_err());
+        assert_eq!(
+            "branch 'topic' set up to track 'origin/main'.",
+            git.set_upstream("origin/main", None).unwrap()
+        );
+        assert!(fs::remove_file(git.repo_path.join(REMOTE_REFS_DIR).join("origin/main")).is_ok());
+        assert!(git
+            .status()
+            .unwrap()
+            .starts_with("Your branch is based on 'origin/main', but the upstream is gone."));
+        assert!(git.remote_remove("origin").is_ok());
+        assert!(git.status().unwrap().starts_with("=== Branches"));
+
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(dst).is_ok());
+    }
+
+    #[test]
+    fn force_push_ut() {
+        init();
+        let remote_dir = ".force_push_ut_remote_dir";
+        let ours = &env::current_dir().unwrap().join("force_push_ut_ours");
+        let theirs = &env::current_dir().unwrap().join("force_push_ut_theirs");
+        clean_repo(remote_dir);
+        for dir in [ours, theirs] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        let mut upstream = GitRepository::new(remote_dir);
+        assert!(upstream.init().is_ok());
+        assert!(upstream.branch("shared").is_ok());
+        assert!(upstream.checkout_branch("main").is_ok());
+        let init_commit = upstream.resolve_commit("main").unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            crate::http::serve_on(
+                listener,
+                |request| transport::body_limit(request, None),
+                move |request| transport::handle(&mut upstream, request),
+            )
+        });
+        let mut clones = vec![];
+        for (dir, file) in [(ours, "f1"), (theirs, "f2")] {
+            let name = dir.file_name().unwrap().to_str().unwrap();
+            assert!(GitRepository::clone_repo(&url, Some(name), None).is_ok());
+            let mut git = GitRepository::new(&format!("{}/.git-rs", name));
+            git.cwd = dir.clone();
+            assert!(git.checkout_branch("shared").is_ok());
+            assert!(git.fetch("origin", None).is_ok());
+            assert!(git.merge("origin/shared", &MergeOptions::default()).is_ok());
+            assert!(fs::write(dir.join(file), file).is_ok());
+            assert!(git.add(&[file.to_string()]).is_ok());
+            assert!(git.commit(file).is_ok());
+            assert!(!git.push("origin", None).unwrap()[1].starts_with('!'));
+            clones.push(git);
+        }
+        let theirs_tip = clones[1].commit_sha1.clone();
+        let git = &mut clones[0];
+        let upstream = GitRepository::new(remote_dir);
+
+        // rewind our shared branch, the remote has commits we have not seen
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(git.heads_path.join("shared"), &init_commit).is_ok());
+        let lease = PushOptions {
+            force_with_lease: true,
+            ..Default::default()
+        };
+        let lines = git.push_with("origin", Some("shared"), &lease).unwrap();
+        assert_eq!("! [rejected] shared (stale info)", lines[1]);
+        assert_eq!(theirs_tip, upstream.resolve_commit("shared").unwrap());
+
+        // once fetched, the lease holds
+        assert!(git.fetch("origin", None).is_ok());
+        let lines = git.push_with("origin", Some("shared"), &lease).unwrap();
+        let forced = format!(
+            "+ {}...{} shared (forced update)",
+            &theirs_tip[..7],
+            &init_commit[..7]
+        );
+        assert_eq!(forced, lines[1]);
+        assert_eq!(init_commit, upstream.resolve_commit("shared").unwrap());
+        let tracking = git.repo_path.join(REMOTE_REFS_DIR).join("origin/shared");
+        assert_eq!(init_commit, fs::read_to_string(&tracking).unwrap());
+
+        // --force needs no lease
+        assert!(fs::write(git.heads_path.join("shared"), &theirs_tip).is_ok());
+        assert!(git.push("origin", Some("shared")).is_ok());
+        assert!(fs::write(git.heads_path.join("shared"), &init_commit).is_ok());
+        assert!(git.push("origin", Some("shared")).unwrap()[1].starts_with("! [rejected]"));
+        let force = PushOptions {
+            force: true,
+            ..Default::default()
+        };
+        let lines = git.push_with("origin", Some("shared"), &force).unwrap();
+        assert!(lines[1].ends_with("shared (forced update)"), "{:?}", lines);
+        assert_eq!(init_commit, upstream.resolve_commit("shared").unwrap());
+
+        clean_repo(remote_dir);
+        assert!(fs::remove_dir_all(ours).is_ok());
+        assert!(fs::remove_dir_all(theirs).is_ok());
+    }
+
+    #[test]
+    fn ancestry_ut() {
+        init();
+        let repo_dir = ".ancestry_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("ancestry_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let initial = git.resolve_commit("HEAD").unwrap();
+        let commit = |git: &mut GitRepository, file: &str, content: &str| {
+            assert!(fs::write(work_dir.join(file), content).is_ok());
+            assert!(git.add(&[format!("ancestry_ut/{}", file)]).is_ok());
+            assert!(git.commit(content).is_ok());
+            git.resolve_commit("HEAD").unwrap()
+        };
+        let first = commit(git, "f1", "v1");
+        assert!(git.branch("topic").is_ok());
+        let topic = commit(git, "f2", "topic");
+        assert!(git.checkout_branch("main").is_ok());
+        let second = commit(git, "f1", "v2");
+        assert!(git.merge("topic", &MergeOptions::default()).is_ok());
+        let merge = git.resolve_commit("HEAD").unwrap();
+
+        for (rev, sha1) in [
+            ("HEAD^0", &merge),
+            ("HEAD^", &second),
+            ("HEAD~", &second),
+            ("HEAD^1", &second),
+            ("HEAD^2", &topic),
+            ("HEAD~2", &first),
+            ("main^^", &first),
+            ("HEAD^2~1", &first),
+            ("topic~2", &initial),
+            (&format!("{}~1", &merge[..8]), &second),
+        ] {
+            assert_eq!(*sha1, git.resolve_commit(rev).unwrap(), "{}", rev);
+        }
+        for rev in ["HEAD^3", "HEAD~4", "missing~1", "~1", "HEAD~x"] {
+            assert!(git.resolve_commit(rev).is_err(), "{}", rev);
+        }
+
+        assert!(git.checkout_file_from("HEAD~1", "ancestry_ut/f1").is_ok());
+        assert_eq!("v2", fs::read_to_string(work_dir.join("f1")).unwrap());
+        assert!(git.checkout_file_from("main~2", "ancestry_ut/f1").is_ok());
+        assert_eq!("v1", fs::read_to_string(work_dir.join("f1")).unwrap());
+        assert!(git.checkout_file_from("main~2", "ancestry_ut/f2").is_err());
+        assert!(git
+            .diff_commits("HEAD~2", "HEAD")
+            .unwrap()
+            .contains("ancestry_ut/f2"));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn range_ut() {
+        init();
+        let repo_dir = ".range_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("range_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["range_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("base"), "base").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        let base = git.resolve_commit("HEAD").unwrap();
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("t"), "t").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("t1").is_ok());
+        assert!(git.commit("t2").is_ok());
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(work_dir.join("m"), "m").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("m1").is_ok());
+
+        let messages = |git: &GitRepository, range: &str| -> Vec<String> {
+            git.commit_range(range)
+                .unwrap()
+                .iter()
+                .map(|(_, c)| c.message().to_string())
+                .collect()
+        };
+        assert_eq!(vec!["t2", "t1"], messages(git, "main..topic"));
+        assert_eq!(vec!["m1"], messages(git, "topic..main"));
+        assert_eq!(vec!["m1"], messages(git, "topic.."));
+        let mut symmetric = messages(git, "main...topic");
+        symmetric.sort();
+        assert_eq!(vec!["m1", "t1", "t2"], symmetric);
+        assert!(messages(git, "main...main").is_empty());
+        assert!(git.commit_range("main...missing").is_err());
+
+        let diff = git.diff_range_with("main..topic", &DiffOptions::default());
+        let diff = diff.unwrap();
+        assert!(diff.contains("+++ b/range_ut/t") && diff.contains("--- a/range_ut/m"));
+        let diff = git.diff_range_with("main...topic", &DiffOptions::default());
+        let diff = diff.unwrap();
+        assert!(diff.contains("+++ b/range_ut/t") && !diff.contains("range_ut/m"));
+        assert_eq!(
+            git.diff_commits("main", "topic").unwrap(),
+            git.diff_range_with("main..topic", &DiffOptions::default())
+                .unwrap()
+        );
+        assert!(git.range_ends("main").is_err());
+
+        // merged commits are in the range, each after its children
+        assert!(git.merge("topic", &MergeOptions::default()).is_ok());
+        let merged = messages(git, &format!("{}..main", base));
+        assert_eq!(4, merged.len());
+        assert_eq!("Merged topic into main.", merged[0]);
+        let at = |message: &str| merged.iter().position(|m| m == message).unwrap();
+        assert!(at("t2") < at("t1"));
+        assert!(messages(git, "main..topic").is_empty());
+        assert!(git.range_ends("main...topic").is_ok());
+
+        let log = git
+            .log_with(&LogOptions {
+                range: Some("topic..main".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(2, log.matches("commit ").count(), "{}", log);
+        assert!(log.contains("m1") && !log.contains("t1"));
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn log_filter_ut() {
+        init();
+        let repo_dir = ".log_filter_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.set_config("user.name", "Jane").is_ok());
+        assert!(git.set_config("user.email", "jane@example.com").is_ok());
+        assert!(git.commit("feat: one").is_ok());
+        let bob = CommitOptions {
+            author: Some("Bob <bob@example.com>".to_string()),
+            ..Default::default()
+        };
+        assert!(git.commit_with("fix: two", &[], &bob).is_ok());
+        assert!(git.commit("fix: three").is_ok());
+        let id = |rev: &str| format!("commit {}", git.resolve_commit(rev).unwrap());
+        let (one, two, three) = (id("HEAD~2"), id("HEAD~1"), id("HEAD"));
+
+        let mut log = |options: LogOptions| -> Vec<String> {
+            git.log_with(&options)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("commit "))
+                .map(|line| line.to_string())
+                .collect()
+        };
+        assert_eq!(4, log(LogOptions::default()).len());
+        let grep = |pattern: &str| LogOptions {
+            grep: Some(pattern.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(vec![three.clone(), two.clone()], log(grep("^fix")));
+        let first_fix = LogOptions {
+            max_count: Some(1),
+            ..grep("^fix")
+        };
+        assert_eq!(vec![three.clone()], log(first_fix));
+        let author = |author: &str| LogOptions {
+            author: Some(author.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(vec![two], log(author("bob@")));
+        assert_eq!(vec![three, one], log(author("Jane")));
+        assert!(log(author("jane <")).is_empty());
+
+        let dates = |since: &str, until: &str| LogOptions {
+            since: Some(since.to_string()),
+            until: Some(until.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(4, log(dates("1 hour ago", "now")).len());
+        assert!(log(dates("2000-01-01", "@0")).is_empty());
+        assert!(log(dates("@99999999999", "@99999999999")).is_empty());
+        assert!(git.log_with(&dates("soon", "now")).is_err());
+        assert!(git.log_with(&grep("(")).is_err());
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn follow_ut() {
+        init();
+        let repo_dir = ".follow_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("follow_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["follow_ut".to_string()];
+        let (a, c) = ("follow_ut/a", "follow_ut/c");
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("a"), "1\n2\n3\n4\n").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add a").is_ok());
+        assert!(fs::write(work_dir.join("b"), "b").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add b").is_ok());
+        assert!(fs::write(work_dir.join("a"), "1\n2\n3\n4\n5\n").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("edit a").is_ok());
+        assert!(fs::remove_file(work_dir.join("a")).is_ok());
+        assert!(fs::write(work_dir.join("c"), "1\n2\n3\n4\n5\n6\n").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("rename a to c").is_ok());
+        assert!(fs::write(work_dir.join("c"), "0\n1\n2\n3\n4\n5\n6\n").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("edit c").is_ok());
+
+        let head = git.resolve_commit("HEAD").unwrap();
+        let followed: Vec<(String, String)> = git
+            .follow_history(&head, c)
+            .unwrap()
+            .into_iter()
+            .map(|(_, commit, path)| (commit.message().to_string(), path))
+            .collect();
+        let expected = [
+            ("edit c", c),
+            ("rename a to c", c),
+            ("edit a", a),
+            ("add a", a),
+        ]
+        .map(|(message, path)| (message.to_string(), path.to_string()));
+        assert_eq!(expected.to_vec(), followed);
+        assert_eq!(2, git.file_history(&head, c).unwrap().len());
+        // a removed file is not followed into the file replacing it
+        assert_eq!(3, git.follow_history(&head, a).unwrap().len());
+
+        let log = git
+            .log_with(&LogOptions {
+                follow: Some(c.to_string()),
+                grep: Some("edit".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(2, log.matches("\ncommit ").count(), "{}", log);
+        assert!(log.contains("edit a") && !log.contains("add b"));
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn log_stat_ut() {
+        init();
+        let repo_dir = ".log_stat_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("log_stat_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["log_stat_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f"), "a\nb\nc\n").is_ok());
+        assert!(fs::write(work_dir.join("bin"), b"\0\x01").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add files").is_ok());
+        assert!(fs::write(work_dir.join("f"), "a\nB\nc\nd\n").is_ok());
+        assert!(fs::remove_file(work_dir.join("bin")).is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("edit f, remove bin").is_ok());
+
+        let log = git
+            .log_with(&LogOptions {
+                stat: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let edit = "edit f, remove bin\n\n log_stat_ut/bin | Bin\n log_stat_ut/f   |   3 ++-\n \
+                    2 files changed, 2 insertions(+), 1 deletion(-)\n\n\n\n===";
+        assert!(log.contains(edit), "{}", log);
+        let add = "add files\n\n log_stat_ut/bin | Bin\n log_stat_ut/f   |   3 +++\n \
+                   2 files changed, 3 insertions(+)\n\n\n\n===";
+        assert!(log.contains(add), "{}", log);
+        assert!(log.ends_with("initial commit\n\n\n"), "{}", log);
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn status_porcelain_ut() {
+        init();
+        let repo_dir = ".status_porcelain_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("status_porcelain_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let path = |name: &str| format!("status_porcelain_ut/{}", name);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        for name in ["changed", "gone", "removed", "staged", "both"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        assert!(git.add_all(&["status_porcelain_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+        // the files of this repository outside the test directory are untracked
+        let status = |git: &mut GitRepository| -> String {
+            let status = git.status_porcelain().unwrap();
+            let lines = status
+                .lines()
+                .filter(|l| l.contains("status_porcelain_ut/"));
+            lines.map(|line| format!("{}\n", line)).collect()
+        };
+        assert_eq!("", status(git));
+
+        assert!(fs::write(work_dir.join("changed"), "v2").is_ok());
+        assert!(fs::remove_file(work_dir.join("gone")).is_ok());
+        assert!(git.remove(&[path("removed")]).is_ok());
+        assert!(fs::write(work_dir.join("staged"), "v2").is_ok());
+        assert!(fs::write(work_dir.join("both"), "v2").is_ok());
+        assert!(fs::write(work_dir.join("new"), "new").is_ok());
+        assert!(fs::write(work_dir.join("new_changed"), "new").is_ok());
+        assert!(git
+            .add(&[path("staged"), path("both"), path("new_changed")])
+            .is_ok());
+        assert!(fs::write(work_dir.join("both"), "v3").is_ok());
+        assert!(fs::write(work_dir.join("new_changed"), "v2").is_ok());
+        assert!(fs::write(work_dir.join("untracked"), "?").is_ok());
+        let expected = [
+            "MM both",
+            " M changed",
+            " D gone",
+            "AM new_changed",
+            "D  removed",
+            "M  staged",
+            "?? new",
+            "?? untracked",
+        ]
+        .map(|line| format!("{}{}\n", &line[..3], path(&line[3..])))
+        .concat();
+        assert_eq!(expected, status(git));
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn status_short_ut() {
+        init();
+        let repo_dir = ".status_short_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("status_short_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let path = |name: &str| format!("status_short_ut/{}", name);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        for name in ["gone", "both"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        assert!(git.add_all(&["status_short_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+        assert!(fs::remove_file(work_dir.join("gone")).is_ok());
+        assert!(fs::write(work_dir.join("both"), "v2").is_ok());
+        assert!(git.add(&[path("both")]).is_ok());
+        assert!(fs::write(work_dir.join("both"), "v3").is_ok());
+        assert!(fs::write(work_dir.join("new"), "new").is_ok());
+
+        // the porcelain lines, with the staged and unstaged columns colored apart
+        let short = git.status_short(&StatusOptions::default()).unwrap();
+        assert_eq!(git.status_porcelain().unwrap(), short);
+        let short = git.status_short(&StatusOptions { color: true }).unwrap();
+        let both = format!("\x1b[32mM\x1b[m\x1b[31mM\x1b[m {}\n", path("both"));
+        let gone = format!(" \x1b[31mD\x1b[m {}\n", path("gone"));
+        let new = format!("\x1b[36m?? {}\x1b[m\n", path("new"));
+        assert!(short.contains(&both) && short.contains(&gone) && short.contains(&new));
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn status_json_ut() {
+        init();
+        let repo_dir = ".status_json_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("status_json_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let path = |name: &str| format!("status_json_ut/{}", name);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        for name in ["removed", "staged"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        assert!(git.add_all(&["status_json_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+        assert!(git.remove(&[path("removed")]).is_ok());
+        assert!(fs::write(work_dir.join("staged"), "v2").is_ok());
+        assert!(git.add(&[path("staged")]).is_ok());
+
+        let json = git.status_json().unwrap();
+        let report: StatusReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(git.status_report().unwrap(), report);
+        assert_eq!(
+            ("main", vec![path("removed")]),
+            (report.branch.as_str(), report.removed)
+        );
+        assert!(json.contains("\n  \"staged\": [\n"), "{}", json);
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn color_ut() {
+        init();
+        let repo_dir = ".color_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("color_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.color_enabled(true).unwrap() && !git.color_enabled(false).unwrap());
+        assert!(git.set_config("color.ui", "always").is_ok());
+        assert!(git.color_enabled(false).unwrap());
+        assert!(git.set_config("color.ui", "never").is_ok());
+        assert!(!git.color_enabled(true).unwrap());
+        assert!(git.set_config("color.ui", "rainbow").is_ok());
+        assert!(git.color_enabled(true).is_err());
+
+        assert!(fs::write(work_dir.join("f1"), "one\n").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "two\n").is_ok());
+        assert!(git.add(&["color_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "uno\n").is_ok());
+        let status = git.status_with(&StatusOptions { color: true }).unwrap();
+        assert!(
+            status.contains("\x1b[31mcolor_ut/f1 (modified)\x1b[m"),
+            "{}",
+            status
+        );
+        assert!(status.contains("\x1b[36mcolor_ut/f2\x1b[m"), "{}", status);
+        assert!(git.add(&["color_ut/f2".to_string()]).is_ok());
+        let status = git.status_with(&StatusOptions { color: true }).unwrap();
+        assert!(
+            status.contains("===\n\x1b[32mcolor_ut/f2\x1b[m"),
+            "{}",
+            status
+        );
+        assert!(!git.status().unwrap().contains('\x1b'));
+
+        let options = DiffOptions {
+            color: true,
+            ..Default::default()
+        };
+        let diff = git.diff_with(&options).unwrap();
+        assert!(
+            diff.contains("\x1b[31m-one\x1b[m\n\x1b[32m+uno\x1b[m\n"),
+            "{}",
+            diff
+        );
+        assert_eq!(
+            git.diff().unwrap(),
+            diff.replace("\x1b[1m", "")
+                .replace("\x1b[31m", "")
+                .replace("\x1b[32m", "")
+                .replace("\x1b[36m", "")
+                .replace("\x1b[m", "")
+        );
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn octopus_merge_ut() {
+        init();
+        let repo_dir = ".octopus_merge_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("octopus_merge_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["octopus_merge_ut".to_string()];
+        let options = &MergeOptions::default();
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f0"), "base").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        let commit_on = |git: &mut GitRepository, branch: &str, file: &str, content: &str| {
+            assert!(git.branch(branch).is_ok());
+            assert!(fs::write(work_dir.join(file), content).is_ok());
+            assert!(git.add_all(all).is_ok());
+            assert!(git.commit(branch).is_ok());
+            assert!(git.checkout_branch("main").is_ok());
+            git.resolve_commit(branch).unwrap()
+        };
+        let b1 = commit_on(git, "b1", "f1", "one");
+        let b2 = commit_on(git, "b2", "f2", "two");
+        let b3 = commit_on(git, "b3", "f0", "three");
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert!(git
+            .merge_branches(&names(&["b1", "missing"]), options)
+            .is_err());
+        assert!(git
+            .merge_branches(&names(&["b1", "main"]), options)
+            .is_err());
+
+        let main = git.resolve_commit("main").unwrap();
+        assert_eq!(
+            "",
+            git.merge_branches(&names(&["b1", "b2", "b3", "b2"]), options)
+                .unwrap()
+        );
+        assert_eq!(vec![main.as_str(), &b1, &b2, &b3], git.commit.parents());
+        assert_eq!("Merged b1, b2 and b3 into main.", git.commit.message());
+        for (file, content) in [("f0", "three"), ("f1", "one"), ("f2", "two")] {
+            assert_eq!(content, fs::read_to_string(work_dir.join(file)).unwrap());
+        }
+        let merge_line = format!(
+            "Merge: {} {} {} {}\n",
+            &main[..7],
+            &b1[..7],
+            &b2[..7],
+            &b3[..7]
+        );
+        assert!(git.log().unwrap().contains(&merge_line));
+        assert_eq!(
+            "Given branches are ancestors of the current branch.",
+            git.merge_branches(&names(&["b1", "b2"]), options).unwrap()
+        );
+
+        // conflicting branches are merged one by one
+        let c1 = commit_on(git, "c1", "f0", "c1");
+        commit_on(git, "c2", "f0", "c2");
+        let c3 = commit_on(git, "c3", "f3", "c3");
+        assert_eq!(
+            "Current branch fast-forwarded.\nEncountered a merge conflict.",
+            git.merge_branches(&names(&["c1", "c2", "c3"]), options)
+                .unwrap()
+        );
+        assert_eq!(
+            git.resolve_commit("c2").unwrap(),
+            git.commit.merge_parents[0]
+        );
+        assert_eq!(c1, git.commit.parent);
+        let head = git.commit_sha1.clone();
+        assert!(!git.ancestors(&head).unwrap().contains(&c3));
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn merge_abort_ut() {
+        init();
+        let repo_dir = ".merge_abort_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("merge_abort_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["merge_abort_ut".to_string()];
+        let options = &MergeOptions::default();
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        for (file, content) in [("f0", "base"), ("f1", "keep"), ("f3", "gone")] {
+            assert!(fs::write(work_dir.join(file), content).is_ok());
+        }
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f0"), "theirs").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "new").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("topic").is_ok());
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(work_dir.join("f0"), "ours").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("ours").is_ok());
+        assert!(git.merge_abort().is_err());
+
+        let main = git.commit_sha1.clone();
+        assert!(fs::write(work_dir.join("f1"), "dirty").is_ok());
+        assert!(fs::remove_file(work_dir.join("f3")).is_ok());
+        assert_eq!(
+            "Encountered a merge conflict.",
+            git.merge("topic", options).unwrap()
+        );
+        assert!(git.merge_state_file.is_file());
+        // the backups of the dirty files survive gc
+        assert!(git.gc().is_ok());
+        assert!(git.merge_abort().is_ok());
+        assert_eq!(main, git.resolve_commit("main").unwrap());
+        assert_eq!("ours", fs::read_to_string(work_dir.join("f0")).unwrap());
+        assert_eq!("dirty", fs::read_to_string(work_dir.join("f1")).unwrap());
+        assert!(!work_dir.join("f2").exists());
+        assert!(!work_dir.join("f3").exists());
+        assert!(git.staging_area.is_empty());
+        assert!(!git.merge_state_file.exists());
+        assert!(git.merge_abort().is_err());
+
+        // only the merge commit can be undone
+        assert!(git.merge("topic", options).is_ok());
+        assert!(fs::write(work_dir.join("f0"), "resolved").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("resolved").is_ok());
+        assert!(git.merge_abort().is_err());
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn compression_ut() {
+        init();
+        let repo_dir = ".compression_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("compression_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let f1 = &["compression_ut/f1".to_string()];
+
+        let plain = "plain text\n".repeat(100);
+        assert!(fs::write(work_dir.join("f1"), &plain).is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("plain").is_ok());
+        let plain_sha1 = utils::crypto_string(&plain);
+        assert_eq!(
+            plain,
+            fs::read_to_string(git.blob_file(&plain_sha1).unwrap()).unwrap()
+        );
+
+        assert!(git.set_config("core.compression", "9").is_ok());
+        let text = "compressed text\n".repeat(100);
+        assert!(fs::write(work_dir.join("f1"), &text).is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("compressed").is_ok());
+        let sha1 = utils::crypto_string(&text);
+        let size = fs::metadata(git.blob_file(&sha1).unwrap()).unwrap().len();
+        assert!(size < text.len() as u64 / 10, "{}", size);
+        let head = git.resolve_commit("HEAD").unwrap();
+        assert_eq!(0, fs::read(git.commit_file(&head).unwrap()).unwrap()[0]);
+
+        assert!(git.set_config("core.compressionAlgorithm", "zstd").is_ok());
+        let zstd_text = "zstd text\n".repeat(100);
+        assert!(fs::write(work_dir.join("f1"), &zstd_text).is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("zstd").is_ok());
+        let zstd_sha1 = utils::crypto_string(&zstd_text);
+        assert!(fs::read(git.blob_file(&zstd_sha1).unwrap())
+            .unwrap()
+            .starts_with(b"\0zstd\n"));
+
+        // all kinds of objects read back whatever the settings
+        assert!(git.set_config("core.compression", "0").is_ok());
+        assert!(git.unset_config("core.compressionAlgorithm").is_ok());
+        assert_eq!(zstd_text, git.read_blob_text(&zstd_sha1).unwrap());
+        assert_eq!(text, git.read_blob_text(&sha1).unwrap());
+        assert_eq!(plain, git.read_blob_text(&plain_sha1).unwrap());
+        assert_eq!("compressed", git.read_commit(&head).unwrap().message());
+        assert!(git.fsck().unwrap().is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn add_all_ut() {
+        init();
+        let repo_dir = ".add_all_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("add_all_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("d1")).is_ok());
+        for name in ["f1", "f2", "d1/f3"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["add_all_ut/".to_string()]).is_ok());
+        assert_eq!(
+            vec!["add_all_ut/d1/f3", "add_all_ut/f1", "add_all_ut/f2"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert!(git.commit("add files").is_ok());
+
+        assert!(fs::write(work_dir.join("f1"), "f1 modified").is_ok());
+        assert!(fs::remove_file(work_dir.join("f2")).is_ok());
+        assert!(fs::write(work_dir.join("f4"), "f4").is_ok());
+        // not valid utf-8, added as bytes
+        assert!(fs::write(work_dir.join("d1/binary"), [0xff, 0xfe]).is_ok());
+        assert!(git.add_all(&["add_all_ut".to_string()]).is_ok());
+        assert_eq!(
+            vec!["add_all_ut/d1/binary", "add_all_ut/f1", "add_all_ut/f4"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["add_all_ut/f2"],
+            git.staging_area.deleted.keys().collect::<Vec<_>>()
+        );
+        assert!(git.take_warnings().is_empty());
+        assert!(git.remove(&["add_all_ut/d1/binary".to_string()]).is_ok());
+        assert!(fs::remove_file(work_dir.join("d1/binary")).is_ok());
+
+        assert!(fs::write(work_dir.join("f4"), "f4 modified").is_ok());
+        assert!(fs::remove_file(work_dir.join("d1/f3")).is_ok());
+        assert!(fs::write(work_dir.join("f5"), "f5").is_ok());
+        let pathspec = &["add_all_ut".to_string()];
+        let ls = |git: &mut GitRepository, options: LsFilesOptions| {
+            git.ls_files(&options, pathspec).unwrap()
+        };
+        assert_eq!(
+            vec!["add_all_ut/d1/f3", "add_all_ut/f1", "add_all_ut/f4"],
+            ls(git, LsFilesOptions::default())
+        );
+        let staged = LsFilesOptions {
+            staged: true,
+            ..Default::default()
+        };
+        assert_eq!(vec!["add_all_ut/f1", "add_all_ut/f4"], ls(git, staged));
+        let deleted = LsFilesOptions {
+            deleted: true,
+            ..Default::default()
+        };
+        assert_eq!(vec!["add_all_ut/d1/f3"], ls(git, deleted));
+        let modified = LsFilesOptions {
+            modified: true,
+            ..Default::default()
+        };
+        assert_eq!(vec!["add_all_ut/d1/f3", "add_all_ut/f4"], ls(git, modified));
+        let others = LsFilesOptions {
+            others: true,
+            deleted: true,
+            ..Default::default()
+        };
+        assert_eq!(vec!["add_all_ut/d1/f3", "add_all_ut/f5"], ls(git, others));
+        git.take_warnings();
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn add_update_ut() {
+        init();
+        let repo_dir = ".add_update_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("add_update_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        for name in ["f1", "f2", "f3"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["add_update_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+
+        assert!(fs::write(work_dir.join("f1"), "f1 modified").is_ok());
+        assert!(fs::remove_file(work_dir.join("f2")).is_ok());
+        assert!(fs::write(work_dir.join("f4"), "f4").is_ok());
+        assert!(fs::write(work_dir.join("f5"), "f5").is_ok());
+        assert!(git.add(&["add_update_ut/f5".to_string()]).is_ok());
+        assert!(fs::write(work_dir.join("f5"), "f5 modified").is_ok());
+        assert!(git.add_update(&["add_update_ut".to_string()]).is_ok());
+        let f5 = git.staging_area.staged.get("add_update_ut/f5").cloned();
+        assert_eq!(
+            vec!["add_update_ut/f1", "add_update_ut/f5"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["add_update_ut/f2"],
+            git.staging_area.deleted.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            git.hash_object("add_update_ut/f5", false).ok(),
+            f5.map(|entry| entry.sha1)
+        );
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn add_dir_ut() {
+        init();
+        let repo_dir = ".add_dir_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("add_dir_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("src/d1")).is_ok());
+        for name in ["f1", "src/f2", "src/d1/f3", "src/f2.log"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        assert!(fs::write(work_dir.join("src/.gitignore"), "*.log\n").is_ok());
+        let git = &mut GitRepository::new("add_dir_ut/.git-rs");
+        git.cwd = work_dir.clone();
+        assert!(git.init().is_ok());
+        assert!(git.add(&["src/".to_string()]).is_ok());
+        assert_eq!(
+            vec!["src/.gitignore", "src/d1/f3", "src/f2"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert!(git.add(&[".".to_string()]).is_ok());
+        assert_eq!(
+            vec!["f1", "src/.gitignore", "src/d1/f3", "src/f2"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert!(git.add(&["../outside".to_string()]).is_err());
+
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn unstage_ut() {
+        init();
+        let repo_dir = ".unstage_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("unstage_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        for name in ["f1", "f2"] {
+            assert!(fs::write(work_dir.join(name), format!("{}\n", name)).is_ok());
+        }
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["unstage_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+
+        for name in ["f1", "f2"] {
+            assert!(fs::write(work_dir.join(name), format!("{} modified\n", name)).is_ok());
+        }
+        assert!(git.add_all(&["unstage_ut".to_string()]).is_ok());
+        let options = DiffOptions {
+            paths: vec!["unstage_ut/f2".to_string()],
+            ..Default::default()
+        };
+        let diff = git.diff_staged_with(&options).unwrap();
+        assert!(diff.contains("+f2 modified") && !diff.contains("f1"));
+        let diff = git.diff_with(&options).unwrap();
+        assert!(diff.contains("+f2 modified") && !diff.contains("f1"));
+
+        assert!(git.unstage(&["unstage_ut/f1".to_string()]).is_ok());
+        assert_eq!(
+            vec!["unstage_ut/f2"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "f1 modified\n",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        let options = DiffOptions {
+            paths: vec!["unstage_ut/f1".to_string()],
+            ..Default::default()
+        };
+        assert!(git.diff_with(&options).unwrap().contains("+f1 modified"));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn commit_paths_ut() {
+        init();
+        let repo_dir = ".commit_paths_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("commit_paths_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("d1")).is_ok());
+        for name in ["f1", "f2", "d1/f3"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["commit_paths_ut".to_string()]).is_ok());
+        assert!(git
+            .commit_paths(
+                "d1 and f1",
+                &[
+                    "commit_paths_ut/d1".to_string(),
+                    "commit_paths_ut/f1".to_string()
+                ]
+            )
+            .is_ok());
+        assert_eq!(
+            vec!["commit_paths_ut/d1/f3", "commit_paths_ut/f1"],
+            git.commit.blobs.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["commit_paths_ut/f2"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert!(git
+            .commit_paths("nothing", &["commit_paths_ut/d1".to_string()])
+            .is_err());
+
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.commit("the rest").is_ok());
+        assert_eq!(3, git.commit.blobs.len());
+        assert!(git.staging_area.is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn author_ut() {
+        init();
+        let repo_dir = ".author_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("author_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["author_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.set_config("user.name", "Jane").is_ok());
+        assert!(git.set_config("user.email", "jane@example.com").is_ok());
+        let jane = "Jane <jane@example.com>";
+        let bob = "Bob <bob@example.com>";
+
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        let head = git
+            .read_commit(&git.resolve_commit("HEAD").unwrap())
+            .unwrap();
+        assert_eq!((jane, jane), (head.author(), head.committer()));
+
+        assert!(fs::write(work_dir.join("f2"), "f2").is_ok());
+        assert!(git.add_all(all).is_ok());
+        let options = |author: &str| CommitOptions {
+            author: Some(author.to_string()),
+            ..Default::default()
+        };
+        assert!(git.commit_with("add f2", &[], &options(" ")).is_err());
+        assert!(git.commit_with("add f2", &[], &options(bob)).is_ok());
+        let sha1 = git.resolve_commit("HEAD").unwrap();
+        let head = git.read_commit(&sha1).unwrap();
+        assert_eq!((bob, jane), (head.author(), head.committer()));
+        let lines = format!("Author: {}\nCommitter: {}\nDate: ", bob, jane);
+        assert!(git
+            .log()
+            .unwrap()
+            .starts_with(&format!("===\ncommit {}\n{}", sha1, lines)));
+        assert!(git.show("HEAD").unwrap().contains(&format!("\n{}", lines)));
+
+        // amend keeps the author and records the new committer
+        assert!(git.set_config("user.name", "Ann").is_ok());
+        assert!(git.amend(Some("add f2 again")).is_ok());
+        let head = git
+            .read_commit(&git.resolve_commit("HEAD").unwrap())
+            .unwrap();
+        assert_eq!(
+            (bob, "Ann <jane@example.com>"),
+            (head.author(), head.committer())
+        );
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn signing_ut() {
+        init();
+        let repo_dir = ".signing_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("signing_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let key = work_dir.join("key");
+        assert!(Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "jane", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap()
+            .success());
+        let allowed = work_dir.join("allowed_signers");
+        let public_key = fs::read_to_string(work_dir.join("key.pub")).unwrap();
+        assert!(fs::write(&allowed, format!("jane {}", public_key)).is_ok());
+        let all = &["signing_ut/f1".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.set_config("gpg.format", "ssh").is_ok());
+        assert!(git
+            .set_config("user.signingKey", &key.display().to_string())
+            .is_ok());
+
+        let sign = CommitOptions {
+            sign: true,
+            ..Default::default()
+        };
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(all).is_ok());
+        assert!(git.commit_with("signed", &[], &sign).is_ok());
+        let signed = git.resolve_commit("HEAD").unwrap();
+        assert!(!git.read_commit(&signed).unwrap().signature().is_empty());
+        // verifying needs the allowed signers
+        assert!(git.verify_commit("HEAD").is_err());
+        let allowed = allowed.display().to_string();
+        assert!(git
+            .set_config("gpg.ssh.allowedSignersFile", &allowed)
+            .is_ok());
+        assert!(git
+            .verify_commit("HEAD")
+            .unwrap()
+            .starts_with("Good \"git\" signature for jane"));
+
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(git.add(all).is_ok());
+        assert!(git.commit("unsigned").is_ok());
+        assert!(git.verify_commit("HEAD").is_err());
+        let log = git
+            .log_with(&LogOptions {
+                show_signature: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let head = git.resolve_commit("HEAD").unwrap();
+        assert!(log.starts_with(&format!("===\ncommit {}\nSignature: none\n", head)));
+        assert!(log.contains(&format!(
+            "commit {}\nSignature: Good \"git\" signature for jane",
+            signed
+        )));
+        assert!(!git.log().unwrap().contains("Signature:"));
+
+        // commit.gpgSign signs every commit
+        assert!(git.set_config("commit.gpgSign", "true").is_ok());
+        assert!(git.amend(Some("signed too")).is_ok());
+        assert!(git.verify_commit("HEAD").is_ok());
+
+        // a changed commit no longer matches its signature
+        let mut commit = git.read_commit(&signed).unwrap();
+        commit.set_message("tampered");
+        assert!(git
+            .persist_object(&commit, &git.commit_file(&signed).unwrap())
+            .is_ok());
+        assert!(git.verify_commit(&signed).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn hooks_ut() {
+        use std::os::unix::fs::PermissionsExt;
+        init();
+        let repo_dir = ".hooks_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("hooks_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let hooks_dir = git.repo_path.join(HOOKS_DIR);
+        assert!(fs::create_dir(&hooks_dir).is_ok());
+        let install = |name: &str, script: &str| {
+            let path = hooks_dir.join(name);
+            assert!(fs::write(&path, format!("#!/bin/sh\n{}", script)).is_ok());
+            assert!(fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).is_ok());
+        };
+        let f1 = &["hooks_ut/f1".to_string()];
+        let initial = git.resolve_commit("HEAD").unwrap();
+
+        install(hooks::PRE_COMMIT, "exit 1\n");
+        install(
+            hooks::POST_COMMIT,
+            "echo \"$GIT_DIR\" > hooks_ut/post\nexit 1\n",
+        );
+        assert!(fs::write(work_dir.join("f1"), "v1").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("add f1").is_err());
+        assert_eq!(initial, git.resolve_commit("HEAD").unwrap());
+        assert!(!work_dir.join("post").exists());
+        let no_verify = CommitOptions {
+            no_verify: true,
+            ..Default::default()
+        };
+        assert!(git.commit_with("add f1", &[], &no_verify).is_ok());
+        assert_ne!(initial, git.resolve_commit("HEAD").unwrap());
+        assert_eq!(
+            format!("{}\n", git.repo_path.display()),
+            fs::read_to_string(work_dir.join("post")).unwrap()
+        );
+
+        // commit-msg rejects messages without a ticket and rewrites the others
+        install(hooks::PRE_COMMIT, "exit 0\n");
+        install(
+            hooks::COMMIT_MSG,
+            "grep -q '#' \"$1\" || exit 1\necho \"$(cat \"$1\") (checked)\" > \"$1\"\n",
+        );
+        assert!(fs::write(work_dir.join("f1"), "v2").is_ok());
+        assert!(git.add(f1).is_ok());
+        assert!(git.commit("change f1").is_err());
+        assert!(git.commit("change f1 #12").is_ok());
+        let head = git
+            .read_commit(&git.resolve_commit("HEAD").unwrap())
+            .unwrap();
+        assert_eq!("change f1 #12 (checked)", head.message());
+        assert!(git.amend(Some("no ticket")).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn amend_ut() {
+        init();
+        let repo_dir = ".amend_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("amend_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["amend_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.amend(Some("no parent")).is_err());
+        let initial = git.resolve_commit("HEAD").unwrap();
+
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add fiels").is_ok());
+        let typo = git.resolve_commit("HEAD").unwrap();
+        assert!(git.amend(Some("add files")).is_ok());
+        let head = git.resolve_commit("main").unwrap();
+        assert_ne!(typo, head);
+        let amended = git.read_commit(&head).unwrap();
+        assert_eq!("add files", amended.message());
+        assert_eq!(initial, amended.parent());
+        assert_eq!(git.read_commit(&typo).unwrap().blobs(), amended.blobs());
+
+        // staged changes are folded in and the message is kept
+        assert!(fs::write(work_dir.join("f1"), "f1 changed").is_ok());
+        assert!(fs::remove_file(work_dir.join("f2")).is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.amend(None).is_ok());
+        let amended = git
+            .read_commit(&git.resolve_commit("HEAD").unwrap())
+            .unwrap();
+        assert_eq!("add files", amended.message());
+        assert_eq!(initial, amended.parent());
+        assert_eq!(
+            vec!["amend_ut/f1"],
+            amended.blobs().keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            "f1 changed",
+            git.read_blob_text(&amended.blobs()["amend_ut/f1"].sha1)
+                .unwrap()
+        );
+        assert!(git.staging_area.is_empty());
+        assert_eq!(2, git.history(&git.commit_sha1.clone()).unwrap().len());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn stash_ut() {
+        init();
+        let repo_dir = ".stash_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("stash_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        for name in ["f1", "f2", "f3"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let all = &["stash_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        assert!(git.stash_push(None, false).is_err());
+        assert!(git.stash_pop(0).is_err());
+
+        // staged f1 and new f4, unstaged f2, deleted f3
+        assert!(fs::write(work_dir.join("f1"), "f1 staged").is_ok());
+        assert!(fs::write(work_dir.join("f4"), "f4").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2 unstaged").is_ok());
+        assert!(fs::remove_file(work_dir.join("f3")).is_ok());
+        assert!(fs::write(work_dir.join("untracked"), "untracked").is_ok());
+        let saved = git.stash_push(None, false).unwrap();
+        assert!(saved.starts_with("Saved working directory and index state WIP on main: "));
+        assert!(saved.ends_with(" base"));
+        for name in ["f1", "f2", "f3"] {
+            assert_eq!(name, fs::read_to_string(work_dir.join(name)).unwrap());
+        }
+        assert!(!work_dir.join("f4").exists());
+        assert!(work_dir.join("untracked").exists());
+        assert!(git.staging_area.is_empty());
+
+        assert!(fs::write(work_dir.join("f1"), "f1 second").is_ok());
+        assert!(git.stash_push(Some("second"), false).is_ok());
+        let list = git.stash_list().unwrap();
+        assert_eq!(2, list.len());
+        assert_eq!("stash@{0}: On main: second", list[0]);
+        assert!(list[1].starts_with("stash@{1}: WIP on main: "));
+
+        // HEAD moved on in a file the stash does not touch
+        assert!(fs::write(work_dir.join("f5"), "f5").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add f5").is_ok());
+        assert!(git.stash_pop(2).is_err());
+        assert!(git.stash_pop(1).is_ok());
+        assert_eq!(
+            "f1 staged",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert_eq!(
+            "f2 unstaged",
+            fs::read_to_string(work_dir.join("f2")).unwrap()
+        );
+        assert!(!work_dir.join("f3").exists());
+        assert_eq!("f4", fs::read_to_string(work_dir.join("f4")).unwrap());
+        assert_eq!("f5", fs::read_to_string(work_dir.join("f5")).unwrap());
+        assert_eq!(
+            vec!["stash_ut/f1", "stash_ut/f4"],
+            git.staging_area.staged.keys().collect::<Vec<_>>()
+        );
+        assert!(git.staging_area.deleted.is_empty());
+        assert_eq!(
+            vec!["stash@{0}: On main: second"],
+            git.stash_list().unwrap()
+        );
+
+        // the remaining entry would overwrite the staged f1
+        assert!(git.stash_pop(0).is_err());
+        assert_eq!(
+            "Dropped stash@{0} (On main: second)",
+            git.stash_drop(0).unwrap()
+        );
+        assert!(git.stash_list().unwrap().is_empty());
+        assert!(git.stash_drop(0).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn stash_extras_ut() {
+        init();
+        let work_dir = &env::current_dir().unwrap().join("stash_extras_ut");
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        // untracked files are stashed, the repository gets a working directory of its own
+        let git = &mut GitRepository::new("stash_extras_ut/.git-rs");
+        git.cwd = work_dir.clone();
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(git.add_all(&[]).is_ok());
+        assert!(git.commit("base").is_ok());
+
+        assert!(fs::write(work_dir.join("f1"), "f1 changed").is_ok());
+        assert!(fs::write(work_dir.join("new"), "new").is_ok());
+        assert!(git.stash_push(Some("wip"), true).is_ok());
+        assert_eq!("f1", fs::read_to_string(work_dir.join("f1")).unwrap());
+        assert!(!work_dir.join("new").exists());
+        // the stashed untracked files survive gc
+        assert!(git.gc().is_ok());
+        assert_eq!(
+            "Applied stash@{0} (On main: wip)",
+            git.stash_apply(0).unwrap()
+        );
+        assert_eq!(
+            "f1 changed",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert_eq!("new", fs::read_to_string(work_dir.join("new")).unwrap());
+        assert_eq!(1, git.stash_list().unwrap().len());
+        assert!(matches!(
+            git.stash_apply(0),
+            Err(GitError::StashError(msg)) if msg.contains("f1")
+        ));
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(matches!(
+            git.stash_apply(0),
+            Err(GitError::StashError(msg)) if msg == "new already exists, no checkout"
+        ));
+
+        // a branch from the entry's commit, with HEAD moved on
+        assert!(fs::write(work_dir.join("f1"), "f1 on main").is_ok());
+        assert!(git.add_all(&[]).is_ok());
+        assert!(git.commit("main").is_ok());
+        assert!(git.stash_branch("main", 0).is_err());
+        assert_eq!(
+            "Switched to a new branch 'topic'\nDropped stash@{0} (On main: wip)",
+            git.stash_branch("topic", 0).unwrap()
+        );
+        assert_eq!("topic", git.current_branch());
+        assert_eq!("base", git.commit.message());
+        assert_eq!(
+            "f1 changed",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert_eq!("new", fs::read_to_string(work_dir.join("new")).unwrap());
+        assert!(git.stash_list().unwrap().is_empty());
+        assert!(git.stash_apply(0).is_err());
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn stash_branch_rollback_ut() {
+        init();
+        let work_dir = &env::current_dir().unwrap().join("stash_branch_rollback_ut");
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new("stash_branch_rollback_ut/.git-rs");
+        git.cwd = work_dir.clone();
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(git.add_all(&[]).is_ok());
+        assert!(git.commit("base").is_ok());
+        assert!(fs::write(work_dir.join("new"), "new").is_ok());
+        assert!(git.stash_push(Some("wip"), true).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1 on main").is_ok());
+        assert!(git.add_all(&[]).is_ok());
+        assert!(git.commit("main").is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+
+        // the stashed untracked file is in the way on the new branch
+        assert!(fs::write(work_dir.join("new"), "in the way").is_ok());
+        assert!(matches!(
+            git.stash_branch("topic", 0),
+            Err(GitError::StashError(msg)) if msg == "new already exists, no checkout"
+        ));
+        assert_eq!("main", git.current_branch());
+        assert_eq!(head, git.resolve_commit("HEAD").unwrap());
+        assert_eq!(
+            "f1 on main",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(git.resolve_commit("topic").is_err());
+        assert!(git.reflog("refs/heads/topic").is_empty());
+        assert_eq!(1, git.stash_list().unwrap().len());
+
+        // with the file out of the way the same entry goes on the branch
+        assert!(fs::remove_file(work_dir.join("new")).is_ok());
+        assert!(git.stash_branch("topic", 0).is_ok());
+        assert_eq!("topic", git.current_branch());
+        assert_eq!("new", fs::read_to_string(work_dir.join("new")).unwrap());
+        assert!(git.stash_list().unwrap().is_empty());
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn worktree_ut() {
+        init();
+        let repo_dir = ".worktree_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("worktree_ut");
+        let linked_dir = &env::current_dir().unwrap().join("worktree_ut_linked");
+        clean_repo(repo_dir);
+        for dir in [work_dir, linked_dir] {
+            if dir.exists() {
+                assert!(fs::remove_dir_all(dir).is_ok());
+            }
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["worktree_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        assert!(git.branch("topic").is_ok());
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(git.worktree_add("worktree_ut_linked", "main").is_err());
+        assert!(git.worktree_add("worktree_ut_linked", "missing").is_err());
+        assert!(git.worktree_add("worktree_ut", "topic").is_err());
+
+        assert_eq!(
+            "Preparing worktree (checking out 'topic')",
+            git.worktree_add("worktree_ut_linked", "topic").unwrap()
+        );
+        assert_eq!(
+            "f1",
+            fs::read_to_string(linked_dir.join("worktree_ut/f1")).unwrap()
+        );
+        assert!(git.worktree_add("worktree_ut_other", "topic").is_err());
+        let list = git.worktree_list().unwrap();
+        assert_eq!(2, list.len());
+        assert!(list[0].ends_with(" [main]"));
+        let linked_path = linked_dir.canonicalize().unwrap().display().to_string();
+        assert!(list[1].starts_with(&linked_path) && list[1].ends_with(" [topic]"));
+
+        // the linked worktree has its own HEAD and index, the branches are shared
+        let linked = &mut GitRepository::open_dir(linked_dir).unwrap();
+        assert!(fs::write(linked_dir.join("worktree_ut/f2"), "f2").is_ok());
+        assert!(linked.add_all(&[]).is_ok());
+        assert_eq!("A  worktree_ut/f2\n", linked.status_porcelain().unwrap());
+        // blobs staged in a linked worktree survive gc in the main one
+        assert!(git.gc().is_ok());
+        assert!(linked.commit("on topic").is_ok());
+        assert!(git.fsck().unwrap().is_empty());
+        assert_eq!("topic", linked.current_branch());
+        assert_eq!(linked.commit_sha1, git.resolve_commit("topic").unwrap());
+        assert!(git.load_basic_info().is_ok());
+        assert_eq!("main", git.current_branch());
+        assert!(git.staging_area.is_empty());
+        assert!(!work_dir.join("f2").exists());
+
+        // a push does not move the branch of a linked worktree
+        let bundle = "worktree_ut/topic.bundle";
+        assert!(git.bundle_create(bundle, &["topic".to_string()]).is_ok());
+        let base = git.resolve_commit("main").unwrap();
+        assert!(fs::write(git.heads_path.join("topic"), &base).is_ok());
+        assert_eq!(
+            vec!["! [rejected] topic (checked out)"],
+            git.unbundle(bundle).unwrap()
+        );
+        assert!(fs::write(git.heads_path.join("topic"), &linked.commit_sha1).is_ok());
+
+        assert!(fs::write(linked_dir.join("untracked"), "untracked").is_ok());
+        assert!(git.worktree_remove("worktree_ut_linked", false).is_err());
+        assert!(matches!(
+            git.worktree_remove(".", true),
+            Err(GitError::WorktreeError(msg)) if msg.ends_with("is a main working tree")
+        ));
+        assert!(git.worktree_remove("worktree_ut", true).is_err());
+        assert!(git.worktree_remove("worktree_ut_linked", true).is_ok());
+        assert!(!linked_dir.exists());
+        assert_eq!(1, git.worktree_list().unwrap().len());
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn ignore_ut() {
+        init();
+        let repo_dir = ".ignore_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("ignore_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("build")).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        // tracked before it is ignored
+        assert!(fs::write(work_dir.join("tracked.log"), "v1").is_ok());
+        assert!(git.add(&["ignore_ut/tracked.log".to_string()]).is_ok());
+        assert!(git.commit("add tracked.log").is_ok());
+
+        assert!(fs::write(work_dir.join(".gitignore"), "*.log\n!keep.log\nbuild/\n").is_ok());
+        for path in ["a.log", "keep.log", "build/out", "src.txt"] {
+            assert!(fs::write(work_dir.join(path), path).is_ok());
+        }
+        assert!(fs::write(work_dir.join("tracked.log"), "v2").is_ok());
+        let status = git.status().unwrap();
+        for path in ["a.log", "build/out", "tracked.log"] {
+            assert!(
+                !status.contains(&format!("\nignore_ut/{}\n", path)),
+                "{}",
+                status
+            );
+        }
+        assert!(
+            status.contains("\nignore_ut/tracked.log (modified)\n"),
+            "{}",
+            status
+        );
+        let paths = &["ignore_ut".to_string()];
+        assert_eq!(
+            vec![
+                "Would remove ignore_ut/.gitignore",
+                "Would remove ignore_ut/keep.log",
+                "Would remove ignore_ut/src.txt"
+            ],
+            git.clean(paths, true, true).unwrap()
+        );
+
+        // user-level excludes, overridden by .gitignore files
+        assert!(fs::write(work_dir.join("a.swp"), "swap").is_ok());
+        assert!(fs::write(work_dir.join("keep.swp"), "swap").is_ok());
+        assert!(fs::create_dir_all(work_dir.join("home")).is_ok());
+        assert!(fs::write(work_dir.join("home/excludes"), "*.swp\n").is_ok());
+        assert!(fs::write(
+            work_dir.join(".gitignore"),
+            "*.log\n!keep.*\nbuild/\nhome/\n"
+        )
+        .is_ok());
+        assert!(git
+            .set_config("core.excludesFile", "ignore_ut/home/excludes")
+            .is_ok());
+        assert_eq!(
+            vec![
+                "Would remove ignore_ut/.gitignore",
+                "Would remove ignore_ut/keep.log",
+                "Would remove ignore_ut/keep.swp",
+                "Would remove ignore_ut/src.txt"
+            ],
+            git.clean(paths, true, true).unwrap()
+        );
+        assert!(git
+            .set_config("core.excludesFile", "ignore_ut/missing")
+            .is_ok());
+        assert!(git
+            .clean(paths, true, true)
+            .unwrap()
+            .contains(&"Would remove ignore_ut/a.swp".to_string()));
+        assert!(fs::remove_file(work_dir.join("a.swp")).is_ok());
+        assert!(fs::remove_file(work_dir.join("keep.swp")).is_ok());
+        assert!(fs::write(work_dir.join(".gitignore"), "*.log\n!keep.log\nbuild/\n").is_ok());
+        assert!(fs::remove_dir_all(work_dir.join("home")).is_ok());
+        assert!(git.add_all(paths).is_ok());
+        let staged = LsFilesOptions {
+            staged: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![
+                "ignore_ut/.gitignore",
+                "ignore_ut/keep.log",
+                "ignore_ut/src.txt",
+                "ignore_ut/tracked.log"
+            ],
+            git.ls_files(&staged, paths).unwrap()
+        );
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn check_ignore_ut() {
+        init();
+        let repo_dir = ".check_ignore_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("check_ignore_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("build")).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        // tracked before it is ignored
+        assert!(fs::write(work_dir.join("tracked.log"), "v1").is_ok());
+        assert!(git
+            .add(&["check_ignore_ut/tracked.log".to_string()])
+            .is_ok());
+        assert!(git.commit("add tracked.log").is_ok());
+        assert!(fs::write(work_dir.join(".gitignore"), "*.log\n!keep.log\nbuild/\n").is_ok());
+        for path in ["a.log", "keep.log", "build/keep.log", "src.txt"] {
+            assert!(fs::write(work_dir.join(path), path).is_ok());
+        }
+
+        let checked = git
+            .check_ignore(&[
+                "check_ignore_ut/a.log".to_string(),
+                "check_ignore_ut/keep.log".to_string(),
+                "check_ignore_ut/build/keep.log".to_string(),
+                "check_ignore_ut/build/".to_string(),
+                "check_ignore_ut/src.txt".to_string(),
+                "check_ignore_ut/tracked.log".to_string(),
+            ])
+            .unwrap();
+        let lines: Vec<String> = checked
+            .iter()
+            .map(|(path, rule)| format!("{}:{}:{} {}", rule.source, rule.line, rule.pattern, path))
+            .collect();
+        assert_eq!(
+            vec![
+                "check_ignore_ut/.gitignore:1:*.log check_ignore_ut/a.log",
+                "check_ignore_ut/.gitignore:2:!keep.log check_ignore_ut/keep.log",
+                "check_ignore_ut/.gitignore:3:build/ check_ignore_ut/build/keep.log",
+                "check_ignore_ut/.gitignore:3:build/ check_ignore_ut/build/",
+            ],
+            lines
+        );
+        assert!(checked[1].1.negated);
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn attributes_ut() {
+        init();
+        let repo_dir = ".attributes_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("attributes_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join(".gitattributes"), "*.dat binary\n").is_ok());
+        assert!(fs::write(work_dir.join("a.dat"), "v1").is_ok());
+        assert!(fs::write(work_dir.join("a.txt"), "v1\n").is_ok());
+        let paths = &[
+            "attributes_ut/.gitattributes".to_string(),
+            "attributes_ut/a.dat".to_string(),
+            "attributes_ut/a.txt".to_string(),
+        ];
+        assert!(git.add(paths).is_ok());
+        assert_eq!(
+            "Binary files /dev/null and b/attributes_ut/a.dat differ\n",
+            git.diff_staged()
+                .unwrap()
+                .lines()
+                .find(|line| line.starts_with("Binary"))
+                .map(|line| format!("{}\n", line))
+                .unwrap()
+        );
+        assert!(git.commit("add files").is_ok());
+
+        assert!(fs::write(work_dir.join("a.dat"), "v2").is_ok());
+        assert!(fs::write(work_dir.join("a.txt"), "v2\n").is_ok());
+        let diff = git.diff().unwrap();
+        assert!(
+            diff.contains("Binary files a/attributes_ut/a.dat and b/attributes_ut/a.dat differ\n"),
+            "{}",
+            diff
+        );
+        assert!(diff.contains("-v1\n+v2\n"), "{}", diff);
+        assert!(!diff.contains("-v1\n+v2\n+"), "{}", diff);
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn autocrlf_ut() {
+        init();
+        let repo_dir = ".autocrlf_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("autocrlf_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.set_config("core.autocrlf", "true").is_ok());
+        assert!(fs::write(
+            work_dir.join(".gitattributes"),
+            "*.sh eol=lf\n*.bin -text\n"
+        )
+        .is_ok());
+        assert!(fs::write(work_dir.join("a.txt"), "a\r\nb\r\n").is_ok());
+        assert!(fs::write(work_dir.join("run.sh"), "echo\r\n").is_ok());
+        assert!(fs::write(work_dir.join("data.bin"), "raw\r\n").is_ok());
+        let paths: Vec<String> = [".gitattributes", "a.txt", "run.sh", "data.bin"]
+            .iter()
+            .map(|path| format!("autocrlf_ut/{}", path))
+            .collect();
+        assert!(git.add(&paths).is_ok());
+        assert!(git.commit("add files").is_ok());
+        let blobs = git.read_commit(&git.commit_sha1).unwrap().blobs;
+        // the repository stores LF, except for files that are not text
+        assert_eq!(
+            utils::crypto_string("a\nb\n"),
+            blobs["autocrlf_ut/a.txt"].sha1
+        );
+        assert_eq!(
+            utils::crypto_string("echo\n"),
+            blobs["autocrlf_ut/run.sh"].sha1
+        );
+        assert_eq!(
+            utils::crypto_string("raw\r\n"),
+            blobs["autocrlf_ut/data.bin"].sha1
+        );
+        assert_eq!(
+            utils::crypto_string("a\nb\n"),
+            git.hash_object("autocrlf_ut/a.txt", false).unwrap()
+        );
+
+        // the same content with either line ending is unmodified
+        assert!(fs::write(work_dir.join("a.txt"), "a\nb\n").is_ok());
+        let status = git.status().unwrap();
+        assert!(!status.contains("autocrlf_ut/a.txt"), "{}", status);
+
+        for path in ["a.txt", "run.sh", "data.bin"] {
+            assert!(fs::remove_file(work_dir.join(path)).is_ok());
+            assert!(git.checkout_file(&format!("autocrlf_ut/{}", path)).is_ok());
+        }
+        let read = |path: &str| fs::read_to_string(work_dir.join(path)).unwrap();
+        assert_eq!("a\r\nb\r\n", read("a.txt"));
+        assert_eq!("echo\n", read("run.sh"));
+        assert_eq!("raw\r\n", read("data.bin"));
+
+        // input normalizes when adding and checks files out as stored
+        assert!(git.set_config("core.autocrlf", "input").is_ok());
+        assert!(fs::remove_file(work_dir.join("a.txt")).is_ok());
+        assert!(git.checkout_file("autocrlf_ut/a.txt").is_ok());
+        assert_eq!("a\nb\n", read("a.txt"));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn clean_ut() {
+        init();
+        let repo_dir = ".clean_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("clean_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        for dir in ["tracked", "sub/deep"] {
+            assert!(fs::create_dir_all(work_dir.join(dir)).is_ok());
+        }
+        assert!(fs::write(work_dir.join("t"), "t").is_ok());
+        assert!(fs::write(work_dir.join("tracked/t"), "t").is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["clean_ut".to_string()]).is_ok());
+        assert!(git.commit("tracked files").is_ok());
+        for path in ["u", "s", "tracked/u", "sub/u", "sub/deep/u"] {
+            assert!(fs::write(work_dir.join(path), path).is_ok());
+        }
+        assert!(git.add(&["clean_ut/s".to_string()]).is_ok());
+
+        let paths = &["clean_ut".to_string()];
+        assert_eq!(
+            vec!["Would remove clean_ut/tracked/u", "Would remove clean_ut/u"],
+            git.clean(paths, true, false).unwrap()
+        );
+        assert_eq!(
+            vec![
+                "Would remove clean_ut/sub/",
+                "Would remove clean_ut/tracked/u",
+                "Would remove clean_ut/u"
+            ],
+            git.clean(paths, true, true).unwrap()
+        );
+        assert_eq!(
+            vec!["Would remove clean_ut/sub/deep/u"],
+            git.clean(&["clean_ut/sub/deep/u".to_string()], true, true)
+                .unwrap()
+        );
+        assert!(work_dir.join("u").exists());
+
+        assert_eq!(
+            vec!["Removing clean_ut/u"],
+            git.clean(&["clean_ut/u".to_string()], false, false)
+                .unwrap()
+        );
+        assert!(!work_dir.join("u").exists());
+        assert_eq!(
+            vec!["Removing clean_ut/sub/", "Removing clean_ut/tracked/u"],
+            git.clean(paths, false, true).unwrap()
+        );
+        assert!(!work_dir.join("sub").exists());
+        for path in ["t", "s", "tracked/t"] {
+            assert!(work_dir.join(path).exists());
+        }
+        assert!(git.clean(paths, false, true).unwrap().is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn bisect_ut() {
+        init();
+        let repo_dir = ".bisect_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("bisect_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["bisect_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let good = git.resolve_commit("HEAD").unwrap();
+        let mut commits = vec![];
+        for i in 0..9 {
+            // the bug sneaks in with change 5
+            let state = if i < 5 { "fine" } else { "bug" };
+            assert!(fs::write(work_dir.join("state"), state).is_ok());
+            assert!(fs::write(work_dir.join("n"), i.to_string()).is_ok());
+            assert!(git.add_all(all).is_ok());
+            assert!(git.commit(&format!("change {}", i)).is_ok());
+            commits.push(git.resolve_commit("HEAD").unwrap());
+        }
+        assert!(git.bisect_mark(&[], true).is_err());
+
+        assert_eq!(
+            "status: waiting for both good and bad commits",
+            git.bisect_start(Some("HEAD"), &[]).unwrap()
+        );
+        assert!(git.bisect_start(None, &[]).is_err());
+        let mut msg = git.bisect_mark(&[good], false).unwrap();
+        let mut steps = 0;
+        while msg.starts_with("Bisecting: ") {
+            steps += 1;
+            let state = fs::read_to_string(work_dir.join("state")).unwrap();
+            msg = git.bisect_mark(&[], state == "bug").unwrap();
+        }
+        assert!(steps <= 4, "{}", steps);
+        assert!(
+            msg.starts_with(&format!("{} is the first bad commit\n", commits[5])),
+            "{}",
+            msg
+        );
+        assert!(msg.contains("change 5"));
+        // bisect never moves the branch
+        assert_eq!(commits[8], git.resolve_commit("main").unwrap());
+
+        assert_eq!("Back on main.", git.bisect_reset().unwrap());
+        assert_eq!("8", fs::read_to_string(work_dir.join("n")).unwrap());
+        assert!(git.bisect_reset().is_err());
+
+        // a bad commit behind a good one cannot be bisected
+        assert!(git
+            .bisect_start(Some(&commits[2]), &[commits[4].clone()])
+            .is_err());
+        assert!(git.bisect_reset().is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn restore_ut() {
+        init();
+        let repo_dir = ".restore_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("restore_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("d1")).is_ok());
+        for name in ["f1", "d1/f2"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["restore_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+        let read = |name: &str| fs::read_to_string(work_dir.join(name)).unwrap_or_default();
+
+        // the working directory comes back to the staged version
+        assert!(fs::write(work_dir.join("f1"), "f1 staged").is_ok());
+        assert!(git.add(&["restore_ut/f1".to_string()]).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1 modified").is_ok());
+        assert!(fs::remove_file(work_dir.join("d1/f2")).is_ok());
+        assert!(git
+            .restore(&["restore_ut".to_string()], false, true)
+            .is_ok());
+        assert_eq!("f1 staged", read("f1"));
+        assert_eq!("d1/f2", read("d1/f2"));
+
+        // --staged only touches the staging area
+        assert!(git
+            .restore(&["restore_ut/f1".to_string()], true, false)
+            .is_ok());
+        assert!(git.staging_area.is_empty());
+        assert_eq!("f1 staged", read("f1"));
+        assert!(git
+            .restore(&["restore_ut/f1".to_string()], true, true)
+            .is_ok());
+        assert_eq!("f1", read("f1"));
+
+        assert!(git
+            .restore(&["restore_ut/missing".to_string()], false, true)
+            .is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn branch_rename_ut() {
+        init();
+        let repo_dir = ".branch_rename_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.remote_add("origin", "../other").is_ok());
+        let mut config = git.config().unwrap();
+        assert!(remote::set_upstream(&mut config, "main", "origin", "main").is_ok());
+        assert!(config.save(&git.config_file).is_ok());
+        assert!(git.load_basic_info().is_ok());
+        let sha1 = git.commit_sha1.clone();
+
+        assert!(git.branch_rename(None, "trunk").is_ok());
+        assert_eq!("trunk", git.current_branch());
+        assert!(!git.heads_path.join("main").exists());
+        let mut reopened = GitRepository::new(repo_dir);
+        assert!(reopened.load_basic_info().is_ok());
+        assert_eq!("trunk", reopened.current_branch());
+        assert_eq!(sha1, reopened.commit_sha1);
+        let mut reopened = GitRepository::new(repo_dir);
+        assert_eq!(vec!["* trunk"], reopened.branch_list(false).unwrap());
+        let config = git.config().unwrap();
+        assert_eq!(None, remote::upstream(&config, "main"));
+        assert_eq!(
+            Some(("origin".to_string(), "main".to_string())),
+            remote::upstream(&config, "trunk")
+        );
+
+        // other branches keep the current one
+        assert!(git.branch("topic").is_ok());
+        assert!(git.branch_rename(Some("trunk"), "main").is_ok());
+        assert_eq!("topic", git.current_branch());
+        assert!(git.branch_rename(Some("main"), "topic").is_err());
+        assert!(git.branch_rename(Some("missing"), "other").is_err());
+        assert!(git.branch_rename(Some("main"), "a/b").is_err());
+
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn branch_delete_ut() {
+        init();
+        let repo_dir = ".branch_delete_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("branch_delete_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.branch("merged").is_ok());
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        assert!(git.add(&["branch_delete_ut/f1".to_string()]).is_ok());
+        assert!(git.commit("topic work").is_ok());
+        let topic = git.commit_sha1.clone();
+        let first = git.read_commit(&topic).unwrap().parent().to_string();
+        assert!(git.is_ancestor(&first, &topic).unwrap());
+        assert!(git.is_ancestor(&topic, &topic).unwrap());
+        assert!(!git.is_ancestor(&topic, &first).unwrap());
+
+        assert!(git.branch_delete("topic", true).is_err());
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(git.branch_delete("topic", false).is_err());
+        assert!(git.heads_path.join("topic").exists());
+        assert_eq!(
+            format!("Deleted branch merged (was {}).", &first[..7]),
+            git.branch_delete("merged", false).unwrap()
+        );
+        assert!(git.branch_delete("topic", true).is_ok());
+        assert!(!git.heads_path.join("topic").exists());
+        assert!(git.branch_delete("topic", true).is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn checkout_create_ut() {
+        init();
+        let repo_dir = ".checkout_create_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("checkout_create_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["checkout_create_ut".to_string()]).is_ok());
+        assert!(git.commit("add f1").is_ok());
+        let first = git.commit_sha1.clone();
+
+        // local changes come along to the new branch
+        assert!(fs::write(work_dir.join("f1"), "f1 modified").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2").is_ok());
+        assert!(git.add(&["checkout_create_ut/f2".to_string()]).is_ok());
+        assert!(git.checkout_create("topic", None).is_ok());
+        assert_eq!("topic", git.current_branch());
+        assert_eq!(first, git.commit_sha1);
+        assert!(git
+            .staging_area
+            .staged
+            .contains_key("checkout_create_ut/f2"));
+        assert_eq!(
+            "f1 modified",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(git.commit("add f2").is_ok());
+
+        assert!(git.checkout_create("topic", None).is_err());
+        assert!(git.checkout_create("a/b", None).is_err());
+        assert!(git.checkout_create("old", Some("no-such-rev")).is_err());
+        assert!(!git.heads_path.join("old").exists());
+
+        assert!(git.checkout_create("old", Some(&first)).is_ok());
+        assert_eq!("old", git.current_branch());
+        assert_eq!(first, git.commit_sha1);
+        assert!(!work_dir.join("f2").exists());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn checkout_branch_ut() {
+        init();
+        let repo_dir = ".checkout_branch_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("checkout_branch_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("d1/d2")).is_ok());
+        for name in ["f1", "f2", "d1/d2/f3"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["checkout_branch_ut".to_string()]).is_ok());
+        assert!(git.commit("main files").is_ok());
+        assert!(git.branch("other").is_ok());
+        assert!(git.checkout_branch("other").is_err());
+
+        assert!(fs::write(work_dir.join("f1"), "f1 on other").is_ok());
+        assert!(fs::remove_dir_all(work_dir.join("d1")).is_ok());
+        assert!(fs::write(work_dir.join("f4"), "f4").is_ok());
+        assert!(git.add_all(&["checkout_branch_ut".to_string()]).is_ok());
+        assert!(git.commit("other files").is_ok());
+
+        assert!(git.set_config("checkout.workers", "2").is_ok());
+        assert!(git.checkout_branch("main").is_ok());
+        assert_eq!("main", git.current_branch());
+        assert_eq!("f1", fs::read_to_string(work_dir.join("f1")).unwrap());
+        assert_eq!(
+            "d1/d2/f3",
+            fs::read_to_string(work_dir.join("d1/d2/f3")).unwrap()
+        );
+        assert!(!work_dir.join("f4").exists());
+
+        assert!(fs::write(work_dir.join("f4"), "untracked").is_ok());
+        assert!(git.checkout_branch("other").is_err());
+        assert!(fs::remove_file(work_dir.join("f4")).is_ok());
+        assert!(git.checkout_branch("other").is_ok());
+        assert_eq!(
+            "f1 on other",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(!work_dir.join("d1/d2/f3").exists());
+        assert!(git.checkout_branch("missing").is_err());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn repair_ut() {
+        init();
+        let repo_dir = ".repair_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.repair().unwrap().is_empty());
+
+        assert!(fs::write(git.heads_path.join("main.lock"), "").is_ok());
+        assert!(fs::write(&git.head_file, "refs/heads/gone").is_ok());
+        assert!(fs::write(&git.index_file, "{\"staged\":{").is_ok());
+        assert!(fs::write(git.heads_path.join("lost"), "f".repeat(40)).is_ok());
+        assert!(git.status().is_err());
+
+        let actions = git.repair().unwrap();
+        assert_eq!(4, actions.len(), "{:?}", actions);
+        assert!(actions[0].starts_with("removed stale lock file"));
+        assert_eq!(
+            "HEAD pointed at missing ref \"refs/heads/gone\", reset to refs/heads/main",
+            actions[1]
+        );
+        assert_eq!("index was unreadable, rebuilt it from HEAD", actions[2]);
+        assert_eq!(
+            format!(
+                "refs/heads/lost points at missing commit {}, no reflog entry to rewind it to",
+                "f".repeat(40)
+            ),
+            actions[3]
+        );
+        assert!(!git.heads_path.join("main.lock").exists());
+        assert!(git.status().is_ok());
+        assert!(fs::remove_file(git.heads_path.join("lost")).is_ok());
+
+        // only lock files of the repository are removed, no ref is named like one
+        assert!(git.create_tag("release.lock", HEAD_FILE, None).is_err());
+        assert!(fs::write(git.repo_path.join("HEAD.lock"), "").is_ok());
+        assert!(fs::write(git.repo_path.join("notes.lock"), "").is_ok());
+        let actions = git.repair().unwrap();
+        assert_eq!(1, actions.len(), "{:?}", actions);
+        assert!(!git.repo_path.join("HEAD.lock").exists());
+        assert!(git.repo_path.join("notes.lock").exists());
+
+        // a branch pointing at a missing commit goes back to the last commit it had
+        let first = git.resolve_commit(MAIN_BRANCH).unwrap();
+        assert!(git.commit("second").is_ok());
+        let second = git.resolve_commit(MAIN_BRANCH).unwrap();
+        assert_ne!(first, second);
+        assert!(fs::write(git.heads_path.join(MAIN_BRANCH), "e".repeat(40)).is_ok());
+        assert_eq!(
+            vec![format!(
+                "refs/heads/main pointed at missing commit {}, rewound it to {}",
+                "e".repeat(40),
+                second
+            )],
+            git.repair().unwrap()
+        );
+        assert_eq!(
+            second,
+            fs::read_to_string(git.heads_path.join(MAIN_BRANCH)).unwrap()
+        );
+
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn graph_dot_ut() {
+        init();
+        let repo_dir = ".graph_dot_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.branch("other").is_ok());
+        assert!(git.commit("on other").is_ok());
+        assert!(git.create_tag("v1", "HEAD", None).is_ok());
+
+        let dot = git.graph_dot().unwrap();
+        let head = git.resolve_commit("HEAD").unwrap();
+        let root = git.resolve_commit("main").unwrap();
+        assert!(dot.starts_with("digraph commits {\n"));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}\";", head, root)));
+        assert!(dot.contains(&format!("\"ref:main\" -> \"{}\";", root)));
+        assert!(dot.contains(&format!("\"ref:other\" -> \"{}\";", head)));
+        assert!(dot.contains(&format!("\"ref:tag: v1\" -> \"{}\";", head)));
+        let nodes = dot
+            .lines()
+            .filter(|l| l.contains("[label=") && !l.contains("ref:"));
+        assert_eq!(2, nodes.count());
+
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn checkout_file_ut() {
+        init();
+        let repo_dir = ".checkout_file_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("checkout_file_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir.join("d1")).is_ok());
+        assert!(fs::write(work_dir.join("d1/f1"), "f1").is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add(&["checkout_file_ut/d1/f1".to_string()]).is_ok());
+        assert!(git.commit("add f1").is_ok());
+
+        assert!(fs::write(work_dir.join("d1/f1"), "local edit").is_ok());
+        assert!(git.checkout_file("checkout_file_ut/d1/f1").is_ok());
+        assert_eq!("f1", fs::read_to_string(work_dir.join("d1/f1")).unwrap());
+
+        assert!(fs::remove_dir_all(work_dir.join("d1")).is_ok());
+        assert!(git.checkout_file("checkout_file_ut/d1/f1").is_ok());
+        assert_eq!("f1", fs::read_to_string(work_dir.join("d1/f1")).unwrap());
+        assert!(git.staging_area.is_empty());
+        assert!(matches!(
+            git.checkout_file("checkout_file_ut/missing"),
+            Err(GitError::CheckoutError(_))
+        ));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn symlink_ut() {
+        init();
+        let repo_dir = ".symlink_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("symlink_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        assert!(fs::write(work_dir.join("target"), "content").is_ok());
+        assert!(std::os::unix::fs::symlink("target", work_dir.join("link")).is_ok());
+        assert!(std::os::unix::fs::symlink("missing", work_dir.join("dangling")).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let paths = ["target", "link", "dangling"].map(|p| format!("symlink_ut/{}", p));
+        assert!(git.add(&paths).is_ok());
+        assert!(git.commit("add links").is_ok());
+
+        let link = &git.commit.blobs["symlink_ut/link"];
+        assert_eq!(
+            Entry::with_mode(utils::crypto_string("target"), FileMode::Symlink),
+            *link
+        );
+        assert_eq!(
+            FileMode::Regular,
+            git.commit.blobs["symlink_ut/target"].mode
+        );
+        assert!(git.commit.blobs["symlink_ut/dangling"].is_symlink());
+
+        // links are recreated as links, not as files holding the target
+        assert!(fs::remove_file(work_dir.join("link")).is_ok());
+        assert!(fs::remove_file(work_dir.join("dangling")).is_ok());
+        assert!(git.checkout_file("symlink_ut/link").is_ok());
+        assert!(git.checkout_file("symlink_ut/dangling").is_ok());
+        assert_eq!(
+            Path::new("target"),
+            fs::read_link(work_dir.join("link")).unwrap()
+        );
+        assert_eq!(
+            Path::new("missing"),
+            fs::read_link(work_dir.join("dangling")).unwrap()
+        );
+
+        // a link whose target is gone is still there
+        assert!(fs::remove_file(work_dir.join("target")).is_ok());
+        assert!(git.add_all(&["symlink_ut".to_string()]).is_ok());
+        let deleted: Vec<_> = git.staging_area.deleted.keys().collect();
+        assert_eq!(vec!["symlink_ut/target"], deleted);
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn executable_ut() {
+        use std::os::unix::fs::PermissionsExt;
+        init();
+        let repo_dir = ".executable_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("executable_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let script = &work_dir.join("run.sh");
+        let chmod = |mode| fs::set_permissions(script, fs::Permissions::from_mode(mode));
+        assert!(fs::write(script, "echo run\n").is_ok());
+        assert!(chmod(0o755).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add(&["executable_ut/run.sh".to_string()]).is_ok());
+        assert!(git.commit("add script").is_ok());
+        assert_eq!(
+            FileMode::Executable,
+            git.commit.blobs["executable_ut/run.sh"].mode
+        );
+
+        // losing the execute permission alone is a change
+        assert!(chmod(0o644).is_ok());
+        assert_eq!(
+            "mode change 100755 => 100644 executable_ut/run.sh\n",
+            git.diff().unwrap()
+        );
+        assert!(git.checkout_file("executable_ut/run.sh").is_ok());
+        let mode = fs::metadata(script).unwrap().permissions().mode();
+        assert_eq!(0o755, mode & 0o777);
+        assert!(git.diff().unwrap().is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn binary_ut() {
+        init();
+        let repo_dir = ".binary_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("binary_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let image: Vec<u8> = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', 0, 0xff];
+        assert!(fs::write(work_dir.join("image.png"), &image).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.set_config("core.autocrlf", "true").is_ok());
+        assert!(git.add(&["binary_ut/image.png".to_string()]).is_ok());
+        assert!(git.commit("add image").is_ok());
+        let blob = &git.commit.blobs["binary_ut/image.png"];
+        assert_eq!(utils::crypto_bytes(&image), blob.sha1);
+        assert_eq!(image, git.read_blob(&blob.sha1).unwrap());
+        assert!(git.read_blob_text(&blob.sha1).is_err());
+        assert!(git.status_report().unwrap().modified.is_empty());
+
+        assert!(fs::write(work_dir.join("image.png"), &image[..4]).is_ok());
+        assert_eq!(
+            vec!["binary_ut/image.png (modified)"],
+            git.status_report().unwrap().modified
+        );
+        assert_eq!(
+            "Binary files a/binary_ut/image.png and b/binary_ut/image.png differ\n",
+            git.diff().unwrap()
+        );
+        assert!(git.checkout_file("binary_ut/image.png").is_ok());
+        assert_eq!(image, fs::read(work_dir.join("image.png")).unwrap());
+        assert!(git.grep("PNG", None).unwrap().is_empty());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn merge_ut() {
+        init();
+        let repo_dir = ".merge_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("merge_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        for name in ["f1", "f2", "f3"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+        }
+        let all = &["merge_ut".to_string()];
+        let options = &MergeOptions::default();
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        let base = git.resolve_commit("HEAD").unwrap();
+        assert!(git.branch("other").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1 on other").is_ok());
+        assert!(fs::remove_file(work_dir.join("f3")).is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("change f1, remove f3").is_ok());
+
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(git.merge("main", options).is_err());
+        assert!(git.merge("missing", options).is_err());
+        assert_eq!(
+            "Current branch fast-forwarded.",
+            git.merge("other", options).unwrap()
+        );
+        assert_eq!(
+            git.resolve_commit("other").unwrap(),
+            git.resolve_commit("main").unwrap()
+        );
+        assert!(!work_dir.join("f3").exists());
+
+        // diverge: main changes f2, other adds f4
+        assert!(fs::write(work_dir.join("f2"), "f2 on main").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.merge("other", options).is_err());
+        assert!(git.commit("change f2").is_ok());
+        assert!(git.checkout_branch("other").is_ok());
+        assert!(fs::write(work_dir.join("f4"), "f4").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add f4").is_ok());
+        let other = git.resolve_commit("HEAD").unwrap();
+        assert!(git.checkout_branch("main").is_ok());
+        let main = git.resolve_commit("HEAD").unwrap();
+        assert_eq!(
+            Some(git.read_commit(&main).unwrap().parent().to_string()),
+            git.merge_base(&main, &other).unwrap()
+        );
+
+        assert_eq!("", git.merge("other", options).unwrap());
+        let merged = git
+            .read_commit(&git.resolve_commit("HEAD").unwrap())
+            .unwrap();
+        assert_eq!("Merged other into main.", merged.message());
+        assert_eq!(vec![main.as_str(), other.as_str()], merged.parents());
+        assert_eq!(
+            vec!["merge_ut/f1", "merge_ut/f2", "merge_ut/f4"],
+            merged.blobs().keys().collect::<Vec<_>>()
+        );
+        assert_eq!("f4", fs::read_to_string(work_dir.join("f4")).unwrap());
+        assert_eq!(
+            "f2 on main",
+            fs::read_to_string(work_dir.join("f2")).unwrap()
+        );
+        assert!(merged
+            .to_string()
+            .contains(&format!("Merge: {} {}", &main[..7], &other[..7])));
+        assert_eq!(
+            "Given branch is an ancestor of the current branch.",
+            git.merge("other", options).unwrap()
+        );
+        assert!(git
+            .ancestors(&git.commit_sha1.clone())
+            .unwrap()
+            .contains(&base));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
     }
 
     #[test]
-    fn init_repo_dir_ut() {
+    fn rebase_ut() {
         init();
-        let tmp_path = &env::current_dir().unwrap().join("init_repo_dir_ut");
-        assert!(GitRepository::init_repo_dir(tmp_path).is_ok());
-        assert!(tmp_path.exists());
-        assert!(tmp_path.is_dir());
-        assert!(fs::remove_dir(tmp_path).is_ok());
+        let repo_dir = ".rebase_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("rebase_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1").is_ok());
+        let all = &["rebase_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f1"), "f1 on topic").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("change f1").is_ok());
+        let change_f1 = git.resolve_commit("HEAD").unwrap();
+        assert!(fs::write(work_dir.join("f3"), "f3").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add f3").is_ok());
+        let old_topic = git.resolve_commit("HEAD").unwrap();
+
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("add f2").is_ok());
+        let main = git.resolve_commit("HEAD").unwrap();
+        assert!(git.rebase("main").is_err());
+        assert!(git.rebase("missing").is_err());
+
+        // cherry-pick a single commit onto main
+        assert!(git.cherry_pick(&change_f1).is_ok());
+        let picked = git
+            .read_commit(&git.resolve_commit("HEAD").unwrap())
+            .unwrap();
+        assert_eq!("change f1", picked.message());
+        assert_eq!(main, picked.parent());
+        assert_eq!(
+            "f1 on topic",
+            fs::read_to_string(work_dir.join("f1")).unwrap()
+        );
+        assert!(git.cherry_pick(&change_f1).is_err());
+
+        // replay topic on top of main, the already picked change is dropped
+        assert!(git.checkout_branch("topic").is_ok());
+        assert_eq!(
+            "Successfully rebased topic onto main.",
+            git.rebase("main").unwrap()
+        );
+        let topic = git.resolve_commit("HEAD").unwrap();
+        assert_ne!(old_topic, topic);
+        let rebased = git.read_commit(&topic).unwrap();
+        assert_eq!("add f3", rebased.message());
+        assert_eq!(git.resolve_commit("main").unwrap(), rebased.parent());
+        assert_eq!(
+            vec!["rebase_ut/f1", "rebase_ut/f2", "rebase_ut/f3"],
+            rebased.blobs().keys().collect::<Vec<_>>()
+        );
+        assert_eq!("f2", fs::read_to_string(work_dir.join("f2")).unwrap());
+        assert_eq!("Current branch is up to date.", git.rebase("main").unwrap());
+
+        // a conflicting commit stops the rebase, aborting it leaves everything as it was
+        assert!(fs::write(work_dir.join("f2"), "f2 on topic").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("change f2 on topic").is_ok());
+        let topic = git.resolve_commit("HEAD").unwrap();
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2 on main").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("change f2 on main").is_ok());
+        assert!(git.checkout_branch("topic").is_ok());
+        assert!(matches!(
+            git.rebase("main"),
+            Err(GitError::RebaseError(msg)) if msg.contains("conflict in rebase_ut/f2\n")
+        ));
+        assert!(git.rebase_abort().is_ok());
+        assert_eq!(topic, git.resolve_commit("HEAD").unwrap());
+        assert_eq!(
+            "f2 on topic",
+            fs::read_to_string(work_dir.join("f2")).unwrap()
+        );
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
     }
 
     #[test]
-    fn smoke_ut() {
+    fn rebase_continue_ut() {
         init();
-        info!("This record will be captured by `cargo test`");
-        let smoke_ut_repo_dir = ".smoke_ut_repo_dir";
-        let smoke_ut_dir = &env::current_dir().unwrap().join("smoke_ut");
-
-        if smoke_ut_dir.exists() {
-            assert!(fs::remove_dir_all(smoke_ut_dir).is_ok());
+        let repo_dir = ".rebase_continue_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("rebase_continue_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
         }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["rebase_continue_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("base").is_ok());
+        assert!(git.branch("topic").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2 on topic").is_ok());
+        assert!(fs::write(work_dir.join("f4"), "f4").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("change f2 on topic").is_ok());
+        let topic = git.resolve_commit("HEAD").unwrap();
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "f2 on main").is_ok());
+        assert!(git.add_all(all).is_ok());
+        assert!(git.commit("change f2 on main").is_ok());
+        let main = git.resolve_commit("HEAD").unwrap();
+        assert!(git.checkout_branch("topic").is_ok());
 
-        // prepare dir and files
-        assert!(fs::create_dir(smoke_ut_dir).is_ok());
-        assert!(fs::create_dir(smoke_ut_dir.join("d1")).is_ok());
-        let paths: Vec<PathBuf> = vec!["f1", "f2", "f3", "f4", "f5", "d1/f1", "d1/f2"]
-            .iter()
-            .map(|f| smoke_ut_dir.join(f))
-            .collect();
-        for path in paths.iter() {
-            let mut file = fs::File::create(path).unwrap();
-            assert!(file
-                .write_all(format!("this is a demo content for {}", path.display()).as_bytes())
-                .is_ok());
-        }
+        // the rebase stops at the conflict with the rest of the commit staged
+        assert!(matches!(
+            git.rebase("main"),
+            Err(GitError::RebaseError(msg)) if msg.contains("conflict in rebase_continue_ut/f2\n")
+        ));
+        let stopped = git.resolve_commit("HEAD").unwrap();
+        assert!(git.ancestors(&stopped).unwrap().contains(&main));
+        assert!(!git.ancestors(&stopped).unwrap().contains(&topic));
+        assert_eq!(
+            "<<<<<<< HEAD\nf2 on main\n=======\nf2 on topic\n>>>>>>>\n",
+            fs::read_to_string(work_dir.join("f2")).unwrap()
+        );
+        assert_eq!("f4", fs::read_to_string(work_dir.join("f4")).unwrap());
+        assert!(git
+            .staging_area
+            .staged
+            .contains_key("rebase_continue_ut/f4"));
+        assert!(matches!(
+            git.rebase_continue(),
+            Err(GitError::RebaseError(msg)) if msg.ends_with("rebase_continue_ut/f2")
+        ));
+        assert!(git.rebase_abort().is_ok());
+        assert_eq!(topic, git.resolve_commit("HEAD").unwrap());
+        assert!(git.staging_area.is_empty());
+        assert!(!git.rebase_state_file.exists());
 
-        clean_repo(GIT_DIR);
-        clean_repo(smoke_ut_repo_dir);
-        let git = &mut GitRepository::new(smoke_ut_repo_dir);
-        assert!(!git.repo_path.exists());
+        // resolved and staged, the rebase goes on where it stopped
+        assert!(git.rebase("main").is_err());
+        assert!(fs::write(work_dir.join("f2"), "resolved").is_ok());
+        assert!(git.add(&["rebase_continue_ut/f2".to_string()]).is_ok());
+        assert_eq!(
+            "Successfully rebased and updated topic.",
+            git.rebase_continue().unwrap()
+        );
+        assert_eq!(stopped, git.commit.parent);
+        assert_eq!("change f2 on topic", git.commit.message());
+        assert_eq!("resolved", fs::read_to_string(work_dir.join("f2")).unwrap());
+        assert_eq!("f4", fs::read_to_string(work_dir.join("f4")).unwrap());
+        assert!(git.staging_area.is_empty());
+        assert!(!git.rebase_state_file.exists());
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
 
+    #[test]
+    fn cherry_pick_range_ut() {
+        init();
+        let repo_dir = ".cherry_pick_range_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("cherry_pick_range_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["cherry_pick_range_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
         assert!(git.init().is_ok());
+        let commit = |git: &mut GitRepository, file: &str, content: &str, message: &str| {
+            assert!(fs::write(work_dir.join(file), content).is_ok());
+            assert!(git.add_all(all).is_ok());
+            assert!(git.commit(message).is_ok());
+            git.commit_sha1.clone()
+        };
+        commit(git, "f0", "base", "base");
+        assert!(git.branch("topic").is_ok());
+        let c1 = commit(git, "f1", "one", "c1");
+        commit(git, "f2", "two", "c2");
+        let c3 = commit(git, "f0", "topic", "c3");
+        assert!(git.checkout_branch("main").is_ok());
+        let main = commit(git, "f0", "main", "main");
+        assert!(git.cherry_pick_revs(&["topic..topic".to_string()]).is_err());
+        assert!(git.cherry_pick_continue().is_err());
 
-        assert!(git.repo_path.exists());
-        assert!(git.repo_path.is_dir());
-        assert!(git.blobs_path.exists());
-        assert!(git.blobs_path.is_dir());
-        assert!(git.commits_path.exists());
-        assert!(git.commits_path.is_dir());
-        assert!(git.heads_path.exists());
-        assert!(git.heads_path.is_dir());
+        // the range is picked oldest first up to the conflicting commit
+        let range = format!("{}..topic", main);
+        assert!(matches!(
+            git.cherry_pick_revs(&[range]),
+            Err(GitError::CherryPickError(msg)) if msg.contains("conflict in cherry_pick_range_ut/f0\n")
+        ));
+        let picked = git.resolve_commit("HEAD").unwrap();
+        assert_eq!("c2", git.commit.message());
+        assert_eq!(main, git.resolve_commit("HEAD~2").unwrap());
+        assert_eq!("two", fs::read_to_string(work_dir.join("f2")).unwrap());
+        assert_eq!(
+            "<<<<<<< HEAD\nmain\n=======\ntopic\n>>>>>>>\n",
+            fs::read_to_string(work_dir.join("f0")).unwrap()
+        );
+        assert!(git.cherry_pick(&c1).is_err());
+        assert!(git.cherry_pick_continue().is_err());
+        assert!(fs::write(work_dir.join("f0"), "resolved").is_ok());
+        assert!(git.add(&["cherry_pick_range_ut/f0".to_string()]).is_ok());
+        assert!(git.cherry_pick_continue().is_ok());
+        assert_eq!("c3", git.commit.message());
+        assert_eq!(picked, git.commit.parent);
+        assert_eq!("resolved", fs::read_to_string(work_dir.join("f0")).unwrap());
+        assert!(!git.cherry_pick_state_file.exists());
 
-        assert!(git.head_file.exists());
-        assert!(git.head_file.is_file());
-        assert!(git.head_file.is_file());
-        assert!(git.index_file.exists());
-        assert!(git.index_file.is_file());
+        // several commits, aborted on a conflict
+        let head = git.commit_sha1.clone();
+        assert!(git.cherry_pick(&c3).is_err());
+        assert!(git.cherry_pick_abort().is_ok());
+        assert_eq!(head, git.resolve_commit("HEAD").unwrap());
+        assert_eq!("resolved", fs::read_to_string(work_dir.join("f0")).unwrap());
+        assert!(git.staging_area.is_empty());
+        assert!(git.cherry_pick_abort().is_err());
 
-        assert!(git.heads_path.join(MAIN_BRANCH).exists());
-        assert!(git.heads_path.join(MAIN_BRANCH).is_file());
-        // Act git add f1
-        assert_eq!(git.branch, "main");
-        assert_eq!(git.commit, Commit::new());
-        let res = git.add(&vec!["smoke_ut/f1".to_string()]);
-        assert!(res.is_ok(), "{:?}", res.err().unwrap());
-        // Verify staging add file
-        let mut file = fs::File::open(&git.index_file).unwrap();
-        let mut content = String::new();
-        assert!(file.read_to_string(&mut content).is_ok());
+        // a change already there stops before the commits left
+        assert!(matches!(
+            git.cherry_pick_revs(&[c1, c3]),
+            Err(GitError::CherryPickError(msg)) if msg.ends_with("cherry-pick --abort")
+        ));
+        assert!(git.cherry_pick_state_file.exists());
+        assert!(git.cherry_pick_continue().is_err());
+        assert!(git.cherry_pick_abort().is_ok());
+        assert_eq!(head, git.resolve_commit("HEAD").unwrap());
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn rebase_onto_ut() {
+        init();
+        let repo_dir = ".rebase_onto_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("rebase_onto_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["rebase_onto_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let commit = |git: &mut GitRepository, file: &str| {
+            assert!(fs::write(work_dir.join(file), file).is_ok());
+            assert!(git.add_all(all).is_ok());
+            assert!(git.commit(file).is_ok());
+        };
+        commit(git, "f0");
+        assert!(git.branch("next").is_ok());
+        commit(git, "f1");
+        assert!(git.branch("topic").is_ok());
+        commit(git, "f2");
+        commit(git, "f3");
+        let main = git.resolve_commit("main").unwrap();
+        assert!(git.rebase_onto("missing", "next").is_err());
+        assert!(git.rebase_onto("main", "missing").is_err());
+
+        // only the commits of next..topic move onto main
         assert_eq!(
-            r#"{"staged":{"smoke_ut/f1":"436e9d92cf041816563850964d9256d7b0484c46"},"deleted":{}}"#,
-            content.as_str()
+            "Successfully rebased topic onto main.",
+            git.rebase_onto("main", "next").unwrap()
+        );
+        assert_eq!(main, git.resolve_commit("HEAD~2").unwrap());
+        assert_eq!("f3", git.commit.message());
+        assert!(!work_dir.join("f1").exists());
+        assert_eq!("f2", fs::read_to_string(work_dir.join("f2")).unwrap());
+        assert_eq!(
+            vec!["f3", "f2"],
+            git.commit_range("main..topic")
+                .unwrap()
+                .iter()
+                .map(|(_, c)| c.message())
+                .collect::<Vec<_>>()
         );
 
-        let res = git.add(&vec!["smoke_ut/f2".to_string(), "smoke_ut/f3".to_string()]);
-        // Act git add f2
-        assert!(res.is_ok(), "{:?}", res);
-        // Verify staging add file
-        let mut file = fs::File::open(&git.index_file).unwrap();
-        let mut content = String::new();
-        assert!(file.read_to_string(&mut content).is_ok());
+        // an empty range moves the branch to the new base
+        assert!(git.rebase_onto("next", "HEAD").is_ok());
         assert_eq!(
-            r#"{"staged":{"smoke_ut/f1":"436e9d92cf041816563850964d9256d7b0484c46","smoke_ut/f2":"edf058309c9c35b69458bc469344d7e7f9906ac2","smoke_ut/f3":"de9c94ac88cae8cd61843b1ccd1339ad507e7f49"},"deleted":{}}"#,
-            content.as_str()
+            git.resolve_commit("next").unwrap(),
+            git.resolve_commit("topic").unwrap()
         );
+        assert!(!work_dir.join("f2").exists());
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
 
-        // Act git rm f2
-        let res = git.remove(&vec!["smoke_ut/f2".to_string()]);
-        assert!(res.is_ok(), "{:?}", res);
-        // Verify staging add file
-        let mut file = fs::File::open(&git.index_file).unwrap();
-        let mut content = String::new();
-        assert!(file.read_to_string(&mut content).is_ok());
+    #[test]
+    fn rebase_interactive_ut() {
+        init();
+        let repo_dir = ".rebase_interactive_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("rebase_interactive_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let all = &["rebase_interactive_ut".to_string()];
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        let base = git.resolve_commit("HEAD").unwrap();
+        for name in ["one", "two", "three", "four"] {
+            assert!(fs::write(work_dir.join(name), name).is_ok());
+            assert!(git.add_all(all).is_ok());
+            assert!(git.commit(name).is_ok());
+        }
+        let orig_head = git.resolve_commit("HEAD").unwrap();
+
+        // the editor drops two, rewords three and squashes four into it
+        let editor = work_dir.join("editor.sh");
+        assert!(fs::write(
+            &editor,
+            r#"sed -i -e 's/^pick \(.*\) two$/drop \1 two/' \
+  -e 's/^pick \(.*\) three$/reword \1 three/' \
+  -e 's/^pick \(.*\) four$/squash \1 four/' \
+  -e 's/^three$/three reworded/' "$1"
+"#
+        )
+        .is_ok());
+        assert!(git
+            .set_config("core.editor", &format!("sh {}", editor.display()))
+            .is_ok());
+        assert!(git.rebase_continue().is_err());
+        assert!(git.rebase_abort().is_err());
         assert_eq!(
-            r#"{"staged":{"smoke_ut/f1":"436e9d92cf041816563850964d9256d7b0484c46","smoke_ut/f3":"de9c94ac88cae8cd61843b1ccd1339ad507e7f49"},"deleted":{}}"#,
-            content.as_str()
+            "Successfully rebased and updated main.",
+            git.rebase_interactive(&base).unwrap()
         );
-        let mut git = GitRepository::new(smoke_ut_repo_dir);
-        assert!(git.load_basic_info().is_ok());
-        let res = git.staged_status();
-        assert!(res.is_ok(), "{:?}", res);
+        assert!(!git.rebase_state_file.exists());
+        assert!(!git.rebase_todo_file.exists());
+        let history = git.history(&git.resolve_commit("HEAD").unwrap()).unwrap();
         assert_eq!(
-            r#"=== Staged Files ===
-smoke_ut/f1
-smoke_ut/f3"#,
-            res.unwrap()
+            vec!["three reworded\n\nfour", "one", "initial commit"],
+            history.iter().map(|(_, c)| c.message()).collect::<Vec<_>>()
         );
-        // Act git commit "commit test"
-        let res = git.commit("commit test");
-        assert!(res.is_ok(), "{:?}", res);
-        // Verify staging add file
-        // let res = git.load_basic_info();
-        // assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(base, history[1].1.parent());
         assert_eq!(
-            git.commit.blobs,
-            BTreeMap::from([
-                (
-                    "smoke_ut/f1".to_string(),
-                    "436e9d92cf041816563850964d9256d7b0484c46".to_string()
-                ),
-                (
-                    "smoke_ut/f3".to_string(),
-                    "de9c94ac88cae8cd61843b1ccd1339ad507e7f49".to_string()
-                ),
-            ])
+            vec![
+                "rebase_interactive_ut/four",
+                "rebase_interactive_ut/one",
+                "rebase_interactive_ut/three"
+            ],
+            history[0].1.blobs().keys().collect::<Vec<_>>()
         );
+        assert!(!work_dir.join("two").exists());
+        assert_eq!(5, git.ancestors(&orig_head).unwrap().len());
 
-        // Act git rm f1
-        let res = git.remove(&vec!["smoke_ut/f1".to_string()]);
-        assert!(res.is_ok(), "{:?}", res);
-        // Verify staging add file
-        let mut file = fs::File::open(&git.index_file).unwrap();
-        let mut content = String::new();
-        assert!(file.read_to_string(&mut content).is_ok());
+        // a plan that cannot run stops and can be resumed or aborted
+        let head = git.resolve_commit("HEAD").unwrap();
+        assert!(fs::write(&editor, "sed -i -e 's/^pick/squash/' \"$1\"\n").is_ok());
+        assert!(matches!(
+            git.rebase_interactive(&history[1].0),
+            Err(GitError::RebaseError(_))
+        ));
+        assert!(git.rebase_state_file.exists());
+        assert!(git.rebase("main").is_err());
+        assert!(git.rebase_interactive(&base).is_err());
+        assert!(git.rebase_abort().is_ok());
+        assert_eq!(head, git.resolve_commit("HEAD").unwrap());
+
+        assert!(matches!(
+            git.rebase_interactive(&history[1].0),
+            Err(GitError::RebaseError(_))
+        ));
+        let todo = fs::read_to_string(&git.rebase_todo_file).unwrap();
+        assert!(fs::write(&git.rebase_todo_file, todo.replace("squash", "reword")).is_ok());
+        assert!(fs::write(&editor, "sed -i -e 's/^three reworded$/three/' \"$1\"\n").is_ok());
+        assert!(git.rebase_continue().is_ok());
         assert_eq!(
-            r#"{"staged":{},"deleted":{"smoke_ut/f1":""}}"#,
-            content.as_str()
+            "three\n\nfour",
+            git.read_commit(&git.resolve_commit("HEAD").unwrap())
+                .unwrap()
+                .message()
         );
+        assert_eq!("Nothing to do.", git.rebase_interactive("HEAD").unwrap());
 
-        let mut git = GitRepository::new(smoke_ut_repo_dir);
-        assert!(git.load_basic_info().is_ok());
-        let res = git.removal_status();
-        assert!(res.is_ok(), "{:?}", res);
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn global_log_ut() {
+        init();
+        let repo_dir = ".global_log_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.branch("other").is_ok());
+        assert!(git.commit("on other").is_ok());
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(git.commit("on main").is_ok());
+
+        let commits = git.all_commits().unwrap();
+        assert_eq!(3, commits.len());
+        for (sha1, commit) in commits.iter() {
+            assert_eq!(sha1, &utils::sha1(commit).unwrap());
+        }
+        let log = git.global_log().unwrap();
+        assert_eq!(3, log.matches("===\ncommit ").count());
+        for msg in ["initial commit", "on other", "on main"] {
+            assert!(log.contains(&format!("{}\n", msg)), "{}", log);
+        }
+        assert!(!git.log().unwrap().contains("on other"));
+
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn find_ut() {
+        init();
+        let repo_dir = ".find_ut_repo_dir";
+        clean_repo(repo_dir);
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.branch("other").is_ok());
+        assert!(git.commit("on other").is_ok());
+        assert!(git.checkout_branch("main").is_ok());
+        assert!(git.commit("on main").is_ok());
+        assert!(git.commit("on main").is_ok());
+
+        // commits of every branch, not only the ones reachable from HEAD
+        let on_other = git.find("on other").unwrap();
+        assert_eq!(vec![git.resolve_commit("other").unwrap()], on_other);
+        assert_eq!(1, git.find("initial commit").unwrap().len());
+        assert_eq!(2, git.find("on main").unwrap().len());
+        assert!(git.find("on").is_err());
+
+        clean_repo(repo_dir);
+    }
+
+    #[test]
+    fn notes_ut() {
+        init();
+        let repo_dir = ".notes_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("notes_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(fs::write(work_dir.join("f1"), "a\n").is_ok());
+        assert!(git.add_all(&["notes_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+        let first = git.resolve_commit("HEAD").unwrap();
+        assert!(fs::write(work_dir.join("f1"), "b\n").is_ok());
+        assert!(git.add_all(&["notes_ut".to_string()]).is_ok());
+        assert!(git.commit("change files").is_ok());
+        let head = git.resolve_commit("HEAD").unwrap();
+        let author = git.identity().unwrap();
+
+        assert!(git.notes_show("HEAD").is_err());
+        assert!(git
+            .notes_add("HEAD", "build: passed\nreviewed", false)
+            .is_ok());
+        assert!(git.notes_add("HEAD", "again", false).is_err());
+        assert_eq!("build: passed\nreviewed", git.notes_show(&head).unwrap());
+        let notes = "\nNotes:\n    build: passed\n    reviewed\n";
+        assert!(git
+            .show("HEAD")
+            .unwrap()
+            .contains(&format!("\n\nchange files\n{}\n--- a/notes_ut/f1\n", notes)));
+        assert!(git
+            .log()
+            .unwrap()
+            .starts_with(&format!("===\ncommit {}\nAuthor: {}\nDate: ", head, author)));
+        assert!(git
+            .log()
+            .unwrap()
+            .contains(&format!("\nchange files\n{}\n\n\n===\n", notes)));
+        assert!(git.notes_add("HEAD", "replaced", true).is_ok());
+        assert_eq!("replaced", git.notes_show("HEAD").unwrap());
+        assert!(!git.show(&first).unwrap().contains("Notes:"));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+    }
+
+    #[test]
+    fn diff_ut() {
+        init();
+        let repo_dir = ".diff_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("diff_ut");
+        clean_repo(repo_dir);
+        if work_dir.exists() {
+            assert!(fs::remove_dir_all(work_dir).is_ok());
+        }
+        assert!(fs::create_dir_all(work_dir).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "a\nb\nc\n").is_ok());
+        assert!(fs::write(work_dir.join("f2"), "x\n").is_ok());
+        let git = &mut GitRepository::new(repo_dir);
+        assert!(git.init().is_ok());
+        assert!(git.add_all(&["diff_ut".to_string()]).is_ok());
+        assert!(git.commit("add files").is_ok());
+        assert_eq!("", git.diff().unwrap());
+
+        assert!(fs::write(work_dir.join("f1"), "a\nB\nc\n").is_ok());
+        assert!(fs::remove_file(work_dir.join("f2")).is_ok());
+        assert!(fs::write(work_dir.join("f3"), "untracked\n").is_ok());
         assert_eq!(
-            r#"=== Removed Files ===
-smoke_ut/f1"#,
-            res.unwrap()
+            r#"--- a/diff_ut/f1
++++ b/diff_ut/f1
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+--- a/diff_ut/f2
++++ /dev/null
+@@ -1,1 +0,0 @@
+-x
+"#,
+            git.diff().unwrap()
         );
 
-        // Act git commit "commit test"
-        let prev_commit = git.commit_sha1.clone();
-        let res = git.commit("commit 2nd");
-        assert!(res.is_ok(), "{:?}", res);
-        // Verify staging add file
-        let mut git = GitRepository::new(smoke_ut_repo_dir);
-        let res = git.load_basic_info();
-        assert!(res.is_ok(), "{:?}", res);
-        let commit = &git.commit;
+        let first = git.resolve_commit("HEAD").unwrap();
+        assert!(git.add_all(&["diff_ut".to_string()]).is_ok());
+        assert!(git.commit("change files").is_ok());
+        assert_eq!("", git.diff_commits("HEAD", "HEAD").unwrap());
         assert_eq!(
-            commit.blobs,
-            BTreeMap::from([(
-                "smoke_ut/f3".to_string(),
-                "de9c94ac88cae8cd61843b1ccd1339ad507e7f49".to_string()
-            ),])
+            r#"--- a/diff_ut/f1
++++ b/diff_ut/f1
+@@ -1,3 +1,3 @@
+ a
+-b
++B
+ c
+--- a/diff_ut/f2
++++ /dev/null
+@@ -1,1 +0,0 @@
+-x
+--- /dev/null
++++ b/diff_ut/f3
+@@ -0,0 +1,1 @@
++untracked
+"#,
+            git.diff_commits(&first, "HEAD").unwrap()
         );
-        assert_eq!(prev_commit, commit.parent);
+        assert!(git.diff_commits("missing", "HEAD").is_err());
 
-        let mut git = GitRepository::new(smoke_ut_repo_dir);
-        assert!(git.load_basic_info().is_ok());
-        let res = git.branch_status();
-        assert!(res.is_ok(), "{:?}", res);
+        let head = git.resolve_commit("HEAD").unwrap();
+        let show = git.show("HEAD").unwrap();
+        let author = git.identity().unwrap();
+        assert!(show.starts_with(&format!(
+            "commit {}\nParents: {}\nAuthor: {}\nDate: ",
+            head, first, author
+        )));
+        assert!(show.contains("\n\nchange files\n\n--- a/diff_ut/f1\n"));
+        assert!(show.ends_with(&git.diff_commits(&first, "HEAD").unwrap()));
+
+        // tag names are accepted wherever a commit id is
+        assert!(git.create_tag("before", &first, None).is_ok());
+        assert_eq!(Some(&first), git.tags().unwrap().get("before"));
         assert_eq!(
-            r#"=== Branches ===
-*main"#,
-            res.unwrap()
+            git.diff_commits(&first, "HEAD").unwrap(),
+            git.diff_commits("before", "HEAD").unwrap()
         );
+        assert_eq!(1, git.commit_range("before..HEAD").unwrap().len());
 
-        let res = git.modified_not_staged();
-        assert!(res.is_ok(), "{:?}", res);
+        // so are unique prefixes of commit ids
+        assert_eq!(head, git.resolve_commit(&head[..10]).unwrap());
         assert_eq!(
-            r#"=== Modifications Not Staged For Commit ==="#,
-            res.unwrap()
+            first,
+            git.resolve_commit(&first[..7].to_uppercase()).unwrap()
         );
-
-        fs::write(
-            smoke_ut_dir.join("f3"),
-            "this is a modification content for f3",
-        )
-        .unwrap();
-        let res = git.modified_not_staged();
-        assert!(res.is_ok(), "{:?}", res);
+        assert!(git.resolve_commit(&head[..3]).is_err());
+        let short = git.abbreviate(&head, revision::DEFAULT_ABBREV).unwrap();
+        assert!(short.len() >= revision::DEFAULT_ABBREV && head.starts_with(&short));
+        assert_eq!(head, git.resolve_commit(&short).unwrap());
         assert_eq!(
-            r#"=== Modifications Not Staged For Commit ===
-smoke_ut/f3 (modified)"#,
-            res.unwrap()
+            git.diff_commits(&first, "HEAD").unwrap(),
+            git.diff_commits(&first[..8], &short).unwrap()
         );
 
-        fs::remove_file(smoke_ut_dir.join("f3")).unwrap();
-        let res = git.modified_not_staged();
-        assert!(res.is_ok(), "{:?}", res);
         assert_eq!(
-            r#"=== Modifications Not Staged For Commit ===
-smoke_ut/f3 (deleted)"#,
-            res.unwrap()
+            vec![
+                "diff_ut/patches/0001-add-files.patch",
+                "diff_ut/patches/0002-change-files.patch"
+            ],
+            git.format_patch("..HEAD", Some("diff_ut/patches")).unwrap()
         );
+        let mail = fs::read_to_string(work_dir.join("patches/0002-change-files.patch")).unwrap();
+        assert!(mail.starts_with(&format!("From {} Mon Sep 17 00:00:00 2001\nFrom: ", head)));
+        assert!(mail.contains("\nSubject: [PATCH 2/2] change files\n\n---\n"));
+        assert!(mail.ends_with(&format!(
+            "{}-- \ngit-rs\n",
+            git.diff_commits(&first, "HEAD").unwrap()
+        )));
+        assert!(git.format_patch("HEAD..HEAD", None).unwrap().is_empty());
+        assert!(fs::remove_dir_all(work_dir.join("patches")).is_ok());
 
-        let mut git = GitRepository::new(smoke_ut_repo_dir);
-        let res = git.branch("new_branch");
-        assert!(res.is_ok(), "{:?}", res);
-        let res = git.branch_status();
-        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!("", git.diff_staged().unwrap());
+        assert!(fs::write(work_dir.join("f1"), "a\nB\nc\nd\n").is_ok());
+        assert!(git.add(&["diff_ut/f1".to_string()]).is_ok());
+        assert!(git.remove(&["diff_ut/f3".to_string()]).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "not staged\n").is_ok());
         assert_eq!(
-            r#"=== Branches ===
-*new_branch
-main"#,
-            res.unwrap()
+            r#"--- a/diff_ut/f1
++++ b/diff_ut/f1
+@@ -1,3 +1,4 @@
+ a
+ B
+ c
++d
+--- a/diff_ut/f3
++++ /dev/null
+@@ -1,1 +0,0 @@
+-untracked
+"#,
+            git.diff_staged().unwrap()
         );
-        clean_repo(smoke_ut_repo_dir);
-        assert!(fs::remove_dir_all(smoke_ut_dir).is_ok());
+
+        // a new file mostly copied from a tracked one
+        assert!(fs::write(work_dir.join("f4"), "a\nB\nc\ne\n").is_ok());
+        assert!(git.add(&["diff_ut/f4".to_string()]).is_ok());
+        assert!(git
+            .diff_staged()
+            .unwrap()
+            .contains("--- /dev/null\n+++ b/diff_ut/f4\n"));
+        let options = DiffOptions {
+            find_copies: true,
+            ..Default::default()
+        };
+        assert!(git.diff_staged_with(&options).unwrap().ends_with(
+            r#"similarity index 85%
+copy from diff_ut/f1
+copy to diff_ut/f4
+--- a/diff_ut/f1
++++ b/diff_ut/f4
+@@ -1,3 +1,4 @@
+ a
+ B
+ c
++e
+"#
+        ));
+
+        clean_repo(repo_dir);
+        assert!(fs::remove_dir_all(work_dir).is_ok());
     }
 
     #[test]
     fn staged_area_serialized_deserialized_ut() {
         let area = StagingArea {
             staged: BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
             ]),
             deleted: BTreeMap::new(),
         };
@@ -894,8 +12317,8 @@ main"#,
 
         let deserialized: StagingArea = serde_json::from_str(&serialized).unwrap();
         assert_eq!(2, deserialized.staged.len());
-        assert_eq!("hash1", deserialized.staged.get("file1").unwrap().as_str());
-        assert_eq!("hash2", deserialized.staged.get("file2").unwrap().as_str());
+        assert_eq!("hash1", deserialized.staged["file1"].sha1);
+        assert_eq!("hash2", deserialized.staged["file2"].sha1);
     }
 
     #[test]
@@ -918,8 +12341,8 @@ main"#,
 
         let area = StagingArea {
             staged: BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
             ]),
             deleted: BTreeMap::new(),
         };
@@ -935,7 +12358,7 @@ main"#,
             content.as_str()
         );
         assert!(fs::remove_file(&tmp_file).is_ok());
-        assert!(fs::remove_dir(&tmp_dir).is_ok());
+        assert!(fs::remove_dir(tmp_dir).is_ok());
     }
 
     #[test]
@@ -949,12 +12372,16 @@ main"#,
             meta: CommitMeta {
                 message: "persist commit ut message".to_string(),
                 date_time: 1234567890,
+                author: String::new(),
+                committer: String::new(),
             },
             blobs: BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
             ]),
             parent: "mock_parent".to_string(),
+            merge_parents: vec![],
+            signature: String::new(),
         };
         let res = GitRepository::persist(&area, &tmp_file);
         assert!(res.is_ok(), "{:?}", res);
@@ -968,7 +12395,7 @@ main"#,
             content.as_str()
         );
         assert!(fs::remove_file(&tmp_file).is_ok());
-        assert!(fs::remove_dir(&tmp_dir).is_ok());
+        assert!(fs::remove_dir(tmp_dir).is_ok());
     }
 
     #[test]
@@ -989,15 +12416,15 @@ main"#,
         assert_eq!(
             StagingArea {
                 staged: BTreeMap::from([
-                    ("file1".to_string(), "hash1".to_string()),
-                    ("file2".to_string(), "hash2".to_string()),
+                    ("file1".to_string(), Entry::new("hash1")),
+                    ("file2".to_string(), Entry::new("hash2")),
                 ]),
                 deleted: BTreeMap::new(),
             },
             res.unwrap()
         );
         assert!(fs::remove_file(&tmp_file).is_ok());
-        assert!(fs::remove_dir(&tmp_dir).is_ok());
+        assert!(fs::remove_dir(tmp_dir).is_ok());
     }
 
     #[test]
@@ -1016,17 +12443,21 @@ main"#,
                 meta: CommitMeta {
                     message: "persist commit ut message".to_string(),
                     date_time: 1234567890,
+                    author: String::new(),
+                    committer: String::new(),
                 },
                 blobs: BTreeMap::from([
-                    ("file1".to_string(), "hash1".to_string()),
-                    ("file2".to_string(), "hash2".to_string()),
+                    ("file1".to_string(), Entry::new("hash1")),
+                    ("file2".to_string(), Entry::new("hash2")),
                 ]),
                 parent: "mock_parent".to_string(),
+                merge_parents: vec![],
+                signature: String::new(),
             },
             res.unwrap()
         );
         assert!(fs::remove_file(&tmp_file).is_ok());
-        assert!(fs::remove_dir(&tmp_dir).is_ok());
+        assert!(fs::remove_dir(tmp_dir).is_ok());
     }
 
     #[test]
@@ -1034,16 +12465,16 @@ main"#,
         let old = BTreeMap::new();
         let staging_area = StagingArea {
             staged: BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
             ]),
             deleted: BTreeMap::new(),
         };
         let new_blobs = GitRepository::generate_commit_blobs(&old, &staging_area).unwrap();
         assert_eq!(
             BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
             ]),
             new_blobs
         );
@@ -1052,23 +12483,23 @@ main"#,
     #[test]
     fn generate_commit_blobs_ut2() {
         let old = BTreeMap::from([
-            ("file1".to_string(), "hash1".to_string()),
-            ("file2".to_string(), "hash2".to_string()),
+            ("file1".to_string(), Entry::new("hash1")),
+            ("file2".to_string(), Entry::new("hash2")),
         ]);
         let staging_area = StagingArea {
             staged: BTreeMap::from([
-                ("file3".to_string(), "hash3".to_string()),
-                ("file4".to_string(), "hash4".to_string()),
+                ("file3".to_string(), Entry::new("hash3")),
+                ("file4".to_string(), Entry::new("hash4")),
             ]),
             deleted: BTreeMap::new(),
         };
         let new_blobs = GitRepository::generate_commit_blobs(&old, &staging_area).unwrap();
         assert_eq!(
             BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
-                ("file3".to_string(), "hash3".to_string()),
-                ("file4".to_string(), "hash4".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
+                ("file3".to_string(), Entry::new("hash3")),
+                ("file4".to_string(), Entry::new("hash4")),
             ]),
             new_blobs
         );
@@ -1076,16 +12507,20 @@ main"#,
 
     #[test]
     fn commit_display_ut() {
-        let commit = Commit {
+        let mut commit = Commit {
             meta: CommitMeta {
                 message: "commit display ut message".to_string(),
                 date_time: 1234567890,
+                author: String::new(),
+                committer: String::new(),
             },
             blobs: BTreeMap::from([
-                ("file1".to_string(), "hash1".to_string()),
-                ("file2".to_string(), "hash2".to_string()),
+                ("file1".to_string(), Entry::new("hash1")),
+                ("file2".to_string(), Entry::new("hash2")),
             ]),
             parent: "mock_parent".to_string(),
+            merge_parents: vec![],
+            signature: String::new(),
         };
         assert_eq!(
             r#"===
@@ -1095,6 +12530,20 @@ commit display ut message
 "#,
             commit.to_string()
         );
+
+        // commits without author still deserialize
+        let json = serde_json::to_string(&commit).unwrap();
+        assert!(!json.contains("author"));
+        assert_eq!(commit, serde_json::from_str(&json).unwrap());
+        commit.meta.author = "Jane <jane@example.com>".to_string();
+        commit.meta.committer = "Jane <jane@example.com>".to_string();
+        assert!(commit
+            .to_string()
+            .ends_with("\nAuthor: Jane <jane@example.com>\nDate: Fri Feb 13 23:31:30 2009 +0000\ncommit display ut message\n"));
+        commit.meta.committer = "Joe <joe@example.com>".to_string();
+        assert!(commit.to_string().contains(
+            "\nAuthor: Jane <jane@example.com>\nCommitter: Joe <joe@example.com>\nDate: "
+        ));
     }
 
     #[test]
@@ -1103,15 +12552,15 @@ commit display ut message
             .unwrap()
             .join("committed_file_modified_not_stage_ut");
         if tmp_dir.exists() {
-            assert!(fs::remove_dir_all(&tmp_dir).is_ok());
+            assert!(fs::remove_dir_all(tmp_dir).is_ok());
         }
         assert!(fs::create_dir_all(tmp_dir).is_ok());
 
-        for dir in vec!["d1", "d2"] {
-            assert!(fs::create_dir_all(&tmp_dir.join(dir)).is_ok());
+        for dir in ["d1", "d2"] {
+            assert!(fs::create_dir_all(tmp_dir.join(dir)).is_ok());
         }
 
-        for path in vec!["f1", "f2", "f3", "d1/f1", "d2/f2"] {
+        for path in ["f1", "f2", "f3", "d1/f1", "d2/f2"] {
             let tmp_file = tmp_dir.join(path);
             let mut file = fs::File::create(&tmp_file).unwrap();
             assert!(file
@@ -1120,21 +12569,21 @@ commit display ut message
         }
 
         let file_sha1_map = BTreeMap::from([
-            ("f1".to_string(), "hash1".to_string()),
-            ("f2".to_string(), "hash2_new".to_string()),
-            ("f3".to_string(), "hash3".to_string()),
-            ("d1/f1".to_string(), "hash4".to_string()),
-            ("d2/f2".to_string(), "hash5_new".to_string()),
+            ("f1".to_string(), Entry::new("hash1")),
+            ("f2".to_string(), Entry::new("hash2_new")),
+            ("f3".to_string(), Entry::new("hash3")),
+            ("d1/f1".to_string(), Entry::new("hash4")),
+            ("d2/f2".to_string(), Entry::new("hash5_new")),
         ]);
         let commit = BTreeMap::from([
-            ("f1".to_string(), "hash1".to_string()),
-            ("f2".to_string(), "hash2".to_string()),
-            ("f4".to_string(), "hash2".to_string()),
+            ("f1".to_string(), Entry::new("hash1")),
+            ("f2".to_string(), Entry::new("hash2")),
+            ("f4".to_string(), Entry::new("hash2")),
         ]);
         let staged = BTreeMap::from([
-            ("f3".to_string(), "hash3".to_string()),
-            ("d2/f2".to_string(), "hash5".to_string()),
-            ("d2/f3".to_string(), "hash5".to_string()),
+            ("f3".to_string(), Entry::new("hash3")),
+            ("d2/f2".to_string(), Entry::new("hash5")),
+            ("d2/f3".to_string(), Entry::new("hash5")),
         ]);
         let deleted = BTreeMap::from([("d1/f1".to_string(), "".to_string())]);
         assert_eq!(
@@ -1153,21 +12602,21 @@ commit display ut message
             vec!["f4 (deleted)"],
             GitRepository::not_staged_for_removal_but_deleted(&file_sha1_map, &commit, &deleted)
         );
-        assert!(fs::remove_dir_all(&tmp_dir).is_ok());
+        assert!(fs::remove_dir_all(tmp_dir).is_ok());
     }
     #[test]
     fn untracked_file_ut() {
         let tmp_dir = &env::current_dir().unwrap().join("untracked_file_ut");
         if tmp_dir.exists() {
-            assert!(fs::remove_dir_all(&tmp_dir).is_ok());
+            assert!(fs::remove_dir_all(tmp_dir).is_ok());
         }
         assert!(fs::create_dir_all(tmp_dir).is_ok());
 
-        for dir in vec!["d1", "d2"] {
-            assert!(fs::create_dir_all(&tmp_dir.join(dir)).is_ok());
+        for dir in ["d1", "d2"] {
+            assert!(fs::create_dir_all(tmp_dir.join(dir)).is_ok());
         }
 
-        for path in vec!["f1", "f2", "f3", "d1/f1", "d2/f2"] {
+        for path in ["f1", "f2", "f3", "d1/f1", "d2/f2"] {
             let tmp_file = tmp_dir.join(path);
             let mut file = fs::File::create(&tmp_file).unwrap();
             assert!(file
@@ -1176,26 +12625,26 @@ commit display ut message
         }
 
         let file_sha1_map = BTreeMap::from([
-            ("f1".to_string(), "hash1".to_string()),
-            ("f2".to_string(), "hash2_new".to_string()),
-            ("f3".to_string(), "hash3".to_string()),
-            ("d1/f1".to_string(), "hash4".to_string()),
-            ("d2/f2".to_string(), "hash5_new".to_string()),
+            ("f1".to_string(), Entry::new("hash1")),
+            ("f2".to_string(), Entry::new("hash2_new")),
+            ("f3".to_string(), Entry::new("hash3")),
+            ("d1/f1".to_string(), Entry::new("hash4")),
+            ("d2/f2".to_string(), Entry::new("hash5_new")),
         ]);
         let commit = BTreeMap::from([
-            ("f1".to_string(), "hash1".to_string()),
-            ("f2".to_string(), "hash2".to_string()),
-            ("f4".to_string(), "hash2".to_string()),
+            ("f1".to_string(), Entry::new("hash1")),
+            ("f2".to_string(), Entry::new("hash2")),
+            ("f4".to_string(), Entry::new("hash2")),
         ]);
         let staged = BTreeMap::from([
-            ("d2/f2".to_string(), "hash5".to_string()),
-            ("d2/f3".to_string(), "hash5".to_string()),
+            ("d2/f2".to_string(), Entry::new("hash5")),
+            ("d2/f3".to_string(), Entry::new("hash5")),
         ]);
-        let deleted = BTreeMap::from([("d1/f1".to_string(), "".to_string())]);
+        let _deleted = BTreeMap::from([("d1/f1".to_string(), "".to_string())]);
         assert_eq!(
             vec!["d1/f1", "f3"],
             GitRepository::untracked_file(&file_sha1_map, &commit, &staged)
         );
-        assert!(fs::remove_dir_all(&tmp_dir).is_ok());
+        assert!(fs::remove_dir_all(tmp_dir).is_ok());
     }
 }
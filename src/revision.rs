@@ -0,0 +1,255 @@
+use crate::error::GitError;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// shortest sha1 prefix accepted as an abbreviated commit id
+pub const MIN_PREFIX_LEN: usize = 4;
+/// default length of abbreviated commit ids
+pub const DEFAULT_ABBREV: usize = 7;
+
+/// check that a revision can name a ref or a commit: not empty, no path separators, no
+/// ancestry suffixes and not named like the lock file of a ref
+pub fn check_name(rev: &str) -> Result<(), GitError> {
+    if rev.is_empty()
+        || rev.contains(['/', '~', '^'])
+        || rev.contains("..")
+        || rev.ends_with(".lock")
+    {
+        return Err(GitError::RevisionError(format!("invalid revision {}", rev)));
+    }
+    Ok(())
+}
+
+/// A step from a commit to one of its ancestors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ancestry {
+    /// `^n`, the n-th parent, `^0` is the commit itself
+    Parent(usize),
+    /// `~n`, the n-th generation ancestor following first parents
+    Ancestor(usize),
+}
+
+/// Split the ancestry suffixes off a revision: `main~2^2` is `main` with the steps
+/// `~2` and `^2`. A suffix without a number counts one, `HEAD^^` is `HEAD^1^1`.
+pub fn split_ancestry(rev: &str) -> Result<(&str, Vec<Ancestry>), GitError> {
+    let Some(at) = rev.find(['~', '^']) else {
+        return Ok((rev, vec![]));
+    };
+    let invalid = || GitError::RevisionError(format!("invalid revision {}", rev));
+    let (base, mut suffixes) = rev.split_at(at);
+    if base.is_empty() {
+        return Err(invalid());
+    }
+    let mut steps = vec![];
+    while let Some(op) = suffixes.chars().next() {
+        let rest = &suffixes[1..];
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n = match digits {
+            0 => 1,
+            _ => rest[..digits].parse::<usize>().map_err(|_| invalid())?,
+        };
+        steps.push(match op {
+            '~' => Ancestry::Ancestor(n),
+            '^' => Ancestry::Parent(n),
+            _ => return Err(invalid()),
+        });
+        suffixes = &rest[digits..];
+    }
+    Ok((base, steps))
+}
+
+/// The only id starting with `prefix`, None when no id does or when the prefix is too short
+/// or not hexadecimal. Several matching ids are an error.
+pub fn expand_prefix<'a, I>(prefix: &str, ids: I) -> Result<Option<String>, GitError>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    if prefix.len() < MIN_PREFIX_LEN || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(None);
+    }
+    let prefix = prefix.to_ascii_lowercase();
+    let mut matches = ids.into_iter().filter(|id| id.starts_with(&prefix));
+    match (matches.next(), matches.next()) {
+        (Some(id), None) => Ok(Some(id.to_string())),
+        (Some(_), Some(_)) => Err(GitError::RevisionError(format!(
+            "short sha1 {} is ambiguous",
+            prefix
+        ))),
+        _ => Ok(None),
+    }
+}
+
+/// shortest prefix of `id`, at least `min_len` long, that no other id starts with
+pub fn abbreviate<'a, I>(id: &str, ids: I, min_len: usize) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let common = ids
+        .into_iter()
+        .filter(|other| *other != id)
+        .map(|other| {
+            id.chars()
+                .zip(other.chars())
+                .take_while(|(a, b)| a == b)
+                .count()
+        })
+        .max()
+        .unwrap_or(0);
+    id.chars().take(min_len.max(common + 1)).collect()
+}
+
+/// Seconds since the epoch of a date: `@<seconds>`, RFC 3339, RFC 2822, `YYYY-MM-DD` or
+/// `YYYY-MM-DD HH:MM[:SS]` in UTC, or relative to `now` like `now`, `yesterday` or
+/// `3 weeks ago`
+pub fn parse_date(date: &str, now: i64) -> Result<i64, GitError> {
+    let date = date.trim();
+    let invalid = || GitError::RevisionError(format!("invalid date {}", date));
+    if let Some(seconds) = date.strip_prefix('@') {
+        return seconds.parse().map_err(|_| invalid());
+    }
+    match date {
+        "now" => return Ok(now),
+        "yesterday" => return Ok(now - 24 * 3600),
+        _ => {}
+    }
+    if let Some(ago) = date.strip_suffix(" ago") {
+        let (count, unit) = ago.split_once(' ').ok_or_else(invalid)?;
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        let unit = unit.trim();
+        let seconds = match unit.strip_suffix('s').unwrap_or(unit) {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 3600,
+            "day" => 24 * 3600,
+            "week" => 7 * 24 * 3600,
+            "month" => 30 * 24 * 3600,
+            "year" => 365 * 24 * 3600,
+            _ => return Err(invalid()),
+        };
+        return count
+            .checked_mul(seconds)
+            .and_then(|ago| now.checked_sub(ago))
+            .ok_or_else(invalid);
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(date).or(DateTime::parse_from_rfc2822(date)) {
+        return Ok(t.timestamp());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(date, format) {
+            return Ok(Utc.from_utc_datetime(&t).timestamp());
+        }
+    }
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())?;
+    let midnight = day.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+    Ok(Utc.from_utc_datetime(&midnight).timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_prefix_ut() {
+        let ids = ["abcdef01", "abcdff02", "12345678"];
+        assert_eq!(
+            Some("abcdef01".to_string()),
+            expand_prefix("abcde", ids).unwrap()
+        );
+        assert_eq!(
+            Some("12345678".to_string()),
+            expand_prefix("1234", ids).unwrap()
+        );
+        assert_eq!(
+            Some("abcdef01".to_string()),
+            expand_prefix("ABCDE", ids).unwrap()
+        );
+        assert!(expand_prefix("abcd", ids).is_err());
+        assert_eq!(None, expand_prefix("123", ids).unwrap());
+        assert_eq!(None, expand_prefix("main", ids).unwrap());
+        assert_eq!(None, expand_prefix("ffff", ids).unwrap());
+
+        assert!(check_name("main").is_ok());
+        for rev in ["", "a/b", "a..b", "a~1", "a^", "release.lock"] {
+            assert!(check_name(rev).is_err());
+        }
+    }
+
+    #[test]
+    fn split_ancestry_ut() {
+        assert_eq!(("main", vec![]), split_ancestry("main").unwrap());
+        assert_eq!(
+            ("HEAD", vec![Ancestry::Ancestor(3)]),
+            split_ancestry("HEAD~3").unwrap()
+        );
+        assert_eq!(
+            (
+                "HEAD",
+                vec![
+                    Ancestry::Parent(1),
+                    Ancestry::Parent(1),
+                    Ancestry::Ancestor(1)
+                ]
+            ),
+            split_ancestry("HEAD^^~").unwrap()
+        );
+        assert_eq!(
+            (
+                "origin/main",
+                vec![
+                    Ancestry::Ancestor(12),
+                    Ancestry::Parent(2),
+                    Ancestry::Parent(0)
+                ]
+            ),
+            split_ancestry("origin/main~12^2^0").unwrap()
+        );
+        for rev in ["~1", "^", "main~x", "main~1x", "main~99999999999999999999"] {
+            assert!(split_ancestry(rev).is_err(), "{}", rev);
+        }
+    }
+
+    #[test]
+    fn abbreviate_ut() {
+        let ids = ["abcdef01", "abcdff02", "12345678"];
+        assert_eq!("abcde", abbreviate("abcdef01", ids, 4));
+        assert_eq!("1234", abbreviate("12345678", ids, 4));
+        assert_eq!("1234567", abbreviate("12345678", ids, DEFAULT_ABBREV));
+        assert_eq!("ab", abbreviate("abcdef01", [], 2));
+    }
+
+    #[test]
+    fn parse_date_ut() {
+        let now = 1_700_000_000;
+        assert_eq!(1234, parse_date("@1234", now).unwrap());
+        assert_eq!(now, parse_date("now", now).unwrap());
+        assert_eq!(now - 86400, parse_date("yesterday", now).unwrap());
+        assert_eq!(now - 2 * 86400, parse_date("2 days ago", now).unwrap());
+        assert_eq!(now - 3600, parse_date("1 hour ago", now).unwrap());
+        assert_eq!(1_699_920_000, parse_date("2023-11-14", now).unwrap());
+        assert_eq!(1_699_966_800, parse_date("2023-11-14 13:00", now).unwrap());
+        assert_eq!(
+            1_699_966_805,
+            parse_date("2023-11-14T13:00:05", now).unwrap()
+        );
+        assert_eq!(
+            1_699_966_805,
+            parse_date("2023-11-14T14:00:05+01:00", now).unwrap()
+        );
+        assert_eq!(
+            1_699_966_805,
+            parse_date("Tue, 14 Nov 2023 13:00:05 +0000", now).unwrap()
+        );
+        for date in [
+            "",
+            "soon",
+            "2 fortnights ago",
+            "x days ago",
+            "2023-13-01",
+            "@x",
+        ] {
+            assert!(parse_date(date, now).is_err(), "{}", date);
+        }
+        assert!(parse_date("9999999999999999 years ago", now).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::error::GitError;
+use crate::utils;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Command, Output, Stdio};
+
+/// namespace of ssh signatures made for commits
+const SSH_NAMESPACE: &str = "git";
+
+/// Kind of commit signature, the `gpg.format` config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// detached armored signature made by gpg
+    OpenPgp,
+    /// signature made by `ssh-keygen -Y sign`
+    Ssh,
+}
+
+impl SignatureFormat {
+    /// parse a `gpg.format` value
+    pub fn parse(name: &str) -> Result<Self, GitError> {
+        match name {
+            "openpgp" => Ok(Self::OpenPgp),
+            "ssh" => Ok(Self::Ssh),
+            _ => Err(GitError::SignatureError(format!(
+                "unsupported signature format {}",
+                name
+            ))),
+        }
+    }
+
+    /// program signing and verifying when none is configured
+    pub fn default_program(&self) -> &'static str {
+        match self {
+            Self::OpenPgp => "gpg",
+            Self::Ssh => "ssh-keygen",
+        }
+    }
+}
+
+/// Signs and verifies payloads with an external program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signer {
+    pub format: SignatureFormat,
+    pub program: String,
+    /// gpg key id, or path of the ssh key; gpg falls back to its default key
+    pub key: Option<String>,
+    /// ssh allowed signers file mapping principals to public keys, needed to verify
+    pub allowed_signers: Option<PathBuf>,
+}
+
+impl Signer {
+    /// signature of the payload
+    pub fn sign(&self, payload: &str) -> Result<String, GitError> {
+        let mut args = vec![];
+        match self.format {
+            SignatureFormat::OpenPgp => {
+                args.push("--status-fd=2");
+                args.push("-bsa");
+                if let Some(key) = self.key.as_deref() {
+                    args.extend(["-u", key]);
+                }
+            }
+            SignatureFormat::Ssh => {
+                let key = self.key.as_deref().ok_or_else(|| {
+                    GitError::SignatureError("user.signingKey is needed to sign with ssh".into())
+                })?;
+                args.extend(["-Y", "sign", "-n", SSH_NAMESPACE, "-f", key]);
+            }
+        }
+        let output = self.run(&args, payload)?;
+        let signature = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() || signature.is_empty() {
+            return Err(GitError::SignatureError(format!(
+                "{} failed to sign the data: {}",
+                self.program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(signature)
+    }
+
+    /// Check the signature of the payload, returns the verifier's description of a good
+    /// signature, e.g. `Good "git" signature for jane with ED25519 key SHA256:...`
+    pub fn verify(&self, payload: &str, signature: &str) -> Result<String, GitError> {
+        // the verifiers read the signature from a file and the payload from stdin
+        let sig_file = env::temp_dir().join(format!(
+            "git-rs-{}-{}.sig",
+            process::id(),
+            utils::crypto_string(signature)
+        ));
+        fs::write(&sig_file, signature).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        let sig_path = sig_file.display().to_string();
+        let res = match self.format {
+            SignatureFormat::OpenPgp => self.verify_gpg(payload, &sig_path),
+            SignatureFormat::Ssh => self.verify_ssh(payload, &sig_path),
+        };
+        let _ = fs::remove_file(&sig_file);
+        res
+    }
+
+    fn verify_gpg(&self, payload: &str, sig_path: &str) -> Result<String, GitError> {
+        let output = self.run(&["--status-fd=1", "--verify", sig_path, "-"], payload)?;
+        let status = String::from_utf8_lossy(&output.stdout);
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if output.status.success() && status.contains("[GNUPG:] GOODSIG") {
+            Ok(message)
+        } else {
+            Err(GitError::SignatureError(message))
+        }
+    }
+
+    fn verify_ssh(&self, payload: &str, sig_path: &str) -> Result<String, GitError> {
+        let allowed = self.allowed_signers.as_ref().ok_or_else(|| {
+            GitError::SignatureError(
+                "gpg.ssh.allowedSignersFile is needed to verify ssh signatures".into(),
+            )
+        })?;
+        let allowed = allowed.display().to_string();
+        let output = self.run(
+            &["-Y", "find-principals", "-f", &allowed, "-s", sig_path],
+            "",
+        )?;
+        let principals = String::from_utf8_lossy(&output.stdout);
+        let principal = match principals.lines().next() {
+            Some(principal) if output.status.success() => principal.to_string(),
+            _ => {
+                return Err(GitError::SignatureError(
+                    "no principal matched the signing key".to_string(),
+                ))
+            }
+        };
+        let output = self.run(
+            &[
+                "-Y",
+                "verify",
+                "-f",
+                &allowed,
+                "-I",
+                &principal,
+                "-n",
+                SSH_NAMESPACE,
+                "-s",
+                sig_path,
+            ],
+            payload,
+        )?;
+        let message = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() {
+            Ok(message)
+        } else {
+            Err(GitError::SignatureError(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ))
+        }
+    }
+
+    /// run the program with the input on stdin
+    fn run(&self, args: &[&str], input: &str) -> Result<Output, GitError> {
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                GitError::SignatureError(format!("cannot run {}: {:?}", self.program, e))
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(input.as_bytes())
+                .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+        }
+        child
+            .wait_with_output()
+            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_format_ut() {
+        assert_eq!(
+            SignatureFormat::OpenPgp,
+            SignatureFormat::parse("openpgp").unwrap()
+        );
+        assert_eq!(SignatureFormat::Ssh, SignatureFormat::parse("ssh").unwrap());
+        assert!(SignatureFormat::parse("x509").is_err());
+        assert_eq!("ssh-keygen", SignatureFormat::Ssh.default_program());
+    }
+
+    #[test]
+    fn ssh_sign_verify_ut() {
+        let dir = &env::current_dir().unwrap().join("ssh_sign_verify_ut");
+        if dir.exists() {
+            assert!(fs::remove_dir_all(dir).is_ok());
+        }
+        assert!(fs::create_dir(dir).is_ok());
+        let key = dir.join("key");
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", "jane", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let public_key = fs::read_to_string(dir.join("key.pub")).unwrap();
+        let allowed = dir.join("allowed_signers");
+        assert!(fs::write(&allowed, format!("jane {}", public_key)).is_ok());
+
+        let mut signer = Signer {
+            format: SignatureFormat::Ssh,
+            program: "ssh-keygen".to_string(),
+            key: None,
+            allowed_signers: None,
+        };
+        assert!(signer.sign("payload").is_err());
+        signer.key = Some(key.display().to_string());
+        let signature = signer.sign("payload").unwrap();
+        assert!(signature.starts_with("-----BEGIN SSH SIGNATURE-----"));
+        assert!(signer.verify("payload", &signature).is_err());
+
+        signer.allowed_signers = Some(allowed);
+        let good = signer.verify("payload", &signature).unwrap();
+        assert!(
+            good.starts_with("Good \"git\" signature for jane"),
+            "{}",
+            good
+        );
+        assert!(signer.verify("tampered", &signature).is_err());
+
+        signer.program = "no-such-signing-program".to_string();
+        assert!(signer.sign("payload").is_err());
+        assert!(fs::remove_dir_all(dir).is_ok());
+    }
+}
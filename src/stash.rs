@@ -0,0 +1,68 @@
+use crate::entry::Entry;
+use crate::error::GitError;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Operation of the stash command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StashAction {
+    /// save local changes as a new entry and reset them to HEAD
+    #[default]
+    Push,
+    /// reapply an entry and drop it
+    Pop,
+    /// reapply an entry and keep it
+    Apply,
+    /// list the entries, newest first
+    List,
+    /// discard an entry
+    Drop,
+    /// create a branch at the commit an entry was taken on, reapply the entry there and drop it
+    Branch,
+}
+
+/// Snapshot of local changes taken on top of the commit `base`.
+/// index: file path --> entry tree of the staging area, HEAD with the staged changes applied
+/// worktree: file path --> entry tree of the tracked files in the working directory
+/// untracked: file path --> entry of the untracked files, when they were included
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StashEntry {
+    pub message: String,
+    pub base: String,
+    pub index: BTreeMap<String, Entry>,
+    pub worktree: BTreeMap<String, Entry>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub untracked: BTreeMap<String, Entry>,
+    pub date_time: i64,
+}
+
+/// name of the n-th entry, the newest entry is `stash@{0}`
+pub fn label(n: usize) -> String {
+    format!("stash@{{{}}}", n)
+}
+
+/// position of an entry named `stash@{n}` or just `n`
+pub fn parse_label(name: &str) -> Result<usize, GitError> {
+    name.strip_prefix("stash@{")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(name)
+        .parse::<usize>()
+        .map_err(|_| GitError::StashError(format!("{} is not a valid reference", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn label_ut() {
+        assert_eq!("stash@{0}", label(0));
+        assert_eq!(2, parse_label("stash@{2}").unwrap());
+        assert_eq!(3, parse_label("3").unwrap());
+        assert_eq!(12, parse_label(&label(12)).unwrap());
+        for name in ["stash@{x}", "stash@{1", "-1", ""] {
+            assert!(parse_label(name).is_err());
+        }
+    }
+}
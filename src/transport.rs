@@ -0,0 +1,258 @@
+use crate::bundle::Bundle;
+use crate::daemon;
+use crate::error::GitError;
+use crate::http::{self, Request, Response};
+use crate::repo::{Commit, GitRepository, ReceiveOptions, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a repository offers to fetch from it: its current branch, its branches and tags,
+/// `refs/heads/<name>` and `refs/tags/<name>` --> commit or annotated tag sha1, and its
+/// shallow commits
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Advertisement {
+    pub head: String,
+    pub refs: BTreeMap<String, String>,
+    #[serde(default)]
+    pub shallow: BTreeSet<String>,
+}
+
+impl Advertisement {
+    /// branch name --> commit
+    pub fn branches(&self) -> BTreeMap<&str, &str> {
+        self.refs
+            .iter()
+            .filter_map(|(name, target)| Some((name.strip_prefix("refs/heads/")?, target.as_str())))
+            .collect()
+    }
+}
+
+/// Commits a fetch is after: the history of `wants`, at most `depth` generations of it,
+/// without the history of `haves`, commits the fetching side has with their whole history
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UploadRequest {
+    pub wants: Vec<String>,
+    pub haves: Vec<String>,
+    pub depth: Option<usize>,
+}
+
+/// Access to the refs and objects of the repository a remote points at
+pub trait Transport {
+    fn advertise(&mut self) -> Result<Advertisement, GitError>;
+
+    /// Tell the other side which commits will be read, so it can send them at once.
+    /// Objects it does not send are still read one by one.
+    fn negotiate(&mut self, _request: &UploadRequest) -> Result<(), GitError> {
+        Ok(())
+    }
+
+    /// the commit as stored, a shallow commit keeps its parents
+    fn commit_object(&mut self, sha1: &str) -> Result<Commit, GitError>;
+
+    fn blob_content(&mut self, sha1: &str) -> Result<Vec<u8>, GitError>;
+
+    /// the annotated tag object, None when sha1 is not one
+    fn tag_object(&mut self, sha1: &str) -> Result<Option<Tag>, GitError>;
+
+    /// Hand the objects and refs of a bundle to the other side, see
+    /// `GitRepository::receive_with`. Returns one line per ref.
+    fn push_bundle(
+        &mut self,
+        bundle: &Bundle,
+        options: &ReceiveOptions,
+    ) -> Result<Vec<String>, GitError>;
+}
+
+/// whether the url points at a repository served over the network, by `serve` over http or
+/// by `daemon`
+pub fn is_network(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://") || daemon::is_daemon_url(url)
+}
+
+/// Transport to a repository served over the network: by `serve` at a http url or by
+/// `daemon` at a git-rs:// url, see `handle` for the protocol. Negotiated objects are kept
+/// in memory until read.
+#[derive(Debug)]
+pub struct NetworkTransport {
+    url: String,
+    received: Bundle,
+}
+
+impl NetworkTransport {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            received: Bundle::default(),
+        }
+    }
+
+    fn send(&self, method: &str, path: &str, body: &[u8]) -> Result<Option<Vec<u8>>, GitError> {
+        let url = format!("{}/{}", self.url, path);
+        let response = match daemon::is_daemon_url(&url) {
+            true => daemon::send(method, &url, body)?,
+            false => http::send(method, &url, body)?,
+        };
+        match response.status {
+            200 => Ok(Some(response.body)),
+            404 => Ok(None),
+            status => Err(GitError::RemoteError(format!(
+                "{} {}: {}",
+                status,
+                url,
+                String::from_utf8_lossy(&response.body)
+            ))),
+        }
+    }
+
+    /// a JSON object, an error when the other side does not have it
+    fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, GitError> {
+        let body = self
+            .send("GET", path, b"")?
+            .ok_or_else(|| GitError::RemoteError(format!("{}/{} not found", self.url, path)))?;
+        serde_json::from_slice(&body).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+}
+
+impl Transport for NetworkTransport {
+    fn advertise(&mut self) -> Result<Advertisement, GitError> {
+        self.get("info/refs")
+    }
+
+    fn negotiate(&mut self, request: &UploadRequest) -> Result<(), GitError> {
+        let body =
+            serde_json::to_vec(request).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+        let content = self
+            .send("POST", "upload", &body)?
+            .ok_or_else(|| GitError::RemoteError(format!("{}/upload not found", self.url)))?;
+        let bundle = Bundle::from_file_content(&String::from_utf8_lossy(&content))?;
+        self.received.commits.extend(bundle.commits);
+        self.received.tags.extend(bundle.tags);
+        self.received.blobs.extend(bundle.blobs);
+        self.received.binary_blobs.extend(bundle.binary_blobs);
+        Ok(())
+    }
+
+    fn commit_object(&mut self, sha1: &str) -> Result<Commit, GitError> {
+        match self.received.commits.remove(sha1) {
+            Some(commit) => Ok(commit),
+            None => self.get(&format!("objects/commit/{}", sha1)),
+        }
+    }
+
+    fn blob_content(&mut self, sha1: &str) -> Result<Vec<u8>, GitError> {
+        if let Some(text) = self.received.blobs.remove(sha1) {
+            return Ok(text.into_bytes());
+        }
+        if let Some(content) = self.received.binary_blobs.remove(sha1) {
+            return Ok(content);
+        }
+        self.send("GET", &format!("objects/blob/{}", sha1), b"")?
+            .ok_or_else(|| GitError::RemoteError(format!("no blob {}", sha1)))
+    }
+
+    fn tag_object(&mut self, sha1: &str) -> Result<Option<Tag>, GitError> {
+        if let Some(tag) = self.received.tags.remove(sha1) {
+            return Ok(Some(tag));
+        }
+        match self.send("GET", &format!("objects/tag/{}", sha1), b"")? {
+            Some(body) => serde_json::from_slice(&body)
+                .map(Some)
+                .map_err(|e| GitError::SerdeOpError(format!("{:?}", e))),
+            None => Ok(None),
+        }
+    }
+
+    fn push_bundle(
+        &mut self,
+        bundle: &Bundle,
+        options: &ReceiveOptions,
+    ) -> Result<Vec<String>, GitError> {
+        let content = bundle.to_file_content()?;
+        let mut path = format!("receive?force={}", options.force);
+        if let Some(lease) = &options.lease {
+            path.push_str(&format!("&lease={}", lease));
+        }
+        let body = self
+            .send("POST", &path, content.as_bytes())?
+            .ok_or_else(|| GitError::RemoteError(format!("{}/receive not found", self.url)))?;
+        serde_json::from_slice(&body).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Result<Response, GitError> {
+    let body =
+        serde_json::to_string(value).map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))?;
+    Ok(Response::json(200, body))
+}
+
+fn receive_options(request: &Request) -> ReceiveOptions {
+    ReceiveOptions {
+        force: request
+            .query
+            .get("force")
+            .is_some_and(|force| force == "true"),
+        lease: request.query.get("lease").cloned(),
+    }
+}
+
+fn check_id(id: &str) -> Result<(), GitError> {
+    match id.len() == 40 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(()),
+        false => Err(GitError::FileNotExistError(id.to_string())),
+    }
+}
+
+/// Largest body of a request of the fetch and push protocol: a pushed bundle up to
+/// `max_input`, `receive.maxInputSize`, any size without it, other requests up to
+/// `http::MAX_REQUEST_LEN`
+pub fn body_limit(request: &Request, max_input: Option<usize>) -> Option<usize> {
+    match (request.method.as_str(), request.segments().as_slice()) {
+        ("POST", ["receive"]) => max_input,
+        _ => Some(http::MAX_REQUEST_LEN),
+    }
+}
+
+/// Route a request of the fetch and push protocol, paths relative to the repository url
+/// GET  /info/refs                the `Advertisement` as JSON
+/// POST /upload                   a bundle of the commits, tags and blobs an `UploadRequest`
+///                                in JSON is after
+/// POST /receive                  store a bundle and update its refs, the report lines as JSON;
+///      ?force=true&lease=<sha1>   `ReceiveOptions`
+/// GET  /objects/commit/<sha1>    a commit as JSON
+/// GET  /objects/tag/<sha1>       an annotated tag as JSON
+/// GET  /objects/blob/<sha1>      blob content
+pub fn handle(repo: &mut GitRepository, request: &Request) -> Response {
+    let segments = request.segments();
+    let res = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["info", "refs"]) => repo.advertise().and_then(|refs| json(&refs)),
+        ("POST", ["upload"]) => serde_json::from_slice(&request.body)
+            .map_err(|e| GitError::SerdeOpError(format!("{:?}", e)))
+            .and_then(|wanted| repo.upload(&wanted))
+            .and_then(|bundle| bundle.to_file_content())
+            .map(|content| Response::new(200, "application/x-git-rs-bundle", content.into())),
+        ("POST", ["receive"]) => Bundle::from_file_content(&String::from_utf8_lossy(&request.body))
+            .and_then(|bundle| repo.receive_with(&bundle, &receive_options(request)))
+            .and_then(|lines| json(&lines)),
+        ("GET", ["objects", kind, id]) => check_id(id).and_then(|_| match *kind {
+            "commit" => repo.commit_object(id).and_then(|commit| json(&commit)),
+            "tag" => match repo.tag_object(id) {
+                Ok(Some(tag)) => json(&tag),
+                Ok(None) => Err(GitError::FileNotExistError(id.to_string())),
+                Err(e) => Err(e),
+            },
+            "blob" => repo
+                .blob_content(id)
+                .map(|content| Response::new(200, "application/octet-stream", content)),
+            _ => Err(GitError::FileNotExistError(request.path.clone())),
+        }),
+        _ => return Response::not_found(),
+    };
+    match res {
+        Ok(response) => response,
+        Err(GitError::FileNotExistError(path)) => {
+            Response::text(404, &format!("{} does not exist", path))
+        }
+        Err(GitError::SerdeOpError(msg) | GitError::BundleError(msg)) => Response::text(400, &msg),
+        Err(e) => Response::text(500, &e.to_string()),
+    }
+}
@@ -1,26 +1,16 @@
+use crate::compress;
+use crate::entry::{self, Entry, FileMode};
 use crate::error::GitError;
-use crypto;
+use crate::ignore::{Ignore, IGNORE_FILE};
 use crypto::digest::Digest;
-use log::info;
 use serde::Serialize;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::Read;
-use std::path::PathBuf;
-/// crypto file to sha1
-/// support text file currently, binary file will be supported in the future
-pub fn crypto_file(path: &PathBuf) -> Result<String, GitError> {
-    if path.exists() {
-        let mut file =
-            fs::File::open(&path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-        let mut s = String::new();
-        file.read_to_string(&mut s)
-            .map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-        Ok(crypto_string(s.as_str()))
-    } else {
-        Err(GitError::FileNotExistError(path.display().to_string()))
-    }
-}
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+/// size of each read of streaming hashes and copies
+pub const STREAM_BUFFER_SIZE: usize = 64 * 1024;
 
 /// persistence Serialize object to string
 /// e.g serialize StageArea into json string
@@ -38,33 +28,136 @@ pub fn crypto_string(content: &str) -> String {
     hasher.result_str()
 }
 
-/// copy file to repo
-/// e.g src/d1/f1 to .git-repo-dir/src/d1/f1
-pub fn copy_to(path: &PathBuf, dist: &PathBuf) -> Result<(), GitError> {
-    if path.exists() && path.is_file() {
-        info!("copy {} to {}", path.display(), dist.display());
-        fs::copy(path, dist).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
-        Ok(())
-    } else {
-        Err(GitError::FileNotExistError(path.display().to_string()))
+/// crypto bytes to sha1
+pub fn crypto_bytes(content: &[u8]) -> String {
+    let mut hasher = crypto::sha1::Sha1::new();
+    hasher.input(content);
+    hasher.result_str()
+}
+
+/// sha1 of everything the reader yields, read `STREAM_BUFFER_SIZE` bytes at a time
+pub fn crypto_reader<R: Read>(mut reader: R) -> Result<String, GitError> {
+    let mut hasher = crypto::sha1::Sha1::new();
+    let mut buf = vec![0; STREAM_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(hasher.result_str()),
+            Ok(n) => hasher.input(&buf[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(GitError::FileOpError(format!("{:?}", e))),
+        }
+    }
+}
+
+/// sha1 of a file content, hashed while it is read so large files are never held in memory
+pub fn crypto_file(path: &Path) -> Result<String, GitError> {
+    let file = fs::File::open(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    crypto_reader(file)
+}
+
+/// entry of the file at path with its content as it is, see `crypto_file`
+pub fn hash_entry(path: &Path) -> Result<Entry, GitError> {
+    match FileMode::of(path)? {
+        FileMode::Symlink => {
+            let (target, mode) = entry::read_content(path)?;
+            Ok(Entry::with_mode(crypto_bytes(&target), mode))
+        }
+        mode => Ok(Entry::with_mode(crypto_file(path)?, mode)),
     }
 }
 
+/// whether the text is an object id as `crypto_string` makes them, 40 lowercase hex digits
+pub fn is_sha1(text: &str) -> bool {
+    text.len() == 40 && text.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// whether content read back from the object store still hashes to its sha1
+pub fn verify_bytes(content: &[u8], sha1: &str) -> bool {
+    crypto_bytes(content) == sha1
+}
+
+/// whether the content of an object file, e.g. a blob or a chunk, still hashes to its sha1;
+/// compressed files are hashed as they are decompressed and a broken stream does not verify
+pub fn verify_file(path: &Path, sha1: &str) -> Result<bool, GitError> {
+    let file = fs::File::open(path).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    let reader = compress::reader(file).map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+    Ok(crypto_reader(reader).is_ok_and(|hash| hash == sha1))
+}
+
+/// whether a deserialized object, e.g. a commit, still hashes to its sha1
+pub fn verify_sha1<T: Serialize>(value: &T, expected: &str) -> Result<bool, GitError> {
+    Ok(sha1(value)? == expected)
+}
+
+/// whether a relative path is selected by the pathspecs: the path itself or a file under
+/// a directory given as pathspec, every path matches empty pathspecs
+pub fn pathspec_matches(pathspecs: &[String], path: &str) -> bool {
+    pathspecs.is_empty()
+        || pathspecs.iter().any(|spec| {
+            let spec = spec.trim_end_matches('/');
+            path == spec
+                || path
+                    .strip_prefix(spec)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+}
+
+/// content of a file changed differently on both sides of a merge, an empty side was deleted
+/// <<<<<<< HEAD
+/// current branch content
+/// =======
+/// given branch content
+/// >>>>>>>
+pub fn conflict_content(ours: &str, theirs: &str) -> String {
+    let side = |content: &str| {
+        if content.is_empty() || content.ends_with('\n') {
+            content.to_string()
+        } else {
+            format!("{}\n", content)
+        }
+    };
+    format!(
+        "<<<<<<< HEAD\n{}=======\n{}>>>>>>>\n",
+        side(ours),
+        side(theirs)
+    )
+}
+
 /// visit all files under given directory ans sub directory and return file path vector
+/// unreadable sub directories and entries are skipped with a warning, ignored files and
+/// directories are skipped, the `.gitignore` of each visited directory adds its rules
 fn visit_dirs(
+    root: &Path,
     dir: &PathBuf,
     paths: &mut Vec<PathBuf>,
-    ignore: &HashSet<PathBuf>,
+    ignore: &mut Ignore,
+    warnings: &mut Vec<String>,
 ) -> Result<(), GitError> {
     if dir.exists() && dir.is_dir() {
+        let relative_dir = relative_path(root, dir);
+        if let Err(e) = ignore.add_file(&dir.join(IGNORE_FILE), &relative_dir) {
+            warnings.push(format!("skip ignore file of {}: {:?}", dir.display(), e));
+        }
         for entry in fs::read_dir(dir).map_err(|e| GitError::FileOpError(format!("{:?}", e)))? {
-            let entry = entry.map_err(|e| GitError::FileOpError(format!("{:?}", e)))?;
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warnings.push(format!("skip entry of {}: {}", dir.display(), e));
+                    continue;
+                }
+            };
             let path = entry.path();
-            if ignore.contains(&path) {
+            // symbolic links are entries of their own, even when they point to a directory
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if ignore.is_ignored(&relative_path(root, &path), is_dir) {
                 continue;
             }
-            if path.is_dir() {
-                visit_dirs(&path, paths, ignore)?;
+            if is_dir {
+                if let Err(e) = fs::read_dir(&path) {
+                    warnings.push(format!("skip directory {}: {}", path.display(), e));
+                    continue;
+                }
+                visit_dirs(root, &path, paths, ignore, warnings)?;
             } else {
                 paths.push(path);
             }
@@ -73,24 +166,70 @@ fn visit_dirs(
     Ok(())
 }
 
-/// generate file to sha1 map under given directory
+/// `/` separated path of a file under root, empty for root itself
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .map(|p| {
+            p.display()
+                .to_string()
+                .replace(std::path::MAIN_SEPARATOR, "/")
+        })
+        .unwrap_or_default()
+}
+
+/// generate file to entry map under given directory, leaving out what the ignore rules and
+/// the `.gitignore` files found on the way exclude, symbolic links are not followed
+/// files that can not be read are left out and reported in warnings
 pub fn generate_file_sha1_map(
     dir: &PathBuf,
-    ignore: &HashSet<PathBuf>,
-) -> Result<BTreeMap<String, String>, GitError> {
+    ignore: &mut Ignore,
+    warnings: &mut Vec<String>,
+) -> Result<BTreeMap<String, Entry>, GitError> {
     let mut file_sha1_map = BTreeMap::new();
     if dir.exists() && dir.is_dir() {
         let mut paths = Vec::new();
-        visit_dirs(dir, &mut paths, ignore)?;
+        visit_dirs(dir, dir, &mut paths, ignore, warnings)?;
         for path in paths.iter() {
             let relative_path = path.strip_prefix(dir).unwrap().to_path_buf();
-            let sha1 = crypto_file(&path)?;
-            file_sha1_map.insert(relative_path.display().to_string(), sha1);
+            match hash_entry(path) {
+                Ok(entry) => {
+                    file_sha1_map.insert(relative_path.display().to_string(), entry);
+                }
+                Err(e) => warnings.push(format!("skip file {}: {}", relative_path.display(), e)),
+            }
         }
     }
     Ok(file_sha1_map)
 }
 
+/// files under dir, a directory below root, as `/` separated paths relative to root, leaving
+/// out what the ignore rules and the `.gitignore` files from root down exclude, nothing when
+/// dir itself is ignored; files are listed, not read
+pub fn list_files(
+    root: &Path,
+    dir: &Path,
+    ignore: &mut Ignore,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<String>, GitError> {
+    let mut parent = root.to_path_buf();
+    for component in relative_path(root, dir)
+        .split('/')
+        .filter(|c| !c.is_empty())
+    {
+        let base = relative_path(root, &parent);
+        if let Err(e) = ignore.add_file(&parent.join(IGNORE_FILE), &base) {
+            warnings.push(format!("skip ignore file of {}: {:?}", parent.display(), e));
+        }
+        parent.push(component);
+        if ignore.is_ignored(&relative_path(root, &parent), true) {
+            return Ok(vec![]);
+        }
+    }
+    let mut paths = Vec::new();
+    visit_dirs(root, &dir.to_path_buf(), &mut paths, ignore, warnings)?;
+    Ok(paths.iter().map(|path| relative_path(root, path)).collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -107,42 +246,63 @@ mod tests {
     }
 
     #[test]
-    fn crypto_file_ut() {
-        let tmp_dir_path = &env::current_dir().unwrap().join("crypto_file_ut");
-        if !tmp_dir_path.exists() {
-            assert!(fs::create_dir(&tmp_dir_path).is_ok());
-        }
-        let file_path = tmp_dir_path.join("crypto_file_ut");
-        let mut file = fs::File::create(&file_path).unwrap();
-        assert!(file
-            .write("This is a demo content for crypto_file_ut".as_bytes())
-            .is_ok());
-        let hash = crypto_file(&file_path).unwrap();
-        assert_eq!("2564cf76bd5b1cf65f7b9f52546f1ba7c8accee8", hash);
+    fn crypto_bytes_ut() {
+        assert_eq!(
+            crypto_string("This is a demo content for crypto_string_ut"),
+            crypto_bytes("This is a demo content for crypto_string_ut".as_bytes())
+        );
+    }
 
-        if tmp_dir_path.exists() {
-            assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
-        }
+    #[test]
+    fn crypto_reader_ut() {
+        let data: Vec<u8> = (0..3 * STREAM_BUFFER_SIZE + 7).map(|i| i as u8).collect();
+        assert_eq!(crypto_bytes(&data), crypto_reader(data.as_slice()).unwrap());
+        assert_eq!(crypto_bytes(b""), crypto_reader(&b""[..]).unwrap());
     }
 
     #[test]
-    fn copy_to_ut() {
-        let tmp_dir_path = &env::current_dir().unwrap().join("copy_to_ut");
+    fn verify_ut() {
+        let sha1_content = crypto_string("content");
+        assert!(verify_bytes(b"content", &sha1_content));
+        assert!(!verify_bytes(b"changed", &sha1_content));
+
+        let commit = Commit::new();
+        assert!(verify_sha1(&commit, &sha1(&commit).unwrap()).unwrap());
+        assert!(!verify_sha1(&commit, &sha1_content).unwrap());
+
+        let tmp_dir_path = &env::current_dir().unwrap().join("verify_ut");
         if !tmp_dir_path.exists() {
-            assert!(fs::create_dir(&tmp_dir_path).is_ok());
-        }
-        let file_path = tmp_dir_path.join("copy_to_ut");
-        let mut file = fs::File::create(&file_path).unwrap();
-        assert!(file
-            .write("This is a demo content for copy_to_ut".as_bytes())
-            .is_ok());
-        let dist_path = tmp_dir_path.join("copy_to_ut_dist");
-        assert!(copy_to(&file_path, &dist_path).is_ok());
-        assert!(dist_path.exists());
-        assert!(dist_path.is_file());
-        if tmp_dir_path.exists() {
-            assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
+            assert!(fs::create_dir(tmp_dir_path).is_ok());
         }
+        let file_path = tmp_dir_path.join("verify_ut");
+        assert!(fs::write(&file_path, "content").is_ok());
+        assert!(verify_file(&file_path, &sha1_content).unwrap());
+        assert!(fs::write(&file_path, "changed").is_ok());
+        assert!(!verify_file(&file_path, &sha1_content).unwrap());
+        assert!(verify_file(&tmp_dir_path.join("missing"), &sha1_content).is_err());
+        assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
+    }
+
+    #[test]
+    fn pathspec_matches_ut() {
+        let specs = ["src/d1/".to_string(), "f1".to_string()];
+        assert!(pathspec_matches(&specs, "f1"));
+        assert!(pathspec_matches(&specs, "src/d1/f2"));
+        assert!(!pathspec_matches(&specs, "f10"));
+        assert!(!pathspec_matches(&specs, "src/d10/f1"));
+        assert!(pathspec_matches(&[], "f10"));
+    }
+
+    #[test]
+    fn conflict_content_ut() {
+        assert_eq!(
+            "<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>>\n",
+            conflict_content("ours\n", "theirs")
+        );
+        assert_eq!(
+            "<<<<<<< HEAD\n=======\ntheirs\n>>>>>>>\n",
+            conflict_content("", "theirs\n")
+        );
     }
 
     #[test]
@@ -159,78 +319,161 @@ mod tests {
         if tmp_dir_path.exists() {
             assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
         }
-        assert!(fs::create_dir(&tmp_dir_path).is_ok());
+        assert!(fs::create_dir(tmp_dir_path).is_ok());
 
-        for dir in vec!["d1", "d2"] {
+        for dir in ["d1", "d2"] {
             let dir_path = tmp_dir_path.join(dir);
             assert!(fs::create_dir(&dir_path).is_ok());
         }
-        for file_name in vec!["f1", "f2", "f3", "d1/f1", "d1/f2", "d2/f1", "d2/f2"] {
+        for file_name in ["f1", "f2", "f3", "d1/f1", "d1/f2", "d2/f1", "d2/f2"] {
             let file_path = tmp_dir_path.join(file_name);
             let mut file = fs::File::create(&file_path).unwrap();
             assert!(file
-                .write(format!("This is a demo content for {}", file_path.display()).as_bytes())
+                .write(format!("This is a demo content for {}", file_name).as_bytes())
                 .is_ok());
         }
 
-        let file_sha1_map = generate_file_sha1_map(&tmp_dir_path, &HashSet::new()).unwrap();
+        // a link to a directory is an entry holding the link target
+        assert!(std::os::unix::fs::symlink("d1", tmp_dir_path.join("link")).is_ok());
+
+        let warnings = &mut vec![];
+        let file_sha1_map =
+            generate_file_sha1_map(tmp_dir_path, &mut Ignore::new(), warnings).unwrap();
+        assert!(warnings.is_empty());
         assert_eq!(
-            "c5f2b24026cd1db66d21fed90afd80b258438306",
-            file_sha1_map.get("f1").unwrap()
+            Entry::with_mode(crypto_string("d1"), entry::FileMode::Symlink),
+            file_sha1_map["link"]
         );
+        assert!(!file_sha1_map.contains_key("link/f1"));
         assert_eq!(
-            "2d4cc9562e0bbb7d41a885715f88110da36a853a",
-            file_sha1_map.get("f2").unwrap()
+            "7d9f8e37fbcc2d721bf45f7f4b06641b04bd9557",
+            file_sha1_map["f1"].sha1
         );
         assert_eq!(
-            "210c5f425360d053925af2fd91539c7b72839d37",
-            file_sha1_map.get("f3").unwrap()
+            "2ebe7d07647f4ace292883ab289e393608fa2a90",
+            file_sha1_map["f2"].sha1
         );
         assert_eq!(
-            "4a0c297958c72ab87e189c9f0e038d6e5a402b8d",
-            file_sha1_map.get("d1/f1").unwrap()
+            "fee82caaaa8334067270d0fd3763f8a177fb88f8",
+            file_sha1_map["f3"].sha1
         );
         assert_eq!(
-            "5944e3502db357c0fcec506f8ebf1fcf74dd10b3",
-            file_sha1_map.get("d1/f2").unwrap()
+            "9c9e8edf16782c3ae5004fedf5a04f1be936a373",
+            file_sha1_map["d1/f1"].sha1
         );
         assert_eq!(
-            "c9dd9a1bfd686e3827d31aca8d283b75035a3cb7",
-            file_sha1_map.get("d2/f1").unwrap()
+            "41125f6f2abf31a3ef2c8513488d4d511b1b797f",
+            file_sha1_map["d1/f2"].sha1
         );
         assert_eq!(
-            "04d7bf8ca3f8679f0bd9a80a6dc3b14f55637063",
-            file_sha1_map.get("d2/f2").unwrap()
+            "8c1b7df0c24846f1d90c1d51bb5716da7a351336",
+            file_sha1_map["d2/f1"].sha1
+        );
+        assert_eq!(
+            "d283119870b0493c8c0a829edfd46bf746fca521",
+            file_sha1_map["d2/f2"].sha1
         );
 
-        let file_sha1_map = generate_file_sha1_map(
-            &tmp_dir_path,
-            &HashSet::from([tmp_dir_path.join("d1"), tmp_dir_path.join("f1")]),
-        )
-        .unwrap();
+        let mut ignore = Ignore::new();
+        assert!(ignore.add_patterns("/d1\n/f1\n", "", "ignore").is_ok());
+        let file_sha1_map = generate_file_sha1_map(tmp_dir_path, &mut ignore, &mut vec![]).unwrap();
         assert!(!file_sha1_map.contains_key("d1/f1"));
         assert!(!file_sha1_map.contains_key("d1/f2"));
         assert_eq!(
-            "2d4cc9562e0bbb7d41a885715f88110da36a853a",
-            file_sha1_map.get("f2").unwrap()
+            "2ebe7d07647f4ace292883ab289e393608fa2a90",
+            file_sha1_map["f2"].sha1
         );
         assert_eq!(
-            "210c5f425360d053925af2fd91539c7b72839d37",
-            file_sha1_map.get("f3").unwrap()
+            "fee82caaaa8334067270d0fd3763f8a177fb88f8",
+            file_sha1_map["f3"].sha1
         );
-        // assert_eq!("4a0c297958c72ab87e189c9f0e038d6e5a402b8d", file_sha1_map.get("d1/f1").unwrap());
-        // assert_eq!("5944e3502db357c0fcec506f8ebf1fcf74dd10b3", file_sha1_map.get("d1/f2").unwrap());
+        // assert_eq!("9c9e8edf16782c3ae5004fedf5a04f1be936a373", file_sha1_map["d1/f1"].sha1);
+        // assert_eq!("41125f6f2abf31a3ef2c8513488d4d511b1b797f", file_sha1_map["d1/f2"].sha1);
         assert_eq!(
-            "c9dd9a1bfd686e3827d31aca8d283b75035a3cb7",
-            file_sha1_map.get("d2/f1").unwrap()
+            "8c1b7df0c24846f1d90c1d51bb5716da7a351336",
+            file_sha1_map["d2/f1"].sha1
         );
         assert_eq!(
-            "04d7bf8ca3f8679f0bd9a80a6dc3b14f55637063",
-            file_sha1_map.get("d2/f2").unwrap()
+            "d283119870b0493c8c0a829edfd46bf746fca521",
+            file_sha1_map["d2/f2"].sha1
         );
 
+        // .gitignore files found on the way exclude paths under their directory
+        assert!(fs::write(tmp_dir_path.join("d2/.gitignore"), "f2\n").is_ok());
+        let file_sha1_map =
+            generate_file_sha1_map(tmp_dir_path, &mut Ignore::new(), &mut vec![]).unwrap();
+        assert!(file_sha1_map.contains_key("d2/.gitignore"));
+        assert!(!file_sha1_map.contains_key("d2/f2"));
+        assert!(file_sha1_map.contains_key("f2"));
+
         if tmp_dir_path.exists() {
             assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
         }
     }
+
+    #[test]
+    fn list_files_ut() {
+        let tmp_dir_path = &env::current_dir().unwrap().join("list_files_ut");
+        if tmp_dir_path.exists() {
+            assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
+        }
+        assert!(fs::create_dir_all(tmp_dir_path.join("d1/d2")).is_ok());
+        assert!(fs::create_dir_all(tmp_dir_path.join("d3")).is_ok());
+        for file_name in ["f1", "d1/f1", "d1/f2", "d1/d2/f1", "d1/d2/f2", "d3/f1"] {
+            assert!(fs::write(tmp_dir_path.join(file_name), file_name).is_ok());
+        }
+        // rules of the directories above the listed one apply
+        assert!(fs::write(tmp_dir_path.join(".gitignore"), "f2\n").is_ok());
+        assert!(fs::write(tmp_dir_path.join("d1/.gitignore"), "d3\n").is_ok());
+
+        let warnings = &mut vec![];
+        let mut files = list_files(
+            tmp_dir_path,
+            &tmp_dir_path.join("d1"),
+            &mut Ignore::new(),
+            warnings,
+        )
+        .unwrap();
+        files.sort();
+        assert_eq!(vec!["d1/.gitignore", "d1/d2/f1", "d1/f1"], files);
+        assert!(warnings.is_empty());
+
+        let mut ignore = Ignore::new();
+        assert!(ignore.add_patterns("/d1/d2\n", "", "ignore").is_ok());
+        let path = tmp_dir_path.join("d1/d2");
+        assert!(list_files(tmp_dir_path, &path, &mut ignore, warnings)
+            .unwrap()
+            .is_empty());
+
+        assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
+    }
+
+    #[test]
+    fn generate_file_sha1_map_warnings_ut() {
+        let tmp_dir_path = &env::current_dir()
+            .unwrap()
+            .join("generate_file_sha1_map_warnings_ut");
+        if tmp_dir_path.exists() {
+            assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
+        }
+        assert!(fs::create_dir(tmp_dir_path).is_ok());
+        assert!(fs::write(tmp_dir_path.join("text"), "text").is_ok());
+        // not valid utf-8, hashed as bytes
+        assert!(fs::write(tmp_dir_path.join("binary"), [0xff, 0xfe, 0xfd]).is_ok());
+
+        let warnings = &mut vec![];
+        let file_sha1_map =
+            generate_file_sha1_map(tmp_dir_path, &mut Ignore::new(), warnings).unwrap();
+        assert_eq!(
+            vec!["binary", "text"],
+            file_sha1_map.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            crypto_bytes(&[0xff, 0xfe, 0xfd]),
+            file_sha1_map["binary"].sha1
+        );
+        assert!(warnings.is_empty());
+
+        assert!(fs::remove_dir_all(tmp_dir_path).is_ok());
+    }
 }
@@ -0,0 +1,282 @@
+use crate::diff;
+use crate::entry::Entry;
+use crate::error::GitError;
+use crate::http::{Request, Response};
+use crate::repo::GitRepository;
+use std::collections::BTreeSet;
+
+/// escape text for embedding into html
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// wrap page body with common layout
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{0} - git-rs</title>
+<style>
+body {{ font-family: monospace; margin: 2em; }}
+pre {{ background: #f6f8fa; padding: 1em; }}
+td {{ padding: 0 1em 0 0; }}
+</style>
+</head>
+<body>
+<p><a href="/">git-rs</a></p>
+<h2>{0}</h2>
+{1}
+</body>
+</html>
+"#,
+        escape(title),
+        body
+    )
+}
+
+/// short form of a commit sha1 used in links
+fn short(sha1: &str) -> &str {
+    &sha1[..sha1.len().min(8)]
+}
+
+/// Branch list followed by the log of the current branch
+fn index(repo: &mut GitRepository) -> Result<Response, GitError> {
+    let branches = repo.branches()?;
+    let current = repo.current_branch();
+    let mut body = String::from("<h3>Branches</h3>\n<ul>\n");
+    for (name, sha1) in branches.iter() {
+        let mark = if *name == current { "*" } else { "" };
+        body.push_str(&format!(
+            "<li>{}<a href=\"/log/{}\">{}</a> <a href=\"/commit/{}\">{}</a></li>\n",
+            mark,
+            escape(name),
+            escape(name),
+            sha1,
+            short(sha1)
+        ));
+    }
+    body.push_str("</ul>\n");
+    body.push_str(&log_table(repo, &current)?);
+    Ok(Response::html(page("Repository", &body)))
+}
+
+/// commit table for the history of rev
+fn log_table(repo: &GitRepository, rev: &str) -> Result<String, GitError> {
+    let sha1 = repo.resolve_commit(rev)?;
+    let mut body = format!("<h3>Log of {}</h3>\n<table>\n", escape(rev));
+    for (sha1, commit) in repo.history(&sha1)?.iter() {
+        body.push_str(&format!(
+            "<tr><td><a href=\"/commit/{}\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            sha1,
+            short(sha1),
+            escape(&commit.date_string()),
+            escape(commit.message())
+        ));
+    }
+    body.push_str("</table>\n");
+    Ok(body)
+}
+
+fn log(repo: &GitRepository, rev: &str) -> Result<Response, GitError> {
+    Ok(Response::html(page(rev, &log_table(repo, rev)?)))
+}
+
+/// Commit header, the files it changed compared with its parent and their diffs
+fn commit(repo: &GitRepository, rev: &str) -> Result<Response, GitError> {
+    let sha1 = repo.resolve_commit(rev)?;
+    let commit = repo.read_commit(&sha1)?;
+    let parent_blobs = if commit.parent().is_empty() {
+        Default::default()
+    } else {
+        repo.read_commit(commit.parent())?.blobs().clone()
+    };
+
+    let mut body = format!(
+        "<pre>commit {}\n{}Date: {}\n\n{}</pre>\n",
+        sha1,
+        escape(&commit.identity_lines()),
+        escape(&commit.date_string()),
+        escape(commit.message())
+    );
+    if !commit.parent().is_empty() {
+        body.push_str(&format!(
+            "<p>parent <a href=\"/commit/{0}\">{0}</a></p>\n",
+            commit.parent()
+        ));
+    }
+    body.push_str(&format!(
+        "<p><a href=\"/tree/{}\">browse files</a></p>\n<h3>Changes</h3>\n<table>\n",
+        sha1
+    ));
+    let paths: BTreeSet<&String> = commit.blobs().keys().chain(parent_blobs.keys()).collect();
+    let mut diffs = String::new();
+    for path in paths {
+        let (old, new) = (parent_blobs.get(path), commit.blobs().get(path));
+        let row = match (old, new) {
+            (None, Some(_)) => ("added", sha1.as_str()),
+            (Some(_), None) => ("deleted", commit.parent()),
+            (Some(old), Some(new)) if old != new => ("modified", sha1.as_str()),
+            _ => continue,
+        };
+        body.push_str(&format!(
+            "<tr><td>{}</td><td><a href=\"/file/{}/{}\">{}</a></td></tr>\n",
+            row.0,
+            row.1,
+            escape(path),
+            escape(path)
+        ));
+        let name = |prefix: &str, side: bool| match side {
+            true => format!("{}/{}", prefix, path),
+            false => "/dev/null".to_string(),
+        };
+        let (old_name, new_name) = (name("a", old.is_some()), name("b", new.is_some()));
+        let text = |blob: Option<&Entry>| {
+            blob.map_or(Ok(String::new()), |blob| repo.read_blob_text(&blob.sha1))
+        };
+        let file_diff = match (text(old), text(new)) {
+            (Ok(old), Ok(new)) => diff::unified(&old_name, &new_name, &old, &new),
+            _ => format!("Binary files {} and {} differ\n", old_name, new_name),
+        };
+        diffs.push_str(&format!("<pre>{}</pre>\n", escape(&file_diff)));
+    }
+    body.push_str("</table>\n<h3>Diff</h3>\n");
+    body.push_str(&diffs);
+    Ok(Response::html(page(
+        &format!("commit {}", short(&sha1)),
+        &body,
+    )))
+}
+
+/// All files tracked by a commit
+fn tree(repo: &GitRepository, rev: &str) -> Result<Response, GitError> {
+    let sha1 = repo.resolve_commit(rev)?;
+    let commit = repo.read_commit(&sha1)?;
+    let mut body = String::from("<ul>\n");
+    for path in commit.blobs().keys() {
+        body.push_str(&format!(
+            "<li><a href=\"/file/{}/{}\">{}</a></li>\n",
+            sha1,
+            escape(path),
+            escape(path)
+        ));
+    }
+    body.push_str("</ul>\n");
+    Ok(Response::html(page(&format!("tree {}", rev), &body)))
+}
+
+/// Content of a file as it exists in a commit
+fn file(repo: &GitRepository, rev: &str, path: &str) -> Result<Response, GitError> {
+    let sha1 = repo.resolve_commit(rev)?;
+    let commit = repo.read_commit(&sha1)?;
+    let blob = commit
+        .blobs()
+        .get(path)
+        .ok_or_else(|| GitError::FileNotExistError(path.to_string()))?;
+    let body = match repo.read_blob_text(&blob.sha1) {
+        Ok(content) => format!("<pre>{}</pre>\n", escape(&content)),
+        Err(_) => "<p>Binary file not shown</p>\n".to_string(),
+    };
+    Ok(Response::html(page(&format!("{} @ {}", path, rev), &body)))
+}
+
+/// Route a request of the read-only web interface
+/// /                      branches and log of the current branch
+/// /log/<rev>             log starting at rev
+/// /commit/<rev>          commit details, changed files and their diffs
+/// /tree/<rev>            files tracked by rev
+/// /file/<rev>/<path>     file content at rev
+pub fn handle(repo: &mut GitRepository, request: &Request) -> Response {
+    if request.method != "GET" {
+        return Response::text(405, "Method Not Allowed");
+    }
+    let segments = request.segments();
+    let res = match segments.as_slice() {
+        [] => index(repo),
+        ["log", rev] => log(repo, rev),
+        ["commit", rev] => commit(repo, rev),
+        ["tree", rev] => tree(repo, rev),
+        ["file", rev, path @ ..] if !path.is_empty() => file(repo, rev, &path.join("/")),
+        _ => return Response::not_found(),
+    };
+    match res {
+        Ok(response) => response,
+        Err(GitError::RevisionError(msg)) => Response::text(404, &msg),
+        Err(GitError::FileNotExistError(msg)) => Response::text(404, &msg),
+        Err(e) => Response::text(500, &e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn get(repo: &mut GitRepository, path: &str) -> Response {
+        let raw = format!("GET {} HTTP/1.1\r\n\r\n", path);
+        handle(repo, &Request::parse(&mut raw.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn escape_ut() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;&amp;",
+            escape("<a href=\"x\">&")
+        );
+    }
+
+    #[test]
+    fn handle_ut() {
+        let repo_dir = ".web_handle_ut_repo_dir";
+        let work_dir = &env::current_dir().unwrap().join("web_handle_ut");
+        for path in [work_dir, &env::current_dir().unwrap().join(repo_dir)] {
+            if path.exists() {
+                assert!(fs::remove_dir_all(path).is_ok());
+            }
+        }
+        assert!(fs::create_dir(work_dir).is_ok());
+        assert!(fs::write(work_dir.join("f1"), "<web handle ut>").is_ok());
+
+        let mut repo = GitRepository::new(repo_dir);
+        assert!(repo.init().is_ok());
+        assert!(repo.add(&["web_handle_ut/f1".to_string()]).is_ok());
+        assert!(repo.commit("add f1").is_ok());
+        let head = repo.resolve_commit("main").unwrap();
+
+        let res = get(&mut repo, "/");
+        assert_eq!(200, res.status);
+        let body = String::from_utf8(res.body).unwrap();
+        assert!(body.contains("*<a href=\"/log/main\">main</a>"));
+        assert!(body.contains("add f1"));
+        assert!(body.contains("initial commit"));
+
+        let res = get(&mut repo, &format!("/commit/{}", head));
+        assert_eq!(200, res.status);
+        let body = String::from_utf8(res.body).unwrap();
+        assert!(body.contains(&format!(
+            "<td>added</td><td><a href=\"/file/{}/web_handle_ut/f1\">",
+            head
+        )));
+        assert!(body.contains(
+            "<pre>--- /dev/null\n+++ b/web_handle_ut/f1\n@@ -0,0 +1,1 @@\n+&lt;web handle ut&gt;\n</pre>"
+        ));
+
+        let res = get(&mut repo, "/file/main/web_handle_ut/f1");
+        assert_eq!(200, res.status);
+        assert!(String::from_utf8(res.body)
+            .unwrap()
+            .contains("<pre>&lt;web handle ut&gt;</pre>"));
+
+        assert_eq!(404, get(&mut repo, "/file/main/missing").status);
+        assert_eq!(404, get(&mut repo, "/tree/unknown").status);
+        assert_eq!(404, get(&mut repo, "/tree/..").status);
+        assert_eq!(404, get(&mut repo, "/nothing/here").status);
+
+        assert!(fs::remove_dir_all(work_dir).is_ok());
+        assert!(fs::remove_dir_all(repo.repo_path).is_ok());
+    }
+}
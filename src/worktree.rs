@@ -0,0 +1,81 @@
+use clap::ValueEnum;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Operation of the worktree command
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WorktreeAction {
+    /// list the working trees, the main one first
+    #[default]
+    List,
+    /// check a branch out in a new working tree
+    Add,
+    /// delete a linked working tree
+    Remove,
+}
+
+/// directory of the repository holding one administrative directory per linked worktree
+pub const WORKTREES_DIR: &str = "worktrees";
+/// file of an administrative directory holding the path of its worktree
+pub const GITDIR_FILE: &str = "gitdir";
+const GITDIR_PREFIX: &str = "gitdir: ";
+
+/// content of the git directory file of a linked worktree, pointing at its administrative
+/// directory
+pub fn link(admin: &Path) -> String {
+    format!("{}{}\n", GITDIR_PREFIX, admin.display())
+}
+
+/// administrative directory a git directory file points at
+pub fn parse_link(content: &str) -> Option<PathBuf> {
+    content
+        .trim_end()
+        .strip_prefix(GITDIR_PREFIX)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// administrative directory of a linked worktree from its git directory, None when
+/// `git_dir` is a directory, the git directory of a main worktree
+pub fn admin_dir(git_dir: &Path) -> Option<PathBuf> {
+    if !git_dir.is_file() {
+        return None;
+    }
+    parse_link(&fs::read_to_string(git_dir).ok()?)
+}
+
+/// name of the administrative directory of a worktree at `path`: its directory name, with
+/// a number after it when `taken`
+pub fn admin_name(path: &Path, taken: impl Fn(&str) -> bool) -> String {
+    let base = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .unwrap_or_else(|| "worktree".to_string());
+    let mut name = base.clone();
+    let mut n = 1;
+    while taken(&name) {
+        name = format!("{}{}", base, n);
+        n += 1;
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_ut() {
+        let admin = Path::new("/repo/.git-rs/worktrees/wt");
+        assert_eq!("gitdir: /repo/.git-rs/worktrees/wt\n", link(admin));
+        assert_eq!(Some(admin.to_path_buf()), parse_link(&link(admin)));
+        assert_eq!(None, parse_link("gitdir: \n"));
+        assert_eq!(None, parse_link("ref: refs/heads/main"));
+
+        let taken = |name: &str| ["wt", "wt1"].contains(&name);
+        assert_eq!("wt2", admin_name(Path::new("/tmp/wt"), taken));
+        assert_eq!("other", admin_name(Path::new("../other"), taken));
+        assert_eq!("worktree", admin_name(Path::new("/tmp/.hidden"), taken));
+    }
+}